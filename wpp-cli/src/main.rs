@@ -82,6 +82,12 @@ enum Commands {
 },
 
 
+    /// Type-check a W++ source file without running it
+    Check {
+        /// Optional path to a W++ file (if omitted, the project entrypoint is checked)
+        file: Option<String>,
+    },
+
     /// Build a W++ source file into LLVM IR (.ll)
     Build {
         /// Path to the W++ file
@@ -221,6 +227,7 @@ fn main() {
         run_file_command(".", opt); // treat "." as project root
     }
 }
+        Commands::Check { file } => check_file_command(file.as_deref()),
         Commands::Build { file, output, opt } => build_file_command(&file, &output, opt),
         Commands::NpmInstall => troll_npm_install(),
         Commands::Pacman => troll_pacman(),
@@ -657,7 +664,7 @@ match WppConfig::load(&config_path) {
                 .unwrap_or(".");
 
             // Catch parser/codegen panics and present friendly errors
-            let result = std::panic::catch_unwind(|| run_single_source(&source, base_dir, optimize));
+            let result = std::panic::catch_unwind(|| run_single_source(path, &source, base_dir, optimize));
             if let Err(panic) = result {
                 print_pretty_panic(panic);
             }
@@ -666,6 +673,49 @@ match WppConfig::load(&config_path) {
     }
 }
 
+/// 🔎 Type-check a W++ file (or the project entrypoint) without compiling or running it
+fn check_file_command(path: Option<&str>) {
+    let path = match path {
+        Some(p) => p.to_string(),
+        None => {
+            let config_path = std::env::current_dir().unwrap().join("wpp.config.hs");
+            let entrypoint = if config_path.exists() {
+                match WppConfig::load(&config_path) {
+                    Ok(cfg) => cfg.entrypoint,
+                    Err(e) => {
+                        eprintln!("❌ Failed to load wpp.config.hs: {e}");
+                        std::process::exit(1);
+                    }
+                }
+            } else {
+                None
+            };
+            entrypoint.unwrap_or_else(|| "src/main.wpp".to_string())
+        }
+    };
+
+    let source = match fs::read_to_string(&path) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("❌ Could not read {path}: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let diags = match wpp_v2::parser::parse(&source) {
+        Ok(ast) => wpp_v2::checker::check_program(&ast),
+        Err(diags) => {
+            print_diagnostics(&path, &source, &diags, "syntax", "check");
+            std::process::exit(1);
+        }
+    };
+    if !diags.is_empty() {
+        print_diagnostics(&path, &source, &diags, "type", "check");
+        std::process::exit(1);
+    }
+    println!("✅ {path}: no type errors");
+}
+
 /// 🏗️ Build a W++ file into LLVM IR (.ll)
 fn build_file_command(path: &str, output: &str, optimize: bool) {
     println!("🏗️  Building {path} → {output}...");
@@ -759,6 +809,12 @@ fn print_help() {
         "--opt / -o".bright_green().to_string() + " → Enable LLVM optimization passes"
     );
 
+    println!(
+        "  {} {}",
+        "check [file]".cyan(),
+        "→ Type-check a W++ file without running it".bright_black()
+    );
+
     println!(
         "  {} {}\n      {}",
         "build <file> [-o <out.ll>]".cyan(),
@@ -911,10 +967,22 @@ fn run_with_codegen(_source: &str, optimize: bool) {
     let mut lexer = Lexer::new(&main_source);
     let tokens = lexer.tokenize();
     let mut parser = WppParser::new(tokens);
-    let main_ast = parser.parse_program();
+    let main_ast = match parser.parse_program() {
+        Ok(ast) => ast,
+        Err(diags) => {
+            print_diagnostics(&main_source_path.display().to_string(), &main_source, &diags, "syntax", "run");
+            return;
+        }
+    };
+    let type_errors = wpp_v2::checker::check_program(&main_ast);
+    if !type_errors.is_empty() {
+        print_diagnostics(&main_source_path.display().to_string(), &main_source, &type_errors, "type", "run");
+        return;
+    }
 
     // Initialize the top-level codegen shell
     let mut codegen = Codegen::new(&context, "main", src_dir.to_str().unwrap());
+    codegen.set_source(&main_source);
     codegen.wms = Some(Arc::new(Mutex::new(wms)));
     codegen.resolver = Some(Arc::new(Mutex::new(resolver)));
 
@@ -928,9 +996,16 @@ fn run_with_codegen(_source: &str, optimize: bool) {
     if std::env::var("WPP_DEBUG").ok().as_deref() == Some("1") {
         println!("🧩 Compiling main module with resolved exports...");
     }
-    if let Err(e) = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| codegen.compile_main(&main_ast))) {
-        print_pretty_panic(e);
-        return;
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| codegen.compile_main(&main_ast))) {
+        Ok(Ok(_)) => {}
+        Ok(Err(e)) => {
+            print_compile_error(&main_source_path.display().to_string(), &main_source, &e);
+            return;
+        }
+        Err(panic) => {
+            print_pretty_panic(panic);
+            return;
+        }
     }
 
     // 🧠 Step 7: Run via JIT
@@ -941,7 +1016,7 @@ fn run_with_codegen(_source: &str, optimize: bool) {
 }
 
 /// 🚀 Run a single W++ source buffer directly (with module system for imports)
-fn run_single_source(source: &str, base_dir: &str, optimize: bool) {
+fn run_single_source(path: &str, source: &str, base_dir: &str, optimize: bool) {
     println!("🎯 [CLI] Single-file mode: compiling buffer (base_dir = {})", base_dir);
 
     // Initialize module system for Rust module support
@@ -972,12 +1047,29 @@ fn run_single_source(source: &str, base_dir: &str, optimize: bool) {
     let mut lexer = wpp_v2::lexer::Lexer::new(source);
     let tokens = lexer.tokenize();
     let mut parser = wpp_v2::parser::Parser::new(tokens);
-    let ast = parser.parse_program();
+    let ast = match parser.parse_program() {
+        Ok(ast) => ast,
+        Err(diags) => {
+            print_diagnostics(path, source, &diags, "syntax", "run");
+            return;
+        }
+    };
+
+    // Type check before generating any IR
+    let type_errors = wpp_v2::checker::check_program(&ast);
+    if !type_errors.is_empty() {
+        print_diagnostics(path, source, &type_errors, "type", "run");
+        return;
+    }
 
     // Codegen + JIT with module system
     let mut codegen = wpp_v2::codegen::Codegen::new(&context, "single", base_dir);
+    codegen.set_source(source);
     codegen.wms = Some(std::sync::Arc::new(std::sync::Mutex::new(wms)));
-    codegen.compile_main(&ast);
+    if let Err(e) = codegen.compile_main(&ast) {
+        print_compile_error(path, source, &e);
+        return;
+    }
 
     match wpp_v2::run_file(&mut codegen, optimize) {
         Ok(_) => println!("✅ Execution finished successfully."),
@@ -985,6 +1077,69 @@ fn run_single_source(source: &str, base_dir: &str, optimize: bool) {
     }
 }
 
+/// Print every parser or checker diagnostic for a file, then a one-line summary
+/// (`kind` is "syntax" or "type", `verb` is what the command could not do: "run" or "check")
+fn print_diagnostics(path: &str, source: &str, diags: &[wpp_v2::diagnostics::Diagnostic], kind: &str, verb: &str) {
+    for d in diags {
+        let label = match d.severity {
+            wpp_v2::diagnostics::Severity::Error => "error".bright_red().bold(),
+            wpp_v2::diagnostics::Severity::Warning => "warning".bright_yellow().bold(),
+            wpp_v2::diagnostics::Severity::Note => "note".bright_cyan().bold(),
+        };
+        print_report(label, &d.message, path, source, Some(d.span), "", &d.notes);
+    }
+    let errors = diags.iter().filter(|d| d.is_error()).count();
+    eprintln!("❌ Could not {} {} due to {} {} error{}", verb, path, errors, kind, if errors == 1 { "" } else { "s" });
+}
+
+/// Print a codegen error with the offending source line underlined
+fn print_compile_error(path: &str, source: &str, err: &wpp_v2::diagnostics::CompileError) {
+    print_report("error".bright_red().bold(), &err.to_string(), path, source, err.span(), err.label(), &[]);
+    eprintln!("❌ Could not compile {}", path);
+}
+
+/// rustc-style report: message, `-->` location, the source line and a caret under the span
+fn print_report(
+    label: ColoredString,
+    message: &str,
+    path: &str,
+    source: &str,
+    span: Option<wpp_v2::ast::Span>,
+    caret_label: &str,
+    notes: &[String],
+) {
+    use wpp_v2::ast::span::{line_col, line_text};
+
+    eprintln!("{}: {}", label, message.bold());
+    if let Some(span) = span {
+        let (line, col) = span.line_col(source);
+        let text = line_text(source, line);
+        let gutter = " ".repeat(line.to_string().len());
+
+        // Underline to the end of the span, but never past the end of this line
+        let (end_line, end_col) = line_col(source, span.end);
+        let line_len = text.chars().count();
+        let end_col = if end_line == line { end_col } else { line_len + 1 };
+        let width = end_col.saturating_sub(col).max(1);
+
+        eprintln!("{}{} {}:{}:{}", gutter, "-->".bright_blue(), path, line, col);
+        eprintln!("{} {}", gutter, "|".bright_blue());
+        eprintln!("{} {} {}", line.to_string().bright_blue(), "|".bright_blue(), text);
+        eprintln!(
+            "{} {} {}{} {}",
+            gutter,
+            "|".bright_blue(),
+            " ".repeat(col - 1),
+            "^".repeat(width).bright_red().bold(),
+            caret_label.bright_red().bold()
+        );
+    }
+    for note in notes {
+        eprintln!("   {} note: {}", "=".bright_blue(), note);
+    }
+    eprintln!();
+}
+
 fn print_pretty_panic(panic: Box<dyn std::any::Any + Send>) {
     if let Some(msg) = panic.downcast_ref::<&str>() {
         eprintln!("❌ {}", msg);
@@ -1117,15 +1272,30 @@ fn execute_cells_range(path: &str, cells: &[ProximaCell], from: usize, to: usize
     let mut lexer = Lexer::new(&source);
     let tokens = lexer.tokenize();
     let mut parser = WppParser::new(tokens);
-    let main_ast = parser.parse_program();
+    let main_ast = match parser.parse_program() {
+        Ok(ast) => ast,
+        Err(diags) => {
+            print_diagnostics(path, &source, &diags, "syntax", "run");
+            return Ok(());
+        }
+    };
+    let type_errors = wpp_v2::checker::check_program(&main_ast);
+    if !type_errors.is_empty() {
+        print_diagnostics(path, &source, &type_errors, "type", "run");
+        return Ok(());
+    }
 
     let mut codegen = Codegen::new(&context, "main", base_dir_str);
+    codegen.set_source(&source);
     codegen.wms = Some(Arc::new(Mutex::new(wms)));
     codegen.resolver = Some(Arc::new(Mutex::new(resolver)));
     codegen.link_dependency_modules();
 
     let run = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
-        codegen.compile_main(&main_ast);
+        if let Err(e) = codegen.compile_main(&main_ast) {
+            print_compile_error(path, &source, &e);
+            return;
+        }
         let _ = wpp_v2::run_file(&mut codegen, optimize);
     }));
     if let Err(p) = run { print_pretty_panic(p); }
@@ -1,8 +1,40 @@
 use super::node::Node; // 👈 to use Node inside Expr
-use super::types::{ParameterPattern, TypeDescriptor};
+use super::span::Span;
+use super::types::{Number, ParameterPattern, TypeDescriptor, TypePattern};
 
+/// An expression together with the source range it was parsed from
 #[derive(Debug, Clone)]
-pub enum Expr {
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+impl Expr {
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Self { kind, span }
+    }
+
+    /// The value of a number written without a type suffix (`5`, `2.5`, `-3`); such literals
+    /// take the numeric type their context expects when the value fits (see `FieldType::fits`)
+    pub fn untyped_number(&self) -> Option<Number> {
+        match &self.kind {
+            ExprKind::Literal(n) => Some(Number::Int(*n as i128)),
+            ExprKind::TypedLiteral { value, ty } if ty == "i32" => value.parse().ok().map(Number::Int),
+            ExprKind::TypedLiteral { value, ty } if ty == "f64" => value.parse().ok().map(Number::Float),
+            // Unary minus is parsed as `0 - x`
+            ExprKind::BinaryOp { left, op, right } if op == "-" && matches!(left.kind, ExprKind::Literal(0)) => {
+                match right.untyped_number()? {
+                    Number::Int(n) => Some(Number::Int(-n)),
+                    Number::Float(f) => Some(Number::Float(-f)),
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ExprKind {
     Literal(i32),
     BoolLiteral(bool),
     StringLiteral(String),
@@ -32,6 +64,18 @@ pub enum Expr {
         post: Option<Box<Expr>>,
         body: Vec<Node>,
     },
+    /// `for item in iterable { ... }`; `key` is the index or map key in `for k, v in ...`
+    ForIn {
+        key: Option<String>,
+        item: String,
+        iterable: Box<Expr>,
+        body: Vec<Node>,
+    },
+    /// `start..end` (end exclusive), only valid as the iterable of `for ... in`
+    Range {
+        start: Box<Expr>,
+        end: Box<Expr>,
+    },
     Break,
     Continue,
     Switch {
@@ -39,15 +83,18 @@ pub enum Expr {
     cases: Vec<(Expr, Vec<Node>)>, // Each case: condition + block
     default: Option<Vec<Node>>,    // Optional default block
 },
+    /// `try { } catch (e: T) { } ... finally { }`; catch clauses are tried in order
     TryCatch {
         try_block: Vec<Node>,
-        catch_var: Option<String>,
-        catch_block: Vec<Node>,
+        catches: Vec<CatchClause>,
         finally_block: Option<Vec<Node>>, // ✅ NEW
     },
     Throw {
         expr: Box<Expr>,
     },
+    /// `defer expr`: evaluated when control leaves the enclosing block, by any path; the
+    /// deferred expressions of a block run last-registered first
+    Defer(Box<Expr>),
     Funcy {
         name: String,
         params: Vec<String>, // Kept for backward compatibility - stores "name:type" format
@@ -59,6 +106,11 @@ pub enum Expr {
     Return(Option<Box<Expr>>),
     Await(Box<Expr>), // ✅ new expression kind
     ArrayLiteral(Vec<Expr>),
+    /// `target[index]` — element access, also used as an assignment target
+    Index {
+        target: Box<Expr>,
+        index: Box<Expr>,
+    },
     ObjectLiteral {
         fields: Vec<(String, Expr)>,
         type_name: Option<String>, // ✅ NEW: Track object type for dispatch
@@ -67,5 +119,36 @@ pub enum Expr {
         entity: String,
         args: Vec<Expr>,
     },
+    /// `value as u8`: explicit numeric conversion, which may truncate or change sign
+    Cast {
+        expr: Box<Expr>,
+        ty: String,
+    },
+    /// `match (subject) { Circle(r) => ..., _ => ... }`; arms are tried in order
+    Match {
+        subject: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
+}
+
+/// One `pattern => body` arm of a `match`
+#[derive(Debug, Clone)]
+pub struct MatchArm {
+    pub pattern: TypePattern,
+    /// Span of the pattern, for errors about it
+    pub span: Span,
+    /// A single expression arm (`=> expr`) is a one-node body; its value is the arm's value
+    pub body: Vec<Node>,
+}
+
+/// One `catch (e: T) { ... }` clause of a `try`
+#[derive(Debug, Clone)]
+pub struct CatchClause {
+    /// Name the caught value is bound to, if any
+    pub var: Option<String>,
+    /// Only values of this type are caught (entity instances also match subtypes); any
+    /// value when absent
+    pub ty: Option<TypeDescriptor>,
+    pub body: Vec<Node>,
 }
 
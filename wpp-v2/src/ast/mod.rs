@@ -1,7 +1,10 @@
 pub mod expr;
 pub mod node;
+pub mod pattern;
+pub mod span;
 pub mod types;
 
-pub use expr::Expr;
-pub use node::Node;
+pub use expr::{CatchClause, Expr, ExprKind, MatchArm};
+pub use node::{Node, NodeKind};
+pub use span::{FileId, Span};
 pub use types::*;
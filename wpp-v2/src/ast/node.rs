@@ -1,8 +1,32 @@
 use super::expr::Expr;
-use super::types::{ObjectTypeDefinition};
+use super::span::Span;
+use super::types::{EnumDefinition, ObjectTypeDefinition};
 
+/// A statement or declaration together with its source range
 #[derive(Debug, Clone)]
-pub enum Node {
+pub struct Node {
+    pub kind: NodeKind,
+    pub span: Span,
+    /// `///` comments written just before a declaration
+    pub doc: Option<String>,
+}
+
+impl Node {
+    pub fn new(kind: NodeKind, span: Span) -> Self {
+        Self { kind, span, doc: None }
+    }
+}
+
+/// Expression statements reuse the span of the wrapped expression
+impl From<Expr> for Node {
+    fn from(expr: Expr) -> Self {
+        let span = expr.span;
+        Node { kind: NodeKind::Expr(expr), span, doc: None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum NodeKind {
     Let {
         name: String,
         value: Expr,
@@ -23,6 +47,10 @@ pub enum Node {
         members: Vec<(String, Option<String>)>, // name, alias
     },
     TypeAlias(ObjectTypeDefinition), // ✅ NEW: type aliases for named objects
+    /// `enum Shape { Circle(f64), Rect(f64, f64), Empty }`
+    Enum(EnumDefinition),
+    /// `interface Speaker { speak() }`
+    Interface(InterfaceNode),
 }
 
 #[derive(Debug, Clone)]
@@ -30,6 +58,21 @@ pub struct EntityNode {
     pub name: String,                    // entity name (e.g. "Dog")
     pub base: Option<String>,            // optional parent (for `alters`)
     pub members: Vec<EntityMember>,      // methods + fields
+    pub interfaces: Vec<String>,         // `implements Speaker, Walker`
+}
+
+/// Methods an entity must provide to implement the interface
+#[derive(Debug, Clone)]
+pub struct InterfaceNode {
+    pub name: String,
+    pub methods: Vec<InterfaceMethod>,
+}
+
+#[derive(Debug, Clone)]
+pub struct InterfaceMethod {
+    pub name: String,
+    /// Parameter names, not counting `me`
+    pub params: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -37,9 +80,11 @@ pub enum EntityMember {
     Field {
         name: String,
         value: Expr,
+        doc: Option<String>,
     },
     Method {
         name: String,
         func: Expr, // Will be Expr::Funcy
+        doc: Option<String>,
     },
 }
\ No newline at end of file
@@ -0,0 +1,282 @@
+//! `match` patterns resolved against the type being matched
+//!
+//! Both the checker and codegen resolve each arm's `TypePattern` with [`resolve`] (which
+//! reports unknown variants and wrong payload counts) and then ask [`uncovered`] for a
+//! value no arm matches, so both report the same errors.
+
+use std::collections::HashMap;
+
+use super::expr::{Expr, ExprKind};
+use super::types::{EnumDefinition, FieldType, ParameterPattern, TypeDescriptor, TypePattern};
+
+/// A pattern whose variant names have been looked up
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    /// `_`: matches anything and binds nothing
+    Wild,
+    /// Matches anything and binds it to the name
+    Binding(String),
+    /// Matches values equal to the literal
+    Literal(Expr),
+    /// Matches the variant with this index; `fields` match its payload
+    Variant { index: usize, fields: Vec<Pattern> },
+}
+
+impl Pattern {
+    /// Matches every value of its type
+    pub fn is_irrefutable(&self) -> bool {
+        matches!(self, Pattern::Wild | Pattern::Binding(_))
+    }
+}
+
+/// The enum a `match` is over when the subject's type is not known statically: the one
+/// named by the first qualified pattern, or the only enum declaring the first variant used
+pub fn infer_enum<'a>(
+    patterns: impl IntoIterator<Item = &'a TypePattern>,
+    enums: &HashMap<String, EnumDefinition>,
+) -> Option<String> {
+    patterns.into_iter().find_map(|pattern| {
+        let TypePattern::Variant { name, .. } = pattern else {
+            return None;
+        };
+        if let Some((enum_name, _)) = name.split_once('.') {
+            return enums.contains_key(enum_name).then(|| enum_name.to_string());
+        }
+        let mut owners = enums.values().filter(|def| def.variant(name).is_some());
+        match (owners.next(), owners.next()) {
+            (Some(def), None) => Some(def.name.clone()),
+            _ => None,
+        }
+    })
+}
+
+/// Resolve an arm's pattern against a subject of type `ty`
+pub fn resolve(pattern: &TypePattern, ty: &FieldType, enums: &HashMap<String, EnumDefinition>) -> Result<Pattern, String> {
+    match pattern {
+        TypePattern::Type(TypeDescriptor::Any) => Ok(Pattern::Wild),
+        TypePattern::Type(td) => Err(format!("`{}` is a type, not a pattern", td.to_mangle_string())),
+        TypePattern::Value(expr) => match enum_of(ty, enums) {
+            Some(def) => Err(format!("expected a `{}` variant, found a literal", def.name)),
+            None => Ok(Pattern::Literal(expr.clone())),
+        },
+        TypePattern::Variant { name, fields } => {
+            let Some(def) = enum_of(ty, enums) else {
+                return Err(format!("cannot match a value of type `{}` against variant `{}`", ty, name));
+            };
+            let variant_name = match name.split_once('.') {
+                Some((enum_name, variant)) if enum_name == def.name => variant,
+                Some(_) => return Err(format!("expected a `{}` variant, found `{}`", def.name, name)),
+                None => name.as_str(),
+            };
+            let Some((index, variant)) = def.variant(variant_name) else {
+                return Err(format!("`{}` has no variant `{}`", def.name, variant_name));
+            };
+            if fields.len() != variant.fields.len() {
+                return Err(format!(
+                    "`{}.{}` has {} field{}, but the pattern has {}",
+                    def.name,
+                    variant.name,
+                    variant.fields.len(),
+                    if variant.fields.len() == 1 { "" } else { "s" },
+                    fields.len()
+                ));
+            }
+            let fields = fields
+                .iter()
+                .zip(&variant.fields)
+                .map(|(field, ty)| resolve_field(field, ty, enums))
+                .collect::<Result<_, _>>()?;
+            Ok(Pattern::Variant { index, fields })
+        }
+    }
+}
+
+/// A payload position: a nested pattern, `_`, a unit variant of the field's enum, or a binding
+fn resolve_field(field: &ParameterPattern, ty: &FieldType, enums: &HashMap<String, EnumDefinition>) -> Result<Pattern, String> {
+    if let Some(pattern) = &field.pattern {
+        return resolve(pattern, ty, enums);
+    }
+    if field.name == "_" {
+        return Ok(Pattern::Wild);
+    }
+    match enum_of(ty, enums).and_then(|def| def.variant(&field.name)) {
+        Some((index, variant)) if variant.fields.is_empty() => Ok(Pattern::Variant { index, fields: Vec::new() }),
+        _ => Ok(Pattern::Binding(field.name.clone())),
+    }
+}
+
+/// Names bound by `pat` on a value of type `ty`, with their types, in pattern order
+pub fn bindings(pat: &Pattern, ty: &FieldType, enums: &HashMap<String, EnumDefinition>) -> Vec<(String, FieldType)> {
+    match (pat, enum_of(ty, enums)) {
+        (Pattern::Binding(name), _) => vec![(name.clone(), ty.clone())],
+        (Pattern::Variant { index, fields }, Some(def)) => fields
+            .iter()
+            .zip(&def.variants[*index].fields)
+            .flat_map(|(field, field_ty)| bindings(field, field_ty, enums))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn enum_of<'a>(ty: &FieldType, enums: &'a HashMap<String, EnumDefinition>) -> Option<&'a EnumDefinition> {
+    match ty {
+        FieldType::Object(name) => enums.get(name),
+        _ => None,
+    }
+}
+
+/// A value of type `ty` that none of `arms` matches, written as a pattern (`Rect(_, _)`),
+/// or `None` when the arms are exhaustive
+pub fn uncovered(arms: &[Pattern], ty: &FieldType, enums: &HashMap<String, EnumDefinition>) -> Option<String> {
+    let rows: Vec<Vec<&Pattern>> = arms.iter().map(|arm| vec![arm]).collect();
+    missing(&rows, std::slice::from_ref(ty), enums).map(|mut witness| witness.remove(0))
+}
+
+const WILD: &Pattern = &Pattern::Wild;
+
+/// Find a row of values (one per column of `tys`) matched by no row of patterns
+fn missing(rows: &[Vec<&Pattern>], tys: &[FieldType], enums: &HashMap<String, EnumDefinition>) -> Option<Vec<String>> {
+    let Some((ty, rest)) = tys.split_first() else {
+        return if rows.is_empty() { Some(Vec::new()) } else { None };
+    };
+
+    if let Some(def) = enum_of(ty, enums) {
+        // Every variant must be covered, with its payload in turn covered
+        for (index, variant) in def.variants.iter().enumerate() {
+            let specialized: Vec<Vec<&Pattern>> = rows
+                .iter()
+                .filter_map(|row| {
+                    let head: Vec<&Pattern> = match row[0] {
+                        Pattern::Variant { index: i, fields } if *i == index => fields.iter().collect(),
+                        Pattern::Variant { .. } | Pattern::Literal(_) => return None,
+                        Pattern::Wild | Pattern::Binding(_) => vec![WILD; variant.fields.len()],
+                    };
+                    Some(head.into_iter().chain(row[1..].iter().copied()).collect())
+                })
+                .collect();
+            let tys: Vec<FieldType> = variant.fields.iter().chain(rest).cloned().collect();
+            if let Some(mut witness) = missing(&specialized, &tys, enums) {
+                let payload: Vec<String> = witness.drain(..variant.fields.len()).collect();
+                let value = if payload.is_empty() {
+                    variant.name.clone()
+                } else {
+                    format!("{}({})", variant.name, payload.join(", "))
+                };
+                witness.insert(0, value);
+                return Some(witness);
+            }
+        }
+        return None;
+    }
+
+    if *ty == FieldType::Bool {
+        // `true` and `false` together cover a bool
+        for value in [true, false] {
+            let specialized: Vec<Vec<&Pattern>> = rows
+                .iter()
+                .filter(|row| match row[0] {
+                    Pattern::Literal(Expr { kind: ExprKind::BoolLiteral(b), .. }) => *b == value,
+                    other => other.is_irrefutable(),
+                })
+                .map(|row| row[1..].to_vec())
+                .collect();
+            if let Some(mut witness) = missing(&specialized, rest, enums) {
+                witness.insert(0, value.to_string());
+                return Some(witness);
+            }
+        }
+        return None;
+    }
+
+    // Any other type has more values than literals can list: only `_` or a binding covers it
+    let defaults: Vec<Vec<&Pattern>> = rows
+        .iter()
+        .filter(|row| row[0].is_irrefutable())
+        .map(|row| row[1..].to_vec())
+        .collect();
+    missing(&defaults, rest, enums).map(|mut witness| {
+        witness.insert(0, "_".to_string());
+        witness
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::types::EnumVariant;
+
+    fn shapes() -> HashMap<String, EnumDefinition> {
+        let variant = |name: &str, fields: Vec<FieldType>| EnumVariant { name: name.to_string(), fields };
+        let option = EnumDefinition {
+            name: "Opt".to_string(),
+            variants: vec![variant("Some", vec![FieldType::Object("Shape".to_string())]), variant("None", vec![])],
+        };
+        let shape = EnumDefinition {
+            name: "Shape".to_string(),
+            variants: vec![
+                variant("Circle", vec![FieldType::Float64]),
+                variant("Rect", vec![FieldType::Float64, FieldType::Float64]),
+                variant("Empty", vec![]),
+            ],
+        };
+        HashMap::from([("Opt".to_string(), option), ("Shape".to_string(), shape)])
+    }
+
+    fn variant(name: &str, fields: &[&str]) -> TypePattern {
+        TypePattern::Variant {
+            name: name.to_string(),
+            fields: fields.iter().map(|f| ParameterPattern { name: f.to_string(), pattern: None }).collect(),
+        }
+    }
+
+    fn check(patterns: &[TypePattern], ty: &str) -> Result<Option<String>, String> {
+        let enums = shapes();
+        let ty = FieldType::Object(ty.to_string());
+        let arms = patterns.iter().map(|p| resolve(p, &ty, &enums)).collect::<Result<Vec<_>, _>>()?;
+        Ok(uncovered(&arms, &ty, &enums))
+    }
+
+    #[test]
+    fn test_every_variant_must_be_covered() {
+        let circle = variant("Circle", &["r"]);
+        let rect = variant("Shape.Rect", &["w", "_"]);
+        assert_eq!(check(&[circle.clone(), rect.clone(), variant("Empty", &[])], "Shape"), Ok(None));
+        assert_eq!(check(&[circle.clone(), variant("Empty", &[])], "Shape"), Ok(Some("Rect(_, _)".to_string())));
+        let wildcard = TypePattern::Type(TypeDescriptor::Any);
+        assert_eq!(check(&[circle, wildcard], "Shape"), Ok(None));
+    }
+
+    #[test]
+    fn test_nested_payload_patterns() {
+        let some_circle = TypePattern::Variant {
+            name: "Some".to_string(),
+            fields: vec![ParameterPattern { name: "_".to_string(), pattern: Some(variant("Circle", &["r"])) }],
+        };
+        // A bare name in a payload position is a unit variant of the payload's enum
+        let some_empty = variant("Some", &["Empty"]);
+        let none = variant("None", &[]);
+        assert_eq!(
+            check(&[some_circle.clone(), some_empty.clone(), none.clone()], "Opt"),
+            Ok(Some("Some(Rect(_, _))".to_string()))
+        );
+        assert_eq!(check(&[some_circle, some_empty, variant("Some", &["s"]), none], "Opt"), Ok(None));
+    }
+
+    #[test]
+    fn test_bad_patterns_are_reported() {
+        assert_eq!(check(&[variant("Square", &["s"])], "Shape"), Err("`Shape` has no variant `Square`".to_string()));
+        assert_eq!(
+            check(&[variant("Rect", &["w"])], "Shape"),
+            Err("`Shape.Rect` has 2 fields, but the pattern has 1".to_string())
+        );
+        assert_eq!(check(&[variant("Opt.None", &[])], "Shape"), Err("expected a `Shape` variant, found `Opt.None`".to_string()));
+    }
+
+    #[test]
+    fn test_infer_enum_from_patterns() {
+        let enums = shapes();
+        assert_eq!(infer_enum(&[variant("Rect", &["w", "h"])], &enums), Some("Shape".to_string()));
+        assert_eq!(infer_enum(&[TypePattern::Type(TypeDescriptor::Any), variant("Opt.None", &[])], &enums), Some("Opt".to_string()));
+        assert_eq!(infer_enum(&[variant("Triangle", &[])], &enums), None);
+    }
+}
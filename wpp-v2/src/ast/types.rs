@@ -1,5 +1,5 @@
 /// Type system for W++ dispatch and type aliases
-use super::expr::Expr;
+use super::expr::{Expr, ExprKind};
 
 /// TypeDescriptor represents all types that can be used in function dispatch
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -13,6 +13,9 @@ pub enum TypeDescriptor {
     /// Named object type (type alias)
     ObjectType(String),
 
+    /// Interface by name; matches any entity that implements it
+    Interface(String),
+
     /// HTTP status code literal (e.g., 200, 404)
     HttpStatusLiteral(u16),
 
@@ -38,6 +41,7 @@ impl TypeDescriptor {
             TypeDescriptor::HttpStatusLiteral(_) => 100,
             TypeDescriptor::ObjectType(_) => 90,
             TypeDescriptor::Entity(_) => 90,
+            TypeDescriptor::Interface(_) => 85, // any implementing entity is less specific than one entity
             TypeDescriptor::Primitive(p) if p != "ptr" => 80,
             TypeDescriptor::Primitive(_) => 70, // ptr is less specific
             TypeDescriptor::HttpStatusRange(_, _) => 50,
@@ -59,6 +63,7 @@ impl TypeDescriptor {
             (TypeDescriptor::Primitive(a), TypeDescriptor::Primitive(b)) => a == b,
             (TypeDescriptor::Entity(a), TypeDescriptor::Entity(b)) => a == b,
             (TypeDescriptor::ObjectType(a), TypeDescriptor::ObjectType(b)) => a == b,
+            (TypeDescriptor::Interface(a), TypeDescriptor::Interface(b)) => a == b,
             (TypeDescriptor::HttpStatusLiteral(a), TypeDescriptor::HttpStatusLiteral(b)) => a == b,
 
             // HTTP status code ranges
@@ -96,6 +101,7 @@ impl TypeDescriptor {
             TypeDescriptor::Primitive(p) => p.clone(),
             TypeDescriptor::Entity(e) => format!("entity_{}", e),
             TypeDescriptor::ObjectType(o) => format!("obj_{}", o),
+            TypeDescriptor::Interface(i) => format!("iface_{}", i),
             TypeDescriptor::HttpStatusLiteral(code) => format!("http_{}", code),
             TypeDescriptor::HttpStatusRange(min, max) => format!("http_{}xx", min / 100),
             TypeDescriptor::Function { param_types, return_type } => {
@@ -125,16 +131,23 @@ pub struct ObjectField {
     pub ty: FieldType,
 }
 
-/// Types that can be used in object fields
-#[derive(Debug, Clone)]
+/// Types that can be used in object fields and as array element types
+#[derive(Debug, Clone, PartialEq)]
 pub enum FieldType {
+    Int8,
+    Int16,
     Int32,
     Int64,
+    UInt8,
+    UInt16,
+    UInt32,
+    UInt64,
     Float32,
     Float64,
     Bool,
     String,
     Array(Box<FieldType>),
+    Map(Box<FieldType>), // String keys, values of the given type
     Object(String), // Nested object by type name
     Any,
 }
@@ -143,17 +156,26 @@ impl FieldType {
     /// Parse a field type from string annotation
     pub fn from_string(s: &str) -> Self {
         match s {
+            "i8" => FieldType::Int8,
+            "i16" => FieldType::Int16,
             "i32" | "int" => FieldType::Int32,
             "i64" | "long" => FieldType::Int64,
+            "u8" => FieldType::UInt8,
+            "u16" => FieldType::UInt16,
+            "u32" => FieldType::UInt32,
+            "u64" => FieldType::UInt64,
             "f32" | "float" => FieldType::Float32,
             "f64" | "double" => FieldType::Float64,
             "bool" | "boolean" => FieldType::Bool,
             "string" | "str" => FieldType::String,
+            "any" => FieldType::Any,
             _ => {
                 // Check for array syntax: type[]
                 if s.ends_with("[]") {
                     let inner = &s[..s.len() - 2];
                     FieldType::Array(Box::new(FieldType::from_string(inner)))
+                } else if let Some(inner) = s.strip_prefix("map<").and_then(|rest| rest.strip_suffix('>')) {
+                    FieldType::Map(Box::new(FieldType::from_string(inner)))
                 } else {
                     // Assume it's a named object type
                     FieldType::Object(s.to_string())
@@ -161,6 +183,127 @@ impl FieldType {
             }
         }
     }
+
+    /// Numeric element types can be mixed in one array literal (promoted to f64)
+    pub fn is_numeric(&self) -> bool {
+        self.is_integer() || self.is_float()
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.int_width().is_some()
+    }
+
+    pub fn is_unsigned(&self) -> bool {
+        matches!(self, FieldType::UInt8 | FieldType::UInt16 | FieldType::UInt32 | FieldType::UInt64)
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, FieldType::Float32 | FieldType::Float64)
+    }
+
+    /// Bit width of an integer type
+    pub fn int_width(&self) -> Option<u32> {
+        match self {
+            FieldType::Int8 | FieldType::UInt8 => Some(8),
+            FieldType::Int16 | FieldType::UInt16 => Some(16),
+            FieldType::Int32 | FieldType::UInt32 => Some(32),
+            FieldType::Int64 | FieldType::UInt64 => Some(64),
+            _ => None,
+        }
+    }
+
+    /// The integer type with this width and signedness
+    pub fn int_of(width: u32, unsigned: bool) -> Option<FieldType> {
+        Some(match (width, unsigned) {
+            (8, false) => FieldType::Int8,
+            (16, false) => FieldType::Int16,
+            (32, false) => FieldType::Int32,
+            (64, false) => FieldType::Int64,
+            (8, true) => FieldType::UInt8,
+            (16, true) => FieldType::UInt16,
+            (32, true) => FieldType::UInt32,
+            (64, true) => FieldType::UInt64,
+            _ => return None,
+        })
+    }
+
+    /// A value of this numeric type converts to `to` without an `as` cast: integers widen
+    /// within their signedness (or unsigned to a strictly wider signed type), any integer
+    /// becomes a float, and `f32` becomes `f64`
+    pub fn widens_to(&self, to: &FieldType) -> bool {
+        if self == to {
+            return true;
+        }
+        match (self.int_width(), to.int_width()) {
+            (Some(from_bits), Some(to_bits)) => match (self.is_unsigned(), to.is_unsigned()) {
+                (false, false) | (true, true) => from_bits <= to_bits,
+                (true, false) => from_bits < to_bits,
+                (false, true) => false,
+            },
+            (Some(_), None) => to.is_float(),
+            (None, _) => *self == FieldType::Float32 && *to == FieldType::Float64,
+        }
+    }
+
+    /// The type both operands of an arithmetic or comparison operator are converted to,
+    /// or `None` when neither widens to the other and no wider integer holds both
+    /// (`u64` with a signed type)
+    pub fn common_numeric(a: &FieldType, b: &FieldType) -> Option<FieldType> {
+        if b.widens_to(a) {
+            return Some(a.clone());
+        }
+        if a.widens_to(b) {
+            return Some(b.clone());
+        }
+        // Mixed signedness: the next signed width holds both
+        let bits = a.int_width()?.max(b.int_width()?);
+        FieldType::int_of(bits * 2, false)
+    }
+}
+
+impl std::fmt::Display for FieldType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldType::Int8 => write!(f, "i8"),
+            FieldType::Int16 => write!(f, "i16"),
+            FieldType::Int32 => write!(f, "i32"),
+            FieldType::Int64 => write!(f, "i64"),
+            FieldType::UInt8 => write!(f, "u8"),
+            FieldType::UInt16 => write!(f, "u16"),
+            FieldType::UInt32 => write!(f, "u32"),
+            FieldType::UInt64 => write!(f, "u64"),
+            FieldType::Float32 => write!(f, "f32"),
+            FieldType::Float64 => write!(f, "f64"),
+            FieldType::Bool => write!(f, "bool"),
+            FieldType::String => write!(f, "str"),
+            FieldType::Array(inner) => write!(f, "{}[]", inner),
+            FieldType::Map(inner) => write!(f, "map<{}>", inner),
+            FieldType::Object(name) => write!(f, "{}", name),
+            FieldType::Any => write!(f, "any"),
+        }
+    }
+}
+
+/// A numeric literal's value, before it is given a type
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+    Int(i128),
+    Float(f64),
+}
+
+impl FieldType {
+    /// `n` can be represented exactly enough in this type: integers must be in range,
+    /// and only float types accept fractional values
+    pub fn fits(&self, n: &Number) -> bool {
+        match n {
+            Number::Int(v) => match self.int_width() {
+                Some(bits) if self.is_unsigned() => *v >= 0 && *v < 1i128 << bits,
+                Some(bits) => *v >= -(1i128 << (bits - 1)) && *v < 1i128 << (bits - 1),
+                None => self.is_float(),
+            },
+            Number::Float(_) => self.is_float(),
+        }
+    }
 }
 
 /// Enhanced parameter with optional type pattern
@@ -178,6 +321,13 @@ pub enum TypePattern {
 
     /// Value pattern: param: literal_value
     Value(Expr),
+
+    /// Enum variant pattern in a `match` arm: `Circle(r)`, `Shape.Rect(w, _)`, `Empty`.
+    /// Each payload position binds `name` (`_` binds nothing) or matches a nested pattern.
+    Variant {
+        name: String,
+        fields: Vec<ParameterPattern>,
+    },
 }
 
 impl TypePattern {
@@ -187,8 +337,8 @@ impl TypePattern {
             TypePattern::Type(td) => Some(td.clone()),
             TypePattern::Value(expr) => {
                 // Try to extract type from literal values
-                match expr {
-                    Expr::Literal(n) => {
+                match &expr.kind {
+                    ExprKind::Literal(n) => {
                         // Check if it looks like HTTP status code
                         let num = *n as u16;
                         if num >= 100 && num < 600 {
@@ -200,6 +350,28 @@ impl TypePattern {
                     _ => None,
                 }
             }
+            TypePattern::Variant { .. } => None,
         }
     }
 }
+
+/// Enum (tagged union) definition: `enum Shape { Circle(f64), Rect(f64, f64), Empty }`
+#[derive(Debug, Clone)]
+pub struct EnumDefinition {
+    pub name: String,
+    pub variants: Vec<EnumVariant>,
+}
+
+/// One variant of an enum and the types of its payload fields
+#[derive(Debug, Clone)]
+pub struct EnumVariant {
+    pub name: String,
+    pub fields: Vec<FieldType>,
+}
+
+impl EnumDefinition {
+    /// Index and definition of the variant called `name`
+    pub fn variant(&self, name: &str) -> Option<(usize, &EnumVariant)> {
+        self.variants.iter().enumerate().find(|(_, v)| v.name == name)
+    }
+}
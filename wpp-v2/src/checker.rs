@@ -0,0 +1,1910 @@
+//! Static type checking between the parser and codegen
+//!
+//! `check_program` walks the parsed program once, infers the W++ type of every expression
+//! it can and reports mismatches as diagnostics before any IR is generated: `let`
+//! annotations, assignments, operators, conditions, `return` values, builtin arguments and
+//! calls. Calls to user functions are resolved against the same `FunctionSignature`s and
+//! `TypeDescriptor`s that codegen dispatches on, and object literals are checked against
+//! `type` aliases.
+//!
+//! The checker is conservative: whatever it cannot type statically (FFI and imported
+//! functions, entity members, thread handles) is `Ty::Unknown` and never reported, so
+//! codegen stays the final word on those.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::ast::node::{EntityMember, EntityNode};
+use crate::ast::pattern::{self, Pattern};
+use crate::ast::types::{EnumDefinition, FieldType, Number, ObjectTypeDefinition, ParameterPattern, TypeDescriptor, TypePattern};
+use crate::ast::{Expr, ExprKind, MatchArm, Node, NodeKind, Span};
+use crate::codegen::FunctionSignature;
+use crate::diagnostics::{CompileError, Diagnostic};
+
+/// Check a whole program, returning every type error found (empty when it is well typed)
+pub fn check_program(nodes: &[Node]) -> Vec<Diagnostic> {
+    let mut checker = Checker::default();
+    checker.frames.push(HashMap::new());
+    checker.declare(nodes);
+    checker.check_block(nodes);
+    checker.diagnostics
+}
+
+/// What the checker knows about a value
+#[derive(Debug, Clone, PartialEq)]
+enum Ty {
+    Value(FieldType),
+    /// A function or closure, described like a `func(...) -> T` parameter
+    Func(TypeDescriptor),
+    /// Not known statically; never reported
+    Unknown,
+}
+
+impl Ty {
+    fn int() -> Self {
+        Ty::Value(FieldType::Int32)
+    }
+
+    fn bool() -> Self {
+        Ty::Value(FieldType::Bool)
+    }
+
+    fn string() -> Self {
+        Ty::Value(FieldType::String)
+    }
+
+    /// Numbers and bools, which codegen converts between on assignment
+    fn is_scalar(&self) -> bool {
+        matches!(self, Ty::Value(t) if is_scalar(t))
+    }
+
+    /// Valid as a condition or a builtin integer argument
+    fn is_integral(&self) -> bool {
+        matches!(self, Ty::Value(t) if t.is_integer() || *t == FieldType::Bool)
+    }
+
+    fn is_float(&self) -> bool {
+        matches!(self, Ty::Value(FieldType::Float32 | FieldType::Float64))
+    }
+
+    /// Valid as an operand of `~`, `&`, `|`, `^`, `<<` and `>>`
+    fn is_integer(&self) -> bool {
+        matches!(self, Ty::Value(t) if t.is_integer())
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ty::Value(t) => write!(f, "{}", t),
+            Ty::Func(td) => write!(f, "{}", describe(td)),
+            Ty::Unknown => write!(f, "_"),
+        }
+    }
+}
+
+fn is_scalar(t: &FieldType) -> bool {
+    t.is_numeric() || *t == FieldType::Bool
+}
+
+/// Source-level spelling of a descriptor, for messages
+fn describe(td: &TypeDescriptor) -> String {
+    match td {
+        TypeDescriptor::Primitive(p) if p == "string" => "str".to_string(),
+        TypeDescriptor::Primitive(p) => p.clone(),
+        TypeDescriptor::Entity(name) | TypeDescriptor::ObjectType(name) | TypeDescriptor::Interface(name) => name.clone(),
+        TypeDescriptor::HttpStatusLiteral(code) => code.to_string(),
+        TypeDescriptor::HttpStatusRange(min, _) => format!("{}xx", min / 100),
+        TypeDescriptor::Function { param_types, return_type } => format!(
+            "func({}) -> {}",
+            param_types.iter().map(describe).collect::<Vec<_>>().join(", "),
+            describe(return_type)
+        ),
+        TypeDescriptor::Any => "any".to_string(),
+    }
+}
+
+pub(crate) fn describe_signature(sig: &FunctionSignature) -> String {
+    format!("{}({})", sig.name, sig.param_types.iter().map(describe).collect::<Vec<_>>().join(", "))
+}
+
+fn arguments(n: usize) -> String {
+    format!("{} argument{}", n, if n == 1 { "" } else { "s" })
+}
+
+/// Number of `{}` / `{:.N}` placeholders in a `format` template, or why it is malformed
+fn format_placeholders(template: &str) -> Result<usize, String> {
+    let mut count = 0;
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+            }
+            '{' => {
+                let spec: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let precision = spec.strip_prefix(":.").is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+                if !(spec.is_empty() || precision) {
+                    return Err(format!("unsupported placeholder `{{{}}}` in format string; use `{{}}` or `{{:.2}}`", spec));
+                }
+                count += 1;
+            }
+            '}' => return Err("unmatched `}` in format string; write `}}` for a literal brace".to_string()),
+            _ => {}
+        }
+    }
+    Ok(count)
+}
+
+/// One declaration (overload) of a user function
+struct FnDecl {
+    sig: FunctionSignature,
+    /// Declared return type, or what the body returns once it has been checked
+    ret: Ty,
+}
+
+/// The function whose body is being checked
+struct ReturnScope {
+    name: String,
+    declared: Option<Ty>,
+    found: Vec<Ty>,
+}
+
+#[derive(Default)]
+struct Checker {
+    diagnostics: Vec<Diagnostic>,
+    aliases: HashMap<String, ObjectTypeDefinition>,
+    enums: HashMap<String, EnumDefinition>,
+    /// Entity name → the entity it `alters`
+    entities: HashMap<String, Option<String>>,
+    /// Entity name → the interfaces it names in `implements`
+    implements: HashMap<String, Vec<String>>,
+    /// Interface names; codegen checks that entities provide their methods
+    interfaces: HashSet<String>,
+    /// Entity name → the parameters of the `init` it declares
+    inits: HashMap<String, Vec<TypeDescriptor>>,
+    functions: HashMap<String, Vec<FnDecl>>,
+    /// Names brought in by `import { ... }`, which may shadow builtins
+    imported: HashSet<String>,
+    /// Set by `import * from ...`, after which any builtin may be shadowed
+    imports_all: bool,
+    /// Declarations of each function whose body has been checked so far
+    checked: HashMap<String, usize>,
+    /// Variable types: globals first, then one frame per function (lambdas see outer frames)
+    frames: Vec<HashMap<String, Ty>>,
+    returns: Vec<ReturnScope>,
+}
+
+impl Checker {
+    fn report(&mut self, err: CompileError) {
+        self.diagnostics.push(err.into());
+    }
+
+    fn mismatch(&mut self, span: Span, message: String) {
+        self.report(CompileError::type_mismatch(span, message));
+    }
+
+    // === Declarations ===
+
+    /// Register type aliases, enums, entities and top-level functions so they can be used before
+    /// the point where they are defined
+    fn declare(&mut self, nodes: &[Node]) {
+        let items: Vec<&Node> = nodes
+            .iter()
+            .map(|node| match &node.kind {
+                NodeKind::Export { item, .. } => item.as_ref(),
+                _ => node,
+            })
+            .collect();
+
+        for node in &items {
+            match &node.kind {
+                NodeKind::TypeAlias(def) => {
+                    self.aliases.insert(def.name.clone(), def.clone());
+                }
+                NodeKind::Enum(def) => {
+                    self.enums.insert(def.name.clone(), def.clone());
+                }
+                NodeKind::Interface(def) => {
+                    self.interfaces.insert(def.name.clone());
+                }
+                NodeKind::Entity(entity) => {
+                    self.entities.insert(entity.name.clone(), entity.base.clone());
+                    self.implements.insert(entity.name.clone(), entity.interfaces.clone());
+                    for member in &entity.members {
+                        if let EntityMember::Method { name, func, .. } = member
+                            && name == "init"
+                            && let ExprKind::Funcy { params, params_patterns, .. } = &func.kind
+                        {
+                            let params = self.param_descriptors(params, params_patterns);
+                            self.inits.insert(entity.name.clone(), params);
+                        }
+                    }
+                }
+                NodeKind::ImportList { members, .. } => {
+                    for (name, alias) in members {
+                        self.imported.insert(alias.clone().unwrap_or_else(|| name.clone()));
+                    }
+                }
+                NodeKind::ImportAll { .. } => self.imports_all = true,
+                _ => {}
+            }
+        }
+        for node in items {
+            if let NodeKind::Expr(Expr { kind: ExprKind::Funcy { name, params, params_patterns, return_type, .. }, .. }) =
+                &node.kind
+                && !name.is_empty()
+            {
+                self.declare_function(name, params, params_patterns, return_type.as_ref());
+            }
+        }
+    }
+
+    fn declare_function(
+        &mut self,
+        name: &str,
+        params: &[String],
+        patterns: &Option<Vec<ParameterPattern>>,
+        return_type: Option<&TypeDescriptor>,
+    ) {
+        let sig = FunctionSignature {
+            name: name.to_string(),
+            param_types: self.param_descriptors(params, patterns),
+            return_type: return_type.cloned().unwrap_or_else(|| TypeDescriptor::Primitive("i32".to_string())),
+        };
+        let ret = return_type.map_or(Ty::Unknown, |td| self.descriptor_ty(td));
+        self.functions.entry(name.to_string()).or_default().push(FnDecl { sig, ret });
+    }
+
+    /// Parameter descriptors the way codegen builds them for dispatch: patterns when any
+    /// parameter has one, otherwise the `name:type` strings (unannotated means `i32`)
+    fn param_descriptors(&self, params: &[String], patterns: &Option<Vec<ParameterPattern>>) -> Vec<TypeDescriptor> {
+        match patterns {
+            Some(patterns) => patterns
+                .iter()
+                .map(|p| match p.pattern.as_ref().and_then(TypePattern::to_type_descriptor) {
+                    Some(TypeDescriptor::ObjectType(name)) if self.entities.contains_key(&name) => {
+                        TypeDescriptor::Entity(name)
+                    }
+                    Some(TypeDescriptor::ObjectType(name)) if self.interfaces.contains(&name) => {
+                        TypeDescriptor::Interface(name)
+                    }
+                    Some(td) => td,
+                    None => TypeDescriptor::Primitive("i32".to_string()),
+                })
+                .collect(),
+            None => params
+                .iter()
+                .map(|p| TypeDescriptor::Primitive(p.split_once(':').map_or("i32", |(_, t)| t.trim()).to_string()))
+                .collect(),
+        }
+    }
+
+    fn descriptor_ty(&self, td: &TypeDescriptor) -> Ty {
+        match td {
+            TypeDescriptor::Primitive(p) => match p.as_str() {
+                "bool" | "boolean" => Ty::bool(),
+                "string" | "str" => Ty::string(),
+                _ => match FieldType::from_string(p) {
+                    ty if ty.is_numeric() => Ty::Value(ty),
+                    _ => Ty::Unknown,
+                },
+            },
+            TypeDescriptor::Entity(name) | TypeDescriptor::ObjectType(name) | TypeDescriptor::Interface(name) => {
+                Ty::Value(FieldType::Object(name.clone()))
+            }
+            TypeDescriptor::HttpStatusLiteral(_) | TypeDescriptor::HttpStatusRange(..) => Ty::int(),
+            TypeDescriptor::Function { .. } => Ty::Func(td.clone()),
+            TypeDescriptor::Any => Ty::Unknown,
+        }
+    }
+
+    /// Inverse of `descriptor_ty`, used to describe lambdas; heap values have no descriptor
+    fn ty_descriptor(&self, ty: &Ty) -> TypeDescriptor {
+        let primitive = |p: &str| TypeDescriptor::Primitive(p.to_string());
+        match ty {
+            Ty::Value(t) if t.is_numeric() => primitive(&t.to_string()),
+            Ty::Value(FieldType::Bool) => primitive("bool"),
+            Ty::Value(FieldType::String) => primitive("string"),
+            Ty::Value(FieldType::Object(name)) if self.entities.contains_key(name) => TypeDescriptor::Entity(name.clone()),
+            Ty::Value(FieldType::Object(name)) if self.interfaces.contains(name) => TypeDescriptor::Interface(name.clone()),
+            Ty::Value(FieldType::Object(name)) => TypeDescriptor::ObjectType(name.clone()),
+            Ty::Func(td) => td.clone(),
+            _ => TypeDescriptor::Any,
+        }
+    }
+
+    /// Type of a `let` annotation, reporting names that are neither aliases nor entities
+    fn annotation_ty(&mut self, annotation: &str, span: Span) -> Option<Ty> {
+        let ty = FieldType::from_string(annotation);
+        if let Some(name) = self.unknown_type_name(&ty) {
+            self.mismatch(span, format!("unknown type `{}`", name));
+            return None;
+        }
+        Some(Ty::Value(ty))
+    }
+
+    fn unknown_type_name(&self, ty: &FieldType) -> Option<String> {
+        match ty {
+            FieldType::Array(inner) | FieldType::Map(inner) => self.unknown_type_name(inner),
+            FieldType::Object(name)
+                if !self.aliases.contains_key(name)
+                    && !self.entities.contains_key(name)
+                    && !self.enums.contains_key(name)
+                    && !self.interfaces.contains(name) =>
+            {
+                Some(name.clone())
+            }
+            _ => None,
+        }
+    }
+
+    // === Compatibility ===
+
+    /// Can a value of type `found` be stored where `expected` is required?
+    fn accepts(&self, expected: &Ty, found: &Ty) -> bool {
+        match (expected, found) {
+            (Ty::Unknown, _) | (_, Ty::Unknown) => true,
+            (Ty::Value(FieldType::Any), _) | (_, Ty::Value(FieldType::Any)) => true,
+            (Ty::Value(e), Ty::Value(f)) => {
+                e == f
+                    || (e.is_numeric() && f.is_numeric() && f.widens_to(e))
+                    || (is_scalar(e) && is_scalar(f) && (*e == FieldType::Bool || *f == FieldType::Bool))
+                    || matches!((e, f), (FieldType::Object(e), FieldType::Object(f))
+                        if !self.enums.contains_key(e) && (f == "object" || self.is_subtype(f, e)))
+            }
+            (Ty::Func(e), Ty::Func(f)) => e.matches(f),
+            _ => false,
+        }
+    }
+
+    /// `child` is `parent` or alters it, directly or through other entities; an entity is also a
+    /// subtype of the interfaces it or its base entities implement
+    fn is_subtype(&self, child: &str, parent: &str) -> bool {
+        let mut current = Some(child.to_string());
+        let mut depth = 0;
+        while let Some(name) = current {
+            if name == parent || self.implements.get(&name).is_some_and(|list| list.iter().any(|i| i == parent)) {
+                return true;
+            }
+            depth += 1;
+            if depth > self.entities.len() {
+                return false;
+            }
+            current = self.entities.get(&name).cloned().flatten();
+        }
+        false
+    }
+
+    /// Steps along the `alters` chain from `child` up to `ancestor`, or `None` if `child` does not
+    /// alter it
+    fn alters_distance(&self, child: &str, ancestor: &str) -> Option<u32> {
+        let mut current = Some(child.to_string());
+        let mut steps = 0;
+        while let Some(name) = current {
+            if name == ancestor {
+                return Some(steps);
+            }
+            steps += 1;
+            if steps as usize > self.entities.len() {
+                return None;
+            }
+            current = self.entities.get(&name).cloned().flatten();
+        }
+        None
+    }
+
+    /// How far an argument is from a parameter that accepts it: one per `alters` step up to an
+    /// entity parameter, 1 when a number widens and 2 when an integer becomes a float
+    fn param_distance(&self, param: &TypeDescriptor, arg: &Ty) -> u32 {
+        match (self.descriptor_ty(param), arg) {
+            (Ty::Value(FieldType::Object(param)), Ty::Value(FieldType::Object(arg))) => {
+                self.alters_distance(arg, &param).unwrap_or(0)
+            }
+            (Ty::Value(param), Ty::Value(arg)) if param.is_numeric() && arg.is_numeric() && param != *arg => {
+                if param.is_float() && !arg.is_float() { 2 } else { 1 }
+            }
+            _ => 0,
+        }
+    }
+
+    /// Whether a call argument can bind to a parameter; HTTP status patterns look at literals
+    fn param_accepts(&self, param: &TypeDescriptor, arg: &Ty, expr: &Expr) -> bool {
+        match (param, int_literal(expr)) {
+            (TypeDescriptor::Any, _) => true,
+            (TypeDescriptor::HttpStatusLiteral(code), Some(n)) => n == i64::from(*code),
+            (TypeDescriptor::HttpStatusRange(min, max), Some(n)) => (i64::from(*min)..=i64::from(*max)).contains(&n),
+            (TypeDescriptor::HttpStatusLiteral(_) | TypeDescriptor::HttpStatusRange(..), None) => {
+                *arg == Ty::Unknown || arg.is_integral()
+            }
+            // An interface value reaches the overload for the entity it turns out to be at runtime,
+            // so some implementer must be that entity or alter it
+            (TypeDescriptor::Entity(entity), _)
+                if matches!(arg, Ty::Value(FieldType::Object(interface)) if self.interfaces.contains(interface)
+                    && self.entities.keys().any(|e| self.is_subtype(e, entity) && self.is_subtype(e, interface))) =>
+            {
+                true
+            }
+            _ => {
+                let param = self.descriptor_ty(param);
+                let literal_fits = matches!(&param, Ty::Value(ty) if expr.untyped_number().is_some_and(|n| ty.fits(&n)));
+                literal_fits || self.accepts(&param, arg)
+            }
+        }
+    }
+
+    // === Variables ===
+
+    fn bind(&mut self, name: &str, ty: Ty) {
+        self.frames.last_mut().expect("the global frame is never popped").insert(name.to_string(), ty);
+    }
+
+    fn lookup(&self, name: &str) -> Option<Ty> {
+        self.frames.iter().rev().find_map(|frame| frame.get(name).cloned())
+    }
+
+    fn variable_ty(&mut self, name: &str, span: Span) -> Ty {
+        if let Some((enum_name, variant)) = self.enum_variant(name) {
+            // `Shape.Empty`; a variant with a payload must be called
+            self.check_arity(name, &[], variant.fields.len(), span);
+            return Ty::Value(FieldType::Object(enum_name));
+        }
+        // `p.x` on a variable of a `type` alias is the field's type
+        if let Some((base, field)) = name.split_once('.') {
+            return match self.lookup(base) {
+                Some(Ty::Value(FieldType::Object(alias))) => self
+                    .aliases
+                    .get(&alias)
+                    .and_then(|def| def.fields.iter().find(|f| f.name == field))
+                    .map_or(Ty::Unknown, |f| Ty::Value(f.ty.clone())),
+                _ => Ty::Unknown,
+            };
+        }
+        if let Some(ty) = self.lookup(name) {
+            return ty;
+        }
+        // A function used as a value (`useThread(worker)`, `apply(double, 5)`)
+        match self.functions.get(name).and_then(|decls| decls.first()) {
+            Some(decl) => Ty::Func(TypeDescriptor::Function {
+                param_types: decl.sig.param_types.clone(),
+                return_type: Box::new(match &decl.ret {
+                    Ty::Unknown => decl.sig.return_type.clone(),
+                    ret => self.ty_descriptor(ret),
+                }),
+            }),
+            None => Ty::Unknown,
+        }
+    }
+
+    // === Statements ===
+
+    fn check_block(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            self.check_node(node);
+        }
+    }
+
+    fn check_node(&mut self, node: &Node) {
+        match &node.kind {
+            NodeKind::Let { name, value, ty, .. } => {
+                let declared = ty.as_deref().and_then(|t| self.annotation_ty(t, node.span));
+                let found = self.check_expr(value, declared.as_ref());
+                let var_ty = match declared {
+                    Some(declared) => {
+                        if !self.accepts(&declared, &found) {
+                            self.mismatch(
+                                value.span,
+                                format!("`{}` is declared as `{}` but initialized with `{}`", name, declared, found),
+                            );
+                        }
+                        declared
+                    }
+                    None => found,
+                };
+                self.bind(name, var_ty);
+            }
+            NodeKind::Expr(expr) => match &expr.kind {
+                ExprKind::Funcy { name, params, params_patterns, body, return_type, .. } if !name.is_empty() => {
+                    self.check_function(name, params, params_patterns, body, return_type.as_ref());
+                }
+                _ => {
+                    self.check_expr(expr, None);
+                }
+            },
+            NodeKind::Entity(entity) => self.check_entity(entity),
+            NodeKind::Export { item, .. } => self.check_node(item),
+            NodeKind::TypeAlias(def) => {
+                for field in &def.fields {
+                    if let Some(name) = self.unknown_type_name(&field.ty) {
+                        self.mismatch(node.span, format!("field `{}` of `{}` has unknown type `{}`", field.name, def.name, name));
+                    }
+                }
+            }
+            NodeKind::Enum(def) => {
+                for variant in &def.variants {
+                    for ty in &variant.fields {
+                        let Some(name) = self.unknown_type_name(ty) else { continue };
+                        self.mismatch(node.span, format!("variant `{}.{}` has unknown type `{}`", def.name, variant.name, name));
+                    }
+                }
+            }
+            // Conformance is checked by codegen against the compiled entity
+            NodeKind::Interface(_) | NodeKind::ImportAll { .. } | NodeKind::ImportList { .. } => {}
+        }
+    }
+
+    /// Check a named function's body and remember what it returns when it has no annotation
+    fn check_function(
+        &mut self,
+        name: &str,
+        params: &[String],
+        patterns: &Option<Vec<ParameterPattern>>,
+        body: &[Node],
+        return_type: Option<&TypeDescriptor>,
+    ) {
+        let index = *self.checked.get(name).unwrap_or(&0);
+        if index >= self.functions.get(name).map_or(0, Vec::len) {
+            // Declared inside another body rather than at the top level
+            self.declare_function(name, params, patterns, return_type);
+        }
+        self.checked.insert(name.to_string(), index + 1);
+
+        let param_types = self.functions[name][index].sig.param_types.clone();
+        let declared = return_type.map(|td| self.descriptor_ty(td));
+        let ret = self.check_body(name, params, &param_types, body, declared);
+        self.functions.get_mut(name).expect("declared above")[index].ret = ret;
+    }
+
+    /// Check a function or lambda body in a new frame, returning its (declared or found) result type
+    fn check_body(
+        &mut self,
+        name: &str,
+        params: &[String],
+        param_types: &[TypeDescriptor],
+        body: &[Node],
+        declared: Option<Ty>,
+    ) -> Ty {
+        let frame = params
+            .iter()
+            .zip(param_types)
+            .map(|(p, td)| (p.split(':').next().unwrap_or(p).trim().to_string(), self.descriptor_ty(td)))
+            .collect();
+        self.frames.push(frame);
+        self.returns.push(ReturnScope { name: name.to_string(), declared: declared.clone(), found: Vec::new() });
+        self.check_block(body);
+        let scope = self.returns.pop().expect("pushed above");
+        self.frames.pop();
+
+        declared.unwrap_or_else(|| match scope.found.split_first() {
+            Some((first, rest)) if rest.iter().all(|t| t == first) => first.clone(),
+            _ => Ty::Unknown,
+        })
+    }
+
+    fn check_entity(&mut self, entity: &EntityNode) {
+        for member in &entity.members {
+            match member {
+                EntityMember::Field { value, .. } => {
+                    self.check_expr(value, None);
+                }
+                EntityMember::Method { func, .. } => {
+                    if let ExprKind::Funcy { name, params, params_patterns, body, return_type, .. } = &func.kind {
+                        let param_types = self.param_descriptors(params, params_patterns);
+                        let declared = return_type.as_ref().map(|td| self.descriptor_ty(td));
+                        self.frames.push(HashMap::from([("me".to_string(), Ty::Value(FieldType::Object(entity.name.clone())))]));
+                        self.check_body(name, params, &param_types, body, declared);
+                        self.frames.pop();
+                    }
+                }
+            }
+        }
+    }
+
+    // === Expressions ===
+
+    /// Infer the type of `expr`, reporting errors inside it. `expected` lets annotations flow
+    /// into literals that cannot infer their own type (`[]`, `Map()`, `{ ... }` for an alias).
+    fn check_expr(&mut self, expr: &Expr, expected: Option<&Ty>) -> Ty {
+        // A number without a suffix takes the numeric type expected of it when it fits
+        if let Some(Ty::Value(ty)) = expected
+            && let Some(n) = expr.untyped_number()
+        {
+            if ty.is_numeric() && ty.fits(&n) {
+                return Ty::Value(ty.clone());
+            }
+            if let (true, Number::Int(v)) = (ty.is_integer(), n) {
+                self.mismatch(expr.span, format!("integer literal `{}` does not fit in `{}`", v, ty));
+                return Ty::Value(ty.clone());
+            }
+        }
+        match &expr.kind {
+            ExprKind::Literal(_) => Ty::int(),
+            ExprKind::TypedLiteral { value, ty } => {
+                let found = self.descriptor_ty(&TypeDescriptor::Primitive(ty.clone()));
+                if let (Ty::Value(t), Ok(n)) = (&found, value.parse::<i128>())
+                    && !t.fits(&Number::Int(n))
+                {
+                    self.mismatch(expr.span, format!("integer literal `{}` does not fit in `{}`", value, t));
+                }
+                found
+            }
+            ExprKind::BoolLiteral(_) => Ty::bool(),
+            ExprKind::StringLiteral(_) => Ty::string(),
+            ExprKind::Variable(name) => self.variable_ty(name, expr.span),
+            ExprKind::BinaryOp { left, op, right } if op == "=" => self.check_assign(left, right),
+            ExprKind::BinaryOp { left, op, right } => self.check_binary(left, op, right, expr.span),
+            ExprKind::Call { name, args } => self.check_call(name, args, expected, expr.span),
+            ExprKind::If { cond, then_branch, else_branch } => {
+                self.check_condition(cond, "if");
+                self.check_block(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.check_block(else_branch);
+                }
+                Ty::Unknown
+            }
+            ExprKind::While { cond, body } => {
+                self.check_condition(cond, "while");
+                self.check_block(body);
+                Ty::Unknown
+            }
+            ExprKind::For { init, cond, post, body } => {
+                if let Some(init) = init {
+                    self.check_node(init);
+                }
+                if let Some(cond) = cond {
+                    self.check_condition(cond, "for");
+                }
+                if let Some(post) = post {
+                    self.check_expr(post, None);
+                }
+                self.check_block(body);
+                Ty::Unknown
+            }
+            ExprKind::ForIn { key, item, iterable, body } => {
+                self.check_for_in(key.as_deref(), item, iterable);
+                self.check_block(body);
+                Ty::Unknown
+            }
+            ExprKind::Range { start, end } => {
+                self.check_range_bound(start);
+                self.check_range_bound(end);
+                Ty::Unknown
+            }
+            ExprKind::Break | ExprKind::Continue => Ty::Unknown,
+            ExprKind::Switch { expr: scrutinee, cases, default } => {
+                let subject = self.check_expr(scrutinee, None);
+                for (case, body) in cases {
+                    let found = self.check_expr(case, Some(&subject));
+                    if !self.accepts(&subject, &found) {
+                        self.mismatch(case.span, format!("`case` of type `{}` can never match a `switch` on `{}`", found, subject));
+                    }
+                    self.check_block(body);
+                }
+                if let Some(default) = default {
+                    self.check_block(default);
+                }
+                Ty::Unknown
+            }
+            ExprKind::TryCatch { try_block, catches, finally_block } => {
+                self.check_block(try_block);
+                for clause in catches {
+                    // A typed clause only runs for values of its type
+                    let ty = match &clause.ty {
+                        Some(td) => {
+                            let ty = self.descriptor_ty(td);
+                            if let Ty::Value(field_ty) = &ty
+                                && let Some(name) = self.unknown_type_name(field_ty)
+                            {
+                                self.mismatch(expr.span, format!("unknown type `{}` in `catch`", name));
+                            }
+                            ty
+                        }
+                        None => Ty::Unknown,
+                    };
+                    if let Some(var) = &clause.var {
+                        self.bind(var, ty);
+                    }
+                    self.check_block(&clause.body);
+                }
+                if let Some(finally_block) = finally_block {
+                    self.check_block(finally_block);
+                }
+                Ty::Unknown
+            }
+            ExprKind::Throw { expr } | ExprKind::Await(expr) | ExprKind::Defer(expr) => {
+                self.check_expr(expr, None);
+                Ty::Unknown
+            }
+            ExprKind::Funcy { name, params, params_patterns, body, return_type, .. } => {
+                // A function used as a value: a lambda, or a named one passed inline
+                let param_types = self.param_descriptors(params, params_patterns);
+                let declared = return_type.as_ref().map(|td| self.descriptor_ty(td));
+                let ret = self.check_body(name, params, &param_types, body, declared);
+                Ty::Func(TypeDescriptor::Function { param_types, return_type: Box::new(self.ty_descriptor(&ret)) })
+            }
+            ExprKind::Return(value) => {
+                self.check_return(value.as_deref());
+                Ty::Unknown
+            }
+            ExprKind::ArrayLiteral(elements) => self.check_array_literal(elements, expected),
+            ExprKind::Index { target, index } => {
+                let target_ty = self.check_expr(target, None);
+                let index_ty = self.check_expr(index, None);
+                if !(index_ty == Ty::Unknown || index_ty.is_integral()) {
+                    self.mismatch(index.span, format!("array index must be an integer, found `{}`", index_ty));
+                }
+                match target_ty {
+                    Ty::Value(FieldType::Array(elem)) if *elem != FieldType::Any => Ty::Value(*elem),
+                    Ty::Value(FieldType::Array(_)) | Ty::Unknown => Ty::Unknown,
+                    other => {
+                        self.mismatch(target.span, format!("cannot index into a value of type `{}`", other));
+                        Ty::Unknown
+                    }
+                }
+            }
+            ExprKind::ObjectLiteral { fields, type_name } => self.check_object_literal(fields, type_name.as_deref(), expected, expr.span),
+            ExprKind::NewInstance { entity, args } => self.check_new_instance(entity, args, expr.span),
+            ExprKind::Cast { expr: inner, ty } => {
+                let to = FieldType::from_string(ty);
+                let castable = |t: &FieldType| t.is_numeric() || *t == FieldType::Bool;
+                if !castable(&to) {
+                    self.check_expr(inner, None);
+                    self.mismatch(expr.span, format!("cannot cast to `{}`: `as` converts between numbers and bools", ty));
+                    return Ty::Unknown;
+                }
+                // `5000000000 as u64` is a u64 literal rather than an oversized i32,
+                // while `300 as u8` truncates an i32
+                let hint = inner.untyped_number().is_some_and(|n| to.fits(&n)).then(|| Ty::Value(to.clone()));
+                let from = self.check_expr(inner, hint.as_ref());
+                if let Ty::Value(from) = &from
+                    && !castable(from)
+                {
+                    self.mismatch(inner.span, format!("cannot cast `{}` to `{}`", from, to));
+                }
+                Ty::Value(to)
+            }
+            ExprKind::Match { subject, arms } => self.check_match(subject, arms),
+        }
+    }
+
+    /// The nearest entity up the `alters` chain from `entity` that declares an `init`, with its parameters
+    fn nearest_init(&self, entity: &str) -> Option<(String, Vec<TypeDescriptor>)> {
+        let mut current = Some(entity.to_string());
+        for _ in 0..=self.entities.len() {
+            let name = current?;
+            if let Some(params) = self.inits.get(&name) {
+                return Some((name, params.clone()));
+            }
+            current = self.entities.get(&name).cloned().flatten();
+        }
+        None
+    }
+
+    /// `new(Dog, ...)` passes its arguments to the nearest `init`; unknown entities are left to codegen
+    fn check_new_instance(&mut self, entity: &str, args: &[Expr], span: Span) -> Ty {
+        let instance = Ty::Value(FieldType::Object(entity.to_string()));
+        let (callee, params) = match self.nearest_init(entity) {
+            Some((owner, params)) => (format!("{}.init", owner), params),
+            None => (entity.to_string(), Vec::new()),
+        };
+        if !self.entities.contains_key(entity) || !self.check_arity(&callee, args, params.len(), span) {
+            for arg in args {
+                self.check_expr(arg, None);
+            }
+            return instance;
+        }
+        for (i, (arg, param)) in args.iter().zip(&params).enumerate() {
+            let found = self.check_expr(arg, Some(&self.descriptor_ty(param)));
+            if !self.param_accepts(param, &found, arg) {
+                self.mismatch(
+                    arg.span,
+                    format!("argument {} of `{}` must be `{}`, found `{}`", i + 1, callee, describe(param), found),
+                );
+            }
+        }
+        instance
+    }
+
+    /// Resolve the arms' patterns against the subject, require them to cover every value and
+    /// check each arm with its bindings in scope. The result is the arms' common type.
+    fn check_match(&mut self, subject: &Expr, arms: &[MatchArm]) -> Ty {
+        let mut subject_ty = self.check_expr(subject, None);
+        let is_enum = matches!(&subject_ty, Ty::Value(FieldType::Object(n)) if self.enums.contains_key(n));
+        if !is_enum
+            && matches!(&subject_ty, Ty::Unknown | Ty::Value(FieldType::String))
+            && let Some(enum_name) = pattern::infer_enum(arms.iter().map(|arm| &arm.pattern), &self.enums)
+        {
+            // Untyped parameters are matched as the enum the patterns name, as in codegen
+            subject_ty = Ty::Value(FieldType::Object(enum_name));
+        }
+
+        let mut patterns: Vec<Option<Pattern>> = Vec::with_capacity(arms.len());
+        for arm in arms {
+            let Ty::Value(ty) = &subject_ty else {
+                patterns.push(None);
+                continue;
+            };
+            match pattern::resolve(&arm.pattern, ty, &self.enums) {
+                Ok(pat) => {
+                    if let Pattern::Literal(literal) = &pat {
+                        let found = self.check_expr(literal, None);
+                        // Numbers compare with numbers; bools and strings only with their own kind
+                        let comparable = match &found {
+                            Ty::Value(f) if f.is_numeric() => ty.is_numeric(),
+                            Ty::Value(f) => f == ty,
+                            _ => true,
+                        };
+                        if !comparable {
+                            self.report(CompileError::InvalidPattern {
+                                message: format!("cannot match a value of type `{}` against a `{}` literal", ty, found),
+                                span: literal.span,
+                            });
+                        }
+                    }
+                    patterns.push(Some(pat));
+                }
+                Err(message) => {
+                    self.report(CompileError::InvalidPattern { message, span: arm.span });
+                    patterns.push(None);
+                }
+            }
+        }
+        if let Ty::Value(ty) = &subject_ty
+            && let Some(resolved) = patterns.iter().cloned().collect::<Option<Vec<_>>>()
+            && let Some(missing) = pattern::uncovered(&resolved, ty, &self.enums)
+        {
+            self.report(CompileError::NonExhaustiveMatch { missing, span: subject.span });
+        }
+
+        let mut tys = Vec::with_capacity(arms.len());
+        for (arm, pat) in arms.iter().zip(&patterns) {
+            let bound = match (pat, &subject_ty) {
+                (Some(pat), Ty::Value(ty)) => pattern::bindings(pat, ty, &self.enums),
+                _ => Vec::new(),
+            };
+            let frame = self.frames.last_mut().expect("the global frame is never popped");
+            let shadowed: Vec<(String, Option<Ty>)> = bound
+                .into_iter()
+                .map(|(name, ty)| {
+                    let previous = frame.insert(name.clone(), Ty::Value(ty));
+                    (name, previous)
+                })
+                .collect();
+
+            if let Some((last, rest)) = arm.body.split_last() {
+                self.check_block(rest);
+                match &last.kind {
+                    NodeKind::Expr(expr) => tys.push(self.check_expr(expr, None)),
+                    _ => {
+                        self.check_node(last);
+                        tys.push(Ty::Unknown);
+                    }
+                }
+            }
+
+            let frame = self.frames.last_mut().expect("the global frame is never popped");
+            for (name, previous) in shadowed {
+                match previous {
+                    Some(ty) => frame.insert(name, ty),
+                    None => frame.remove(&name),
+                };
+            }
+        }
+
+        match tys.split_first() {
+            Some((first, rest)) if rest.iter().all(|t| t == first) => first.clone(),
+            Some(_) if tys.iter().all(|t| matches!(t, Ty::Value(f) if f.is_numeric())) => {
+                unify(&tys).map_or(Ty::Unknown, Ty::Value)
+            }
+            _ => Ty::Unknown,
+        }
+    }
+
+    /// The type both operands of an arithmetic operator widen to (bools count as `i32`)
+    fn promote(&mut self, l: &Ty, r: &Ty, span: Span) -> Ty {
+        let numeric = |t: &Ty| match t {
+            Ty::Value(FieldType::Bool) | Ty::Unknown => FieldType::Int32,
+            Ty::Value(t) => t.clone(),
+            Ty::Func(_) => FieldType::Any,
+        };
+        match FieldType::common_numeric(&numeric(l), &numeric(r)) {
+            Some(ty) => Ty::Value(ty),
+            None => {
+                self.mismatch(span, format!("`{}` and `{}` have no common type; convert one with `as`", l, r));
+                Ty::Unknown
+            }
+        }
+    }
+
+    fn check_condition(&mut self, cond: &Expr, keyword: &str) {
+        let ty = self.check_expr(cond, None);
+        if !(ty == Ty::Unknown || ty.is_integral()) {
+            self.mismatch(cond.span, format!("condition in `{}` must be int or bool, found `{}`", keyword, ty));
+        }
+    }
+
+    fn check_range_bound(&mut self, bound: &Expr) {
+        let ty = self.check_expr(bound, None);
+        if !(ty == Ty::Unknown || ty.is_integral()) {
+            self.mismatch(bound.span, format!("range bounds must be integers, found `{}`", ty));
+        }
+    }
+
+    fn check_for_in(&mut self, key: Option<&str>, item: &str, iterable: &Expr) {
+        let (key_ty, item_ty) = match &iterable.kind {
+            ExprKind::Range { .. } => {
+                self.check_expr(iterable, None);
+                (Ty::Unknown, Ty::int())
+            }
+            _ => match self.check_expr(iterable, None) {
+                Ty::Value(FieldType::Array(elem)) => (Ty::int(), Ty::Value(*elem)),
+                Ty::Value(FieldType::Map(val)) if key.is_some() => (Ty::string(), Ty::Value(*val)),
+                Ty::Value(FieldType::Map(_)) => (Ty::Unknown, Ty::string()),
+                Ty::Unknown => (Ty::Unknown, Ty::Unknown),
+                other => {
+                    self.mismatch(iterable.span, format!("cannot iterate over `{}`: expected an array, map or range", other));
+                    (Ty::Unknown, Ty::Unknown)
+                }
+            },
+        };
+        if let Some(key) = key {
+            self.bind(key, key_ty);
+        }
+        self.bind(item, item_ty);
+    }
+
+    /// Check `left = right`; the assignment evaluates to the stored value, typed as the target
+    fn check_assign(&mut self, left: &Expr, right: &Expr) -> Ty {
+        match &left.kind {
+            ExprKind::Variable(name) if !name.contains('.') => {
+                let target = self.lookup(name);
+                let found = self.check_expr(right, target.as_ref());
+                match target {
+                    Some(target) => {
+                        if !self.accepts(&target, &found) {
+                            self.mismatch(right.span, format!("cannot assign `{}` to `{}` of type `{}`", found, name, target));
+                        }
+                        target
+                    }
+                    None => found,
+                }
+            }
+            ExprKind::Index { .. } => {
+                let elem = self.check_expr(left, None);
+                let found = self.check_expr(right, Some(&elem));
+                if !self.accepts(&elem, &found) {
+                    self.mismatch(right.span, format!("expected an element of type `{}`, found `{}`", elem, found));
+                }
+                elem
+            }
+            // Entity members and invalid targets are left to codegen
+            _ => self.check_expr(right, None),
+        }
+    }
+
+    fn check_binary(&mut self, left: &Expr, op: &str, right: &Expr, span: Span) -> Ty {
+        // An unsuffixed literal takes the other operand's type when it fits (`x + 1` with
+        // `x: u8` is a `u8`, while `x + 1000` widens to `i32`)
+        // A shift keeps its left operand's type, so only the amount adopts from the value
+        let shift = matches!(op, "<<" | ">>");
+        let bitwise = shift || matches!(op, "&" | "|" | "^" | "~");
+        let hint = |e: &Expr, ty: &Ty| match (e.untyped_number(), ty) {
+            (None, _) => true,
+            (Some(n), Ty::Value(t)) => (if bitwise { t.is_integer() } else { t.is_numeric() }) && t.fits(&n),
+            (Some(_), _) => false,
+        };
+        let (l, r) = if left.untyped_number().is_some() && !shift {
+            let r = self.check_expr(right, None);
+            let l = self.check_expr(left, hint(left, &r).then_some(&r));
+            (l, r)
+        } else {
+            let l = self.check_expr(left, None);
+            let r = self.check_expr(right, hint(right, &l).then_some(&l));
+            (l, r)
+        };
+        let comparison = matches!(op, "==" | "!=" | "<" | "<=" | ">" | ">=" | "and" | "or" | "!");
+        if l == Ty::Unknown || r == Ty::Unknown {
+            return if comparison { Ty::bool() } else { Ty::Unknown };
+        }
+
+        match op {
+            "+" if l == Ty::string() && r == Ty::string() => Ty::string(),
+            "+" | "-" | "*" | "/" | "%" if l.is_scalar() && r.is_scalar() => self.promote(&l, &r, span),
+            // Unary `!x` and `~x` are parsed as `0 ! x` and `0 ~ x`
+            "!" if r.is_integral() => Ty::bool(),
+            "~" if r.is_integer() => r,
+            "&" | "|" | "^" if l == Ty::bool() && r == Ty::bool() => Ty::bool(),
+            "&" | "|" | "^" if l.is_integer() && r.is_integer() => self.promote(&l, &r, span),
+            "<<" | ">>" if l.is_integer() && r.is_integer() => l,
+            "==" | "!=" | "<" | "<=" | ">" | ">=" if l.is_scalar() && r.is_scalar() => {
+                if l.is_float() != r.is_float() {
+                    self.mismatch(span, format!("cannot compare `{}` with `{}` without an explicit cast", l, r));
+                } else {
+                    self.promote(&l, &r, span);
+                }
+                Ty::bool()
+            }
+            "==" | "!=" if l == r => Ty::bool(),
+            "and" | "or" if l.is_integral() && r.is_integral() => Ty::bool(),
+            "!" | "~" => {
+                self.mismatch(span, format!("unsupported operand type for `{}`: `{}`", op, r));
+                if op == "!" { Ty::bool() } else { Ty::Unknown }
+            }
+            "+" | "-" | "*" | "/" | "%" | "&" | "|" | "^" | "<<" | ">>" | "==" | "!=" | "<" | "<=" | ">" | ">="
+            | "and" | "or" => {
+                self.mismatch(span, format!("unsupported operand types for `{}`: `{}` and `{}`", op, l, r));
+                Ty::Unknown
+            }
+            // Operators codegen does not lower are reported there
+            _ => Ty::Unknown,
+        }
+    }
+
+    fn check_return(&mut self, value: Option<&Expr>) {
+        let declared = self.returns.last().and_then(|scope| scope.declared.clone());
+        let Some(value) = value else {
+            return;
+        };
+        let found = self.check_expr(value, declared.as_ref());
+        if let Some(declared) = &declared
+            && !self.accepts(declared, &found)
+        {
+            let name = self.returns.last().map(|scope| scope.name.clone()).unwrap_or_default();
+            self.mismatch(value.span, format!("`{}` is declared to return `{}`, found `{}`", name, declared, found));
+        }
+        if let Some(scope) = self.returns.last_mut() {
+            scope.found.push(found);
+        }
+    }
+
+    fn check_array_literal(&mut self, elements: &[Expr], expected: Option<&Ty>) -> Ty {
+        let expected_elem = match expected {
+            Some(Ty::Value(FieldType::Array(elem))) => Some(Ty::Value((**elem).clone())),
+            _ => None,
+        };
+        let mut tys = Vec::with_capacity(elements.len());
+        for element in elements {
+            let found = self.check_expr(element, expected_elem.as_ref());
+            if let Some(elem) = &expected_elem
+                && !self.accepts(elem, &found)
+            {
+                self.mismatch(element.span, format!("expected an element of type `{}`, found `{}`", elem, found));
+            }
+            tys.push(found);
+        }
+
+        if let Some(elem) = expected_elem {
+            return Ty::Value(FieldType::Array(Box::new(match elem {
+                Ty::Value(t) => t,
+                _ => FieldType::Any,
+            })));
+        }
+        match unify(&tys) {
+            Some(elem) => Ty::Value(FieldType::Array(Box::new(elem))),
+            None => Ty::Unknown,
+        }
+    }
+
+    fn check_object_literal(&mut self, fields: &[(String, Expr)], type_name: Option<&str>, expected: Option<&Ty>, span: Span) -> Ty {
+        let alias = type_name.map(str::to_string).or_else(|| match expected {
+            Some(Ty::Value(FieldType::Object(name))) if self.aliases.contains_key(name) => Some(name.clone()),
+            _ => None,
+        });
+        let Some(def) = alias.and_then(|name| self.aliases.get(&name).cloned()) else {
+            for (_, value) in fields {
+                self.check_expr(value, None);
+            }
+            return Ty::Value(FieldType::Object(type_name.unwrap_or("object").to_string()));
+        };
+
+        for (name, value) in fields {
+            match def.fields.iter().find(|f| f.name == *name) {
+                Some(field) => {
+                    let field_ty = Ty::Value(field.ty.clone());
+                    let found = self.check_expr(value, Some(&field_ty));
+                    if !self.accepts(&field_ty, &found) {
+                        self.mismatch(value.span, format!("field `{}` of `{}` is `{}`, found `{}`", name, def.name, field_ty, found));
+                    }
+                }
+                None => {
+                    self.check_expr(value, None);
+                    self.mismatch(value.span, format!("`{}` has no field `{}`", def.name, name));
+                }
+            }
+        }
+        let missing: Vec<String> = def
+            .fields
+            .iter()
+            .filter(|f| !fields.iter().any(|(name, _)| *name == f.name))
+            .map(|f| format!("`{}`", f.name))
+            .collect();
+        if !missing.is_empty() {
+            self.mismatch(span, format!("`{}` literal is missing {}", def.name, missing.join(", ")));
+        }
+        Ty::Value(FieldType::Object(def.name))
+    }
+
+    // === Calls ===
+
+    /// Resolve a call the way codegen does: array and map builtins (unless a user function
+    /// shadows them), the builtins codegen lowers inline, closures in variables, then
+    /// user functions. Anything else (FFI, imports, entity methods) is unknown.
+    fn check_call(&mut self, name: &str, args: &[Expr], expected: Option<&Ty>, span: Span) -> Ty {
+        if let Some((enum_name, variant)) = self.enum_variant(name) {
+            if self.check_arity(name, args, variant.fields.len(), span) {
+                for (arg, field) in args.iter().zip(&variant.fields) {
+                    let field_ty = Ty::Value(field.clone());
+                    let found = self.check_expr(arg, Some(&field_ty));
+                    if !self.accepts(&field_ty, &found) {
+                        self.mismatch(arg.span, format!("`{}` expects `{}`, found `{}`", name, field_ty, found));
+                    }
+                }
+            } else {
+                for arg in args {
+                    self.check_expr(arg, None);
+                }
+            }
+            return Ty::Value(FieldType::Object(enum_name));
+        }
+        if self.imported.contains(name) || (self.imports_all && !name.contains('.') && !self.functions.contains_key(name)) {
+            for arg in args {
+                self.check_expr(arg, None);
+            }
+            return Ty::Unknown;
+        }
+        let user_fn = self.functions.contains_key(name);
+        if !user_fn && matches!(name, "push" | "pop" | "insert" | "remove" | "slice" | "concat") {
+            return self.check_array_builtin(name, args, span);
+        }
+        if !user_fn && matches!(name, "Map" | "get" | "set" | "has" | "delete" | "keys" | "values") {
+            return self.check_map_builtin(name, args, expected, span);
+        }
+        if let Some(ty) = self.check_builtin(name, args, span) {
+            return ty;
+        }
+        if !name.contains('.')
+            && let Some(ty) = self.lookup(name)
+        {
+            return self.check_closure_call(name, &ty, args, span);
+        }
+        if user_fn {
+            return self.check_user_call(name, args, span);
+        }
+        for arg in args {
+            self.check_expr(arg, None);
+        }
+        Ty::Unknown
+    }
+
+    /// `(enum, variant)` when `name` is a qualified variant such as `Shape.Circle`
+    fn enum_variant(&self, name: &str) -> Option<(String, crate::ast::types::EnumVariant)> {
+        let (enum_name, variant) = name.split_once('.')?;
+        let (_, variant) = self.enums.get(enum_name)?.variant(variant)?;
+        Some((enum_name.to_string(), variant.clone()))
+    }
+
+    fn check_arity(&mut self, name: &str, args: &[Expr], expected: usize, span: Span) -> bool {
+        if args.len() == expected {
+            return true;
+        }
+        self.report(CompileError::ArgumentCount {
+            callee: name.to_string(),
+            expected: arguments(expected),
+            found: args.len(),
+            span,
+        });
+        false
+    }
+
+    /// Builtins lowered inline by codegen; `None` when `name` is not one of them
+    fn check_builtin(&mut self, name: &str, args: &[Expr], span: Span) -> Option<Ty> {
+        use FieldType::{Int32 as I, String as S};
+
+        let (params, ret): (Vec<FieldType>, Ty) = match name {
+            "print" => {
+                if args.is_empty() {
+                    self.report(CompileError::ArgumentCount {
+                        callee: name.to_string(),
+                        expected: "at least one argument".to_string(),
+                        found: 0,
+                        span,
+                    });
+                }
+                for arg in args {
+                    self.check_expr(arg, None);
+                }
+                return Some(Ty::Unknown);
+            }
+            "len" => {
+                if self.check_arity(name, args, 1, span) {
+                    let ty = self.check_expr(&args[0], None);
+                    if !matches!(ty, Ty::Unknown | Ty::Value(FieldType::Array(_) | FieldType::Map(_))) {
+                        self.mismatch(args[0].span, format!("`len` expects an array or map, found `{}`", ty));
+                    }
+                }
+                return Some(Ty::int());
+            }
+            "format" => {
+                let Some((template, rest)) = args.split_first() else {
+                    self.report(CompileError::ArgumentCount {
+                        callee: name.to_string(),
+                        expected: "at least 1 argument (format string)".to_string(),
+                        found: 0,
+                        span,
+                    });
+                    return Some(Ty::string());
+                };
+                self.check_args(name, std::slice::from_ref(template), &[S]);
+                for arg in rest {
+                    if let Ty::Func(_) = self.check_expr(arg, None) {
+                        self.mismatch(arg.span, "cannot format a function".to_string());
+                    }
+                }
+                if let ExprKind::StringLiteral(text) = &template.kind {
+                    match format_placeholders(text) {
+                        Ok(n) if n != rest.len() => self.mismatch(
+                            template.span,
+                            format!("format string takes {}, found {}", arguments(n), rest.len()),
+                        ),
+                        Ok(_) => {}
+                        Err(message) => self.mismatch(template.span, message),
+                    }
+                }
+                return Some(Ty::string());
+            }
+            "server.register" => {
+                if self.check_arity(name, args, 2, span) {
+                    self.check_args(name, &args[..1], &[S]);
+                    let handler = self.check_expr(&args[1], None);
+                    if !matches!(handler, Ty::Unknown | Ty::Func(_)) {
+                        self.mismatch(args[1].span, "server.register() expects a function as its second argument".to_string());
+                    }
+                }
+                return Some(Ty::Unknown);
+            }
+            "stack_trace" => {
+                // The argument is a `catch` variable, whatever its type
+                if self.check_arity(name, args, 1, span) {
+                    self.check_expr(&args[0], None);
+                }
+                return Some(Ty::string());
+            }
+            "strlen" => (vec![S], Ty::int()),
+            "int_to_string" | "to_string" => (vec![I], Ty::string()),
+            "readline" => (vec![], Ty::string()),
+            "wpp_str_substr" => (vec![S, I, I], Ty::string()),
+            "wpp_str_index_of" => (vec![S, S], Ty::int()),
+            "wpp_str_replace" => (vec![S, S, S], Ty::string()),
+            "wpp_str_to_upper" | "wpp_str_to_lower" | "wpp_str_trim" => (vec![S], Ty::string()),
+            "http.get" | "http.delete" => (vec![S], Ty::int()),
+            "http.post" | "http.put" | "http.patch" => (vec![S, S], Ty::int()),
+            "http.status" => (vec![I], Ty::int()),
+            "http.body" | "http.headers" => (vec![I], Ty::string()),
+            "server.start" => (vec![I], Ty::Unknown),
+            _ => return None,
+        };
+        if self.check_arity(name, args, params.len(), span) {
+            self.check_args(name, args, &params);
+        } else {
+            for arg in args {
+                self.check_expr(arg, None);
+            }
+        }
+        Some(ret)
+    }
+
+    /// Arguments passed straight to a runtime function, so they must match exactly
+    fn check_args(&mut self, name: &str, args: &[Expr], params: &[FieldType]) {
+        for (i, (arg, param)) in args.iter().zip(params).enumerate() {
+            let found = self.check_expr(arg, None);
+            let ok = match (param, &found) {
+                (_, Ty::Unknown) => true,
+                (FieldType::Int32, found) => found.is_integral(),
+                (param, Ty::Value(found)) => param == found,
+                _ => false,
+            };
+            if !ok {
+                self.mismatch(arg.span, format!("argument {} of `{}` must be `{}`, found `{}`", i + 1, name, param, found));
+            }
+        }
+    }
+
+    fn check_array_builtin(&mut self, name: &str, args: &[Expr], span: Span) -> Ty {
+        let (arity, usage) = match name {
+            "push" => (2, "exactly 2 arguments (array, value)"),
+            "pop" => (1, "exactly 1 argument (array)"),
+            "insert" => (3, "exactly 3 arguments (array, index, value)"),
+            "remove" => (2, "exactly 2 arguments (array, index)"),
+            "slice" => (3, "exactly 3 arguments (array, start, end)"),
+            _ => (2, "exactly 2 arguments (array, array)"),
+        };
+        if args.len() != arity {
+            self.report(CompileError::ArgumentCount {
+                callee: name.to_string(),
+                expected: usage.to_string(),
+                found: args.len(),
+                span,
+            });
+            return Ty::Unknown;
+        }
+
+        let array = self.check_expr(&args[0], None);
+        let elem = match &array {
+            Ty::Value(FieldType::Array(elem)) => Ty::Value((**elem).clone()),
+            Ty::Unknown => Ty::Unknown,
+            other => {
+                self.mismatch(args[0].span, format!("`{}` expects an array, found `{}`", name, other));
+                Ty::Unknown
+            }
+        };
+
+        // Indices, then the value or second array
+        let (indices, value) = match name {
+            "push" => (&args[1..1], args.get(1)),
+            "insert" => (&args[1..2], args.get(2)),
+            "remove" => (&args[1..2], None),
+            "slice" => (&args[1..3], None),
+            "concat" => (&args[1..1], None),
+            _ => (&args[1..], None),
+        };
+        for index in indices {
+            let ty = self.check_expr(index, None);
+            if !(ty == Ty::Unknown || ty.is_integral()) {
+                self.mismatch(index.span, format!("array index must be an integer, found `{}`", ty));
+            }
+        }
+        if let Some(value) = value {
+            let found = self.check_expr(value, Some(&elem));
+            if !self.accepts(&elem, &found) {
+                self.mismatch(value.span, format!("expected an element of type `{}`, found `{}`", elem, found));
+            }
+        }
+        if name == "concat" {
+            let other = self.check_expr(&args[1], Some(&array));
+            if !matches!(other, Ty::Unknown | Ty::Value(FieldType::Array(_))) {
+                self.mismatch(args[1].span, format!("`concat` expects an array, found `{}`", other));
+            } else if !self.accepts(&array, &other) {
+                self.mismatch(args[1].span, format!("cannot concat `{}` onto `{}`", other, array));
+            }
+        }
+
+        match name {
+            "push" | "insert" => Ty::int(),
+            "pop" | "remove" => elem,
+            _ => array,
+        }
+    }
+
+    fn check_map_builtin(&mut self, name: &str, args: &[Expr], expected: Option<&Ty>, span: Span) -> Ty {
+        let arity_ok = match name {
+            "Map" => args.len() <= 1,
+            "get" => args.len() == 2 || args.len() == 3,
+            "set" => args.len() == 3,
+            "has" | "delete" => args.len() == 2,
+            _ => args.len() == 1,
+        };
+        if !arity_ok {
+            let usage = match name {
+                "Map" => "at most 1 argument (an object literal of initial entries)",
+                "get" => "2 or 3 arguments (map, key, optional default)",
+                "set" => "exactly 3 arguments (map, key, value)",
+                "has" | "delete" => "exactly 2 arguments (map, key)",
+                _ => "exactly 1 argument (map)",
+            };
+            self.report(CompileError::ArgumentCount {
+                callee: name.to_string(),
+                expected: usage.to_string(),
+                found: args.len(),
+                span,
+            });
+            return Ty::Unknown;
+        }
+
+        if name == "Map" {
+            let expected_val = match expected {
+                Some(Ty::Value(FieldType::Map(val))) => Some(Ty::Value((**val).clone())),
+                _ => None,
+            };
+            let fields = match args.first().map(|a| &a.kind) {
+                None => &[][..],
+                Some(ExprKind::ObjectLiteral { fields, type_name: None }) => fields.as_slice(),
+                Some(_) => {
+                    self.mismatch(
+                        args[0].span,
+                        "`Map` expects an object literal of initial entries, e.g. `Map({ \"a\": 1 })`".to_string(),
+                    );
+                    return Ty::Unknown;
+                }
+            };
+            let mut tys = Vec::with_capacity(fields.len());
+            for (_, value) in fields {
+                let found = self.check_expr(value, expected_val.as_ref());
+                if let Some(val) = &expected_val
+                    && !self.accepts(val, &found)
+                {
+                    self.mismatch(value.span, format!("expected an element of type `{}`, found `{}`", val, found));
+                }
+                tys.push(found);
+            }
+            return match (expected_val, unify(&tys)) {
+                (Some(Ty::Value(val)), _) | (None, Some(val)) => Ty::Value(FieldType::Map(Box::new(val))),
+                _ => Ty::Unknown,
+            };
+        }
+
+        let map = self.check_expr(&args[0], None);
+        let val = match &map {
+            Ty::Value(FieldType::Map(val)) => Ty::Value((**val).clone()),
+            Ty::Unknown => Ty::Unknown,
+            other => {
+                self.mismatch(args[0].span, format!("`{}` expects a map, found `{}`", name, other));
+                Ty::Unknown
+            }
+        };
+        if let Some(key) = args.get(1) {
+            let found = self.check_expr(key, None);
+            if !self.accepts(&Ty::string(), &found) {
+                self.mismatch(key.span, format!("map keys are `str`, found `{}`", found));
+            }
+        }
+        if let Some(value) = args.get(2) {
+            let found = self.check_expr(value, Some(&val));
+            if !self.accepts(&val, &found) {
+                self.mismatch(value.span, format!("expected an element of type `{}`, found `{}`", val, found));
+            }
+        }
+
+        match (name, val) {
+            ("get", val) => val,
+            ("set", _) => Ty::int(),
+            ("keys", _) => Ty::Value(FieldType::Array(Box::new(FieldType::String))),
+            ("values", Ty::Value(val)) => Ty::Value(FieldType::Array(Box::new(val))),
+            ("values", _) => Ty::Unknown,
+            _ => Ty::bool(),
+        }
+    }
+
+    /// Call through a variable holding a closure (`let add = funcy(x) { ... }; add(1)`)
+    fn check_closure_call(&mut self, name: &str, callee: &Ty, args: &[Expr], span: Span) -> Ty {
+        let arg_tys: Vec<Ty> = args.iter().map(|arg| self.check_expr(arg, None)).collect();
+        let (param_types, return_type) = match callee {
+            Ty::Func(TypeDescriptor::Function { param_types, return_type }) => (param_types, return_type),
+            Ty::Unknown | Ty::Func(_) => return Ty::Unknown,
+            Ty::Value(_) => {
+                self.mismatch(span, format!("`{}` is a `{}`, not a function", name, callee));
+                return Ty::Unknown;
+            }
+        };
+        if self.check_arity(name, args, param_types.len(), span) {
+            for (i, ((arg, found), param)) in args.iter().zip(&arg_tys).zip(param_types).enumerate() {
+                if !self.param_accepts(param, found, arg) {
+                    self.mismatch(
+                        arg.span,
+                        format!("argument {} of `{}` must be `{}`, found `{}`", i + 1, name, describe(param), found),
+                    );
+                }
+            }
+        }
+        self.descriptor_ty(return_type)
+    }
+
+    /// Whether codegen picks among `overloads` when the call runs, because an argument is `any`,
+    /// an interface value, or an integer passed where an overload expects an HTTP status
+    fn dispatched_at_runtime(&self, args: &[Expr], arg_tys: &[Ty], overloads: &[&FnDecl]) -> bool {
+        arg_tys.iter().zip(args).enumerate().any(|(i, (ty, arg))| match ty {
+            Ty::Unknown | Ty::Value(FieldType::Any) => true,
+            Ty::Value(FieldType::Object(name)) => self.interfaces.contains(name),
+            ty => {
+                ty.is_integral()
+                    && int_literal(arg).is_none()
+                    && overloads.iter().any(|decl| {
+                        matches!(decl.sig.param_types[i], TypeDescriptor::HttpStatusLiteral(_) | TypeDescriptor::HttpStatusRange(..))
+                    })
+            }
+        })
+    }
+
+    /// Pick the overload codegen would dispatch to, or report that none accepts the arguments
+    fn check_user_call(&mut self, name: &str, args: &[Expr], span: Span) -> Ty {
+        // A single overload lets its parameter types flow into `[]` and `Map()` arguments
+        let expected: Vec<Option<Ty>> = match self.functions[name].as_slice() {
+            [decl] => decl.sig.param_types.iter().map(|td| Some(self.descriptor_ty(td))).collect(),
+            _ => Vec::new(),
+        };
+        let arg_tys: Vec<Ty> = args
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| self.check_expr(arg, expected.get(i).and_then(Option::as_ref)))
+            .collect();
+
+        let decls = &self.functions[name];
+        let accepting: Vec<&FnDecl> = decls
+            .iter()
+            .filter(|decl| {
+                decl.sig.param_types.len() == args.len()
+                    && decl
+                        .sig
+                        .param_types
+                        .iter()
+                        .zip(args.iter().zip(&arg_tys))
+                        .all(|(param, (arg, ty))| self.param_accepts(param, ty, arg))
+            })
+            .collect();
+        // Exact parameter types first, then the most specific patterns, then the closest entities
+        let rank = |decl: &FnDecl| {
+            let exact = decl
+                .sig
+                .param_types
+                .iter()
+                .zip(&arg_tys)
+                .filter(|(param, ty)| self.descriptor_ty(param) == **ty)
+                .count();
+            let specificity: u32 = decl.sig.param_types.iter().map(TypeDescriptor::specificity).sum();
+            let distance: u32 = decl.sig.param_types.iter().zip(&arg_tys).map(|(param, ty)| self.param_distance(param, ty)).sum();
+            (exact, specificity, std::cmp::Reverse(distance))
+        };
+        let mut ranked: Vec<_> = accepting.iter().map(|decl| (rank(decl), *decl)).collect();
+        ranked.sort_by_key(|(rank, _)| std::cmp::Reverse(*rank));
+        if let Some((best_rank, best)) = ranked.first() {
+            let ret = best.ret.clone();
+            let tied: Vec<String> =
+                ranked.iter().filter(|(rank, _)| rank == best_rank).map(|(_, decl)| describe_signature(&decl.sig)).collect();
+            // Overloads for values only known at runtime are picked when the call runs
+            if tied.len() > 1 && !self.dispatched_at_runtime(args, &arg_tys, &accepting) {
+                self.report(CompileError::AmbiguousCall {
+                    name: name.to_string(),
+                    arg_types: arg_tys.iter().map(Ty::to_string).collect(),
+                    candidates: tied,
+                    span,
+                });
+            }
+            return ret;
+        }
+
+        if let [decl] = decls.as_slice()
+            && decl.sig.param_types.len() != args.len()
+        {
+            let expected = arguments(decl.sig.param_types.len());
+            self.report(CompileError::ArgumentCount { callee: name.to_string(), expected, found: args.len(), span });
+            return Ty::Unknown;
+        }
+        let candidates: Vec<String> = decls.iter().map(|decl| format!("candidate: {}", describe_signature(&decl.sig))).collect();
+        let mut diag = Diagnostic::from(CompileError::NoMatchingOverload {
+            name: name.to_string(),
+            arg_types: arg_tys.iter().map(Ty::to_string).collect(),
+            span,
+        });
+        for candidate in candidates {
+            diag = diag.with_note(candidate);
+        }
+        self.diagnostics.push(diag);
+        Ty::Unknown
+    }
+}
+
+/// Value of an integer literal argument, used to match HTTP status patterns
+fn int_literal(expr: &Expr) -> Option<i64> {
+    match &expr.kind {
+        ExprKind::Literal(n) => Some(i64::from(*n)),
+        ExprKind::TypedLiteral { value, .. } => value.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Element type of an array or map literal, like codegen's `unify_elem_types`
+/// (`None` when an element is unknown)
+fn unify(tys: &[Ty]) -> Option<FieldType> {
+    let tys = tys
+        .iter()
+        .map(|t| match t {
+            Ty::Value(t) => Some(t.clone()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let Some(first) = tys.first() else {
+        return Some(FieldType::Int32);
+    };
+    Some(if tys.iter().all(|t| t == first) {
+        first.clone()
+    } else if tys.iter().all(FieldType::is_numeric) {
+        if tys.iter().any(FieldType::is_float) {
+            FieldType::Float64
+        } else {
+            tys[1..]
+                .iter()
+                .try_fold(first.clone(), |acc, t| FieldType::common_numeric(&acc, t))
+                .unwrap_or(FieldType::Any)
+        }
+    } else {
+        FieldType::Any
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn check(src: &str) -> Vec<String> {
+        let ast = parse(src).expect("test source should parse");
+        check_program(&ast).into_iter().map(|d| d.message).collect()
+    }
+
+    #[test]
+    fn test_well_typed_program_has_no_errors() {
+        let src = r#"
+type Point = { x: i32, y: i32 }
+funcy label(i) -> str { return "item " + int_to_string(i) }
+funcy area(w: f64, h: f64) -> f64 { return w * h }
+funcy main() {
+    let p: Point = { x: 1, y: 2 }
+    let names: str[] = []
+    push(names, label(p.x))
+    let scores: map<i32> = Map()
+    set(scores, label(2), strlen(names[0]))
+    let add = funcy(x) { return x + 1 }
+    print(area(2.0, 3.5), add(p.y), len(scores))
+}
+"#;
+        assert_eq!(check(src), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_annotations_and_assignments() {
+        let errors = check("let x: i32 = \"a\";\nlet y = 1;\ny = \"b\";\nlet p: Nope = 1;");
+        assert_eq!(
+            errors,
+            [
+                "`x` is declared as `i32` but initialized with `str`",
+                "cannot assign `str` to `y` of type `i32`",
+                "unknown type `Nope`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_returns_are_checked_against_the_declared_type() {
+        let errors = check("funcy f() -> i32 { return \"no\" }\nfuncy g() -> string { return 5 }");
+        assert_eq!(
+            errors,
+            ["`f` is declared to return `i32`, found `str`", "`g` is declared to return `str`, found `i32`"]
+        );
+    }
+
+    #[test]
+    fn test_calls_are_resolved_against_signatures() {
+        let src = "funcy g() -> string { return \"s\" }\nfuncy f(x: i32) { return x }\nfuncy h(s: str, n: i32) { return n }\nf(g());\nh(\"a\");\nf(1);";
+        let errors = check(src);
+        assert_eq!(errors, ["no matching overload for `f(str)`", "`h` expects 2 arguments, but 1 was given"]);
+
+        // Unannotated functions return what their body returns
+        let errors = check("funcy name() { return \"n\" }\nfuncy twice(x: i32) { return x * 2 }\ntwice(name());");
+        assert_eq!(errors, ["no matching overload for `twice(str)`"]);
+    }
+
+    #[test]
+    fn test_http_status_dispatch() {
+        let src = "funcy handle(code: 404) { return 1 }\nfuncy handle(code: 500) { return 2 }\nhandle(404);\nhandle(500);\nhandle(201);";
+        assert_eq!(check(src), ["no matching overload for `handle(i32)`"]);
+    }
+
+    #[test]
+    fn test_operators_and_conditions() {
+        let errors = check("let s = \"a\";\nprint(s - 1);\nif (s) { print(1) }\nlet i = 1;\nwhile (i < 2.5) { print(2) }\nprint(s + s, 1 + 2.5, 1 < 2.5);");
+        assert_eq!(
+            errors,
+            [
+                "unsupported operand types for `-`: `str` and `i32`",
+                "condition in `if` must be int or bool, found `str`",
+                "cannot compare `i32` with `f64` without an explicit cast",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_builtins_and_collections() {
+        let src = "print(strlen(5));\nlet xs = [1, 2];\npush(xs, \"a\");\nlet m: map<str> = Map();\nset(m, 1, \"v\");\nlet n = 3;\nprint(n[0]);";
+        assert_eq!(
+            check(src),
+            [
+                "argument 1 of `strlen` must be `str`, found `i32`",
+                "expected an element of type `i32`, found `str`",
+                "map keys are `str`, found `i32`",
+                "cannot index into a value of type `i32`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_format_templates_are_checked() {
+        let src = r#"
+let n = 3
+let f = funcy(x) { return x }
+print(format("{} at {:.2}", n, 1.5), "n is ${n}", "{literal} ${n}")
+print(format("{} {}", n), format("{x}", n), format("a } b"), format(n), format("{}", f))
+"#;
+        assert_eq!(
+            check(src),
+            [
+                "format string takes 2 arguments, found 1",
+                "unsupported placeholder `{x}` in format string; use `{}` or `{:.2}`",
+                "unmatched `}` in format string; write `}}` for a literal brace",
+                "argument 1 of `format` must be `str`, found `i32`",
+                "cannot format a function",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_object_literals_follow_their_alias() {
+        let src = "type Point = { x: i32, y: i32 }\nlet p: Point = { x: \"a\", z: 2 };";
+        assert_eq!(
+            check(src),
+            ["field `x` of `Point` is `i32`, found `str`", "`Point` has no field `z`", "`Point` literal is missing `y`"]
+        );
+    }
+
+    #[test]
+    fn test_closure_calls_are_checked() {
+        let src = "let add = funcy(x) { return x + 1 };\nprint(add(1, 2));\nlet n = 5;\nprint(n(1));";
+        assert_eq!(check(src), ["`add` expects 1 argument, but 2 were given", "`n` is a `i32`, not a function"]);
+    }
+
+    #[test]
+    fn test_enums_and_match_are_checked() {
+        let src = r#"
+enum Shape { Circle(f64), Rect(f64, f64), Empty }
+funcy area(s: Shape) -> f64 { return match s { Circle(r) => r * r, Rect(w, h) => w * h, Empty => 0 } }
+let s = Shape.Circle("big")
+let t: i32 = Shape.Empty
+let label: str = match s { Circle(r) => r, _ => "other" }
+let n = match area(s) { 1 => "one", Empty => "none", _ => "many" }
+print(match true { true => 1 })
+"#;
+        assert_eq!(
+            check(src),
+            [
+                "`Shape.Circle` expects `f64`, found `str`",
+                "`t` is declared as `i32` but initialized with `Shape`",
+                "cannot match a value of type `f64` against variant `Empty`",
+                "non-exhaustive `match`: `false` not covered",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_constructors_follow_the_nearest_init() {
+        let src = r#"
+entity Animal {
+    name = "?"
+    funcy init(name: str) { me.name = name }
+}
+entity Dog alters Animal { age = 0 }
+entity Rock { size = 1 }
+let a = new(Dog, "Rex")
+let b = new(Dog, 3)
+let c = new Dog()
+let r = new(Rock, 1)
+"#;
+        assert_eq!(
+            check(src),
+            [
+                "argument 1 of `Animal.init` must be `str`, found `i32`",
+                "`Animal.init` expects 1 argument, but 0 were given",
+                "`Rock` expects 0 arguments, but 1 was given",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_interface_parameters_accept_implementers() {
+        let src = r#"
+interface Speaker { speak() }
+entity Animal implements Speaker { funcy speak() { print(1) } }
+entity Dog alters Animal {}
+entity Rock {}
+funcy greet(s: Speaker) { s.speak() }
+greet(new(Dog))
+greet(new(Rock))
+let q: Sparker = new(Rock)
+"#;
+        assert_eq!(check(src), ["no matching overload for `greet(Rock)`", "unknown type `Sparker`"]);
+    }
+
+    #[test]
+    fn test_interface_values_reach_entity_overloads() {
+        let src = r#"
+interface Speaker { speak() }
+entity Dog implements Speaker { funcy speak() { print(1) } }
+entity Rock {}
+funcy describe(d: Dog) { print("dog") }
+funcy describe(r: Rock) { print("rock") }
+let s: Speaker = new(Dog)
+describe(s)
+"#;
+        // Whether `s` holds a Dog is only known at runtime, and only Dog implements Speaker
+        assert!(check(src).is_empty(), "{:?}", check(src));
+    }
+
+    #[test]
+    fn test_overloads_follow_alters_and_report_ambiguity() {
+        let src = r#"
+entity Animal {}
+entity Dog alters Animal {}
+entity Puppy alters Dog {}
+funcy describe(a: Animal) -> str { return "animal" }
+funcy describe(d: Dog) -> i32 { return 1 }
+let n: i32 = describe(new(Puppy))
+funcy pair(a: Animal, d: Dog) { print(1) }
+funcy pair(d: Dog, a: Animal) { print(2) }
+pair(new(Dog), new(Puppy))
+pair(new(Puppy), new(Puppy))
+"#;
+        let errors = check_program(&parse(src).unwrap());
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert_eq!(errors[0].message, "call to `pair(Puppy, Puppy)` is ambiguous");
+        assert_eq!(errors[0].notes, ["candidate: pair(Animal, Dog)", "candidate: pair(Dog, Animal)"]);
+    }
+
+    #[test]
+    fn test_numeric_widening_and_casts() {
+        let src = r#"
+funcy half(n: u32) -> u32 { return n / 2 }
+let b: u8 = 200
+let w: i64 = b + 1
+let narrow: u8 = w
+let big: u8 = 256
+let lo: i8 = -128i8
+let under: i8 = -129i8
+let u: u64 = 18446744073709551615
+print(u + w, half(b), half(7), half(-1))
+let f: f32 = 1.5
+let d: f64 = f * 2
+let back: f32 = d
+print(d as u8, 300 as u8, "a" as i32, b as str)
+"#;
+        assert_eq!(
+            check(src),
+            [
+                "`narrow` is declared as `u8` but initialized with `i64`",
+                "integer literal `256` does not fit in `u8`",
+                "integer literal `-129` does not fit in `i8`",
+                "`u64` and `i64` have no common type; convert one with `as`",
+                "integer literal `-1` does not fit in `u32`",
+                "`back` is declared as `f32` but initialized with `f64`",
+                "cannot cast `str` to `i32`",
+                "cannot cast to `str`: `as` converts between numbers and bools",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bitwise_operators_need_integers() {
+        let src = r#"
+let f = 1.5
+let b: u8 = 6
+let mask: u8 = b & 3 | ~b ^ b << 1
+let r: f64 = f % 2
+print(f & 1, ~f, f << 2, true | false, !b)
+b += 2.5
+let shifted: u8 = b >> 1
+"#;
+        assert_eq!(
+            check(src),
+            [
+                "unsupported operand types for `&`: `f64` and `i32`",
+                "unsupported operand type for `~`: `f64`",
+                "unsupported operand types for `<<`: `f64` and `i32`",
+                "cannot assign `f64` to `b` of type `u8`",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_calls_are_not_reported() {
+        assert_eq!(check("let r = json_parse(\"{}\");\nprint(r + 1, useThreadState(0));"), Vec::<String>::new());
+    }
+}
@@ -3,7 +3,7 @@ use inkwell::{
     context::Context,
     execution_engine::ExecutionEngine,
     module::{self, Linkage, Module},
-    types::BasicTypeEnum,
+    types::{AnyType, BasicTypeEnum},
     values::{AnyValue, BasicMetadataValueEnum, BasicValueEnum, CallSiteValue, FunctionValue, IntValue, PointerValue},
     AddressSpace,
     OptimizationLevel,
@@ -17,8 +17,10 @@ use inkwell::values::BasicValue;
 use libc::malloc;
 
 
-use crate::ast::{node::{EntityMember, EntityNode}, Expr, Node};
-use crate::ast::types::TypeDescriptor;
+use crate::ast::{node::{EntityMember, EntityNode, InterfaceNode}, CatchClause, Expr, ExprKind, MatchArm, Node, NodeKind, Span};
+use crate::ast::pattern::{self, Pattern};
+use crate::ast::types::{EnumDefinition, FieldType, Number, TypeDescriptor};
+use crate::diagnostics::{CompileError, CompileResult};
 use crate::runtime;
 use std::mem;
 use std::ffi::c_void;
@@ -33,7 +35,15 @@ pub struct OopsieEntity<'ctx> {
     pub base: Option<String>,
     pub struct_type: inkwell::types::StructType<'ctx>,
     pub fields: Vec<(String, inkwell::types::BasicTypeEnum<'ctx>)>,
+    /// W++ type and declared default of each field, parallel to `fields`
+    pub defaults: Vec<(FieldType, Expr)>,
     pub methods: HashMap<String, FunctionValue<'ctx>>,
+    /// Method names in vtable order; a base entity's slots come first, so an override keeps its index
+    pub slots: Vec<String>,
+    /// `[N x i8*]` of the implementation behind each slot; every instance points at it from field 0
+    pub vtable: inkwell::values::GlobalValue<'ctx>,
+    /// Interfaces named in `implements`; base entities' interfaces are not repeated
+    pub interfaces: Vec<String>,
 }
 
 #[derive(Hash, Eq, PartialEq, Clone, Debug)]
@@ -43,6 +53,19 @@ pub struct FunctionSignature {
     pub return_type: crate::ast::types::TypeDescriptor,
 }
 
+/// A call argument whose matching overload can only be told at runtime (see `compile_dispatch_call`)
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum DynamicArg {
+    /// An `any` value, whose box carries a type tag
+    Boxed,
+    /// An interface- or entity-typed instance, whose vtable names its entity
+    Instance,
+    /// A pointer whose W++ type is unknown: an instance (told by its vtable) or a string
+    Pointer,
+    /// An integer compared against HTTP status patterns
+    Status,
+}
+
 #[derive(Clone)]
 pub struct VarInfo<'ctx> {
     pub ptr: PointerValue<'ctx>,
@@ -52,6 +75,23 @@ pub struct VarInfo<'ctx> {
     pub entity_type: Option<String>, // 👈 NEW FIELD for entity dispatch
     pub object_type_name: Option<String>, // 👈 NEW FIELD for object type dispatch
     pub function_signature: Option<FunctionTypeSignature>, // 👈 NEW FIELD for function type dispatch
+    /// W++-level type for heap values whose LLVM type is just `i8*` (arrays, objects)
+    pub field_type: Option<FieldType>,
+}
+
+/// How the function being compiled handles exceptions (see `Codegen::unwind_target`)
+#[derive(Clone, Copy, Default)]
+struct ExceptionScope<'ctx> {
+    /// Catch dispatcher of the innermost enclosing `try`
+    handler: Option<inkwell::basic_block::BasicBlock<'ctx>>,
+    /// Number of `defer` blocks open around the handler; those inside it run before it
+    handler_depth: usize,
+    /// Block that returns with the exception still pending, once built
+    unwind: Option<inkwell::basic_block::BasicBlock<'ctx>>,
+    /// Whether the function pushed a stack-trace frame that its returns must pop
+    has_frame: bool,
+    /// The program's entry point, where an uncaught exception is reported
+    is_entry: bool,
 }
 
 /// Function type signature for tracking function-typed values
@@ -70,15 +110,33 @@ pub struct Codegen <'ctx> {
     /// Symbol table: name -> (alloca ptr, type of the slot)
     pub vars: HashMap<String, VarInfo<'ctx>>,
         pub globals: HashMap<String, VarInfo<'ctx>>, // ✅ persistent globals
-        pub loop_stack: Vec<(inkwell::basic_block::BasicBlock<'ctx>, inkwell::basic_block::BasicBlock<'ctx>)>,
-    pub switch_stack: Vec<inkwell::basic_block::BasicBlock<'ctx>>,
-    exception_flag: Option<PointerValue<'ctx>>,
-    exception_value_i32: Option<PointerValue<'ctx>>,
-    exception_value_str: Option<PointerValue<'ctx>>,
+        /// (continue target, break target, `defers` depth) of each enclosing loop
+        pub loop_stack: Vec<(inkwell::basic_block::BasicBlock<'ctx>, inkwell::basic_block::BasicBlock<'ctx>, usize)>,
+    /// (end block, `defers` depth) of each enclosing `switch`
+    pub switch_stack: Vec<(inkwell::basic_block::BasicBlock<'ctx>, usize)>,
+    /// Where exceptions go in the function being compiled
+    exceptions: ExceptionScope<'ctx>,
+    /// Expressions `defer`red in each open block of the function being compiled, innermost last
+    defers: Vec<Vec<Expr>>,
+    /// First error compiling deferred expressions for an exception path, reported by the
+    /// enclosing block (`unwind_target` cannot fail)
+    defer_error: Option<CompileError>,
+    /// Byte offset of each source line's start, for stack traces (empty without a source)
+    line_starts: Vec<usize>,
+    /// Anonymous lambdas compiled so far (used to name their LLVM functions)
+    lambda_count: usize,
+    /// Signature of each compiled lambda, keyed by the span of its `funcy` expression
+    lambda_signatures: HashMap<Span, FunctionTypeSignature>,
+    /// Captures of the lambda `compile_funcy` is about to compile (empty for other functions)
+    pending_captures: Vec<(String, VarInfo<'ctx>)>,
     pub functions: HashMap<FunctionSignature, FunctionValue<'ctx>>,
     pub reverse_func_index: HashMap<String, Vec<FunctionSignature>>,
     pub entities: HashMap<String, OopsieEntity<'ctx>>,
     pub type_aliases: HashMap<String, crate::ast::types::ObjectTypeDefinition>, // ✅ NEW: Type alias registry
+    /// Enum declarations, by name
+    pub enums: HashMap<String, EnumDefinition>,
+    /// Interface declarations, by name
+    pub interfaces: HashMap<String, InterfaceNode>,
     pub wms: Option<Arc<Mutex<ModuleSystem>>>,
     pub resolver: Option<Arc<Mutex<ExportResolver>>>,
 }
@@ -113,6 +171,9 @@ impl<'ctx> Codegen<'ctx> {
         // void wpp_print_object(void* ptr)
         let obj_ty = void_ty.fn_type(&[i8_ptr_ty.into()], false);
         self.module.add_function("wpp_print_object", obj_ty, None);
+
+        // void wpp_print_enum(void* ptr)
+        self.module.add_function("wpp_print_enum", obj_ty, None);
         // Add wpp_str_concat declaration if missing
 let ptr_ty = self.context.i8_type().ptr_type(AddressSpace::default());
 if self.module.get_function("wpp_str_concat").is_none() {
@@ -125,6 +186,67 @@ if self.module.get_function("wpp_readline").is_none() {
     let fn_ty = i8_ptr_ty.fn_type(&[], false);
     self.module.add_function("wpp_readline", fn_ty, None);
 }
+// === Array bounds and missing map key errors ===
+if self.module.get_function("wpp_index_error").is_none() {
+    let fn_ty = i8_ptr_ty.fn_type(&[i32_ty.into(), i32_ty.into()], false);
+    self.module.add_function("wpp_index_error", fn_ty, None);
+}
+if self.module.get_function("wpp_key_error").is_none() {
+    let fn_ty = i8_ptr_ty.fn_type(&[i8_ptr_ty.into()], false);
+    self.module.add_function("wpp_key_error", fn_ty, None);
+}
+// === Exceptions and stack traces (runtime/exception.rs) ===
+let exception_fns = [
+    ("wpp_throw", void_ty.fn_type(&[i8_ptr_ty.into()], false)),
+    ("wpp_throw_message", void_ty.fn_type(&[i8_ptr_ty.into()], false)),
+    ("wpp_exception_pending", i32_ty.fn_type(&[], false)),
+    ("wpp_exception_value", i8_ptr_ty.fn_type(&[], false)),
+    ("wpp_exception_catch", i8_ptr_ty.fn_type(&[], false)),
+    ("wpp_exception_save", i8_ptr_ty.fn_type(&[], false)),
+    ("wpp_exception_restore", void_ty.fn_type(&[i8_ptr_ty.into()], false)),
+    ("wpp_exception_uncaught", void_ty.fn_type(&[], false)),
+    ("wpp_frame_push", void_ty.fn_type(&[i8_ptr_ty.into()], false)),
+    ("wpp_frame_pop", void_ty.fn_type(&[], false)),
+    ("wpp_frame_line", void_ty.fn_type(&[i32_ty.into()], false)),
+];
+for (name, fn_ty) in exception_fns {
+    if self.module.get_function(name).is_none() {
+        self.module.add_function(name, fn_ty, None);
+    }
+}
+// === Heap values: strings, growable arrays, maps and closures (runtime/gc.rs, string.rs, vector.rs, map.rs, closure.rs) ===
+let i64_ty = self.context.i64_type();
+let collection_fns = [
+    ("wpp_gc_alloc", i8_ptr_ty.fn_type(&[i64_ty.into()], false)),
+    ("wpp_gc_is_data", i32_ty.fn_type(&[i8_ptr_ty.into()], false)),
+    ("wpp_str_len", i32_ty.fn_type(&[i8_ptr_ty.into()], false)),
+    ("wpp_str_cmp", i32_ty.fn_type(&[i8_ptr_ty.into(), i8_ptr_ty.into()], false)),
+    ("wpp_str_release", void_ty.fn_type(&[i8_ptr_ty.into()], false)),
+    ("wpp_format", i8_ptr_ty.fn_type(&[i8_ptr_ty.into(), i32_ty.into(), i8_ptr_ty.into()], false)),
+    ("wpp_vec_new", i8_ptr_ty.fn_type(&[i32_ty.into(), i32_ty.into()], false)),
+    ("wpp_vec_push", i32_ty.fn_type(&[i8_ptr_ty.into(), i64_ty.into()], false)),
+    ("wpp_vec_pop", i64_ty.fn_type(&[i8_ptr_ty.into()], false)),
+    ("wpp_vec_insert", void_ty.fn_type(&[i8_ptr_ty.into(), i32_ty.into(), i64_ty.into()], false)),
+    ("wpp_vec_remove", i64_ty.fn_type(&[i8_ptr_ty.into(), i32_ty.into()], false)),
+    ("wpp_vec_slice", i8_ptr_ty.fn_type(&[i8_ptr_ty.into(), i32_ty.into(), i32_ty.into()], false)),
+    ("wpp_vec_concat", i8_ptr_ty.fn_type(&[i8_ptr_ty.into(), i8_ptr_ty.into()], false)),
+    ("wpp_map_new", i8_ptr_ty.fn_type(&[i32_ty.into()], false)),
+    ("wpp_map_set", i32_ty.fn_type(&[i8_ptr_ty.into(), i8_ptr_ty.into(), i64_ty.into()], false)),
+    ("wpp_map_find", i32_ty.fn_type(&[i8_ptr_ty.into(), i8_ptr_ty.into()], false)),
+    ("wpp_map_value_at", i64_ty.fn_type(&[i8_ptr_ty.into(), i32_ty.into()], false)),
+    ("wpp_map_key_at", i8_ptr_ty.fn_type(&[i8_ptr_ty.into(), i32_ty.into()], false)),
+    ("wpp_map_delete", i32_ty.fn_type(&[i8_ptr_ty.into(), i8_ptr_ty.into()], false)),
+    ("wpp_map_keys", i8_ptr_ty.fn_type(&[i8_ptr_ty.into()], false)),
+    ("wpp_map_values", i8_ptr_ty.fn_type(&[i8_ptr_ty.into()], false)),
+    ("wpp_closure_new", i8_ptr_ty.fn_type(&[i8_ptr_ty.into(), i8_ptr_ty.into()], false)),
+    ("wpp_thread_spawn_closure", i8_ptr_ty.fn_type(&[i8_ptr_ty.into()], false)),
+    ("wpp_register_closure_endpoint", void_ty.fn_type(&[i8_ptr_ty.into(), i8_ptr_ty.into()], false)),
+];
+for (name, fn_ty) in collection_fns {
+    if self.module.get_function(name).is_none() {
+        self.module.add_function(name, fn_ty, None);
+    }
+}
 
 
     }
@@ -137,16 +259,6 @@ impl<'ctx> Codegen<'ctx> {
     let builder = context.create_builder();
     let i32_type = context.i32_type();
 
-    // ✅ Create global vars for exception state
-    let flag_global = module.add_global(context.bool_type(), None, "_wpp_exc_flag");
-    flag_global.set_initializer(&context.bool_type().const_int(0, false));
-
-    let exc_i32_global = module.add_global(context.i32_type(), None, "_wpp_exc_i32");
-    exc_i32_global.set_initializer(&context.i32_type().const_int(0, false));
-
-    let exc_str_global = module.add_global(context.i8_type().ptr_type(AddressSpace::default()), None, "_wpp_exc_str");
-    exc_str_global.set_initializer(&context.i8_type().ptr_type(AddressSpace::default()).const_null());
-
     // ✅ Initialize Codegen struct
     let mut codegen = Self {
         context,
@@ -157,15 +269,21 @@ impl<'ctx> Codegen<'ctx> {
         globals: HashMap::new(),
         loop_stack: Vec::new(),
         switch_stack: Vec::new(),
-        exception_flag: Some(flag_global.as_pointer_value()),
-        exception_value_i32: Some(exc_i32_global.as_pointer_value()),
-        exception_value_str: Some(exc_str_global.as_pointer_value()),
+        defers: Vec::new(),
+        defer_error: None,
+        exceptions: ExceptionScope::default(),
+        line_starts: Vec::new(),
+        lambda_count: 0,
+        lambda_signatures: HashMap::new(),
+        pending_captures: Vec::new(),
 
         // 🧩 Multiple dispatch maps
         functions: HashMap::new(),
         reverse_func_index: HashMap::new(),
         entities: HashMap::new(),
         type_aliases: HashMap::new(), // ✅ NEW: Empty type alias registry
+        enums: HashMap::new(),
+        interfaces: HashMap::new(),
         wms: None, // Only set by main CLI, not by submodule compilation
         resolver: None, // Only set by main CLI, not by submodule compilation
     };
@@ -470,25 +588,34 @@ pub fn init_mutex_support(&self) {
     }
 
 
-    pub fn create_engine(&self) -> ExecutionEngine<'ctx> {
+    pub fn create_engine(&self) -> CompileResult<ExecutionEngine<'ctx>> {
         self.module
             .create_jit_execution_engine(OptimizationLevel::None)
-            .expect("Failed to create JIT engine")
+            .map_err(|e| CompileError::Backend(format!("could not create the JIT engine: {}", e)))
     }
 
     /// Compile an expression to a BasicValue (either i32 or i8* for now).
-    pub fn compile_expr(&mut self, expr: &Expr) -> BasicValueEnum<'ctx> {
-    match expr {
+    pub fn compile_expr(&mut self, expr: &Expr) -> CompileResult<BasicValueEnum<'ctx>> {
+    // Stack traces name the line of each call in progress, and of a division that may throw
+    let may_throw = match &expr.kind {
+        ExprKind::Call { .. } | ExprKind::NewInstance { .. } | ExprKind::Throw { .. } => true,
+        ExprKind::BinaryOp { op, .. } => op == "/" || op == "%",
+        _ => false,
+    };
+    if may_throw {
+        self.mark_line(expr.span)?;
+    }
+    Ok(match &expr.kind {
         // === Integer literal ===
-        Expr::Literal(value) => self.i32_type.const_int(*value as u64, false).into(),
+        ExprKind::Literal(value) => self.i32_type.const_int(*value as u64, false).into(),
 
-     Expr::StringLiteral(s) => {
-    use inkwell::values::BasicValueEnum;
-
-    // ✅ Create null-terminated bytes
-    let cstring = std::ffi::CString::new(s.as_str()).unwrap();
-    let bytes = cstring.as_bytes_with_nul();
+     ExprKind::StringLiteral(s) => {
+    // ✅ Header (see runtime/string.rs) followed by the null-terminated bytes
+    let bytes: Vec<u8> = s.bytes().chain(std::iter::once(0)).collect();
+    let i32_ty = self.context.i32_type();
     let array_type = self.context.i8_type().array_type(bytes.len() as u32);
+    let header_types = [i32_ty.into(); 6];
+    let literal_type = self.context.struct_type(&[&header_types[..], &[array_type.into()]].concat(), false);
 
     // ✅ Unique global name
     static mut STRING_ID: usize = 0;
@@ -498,32 +625,48 @@ pub fn init_mutex_support(&self) {
         format!("strlit_{}", id)
     };
 
-    // ✅ Create global constant
-    let global = self.module.add_global(array_type, None, &name);
-    let init = self.context.i8_type().const_array(
-        &bytes
-            .iter()
-            .map(|&b| self.context.i8_type().const_int(b as u64, false))
-            .collect::<Vec<_>>(),
+    // ✅ Create global constant with a static refcount so it is never freed
+    let header = [
+        runtime::STATIC_REFCOUNT as u64,
+        (bytes.len() - 1) as u64,
+        (bytes.len() - 1) as u64,
+        s.chars().count() as u64,
+        0,
+        runtime::WPP_STRING_MAGIC as u64,
+    ];
+    let mut fields: Vec<BasicValueEnum> = header.iter().map(|&v| i32_ty.const_int(v, false).into()).collect();
+    fields.push(
+        self.context
+            .i8_type()
+            .const_array(
+                &bytes
+                    .iter()
+                    .map(|&b| self.context.i8_type().const_int(b as u64, false))
+                    .collect::<Vec<_>>(),
+            )
+            .into(),
     );
-    global.set_initializer(&init);
+    let global = self.module.add_global(literal_type, None, &name);
+    global.set_initializer(&literal_type.const_named_struct(&fields));
     global.set_constant(true);
     global.set_linkage(inkwell::module::Linkage::Private);
 
     // ✅ Get a pointer to the first byte (i8*)
-    let zero = self.context.i32_type().const_zero();
+    let zero = i32_ty.const_zero();
+    self.register_string_literal(unsafe {
+        global.as_pointer_value().const_in_bounds_gep(literal_type, &[zero, i32_ty.const_int(6, false), zero])
+    });
     let ptr = unsafe {
     self.builder
         .build_in_bounds_gep(
-            array_type,                  // ✅ pointee type
-            global.as_pointer_value(),   // ✅ pointer to [N x i8]
-            &[zero, zero],               // ✅ indices [0,0]
+            literal_type,                     // ✅ pointee type
+            global.as_pointer_value(),        // ✅ pointer to { header, [N x i8] }
+            &[zero, i32_ty.const_int(6, false), zero], // ✅ first byte after the header
             "str_gep",
         )
         .expect("Failed to build GEP for string literal")
 };
 
-
     // ✅ Return as a BasicValueEnum (i8*)
     ptr.as_basic_value_enum()
 }
@@ -532,8 +675,18 @@ pub fn init_mutex_support(&self) {
 
 
 
+        // === Unit enum variant (`Shape.Empty`) ===
+        ExprKind::Variable(name) if self.enum_constructor(name).is_some() => {
+            let (enum_name, index) = self.enum_constructor(name).unwrap();
+            self.compile_enum_value(&enum_name, index, &[], expr.span)?.into()
+        }
+        // === Entity field (`d.age`, `me.age`) ===
+        ExprKind::Variable(name) if self.entity_field(name).is_some() => {
+            let (field_ptr, ty) = self.entity_field_ptr(name).unwrap();
+            self.load_slot(field_ptr, &ty)
+        }
         // === Variable lookup ===
-       Expr::Variable(name) => {
+       ExprKind::Variable(name) => {
     // 🧭 Try local or global variable first
     if let Some(var) = self.vars.get(name).or_else(|| self.globals.get(name)) {
         let val = self
@@ -548,93 +701,124 @@ pub fn init_mutex_support(&self) {
                     .builder
                     .build_call(get_fn, &[val.into()], "call_thread_state_get")
                     .unwrap();
-                return call.try_as_basic_value().left().unwrap();
+                return Ok(call.try_as_basic_value().left().unwrap());
             }
         }
 
         // Normal load return
-        return val.into();
+        return Ok(val.into());
     }
 
     // 🌐 Fallback: treat as function reference (for useThread, server.register, etc.)
     if let Some(sigs) = self.reverse_func_index.get(name) {
         if let Some(first_sig) = sigs.first() {
-            if let Some(func) = self.functions.get(first_sig) {
-                let fn_ptr: PointerValue<'_> = func.as_global_value().as_pointer_value();
-                return fn_ptr.as_basic_value_enum();
+            if let Some(func) = self.functions.get(first_sig).copied() {
+                return Ok(self.make_closure(func, None)?.as_basic_value_enum());
             }
         }
     }
 
-    panic!("Unknown variable or function: {}", name);
+    return Err(CompileError::UnknownVariable { name: name.clone(), span: expr.span });
 }
 
 
 
 
-Expr::TypedLiteral { value, ty } => {
-    match ty.as_str() {
-        "i8"  => self.context.i8_type().const_int(value.parse::<i64>().unwrap() as u64, false).into(),
-        "i32" => self.context.i32_type().const_int(value.parse::<i64>().unwrap() as u64, false).into(),
-        "i64" => self.context.i64_type().const_int(value.parse::<i64>().unwrap() as u64, false).into(),
-        "u64" => self.context.i64_type().const_int(value.parse::<u64>().unwrap(), false).into(),
-        "f64" => self.context.f64_type().const_float(value.parse::<f64>().unwrap()).into(),
-        _ => panic!("❌ Unknown literal type: {}", ty),
+ExprKind::TypedLiteral { value, ty } => {
+    let field_ty = FieldType::from_string(ty);
+    if !field_ty.is_numeric() {
+        return Err(CompileError::unsupported(expr.span, format!("unknown literal type `{}`", ty)));
     }
+    let number = match value.parse::<i128>() {
+        Ok(n) => Number::Int(n),
+        Err(_) => Number::Float(value.parse::<f64>().map_err(|_| CompileError::unsupported(expr.span, format!("invalid number `{}`", value)))?),
+    };
+    self.number_const(number, &field_ty, expr.span)?
 }
 
 
         // === Binary operation ===
-        Expr::BinaryOp { left, op, right } => {
+        ExprKind::BinaryOp { left, op, right } => {
     if op == "=" {
     wpp_debug!("🧩 Detected assignment expression!");
 
-    if let Expr::Variable(var_name) = left.as_ref() {
+    if let ExprKind::Variable(field) = &left.kind
+        && let Some((_, _, ty)) = self.entity_field(field)
+    {
+        // ✏️ Field assignment: me.age = age
+        let (val, from) = self.compile_expected(right.as_ref(), &ty)?;
+        let val = self.coerce_to_slot(val, &from, &ty, right.span)?;
+        let (field_ptr, _) = self.entity_field_ptr(field).unwrap();
+        self.builder.build_store(field_ptr, val).unwrap();
+        return Ok(val);
+    }
+    if let ExprKind::Variable(var_name) = &left.kind {
         wpp_debug!("➡️ Assigning to variable: {}", var_name);
-        let rhs_val = self.compile_expr(right.as_ref());
+        let var_field_ty = self
+            .vars
+            .get(var_name)
+            .or_else(|| self.globals.get(var_name))
+            .and_then(|v| v.field_type.clone());
+        let rhs_val = match &var_field_ty {
+            Some(expected @ (FieldType::Array(_) | FieldType::Map(_))) => {
+                let (val, ty) = self.compile_expected(right.as_ref(), expected)?;
+                if ty != *expected {
+                    return Err(CompileError::type_mismatch(
+                        right.span,
+                        format!("cannot assign `{}` to `{}` of type `{}`", ty, var_name, expected),
+                    ));
+                }
+                val
+            }
+            // Numbers convert to the variable's type, so `b = b + 1` keeps a `u8` a `u8`
+            Some(expected) if expected.is_numeric() => {
+                let (val, ty) = self.compile_expected(right.as_ref(), expected)?;
+                self.convert_numeric(val, &ty, expected)
+            }
+            _ => self.compile_expr(right.as_ref())?,
+        };
 
         // Lookup variable info (either local or global)
         if let Some(var) = self.vars.get(var_name).or_else(|| self.globals.get(var_name)) {
             wpp_debug!("🔍 Found variable {} (is_const = {})", var_name, var.is_const);
             if var.is_const {
-                panic!("❌ Cannot assign to constant variable '{}'", var_name);
+                return Err(CompileError::AssignToConst { name: var_name.clone(), span: left.span });
             }
 
             let var_ty = var.ty;
 
-            // ✅ Special case: Thread state pointer
-            if let BasicTypeEnum::PointerType(_) = var_ty {
+            // ✅ Special case: Thread state pointer (arrays and objects are plain pointers)
+            if var.field_type.is_none() && var_ty.is_pointer_type() {
                 println!("🧵 Detected thread state pointer assignment for {}", var_name);
 
-                if let Some(set_fn) = self.module.get_function("wpp_thread_state_set") {
-                    // Load pointer value (this is the actual thread-state handle)
-                    let ptr_val = self
-                        .builder
-                        .build_load(var_ty, var.ptr, "load_thread_ptr")
-                        .unwrap();
+                let set_fn = self.runtime_fn("wpp_thread_state_set")?;
+                // Load pointer value (this is the actual thread-state handle)
+                let ptr_val = self
+                    .builder
+                    .build_load(var_ty, var.ptr, "load_thread_ptr")
+                    .unwrap();
 
-                    // Ensure RHS is integer (thread state stores i32s)
-                    let rhs_int = match rhs_val {
-                        BasicValueEnum::IntValue(iv) => iv,
-                        _ => panic!(
-                            "❌ Thread state set expected i32, got {:?}",
-                            rhs_val.get_type()
-                        ),
-                    };
+                // Ensure RHS is integer (thread state stores i32s)
+                let rhs_int = match rhs_val {
+                    BasicValueEnum::IntValue(iv) => iv,
+                    _ => {
+                        return Err(CompileError::type_mismatch(
+                            right.span,
+                            format!("thread state can only hold i32 values, found {}", rhs_val.get_type().print_to_string()),
+                        ))
+                    }
+                };
 
-                    // Emit: call void @wpp_thread_state_set(ptr, i32)
-                    self.builder
-                        .build_call(
-                            set_fn,
-                            &[ptr_val.into(), rhs_int.into()],
-                            "call_thread_state_set",
-                        )
-                        .unwrap();
+                // Emit: call void @wpp_thread_state_set(ptr, i32)
+                self.builder
+                    .build_call(
+                        set_fn,
+                        &[ptr_val.into(), rhs_int.into()],
+                        "call_thread_state_set",
+                    )
+                    .unwrap();
 
-                    return self.i32_type.const_int(0, false).into();
-                } else {
-                    panic!("❌ Missing runtime function: wpp_thread_state_set");
-                }
+                return Ok(self.i32_type.const_int(0, false).into());
             }
 
             // ✅ Normal value assignment (non-thread variables)
@@ -681,20 +865,30 @@ Expr::TypedLiteral { value, ty } => {
                 // Pointer → Pointer
                 (BasicValueEnum::PointerValue(pv), BasicTypeEnum::PointerType(_)) => pv.into(),
 
-                (val, _) => panic!(
-                    "❌ Type mismatch assigning {:?} to {:?}",
-                    val.get_type(),
-                    var_ty
-                ),
+                (val, _) => {
+                    return Err(CompileError::type_mismatch(
+                        expr.span,
+                        format!(
+                            "cannot assign a value of type {} to `{}` of type {}",
+                            val.get_type().print_to_string(),
+                            var_name,
+                            var_ty.print_to_string()
+                        ),
+                    ))
+                }
             };
 
             self.builder.build_store(var.ptr, casted_val).unwrap();
-            return self.i32_type.const_int(0, false).into();
+            // The assignment evaluates to the stored value, which `x++` builds on
+            return Ok(casted_val);
         } else {
-            panic!("Unknown variable in assignment: {}", var_name);
+            return Err(CompileError::UnknownVariable { name: var_name.clone(), span: left.span });
         }
+    } else if let ExprKind::Index { target, index } = &left.kind {
+        // ✏️ Element assignment: a[i] = v (bounds-checked)
+        return self.compile_index_store(target, index, right);
     } else {
-        panic!("Left-hand side of assignment must be a variable");
+        return Err(CompileError::InvalidAssignTarget { span: left.span });
     }
 }
 
@@ -703,8 +897,7 @@ Expr::TypedLiteral { value, ty } => {
 
     // === Arithmetic and comparison ===
     // === Arithmetic and comparison ===
-let left_raw = self.compile_expr(left.as_ref());
-let right_raw = self.compile_expr(right.as_ref());
+let (left_raw, right_raw, unsigned) = self.compile_operands(left, op, right, expr.span)?;
 
 
 
@@ -724,6 +917,11 @@ let result: BasicValueEnum<'ctx> = match (&left_raw, &right_raw) {
     let call = self.builder
         .build_call(concat_fn, &[lp.clone().into(), rp.clone().into()], "concat")
         .expect("Failed to call wpp_str_concat");
+    self.check_exception()?;
+
+    // ♻️ Intermediate results of `a + b + c` are only needed for the next concat
+    self.release_string_temporary(left, left_raw)?;
+    self.release_string_temporary(right, right_raw)?;
 
     // ✅ Extract pointer result (the concatenated string)
     call.try_as_basic_value()
@@ -777,7 +975,7 @@ let result: BasicValueEnum<'ctx> = match (&left_raw, &right_raw) {
 
     // ✅ Extend i1 → i32 for consistent numeric behavior
     // ✅ Keep as i1 (no extension) for proper bool return type
-return result.as_basic_value_enum();
+return Ok(result.as_basic_value_enum());
 
 }
 
@@ -786,16 +984,53 @@ return result.as_basic_value_enum();
         "+" => self.builder.build_int_add(*l, *r, "addtmp").unwrap().as_basic_value_enum(),
         "-" => self.builder.build_int_sub(*l, *r, "subtmp").unwrap().as_basic_value_enum(),
         "*" => self.builder.build_int_mul(*l, *r, "multmp").unwrap().as_basic_value_enum(),
-        "/" => self.builder.build_int_signed_div(*l, *r, "divtmp").unwrap().as_basic_value_enum(),
+        "/" | "%" => {
+            let r = self.checked_divisor(*r)?;
+            match (op.as_str(), unsigned) {
+                ("/", true) => self.builder.build_int_unsigned_div(*l, r, "udivtmp").unwrap().as_basic_value_enum(),
+                ("/", false) => self.builder.build_int_signed_div(*l, r, "divtmp").unwrap().as_basic_value_enum(),
+                (_, true) => self.builder.build_int_unsigned_rem(*l, r, "uremtmp").unwrap().as_basic_value_enum(),
+                (_, false) => self.builder.build_int_signed_rem(*l, r, "remtmp").unwrap().as_basic_value_enum(),
+            }
+        }
+
+        // ✅ Bitwise operators and shifts (bools may use `& | ^` as well)
+        "&" | "|" | "^" | "<<" | ">>" if l.get_type() != r.get_type() => {
+            return Err(CompileError::type_mismatch(expr.span, format!("unsupported operand types for operator `{}`", op)));
+        }
+        "&" => self.builder.build_and(*l, *r, "bandtmp").unwrap().as_basic_value_enum(),
+        "|" => self.builder.build_or(*l, *r, "bortmp").unwrap().as_basic_value_enum(),
+        "^" => self.builder.build_xor(*l, *r, "bxortmp").unwrap().as_basic_value_enum(),
+        "<<" => self.builder.build_left_shift(*l, *r, "shltmp").unwrap().as_basic_value_enum(),
+        ">>" => self.builder.build_right_shift(*l, *r, !unsigned, "shrtmp").unwrap().as_basic_value_enum(),
+
+        // ✅ Unary `!x` and `~x`, parsed as `0 ! x` and `0 ~ x`
+        "!" if r.get_type().get_bit_width() == 1 => self.builder.build_not(*r, "nottmp").unwrap().as_basic_value_enum(),
+        "!" => self
+            .builder
+            .build_int_compare(inkwell::IntPredicate::EQ, *r, r.get_type().const_zero(), "nottmp")
+            .unwrap()
+            .as_basic_value_enum(),
+        "~" => self.builder.build_not(*r, "bnottmp").unwrap().as_basic_value_enum(),
 
         "==" => self.builder.build_int_compare(inkwell::IntPredicate::EQ, *l, *r, "eqtmp").unwrap().as_basic_value_enum(),
         "!=" => self.builder.build_int_compare(inkwell::IntPredicate::NE, *l, *r, "netmp").unwrap().as_basic_value_enum(),
-        "<"  => self.builder.build_int_compare(inkwell::IntPredicate::SLT, *l, *r, "lttmp").unwrap().as_basic_value_enum(),
-        "<=" => self.builder.build_int_compare(inkwell::IntPredicate::SLE, *l, *r, "letmp").unwrap().as_basic_value_enum(),
-        ">"  => self.builder.build_int_compare(inkwell::IntPredicate::SGT, *l, *r, "gttmp").unwrap().as_basic_value_enum(),
-        ">=" => self.builder.build_int_compare(inkwell::IntPredicate::SGE, *l, *r, "getmp").unwrap().as_basic_value_enum(),
+        "<" | "<=" | ">" | ">=" => {
+            use inkwell::IntPredicate::*;
+            let pred = match (op.as_str(), unsigned) {
+                ("<", false) => SLT,
+                ("<=", false) => SLE,
+                (">", false) => SGT,
+                (">=", false) => SGE,
+                ("<", true) => ULT,
+                ("<=", true) => ULE,
+                (">", true) => UGT,
+                _ => UGE,
+            };
+            self.builder.build_int_compare(pred, *l, *r, "cmptmp").unwrap().as_basic_value_enum()
+        }
 
-        _ => panic!("Unsupported integer operator: {}", op),
+        _ => return Err(CompileError::unsupported(expr.span, format!("unsupported integer operator `{}`", op))),
     }
 }
 
@@ -806,57 +1041,37 @@ return result.as_basic_value_enum();
         "-" => self.builder.build_float_sub(lf.clone(), rf.clone(), "fsub").unwrap().as_basic_value_enum(),
         "*" => self.builder.build_float_mul(lf.clone(), rf.clone(), "fmul").unwrap().as_basic_value_enum(),
         "/" => self.builder.build_float_div(lf.clone(), rf.clone(), "fdiv").unwrap().as_basic_value_enum(),
+        "%" => self.builder.build_float_rem(*lf, *rf, "frem").unwrap().as_basic_value_enum(),
         "==" => self.builder.build_float_compare(inkwell::FloatPredicate::OEQ, lf.clone(), rf.clone(), "feq").unwrap().as_basic_value_enum(),
         "!=" => self.builder.build_float_compare(inkwell::FloatPredicate::ONE, lf.clone(), rf.clone(), "fne").unwrap().as_basic_value_enum(),
         "<"  => self.builder.build_float_compare(inkwell::FloatPredicate::OLT, lf.clone(), rf.clone(), "flt").unwrap().as_basic_value_enum(),
         "<=" => self.builder.build_float_compare(inkwell::FloatPredicate::OLE, lf.clone(), rf.clone(), "fle").unwrap().as_basic_value_enum(),
         ">"  => self.builder.build_float_compare(inkwell::FloatPredicate::OGT, lf.clone(), rf.clone(), "fgt").unwrap().as_basic_value_enum(),
         ">=" => self.builder.build_float_compare(inkwell::FloatPredicate::OGE, lf.clone(), rf.clone(), "fge").unwrap().as_basic_value_enum(),
-        _ => panic!("Unsupported float operator: {}", op),
+        _ => return Err(CompileError::unsupported(expr.span, format!("unsupported float operator `{}`", op))),
     },
 
-    // --- Mixed: Int + Float ---
-    (BasicValueEnum::IntValue(iv), BasicValueEnum::FloatValue(fv)) => {
-        let f_ty = fv.get_type();
-        let iv_cast = self.builder.build_signed_int_to_float(iv.clone(), f_ty, "int_to_float_lhs").unwrap();
-        let new_expr = self.builder.build_float_add(iv_cast, fv.clone(), "promoted_add").unwrap();
-        if ["+", "-", "*", "/"].contains(&op.as_str()) {
-            new_expr.as_basic_value_enum()
-        } else {
-            panic!("Cannot compare mixed int/float types without explicit cast")
-        }
-    }
-
-    (BasicValueEnum::FloatValue(fv), BasicValueEnum::IntValue(iv)) => {
-        let f_ty = fv.get_type();
-        let iv_cast = self.builder.build_signed_int_to_float(iv.clone(), f_ty, "int_to_float_rhs").unwrap();
-        let new_expr = self.builder.build_float_add(fv.clone(), iv_cast, "promoted_add").unwrap();
-        if ["+", "-", "*", "/"].contains(&op.as_str()) {
-            new_expr.as_basic_value_enum()
-        } else {
-            panic!("Cannot compare mixed float/int types without explicit cast")
-        }
+    // --- Mixed: Int and Float (arithmetic operands were already widened to the float) ---
+    (BasicValueEnum::IntValue(_), BasicValueEnum::FloatValue(_)) | (BasicValueEnum::FloatValue(_), BasicValueEnum::IntValue(_)) => {
+        return Err(CompileError::type_mismatch(expr.span, "cannot compare mixed int/float types without an explicit cast"));
     }
    // --- String (ptr) + String (ptr) ---
     (BasicValueEnum::PointerValue(lp), BasicValueEnum::PointerValue(rp)) => {
         match op.as_str() {
             "==" | "!=" => {
-                // Use strcmp for string comparison
+                // Compare lengths and bytes, so embedded NULs count
                 let i32_ty = self.context.i32_type();
-                let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
-
-                let strcmp_fn = self.module.get_function("strcmp").unwrap_or_else(|| {
-                    let ty = i32_ty.fn_type(&[i8ptr.into(), i8ptr.into()], false);
-                    self.module.add_function("strcmp", ty, None)
-                });
+                let cmp_fn = self.runtime_fn("wpp_str_cmp")?;
 
                 let cmp_result = self.builder
-                    .build_call(strcmp_fn, &[(*lp).into(), (*rp).into()], "strcmp")
+                    .build_call(cmp_fn, &[(*lp).into(), (*rp).into()], "strcmp")
                     .unwrap()
                     .try_as_basic_value()
                     .left()
                     .unwrap()
                     .into_int_value();
+                self.release_string_temporary(left, left_raw)?;
+                self.release_string_temporary(right, right_raw)?;
 
                 let zero = i32_ty.const_int(0, false);
                 let is_equal = self.builder
@@ -882,14 +1097,16 @@ return result.as_basic_value_enum();
                     self.module.add_function("wpp_str_concat", ty, None)
                 });
 
-                self.builder
+                let joined = self.builder
                     .build_call(concat_fn, &[(*lp).into(), (*rp).into()], "strconcat")
                     .unwrap()
                     .try_as_basic_value()
                     .left()
-                    .unwrap()
+                    .unwrap();
+                self.check_exception()?;
+                joined
             }
-            _ => panic!("❌ Unsupported string operator: {}", op),
+            _ => return Err(CompileError::unsupported(expr.span, format!("unsupported string operator `{}`", op))),
         }
     }
 
@@ -898,7 +1115,7 @@ return result.as_basic_value_enum();
 
 
 
-    _ => panic!("❌ Unsupported operand types for operator `{}`", op),
+    _ => return Err(CompileError::type_mismatch(expr.span, format!("unsupported operand types for operator `{}`", op))),
 };
 
 // ✅ Result is now type-accurate (no auto i32 cast)
@@ -910,10 +1127,18 @@ result
 
 
         // === Boolean literal ===
-        Expr::BoolLiteral(value) => self.context.bool_type().const_int(*value as u64, false).into(),
+        ExprKind::BoolLiteral(value) => self.context.bool_type().const_int(*value as u64, false).into(),
+
+        // === Enum variant with a payload (`Shape.Circle(2.0)`) ===
+        ExprKind::Call { name, args } if self.enum_constructor(name).is_some() => {
+            let (enum_name, index) = self.enum_constructor(name).unwrap();
+            self.compile_enum_value(&enum_name, index, args, expr.span)?.into()
+        }
+        ExprKind::Match { subject, arms } => self.compile_match(subject, arms)?.0,
+        ExprKind::Cast { expr: inner, ty } => self.compile_cast(inner, ty, expr.span)?,
 
         // === Function call (print, etc.) ===
-        Expr::Call { name, args } => {
+        ExprKind::Call { name, args } => {
             if name == "print" {
     // === Declare runtime externs ===
     let void_ty = self.context.void_type();
@@ -935,6 +1160,10 @@ result
         let ty = void_ty.fn_type(&[i8ptr.into()], false);
         self.module.add_function("wpp_print_object", ty, None)
     });
+    let wpp_print_enum = self.module.get_function("wpp_print_enum").unwrap_or_else(|| {
+        let ty = void_ty.fn_type(&[i8ptr.into()], false);
+        self.module.add_function("wpp_print_enum", ty, None)
+    });
 
     // Declare printf for newline
     let printf_fn = self.module.get_function("printf").unwrap_or_else(|| {
@@ -944,22 +1173,62 @@ result
 
     // === Compile all arguments ===
     if args.is_empty() {
-        panic!("print() expects at least one argument");
+        return Err(CompileError::ArgumentCount {
+            callee: "print".to_string(),
+            expected: "at least one argument".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
     // Print each argument with a space separator
     for (i, arg) in args.iter().enumerate() {
-        let val = self.compile_expr(arg);
+        let (val, val_ty) = self.compile_typed(arg)?;
+
+        // 🎲 An `any` box carries its own type tag
+        if let (BasicValueEnum::PointerValue(pv), FieldType::Any) = (val, &val_ty) {
+            let casted = self.builder.build_pointer_cast(pv, i8ptr, "casted_box").unwrap();
+            self.builder
+                .build_call(wpp_print_value_basic, &[casted.into(), i32_ty.const_int(0, false).into()], "call_print_any")
+                .unwrap();
+            continue;
+        }
+
+        // 📦 Arrays and object literals are pointers too, but have their own printers
+        if let BasicValueEnum::PointerValue(pv) = val {
+            let printer = match &val_ty {
+                FieldType::Array(_) => Some((wpp_print_array, "call_print_array")),
+                FieldType::Map(_) => Some((wpp_print_object, "call_print_object")),
+                FieldType::Object(name) if self.enums.contains_key(name) => Some((wpp_print_enum, "call_print_enum")),
+                FieldType::Object(name) if !self.entities.contains_key(name) && !self.interfaces.contains_key(name) => {
+                    Some((wpp_print_object, "call_print_object"))
+                }
+                _ => None,
+            };
+            if let Some((printer, call_name)) = printer {
+                let casted = self.builder.build_pointer_cast(pv, i8ptr, "casted_heap").unwrap();
+                self.builder.build_call(printer, &[casted.into()], call_name).unwrap();
+                continue;
+            }
+        }
 
         // === Handle based on value type ===
         match val {
             BasicValueEnum::IntValue(iv) => {
-                // 🧮 Detect width to choose between i32/i64/bool
+                // 🧮 The static type says how to read the bits (`u8` 255 vs `i8` -1)
                 let width = iv.get_type().get_bit_width();
                 let type_id = match width {
-                    1 => 5,    // bool
-                    64 => 2,   // i64
-                    _ => 1,    // i32 default
+                    1 => 5, // bool
+                    _ if val_ty.int_width() == Some(width) => self.field_type_tag(&val_ty),
+                    64 => 2, // i64
+                    _ => 1,  // i32 default
+                };
+
+                // The runtime reads bools as a full i32
+                let iv = if width == 1 {
+                    self.builder.build_int_z_extend(iv, i32_ty, "bool_i32").unwrap()
+                } else {
+                    iv
                 };
 
                 // Store to temporary alloca to pass by pointer
@@ -1007,6 +1276,7 @@ result
                         "call_print_basic_ptr",
                     )
                     .unwrap();
+                self.release_string_temporary(arg, val)?;
             }
 
             BasicValueEnum::ArrayValue(av) => {
@@ -1060,17 +1330,22 @@ result
         .unwrap();
 
     // === Return dummy i32 ===
-    return self.i32_type.const_int(0, false).into();
+    return Ok(self.i32_type.const_int(0, false).into());
 }
 
 // === HTTP GET ===
 else if name == "http.get" {
     // Expect one argument: a string literal or variable containing URL
     if args.len() != 1 {
-        panic!("http.get() expects 1 argument (URL)");
+        return Err(CompileError::ArgumentCount {
+            callee: "http.get".to_string(),
+            expected: "1 argument (URL)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let url_val = self.compile_expr(&args[0]);
+    let url_val = self.compile_expr(&args[0])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -1084,10 +1359,11 @@ else if name == "http.get" {
     let call = self.builder
         .build_call(http_get_fn, &[url_val.into()], "call_http_get")
         .unwrap();
+    self.check_exception()?;
 
-    return call.try_as_basic_value().left().unwrap_or_else(|| {
+    return Ok(call.try_as_basic_value().left().unwrap_or_else(|| {
         i32_ty.const_int(0, false).into()
-    });
+    }));
 }
 else if name == "http.post" || name == "http.put" || name == "http.patch" || name == "http.delete" {
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
@@ -1103,16 +1379,21 @@ else if name == "http.post" || name == "http.put" || name == "http.patch" || nam
 
     // Validate argument count
     if (needs_body && args.len() != 2) || (!needs_body && args.len() != 1) {
-        panic!("{} expects {} argument(s)", name, if needs_body { 2 } else { 1 });
+        return Err(CompileError::ArgumentCount {
+            callee: name.clone(),
+            expected: if needs_body { "2 arguments (URL, body)" } else { "1 argument (URL)" }.to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
     // Compile URL
-    let url_val = self.compile_expr(&args[0]);
+    let url_val = self.compile_expr(&args[0])?;
 
     // Compile body if applicable
     let mut params = vec![url_val.into()];
     if needs_body {
-        let body_val = self.compile_expr(&args[1]);
+        let body_val = self.compile_expr(&args[1])?;
         params.push(body_val.into());
     }
 
@@ -1130,18 +1411,25 @@ else if name == "http.post" || name == "http.put" || name == "http.patch" || nam
         .build_call(extern_fn, &params, &format!("call_{}", name))
         .unwrap();
 
-    return call.try_as_basic_value().left().unwrap_or_else(|| {
+    self.check_exception()?;
+
+    return Ok(call.try_as_basic_value().left().unwrap_or_else(|| {
         i32_ty.const_int(0, false).into()
-    });
+    }));
 }
 // === HTTP STATUS ===
 else if name == "http.status" {
     if args.len() != 1 {
-        panic!("http.status(handle) expects 1 argument");
+        return Err(CompileError::ArgumentCount {
+            callee: "http.status".to_string(),
+            expected: "1 argument".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
     let i32_ty = self.context.i32_type();
-    let handle = self.compile_expr(&args[0]);
+    let handle = self.compile_expr(&args[0])?;
 
     let fnc = self.module.get_function("wpp_http_status").unwrap_or_else(|| {
         let ty = i32_ty.fn_type(&[i32_ty.into()], false);
@@ -1152,18 +1440,25 @@ else if name == "http.status" {
         .build_call(fnc, &[handle.into()], "call_http_status")
         .unwrap();
 
-    return call.try_as_basic_value().left().unwrap();
+    self.check_exception()?;
+
+    return Ok(call.try_as_basic_value().left().unwrap());
 }
 
 // === HTTP BODY ===
 else if name == "http.body" {
     if args.len() != 1 {
-        panic!("http.body(handle) expects 1 argument");
+        return Err(CompileError::ArgumentCount {
+            callee: "http.body".to_string(),
+            expected: "1 argument".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
     let i32_ty = self.context.i32_type();
     let i8ptr_ty = self.context.i8_type().ptr_type(inkwell::AddressSpace::default());
-    let handle = self.compile_expr(&args[0]);
+    let handle = self.compile_expr(&args[0])?;
 
     let fnc = self.module.get_function("wpp_http_body").unwrap_or_else(|| {
         let ty = i8ptr_ty.fn_type(&[i32_ty.into()], false);
@@ -1174,18 +1469,25 @@ else if name == "http.body" {
         .build_call(fnc, &[handle.into()], "call_http_body")
         .unwrap();
 
-    return call.try_as_basic_value().left().unwrap();
+    self.check_exception()?;
+
+    return Ok(call.try_as_basic_value().left().unwrap());
 }
 
 // === HTTP HEADERS ===
 else if name == "http.headers" {
     if args.len() != 1 {
-        panic!("http.headers(handle) expects 1 argument");
+        return Err(CompileError::ArgumentCount {
+            callee: "http.headers".to_string(),
+            expected: "1 argument".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
     let i32_ty = self.context.i32_type();
     let i8ptr_ty = self.context.i8_type().ptr_type(inkwell::AddressSpace::default());
-    let handle = self.compile_expr(&args[0]);
+    let handle = self.compile_expr(&args[0])?;
 
     let fnc = self.module.get_function("wpp_http_headers").unwrap_or_else(|| {
         let ty = i8ptr_ty.fn_type(&[i32_ty.into()], false);
@@ -1196,65 +1498,54 @@ else if name == "http.headers" {
         .build_call(fnc, &[handle.into()], "call_http_headers")
         .unwrap();
 
-    return call.try_as_basic_value().left().unwrap();
+    self.check_exception()?;
+
+    return Ok(call.try_as_basic_value().left().unwrap());
 }
 
 // === SERVER REGISTER ===
 else if name == "server.register" {
     if args.len() != 2 {
-        panic!("server.register() expects 2 arguments (path, handler)");
+        return Err(CompileError::ArgumentCount {
+            callee: "server.register".to_string(),
+            expected: "2 arguments (path, handler)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let path_val = self.compile_expr(&args[0]);
-    let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
-    let void_ty = self.context.void_type();
-
-    let register_fn = self.module.get_function("wpp_register_endpoint").unwrap_or_else(|| {
-        let ty = void_ty.fn_type(&[i8ptr.into(), i8ptr.into()], false);
-        self.module.add_function("wpp_register_endpoint", ty, None)
-    });
-
-    // Handler must be a function name (variable)
-    let handler_name = if let Expr::Variable(ref s) = args[1] {
-        s.clone()
-    } else {
-        panic!("Expected function name as second argument in server.register");
-    };
+    let path_val = self.compile_expr(&args[0])?;
 
-    // 🧠 Resolve handler function via multiple dispatch table
-let handler_fn: &FunctionValue<'_> = if let Some(sigs) = self.reverse_func_index.get(&handler_name) {
-    if let Some(first_sig) = sigs.first() {
-        self.functions.get(first_sig).unwrap_or_else(|| {
-            panic!("Unknown handler function '{}'", handler_name)
-        })
-    } else {
-        panic!("No overloads registered for handler '{}'", handler_name);
+    // Handler: a function name, a lambda or a variable holding a closure
+    if let ExprKind::Variable(ref handler_name) = args[1].kind {
+        if !self.vars.contains_key(handler_name) && !self.reverse_func_index.contains_key(handler_name) {
+            return Err(CompileError::UnknownFunction { name: handler_name.clone(), span: args[1].span });
+        }
+    } else if !matches!(args[1].kind, ExprKind::Funcy { .. }) {
+        return Err(CompileError::type_mismatch(args[1].span, "server.register() expects a function as its second argument"));
     }
-} else {
-    panic!("Unknown handler function '{}'", handler_name);
-};
+    let handler = self.compile_expr(&args[1])?;
 
+    let register_fn = self.runtime_fn("wpp_register_closure_endpoint")?;
     self.builder
-        .build_call(
-            register_fn,
-            &[
-                path_val.into(),
-                handler_fn.as_global_value().as_pointer_value().into(),
-            ],
-            "call_server_register",
-        )
+        .build_call(register_fn, &[path_val.into(), handler.into()], "call_server_register")
         .unwrap();
 
-    return self.i32_type.const_int(0, false).into();
+    return Ok(self.i32_type.const_int(0, false).into());
 }
 
 // === SERVER START ===
 else if name == "server.start" {
     if args.len() != 1 {
-        panic!("server.start() expects 1 argument (port)");
+        return Err(CompileError::ArgumentCount {
+            callee: "server.start".to_string(),
+            expected: "1 argument (port)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let port_val = self.compile_expr(&args[0]);
+    let port_val = self.compile_expr(&args[0])?;
     let i32_ty = self.context.i32_type();
     let void_ty = self.context.void_type();
 
@@ -1278,54 +1569,61 @@ else if name == "server.start" {
         .build_call(wait_fn, &[], "call_runtime_wait")
         .unwrap();
 
-    return self.i32_type.const_int(0, false).into();
+    return Ok(self.i32_type.const_int(0, false).into());
 }
 
 
 // === THREAD: useThread(fn) ===
 else if name == "useThread" {
     if args.is_empty() {
-        panic!("useThread(fn [, detached]) requires at least one argument");
+        return Err(CompileError::ArgumentCount {
+            callee: "useThread".to_string(),
+            expected: "at least one argument".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let fn_ptr_val = self.compile_expr(&args[0]);
+    let fn_ptr_val = self.compile_expr(&args[0])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let void_ty = self.context.void_type();
     let i32_ty = self.context.i32_type();
 
     // Optional detached flag
     let detached_flag = if args.len() > 1 {
-        let val = self.compile_expr(&args[1]);
+        let val = self.compile_expr(&args[1])?;
         match val {
             BasicValueEnum::IntValue(iv) => iv,
-            _ => panic!("useThread(fn, detached) expects bool/int flag"),
+            _ => return Err(CompileError::type_mismatch(args[1].span, "useThread(fn, detached) expects a bool/int flag")),
         }
     } else {
         i32_ty.const_int(0, false)
     };
 
     // externs
-    let spawn_fn = self.module.get_function("wpp_thread_spawn_gc").unwrap_or_else(|| {
-        let ty = i8ptr.fn_type(&[i8ptr.into()], false);
-        self.module.add_function("wpp_thread_spawn_gc", ty, None)
-    });
+    let spawn_fn = self.runtime_fn("wpp_thread_spawn_closure")?;
     let join_fn = self.module.get_function("wpp_thread_join").unwrap_or_else(|| {
         let ty = void_ty.fn_type(&[i8ptr.into()], false);
         self.module.add_function("wpp_thread_join", ty, None)
     });
 
-    // cast function pointer
+    // cast closure pointer
     let casted_ptr = if let BasicValueEnum::PointerValue(pv) = fn_ptr_val {
         self.builder
-            .build_pointer_cast(pv, i8ptr, "thread_fn_cast")
+            .build_pointer_cast(pv, i8ptr, "thread_closure_cast")
             .unwrap()
     } else {
-        panic!("useThread() expects a function reference");
+        return Err(CompileError::ArgumentCount {
+            callee: "useThread".to_string(),
+            expected: "a function reference".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     };
 
     // spawn thread
     let thread_handle = self.builder
-        .build_call(spawn_fn, &[casted_ptr.into()], "call_thread_spawn_gc")
+        .build_call(spawn_fn, &[casted_ptr.into()], "call_thread_spawn_closure")
         .unwrap()
         .try_as_basic_value()
         .left()
@@ -1352,6 +1650,7 @@ else if name == "useThread" {
     self.builder
         .build_call(join_fn, &[thread_handle.into()], "call_thread_join")
         .unwrap();
+    self.check_exception()?;
     self.builder.build_unconditional_branch(cont_block).unwrap();
 
     // === cont_thread ===
@@ -1360,7 +1659,7 @@ else if name == "useThread" {
     // 🚫 DO NOT insert a return here!
     // Leave this block open so the next AST nodes (sleep, print, etc.) can continue.
 
-    return self.i32_type.const_int(0, false).into();
+    return Ok(self.i32_type.const_int(0, false).into());
 }
 
 
@@ -1369,10 +1668,15 @@ else if name == "useThread" {
 // === THREAD: useThreadState(initial) ===
 else if name == "useThreadState" {
     if args.len() != 1 {
-        panic!("useThreadState(initial) requires one argument");
+        return Err(CompileError::ArgumentCount {
+            callee: "useThreadState".to_string(),
+            expected: "one argument".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let init_val = self.compile_expr(&args[0]);
+    let init_val = self.compile_expr(&args[0])?;
     let i32_ty = self.context.i32_type();
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
 
@@ -1385,14 +1689,19 @@ else if name == "useThreadState" {
         .build_call(state_new_fn, &[init_val.into()], "call_thread_state_new")
         .unwrap();
 
-    return call.try_as_basic_value().left().unwrap();
+    return Ok(call.try_as_basic_value().left().unwrap());
 }
 else if name == "getThreadState" {
     if args.len() != 1 {
-        panic!("getThreadState(ptr) requires one argument");
+        return Err(CompileError::ArgumentCount {
+            callee: "getThreadState".to_string(),
+            expected: "one argument".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let ptr_val = self.compile_expr(&args[0]);
+    let ptr_val = self.compile_expr(&args[0])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
 
     let fn_get = self.module.get_function("wpp_thread_state_get")
@@ -1402,16 +1711,21 @@ else if name == "getThreadState" {
         .build_call(fn_get, &[ptr_val.into()], "call_thread_state_get")
         .unwrap();
 
-    return call.try_as_basic_value().left().unwrap();
+    return Ok(call.try_as_basic_value().left().unwrap());
 }
 
 // === MUTEX: useMutex(initial) ===
 else if name == "useMutex" {
     if args.len() != 1 {
-        panic!("useMutex(initial) requires one argument");
+        return Err(CompileError::ArgumentCount {
+            callee: "useMutex".to_string(),
+            expected: "one argument".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let init_val = self.compile_expr(&args[0]);
+    let init_val = self.compile_expr(&args[0])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -1424,17 +1738,22 @@ else if name == "useMutex" {
         .build_call(fn_new, &[init_val.into()], "call_mutex_new")
         .unwrap();
 
-    return call.try_as_basic_value().left().unwrap();
+    return Ok(call.try_as_basic_value().left().unwrap());
 }
 
 // === MUTEX: lock(mtx, threadId) ===
 else if name == "lock" {
     if args.len() != 2 {
-        panic!("lock(mutex, threadId) requires 2 arguments");
+        return Err(CompileError::ArgumentCount {
+            callee: "lock".to_string(),
+            expected: "2 arguments".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let mtx_val = self.compile_expr(&args[0]);
-    let tid_val = self.compile_expr(&args[1]);
+    let mtx_val = self.compile_expr(&args[0])?;
+    let tid_val = self.compile_expr(&args[1])?;
     let void_ty = self.context.void_type();
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
@@ -1448,16 +1767,21 @@ else if name == "lock" {
         .build_call(fn_lock, &[mtx_val.into(), tid_val.into()], "call_mutex_lock")
         .unwrap();
 
-    return self.i32_type.const_int(0, false).into();
+    return Ok(self.i32_type.const_int(0, false).into());
 }
 
 // === MUTEX: unlock(mtx) ===
 else if name == "unlock" {
     if args.len() != 1 {
-        panic!("unlock(mutex) requires one argument");
+        return Err(CompileError::ArgumentCount {
+            callee: "unlock".to_string(),
+            expected: "one argument".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let mtx_val = self.compile_expr(&args[0]);
+    let mtx_val = self.compile_expr(&args[0])?;
     let void_ty = self.context.void_type();
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
 
@@ -1470,7 +1794,7 @@ else if name == "unlock" {
         .build_call(fn_unlock, &[mtx_val.into()], "call_mutex_unlock")
         .unwrap();
 
-    return self.i32_type.const_int(0, false).into();
+    return Ok(self.i32_type.const_int(0, false).into());
 }
 
 // === READLINE ===
@@ -1492,42 +1816,82 @@ else if name == "readline" {
         .left()
         .expect("wpp_readline must return a pointer");
 
-    return result;
+    return Ok(result);
+}
+
+// === ARRAY LENGTH ===
+else if name == "len" {
+    if args.len() != 1 {
+        return Err(CompileError::ArgumentCount {
+            callee: "len".to_string(),
+            expected: "exactly 1 argument (array)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
+    }
+
+    // Maps keep their length in the same place as arrays
+    let (coll, ty) = self.compile_typed(&args[0])?;
+    if !matches!(ty, FieldType::Array(_) | FieldType::Map(_)) {
+        return Err(CompileError::type_mismatch(
+            args[0].span,
+            format!("`len` expects an array or map, found `{}`", ty),
+        ));
+    }
+    let i8_ptr = self.context.i8_type().ptr_type(AddressSpace::default());
+    let coll = self.builder.build_pointer_cast(coll.into_pointer_value(), i8_ptr, "len_view").unwrap();
+    return Ok(self.array_len(coll).into());
+}
+
+// === GROWABLE ARRAYS: push / pop / insert / remove / slice / concat ===
+else if Self::is_array_builtin(name) && !self.reverse_func_index.contains_key(name) {
+    return Ok(self.compile_array_builtin(name, args, expr.span)?.0);
+}
+
+// === MAPS: Map / get / set / has / delete / keys / values ===
+else if Self::is_map_builtin(name) && !self.reverse_func_index.contains_key(name) {
+    return Ok(self.compile_map_builtin(name, args, None, expr.span)?.0);
 }
 
 // === STRING LENGTH ===
 else if name == "strlen" {
     if args.len() != 1 {
-        panic!("strlen() expects exactly 1 argument (string)");
+        return Err(CompileError::ArgumentCount {
+            callee: "strlen".to_string(),
+            expected: "exactly 1 argument (string)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let str_val = self.compile_expr(&args[0]);
-    let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
-    let i32_ty = self.context.i32_type();
+    let str_val = self.compile_expr(&args[0])?;
 
-    // Declare strlen from C standard library
-    let strlen_fn = self.module.get_function("strlen").unwrap_or_else(|| {
-        let fn_ty = i32_ty.fn_type(&[i8ptr.into()], false);
-        self.module.add_function("strlen", fn_ty, None)
-    });
+    // Length in code points, read from the string header (runtime/string.rs)
+    let strlen_fn = self.runtime_fn("wpp_str_len")?;
 
     let call = self.builder
         .build_call(strlen_fn, &[str_val.into()], "call_strlen")
         .unwrap();
+    self.release_string_temporary(&args[0], str_val)?;
 
-    return call
+    return Ok(call
         .try_as_basic_value()
         .left()
-        .expect("strlen must return an integer");
+        .expect("wpp_str_len must return an integer"));
 }
 
 // === INTEGER TO STRING ===
 else if name == "int_to_string" || name == "to_string" {
     if args.len() != 1 {
-        panic!("{}() expects exactly 1 argument (integer)", name);
+        return Err(CompileError::ArgumentCount {
+            callee: name.clone(),
+            expected: "exactly 1 argument (integer)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let int_val = self.compile_expr(&args[0]);
+    let int_val = self.compile_expr(&args[0])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -1541,21 +1905,52 @@ else if name == "int_to_string" || name == "to_string" {
         .build_call(int_to_str_fn, &[int_val.into()], "call_int_to_string")
         .unwrap();
 
-    return call
+    return Ok(call
         .try_as_basic_value()
         .left()
-        .expect("wpp_int_to_string must return a pointer");
+        .expect("wpp_int_to_string must return a pointer"));
+}
+
+// === FORMAT ===
+else if name == "format" {
+    return self.compile_format(args, expr.span);
+}
+
+// === STACK TRACE OF A CAUGHT EXCEPTION ===
+else if name == "stack_trace" {
+    if args.len() != 1 {
+        return Err(CompileError::ArgumentCount {
+            callee: "stack_trace".to_string(),
+            expected: "exactly 1 argument (a caught exception)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
+    }
+    // `catch` keeps the trace next to the variable it binds
+    let trace = match &args[0].kind {
+        ExprKind::Variable(var) => self.vars.get(&Self::trace_var(var)).cloned(),
+        _ => None,
+    };
+    let Some(trace) = trace else {
+        return Err(CompileError::unsupported(args[0].span, "`stack_trace` expects the variable of a `catch`"));
+    };
+    return Ok(self.builder.build_load(trace.ty, trace.ptr, "stack_trace").unwrap());
 }
 
 // === STRING UTILITIES ===
 else if name == "wpp_str_substr" {
     if args.len() != 3 {
-        panic!("wpp_str_substr() expects exactly 3 arguments (string, start, length)");
+        return Err(CompileError::ArgumentCount {
+            callee: "wpp_str_substr".to_string(),
+            expected: "exactly 3 arguments (string, start, length)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let str_val = self.compile_expr(&args[0]);
-    let start_val = self.compile_expr(&args[1]);
-    let length_val = self.compile_expr(&args[2]);
+    let str_val = self.compile_expr(&args[0])?;
+    let start_val = self.compile_expr(&args[1])?;
+    let length_val = self.compile_expr(&args[2])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -1568,16 +1963,24 @@ else if name == "wpp_str_substr" {
         .build_call(substr_fn, &[str_val.into(), start_val.into(), length_val.into()], "call_substr")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("wpp_str_substr must return a pointer");
+    self.check_exception()?;
+    self.release_string_temporary(&args[0], str_val)?;
+
+    return Ok(call.try_as_basic_value().left().expect("wpp_str_substr must return a pointer"));
 }
 
 else if name == "wpp_str_index_of" {
     if args.len() != 2 {
-        panic!("wpp_str_index_of() expects exactly 2 arguments (haystack, needle)");
+        return Err(CompileError::ArgumentCount {
+            callee: "wpp_str_index_of".to_string(),
+            expected: "exactly 2 arguments (haystack, needle)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let haystack = self.compile_expr(&args[0]);
-    let needle = self.compile_expr(&args[1]);
+    let haystack = self.compile_expr(&args[0])?;
+    let needle = self.compile_expr(&args[1])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -1590,17 +1993,26 @@ else if name == "wpp_str_index_of" {
         .build_call(index_of_fn, &[haystack.into(), needle.into()], "call_index_of")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("wpp_str_index_of must return an integer");
+    self.check_exception()?;
+    self.release_string_temporary(&args[0], haystack)?;
+    self.release_string_temporary(&args[1], needle)?;
+
+    return Ok(call.try_as_basic_value().left().expect("wpp_str_index_of must return an integer"));
 }
 
 else if name == "wpp_str_replace" {
     if args.len() != 3 {
-        panic!("wpp_str_replace() expects exactly 3 arguments (string, find, replace)");
+        return Err(CompileError::ArgumentCount {
+            callee: "wpp_str_replace".to_string(),
+            expected: "exactly 3 arguments (string, find, replace)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let str_val = self.compile_expr(&args[0]);
-    let find_val = self.compile_expr(&args[1]);
-    let replace_val = self.compile_expr(&args[2]);
+    let str_val = self.compile_expr(&args[0])?;
+    let find_val = self.compile_expr(&args[1])?;
+    let replace_val = self.compile_expr(&args[2])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
 
     let replace_fn = self.module.get_function("wpp_str_replace").unwrap_or_else(|| {
@@ -1612,15 +2024,25 @@ else if name == "wpp_str_replace" {
         .build_call(replace_fn, &[str_val.into(), find_val.into(), replace_val.into()], "call_replace")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("wpp_str_replace must return a pointer");
+    self.check_exception()?;
+    self.release_string_temporary(&args[0], str_val)?;
+    self.release_string_temporary(&args[1], find_val)?;
+    self.release_string_temporary(&args[2], replace_val)?;
+
+    return Ok(call.try_as_basic_value().left().expect("wpp_str_replace must return a pointer"));
 }
 
 else if name == "wpp_str_to_upper" {
     if args.len() != 1 {
-        panic!("wpp_str_to_upper() expects exactly 1 argument (string)");
+        return Err(CompileError::ArgumentCount {
+            callee: "wpp_str_to_upper".to_string(),
+            expected: "exactly 1 argument (string)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let str_val = self.compile_expr(&args[0]);
+    let str_val = self.compile_expr(&args[0])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
 
     let to_upper_fn = self.module.get_function("wpp_str_to_upper").unwrap_or_else(|| {
@@ -1632,15 +2054,23 @@ else if name == "wpp_str_to_upper" {
         .build_call(to_upper_fn, &[str_val.into()], "call_to_upper")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("wpp_str_to_upper must return a pointer");
+    self.check_exception()?;
+    self.release_string_temporary(&args[0], str_val)?;
+
+    return Ok(call.try_as_basic_value().left().expect("wpp_str_to_upper must return a pointer"));
 }
 
 else if name == "wpp_str_to_lower" {
     if args.len() != 1 {
-        panic!("wpp_str_to_lower() expects exactly 1 argument (string)");
+        return Err(CompileError::ArgumentCount {
+            callee: "wpp_str_to_lower".to_string(),
+            expected: "exactly 1 argument (string)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let str_val = self.compile_expr(&args[0]);
+    let str_val = self.compile_expr(&args[0])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
 
     let to_lower_fn = self.module.get_function("wpp_str_to_lower").unwrap_or_else(|| {
@@ -1652,15 +2082,23 @@ else if name == "wpp_str_to_lower" {
         .build_call(to_lower_fn, &[str_val.into()], "call_to_lower")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("wpp_str_to_lower must return a pointer");
+    self.check_exception()?;
+    self.release_string_temporary(&args[0], str_val)?;
+
+    return Ok(call.try_as_basic_value().left().expect("wpp_str_to_lower must return a pointer"));
 }
 
 else if name == "wpp_str_trim" {
     if args.len() != 1 {
-        panic!("wpp_str_trim() expects exactly 1 argument (string)");
+        return Err(CompileError::ArgumentCount {
+            callee: "wpp_str_trim".to_string(),
+            expected: "exactly 1 argument (string)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let str_val = self.compile_expr(&args[0]);
+    let str_val = self.compile_expr(&args[0])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
 
     let trim_fn = self.module.get_function("wpp_str_trim").unwrap_or_else(|| {
@@ -1672,13 +2110,21 @@ else if name == "wpp_str_trim" {
         .build_call(trim_fn, &[str_val.into()], "call_trim")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("wpp_str_trim must return a pointer");
+    self.check_exception()?;
+    self.release_string_temporary(&args[0], str_val)?;
+
+    return Ok(call.try_as_basic_value().left().expect("wpp_str_trim must return a pointer"));
 }
 
 // === VALIDATION FRAMEWORK ===
 else if name == "validation_errors_create" {
     if !args.is_empty() {
-        panic!("validation_errors_create() expects no arguments");
+        return Err(CompileError::ArgumentCount {
+            callee: "validation_errors_create".to_string(),
+            expected: "no arguments".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
@@ -1692,17 +2138,22 @@ else if name == "validation_errors_create" {
         .build_call(fn_val, &[], "call_validation_errors_create")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("validation_errors_create must return a pointer");
+    return Ok(call.try_as_basic_value().left().expect("validation_errors_create must return a pointer"));
 }
 
 else if name == "validation_errors_add" {
     if args.len() != 3 {
-        panic!("validation_errors_add() expects 3 arguments (id, field, message)");
+        return Err(CompileError::ArgumentCount {
+            callee: "validation_errors_add".to_string(),
+            expected: "3 arguments (id, field, message)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let id_val = self.compile_expr(&args[0]);
-    let field_val = self.compile_expr(&args[1]);
-    let message_val = self.compile_expr(&args[2]);
+    let id_val = self.compile_expr(&args[0])?;
+    let field_val = self.compile_expr(&args[1])?;
+    let message_val = self.compile_expr(&args[2])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -1715,15 +2166,20 @@ else if name == "validation_errors_add" {
         .build_call(fn_val, &[id_val.into(), field_val.into(), message_val.into()], "call_validation_errors_add")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("validation_errors_add must return an integer");
+    return Ok(call.try_as_basic_value().left().expect("validation_errors_add must return an integer"));
 }
 
 else if name == "validation_errors_has" {
     if args.len() != 1 {
-        panic!("validation_errors_has() expects 1 argument (id)");
+        return Err(CompileError::ArgumentCount {
+            callee: "validation_errors_has".to_string(),
+            expected: "1 argument (id)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let id_val = self.compile_expr(&args[0]);
+    let id_val = self.compile_expr(&args[0])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -1736,15 +2192,20 @@ else if name == "validation_errors_has" {
         .build_call(fn_val, &[id_val.into()], "call_validation_errors_has")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("validation_errors_has must return an integer");
+    return Ok(call.try_as_basic_value().left().expect("validation_errors_has must return an integer"));
 }
 
 else if name == "validation_errors_count" {
     if args.len() != 1 {
-        panic!("validation_errors_count() expects 1 argument (id)");
+        return Err(CompileError::ArgumentCount {
+            callee: "validation_errors_count".to_string(),
+            expected: "1 argument (id)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let id_val = self.compile_expr(&args[0]);
+    let id_val = self.compile_expr(&args[0])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -1757,15 +2218,20 @@ else if name == "validation_errors_count" {
         .build_call(fn_val, &[id_val.into()], "call_validation_errors_count")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("validation_errors_count must return an integer");
+    return Ok(call.try_as_basic_value().left().expect("validation_errors_count must return an integer"));
 }
 
 else if name == "validation_errors_get" {
     if args.len() != 1 {
-        panic!("validation_errors_get() expects 1 argument (id)");
+        return Err(CompileError::ArgumentCount {
+            callee: "validation_errors_get".to_string(),
+            expected: "1 argument (id)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let id_val = self.compile_expr(&args[0]);
+    let id_val = self.compile_expr(&args[0])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
 
     let fn_val = self.module.get_function("validation_errors_get").unwrap_or_else(|| {
@@ -1777,15 +2243,20 @@ else if name == "validation_errors_get" {
         .build_call(fn_val, &[id_val.into()], "call_validation_errors_get")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("validation_errors_get must return a pointer");
+    return Ok(call.try_as_basic_value().left().expect("validation_errors_get must return a pointer"));
 }
 
 else if name == "validation_errors_clear" {
     if args.len() != 1 {
-        panic!("validation_errors_clear() expects 1 argument (id)");
+        return Err(CompileError::ArgumentCount {
+            callee: "validation_errors_clear".to_string(),
+            expected: "1 argument (id)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let id_val = self.compile_expr(&args[0]);
+    let id_val = self.compile_expr(&args[0])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -1798,15 +2269,20 @@ else if name == "validation_errors_clear" {
         .build_call(fn_val, &[id_val.into()], "call_validation_errors_clear")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("validation_errors_clear must return an integer");
+    return Ok(call.try_as_basic_value().left().expect("validation_errors_clear must return an integer"));
 }
 
 else if name == "validation_errors_destroy" {
     if args.len() != 1 {
-        panic!("validation_errors_destroy() expects 1 argument (id)");
+        return Err(CompileError::ArgumentCount {
+            callee: "validation_errors_destroy".to_string(),
+            expected: "1 argument (id)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let id_val = self.compile_expr(&args[0]);
+    let id_val = self.compile_expr(&args[0])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -1819,17 +2295,22 @@ else if name == "validation_errors_destroy" {
         .build_call(fn_val, &[id_val.into()], "call_validation_errors_destroy")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("validation_errors_destroy must return an integer");
+    return Ok(call.try_as_basic_value().left().expect("validation_errors_destroy must return an integer"));
 }
 
 else if name == "validate_presence" {
     if args.len() != 3 {
-        panic!("validate_presence() expects 3 arguments (value, field, errors_id)");
+        return Err(CompileError::ArgumentCount {
+            callee: "validate_presence".to_string(),
+            expected: "3 arguments (value, field, errors_id)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let value_val = self.compile_expr(&args[0]);
-    let field_val = self.compile_expr(&args[1]);
-    let errors_id_val = self.compile_expr(&args[2]);
+    let value_val = self.compile_expr(&args[0])?;
+    let field_val = self.compile_expr(&args[1])?;
+    let errors_id_val = self.compile_expr(&args[2])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -1842,19 +2323,24 @@ else if name == "validate_presence" {
         .build_call(fn_val, &[value_val.into(), field_val.into(), errors_id_val.into()], "call_validate_presence")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("validate_presence must return an integer");
+    return Ok(call.try_as_basic_value().left().expect("validate_presence must return an integer"));
 }
 
 else if name == "validate_length" {
     if args.len() != 5 {
-        panic!("validate_length() expects 5 arguments (value, field, min, max, errors_id)");
+        return Err(CompileError::ArgumentCount {
+            callee: "validate_length".to_string(),
+            expected: "5 arguments (value, field, min, max, errors_id)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let value_val = self.compile_expr(&args[0]);
-    let field_val = self.compile_expr(&args[1]);
-    let min_val = self.compile_expr(&args[2]);
-    let max_val = self.compile_expr(&args[3]);
-    let errors_id_val = self.compile_expr(&args[4]);
+    let value_val = self.compile_expr(&args[0])?;
+    let field_val = self.compile_expr(&args[1])?;
+    let min_val = self.compile_expr(&args[2])?;
+    let max_val = self.compile_expr(&args[3])?;
+    let errors_id_val = self.compile_expr(&args[4])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -1867,17 +2353,22 @@ else if name == "validate_length" {
         .build_call(fn_val, &[value_val.into(), field_val.into(), min_val.into(), max_val.into(), errors_id_val.into()], "call_validate_length")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("validate_length must return an integer");
+    return Ok(call.try_as_basic_value().left().expect("validate_length must return an integer"));
 }
 
 else if name == "validate_email" {
     if args.len() != 3 {
-        panic!("validate_email() expects 3 arguments (value, field, errors_id)");
+        return Err(CompileError::ArgumentCount {
+            callee: "validate_email".to_string(),
+            expected: "3 arguments (value, field, errors_id)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let value_val = self.compile_expr(&args[0]);
-    let field_val = self.compile_expr(&args[1]);
-    let errors_id_val = self.compile_expr(&args[2]);
+    let value_val = self.compile_expr(&args[0])?;
+    let field_val = self.compile_expr(&args[1])?;
+    let errors_id_val = self.compile_expr(&args[2])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -1890,17 +2381,22 @@ else if name == "validate_email" {
         .build_call(fn_val, &[value_val.into(), field_val.into(), errors_id_val.into()], "call_validate_email")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("validate_email must return an integer");
+    return Ok(call.try_as_basic_value().left().expect("validate_email must return an integer"));
 }
 
 else if name == "validate_url" {
     if args.len() != 3 {
-        panic!("validate_url() expects 3 arguments (value, field, errors_id)");
+        return Err(CompileError::ArgumentCount {
+            callee: "validate_url".to_string(),
+            expected: "3 arguments (value, field, errors_id)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let value_val = self.compile_expr(&args[0]);
-    let field_val = self.compile_expr(&args[1]);
-    let errors_id_val = self.compile_expr(&args[2]);
+    let value_val = self.compile_expr(&args[0])?;
+    let field_val = self.compile_expr(&args[1])?;
+    let errors_id_val = self.compile_expr(&args[2])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -1913,18 +2409,23 @@ else if name == "validate_url" {
         .build_call(fn_val, &[value_val.into(), field_val.into(), errors_id_val.into()], "call_validate_url")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("validate_url must return an integer");
+    return Ok(call.try_as_basic_value().left().expect("validate_url must return an integer"));
 }
 
 else if name == "validate_format" {
     if args.len() != 4 {
-        panic!("validate_format() expects 4 arguments (value, field, pattern, errors_id)");
+        return Err(CompileError::ArgumentCount {
+            callee: "validate_format".to_string(),
+            expected: "4 arguments (value, field, pattern, errors_id)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let value_val = self.compile_expr(&args[0]);
-    let field_val = self.compile_expr(&args[1]);
-    let pattern_val = self.compile_expr(&args[2]);
-    let errors_id_val = self.compile_expr(&args[3]);
+    let value_val = self.compile_expr(&args[0])?;
+    let field_val = self.compile_expr(&args[1])?;
+    let pattern_val = self.compile_expr(&args[2])?;
+    let errors_id_val = self.compile_expr(&args[3])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -1937,17 +2438,22 @@ else if name == "validate_format" {
         .build_call(fn_val, &[value_val.into(), field_val.into(), pattern_val.into(), errors_id_val.into()], "call_validate_format")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("validate_format must return an integer");
+    return Ok(call.try_as_basic_value().left().expect("validate_format must return an integer"));
 }
 
 else if name == "validate_numericality" {
     if args.len() != 3 {
-        panic!("validate_numericality() expects 3 arguments (value, field, errors_id)");
+        return Err(CompileError::ArgumentCount {
+            callee: "validate_numericality".to_string(),
+            expected: "3 arguments (value, field, errors_id)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let value_val = self.compile_expr(&args[0]);
-    let field_val = self.compile_expr(&args[1]);
-    let errors_id_val = self.compile_expr(&args[2]);
+    let value_val = self.compile_expr(&args[0])?;
+    let field_val = self.compile_expr(&args[1])?;
+    let errors_id_val = self.compile_expr(&args[2])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -1960,19 +2466,24 @@ else if name == "validate_numericality" {
         .build_call(fn_val, &[value_val.into(), field_val.into(), errors_id_val.into()], "call_validate_numericality")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("validate_numericality must return an integer");
+    return Ok(call.try_as_basic_value().left().expect("validate_numericality must return an integer"));
 }
 
 else if name == "validate_number_range" {
     if args.len() != 5 {
-        panic!("validate_number_range() expects 5 arguments (value, field, min, max, errors_id)");
+        return Err(CompileError::ArgumentCount {
+            callee: "validate_number_range".to_string(),
+            expected: "5 arguments (value, field, min, max, errors_id)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let value_val = self.compile_expr(&args[0]);
-    let field_val = self.compile_expr(&args[1]);
-    let min_val = self.compile_expr(&args[2]);
-    let max_val = self.compile_expr(&args[3]);
-    let errors_id_val = self.compile_expr(&args[4]);
+    let value_val = self.compile_expr(&args[0])?;
+    let field_val = self.compile_expr(&args[1])?;
+    let min_val = self.compile_expr(&args[2])?;
+    let max_val = self.compile_expr(&args[3])?;
+    let errors_id_val = self.compile_expr(&args[4])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -1985,18 +2496,23 @@ else if name == "validate_number_range" {
         .build_call(fn_val, &[value_val.into(), field_val.into(), min_val.into(), max_val.into(), errors_id_val.into()], "call_validate_number_range")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("validate_number_range must return an integer");
+    return Ok(call.try_as_basic_value().left().expect("validate_number_range must return an integer"));
 }
 
 else if name == "validate_inclusion" {
     if args.len() != 4 {
-        panic!("validate_inclusion() expects 4 arguments (value, field, allowed, errors_id)");
+        return Err(CompileError::ArgumentCount {
+            callee: "validate_inclusion".to_string(),
+            expected: "4 arguments (value, field, allowed, errors_id)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let value_val = self.compile_expr(&args[0]);
-    let field_val = self.compile_expr(&args[1]);
-    let allowed_val = self.compile_expr(&args[2]);
-    let errors_id_val = self.compile_expr(&args[3]);
+    let value_val = self.compile_expr(&args[0])?;
+    let field_val = self.compile_expr(&args[1])?;
+    let allowed_val = self.compile_expr(&args[2])?;
+    let errors_id_val = self.compile_expr(&args[3])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -2009,18 +2525,23 @@ else if name == "validate_inclusion" {
         .build_call(fn_val, &[value_val.into(), field_val.into(), allowed_val.into(), errors_id_val.into()], "call_validate_inclusion")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("validate_inclusion must return an integer");
+    return Ok(call.try_as_basic_value().left().expect("validate_inclusion must return an integer"));
 }
 
 else if name == "validate_exclusion" {
     if args.len() != 4 {
-        panic!("validate_exclusion() expects 4 arguments (value, field, forbidden, errors_id)");
+        return Err(CompileError::ArgumentCount {
+            callee: "validate_exclusion".to_string(),
+            expected: "4 arguments (value, field, forbidden, errors_id)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let value_val = self.compile_expr(&args[0]);
-    let field_val = self.compile_expr(&args[1]);
-    let forbidden_val = self.compile_expr(&args[2]);
-    let errors_id_val = self.compile_expr(&args[3]);
+    let value_val = self.compile_expr(&args[0])?;
+    let field_val = self.compile_expr(&args[1])?;
+    let forbidden_val = self.compile_expr(&args[2])?;
+    let errors_id_val = self.compile_expr(&args[3])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -2033,18 +2554,23 @@ else if name == "validate_exclusion" {
         .build_call(fn_val, &[value_val.into(), field_val.into(), forbidden_val.into(), errors_id_val.into()], "call_validate_exclusion")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("validate_exclusion must return an integer");
+    return Ok(call.try_as_basic_value().left().expect("validate_exclusion must return an integer"));
 }
 
 else if name == "validate_confirmation" {
     if args.len() != 4 {
-        panic!("validate_confirmation() expects 4 arguments (value, confirmation, field, errors_id)");
+        return Err(CompileError::ArgumentCount {
+            callee: "validate_confirmation".to_string(),
+            expected: "4 arguments (value, confirmation, field, errors_id)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let value_val = self.compile_expr(&args[0]);
-    let confirmation_val = self.compile_expr(&args[1]);
-    let field_val = self.compile_expr(&args[2]);
-    let errors_id_val = self.compile_expr(&args[3]);
+    let value_val = self.compile_expr(&args[0])?;
+    let confirmation_val = self.compile_expr(&args[1])?;
+    let field_val = self.compile_expr(&args[2])?;
+    let errors_id_val = self.compile_expr(&args[3])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -2057,17 +2583,22 @@ else if name == "validate_confirmation" {
         .build_call(fn_val, &[value_val.into(), confirmation_val.into(), field_val.into(), errors_id_val.into()], "call_validate_confirmation")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("validate_confirmation must return an integer");
+    return Ok(call.try_as_basic_value().left().expect("validate_confirmation must return an integer"));
 }
 
 else if name == "validate_acceptance" {
     if args.len() != 3 {
-        panic!("validate_acceptance() expects 3 arguments (value, field, errors_id)");
+        return Err(CompileError::ArgumentCount {
+            callee: "validate_acceptance".to_string(),
+            expected: "3 arguments (value, field, errors_id)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let value_val = self.compile_expr(&args[0]);
-    let field_val = self.compile_expr(&args[1]);
-    let errors_id_val = self.compile_expr(&args[2]);
+    let value_val = self.compile_expr(&args[0])?;
+    let field_val = self.compile_expr(&args[1])?;
+    let errors_id_val = self.compile_expr(&args[2])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -2080,15 +2611,20 @@ else if name == "validate_acceptance" {
         .build_call(fn_val, &[value_val.into(), field_val.into(), errors_id_val.into()], "call_validate_acceptance")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("validate_acceptance must return an integer");
+    return Ok(call.try_as_basic_value().left().expect("validate_acceptance must return an integer"));
 }
 
 else if name == "validation_is_blank" {
     if args.len() != 1 {
-        panic!("validation_is_blank() expects 1 argument (value)");
+        return Err(CompileError::ArgumentCount {
+            callee: "validation_is_blank".to_string(),
+            expected: "1 argument (value)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let value_val = self.compile_expr(&args[0]);
+    let value_val = self.compile_expr(&args[0])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -2101,15 +2637,20 @@ else if name == "validation_is_blank" {
         .build_call(fn_val, &[value_val.into()], "call_validation_is_blank")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("validation_is_blank must return an integer");
+    return Ok(call.try_as_basic_value().left().expect("validation_is_blank must return an integer"));
 }
 
 else if name == "validation_strlen" {
     if args.len() != 1 {
-        panic!("validation_strlen() expects 1 argument (value)");
+        return Err(CompileError::ArgumentCount {
+            callee: "validation_strlen".to_string(),
+            expected: "1 argument (value)".to_string(),
+            found: args.len(),
+            span: expr.span,
+        });
     }
 
-    let value_val = self.compile_expr(&args[0]);
+    let value_val = self.compile_expr(&args[0])?;
     let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
     let i32_ty = self.context.i32_type();
 
@@ -2122,22 +2663,23 @@ else if name == "validation_strlen" {
         .build_call(fn_val, &[value_val.into()], "call_validation_strlen")
         .unwrap();
 
-    return call.try_as_basic_value().left().expect("validation_strlen must return an integer");
+    return Ok(call.try_as_basic_value().left().expect("validation_strlen must return an integer"));
 }
 
-// === INDIRECT FUNCTION CALL (lambda stored in variable) ===
+// === INDIRECT FUNCTION CALL (closure stored in variable) ===
 if let Some(var_info) = self.vars.get(name) {
-    // === Load the function pointer ===
-    let fn_ptr = self.builder
-        .build_load(var_info.ty, var_info.ptr, &format!("load_fnptr_{}", name))
-        .unwrap()
-        .into_pointer_value();
+    // === Load the closure pointer ===
+    let signature = var_info.function_signature.clone();
+    let closure = match self.builder.build_load(var_info.ty, var_info.ptr, &format!("load_closure_{}", name)).unwrap() {
+        BasicValueEnum::PointerValue(closure) => closure,
+        _ => return Err(CompileError::type_mismatch(expr.span, format!("`{}` is not a function", name))),
+    };
 
     // === Compile argument expressions ===
     let compiled_args: Vec<BasicMetadataValueEnum<'ctx>> = args
         .iter()
-        .map(|arg| self.compile_expr(arg).into())
-        .collect();
+        .map(|arg| self.compile_expr(arg).map(Into::into))
+        .collect::<CompileResult<_>>()?;
 
     // === Infer argument types ===
     let param_types: Vec<BasicMetadataTypeEnum<'ctx>> = compiled_args
@@ -2152,7 +2694,7 @@ if let Some(var_info) = self.vars.get(name) {
                     self.i32_type.into()
                 }
             } else if ty.is_float_type() {
-                self.context.f32_type().into()
+                ty.into_float_type().into()
             } else if ty.is_pointer_type() {
                 self.context.i8_type().ptr_type(AddressSpace::default()).into()
             } else {
@@ -2161,8 +2703,10 @@ if let Some(var_info) = self.vars.get(name) {
         })
         .collect();
 
-    // === Infer return type dynamically ===
-    let ret_ty = if name.contains("f32") || name.contains("float") {
+    // === Return type: from the closure's signature when known, else by naming convention ===
+    let ret_ty = if let Some(sig) = &signature {
+        self.descriptor_value_type(&sig.return_type)
+    } else if name.contains("f32") || name.contains("float") {
         self.context.f32_type().as_basic_type_enum()
     } else if name.contains("bool") {
         self.context.bool_type().as_basic_type_enum()
@@ -2172,23 +2716,8 @@ if let Some(var_info) = self.vars.get(name) {
         self.i32_type.as_basic_type_enum()
     };
 
-    let fn_type = ret_ty.fn_type(&param_types, false);
-
     // === Build the indirect call ===
-    let call_site = self.builder
-        .build_indirect_call(fn_type, fn_ptr, &compiled_args, &format!("call_indirect_{}", name))
-        .unwrap();
-
-    // === Return proper type ===
-    return call_site
-        .try_as_basic_value()
-        .left()
-        .unwrap_or_else(|| match ret_ty {
-            BasicTypeEnum::IntType(i) => i.const_int(0, false).into(),
-            BasicTypeEnum::FloatType(f) => f.const_float(0.0).into(),
-            BasicTypeEnum::PointerType(p) => p.const_null().into(),
-            _ => self.i32_type.const_zero().into(),
-        });
+    return self.call_closure(closure, &compiled_args, &param_types, ret_ty, name);
 }
 
 
@@ -2196,118 +2725,21 @@ if let Some(var_info) = self.vars.get(name) {
     // 🧩 Collect argument TypeDescriptors for overload resolution
     use crate::ast::types::TypeDescriptor;
 
-    let arg_types: Vec<TypeDescriptor> = args
-        .iter()
-        .map(|a| {
-            match a {
-                Expr::StringLiteral(_) => TypeDescriptor::Primitive("string".to_string()),
-                Expr::Literal(num) => {
-                    // Check if it looks like an HTTP status code (100-599)
-                    if *num >= 100 && *num < 600 {
-                        TypeDescriptor::HttpStatusLiteral(*num as u16)
-                    } else {
-                        TypeDescriptor::Primitive("i32".to_string())
-                    }
-                }
-                Expr::TypedLiteral { value, ty } if ty == "f64" => TypeDescriptor::Primitive("f64".to_string()),
-                Expr::TypedLiteral { value, ty } if ty.starts_with("i") => {
-                    // Check if it's an HTTP status code
-                    if let Ok(num) = value.parse::<i32>() {
-                        if num >= 100 && num < 600 {
-                            TypeDescriptor::HttpStatusLiteral(num as u16)
-                        } else {
-                            TypeDescriptor::Primitive("i32".to_string())
-                        }
-                    } else {
-                        TypeDescriptor::Primitive("i32".to_string())
-                    }
-                }
-                Expr::Variable(var_name) => {
-                    // Check if it's a known function for higher-order dispatch
-                    if let Some(sigs) = self.reverse_func_index.get(var_name) {
-                        if let Some(sig) = sigs.first() {
-                            // Create Function TypeDescriptor from function signature
-                            return TypeDescriptor::Function {
-                                param_types: sig.param_types.clone(),
-                                return_type: Box::new(sig.return_type.clone()),
-                            };
-                        }
-                    }
-
-                    // Check if it's a known variable (local or global)
-                    if let Some(var_info) = self.vars.get(var_name).or_else(|| self.globals.get(var_name)) {
-                        // 🎯 Check for function signature first (higher-order dispatch)
-                        if let Some(fn_sig) = &var_info.function_signature {
-                            return TypeDescriptor::Function {
-                                param_types: fn_sig.param_types.clone(),
-                                return_type: Box::new(fn_sig.return_type.clone()),
-                            };
-                        }
-
-                        // 🎯 Check for entity or object type
-                        if let Some(entity_name) = &var_info.entity_type {
-                            return TypeDescriptor::Entity(entity_name.clone());
-                        }
-                        if let Some(obj_type_name) = &var_info.object_type_name {
-                            return TypeDescriptor::ObjectType(obj_type_name.clone());
-                        }
-
-                        // Fall back to LLVM type inference
-                        let var_ty = &var_info.ty;
-                        if var_ty.is_int_type() {
-                            TypeDescriptor::Primitive("i32".to_string())
-                        } else if var_ty.is_pointer_type() {
-                            // Treat all pointers as strings for now (i8*)
-                            TypeDescriptor::Primitive("string".to_string())
-                        } else if var_ty.is_float_type() {
-                            TypeDescriptor::Primitive("f64".to_string())
-                        } else {
-                            TypeDescriptor::Primitive("i32".to_string())
-                        }
-                    } else {
-                        TypeDescriptor::Primitive("i32".to_string())
-                    }
-                }
-                Expr::BinaryOp { left, right, .. } => {
-                    let l = self.infer_expr_type(left);
-                    let r = self.infer_expr_type(right);
-                    if l.is_float_type() || r.is_float_type() {
-                        TypeDescriptor::Primitive("f64".to_string())
-                    } else {
-                        TypeDescriptor::Primitive("i32".to_string())
-                    }
-                }
-                Expr::NewInstance { entity, .. } => {
-                    // Instance creation returns an entity type
-                    TypeDescriptor::Entity(entity.clone())
-                }
-                Expr::ObjectLiteral { type_name, .. } => {
-                    // If object has a type name, use it for dispatch
-                    if let Some(obj_type) = type_name {
-                        TypeDescriptor::ObjectType(obj_type.clone())
-                    } else {
-                        TypeDescriptor::Primitive("string".to_string())
-                    }
-                }
-                _ => TypeDescriptor::Primitive("i32".to_string()),
-            }
-        })
-        .collect();
+    let arg_types: Vec<TypeDescriptor> = args.iter().map(|a| self.call_arg_type(a)).collect();
 
     wpp_debug!("💡 Inferred arg types for {}: {:?}", name, arg_types);
 
 // 🧠 Normalize for compatible overloads (f64 → f32, ptr → string, etc.)
-let normalized_arg_types: Vec<TypeDescriptor> = arg_types
-    .iter()
-    .map(|t| match t {
-        TypeDescriptor::Primitive(ty) if ty == "f64" => TypeDescriptor::Primitive("f32".to_string()),
-        TypeDescriptor::Primitive(ty) if ty == "ptr" => TypeDescriptor::Primitive("string".to_string()),
-        _ => t.clone(),
-    })
-    .collect();
+let normalized_arg_types = Self::normalized_arg_types(&arg_types);
 
 wpp_debug!("💡 Normalized arg types for {}: {:?}", name, normalized_arg_types);
 
+// 🎲 Arguments whose type is only known at runtime go through a dispatcher stub
+let dynamic = self.dynamic_args(name, args, &arg_types);
+if dynamic.iter().any(Option::is_some) {
+    return self.compile_dispatch_call(name, args, &arg_types, &dynamic, expr.span);
+}
+
 // 🕵️ Find best match using specificity ranking
 let mut sig_opt = None;
 if let Some(sigs) = self.reverse_func_index.get(name) {
@@ -2319,7 +2751,7 @@ if let Some(sigs) = self.reverse_func_index.get(name) {
     // If no exact match, use specificity-based selection
     if sig_opt.is_none() {
         // Find all compatible signatures and rank by specificity
-        let mut candidates: Vec<(&FunctionSignature, u32)> = sigs
+        let mut candidates: Vec<(&FunctionSignature, (u32, std::cmp::Reverse<u32>))> = sigs
             .iter()
             .filter_map(|sig| {
                 // Check if signature is compatible with arg_types
@@ -2327,53 +2759,31 @@ if let Some(sigs) = self.reverse_func_index.get(name) {
                     return None;
                 }
 
-                let mut is_compatible = true;
-                let mut total_specificity = 0u32;
-
-                for (sig_type, arg_type) in sig.param_types.iter().zip(arg_types.iter()) {
-                    let matches = match (sig_type, arg_type) {
-                        // Exact match
-                        (a, b) if a == b => true,
-                        // Any wildcard matches anything
-                        (TypeDescriptor::Any, _) => true,
-                        // HTTP status range matches literal in range
-                        (TypeDescriptor::HttpStatusRange(min, max), TypeDescriptor::HttpStatusLiteral(code)) => {
-                            *code >= *min && *code <= *max
-                        }
-                        // String and ptr are compatible
-                        (TypeDescriptor::Primitive(s1), TypeDescriptor::Primitive(s2))
-                            if (s1 == "string" || s1 == "ptr") && (s2 == "string" || s2 == "ptr") => true,
-                        // Normalized match (try normalized_arg_types)
-                        _ => {
-                            if let Some(normalized) = normalized_arg_types.get(sig.param_types.iter().position(|t| t == sig_type)?) {
-                                sig_type == normalized
-                            } else {
-                                false
-                            }
-                        }
-                    };
-
-                    if !matches {
-                        is_compatible = false;
-                        break;
-                    }
-
-                    total_specificity += sig_type.specificity();
-                }
+                let is_compatible = sig
+                    .param_types
+                    .iter()
+                    .zip(arg_types.iter())
+                    .zip(args)
+                    .all(|((sig_type, arg_type), arg)| self.static_arg_matches(sig, sig_type, arg_type, arg, &normalized_arg_types));
 
-                if is_compatible {
-                    Some((sig, total_specificity))
-                } else {
-                    None
-                }
+                is_compatible.then(|| (sig, self.overload_rank(sig, &arg_types)))
             })
             .collect();
 
         if !candidates.is_empty() {
-            // Sort by specificity (descending - higher is more specific)
+            // Sort by rank (descending - higher is more specific)
             candidates.sort_by(|a, b| b.1.cmp(&a.1));
+            let tied: Vec<&FunctionSignature> = candidates.iter().filter(|c| c.1 == candidates[0].1).map(|c| c.0).collect();
+            if tied.len() > 1 {
+                return Err(CompileError::AmbiguousCall {
+                    name: name.clone(),
+                    arg_types: arg_types.iter().map(|t| t.to_mangle_string()).collect(),
+                    candidates: tied.into_iter().map(crate::checker::describe_signature).collect(),
+                    span: expr.span,
+                });
+            }
             sig_opt = Some(candidates[0].0.clone());
-            wpp_debug!("🎯 Selected overload by specificity: {:?} (score: {})", sig_opt.as_ref().unwrap().param_types, candidates[0].1);
+            wpp_debug!("🎯 Selected overload by specificity: {:?} (score: {:?})", sig_opt.as_ref().unwrap().param_types, candidates[0].1);
         }
     }
 }
@@ -2390,24 +2800,15 @@ if let Some(sig) = sig_opt {
     // Compile arguments
     let mut compiled_args: Vec<BasicMetadataValueEnum<'ctx>> = Vec::new();
     for a in args {
-        let v = self.compile_expr(a);
+        let v = self.compile_expr(a)?;
         compiled_args.push(v.into());
     }
 
     wpp_debug!("💥 Resolved call {}({:?})", sig.name, sig.param_types);
 
     // Call the correct overload (mangled name)
-    let llvm_name = if sig.param_types.is_empty() {
-        sig.name.clone()
-    } else {
-        let mangled_types: Vec<String> = sig.param_types.iter()
-            .map(|td| td.to_mangle_string())
-            .collect();
-        format!("{}__{}", sig.name, mangled_types.join("_"))
-    };
-
-    let target_fn = self.module.get_function(&llvm_name).unwrap_or(func_val);
-    wpp_debug!("🧬 Using LLVM function: {}", llvm_name);
+    let target_fn = self.overload_function(&sig).unwrap_or(func_val);
+    wpp_debug!("🧬 Using LLVM function: {:?}", target_fn.get_name());
     // 🧠 Ensure argument types match function signature
 let fn_param_types: Vec<_> = target_fn.get_type().get_param_types();
 
@@ -2423,63 +2824,19 @@ if arg_ty_basic != param_ty_basic {
     // handle type conversion
 
 
-    // Handle float double → float
-    if arg_ty.is_float_type() && param_ty.is_float_type() {
-        let arg_float_ty = arg_ty.into_float_type();
-        let param_float_ty = param_ty.into_float_type();
-
-        // Check if it’s actually f64 → f32
-       let f32_ty = self.context.f32_type();
-let f64_ty = self.context.f64_type();
-
-if arg_float_ty == f64_ty && param_float_ty == f32_ty {
-let float_val = arg_val
-    .clone()
-    .into_float_value(); // BasicMetadataValueEnum already implements into_float_value
-
-let casted = self
-    .builder
-    .build_float_cast(
-        float_val,
-        param_float_ty,
-        &format!("cast_f64_to_f32_arg{}", i),
-    )
-    .unwrap();
-
-*arg_val = casted.into();
-
-
-
-
-        }
-    }
-
-    // Handle i1 → i32 (bool promotion)
-    else if arg_ty.is_int_type()
-        && param_ty.is_int_type()
-        && arg_ty.into_int_type().get_bit_width() == 1
-        && param_ty.into_int_type().get_bit_width() == 32
-    {
-        // Convert argument to an IntValue safely
-let int_val = arg_val
-    .clone()
-    .into_int_value(); // works for BasicMetadataValueEnum that holds int
-
-// Perform zero-extend from i1 → i32
-let casted = self
-    .builder
-    .build_int_z_extend(
-        int_val,
-        param_ty.into_int_type(),
-        &format!("bool_to_i32_arg{}", i),
-    )
-    .unwrap();
-
-// Replace the original arg with the extended version
-*arg_val = casted.into();
-
-
-
+    // Numbers convert to the parameter's type (`f64` to `f32`, a bool or `i32` to `i64`)
+    let is_number = |t: inkwell::types::AnyTypeEnum| t.is_int_type() || t.is_float_type();
+    if is_number(arg_ty) && is_number(param_ty.as_any_type_enum()) {
+        let val = BasicValueEnum::try_from(*arg_val).unwrap();
+        let from = args
+            .get(i)
+            .and_then(|arg| self.static_field_type(arg))
+            .unwrap_or_else(|| Self::field_type_of_llvm(val.get_type()));
+        let to = match sig.param_types.get(i) {
+            Some(TypeDescriptor::Primitive(ty)) if FieldType::from_string(ty).is_numeric() => FieldType::from_string(ty),
+            _ => Self::field_type_of_llvm(*param_ty),
+        };
+        *arg_val = self.convert_numeric(val, &from, &to).into();
     }
 }
 
@@ -2489,13 +2846,18 @@ let casted = self
         .builder
         .build_call(target_fn, &compiled_args, &format!("call_{}", sig.name))
         .unwrap();
+    self.check_exception()?;
 
     call_site
         .try_as_basic_value()
         .left()
         .unwrap_or_else(|| self.i32_type.const_int(0, false).into())
 } else {
-    panic!("❌ No matching overload for {}({:?})", name, arg_types);
+    return Err(CompileError::NoMatchingOverload {
+        name: name.clone(),
+        arg_types: arg_types.iter().map(|t| t.to_mangle_string()).collect(),
+        span: expr.span,
+    });
 }
 
 }
@@ -2519,59 +2881,118 @@ else if name.contains('.') {
                 .builder
                 .build_call(*func, &[], &format!("call_{}", name))
                 .unwrap();
-            return call_site
+            self.check_exception()?;
+            return Ok(call_site
                 .try_as_basic_value()
                 .left()
-                .unwrap_or_else(|| self.i32_type.const_int(0, false).into());
+                .unwrap_or_else(|| self.i32_type.const_int(0, false).into()));
         }
 
-       // === 2️⃣ Case B: Instance method call (e.g. d.bark)
-if let Some(var_info) = self.vars.get(lhs) {
-    if let Some(entity_type) = &var_info.entity_type {
-        let resolved = format!("{}.{}", entity_type, method_name);
-        wpp_debug!("🔍 Resolved instance call '{}.{}' -> '{}'", lhs, method_name, resolved);
+       // === 2️⃣ Case B: Parent implementation (e.g. super.speak inside Dog)
+if lhs == "super" {
+    let me = self
+        .vars
+        .get("me")
+        .filter(|var| var.entity_type.is_some())
+        .cloned()
+        .ok_or_else(|| CompileError::unsupported(expr.span, "`super` can only be used inside entity methods".to_string()))?;
+    let entity_type = me.entity_type.clone().unwrap();
+    let base = self
+        .entities
+        .get(&entity_type)
+        .and_then(|info| info.base.clone())
+        .ok_or_else(|| CompileError::unsupported(expr.span, format!("`{}` does not alter another entity, so it has no `super`", entity_type)))?;
+    let Some((owner, func)) = self.resolve_method(&base, method_name) else {
+        return Err(CompileError::UnknownFunction { name: format!("{}.{}", base, method_name), span: expr.span });
+    };
 
-        let sig = FunctionSignature {
-            name: resolved.clone(),
-            param_types: vec![],
-            return_type: TypeDescriptor::Primitive("i32".to_string()), // Default return type
+    let instance_ptr = self.builder.build_load(me.ty, me.ptr, "me_load").unwrap().into_pointer_value();
+    let values = self.compile_typed_args(args)?;
+    let call_args = self.method_call_args(func, instance_ptr, &values);
+    let call_site = self
+        .builder
+        .build_call(func, &call_args, &format!("call_super_{}_{}", owner, method_name))
+        .unwrap();
+    self.check_exception()?;
+    return Ok(call_site
+        .try_as_basic_value()
+        .left()
+        .unwrap_or_else(|| self.i32_type.const_int(0, false).into()));
+}
+
+       // === 3️⃣ Case C: Instance method call (e.g. d.bark), dispatched through the vtable
+if let Some(var_info) = self.vars.get(lhs).or_else(|| self.globals.get(lhs)).cloned()
+    && let Some(entity_type) = var_info.entity_type.clone()
+{
+    wpp_debug!("🔍 Resolved instance call '{}.{}' on entity '{}'", lhs, method_name, entity_type);
+    if let Some(interface) = self.interfaces.get(&entity_type) {
+        let unknown = CompileError::UnknownFunction {
+            name: format!("{}.{}", entity_type, method_name),
+            span: expr.span,
+        };
+        if !interface.methods.iter().any(|m| m.name == *method_name) {
+            return Err(unknown);
+        }
+        let instance_ptr = self
+            .builder
+            .build_load(var_info.ty, var_info.ptr, &format!("{}_load", lhs))
+            .unwrap()
+            .into_pointer_value();
+        // Nothing implements the interface, so no value of this type can exist
+        let Some((func, method_ptr)) = self.interface_method(instance_ptr, &entity_type, method_name) else {
+            return Err(unknown);
         };
+        let values = self.compile_typed_args(args)?;
+        let call_args = self.method_call_args(func, instance_ptr, &values);
+        let call_site = self
+            .builder
+            .build_indirect_call(func.get_type(), method_ptr, &call_args, &format!("call_{}", method_name))
+            .unwrap();
+        self.check_exception()?;
+        return Ok(call_site
+            .try_as_basic_value()
+            .left()
+            .unwrap_or_else(|| self.i32_type.const_int(0, false).into()));
+    }
+    let Some((_, func)) = self.resolve_method(&entity_type, method_name) else {
+        return Err(CompileError::UnknownFunction {
+            name: format!("{}.{}", entity_type, method_name),
+            span: expr.span,
+        });
+    };
 
-        if let Some(func) = self.functions.get(&sig) {
     // ✅ Load the pointer stored in variable `lhs`
     let instance_ptr = self
         .builder
-        .build_load(
-            var_info.ty,             // <-- the type of what we're loading
-            var_info.ptr,            // <-- the pointer variable itself
-            &format!("{}_load", lhs) // <-- name for IR
-        )
-        .unwrap();
+        .build_load(var_info.ty, var_info.ptr, &format!("{}_load", lhs))
+        .unwrap()
+        .into_pointer_value();
 
-    // ✅ Pass `me` as the first argument
-    let args: Vec<BasicMetadataValueEnum> = vec![instance_ptr.into()];
+    // ✅ Pass `me` as the first argument, then the call's own arguments
+    let values = self.compile_typed_args(args)?;
+    let call_args = self.method_call_args(func, instance_ptr, &values);
 
-    let call_site = self
-        .builder
-        .build_call(*func, &args, &format!("call_{}", resolved))
-        .unwrap();
+    // The statically known implementation fixes the signature; the instance's vtable picks the body
+    let call_site = match self.virtual_method(instance_ptr, &entity_type, method_name, func) {
+        Some(method_ptr) => self
+            .builder
+            .build_indirect_call(func.get_type(), method_ptr, &call_args, &format!("call_{}", method_name))
+            .unwrap(),
+        None => self
+            .builder
+            .build_call(func, &call_args, &format!("call_{}", method_name))
+            .unwrap(),
+    };
+    self.check_exception()?;
 
-    return call_site
+    return Ok(call_site
         .try_as_basic_value()
         .left()
-        .unwrap_or_else(|| self.i32_type.const_int(0, false).into());
-}
-else {
-            panic!(
-                "Method '{}' not found for entity type '{}'",
-                method_name, entity_type
-            );
-        }
-    }
+        .unwrap_or_else(|| self.i32_type.const_int(0, false).into()));
 }
 
 
-        // === 3️⃣ Case C: Fallback search by unqualified name
+        // === 4️⃣ Case D: Fallback search by unqualified name
         for (sig, func) in &self.functions {
             if sig.name == method_name {
                 wpp_debug!("🔗 Fallback matched unqualified {}.{}", lhs, method_name);
@@ -2579,15 +3000,16 @@ else {
                     .builder
                     .build_call(*func, &[], &format!("call_{}", method_name))
                     .unwrap();
-                return call_site
+                self.check_exception()?;
+                return Ok(call_site
                     .try_as_basic_value()
                     .left()
-                    .unwrap_or_else(|| self.i32_type.const_int(0, false).into());
+                    .unwrap_or_else(|| self.i32_type.const_int(0, false).into()));
             }
         }
     }
 
-    panic!("Unknown entity-qualified function: {}", name);
+    return Err(CompileError::UnknownFunction { name: name.clone(), span: expr.span });
 }
 
 
@@ -2603,16 +3025,16 @@ else {
                 if func_opt.is_none() {
                     // Infer argument types to construct mangled name
                     let arg_type_strs: Vec<String> = args.iter().map(|arg| {
-                        match arg {
-                            Expr::StringLiteral(_) => "string".to_string(),
-                            Expr::TypedLiteral { ty, .. } => {
+                        match &arg.kind {
+                            ExprKind::StringLiteral(_) => "string".to_string(),
+                            ExprKind::TypedLiteral { ty, .. } => {
                                 match ty.as_str() {
                                     "string" | "ptr" => "string".to_string(),
                                     "float" | "f64" => "f32".to_string(),
                                     other => other.to_string(),
                                 }
                             }
-                            Expr::Variable(v) => {
+                            ExprKind::Variable(v) => {
                                 if let Some(var_info) = self.vars.get(v) {
                                     if var_info.ty.is_pointer_type() {
                                         "string".to_string()
@@ -2642,7 +3064,7 @@ else {
                     // Compile arguments
                     let mut compiled_args: Vec<BasicMetadataValueEnum<'ctx>> = Vec::new();
                     for a in args {
-                        let v = self.compile_expr(a);
+                        let v = self.compile_expr(a)?;
                         compiled_args.push(v.into());
                     }
 
@@ -2651,20 +3073,21 @@ else {
                         .builder
                         .build_call(func, &compiled_args, &format!("call_{}", name))
                         .unwrap();
+                    self.check_exception()?;
 
-                    return call_site
+                    return Ok(call_site
                         .try_as_basic_value()
                         .left()
-                        .unwrap_or_else(|| self.i32_type.const_int(0, false).into());
+                        .unwrap_or_else(|| self.i32_type.const_int(0, false).into()));
                 } else {
-                    panic!("Unknown function: {}", name);
+                    return Err(CompileError::UnknownFunction { name: name.clone(), span: expr.span });
                 }
             }
         }
 
         // === If expression ===
-        Expr::If { cond, then_branch, else_branch } => {
-    let cond_val = self.compile_expr(cond.as_ref());
+        ExprKind::If { cond, then_branch, else_branch } => {
+    let cond_val = self.compile_expr(cond.as_ref())?;
     let cond_i1 = match cond_val {
         BasicValueEnum::IntValue(iv) => {
             if iv.get_type().get_bit_width() == 1 {
@@ -2680,7 +3103,7 @@ else {
                     .unwrap()
             }
         }
-        _ => panic!("Condition in if must be int or bool"),
+        _ => return Err(CompileError::type_mismatch(cond.span, "condition in `if` must be int or bool")),
     };
 
     // Create blocks
@@ -2694,9 +3117,7 @@ else {
 
     // THEN branch
     self.builder.position_at_end(then_bb);
-    for node in then_branch {
-        self.compile_node(node);
-    }
+    self.compile_block(then_branch)?;
     if self.builder.get_insert_block().unwrap().get_terminator().is_none() {
         self.builder.build_unconditional_branch(end_bb).unwrap();
     }
@@ -2704,9 +3125,7 @@ else {
     // ELSE branch
     self.builder.position_at_end(else_bb);
     if let Some(else_nodes) = else_branch {
-        for node in else_nodes {
-            self.compile_node(node);
-        }
+        self.compile_block(else_nodes)?;
     }
     if self.builder.get_insert_block().unwrap().get_terminator().is_none() {
         self.builder.build_unconditional_branch(end_bb).unwrap();
@@ -2722,7 +3141,7 @@ else {
 
 
 
-        Expr::While { cond, body } => {
+        ExprKind::While { cond, body } => {
     let func = self.builder.get_insert_block().unwrap().get_parent().unwrap();
 
     // === Define core blocks ===
@@ -2732,14 +3151,14 @@ else {
     let after_bb = self.context.append_basic_block(func, "after_loop");
 
     // Push (continue_target, break_target) → continue jumps to cond_bb directly
-    self.loop_stack.push((cond_bb, end_bb));
+    self.loop_stack.push((cond_bb, end_bb, self.defers.len()));
 
     // Jump to condition first
     self.builder.build_unconditional_branch(cond_bb).unwrap();
 
     // === Condition ===
     self.builder.position_at_end(cond_bb);
-    let cond_val = self.compile_expr(cond);
+    let cond_val = self.compile_expr(cond)?;
     let cond_i1 = match cond_val {
         BasicValueEnum::IntValue(iv) if iv.get_type().get_bit_width() == 1 => iv,
         BasicValueEnum::IntValue(iv) => self.builder
@@ -2750,7 +3169,7 @@ else {
                 "while_cond_cmp"
             )
             .unwrap(),
-        _ => panic!("while condition must be int/bool"),
+        _ => return Err(CompileError::type_mismatch(cond.span, "condition in `while` must be int or bool")),
     };
 
     // Branch to body or exit
@@ -2758,9 +3177,7 @@ else {
 
     // === Body ===
     self.builder.position_at_end(body_bb);
-    for stmt in body {
-        self.compile_node(stmt);
-    }
+    self.compile_block(body)?;
 
     // If body didn’t end with a terminator (break/continue), loop back to condition
     self.safe_branch(cond_bb);
@@ -2784,9 +3201,9 @@ else {
 
 
 
-Expr::For { init, cond, post, body } => {
+ExprKind::For { init, cond, post, body } => {
     if let Some(init_node) = init {
-        self.compile_node(init_node);
+        self.compile_node(init_node)?;
     }
 
     let func = self.builder.get_insert_block().unwrap().get_parent().unwrap();
@@ -2796,7 +3213,7 @@ Expr::For { init, cond, post, body } => {
     let end_bb  = self.context.append_basic_block(func, "for_end");
 
     // 🧩 Push loop context (for break/continue)
-    self.loop_stack.push((post_bb, end_bb));
+    self.loop_stack.push((post_bb, end_bb, self.defers.len()));
 
     // === Jump to condition
     self.builder.build_unconditional_branch(cond_bb).unwrap();
@@ -2804,7 +3221,7 @@ Expr::For { init, cond, post, body } => {
     // === Condition block
     self.builder.position_at_end(cond_bb);
     let cond_val = if let Some(c) = cond {
-        self.compile_expr(c).into_int_value()
+        self.compile_expr(c)?.into_int_value()
     } else {
         self.context.bool_type().const_int(1, false)
     };
@@ -2812,9 +3229,7 @@ Expr::For { init, cond, post, body } => {
 
     // === Body block
     self.builder.position_at_end(body_bb);
-    for stmt in body {
-        self.compile_node(stmt);
-    }
+    self.compile_block(body)?;
 
     // If body didn’t end with break/continue, jump to post
     if self.builder.get_insert_block().unwrap().get_terminator().is_none() {
@@ -2824,21 +3239,21 @@ Expr::For { init, cond, post, body } => {
     // === Post block
     self.builder.position_at_end(post_bb);
     if let Some(post_expr) = post {
-    match post_expr.as_ref() {
-        Expr::BinaryOp { left, op, right } if op == "=" => {
-            if let Expr::Variable(var_name) = left.as_ref() {
-                let value = self.compile_expr(right.as_ref());
+    match &post_expr.kind {
+        ExprKind::BinaryOp { left, op, right } if op == "=" => {
+            if let ExprKind::Variable(var_name) = &left.kind {
+                let value = self.compile_expr(right.as_ref())?;
                 if let Some(var) = self.vars.get(var_name) {
     if var.is_const {
-        panic!("❌ Cannot assign to constant variable '{}'", var_name);
+        return Err(CompileError::AssignToConst { name: var_name.clone(), span: left.span });
     }
     self.builder.build_store(var.ptr, value).unwrap();
                 }
             }
         }
         _ => {
-            // ✅ Wrap Expr in Node::Expr to reuse compile_node
-            self.compile_node(&Node::Expr(post_expr.as_ref().clone()));
+            // ✅ Wrap Expr in NodeKind::Expr to reuse compile_node
+            self.compile_node(&Node::from(post_expr.as_ref().clone()))?;
         }
     }
 }
@@ -2867,15 +3282,26 @@ Expr::For { init, cond, post, body } => {
 
 
 
-Expr::Break => {
-    if let Some((_, break_target)) = self.loop_stack.last() {
+ExprKind::ForIn { key, item, iterable, body } => {
+    self.compile_for_in(key.as_deref(), item, iterable, body)?;
+    self.i32_type.const_int(0, false).into()
+}
+
+ExprKind::Range { .. } => {
+    return Err(CompileError::unsupported(expr.span, "a range can only be used as the iterable of `for ... in`"));
+}
+
+ExprKind::Break => {
+    if let Some(&(_, break_target, depth)) = self.loop_stack.last() {
         // Break inside loop
-        self.safe_branch(*break_target);
-    } else if let Some(switch_end) = self.switch_stack.last() {
+        self.run_deferred(depth)?;
+        self.safe_branch(break_target);
+    } else if let Some(&(switch_end, depth)) = self.switch_stack.last() {
         // ✅ Break inside switch
-        self.safe_branch(*switch_end);
+        self.run_deferred(depth)?;
+        self.safe_branch(switch_end);
     } else {
-        panic!("'break' used outside of loop or switch");
+        return Err(CompileError::OutsideLoop { keyword: "break".to_string(), span: expr.span });
     }
 
     // Move to dummy block after break
@@ -2887,180 +3313,56 @@ Expr::Break => {
 }
 
 
-Expr::Continue => {
-    if let Some((cont_target, _)) = self.loop_stack.last() {
+ExprKind::Continue => {
+    if let Some(&(cont_target, _, depth)) = self.loop_stack.last() {
         // Jump to continue target
-        self.builder.build_unconditional_branch(*cont_target).unwrap();
+        self.run_deferred(depth)?;
+        self.builder.build_unconditional_branch(cont_target).unwrap();
 
         // ✅ Move builder to a new dummy unreachable block (so codegen continues safely)
         let func = self.builder.get_insert_block().unwrap().get_parent().unwrap();
         let unreachable_block = self.context.append_basic_block(func, "after_continue_unreachable");
         self.builder.position_at_end(unreachable_block);
     } else {
-        panic!("'continue' used outside of loop");
+        return Err(CompileError::OutsideLoop { keyword: "continue".to_string(), span: expr.span });
     }
 
     self.i32_type.const_int(0, false).into()
 }
 
 
-Expr::Switch { expr, cases, default } => {
-    self.compile_switch(expr, cases, default)
+ExprKind::Switch { expr, cases, default } => {
+    self.compile_switch(expr, cases, default)?
 }
-Expr::Throw { expr } => {
-    // Evaluate the thrown expression
-    let value = self.compile_expr(expr);
-
-    // Determine if it’s a string (pointer) or integer
-    match value {
-        BasicValueEnum::PointerValue(pv) => {
-            // Throwing a string: store string in exception_value_str
-            if let Some(val_str) = self.exception_value_str {
-                self.builder.build_store(val_str, pv).unwrap();
-            } else {
-                panic!("throw: no active string exception slot");
-            }
-
-            // Set flag to 1 (string-type exception)
-            if let Some(flag) = self.exception_flag {
-                self.builder.build_store(flag, self.i32_type.const_int(2, false)).unwrap();
-            }
-        }
-
-        BasicValueEnum::IntValue(iv) => {
-            // Throwing an integer: store in exception_value_i32
-            if let Some(val_i32) = self.exception_value_i32 {
-                self.builder.build_store(val_i32, iv).unwrap();
-            } else {
-                panic!("throw: no active int exception slot");
-            }
-
-            // Set flag to 1 (integer-type exception)
-            if let Some(flag) = self.exception_flag {
-                self.builder.build_store(flag, self.i32_type.const_int(1, false)).unwrap();
-            }
-        }
-
-        _ => panic!("Unsupported throw type"),
-    }
+ExprKind::Throw { expr: thrown } => {
+    // Any value can be thrown; it travels as an `any` box so `catch` can test its type
+    let (value, ty) = self.compile_typed(thrown)?;
+    let boxed = match ty {
+        FieldType::Any => value.into_pointer_value(),
+        ty => self.box_any(value, &ty)?,
+    };
+    self.builder.build_call(self.runtime_fn("wpp_throw")?, &[boxed.into()], "").unwrap();
+    let target = self.unwind_target()?;
+    self.builder.build_unconditional_branch(target).unwrap();
 
-    // Jump directly to end of try block — we’ll let TryCatch handle propagation
+    // ✅ Code after the throw is unreachable
     let func = self.builder.get_insert_block().unwrap().get_parent().unwrap();
-    let end_bb = self.context.append_basic_block(func, "throw_exit");
-    self.builder.build_unconditional_branch(end_bb).unwrap();
-    self.builder.position_at_end(end_bb);
-
-    // Return dummy value
+    let after_throw = self.context.append_basic_block(func, "after_throw");
+    self.builder.position_at_end(after_throw);
     self.i32_type.const_int(0, false).into()
 }
 
-
-
-
-Expr::TryCatch { try_block, catch_var, catch_block, finally_block } => {
-    let func = self.builder.get_insert_block().unwrap().get_parent().unwrap();
-
-    // === Create blocks ===
-    let try_bb = self.context.append_basic_block(func, "try_block");
-    let catch_bb = self.context.append_basic_block(func, "catch_block");
-    let finally_bb = self.context.append_basic_block(func, "finally_block");
-    let end_bb = self.context.append_basic_block(func, "try_end");
-
-    // === Preserve old exception state ===
-    let old_flag = self.exception_flag;
-    let old_val_i32 = self.exception_value_i32;
-    let old_val_str = self.exception_value_str;
-
-    // === Allocate local exception slots ===
-    let flag_ptr = self.builder.build_alloca(self.i32_type, "exc_flag_local").unwrap();
-    self.builder.build_store(flag_ptr, self.i32_type.const_int(0, false)).unwrap();
-
-    let val_i32_ptr = self.builder.build_alloca(self.i32_type, "exc_val_i32_local").unwrap();
-    self.builder.build_store(val_i32_ptr, self.i32_type.const_int(0, false)).unwrap();
-
-    let str_ptr_ty = self.context.i8_type().ptr_type(AddressSpace::default());
-    let val_str_ptr = self.builder.build_alloca(str_ptr_ty, "exc_val_str_local").unwrap();
-    self.builder.build_store(val_str_ptr, str_ptr_ty.const_null()).unwrap();
-
-    self.exception_flag = Some(flag_ptr);
-    self.exception_value_i32 = Some(val_i32_ptr);
-    self.exception_value_str = Some(val_str_ptr);
-
-    // === Jump into try ===
-    self.builder.build_unconditional_branch(try_bb).unwrap();
-
-    // --- TRY block ---
-    self.builder.position_at_end(try_bb);
-    for node in try_block {
-        self.compile_node(node);
-    }
-
-    // Read flag
-    let flag_val = self.builder.build_load(self.i32_type, flag_ptr, "flag").unwrap().into_int_value();
-    let cond = self.builder.build_int_compare(
-        inkwell::IntPredicate::EQ,
-        flag_val,
-        self.i32_type.const_int(1, false),
-        "is_throw",
-    ).unwrap();
-
-    self.builder.build_conditional_branch(cond, catch_bb, finally_bb).unwrap();
-
-    // --- CATCH block ---
-self.builder.position_at_end(catch_bb);
-if let Some(var) = catch_var {
-    let str_ty = self.context.i8_type().ptr_type(AddressSpace::default());
-    let alloca = self.builder.build_alloca(str_ty, var).unwrap();
-    let val_str = self.builder.build_load(str_ty, val_str_ptr, "ex_str").unwrap();
-    self.builder.build_store(alloca, val_str).unwrap();
-    self.vars.insert(
-    var.clone(),
-    VarInfo {
-        ptr: alloca,
-        ty: str_ty.as_basic_type_enum(),
-        is_const: false,
-        is_thread_state: false,
-        entity_type: None,
-        object_type_name: None,
-        function_signature: None,
-    },
-);
-
-}
-for node in catch_block {
-    self.compile_node(node);
-}
-// reset flag after catch
-self.builder.build_store(flag_ptr, self.i32_type.const_int(0, false)).unwrap();
-
-// 🔹 instead of jumping directly to finally, go to a neutral after_catch block
-let after_catch_bb = self.context.append_basic_block(func, "after_catch");
-self.builder.build_unconditional_branch(after_catch_bb).unwrap();
-
-// --- AFTER_CATCH -> FINALLY ---
-self.builder.position_at_end(after_catch_bb);
-self.builder.build_unconditional_branch(finally_bb).unwrap();
-
-// --- FINALLY block ---
-self.builder.position_at_end(finally_bb);
-if let Some(fb) = finally_block {
-    for node in fb {
-        self.compile_node(node);
+ExprKind::Defer(deferred) => {
+    match self.defers.last_mut() {
+        Some(block) => block.push((**deferred).clone()),
+        None => return Err(CompileError::unsupported(expr.span, "`defer` can only be used inside a block")),
     }
+    self.i32_type.const_int(0, false).into()
 }
-// always branch to end, but only if block not already terminated
-self.safe_branch(end_bb);
-
-// --- END ---
-self.builder.position_at_end(end_bb);
-
-// === Restore previous exception pointers ===
-self.exception_flag = old_flag;
-self.exception_value_i32 = old_val_i32;
-self.exception_value_str = old_val_str;
-
-self.i32_type.const_int(0, false).into()
 
+ExprKind::TryCatch { try_block, catches, finally_block } => {
+    self.compile_try(try_block, catches, finally_block.as_deref(), expr.span)?;
+    self.i32_type.const_int(0, false).into()
 }
 
 
@@ -3069,29 +3371,38 @@ self.i32_type.const_int(0, false).into()
 
 
 
-Expr::Funcy { name, params, body, is_async, params_patterns: _, return_type } => {
-    // 1️⃣ Compile the function (async or not)
+ExprKind::Funcy { name, params, body, is_async, params_patterns, return_type } => {
+    // 🔒 Anonymous lambdas may capture variables from the enclosing scope
+    if name.is_empty() && !*is_async {
+        return self.compile_lambda(params, body, return_type.as_ref(), expr.span);
+    }
+
+    // 1️⃣ Compile the function (async or not), under the name its overload was registered with
     let func_val = if *is_async {
-        self.compile_async_funcy(name, params, body)
+        self.compile_async_funcy(name, params, body)?
     } else {
-        self.compile_funcy(name, params, body, None, None, return_type.as_ref())
+        let type_descriptors = self.extract_param_type_descriptors(params, params_patterns);
+        self.compile_funcy(name, params, body, Some(&type_descriptors), None, return_type.as_ref())?
     };
 
-    // 2️⃣ Return a pointer to it as a first-class value
-    func_val.as_global_value().as_pointer_value().into()
+    // 2️⃣ Return it as a first-class value (a closure with no environment)
+    self.make_closure(func_val, None)?.into()
 }
 
 
 
 
-Expr::Return(expr_opt) => {
+ExprKind::Return(expr_opt) => {
     let func = self.builder.get_insert_block().unwrap().get_parent().unwrap();
     let func_name = func.get_name().to_str().unwrap_or_default().to_string();
     let func_ret_ty = func.get_type().get_return_type();
 
     // === Evaluate the expression ===
+    let mut raw_ty = None;
     let raw_val = if let Some(expr) = expr_opt {
-        self.compile_expr(expr)
+        let (val, ty) = self.compile_typed(expr)?;
+        raw_ty = Some(ty);
+        val
     } else if let Some(ret_ty) = func_ret_ty {
         match ret_ty {
             BasicTypeEnum::IntType(i) => i.const_int(0, false).into(),
@@ -3106,30 +3417,13 @@ Expr::Return(expr_opt) => {
     // === Normalize the return type ===
     let ret_val = if let Some(ret_ty) = func_ret_ty {
         match ret_ty {
-            BasicTypeEnum::IntType(i) => {
-                if raw_val.is_int_value() {
-                    raw_val
-                } else if raw_val.is_float_value() {
-                    self.builder
-                        .build_float_to_signed_int(raw_val.into_float_value(), i, "ftoi")
-                        .unwrap()
-                        .into()
-                } else {
-                    i.const_int(0, false).into()
-                }
-            }
-            BasicTypeEnum::FloatType(f) => {
-                if raw_val.is_float_value() {
-                    raw_val
-                } else if raw_val.is_int_value() {
-                    self.builder
-                        .build_signed_int_to_float(raw_val.into_int_value(), f, "itof")
-                        .unwrap()
-                        .into()
-                } else {
-                    f.const_float(0.0).into()
-                }
+            // Numbers convert to the declared width, by the value's own signedness
+            BasicTypeEnum::IntType(_) | BasicTypeEnum::FloatType(_) if raw_val.is_int_value() || raw_val.is_float_value() => {
+                let from = raw_ty.unwrap_or_else(|| Self::field_type_of_llvm(raw_val.get_type()));
+                self.convert_numeric(raw_val, &from, &Self::field_type_of_llvm(ret_ty))
             }
+            BasicTypeEnum::IntType(i) => i.const_int(0, false).into(),
+            BasicTypeEnum::FloatType(f) => f.const_float(0.0).into(),
             BasicTypeEnum::PointerType(_) => {
                 if raw_val.is_pointer_value() {
                     raw_val
@@ -3144,6 +3438,9 @@ Expr::Return(expr_opt) => {
         raw_val
     };
 
+    // Every block being left runs its deferred expressions, after the value is computed
+    self.run_deferred(0)?;
+
     // === Async return signal ===
     if func_name != "bootstrap_main" {
         let void_ty = self.context.void_type();
@@ -3181,6 +3478,7 @@ Expr::Return(expr_opt) => {
     }
 
     // === Actual return ===
+    self.leave_frame()?;
     self.builder.build_return(Some(&ret_val)).unwrap();
 
     // === Move builder to a safe continuation block ===
@@ -3192,9 +3490,9 @@ Expr::Return(expr_opt) => {
 
 
 
-Expr::Await(inner) => {
+ExprKind::Await(inner) => {
     // Compile the inner async call
-    let _ = self.compile_expr(inner);
+    let _ = self.compile_expr(inner)?;
 
     // === Ensure wpp_yield() exists ===
     let void_ty = self.context.void_type();
@@ -3226,119 +3524,45 @@ Expr::Await(inner) => {
 }
 
 
-Expr::ArrayLiteral(elements) => {
-    let element_count = elements.len() as u64;
-    let i32_type = self.context.i32_type();
-    let i64_type = self.context.i64_type();
-    let elem_ty = i32_type; // default element type for now
-
-    // === Allocate memory: (len + elements)
-    // Total slots = element_count + 1 (store length first)
-    let total_slots = element_count + 1;
-
-    // Convert total_slots -> i64
-    let total_slots_val = i64_type.const_int(total_slots, false);
-
-    // size_of(i32) as i64
-   // === Compute sizeof(i32) as i64 ===
-let one = i32_type.const_int(1, false);
-let gep = unsafe {
-    self.builder.build_gep(
-        i32_type,
-        i32_type.ptr_type(AddressSpace::default()).const_null(),
-        &[one],
-        "size_of_i32",
-    ).unwrap()
-};
-
-let i32_size_i64 = self.builder.build_ptr_to_int(
-    gep,
-    i64_type,
-    "i32_size_i64",
-).unwrap();
-
-
-    // total bytes = sizeof(i32) * total_slots
-    let total_size_bytes = self.builder.build_int_mul(i32_size_i64, total_slots_val, "arr_total_bytes");
-
-    // malloc(i64)
-    let malloc_ty = self.context.i8_type().ptr_type(AddressSpace::default());
-    let malloc_fn = self.module.get_function("malloc").unwrap_or_else(|| {
-        let ty = malloc_ty.fn_type(&[i64_type.into()], false);
-        self.module.add_function("malloc", ty, None)
-    });
-
-    let total_size_bytes_val = total_size_bytes.unwrap();
-let mem_ptr_i8 = self.builder
-    .build_call(
-        malloc_fn,
-        &[total_size_bytes_val.into()],
-        "arr_malloc",
-    )
-    .unwrap()
-    .try_as_basic_value()
-    .left()
-    .expect("malloc must return pointer")
-    .into_pointer_value();
-
-
-    // Cast to i32*
-    let arr_ptr = self.builder
-        .build_bitcast(mem_ptr_i8, i32_type.ptr_type(AddressSpace::default()), "arr_cast")
-        .unwrap()
-        .into_pointer_value();
-
-    // === Store length at [0]
-    self.builder.build_store(arr_ptr, i32_type.const_int(element_count, false)).unwrap();
-
-    // === Compile and store each element sequentially
-    for (i, el) in elements.iter().enumerate() {
-        let val = self.compile_expr(el);
-        let offset = i32_type.const_int((i + 1) as u64, false);
-        let elem_ptr = unsafe {
-            self.builder.build_gep(i32_type, arr_ptr, &[offset], "elem_ptr").unwrap()
-        };
-
-        match val {
-            BasicValueEnum::IntValue(iv) => {
-                self.builder.build_store(elem_ptr, iv).unwrap();
-            }
-            BasicValueEnum::FloatValue(fv) => {
-                let iv = self.builder.build_float_to_signed_int(fv, i32_type, "arr_cast_float").unwrap();
-                self.builder.build_store(elem_ptr, iv).unwrap();
-            }
-            BasicValueEnum::PointerValue(_) => {
-                println!("⚠️ Pointer elements not yet supported in array literal");
-            }
-            _ => {}
-        }
-    }
+ExprKind::ArrayLiteral(elements) => {
+    let (arr, _) = self.compile_array_literal(elements, None)?;
+    arr.as_basic_value_enum()
+}
 
-    arr_ptr.as_basic_value_enum()
+ExprKind::Index { target, index } => {
+    self.compile_index_load(target, index)?.0
 }
 
-Expr::ObjectLiteral { fields, type_name: _ } => {
+ExprKind::ObjectLiteral { fields, type_name: _ } => {
     let field_count = fields.len() as u64;
     let i32_type = self.context.i32_type();
     let i64_type = self.context.i64_type();
     let i8_ptr_ty = self.context.i8_type().ptr_type(AddressSpace::default());
 
-    // === Define runtime struct: { i32 field_count, i8** keys, i32* values }
+    // === Runtime struct: { i32 field_count, i32 kind, i8** keys, i64* values, i32* tags }
+    // `kind` is 0 here; maps (runtime/map.rs) share the header with kind 1. Values use the
+    // same 8-byte slots as arrays, and `tags` holds each field's type tag for printing
     let struct_ty = self.context.struct_type(
         &[
+            i32_type.into(),
             i32_type.into(),
             i8_ptr_ty.ptr_type(AddressSpace::default()).into(),
+            i64_type.ptr_type(AddressSpace::default()).into(),
             i32_type.ptr_type(AddressSpace::default()).into(),
         ],
         false,
     );
 
-    // === malloc(i64)
-    let malloc_ty = self.context.i8_type().ptr_type(AddressSpace::default());
-    let malloc_fn = self.module.get_function("malloc").unwrap_or_else(|| {
-        let ty = malloc_ty.fn_type(&[i64_type.into()], false);
-        self.module.add_function("malloc", ty, None)
-    });
+    // === Compile the fields first, so their types are known for the tag array
+    let mut compiled = Vec::with_capacity(fields.len());
+    for (key, val) in fields {
+        let (value, ty) = self.compile_typed(val)?;
+        let stored = self.coerce_to_slot(value, &ty, &ty, val.span)?;
+        compiled.push((key, self.slot_to_bits(stored, val.span)?, ty));
+    }
+
+    // === wpp_gc_alloc(i64): zeroed memory on the W++ heap (runtime/gc.rs)
+    let alloc_fn = self.runtime_fn("wpp_gc_alloc")?;
 
     // === Compute sizeof(struct_ty)
     let one = i32_type.const_int(1, false);
@@ -3361,7 +3585,7 @@ Expr::ObjectLiteral { fields, type_name: _ } => {
     // === Allocate struct
     let mem_ptr_i8 = self
         .builder
-        .build_call(malloc_fn, &[struct_size_i64.into()], "obj_malloc")
+        .build_call(alloc_fn, &[struct_size_i64.into()], "obj_malloc")
         .unwrap()
         .try_as_basic_value()
         .left()
@@ -3378,38 +3602,21 @@ Expr::ObjectLiteral { fields, type_name: _ } => {
         .unwrap()
         .into_pointer_value();
 
-    // === Compute sizeof(i32) as i64
-    let one = i32_type.const_int(1, false);
-    let gep = unsafe {
-        self.builder
-            .build_gep(
-                i32_type,
-                i32_type.ptr_type(AddressSpace::default()).const_null(),
-                &[one],
-                "sizeof_i32",
-            )
-            .unwrap()
-    };
-    let i32_size_i64 = self
-        .builder
-        .build_ptr_to_int(gep, i64_type, "i32_size_i64")
-        .unwrap();
-
     // === Compute total bytes for arrays
     let field_count_val = i64_type.const_int(field_count, false);
     let total_keys_bytes = self
         .builder
-        .build_int_mul(i32_size_i64, field_count_val, "total_keys_bytes")
+        .build_int_mul(i8_ptr_ty.size_of(), field_count_val, "total_keys_bytes")
         .unwrap();
     let total_vals_bytes = self
         .builder
-        .build_int_mul(i32_size_i64, field_count_val, "total_vals_bytes")
+        .build_int_mul(i64_type.size_of(), field_count_val, "total_vals_bytes")
         .unwrap();
 
     // === Allocate keys array
     let keys_mem_i8 = self
         .builder
-        .build_call(malloc_fn, &[total_keys_bytes.into()], "keys_malloc")
+        .build_call(alloc_fn, &[total_keys_bytes.into()], "keys_malloc")
         .unwrap()
         .try_as_basic_value()
         .left()
@@ -3429,7 +3636,7 @@ Expr::ObjectLiteral { fields, type_name: _ } => {
     // === Allocate values array
     let vals_mem_i8 = self
         .builder
-        .build_call(malloc_fn, &[total_vals_bytes.into()], "vals_malloc")
+        .build_call(alloc_fn, &[total_vals_bytes.into()], "vals_malloc")
         .unwrap()
         .try_as_basic_value()
         .left()
@@ -3440,16 +3647,14 @@ Expr::ObjectLiteral { fields, type_name: _ } => {
         .builder
         .build_bitcast(
             vals_mem_i8,
-            i32_type.ptr_type(AddressSpace::default()),
+            i64_type.ptr_type(AddressSpace::default()),
             "vals_cast",
         )
         .unwrap()
         .into_pointer_value();
 
     // === Populate keys + values
-    for (i, (key, val)) in fields.iter().enumerate() {
-        let val_compiled = self.compile_expr(val);
-
+    for (i, (key, bits, _)) in compiled.iter().enumerate() {
         // Store key as constant string
         let key_const = self.context.const_string(key.as_bytes(), true);
         let gname = format!("objkey_{}", i);
@@ -3457,7 +3662,7 @@ Expr::ObjectLiteral { fields, type_name: _ } => {
         gkey.set_initializer(&key_const);
         gkey.set_constant(true);
         gkey.set_linkage(Linkage::Private);
-        let key_ptr = gkey.as_pointer_value();
+        let key_ptr = gkey.as_pointer_value().const_cast(i8_ptr_ty);
 
         let key_index = i32_type.const_int(i as u64, false);
         let key_slot = unsafe {
@@ -3467,23 +3672,32 @@ Expr::ObjectLiteral { fields, type_name: _ } => {
         };
         self.builder.build_store(key_slot, key_ptr).unwrap();
 
-        // Store value (convert to i32 if needed)
-        let val_i32 = match val_compiled {
-            BasicValueEnum::IntValue(iv) => iv,
-            BasicValueEnum::FloatValue(fv) => self
-                .builder
-                .build_float_to_signed_int(fv, i32_type, "val_float2int")
-                .unwrap(),
-            _ => i32_type.const_int(0, false),
-        };
         let val_slot = unsafe {
             self.builder
-                .build_gep(i32_type, vals_ptr, &[key_index], "val_slot")
+                .build_gep(i64_type, vals_ptr, &[key_index], "val_slot")
                 .unwrap()
         };
-        self.builder.build_store(val_slot, val_i32).unwrap();
+        self.builder.build_store(val_slot, *bits).unwrap();
     }
 
+    // === Field tags: a constant array shared by every evaluation of this literal
+    let tags_ptr = if compiled.is_empty() {
+        i32_type.ptr_type(AddressSpace::default()).const_null()
+    } else {
+        let tags: Vec<IntValue<'ctx>> = compiled
+            .iter()
+            .map(|(_, _, ty)| i32_type.const_int(self.field_type_tag(ty), false))
+            .collect();
+        let tags = i32_type.const_array(&tags);
+        let gtags = self.module.add_global(tags.get_type(), None, "objtags");
+        gtags.set_initializer(&tags);
+        gtags.set_constant(true);
+        gtags.set_linkage(Linkage::Private);
+        self.builder
+            .build_pointer_cast(gtags.as_pointer_value(), i32_type.ptr_type(AddressSpace::default()), "tags_cast")
+            .unwrap()
+    };
+
     // === Fill struct fields
     let field_0 =
         unsafe { self.builder.build_struct_gep(struct_ty, obj_ptr, 0, "f0").unwrap() };
@@ -3491,146 +3705,73 @@ Expr::ObjectLiteral { fields, type_name: _ } => {
         .build_store(field_0, i32_type.const_int(field_count, false))
         .unwrap();
 
+    let kind_field = self.builder.build_struct_gep(struct_ty, obj_ptr, 1, "f_kind").unwrap();
+    self.builder.build_store(kind_field, i32_type.const_zero()).unwrap();
+
     let field_1 =
-        unsafe { self.builder.build_struct_gep(struct_ty, obj_ptr, 1, "f1").unwrap() };
+        unsafe { self.builder.build_struct_gep(struct_ty, obj_ptr, 2, "f1").unwrap() };
     self.builder.build_store(field_1, keys_ptr).unwrap();
 
     let field_2 =
-        unsafe { self.builder.build_struct_gep(struct_ty, obj_ptr, 2, "f2").unwrap() };
+        unsafe { self.builder.build_struct_gep(struct_ty, obj_ptr, 3, "f2").unwrap() };
     self.builder.build_store(field_2, vals_ptr).unwrap();
 
+    let tags_field = self.builder.build_struct_gep(struct_ty, obj_ptr, 4, "f_tags").unwrap();
+    self.builder.build_store(tags_field, tags_ptr).unwrap();
+
     obj_ptr.as_basic_value_enum()
 }
 
 
-Expr::NewInstance { entity, args } => {
+ExprKind::NewInstance { entity, args } => {
     wpp_debug!("🐾 Allocating new instance of entity: {}", entity);
-
-    // 1️⃣ Retrieve entity definition
-    let oopsie = self.entities.get(entity)
-        .unwrap_or_else(|| panic!("Unknown entity '{}'", entity));
-
-    let struct_ty = oopsie.struct_type;
-
-    // 2️⃣ Allocate memory (via malloc or GC)
-    let malloc_fn = self.module.get_function("malloc").unwrap_or_else(|| {
-        self.module.add_function(
-            "malloc",
-            self.context.i8_type().ptr_type(AddressSpace::default())
-                .fn_type(&[self.i32_type.into()], false),
-            None,
-        )
-    });
-
-    let size_val = struct_ty.size_of().unwrap();
-    let size_i32 = self.builder
-        .build_int_cast(size_val, self.i32_type, "size_i32")
-        .unwrap();
-
-    let raw_ptr = self.builder
-        .build_call(malloc_fn, &[size_i32.into()], "alloc_instance")
-        .unwrap()
-        .try_as_basic_value()
-        .left()
-        .unwrap()
-        .into_pointer_value();
-
-    // 3️⃣ Cast pointer to entity type
-    let typed_ptr = self.builder
-        .build_bitcast(raw_ptr, struct_ty.ptr_type(AddressSpace::default()), "as_struct")
-        .unwrap()
-        .into_pointer_value();
-
-    // 4️⃣ Initialize fields (defaults or zero)
-    for (i, (field_name, field_ty)) in oopsie.fields.iter().enumerate() {
-        let field_ptr = unsafe {
-            self.builder
-                .build_struct_gep(struct_ty, typed_ptr, i as u32, field_name)
-                .unwrap()
-        };
-
-        // TODO: default initialization from entity declaration (for now, zero)
-        let zero_val: BasicValueEnum<'ctx> = match field_ty {
-    BasicTypeEnum::IntType(i) => i.const_int(0, false).into(),
-    BasicTypeEnum::FloatType(f) => f.const_float(0.0).into(),
-    BasicTypeEnum::PointerType(p) => p.const_null().into(),
-    _ => self.i32_type.const_zero().into(),
-};
-
-        self.builder.build_store(field_ptr, zero_val);
-    }
-
-    // 5️⃣ Optional: call constructor if it exists (Dog.new)
-    let ctor_name = format!("{}.new", entity);
-    let ctor_sig = FunctionSignature {
-        name: ctor_name.clone(),
-        param_types: args.iter().map(|_| crate::ast::types::TypeDescriptor::Primitive("i32".to_string())).collect(),
-        return_type: TypeDescriptor::Primitive("ptr".to_string()), // Constructor returns pointer to instance
-    };
-
-    if let Some(func_val) = self.functions.get(&ctor_sig).cloned() {
-    wpp_debug!("🧩 Calling constructor '{}'", ctor_name);
-
-    let mut compiled_args: Vec<BasicMetadataValueEnum<'ctx>> = Vec::new();
-    compiled_args.push(typed_ptr.into()); // 'me' argument
-
-    // ✅ Borrow self mutably again AFTER immutable borrow ended
-    for a in args {
-        let compiled = self.compile_expr(a);
-        compiled_args.push(compiled.into());
-    }
-
-    self.builder
-        .build_call(func_val, &compiled_args, &format!("call_ctor_{}", entity))
-        .unwrap();
-} else {
-    wpp_debug!("⚙️ No constructor found for '{}', skipping init", entity);
-}
-
-
-    // ✅ Return pointer to the instance
-    typed_ptr.into()
+    self.compile_new_instance(entity, args, expr.span)?.into()
 }
 
 
 
         // === Fallback ===
-        _ => panic!("Unhandled expression: {:?}", expr),
-    }
+        _ => return Err(CompileError::Unsupported {
+            message: "this expression is not supported here".to_string(),
+            span: expr.span,
+        }),
+    })
 }
-fn resolve_basic_type(&self, ty: &str) -> inkwell::types::BasicTypeEnum<'ctx> {
-    match ty {
-        "i8"  => self.context.i8_type().into(),
-        "i32" => self.context.i32_type().into(),
-        "i64" => self.context.i64_type().into(),
-        "u64" => self.context.i64_type().into(), // LLVM doesn’t distinguish signed vs unsigned types
-        "f64" => self.context.f64_type().into(),
-        _ => panic!("❌ Unknown type: {}", ty),
-    }
+fn resolve_basic_type(&self, ty: &str, span: Span) -> CompileResult<inkwell::types::BasicTypeEnum<'ctx>> {
+    // LLVM doesn’t distinguish signed vs unsigned types
+    self.primitive_type(ty).ok_or_else(|| CompileError::type_mismatch(span, format!("unknown numeric type `{}`", ty)))
 }
 
 
-    /// Compile a statement. Returns last expression value (if any).
-    pub fn compile_node(&mut self, node: &Node) -> Option<BasicValueEnum<'ctx>> {
+    /// Compile a statement. Returns last expression value (if any), or the first compile error.
+    pub fn compile_node(&mut self, node: &Node) -> CompileResult<Option<BasicValueEnum<'ctx>>> {
     // Don’t compile if the current block already has a terminator
     if let Some(block) = self.builder.get_insert_block() {
         if block.get_terminator().is_some() {
-            return None;
+            return Ok(None);
         }
     }
 
-    match node {
-            Node::Entity(entity) => {
-        self.compile_entity(entity);
+    Ok(match &node.kind {
+            NodeKind::Entity(entity) => {
+        self.compile_entity(entity, node.span)?;
         None // 👈 explicitly return None so the return type matches
     }
-    Node::TypeAlias(type_def) => {
+    NodeKind::Interface(def) => {
+        self.interfaces.insert(def.name.clone(), def.clone());
+        None
+    }
+    NodeKind::TypeAlias(type_def) => {
         // Register type alias for dispatch resolution
         wpp_debug!("📝 Registering type alias: {}", type_def.name);
         self.type_aliases.insert(type_def.name.clone(), type_def.clone());
         None
     }
-    Node::ImportAll { module } | Node::ImportList { module, .. } => {
+    NodeKind::Enum(def) => {
+        self.enums.insert(def.name.clone(), def.clone());
+        None
+    }
+    NodeKind::ImportAll { module } | NodeKind::ImportList { module, .. } => {
         if module.starts_with("rust:") {
             wpp_debug!("🦀 Declaring FFI functions for Rust module '{}'", module);
             self.declare_rust_ffi_functions();
@@ -3640,16 +3781,34 @@ fn resolve_basic_type(&self, ty: &str) -> inkwell::types::BasicTypeEnum<'ctx> {
         None
     }
 
-    Node::Export { name, .. } => {
+    NodeKind::Export { name, .. } => {
         wpp_debug!("📤 Export '{}' handled by ExportResolver", name);
         None
     }
 
-        Node::Let { name, value, is_const, ty } => {
+        NodeKind::Let { name, value, is_const, ty } => {
     wpp_debug!("🧱 Compiling top-level node: Let {{ name: {}, ty: {:?} }}", name, ty);
 
+    // === `let xs: T[] = ...` annotations are W++ types rather than LLVM ones ===
+    let annotated = ty.as_deref().map(FieldType::from_string);
+    if let Some(FieldType::Object(type_name)) = &annotated
+        && !self.entities.contains_key(type_name)
+        && !self.interfaces.contains_key(type_name)
+        && !self.type_aliases.contains_key(type_name)
+        && !self.enums.contains_key(type_name)
+    {
+        return Err(CompileError::type_mismatch(node.span, format!("unknown type `{}`", type_name)));
+    }
+    let annotated_heap = annotated.as_ref().is_some_and(|t| self.slot_type(t).is_pointer_type());
+
     // === Detect heap-allocated expressions (arrays/objects) ===
-    let is_heap_value = matches!(value, Expr::ArrayLiteral(_) | Expr::ObjectLiteral { .. });
+    let is_heap_value = annotated_heap
+        || matches!(&value.kind, ExprKind::ArrayLiteral(_) | ExprKind::ObjectLiteral { .. })
+        || self.static_field_type(value).is_some_and(|t| match t {
+            FieldType::Array(_) | FieldType::Map(_) => true,
+            FieldType::Object(name) => self.enums.contains_key(&name),
+            _ => false,
+        });
     if is_heap_value {
         wpp_debug!("💾 Variable `{}` is a heap object — allocating as pointer", name);
     }
@@ -3665,15 +3824,21 @@ let var_type: BasicTypeEnum<'ctx> = if is_heap_value {
         .as_basic_type_enum()
 
 // 🧩 Case: Lambda (Funcy expression)
-} else if matches!(value, Expr::Funcy { .. }) {
+} else if matches!(&value.kind, ExprKind::Funcy { .. }) {
     // 🧠 Lambdas are compiled functions; store as a function pointer (i8*)
     self.context
         .i8_type()
         .ptr_type(inkwell::AddressSpace::default())
         .as_basic_type_enum()
 
+// 📦 Array element or array builtin (`pop`, `remove`): use the recorded element type
+} else if ty.is_none()
+    && let (ExprKind::Index { .. } | ExprKind::Call { .. }, Some(elem)) = (&value.kind, self.static_field_type(value))
+{
+    self.field_value_type(&elem)
+
 // 🧵 Special case: if RHS is a function call that returns a pointer
-} else if let Expr::Call { name, .. } = value {
+} else if let ExprKind::Call { name, .. } = &value.kind {
     if name == "useThreadState"
         || name == "useMutex"
         || name == "useThread"
@@ -3682,6 +3847,8 @@ let var_type: BasicTypeEnum<'ctx> = if is_heap_value {
         || name == "readline"
         || name == "int_to_string"
         || name == "to_string"
+        || name == "format"
+        || name == "stack_trace"
         // W++ string utility functions
         || name == "wpp_str_substr"
         || name == "wpp_str_replace"
@@ -3784,6 +3951,15 @@ let var_type: BasicTypeEnum<'ctx> = if is_heap_value {
             .i8_type()
             .ptr_type(inkwell::AddressSpace::default())
             .as_basic_type_enum()
+    } else if let Some(ret) = self
+        .reverse_func_index
+        .get(name)
+        .and_then(|sigs| sigs.first())
+        .and_then(|sig| self.functions.get(sig))
+        .and_then(|f| f.get_type().get_return_type())
+    {
+        // 🧩 User function: use its compiled return type (e.g. a closure factory returns i8*)
+        ret
     } else {
         // Default scalar
         self.context.i32_type().as_basic_type_enum()
@@ -3791,18 +3967,18 @@ let var_type: BasicTypeEnum<'ctx> = if is_heap_value {
 }
 
 
- else if let Some(t) = ty {
-    self.resolve_basic_type(t)
+ else if let Some(ft) = &annotated {
+    self.field_value_type(ft)
 
 // 🧵 Special case: BinaryOp with string concatenation
-} else if let Expr::BinaryOp { left, op, right } = value {
+} else if let ExprKind::BinaryOp { left, op, right } = &value.kind {
     // Check if it's string concatenation
     if op == "+" {
         // Check if either operand is a string literal or string variable
-        let is_string_concat = matches!(left.as_ref(), Expr::StringLiteral(_))
-            || matches!(right.as_ref(), Expr::StringLiteral(_))
-            || matches!(left.as_ref(), Expr::Variable(_))
-            || matches!(right.as_ref(), Expr::Variable(_));
+        let is_string_concat = matches!(&left.kind, ExprKind::StringLiteral(_))
+            || matches!(&right.kind, ExprKind::StringLiteral(_))
+            || matches!(&left.kind, ExprKind::Variable(_))
+            || matches!(&right.kind, ExprKind::Variable(_));
 
         if is_string_concat {
             // String concatenation result is a pointer
@@ -3818,27 +3994,24 @@ let var_type: BasicTypeEnum<'ctx> = if is_heap_value {
         // Other binary ops default to i32
         self.context.i32_type().as_basic_type_enum()
     }
+
 }
 
 // 🧠 Type inference from RHS (literal-based)
  else {
-    match value {
-        Expr::TypedLiteral { value: val, ty: lit_ty } => match lit_ty.as_str() {
-            "i1" | "bool" => self.context.bool_type().into(),
-            "i8" => self.context.i8_type().into(),
-            "i32" => self.context.i32_type().into(),
-            "i64" => self.context.i64_type().into(),
-            "f64" => self.context.f64_type().into(),
-            _ => self.infer_type_from_literal(val, lit_ty == "f64"),
+    match &value.kind {
+        ExprKind::TypedLiteral { value: val, ty: lit_ty } => match self.primitive_type(lit_ty) {
+            Some(t) => t,
+            None => self.infer_type_from_literal(val, false, value.span)?,
         },
         
  
 
-            Expr::Literal(v) => self.infer_type_from_literal(&v.to_string(), false),
+            ExprKind::Literal(v) => self.infer_type_from_literal(&v.to_string(), false, value.span)?,
 
-            Expr::BoolLiteral(_) => self.context.bool_type().into(),
+            ExprKind::BoolLiteral(_) => self.context.bool_type().into(),
 
-            Expr::Variable(var_name) => {
+            ExprKind::Variable(var_name) => {
                 if let Some(existing) = self.vars.get(var_name).or_else(|| self.globals.get(var_name)) {
                     existing.ty
                 } else {
@@ -3847,14 +4020,14 @@ let var_type: BasicTypeEnum<'ctx> = if is_heap_value {
                 }
             }
 
-            Expr::BinaryOp { left, right, .. } => {
-                let left_ty = self.infer_expr_type(left);
-                let right_ty = self.infer_expr_type(right);
+            ExprKind::BinaryOp { left, right, .. } => {
+                let left_ty = self.infer_expr_type(left)?;
+                let right_ty = self.infer_expr_type(right)?;
                 self.merge_types(left_ty, right_ty)
             }
 
             // 🔤 String literals should be stored as pointers
-            Expr::StringLiteral(_) => {
+            ExprKind::StringLiteral(_) => {
                 self.context
                     .i8_type()
                     .ptr_type(inkwell::AddressSpace::default())
@@ -3867,12 +4040,39 @@ let var_type: BasicTypeEnum<'ctx> = if is_heap_value {
             }
         }
     };
+    // === Special case: `let x = match ...` takes the type its arms agree on, and
+    // `let x = a + b` or `let x = n as u8` the type the operation produces ===
+    let typed_by_value = match &value.kind {
+        ExprKind::Match { .. } | ExprKind::Cast { .. } => true,
+        ExprKind::BinaryOp { op, .. } => op != "=",
+        _ => false,
+    };
+    if ty.is_none() && typed_by_value {
+        let (val, val_ty) = self.compile_typed(value)?;
+        let alloca = self.builder.build_alloca(val.get_type(), name).unwrap();
+        self.builder.build_store(alloca, val).unwrap();
+        self.vars.insert(
+            name.clone(),
+            VarInfo {
+                ptr: alloca,
+                ty: val.get_type(),
+                is_const: *is_const,
+                is_thread_state: false,
+                entity_type: None,
+                object_type_name: matches!(&val_ty, FieldType::Object(n) if self.enums.contains_key(n)).then(|| val_ty.to_string()),
+                function_signature: None,
+                field_type: Self::tracked_field_type(val_ty),
+            },
+        );
+        return Ok(None);
+    }
+
     // === Special case: entity instantiation (let d = new Dog(...)) ===
-if let Expr::NewInstance { entity, args } = value {
+if let ExprKind::NewInstance { entity, args } = &value.kind {
     println!("🐾 Allocating new instance of entity: {}", entity);
 
     // Call helper to allocate + call constructor
-    let instance_ptr = self.compile_new_instance(entity, args);
+    let instance_ptr = self.compile_new_instance(entity, args, value.span)?;
 
     // All entity instances are stored as i8* (generic object pointer)
     let entity_ptr_ty = self
@@ -3892,61 +4092,68 @@ if let Expr::NewInstance { entity, args } = value {
             ty: entity_ptr_ty,
             is_const: *is_const,
             is_thread_state: false,
-            entity_type: Some(entity.clone()), // 👈 NEW FIELD (add this to VarInfo)
+            // `let a: Animal = new(Dog)` calls methods through Animal's slots
+            entity_type: Some(match &annotated {
+                Some(FieldType::Object(declared)) if self.entities.contains_key(declared) || self.interfaces.contains_key(declared) => {
+                    declared.clone()
+                }
+                _ => entity.clone(),
+            }),
             object_type_name: None,
             function_signature: None,
+            field_type: None,
         },
     );
 
     // ✅ skip the rest of normal let logic
-    return None;
+    return Ok(None);
 }
 
     // === Allocate space for variable ===
     let alloca = self.builder.build_alloca(var_type, name).unwrap();
 
     // === Compile RHS ===
-    let rhs_val = self.compile_expr(value);
+    let (rhs_val, rhs_ty) = match &annotated {
+        Some(expected) => self.compile_expected(value, expected)?,
+        None => self.compile_typed(value)?,
+    };
+    if let Some(expected @ (FieldType::Array(_) | FieldType::Map(_) | FieldType::String | FieldType::Object(_))) = &annotated {
+        let matches = match (expected, &rhs_ty) {
+            (FieldType::Object(_), FieldType::Object(_)) => self.same_object_kind(expected, &rhs_ty),
+            _ => *expected == rhs_ty,
+        };
+        if !matches {
+            return Err(CompileError::type_mismatch(
+                value.span,
+                format!("`{}` is declared as `{}` but initialized with `{}`", name, expected, rhs_ty),
+            ));
+        }
+    }
 
     // === Store safely ===
     if is_heap_value {
-        // Heap values already return a pointer; store directly
-        self.builder.build_store(alloca, rhs_val).unwrap();
+        // Heap values already return a pointer; store it as a generic i8*
+        let stored = match rhs_val {
+            BasicValueEnum::PointerValue(pv) => self
+                .builder
+                .build_pointer_cast(pv, var_type.into_pointer_type(), "heap_ptr")
+                .unwrap()
+                .as_basic_value_enum(),
+            other => other,
+        };
+        self.builder.build_store(alloca, stored).unwrap();
     } else {
-        // Primitive values: perform safe casting if needed
+        // Primitive values: convert numbers to the variable's type, by the value's signedness
         let casted_val = match (rhs_val, var_type) {
-            (BasicValueEnum::IntValue(iv), BasicTypeEnum::IntType(int_ty)) => {
-                let rhs_bits = iv.get_type().get_bit_width();
-                let lhs_bits = int_ty.get_bit_width();
-                if rhs_bits == lhs_bits {
-                    iv.as_basic_value_enum()
-                } else if rhs_bits < lhs_bits {
-                    self.builder
-                        .build_int_z_extend(iv, int_ty, "zext")
-                        .unwrap()
-                        .as_basic_value_enum()
-                } else {
-                    self.builder
-                        .build_int_truncate(iv, int_ty, "trunc")
-                        .unwrap()
-                        .as_basic_value_enum()
-                }
-            }
-
-            (BasicValueEnum::FloatValue(fv), BasicTypeEnum::FloatType(_)) => fv.as_basic_value_enum(),
-
-            (BasicValueEnum::IntValue(iv), BasicTypeEnum::FloatType(f_ty)) => {
-                self.builder
-                    .build_signed_int_to_float(iv, f_ty, "int2float")
-                    .unwrap()
-                    .as_basic_value_enum()
-            }
-
-            (BasicValueEnum::FloatValue(fv), BasicTypeEnum::IntType(i_ty)) => {
-                self.builder
-                    .build_float_to_signed_int(fv, i_ty, "float2int")
-                    .unwrap()
-                    .as_basic_value_enum()
+            (
+                BasicValueEnum::IntValue(_) | BasicValueEnum::FloatValue(_),
+                BasicTypeEnum::IntType(_) | BasicTypeEnum::FloatType(_),
+            ) => {
+                let to = annotated
+                    .clone()
+                    .filter(FieldType::is_numeric)
+                    .unwrap_or_else(|| Self::field_type_of_llvm(var_type));
+                self.convert_numeric(rhs_val, &rhs_ty, &to)
             }
 
             (BasicValueEnum::IntValue(iv), BasicTypeEnum::PointerType(_)) => iv.as_basic_value_enum(),
@@ -3958,13 +4165,29 @@ if let Expr::NewInstance { entity, args } = value {
     }
 
     // === Extract object type name if it's a typed object literal ===
-    let obj_type = if let Expr::ObjectLiteral { type_name, .. } = value {
+    let obj_type = if let ExprKind::ObjectLiteral { type_name, .. } = &value.kind {
         type_name.clone()
+    } else if let FieldType::Object(enum_name) = &rhs_ty
+        && self.enums.contains_key(enum_name)
+    {
+        Some(enum_name.clone())
     } else {
         None
     };
 
     // === Register variable ===
+    // Entity instances dispatch through the declared entity's slots, else the value's own
+    let entity_type = [annotated.as_ref(), Some(&rhs_ty)].into_iter().flatten().find_map(|ty| match ty {
+        FieldType::Object(entity) if self.entities.contains_key(entity) || self.interfaces.contains_key(entity) => Some(entity.clone()),
+        _ => None,
+    });
+    // Numbers keep their W++ type, since the LLVM one does not say whether they are unsigned
+    let field_type = match rhs_ty {
+        FieldType::Array(_) | FieldType::Map(_) | FieldType::Object(_) | FieldType::Any => Some(rhs_ty),
+        _ => annotated
+            .filter(FieldType::is_numeric)
+            .or_else(|| (rhs_ty.is_numeric() && self.slot_type(&rhs_ty) == var_type).then_some(rhs_ty)),
+    };
     self.vars.insert(
         name.clone(),
         VarInfo {
@@ -3972,9 +4195,10 @@ if let Expr::NewInstance { entity, args } = value {
             ty: var_type,
             is_const: *is_const,
             is_thread_state: false,
-            entity_type: None,
+            entity_type,
             object_type_name: obj_type,
-            function_signature: None,
+            function_signature: self.value_function_signature(value),
+            field_type,
         },
     );
 
@@ -3987,20 +4211,20 @@ if let Expr::NewInstance { entity, args } = value {
 
 
 
-        Node::Expr(expr) => {
+        NodeKind::Expr(expr) => {
             
-            let v = self.compile_expr(expr);
+            let v = self.compile_expr(expr)?;
 
             // ✅ Stop if this expression emitted a terminator (e.g. break/continue)
             if let Some(block) = self.builder.get_insert_block() {
                 if block.get_terminator().is_some() {
-                    return None;
+                    return Ok(None);
                 }
             }
 
             Some(v)
         }
-    }
+    })
 }
 
     /// Helper: Extract TypeDescriptors from function parameters
@@ -4032,7 +4256,9 @@ if let Expr::NewInstance { entity, args } = value {
                                     if self.entities.contains_key(name) {
                                         println!("✅ Resolved '{}' as Entity", name);
                                         TypeDescriptor::Entity(name.clone())
-                                    } else if self.type_aliases.contains_key(name) {
+                                    } else if self.interfaces.contains_key(name) {
+                                        TypeDescriptor::Interface(name.clone())
+                                    } else if self.type_aliases.contains_key(name) || self.enums.contains_key(name) {
                                         println!("✅ Resolved '{}' as ObjectType", name);
                                         TypeDescriptor::ObjectType(name.clone())
                                     } else {
@@ -4077,6 +4303,8 @@ if let Expr::NewInstance { entity, args } = value {
                         // Check if it's an entity or object type
                         if self.entities.contains_key(type_str) {
                             TypeDescriptor::Entity(type_str.to_string())
+                        } else if self.interfaces.contains_key(type_str) {
+                            TypeDescriptor::Interface(type_str.to_string())
                         } else if self.type_aliases.contains_key(type_str) {
                             TypeDescriptor::ObjectType(type_str.to_string())
                         } else {
@@ -4143,20 +4371,29 @@ pub fn link_dependency_modules(&mut self) {
 }
 
 /// Create main(), compile nodes, and return i32.
-pub fn compile_main(&mut self, nodes: &[Node]) -> FunctionValue<'ctx> {
+pub fn compile_main(&mut self, nodes: &[Node]) -> CompileResult<FunctionValue<'ctx>> {
     // === Pre-pass: Compile entities and type aliases first (BEFORE module check) ===
     println!("🔍 Pre-pass: Processing {} nodes for entities and type aliases", nodes.len());
+    // Interfaces first, so an entity can implement one declared after it
+    for node in nodes {
+        if let NodeKind::Interface(def) = &node.kind {
+            self.interfaces.insert(def.name.clone(), def.clone());
+        }
+    }
     for node in nodes {
-        match node {
-            Node::Entity(entity) => {
+        match &node.kind {
+            NodeKind::Entity(entity) => {
                 println!("🏗️ Pre-pass found entity: {}", entity.name);
-                self.compile_entity(entity);
+                self.compile_entity(entity, node.span)?;
             }
-            Node::TypeAlias(type_def) => {
+            NodeKind::TypeAlias(type_def) => {
                 self.type_aliases.insert(type_def.name.clone(), type_def.clone());
                 println!("📝 Registered type alias: {}", type_def.name);
             }
-            Node::ImportAll { module } | Node::ImportList { module, .. } => {
+            NodeKind::Enum(def) => {
+                self.enums.insert(def.name.clone(), def.clone());
+            }
+            NodeKind::ImportAll { module } | NodeKind::ImportList { module, .. } => {
                 if module.starts_with("rust:") {
                     println!("🦀 Pre-pass: Declaring FFI functions for Rust module '{}'", module);
                     self.declare_rust_ffi_functions();
@@ -4174,10 +4411,10 @@ pub fn compile_main(&mut self, nodes: &[Node]) -> FunctionValue<'ctx> {
         // Just compile function bodies; no entrypoint creation
         for node in nodes {
             // Handle both direct function expressions and exported functions
-            let funcy_expr = match node {
-                Node::Expr(e @ Expr::Funcy { .. }) => Some(e),
-                Node::Export { item, .. } => {
-                    if let Node::Expr(e @ Expr::Funcy { .. }) = &**item {
+            let funcy_expr = match &node.kind {
+                NodeKind::Expr(e) if matches!(e.kind, ExprKind::Funcy { .. }) => Some(e),
+                NodeKind::Export { item, .. } => {
+                    if let NodeKind::Expr(e @ Expr { kind: ExprKind::Funcy { .. }, .. }) = &item.kind {
                         Some(e)
                     } else {
                         None
@@ -4186,23 +4423,23 @@ pub fn compile_main(&mut self, nodes: &[Node]) -> FunctionValue<'ctx> {
                 _ => None,
             };
 
-            if let Some(Expr::Funcy { name, params, body, is_async, params_patterns, return_type }) = funcy_expr {
+            if let Some(ExprKind::Funcy { name, params, body, is_async, params_patterns, return_type }) = funcy_expr.map(|e| &e.kind) {
                 if *is_async {
-                    self.compile_async_funcy(name, params, body);
+                    self.compile_async_funcy(name, params, body)?;
                 } else {
                     // Extract type descriptors for proper dispatch
                     let type_descriptors = self.extract_param_type_descriptors(params, params_patterns);
-                    self.compile_funcy(name, params, body, Some(&type_descriptors), None, return_type.as_ref());
+                    self.compile_funcy(name, params, body, Some(&type_descriptors), None, return_type.as_ref())?;
                 }
             }
         }
 
         // Return a dummy placeholder function to satisfy return type
-        return self.module.add_function(
+        return Ok(self.module.add_function(
             "__submodule_stub",
             self.i32_type.fn_type(&[], false),
             None,
-        );
+        ));
     }
 
     // === Root module only ===
@@ -4211,25 +4448,9 @@ pub fn compile_main(&mut self, nodes: &[Node]) -> FunctionValue<'ctx> {
     let entry = self.context.append_basic_block(async_fn, "entry");
     self.builder.position_at_end(entry);
 
-    // === Use a dedicated temporary builder for the entry ===
-    let entry_builder = self.context.create_builder();
-    entry_builder.position_at_end(entry);
-
-
-    // === Exception slots (kept global) ===
-    let flag_ptr = entry_builder.build_alloca(self.i32_type, "exc_flag").unwrap();
-    entry_builder.build_store(flag_ptr, self.i32_type.const_int(0, false)).unwrap();
-
-    let val_i32_ptr = entry_builder.build_alloca(self.i32_type, "exc_val_i32").unwrap();
-    entry_builder.build_store(val_i32_ptr, self.i32_type.const_int(0, false)).unwrap();
-
-    let str_ptr_ty = self.context.i8_type().ptr_type(AddressSpace::default());
-    let val_str_ptr = entry_builder.build_alloca(str_ptr_ty, "exc_val_str").unwrap();
-    entry_builder.build_store(val_str_ptr, str_ptr_ty.const_null()).unwrap();
-
-    self.exception_flag = Some(flag_ptr);
-    self.exception_value_i32 = Some(val_i32_ptr);
-    self.exception_value_str = Some(val_str_ptr);
+    // === Top-level code reports exceptions nobody caught ===
+    self.exceptions = ExceptionScope { is_entry: true, ..Default::default() };
+    self.enter_frame("main")?;
     if let (Some(wms_arc), Some(resolver_arc)) = (&self.wms, &self.resolver) {
     // 🔒 Lock both Arc<Mutex<T>> to get access to the inner values
     let wms = wms_arc.lock().unwrap();
@@ -4246,7 +4467,7 @@ pub fn compile_main(&mut self, nodes: &[Node]) -> FunctionValue<'ctx> {
 
   // === Predeclare functions ===
 for node in nodes {
-    if let Node::Expr(Expr::Funcy { name, params, body, params_patterns, .. }) = node {
+    if let NodeKind::Expr(Expr { kind: ExprKind::Funcy { name, params, body, params_patterns, return_type, .. }, .. }) = &node.kind {
         // Infer parameter types from body (minimal version)
         let mut int_params = std::collections::HashSet::new();
         let mut ptr_params = std::collections::HashSet::new();
@@ -4259,24 +4480,24 @@ for node in nodes {
             contains_string_literal: &mut bool,
         ) {
             for node in nodes {
-                match node {
-                    Node::Expr(expr) => match expr {
-                        Expr::BinaryOp { left, right, .. } => {
-                            if let (Expr::StringLiteral(_), _) | (_, Expr::StringLiteral(_)) = (&**left, &**right) {
+                match &node.kind {
+                    NodeKind::Expr(expr) => match &expr.kind {
+                        ExprKind::BinaryOp { left, right, .. } => {
+                            if let (ExprKind::StringLiteral(_), _) | (_, ExprKind::StringLiteral(_)) = (&left.kind, &right.kind) {
                                 *contains_string_literal = true;
                             }
-                            scan_for_types(&[Node::Expr(*left.clone())], int_params, ptr_params, contains_string_literal);
-                            scan_for_types(&[Node::Expr(*right.clone())], int_params, ptr_params, contains_string_literal);
+                            scan_for_types(&[Node::from(*left.clone())], int_params, ptr_params, contains_string_literal);
+                            scan_for_types(&[Node::from(*right.clone())], int_params, ptr_params, contains_string_literal);
                         }
-                        Expr::StringLiteral(_) => *contains_string_literal = true,
-                        Expr::Call { args, .. } => {
+                        ExprKind::StringLiteral(_) => *contains_string_literal = true,
+                        ExprKind::Call { args, .. } => {
                             for a in args {
-                                scan_for_types(&[Node::Expr(a.clone())], int_params, ptr_params, contains_string_literal);
+                                scan_for_types(&[Node::from(a.clone())], int_params, ptr_params, contains_string_literal);
                             }
                         }
-                        Expr::Return(inner) => {
+                        ExprKind::Return(inner) => {
                             if let Some(inner_expr) = inner {
-                                scan_for_types(&[Node::Expr(*inner_expr.clone())], int_params, ptr_params, contains_string_literal);
+                                scan_for_types(&[Node::from(*inner_expr.clone())], int_params, ptr_params, contains_string_literal);
                             }
                         }
                         _ => {}
@@ -4288,17 +4509,40 @@ for node in nodes {
 
         scan_for_types(body, &mut int_params, &mut ptr_params, &mut contains_string_literal);
 
-        if contains_string_literal {
+        // Overloads are told apart by their declared types, so only a lone definition's
+        // parameters are guessed to be strings
+        let overloaded = nodes
+            .iter()
+            .filter(|n| matches!(&n.kind, NodeKind::Expr(Expr { kind: ExprKind::Funcy { name: other, .. }, .. }) if other == name))
+            .count()
+            > 1;
+        if contains_string_literal && !overloaded {
             for p in params {
                 ptr_params.insert(p.clone());
             }
         }
 
+        // 🪶 Extract TypeDescriptors for dispatch
+        println!("🧪 extract_param_type_descriptors for '{}' with params_patterns: {:?}", name, params_patterns);
+        let type_descriptors = self.extract_param_type_descriptors(
+            params,
+            params_patterns,
+        );
+        println!("🧪 Result: {:?}", type_descriptors);
+
         // === Build parameter types ===
         let param_types: Vec<BasicMetadataTypeEnum<'ctx>> = params
             .iter()
-            .map(|p| {
-                if ptr_params.contains(p) {
+            .zip(&type_descriptors)
+            .map(|(p, td)| {
+                // Annotated primitives (`key: str`) keep their type; entities, objects and enums
+                // are passed as pointers, as in `compile_funcy`
+                if let TypeDescriptor::Primitive(ty) = td
+                    && let Some(t) = self.primitive_type(ty).filter(|_| ty != "i32")
+                {
+                    return t.into();
+                }
+                if ptr_params.contains(p) || matches!(td, TypeDescriptor::Entity(_) | TypeDescriptor::Interface(_) | TypeDescriptor::ObjectType(_)) {
                     self.context.i8_type().ptr_type(AddressSpace::default()).into()
                 } else {
                     self.i32_type.into()
@@ -4312,14 +4556,9 @@ fn infer_return_type<'ctx>(
     expr: &Expr,
     locals: &std::collections::HashSet<String>,
 ) -> BasicTypeEnum<'ctx> {
-    match expr {
-        Expr::TypedLiteral { ty, .. } => match ty.as_str() {
-            "f32" | "f64" => codegen.context.f32_type().into(),
-            "bool" => codegen.context.bool_type().into(),
-            "string" | "ptr" => codegen.context.i8_type().ptr_type(AddressSpace::default()).into(),
-            _ => codegen.i32_type.into(),
-        },
-        Expr::BinaryOp { left, right, op } => {
+    match &expr.kind {
+        ExprKind::TypedLiteral { ty, .. } => codegen.primitive_type(ty).unwrap_or(codegen.i32_type.into()),
+        ExprKind::BinaryOp { left, right, op } => {
             // Comparison and logical operators return bool
             if ["==", "!=", "<", ">", "<=", ">=", "and", "or"].contains(&op.as_str()) {
                 return codegen.context.bool_type().into();
@@ -4327,15 +4566,21 @@ fn infer_return_type<'ctx>(
 
             let l = infer_return_type(codegen, left, locals);
             let r = infer_return_type(codegen, right, locals);
-            if l.is_float_type() || r.is_float_type() {
-                codegen.context.f32_type().into()
+            if l.is_float_type() && r.is_float_type() && l != r {
+                codegen.context.f64_type().into()
+            } else if l.is_float_type() || r.is_float_type() {
+                if l.is_float_type() { l } else { r }
             } else if l.is_int_type() && r.is_int_type() {
                 codegen.i32_type.into()
             } else {
                 l
             }
         }
-        Expr::Variable(name) => {
+        // 🔒 Returning a lambda returns a closure pointer, as in `compile_funcy`
+        ExprKind::Funcy { .. } => codegen.context.i8_type().ptr_type(AddressSpace::default()).into(),
+        // Instances are heap pointers
+        ExprKind::NewInstance { .. } => codegen.context.i8_type().ptr_type(AddressSpace::default()).into(),
+        ExprKind::Variable(name) => {
             if locals.contains(name) {
                 codegen.i32_type.into() // assume int for local vars
             } else {
@@ -4352,29 +4597,21 @@ let local_params: std::collections::HashSet<String> = params.iter().cloned().col
 
 println!("🔍 [type-infer] Inferring return type for '{}' with {} body statements", name, body.len());
 for stmt in body {
-    if let Node::Expr(Expr::Return(Some(inner))) = stmt {
+    if let NodeKind::Expr(Expr { kind: ExprKind::Return(Some(inner)), .. }) = &stmt.kind {
         inferred_ret_ty = infer_return_type(self, inner, &local_params);
         println!("🔍 [type-infer] Function '{}' returns type: {:?}", name, inferred_ret_ty);
     }
 }
 
-// === Build function type using inferred return type ===
-let fn_ty = inferred_ret_ty.fn_type(&param_types, false);
-
+// === Build function type using the declared or inferred return type ===
+let ret_ty = return_type.as_ref().map_or(inferred_ret_ty, |ty| self.descriptor_value_type(ty));
+let fn_ty = ret_ty.fn_type(&param_types, false);
 
 
-        // 🪶 Extract TypeDescriptors for dispatch
-        println!("🧪 extract_param_type_descriptors for '{}' with params_patterns: {:?}", name, params_patterns);
-        let type_descriptors = self.extract_param_type_descriptors(
-            params,
-            params_patterns,
-        );
-        println!("🧪 Result: {:?}", type_descriptors);
-
         let sig = FunctionSignature {
             name: name.clone(),
             param_types: type_descriptors.clone(),
-            return_type: TypeDescriptor::Primitive("i32".to_string()), // Default return type, will be inferred from actual function
+            return_type: self.result_type_descriptor(return_type.as_ref(), body),
         };
 
         // 🧩 Generate mangled name using TypeDescriptor
@@ -4408,6 +4645,7 @@ let fn_ty = inferred_ret_ty.fn_type(&param_types, false);
             .map(|td| match td {
                 crate::ast::types::TypeDescriptor::Entity(name) => name.clone(),
                 crate::ast::types::TypeDescriptor::ObjectType(name) => name.clone(),
+                crate::ast::types::TypeDescriptor::Interface(name) => name.clone(),
                 crate::ast::types::TypeDescriptor::Primitive(name) => name.clone(),
                 crate::ast::types::TypeDescriptor::HttpStatusLiteral(code) => format!("{}", code),
                 crate::ast::types::TypeDescriptor::HttpStatusRange(min, _) => format!("{}xx", min / 100),
@@ -4441,11 +4679,12 @@ let fn_ty = inferred_ret_ty.fn_type(&param_types, false);
     // === Compile function bodies ===
     // === Compile function bodies using predeclared signatures ===
 for node in nodes {
-    if let Node::Expr(Expr::Funcy { name, params, body, is_async, return_type, .. }) = node {
-        // For each overload signature of this function name
+    if let NodeKind::Expr(Expr { kind: ExprKind::Funcy { name, params, body, is_async, return_type, params_patterns }, .. }) = &node.kind {
+        // Each overload is compiled from its own definition's body
         if let Some(sig_list) = self.reverse_func_index.get(name).cloned() {
     // release immutable borrow immediately by cloning the Vec<FunctionSignature>
-    for sig in sig_list {
+    let own_types = self.extract_param_type_descriptors(params, params_patterns);
+    for sig in sig_list.into_iter().filter(|sig| sig.param_types == own_types) {
         let mangled_types: Vec<String> = sig.param_types.iter()
             .map(|td| td.to_mangle_string())
             .collect();
@@ -4465,6 +4704,7 @@ for node in nodes {
         let type_display: Vec<String> = sig.param_types.iter()
             .map(|td| match td {
                 crate::ast::types::TypeDescriptor::Entity(n) => n.clone(),
+                crate::ast::types::TypeDescriptor::Interface(n) => n.clone(),
                 crate::ast::types::TypeDescriptor::ObjectType(n) => n.clone(),
                 crate::ast::types::TypeDescriptor::Primitive(n) => n.clone(),
                 _ => format!("{:?}", td),
@@ -4479,18 +4719,18 @@ for node in nodes {
         );
 
         if *is_async {
-            self.compile_async_funcy(name, params, body);
+            self.compile_async_funcy(name, params, body)?;
         } else {
-            self.compile_funcy(name, params, body, Some(&sig.param_types), None, return_type.as_ref());
+            self.compile_funcy(name, params, body, Some(&sig.param_types), None, return_type.as_ref())?;
         }
     }
 }
  else {
             // Fallback — normal single overload
             if *is_async {
-                self.compile_async_funcy(name, params, body);
+                self.compile_async_funcy(name, params, body)?;
             } else {
-                self.compile_funcy(name, params, body, None, None, return_type.as_ref());
+                self.compile_funcy(name, params, body, None, None, return_type.as_ref())?;
             }
         }
     }
@@ -4499,12 +4739,12 @@ for node in nodes {
 
     // === Detect async entry ===
     let async_entry = nodes.iter().find_map(|n| {
-        if let Node::Expr(Expr::Funcy { name, is_async: true, .. }) = n {
+        if let NodeKind::Expr(Expr { kind: ExprKind::Funcy { name, is_async: true, .. }, .. }) = &n.kind {
             if name == "main" { Some(name.clone()) } else { None }
         } else { None }
     }).or_else(|| {
         nodes.iter().find_map(|n| {
-            if let Node::Expr(Expr::Funcy { name, is_async: true, .. }) = n {
+            if let NodeKind::Expr(Expr { kind: ExprKind::Funcy { name, is_async: true, .. }, .. }) = &n.kind {
                 Some(name.clone())
             } else { None }
         })
@@ -4539,20 +4779,29 @@ for node in nodes {
 
             let ret_val = self.i32_type.const_int(0, false);
             boot_builder.build_return(Some(&ret_val)).unwrap();
-            return bootstrap;
+            return Ok(bootstrap);
         }
     }
 
     // === Compile top-level code ===
     let mut last_int: Option<IntValue> = None;
+    self.defers.push(Vec::new());
     for node in nodes {
         println!("🧱 Compiling top-level node: {:?}", node);
-        if let Some(v) = self.compile_node(node) {
+        if let Some(v) = self.compile_node(node)? {
             if let BasicValueEnum::IntValue(iv) = v {
                 last_int = Some(iv);
             }
         }
     }
+    // Top-level `defer`s run when the program's code ends
+    if self.builder.get_insert_block().unwrap().get_terminator().is_none() {
+        self.run_deferred(0)?;
+    }
+    self.defers.clear();
+    if let Some(err) = self.defer_error.take() {
+        return Err(err);
+    }
 
     // === Generate wrapper main() -> main_async ===
     if self.module.get_function("main").is_none() {
@@ -4602,13 +4851,14 @@ if current_block.get_terminator().is_none() {
 };
 
 
+    self.leave_frame()?;
     self.builder.build_return(Some(&ret_val)).unwrap();
     println!("🟢 Added final return terminator to main_async::{:?}", current_block);
 }
 
 
 
-    async_fn
+    Ok(async_fn)
 }
 
 
@@ -4619,13 +4869,13 @@ if current_block.get_terminator().is_none() {
 
 
 
-   pub fn run_jit(&self) {
+   pub fn run_jit(&self) -> CompileResult<i32> {
     use std::mem;
     use libc::printf;
     use crate::runtime;
     use inkwell::execution_engine::ExecutionEngine;
 
-    let engine: ExecutionEngine<'_> = self.create_engine();
+    let engine: ExecutionEngine<'_> = self.create_engine()?;
 
     unsafe {
         crate::runtime::set_engine(mem::transmute::<
@@ -4687,6 +4937,12 @@ if let Some(func) = self.module.get_function("printf") {
         if let Some(func) = self.module.get_function("malloc") {
             engine.add_global_mapping(&func, libc::malloc as usize);
         }
+        if let Some(func) = self.module.get_function("wpp_gc_alloc") {
+            engine.add_global_mapping(&func, runtime::wpp_gc_alloc as *const () as usize);
+        }
+        if let Some(func) = self.module.get_function("wpp_gc_is_data") {
+            engine.add_global_mapping(&func, runtime::wpp_gc_is_data as *const () as usize);
+        }
         
         // === Printing subsystem ===
         // === Printing subsystem (Unified Basic Types) ===
@@ -4694,12 +4950,14 @@ unsafe extern "C" {
     fn wpp_print_value_basic(ptr: *const std::ffi::c_void, type_id: i32);
     fn wpp_print_array(ptr: *const std::ffi::c_void);
     fn wpp_print_object(ptr: *const std::ffi::c_void);
+    fn wpp_print_enum(ptr: *const std::ffi::c_void);
 }
 
 for (name, addr) in [
     ("wpp_print_value_basic", wpp_print_value_basic as usize),
     ("wpp_print_array", wpp_print_array as usize),
     ("wpp_print_object", wpp_print_object as usize),
+    ("wpp_print_enum", wpp_print_enum as usize),
 ] {
     if let Some(func) = self.module.get_function(name) {
         engine.add_global_mapping(&func, addr);
@@ -4854,47 +5112,49 @@ if let (Some(wms_arc), Some(resolver_arc)) = (&self.wms, &self.resolver) {
 
     let entry_addr = engine
         .get_function_address(entry_name)
-        .unwrap_or_else(|_| panic!("❌ Could not find function `{}` in module", entry_name));
+        .map_err(|_| CompileError::Backend(format!("the program has no `{}` function to run", entry_name)))?;
 
     let entry_fn: extern "C" fn() -> i32 = unsafe { mem::transmute(entry_addr) };
-    let result = entry_fn();
+    engine.run_static_constructors();
+    let result = runtime::gc::run_mutator(|| entry_fn());
 
     println!("🏁 [jit] Finished running {}, result = {}", entry_name, result);
+    Ok(result)
 }
 
 
-fn infer_type_from_literal(&self, raw: &str, is_float: bool) -> BasicTypeEnum<'ctx> {
+fn infer_type_from_literal(&self, raw: &str, is_float: bool, span: Span) -> CompileResult<BasicTypeEnum<'ctx>> {
     if is_float || raw.contains('.') {
-        self.context.f64_type().into()
+        Ok(self.context.f64_type().into())
     } else {
         match raw.parse::<i64>() {
-            Ok(v) if v >= i8::MIN as i64 && v <= i8::MAX as i64 => self.context.i8_type().into(),
-            Ok(v) if v >= i32::MIN as i64 && v <= i32::MAX as i64 => self.context.i32_type().into(),
-            Ok(_) => self.context.i64_type().into(),
-            Err(_) => panic!("❌ Invalid numeric literal: {}", raw),
+            Ok(v) if v >= i8::MIN as i64 && v <= i8::MAX as i64 => Ok(self.context.i8_type().into()),
+            Ok(v) if v >= i32::MIN as i64 && v <= i32::MAX as i64 => Ok(self.context.i32_type().into()),
+            Ok(_) => Ok(self.context.i64_type().into()),
+            Err(_) => Err(CompileError::type_mismatch(span, format!("integer literal `{}` does not fit in `i64`", raw))),
         }
     }
 }
 
-fn infer_expr_type(&self, expr: &Expr) -> BasicTypeEnum<'ctx> {
-    match expr {
-        Expr::TypedLiteral { ty, .. } => self.resolve_basic_type(ty),
-        Expr::Literal(v) => self.infer_type_from_literal(&v.to_string(), false),
-        Expr::BoolLiteral(_) => self.context.bool_type().into(),
-        Expr::Variable(name) => {
+fn infer_expr_type(&self, expr: &Expr) -> CompileResult<BasicTypeEnum<'ctx>> {
+    Ok(match &expr.kind {
+        ExprKind::TypedLiteral { ty, .. } => self.resolve_basic_type(ty, expr.span)?,
+        ExprKind::Literal(v) => self.infer_type_from_literal(&v.to_string(), false, expr.span)?,
+        ExprKind::BoolLiteral(_) => self.context.bool_type().into(),
+        ExprKind::Variable(name) => {
             self.vars
                 .get(name)
                 .or_else(|| self.globals.get(name))
                 .map(|v| v.ty)
                 .unwrap_or_else(|| self.i32_type.into())
         }
-        Expr::BinaryOp { left, right, .. } => {
-            let l = self.infer_expr_type(left);
-            let r = self.infer_expr_type(right);
+        ExprKind::BinaryOp { left, right, .. } => {
+            let l = self.infer_expr_type(left)?;
+            let r = self.infer_expr_type(right)?;
             self.merge_types(l, r)
         }
         _ => self.i32_type.into(),
-    }
+    })
 }
 
 fn merge_types(&self, left: BasicTypeEnum<'ctx>, right: BasicTypeEnum<'ctx>) -> BasicTypeEnum<'ctx> {
@@ -4924,213 +5184,1164 @@ fn merge_types(&self, left: BasicTypeEnum<'ctx>, right: BasicTypeEnum<'ctx>) ->
 pub fn compile_new_instance(
     &mut self,
     entity: &str,
-    args: &Vec<Expr>,
-) -> PointerValue<'ctx> {
-    println!("🏗️ [new_instance] Allocating entity '{}'", entity);
+    args: &[Expr],
+    span: Span,
+) -> CompileResult<PointerValue<'ctx>> {
+    wpp_debug!("🏗️ [new_instance] Allocating entity '{}'", entity);
 
     // === 1️⃣ Get the entity definition ===
     let entity_info = self
         .entities
         .get(entity)
-        .unwrap_or_else(|| panic!("Unknown entity type: {}", entity));
+        .ok_or_else(|| CompileError::UnknownEntity { name: entity.to_string(), span })?;
 
     let struct_ty = entity_info.struct_type;
+    let defaults = entity_info.defaults.clone();
+    let vtable = entity_info.vtable.as_pointer_value();
 
-    // === 2️⃣ Allocate memory for the instance ===
+    // === 2️⃣ Allocate the instance on the W++ heap ===
+    let alloc_fn = self.runtime_fn("wpp_gc_alloc")?;
+    let raw_ptr = self
+        .builder
+        .build_call(alloc_fn, &[struct_ty.size_of().unwrap().into()], "alloc_instance")
+        .unwrap()
+        .try_as_basic_value()
+        .left()
+        .unwrap()
+        .into_pointer_value();
     let typed_ptr = self
         .builder
-        .build_alloca(struct_ty, &format!("{}_ptr", entity))
+        .build_pointer_cast(raw_ptr, struct_ty.ptr_type(AddressSpace::default()), "as_struct")
         .unwrap();
 
-    // === 3️⃣ Optional: call constructor if it exists (e.g. Dog.new) ===
-    let ctor_sig = FunctionSignature {
-        name: format!("{}.new", entity),
-        param_types: args.iter().map(|_| TypeDescriptor::Primitive("i32".to_string())).collect(),
-        return_type: TypeDescriptor::Primitive("ptr".to_string()), // Constructor returns pointer to instance
-    };
-
-    if let Some(func) = self.functions.get(&ctor_sig).cloned() {
-        println!("🧩 [new_instance] Calling constructor '{}.new'", entity);
-
-        let mut compiled_args: Vec<BasicMetadataValueEnum<'ctx>> = Vec::new();
-
-        // Pass 'me' argument (the struct pointer itself)
-        compiled_args.push(typed_ptr.into());
+    // === 3️⃣ Point at the entity's vtable; fields start at their declared defaults, base fields first ===
+    let vtable_field = self.builder.build_struct_gep(struct_ty, typed_ptr, 0, "vtable_field").unwrap();
+    let vtable = self
+        .builder
+        .build_pointer_cast(vtable, self.context.i8_type().ptr_type(AddressSpace::default()), "vtable")
+        .unwrap();
+    self.builder.build_store(vtable_field, vtable).unwrap();
+    for (i, (field_ty, value)) in defaults.iter().enumerate() {
+        let val = self.compile_expr(value)?;
+        let from = self.static_field_type(value).unwrap_or_else(|| Self::field_type_of_llvm(val.get_type()));
+        let val = self.coerce_to_slot(val, &from, field_ty, value.span)?;
+        let field_ptr = self.builder.build_struct_gep(struct_ty, typed_ptr, i as u32 + 1, "field").unwrap();
+        self.builder.build_store(field_ptr, val).unwrap();
+    }
+
+    // === 4️⃣ Run the `init` chain, root entity first ===
+    // The nearest `init` takes every argument; an `init` further up the `alters` chain
+    // takes as many leading arguments as it declares.
+    let mut chain = Vec::new();
+    let mut current = Some(entity.to_string());
+    while let Some(name) = current {
+        let Some(info) = self.entities.get(&name) else { break };
+        if let Some(init) = info.methods.get("init") {
+            chain.push((name.clone(), *init));
+        }
+        current = info.base.clone();
+    }
 
-        // Compile constructor arguments
-        for a in args {
-            compiled_args.push(self.compile_expr(a).into());
+    let values = self.compile_typed_args(args)?;
+    let takes = |init: &FunctionValue<'ctx>| init.count_params() as usize - 1;
+    let arity_error = |callee: String, expected: usize| CompileError::ArgumentCount {
+        callee,
+        expected: format!("{} argument{}", expected, if expected == 1 { "" } else { "s" }),
+        found: args.len(),
+        span,
+    };
+    match chain.first() {
+        Some((owner, init)) if takes(init) != values.len() => {
+            return Err(arity_error(format!("{}.init", owner), takes(init)));
         }
+        None if !values.is_empty() => return Err(arity_error(entity.to_string(), 0)),
+        _ => {}
+    }
 
-        // Call the constructor
+    for (owner, init) in chain.into_iter().rev() {
+        let wanted = takes(&init);
+        if wanted > values.len() {
+            return Err(arity_error(format!("{}.init", owner), wanted));
+        }
+        let call_args = self.method_call_args(init, raw_ptr, &values[..wanted]);
         self.builder
-            .build_call(func, &compiled_args, &format!("call_ctor_{}", entity))
+            .build_call(init, &call_args, &format!("call_{}_init", owner))
             .unwrap();
-    } else {
-        println!("⚠️ [new_instance] No constructor found for '{}', skipping init", entity);
+        self.check_exception()?;
     }
 
-    // === 4️⃣ Return pointer to the instance ===
-    typed_ptr
+    // === 5️⃣ Return pointer to the instance ===
+    Ok(raw_ptr)
 }
 
+/// Compile call arguments, keeping each one's W++ type for `method_call_args`
+fn compile_typed_args(&mut self, args: &[Expr]) -> CompileResult<Vec<(BasicValueEnum<'ctx>, FieldType)>> {
+    let mut values = Vec::new();
+    for arg in args {
+        let val = self.compile_expr(arg)?;
+        let ty = self.static_field_type(arg).unwrap_or_else(|| Self::field_type_of_llvm(val.get_type()));
+        values.push((val, ty));
+    }
+    Ok(values)
+}
 
-
-fn compile_switch(
+/// Arguments for a call to an entity method: the instance as `me`, then `args` with numbers
+/// converted to the method's parameter types
+fn method_call_args(
     &mut self,
-    discr_expr: &Expr,
-    cases: &Vec<(Expr, Vec<Node>)>,
-    default: &Option<Vec<Node>>,
-) -> BasicValueEnum<'ctx> {
-    let discr_val = self.compile_expr(discr_expr).into_int_value();
+    method: FunctionValue<'ctx>,
+    me: PointerValue<'ctx>,
+    args: &[(BasicValueEnum<'ctx>, FieldType)],
+) -> Vec<BasicMetadataValueEnum<'ctx>> {
+    let mut call_args: Vec<BasicMetadataValueEnum<'ctx>> = vec![me.into()];
+    for ((val, from), param_ty) in args.iter().zip(method.get_type().get_param_types().into_iter().skip(1)) {
+        let to = Self::field_type_of_llvm(param_ty);
+        let val = if from.is_numeric() && to.is_numeric() { self.convert_numeric(*val, from, &to) } else { *val };
+        call_args.push(val.into());
+    }
+    call_args
+}
+
+/// Load the pointer to `method` from the vtable `instance` points at, typed like `static_impl`;
+/// `None` when the method has no slot (`init`)
+fn virtual_method(
+    &self,
+    instance: PointerValue<'ctx>,
+    entity: &str,
+    method: &str,
+    static_impl: FunctionValue<'ctx>,
+) -> Option<PointerValue<'ctx>> {
+    let slot = self.entities.get(entity)?.slots.iter().position(|s| s == method)?;
+    let i8_ptr = self.context.i8_type().ptr_type(AddressSpace::default());
+    let i8_ptr_ptr = i8_ptr.ptr_type(AddressSpace::default());
 
-    let function = self.builder.get_insert_block().unwrap().get_parent().unwrap();
-    let end_bb = self.context.append_basic_block(function, "switch.end");
-    self.switch_stack.push(end_bb);
-    let default_bb = self.context.append_basic_block(function, "switch.default");
+    let vtable_field = self.builder.build_pointer_cast(instance, i8_ptr_ptr, "vtable_field").unwrap();
+    let vtable = self.builder.build_load(i8_ptr, vtable_field, "vtable").unwrap().into_pointer_value();
+    let vtable = self.builder.build_pointer_cast(vtable, i8_ptr_ptr, "vtable_slots").unwrap();
+    let entry = unsafe {
+        self.builder
+            .build_in_bounds_gep(i8_ptr, vtable, &[self.i32_type.const_int(slot as u64, false)], "vtable_entry")
+            .unwrap()
+    };
+    let method_ptr = self.builder.build_load(i8_ptr, entry, method).unwrap().into_pointer_value();
+    Some(
+        self.builder
+            .build_pointer_cast(method_ptr, static_impl.get_type().ptr_type(AddressSpace::default()), "method_ptr")
+            .unwrap(),
+    )
+}
 
-    // Prepare case blocks
-    let mut case_blocks = Vec::new();
-    for (case_expr, body) in cases {
-        let bb = self.context.append_basic_block(function, "switch.case");
-        case_blocks.push((case_expr.clone(), bb, body));
+/// `entity` or one of the entities it alters names `interface` in `implements`
+fn implements(&self, entity: &str, interface: &str) -> bool {
+    let mut current = Some(entity.to_string());
+    while let Some(name) = current {
+        let Some(info) = self.entities.get(&name) else { return false };
+        if info.interfaces.iter().any(|i| i == interface) {
+            return true;
+        }
+        current = info.base.clone();
     }
+    false
+}
 
-    // Compile expressions before switch instruction
-    let mut case_values = Vec::new();
-    for (case_expr, bb, _) in &case_blocks {
-        let val = self.compile_expr(case_expr);
-        case_values.push((val.into_int_value(), *bb));
+/// Steps along the `alters` chain from `entity` up to `ancestor`, or `None` if `entity` does not alter it
+fn alters_distance(&self, entity: &str, ancestor: &str) -> Option<u32> {
+    let mut current = Some(entity.to_string());
+    let mut steps = 0;
+    while let Some(name) = current {
+        if name == ancestor {
+            return Some(steps);
+        }
+        steps += 1;
+        current = self.entities.get(&name)?.base.clone();
     }
+    None
+}
 
-    // Build switch instruction
-    self.builder
-        .build_switch(discr_val, default_bb, &case_values)
-        .unwrap();
-
-    // Compile each case body
-    for (_, bb, body) in &case_blocks {
-        self.builder.position_at_end(*bb);
-        for node in *body {
-            self.compile_node(node);
-        }
-        if self.builder.get_insert_block().unwrap().get_terminator().is_none() {
-            self.builder.build_unconditional_branch(end_bb).unwrap();
+/// `entity` and every entity that alters it, directly or not
+fn subtypes_of(&self, entity: &str) -> Vec<String> {
+    let mut subtypes: Vec<String> = self.entities.keys().filter(|e| self.alters_distance(e, entity).is_some()).cloned().collect();
+    subtypes.sort();
+    subtypes
+}
+
+/// How far an argument is from a parameter that accepts it: one per `alters` step up to an
+/// entity parameter, 1 when a number widens and 2 when an integer becomes a float
+fn arg_distance(&self, param: &TypeDescriptor, arg_type: &TypeDescriptor) -> u32 {
+    match (param, arg_type) {
+        (TypeDescriptor::Entity(param), TypeDescriptor::Entity(arg)) => self.alters_distance(arg, param).unwrap_or(0),
+        (TypeDescriptor::Primitive(param), TypeDescriptor::Primitive(arg)) if param != arg => {
+            let (param, arg) = (FieldType::from_string(param), FieldType::from_string(arg));
+            match (param.is_numeric() && arg.is_numeric(), param.is_float() && !arg.is_float()) {
+                (true, true) => 2,
+                (true, false) => 1,
+                _ => 0,
+            }
         }
+        _ => 0,
     }
+}
 
-    // Default body
-    self.builder.position_at_end(default_bb);
-    if let Some(body) = default {
-        for node in body {
-            self.compile_node(node);
-        }
+/// Entities between `entity` and the root of its `alters` chain; deeper entities are more specific
+fn alters_depth(&self, entity: &str) -> u32 {
+    let mut depth = 0;
+    let mut current = self.entities.get(entity).and_then(|info| info.base.clone());
+    while let Some(base) = current {
+        depth += 1;
+        current = self.entities.get(&base).and_then(|info| info.base.clone());
     }
-    if self.builder.get_insert_block().unwrap().get_terminator().is_none() {
-        self.builder.build_unconditional_branch(end_bb).unwrap();
+    depth
+}
+
+/// Every method of `interface` must resolve on `entity` with the declared number of parameters,
+/// and with the same LLVM signature as in the other implementing entities, since an interface
+/// call picks the body at runtime
+fn check_conformance(&self, entity: &str, interface: &str, span: Span) -> CompileResult<()> {
+    let Some(def) = self.interfaces.get(interface) else {
+        return Err(CompileError::type_mismatch(span, format!("unknown interface `{}`", interface)));
+    };
+    for method in &def.methods {
+        let Some((owner, func)) = self.resolve_method(entity, &method.name) else {
+            return Err(CompileError::MissingInterfaceMethod {
+                entity: entity.to_string(),
+                interface: interface.to_string(),
+                method: method.name.clone(),
+                span,
+            });
+        };
+        let takes = func.count_params() as usize - 1;
+        if takes != method.params.len() {
+            return Err(CompileError::type_mismatch(
+                span,
+                format!(
+                    "`{}.{}` takes {} parameter{}, but `{}.{}` declares {}",
+                    owner, method.name, takes, if takes == 1 { "" } else { "s" }, interface, method.name, method.params.len()
+                ),
+            ));
+        }
+        if let Some((other, other_func)) = self.interface_impls(interface, &method.name).into_iter().find(|(e, _)| e != entity)
+            && other_func.get_type() != func.get_type()
+        {
+            return Err(CompileError::type_mismatch(
+                span,
+                format!(
+                    "`{}.{}` must take and return the same types as `{}.{}`, which also implements `{}`",
+                    entity, method.name, other, method.name, interface
+                ),
+            ));
+        }
     }
+    Ok(())
+}
 
-    // Return to end
-    self.builder.position_at_end(end_bb);
-    self.switch_stack.pop();
-    self.context.i32_type().const_int(0, false).into()
+/// Each compiled entity that implements `interface`, with its implementation of `method`
+fn interface_impls(&self, interface: &str, method: &str) -> Vec<(String, FunctionValue<'ctx>)> {
+    let mut impls: Vec<(String, FunctionValue<'ctx>)> = self
+        .entities
+        .keys()
+        .filter(|entity| self.implements(entity, interface))
+        .filter_map(|entity| self.resolve_method(entity, method).map(|(_, func)| (entity.clone(), func)))
+        .collect();
+    // Stable order keeps the generated IR deterministic
+    impls.sort_by(|a, b| a.0.cmp(&b.0));
+    impls
+}
+
+/// Pick the implementation of an interface method by comparing the instance's vtable with the
+/// vtable of each implementing entity
+fn interface_method(&self, instance: PointerValue<'ctx>, interface: &str, method: &str) -> Option<(FunctionValue<'ctx>, PointerValue<'ctx>)> {
+    let impls = self.interface_impls(interface, method);
+    let representative = impls.first()?.1;
+    let fn_type = representative.get_type();
+    let fn_ptr_ty = fn_type.ptr_type(AddressSpace::default());
+    let i8_ptr = self.context.i8_type().ptr_type(AddressSpace::default());
+
+    let vtable_field = self.builder.build_pointer_cast(instance, i8_ptr.ptr_type(AddressSpace::default()), "vtable_field").unwrap();
+    let vtable = self.builder.build_load(i8_ptr, vtable_field, "vtable").unwrap().into_pointer_value();
+    let mut chosen = fn_ptr_ty.const_null();
+    for (entity, func) in impls.iter().rev() {
+        let entity_vtable = self.entities[entity].vtable.as_pointer_value().const_cast(i8_ptr);
+        let is_entity = self
+            .builder
+            .build_int_compare(inkwell::IntPredicate::EQ, vtable, entity_vtable, &format!("is_{}", entity))
+            .unwrap();
+        chosen = self
+            .builder
+            .build_select(is_entity, func.as_global_value().as_pointer_value().const_cast(fn_ptr_ty), chosen, method)
+            .unwrap()
+            .into_pointer_value();
+    }
+    Some((representative, chosen))
 }
-pub fn compile_funcy(
-    &mut self,
-    name: &str,
-    params: &[String],
-    body: &[Node],
-    param_override: Option<&[TypeDescriptor]>,
-    entity_name: Option<&str>, // 👈 added
-    return_type_override: Option<&TypeDescriptor>, // ✅ NEW: Explicit return type from annotation
- // 👈 optional explicit type list
-) -> FunctionValue<'ctx> {
-    // === Step 1: Detect inferred types from body ===
-    let mut int_params = std::collections::HashSet::new();
-    let mut ptr_params = std::collections::HashSet::new();
-    let mut float_params = std::collections::HashSet::new();
-    let mut bool_params = std::collections::HashSet::new();
-    let mut contains_string_literal = false;
 
-    fn scan_for_types(
-    nodes: &[Node],
-    int_params: &mut std::collections::HashSet<String>,
-    ptr_params: &mut std::collections::HashSet<String>,
-    float_params: &mut std::collections::HashSet<String>,
-    bool_params: &mut std::collections::HashSet<String>,
-    contains_string_literal: &mut bool,
-) {
-    for node in nodes {
-        match node {
-            Node::Expr(expr) => match expr {
-                Expr::BinaryOp { left, right, op } => {
-                    // 🧵 detect string concatenation
-                    if let (Expr::StringLiteral(_), _) | (_, Expr::StringLiteral(_)) =
-                        (&**left, &**right)
-                    {
-                        *contains_string_literal = true;
-                        if let Expr::Variable(name) = left.as_ref() {
-                            ptr_params.insert(name.clone());
-                        }
-                        if let Expr::Variable(name) = right.as_ref() {
-                            ptr_params.insert(name.clone());
-                        }
-                    }
+/// How a call argument is described for overload resolution
+fn call_arg_type(&self, a: &Expr) -> TypeDescriptor {
+    // 🏷️ Enum values dispatch on their enum name
+    if let Some(FieldType::Object(enum_name)) = self.static_field_type(a)
+        && self.enums.contains_key(&enum_name)
+    {
+        return TypeDescriptor::ObjectType(enum_name);
+    }
+    match &a.kind {
+        ExprKind::StringLiteral(_) => TypeDescriptor::Primitive("string".to_string()),
+        ExprKind::Literal(num) => {
+            // Check if it looks like an HTTP status code (100-599)
+            if *num >= 100 && *num < 600 {
+                TypeDescriptor::HttpStatusLiteral(*num as u16)
+            } else {
+                TypeDescriptor::Primitive("i32".to_string())
+            }
+        }
+        ExprKind::TypedLiteral { value, ty } if ty == "f64" => TypeDescriptor::Primitive("f64".to_string()),
+        ExprKind::TypedLiteral { value, ty } if ty.starts_with("i") => {
+            // Check if it's an HTTP status code
+            if let Ok(num) = value.parse::<i32>() {
+                if num >= 100 && num < 600 {
+                    TypeDescriptor::HttpStatusLiteral(num as u16)
+                } else {
+                    TypeDescriptor::Primitive("i32".to_string())
+                }
+            } else {
+                TypeDescriptor::Primitive("i32".to_string())
+            }
+        }
+        ExprKind::Variable(var_name) => {
+            // Check if it's a known function for higher-order dispatch
+            if let Some(sigs) = self.reverse_func_index.get(var_name) {
+                if let Some(sig) = sigs.first() {
+                    // Create Function TypeDescriptor from function signature
+                    return TypeDescriptor::Function {
+                        param_types: sig.param_types.clone(),
+                        return_type: Box::new(sig.return_type.clone()),
+                    };
+                }
+            }
 
-                    // 🧮 detect arithmetic or logical types
-                    else if ["+", "-", "*", "/", "%"].contains(&op.as_str()) {
-                        // detect if either side is float literal
-                        let mut is_float = false;
-                        if let Expr::TypedLiteral { ty, .. } = &**left {
-                            if ty == "f32" {
-                                is_float = true;
-                            }
-                        }
-                        if let Expr::TypedLiteral { ty, .. } = &**right {
-                            if ty == "f32" {
-                                is_float = true;
-                            }
-                        }
+            // Check if it's a known variable (local or global)
+            if let Some(var_info) = self.vars.get(var_name).or_else(|| self.globals.get(var_name)) {
+                // 🎯 Check for function signature first (higher-order dispatch)
+                if let Some(fn_sig) = &var_info.function_signature {
+                    return TypeDescriptor::Function {
+                        param_types: fn_sig.param_types.clone(),
+                        return_type: Box::new(fn_sig.return_type.clone()),
+                    };
+                }
 
-                        if is_float {
-                            if let Expr::Variable(name) = left.as_ref() {
-                                float_params.insert(name.clone());
-                            }
-                            if let Expr::Variable(name) = right.as_ref() {
-                                float_params.insert(name.clone());
-                            }
-                        } else {
-                            if let Expr::Variable(name) = left.as_ref() {
-                                int_params.insert(name.clone());
-                            }
-                            if let Expr::Variable(name) = right.as_ref() {
-                                int_params.insert(name.clone());
-                            }
-                        }
+                // 🎯 Check for entity or object type
+                if let Some(entity_name) = &var_info.entity_type {
+                    if self.interfaces.contains_key(entity_name) {
+                        return TypeDescriptor::Interface(entity_name.clone());
                     }
+                    return TypeDescriptor::Entity(entity_name.clone());
+                }
+                if let Some(obj_type_name) = &var_info.object_type_name {
+                    return TypeDescriptor::ObjectType(obj_type_name.clone());
+                }
 
-                    // 🔀 detect boolean expressions (comparisons, logic ops)
-                    else if ["==", "!=", "<", ">", "<=", ">=", "and", "or"].contains(&op.as_str()) {
-                        if let Expr::Variable(name) = left.as_ref() {
-                            bool_params.insert(name.clone());
-                        }
-                        if let Expr::Variable(name) = right.as_ref() {
-                            bool_params.insert(name.clone());
-                        }
-                    }
+                match &var_info.field_type {
+                    Some(FieldType::Any) => return TypeDescriptor::Any,
+                    Some(ft) if ft.is_numeric() => return TypeDescriptor::Primitive(ft.to_string()),
+                    _ => {}
+                }
 
-                    // recursive descent into both sides
-                    scan_for_types(
-                        &[Node::Expr(*left.clone())],
-                        int_params,
-                        ptr_params,
-                        float_params,
-                        bool_params,
-                        contains_string_literal,
-                    );
-                    scan_for_types(
-                        &[Node::Expr(*right.clone())],
-                        int_params,
+                // Fall back to LLVM type inference
+                let var_ty = &var_info.ty;
+                if var_ty.is_int_type() {
+                    TypeDescriptor::Primitive("i32".to_string())
+                } else if var_ty.is_pointer_type() {
+                    // Treat all pointers as strings for now (i8*)
+                    TypeDescriptor::Primitive("string".to_string())
+                } else if var_ty.is_float_type() {
+                    TypeDescriptor::Primitive("f64".to_string())
+                } else {
+                    TypeDescriptor::Primitive("i32".to_string())
+                }
+            } else {
+                TypeDescriptor::Primitive("i32".to_string())
+            }
+        }
+        ExprKind::BinaryOp { .. } | ExprKind::Cast { .. }
+            if self.static_field_type(a).is_some_and(|ft| ft.is_numeric()) =>
+        {
+            TypeDescriptor::Primitive(self.static_field_type(a).unwrap().to_string())
+        }
+        ExprKind::BinaryOp { left, right, .. } => {
+            // A bad operand is reported when the argument itself is compiled
+            let is_float = |e: &Expr| self.infer_expr_type(e).is_ok_and(|t| t.is_float_type());
+            if is_float(left) || is_float(right) {
+                TypeDescriptor::Primitive("f64".to_string())
+            } else {
+                TypeDescriptor::Primitive("i32".to_string())
+            }
+        }
+        ExprKind::NewInstance { entity, .. } => {
+            // Instance creation returns an entity type
+            TypeDescriptor::Entity(entity.clone())
+        }
+        ExprKind::Funcy { params, return_type, .. } => {
+            // 🔒 Inline lambda: unannotated parameters and return default to i32
+            let param_types = params
+                .iter()
+                .map(|p| TypeDescriptor::Primitive(p.split_once(':').map_or("i32", |(_, t)| t).to_string()))
+                .collect();
+            let return_type = return_type.clone().unwrap_or(TypeDescriptor::Primitive("i32".to_string()));
+            TypeDescriptor::Function { param_types, return_type: Box::new(return_type) }
+        }
+        ExprKind::ObjectLiteral { type_name, .. } => {
+            // If object has a type name, use it for dispatch
+            if let Some(obj_type) = type_name {
+                TypeDescriptor::ObjectType(obj_type.clone())
+            } else {
+                TypeDescriptor::Primitive("string".to_string())
+            }
+        }
+        // Calls, indexing and the rest: their static type, else the callee's LLVM return type
+        _ => match self.static_field_type(a).or_else(|| self.call_return_field_type(a)) {
+            Some(FieldType::Object(entity)) if self.entities.contains_key(&entity) => TypeDescriptor::Entity(entity),
+            Some(FieldType::Object(interface)) if self.interfaces.contains_key(&interface) => TypeDescriptor::Interface(interface),
+            Some(FieldType::Object(name)) if name != "object" => TypeDescriptor::ObjectType(name),
+            Some(ft @ (FieldType::String | FieldType::Bool | FieldType::Any)) => TypeDescriptor::Primitive(ft.to_string()),
+            Some(ft) if ft.is_numeric() => TypeDescriptor::Primitive(ft.to_string()),
+            _ => TypeDescriptor::Primitive("i32".to_string()),
+        },
+    }
+}
+
+/// Argument types as some overloads spell them (f64 → f32, ptr → string)
+fn normalized_arg_types(arg_types: &[TypeDescriptor]) -> Vec<TypeDescriptor> {
+    arg_types
+        .iter()
+        .map(|t| match t {
+            TypeDescriptor::Primitive(ty) if ty == "f64" => TypeDescriptor::Primitive("f32".to_string()),
+            TypeDescriptor::Primitive(ty) if ty == "ptr" => TypeDescriptor::Primitive("string".to_string()),
+            _ => t.clone(),
+        })
+        .collect()
+}
+
+/// The LLVM function behind an overload, found by its mangled name (`greet__entity_Dog`)
+fn overload_function(&self, sig: &FunctionSignature) -> Option<FunctionValue<'ctx>> {
+    let llvm_name = if sig.param_types.is_empty() {
+        sig.name.clone()
+    } else {
+        let mangled_types: Vec<String> = sig.param_types.iter().map(TypeDescriptor::to_mangle_string).collect();
+        format!("{}__{}", sig.name, mangled_types.join("_"))
+    };
+    self.module.get_function(&llvm_name).or_else(|| self.functions.get(sig).copied())
+}
+
+/// Whether an overload's parameter accepts an argument whose type is known at compile time
+fn static_arg_matches(
+    &self,
+    sig: &FunctionSignature,
+    sig_type: &TypeDescriptor,
+    arg_type: &TypeDescriptor,
+    arg: &Expr,
+    normalized_arg_types: &[TypeDescriptor],
+) -> bool {
+    match (sig_type, arg_type) {
+        // Exact match
+        (a, b) if a == b => true,
+        // Numbers widen to the parameter's type, and untyped literals take it when they fit
+        (TypeDescriptor::Primitive(param), _) if FieldType::from_string(param).is_numeric()
+            && arg.untyped_number().is_some_and(|n| FieldType::from_string(param).fits(&n)) => true,
+        (TypeDescriptor::Primitive(param), TypeDescriptor::Primitive(found))
+            if FieldType::from_string(found).is_numeric()
+                && FieldType::from_string(found).widens_to(&FieldType::from_string(param)) => true,
+        // Any wildcard matches anything
+        (TypeDescriptor::Any, _) => true,
+        // An entity accepts the entities that alter it, and an interface the entities that implement it
+        (TypeDescriptor::Entity(param), TypeDescriptor::Entity(entity)) => self.alters_distance(entity, param).is_some(),
+        (TypeDescriptor::Interface(interface), TypeDescriptor::Entity(entity)) => self.implements(entity, interface),
+        // HTTP status range matches literal in range
+        (TypeDescriptor::HttpStatusRange(min, max), TypeDescriptor::HttpStatusLiteral(code)) => {
+            *code >= *min && *code <= *max
+        }
+        // String and ptr are compatible
+        (TypeDescriptor::Primitive(s1), TypeDescriptor::Primitive(s2))
+            if (s1 == "string" || s1 == "ptr") && (s2 == "string" || s2 == "ptr") => true,
+        // Normalized match (try normalized_arg_types)
+        _ => sig
+            .param_types
+            .iter()
+            .position(|t| t == sig_type)
+            .and_then(|i| normalized_arg_types.get(i))
+            .is_some_and(|normalized| sig_type == normalized),
+    }
+}
+
+/// What a function's callers are told it returns: its declared result, with entity and interface
+/// names resolved, or else the entity its first `return new(...)` builds. Others default to i32
+fn result_type_descriptor(&self, declared: Option<&TypeDescriptor>, body: &[Node]) -> TypeDescriptor {
+    match declared {
+        Some(TypeDescriptor::ObjectType(name)) if self.interfaces.contains_key(name) => TypeDescriptor::Interface(name.clone()),
+        Some(TypeDescriptor::ObjectType(name)) if self.entities.contains_key(name) => TypeDescriptor::Entity(name.clone()),
+        Some(td @ (TypeDescriptor::Primitive(_) | TypeDescriptor::ObjectType(_) | TypeDescriptor::Entity(_) | TypeDescriptor::Interface(_))) => {
+            td.clone()
+        }
+        Some(_) => TypeDescriptor::Primitive("i32".to_string()),
+        None => body
+            .iter()
+            .find_map(|stmt| match &stmt.kind {
+                NodeKind::Expr(Expr { kind: ExprKind::Return(Some(inner)), .. }) => Some(inner),
+                _ => None,
+            })
+            .and_then(|inner| match &inner.kind {
+                ExprKind::NewInstance { entity, .. } => Some(TypeDescriptor::Entity(entity.clone())),
+                _ => None,
+            })
+            .unwrap_or_else(|| TypeDescriptor::Primitive("i32".to_string())),
+    }
+}
+
+/// LLVM return type shared by every overload of a called user function, or of the method a
+/// call on an entity variable resolves to, as a W++ type
+fn call_return_field_type(&self, expr: &Expr) -> Option<FieldType> {
+    let ExprKind::Call { name, .. } = &expr.kind else { return None };
+    if let Some((var_name, method)) = name.split_once('.')
+        && let Some(entity) = self.vars.get(var_name).or_else(|| self.globals.get(var_name)).and_then(|var| var.entity_type.as_ref())
+        && let Some((_, func)) = self.resolve_method(entity, method)
+    {
+        return func.get_type().get_return_type().map(Self::field_type_of_llvm);
+    }
+    let returns: Vec<Option<BasicTypeEnum<'ctx>>> = self
+        .reverse_func_index
+        .get(name)?
+        .iter()
+        .map(|sig| self.overload_function(sig).map(|f| f.get_type().get_return_type()))
+        .collect::<Option<_>>()?;
+    let first = (*returns.first()?)?;
+    returns.iter().all(|ret| *ret == Some(first)).then(|| Self::field_type_of_llvm(first))
+}
+
+/// Whether an argument is a pointer whose W++ type is not known at compile time: a call whose
+/// result was not declared, or a variable that records nothing but its LLVM type
+fn untyped_pointer(&self, arg: &Expr) -> bool {
+    match &arg.kind {
+        ExprKind::Variable(name) => self.vars.get(name).or_else(|| self.globals.get(name)).is_some_and(|var| {
+            var.ty.is_pointer_type()
+                && var.field_type.is_none()
+                && var.entity_type.is_none()
+                && var.object_type_name.is_none()
+                && var.function_signature.is_none()
+        }),
+        ExprKind::Call { .. } => self.static_field_type(arg).is_none() && self.call_return_field_type(arg) == Some(FieldType::String),
+        _ => false,
+    }
+}
+
+/// For each argument of a call to `name`, how its overload is chosen at runtime, if it must be
+fn dynamic_args(&self, name: &str, args: &[Expr], arg_types: &[TypeDescriptor]) -> Vec<Option<DynamicArg>> {
+    let sigs: Vec<&FunctionSignature> = self
+        .reverse_func_index
+        .get(name)
+        .map(|sigs| sigs.iter().filter(|sig| sig.param_types.len() == args.len()).collect())
+        .unwrap_or_default();
+    let some_param = |i: usize, pred: fn(&TypeDescriptor) -> bool| sigs.iter().any(|sig| pred(&sig.param_types[i]));
+    args.iter()
+        .zip(arg_types)
+        .enumerate()
+        .map(|(i, (arg, ty))| {
+            let literal = matches!(arg.kind, ExprKind::Literal(_) | ExprKind::TypedLiteral { .. });
+            if self.static_field_type(arg) == Some(FieldType::Any) {
+                Some(DynamicArg::Boxed)
+            } else if matches!(ty, TypeDescriptor::Interface(_)) && some_param(i, |p| matches!(p, TypeDescriptor::Entity(_))) {
+                Some(DynamicArg::Instance)
+            } else if let TypeDescriptor::Entity(entity) = ty
+                && sigs.iter().any(|sig| matches!(&sig.param_types[i], TypeDescriptor::Entity(p) if p != entity && self.alters_distance(p, entity).is_some()))
+            {
+                // An instance may be of an entity that alters its static type and has its own overload
+                Some(DynamicArg::Instance)
+            } else if self.untyped_pointer(arg) && some_param(i, |p| matches!(p, TypeDescriptor::Entity(_) | TypeDescriptor::Interface(_))) {
+                Some(DynamicArg::Pointer)
+            } else if !literal
+                && *ty == TypeDescriptor::Primitive("i32".to_string())
+                && some_param(i, |p| matches!(p, TypeDescriptor::HttpStatusLiteral(_) | TypeDescriptor::HttpStatusRange(..)))
+            {
+                Some(DynamicArg::Status)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Whether a parameter could accept a dynamic argument once its runtime type is known
+fn may_match_at_runtime(&self, param: &TypeDescriptor, kind: DynamicArg, arg_type: &TypeDescriptor) -> bool {
+    match (kind, param) {
+        (DynamicArg::Boxed, TypeDescriptor::Function { .. }) => false,
+        (DynamicArg::Boxed, _) => true,
+        (DynamicArg::Instance, TypeDescriptor::Entity(entity)) => match arg_type {
+            TypeDescriptor::Interface(interface) => self.subtypes_of(entity).iter().any(|e| self.implements(e, interface)),
+            TypeDescriptor::Entity(base) => self.alters_distance(entity, base).is_some() || self.alters_distance(base, entity).is_some(),
+            _ => false,
+        },
+        (DynamicArg::Instance, TypeDescriptor::Interface(interface)) if matches!(arg_type, TypeDescriptor::Entity(e) if self.implements(e, interface)) => true,
+        (DynamicArg::Instance, other) => other == arg_type,
+        (DynamicArg::Pointer, p) => match p {
+            TypeDescriptor::Entity(_) | TypeDescriptor::Interface(_) => true,
+            TypeDescriptor::Primitive(p) => FieldType::from_string(p) == FieldType::String || p == "ptr",
+            _ => false,
+        },
+        (DynamicArg::Status, p) => matches!(
+            p,
+            TypeDescriptor::HttpStatusLiteral(_) | TypeDescriptor::HttpStatusRange(..) | TypeDescriptor::Any
+        ) || *p == TypeDescriptor::Primitive("i32".to_string()),
+    }
+}
+
+/// The W++ type a boxed argument must have to be passed to `param` (of LLVM type `llvm`)
+fn boxed_param_type(param: &TypeDescriptor, llvm: BasicTypeEnum<'ctx>) -> FieldType {
+    match param {
+        TypeDescriptor::Primitive(p) if p == "string" || p == "ptr" => FieldType::String,
+        TypeDescriptor::Primitive(p) => FieldType::from_string(p),
+        TypeDescriptor::Entity(name) | TypeDescriptor::Interface(name) | TypeDescriptor::ObjectType(name) => {
+            FieldType::Object(name.clone())
+        }
+        TypeDescriptor::HttpStatusLiteral(_) | TypeDescriptor::HttpStatusRange(..) => FieldType::Int32,
+        TypeDescriptor::Function { .. } | TypeDescriptor::Any => Self::field_type_of_llvm(llvm),
+    }
+}
+
+/// The overloads of `name` that could accept a call's arguments once the dynamic ones are
+/// known, most specific first. Entity parameters deeper in an `alters` chain are tried before
+/// their bases, and otherwise equally specific overloads keep their declaration order
+fn dispatch_candidates(
+    &self,
+    name: &str,
+    args: &[Expr],
+    arg_types: &[TypeDescriptor],
+    dynamic: &[Option<DynamicArg>],
+) -> Vec<(FunctionSignature, FunctionValue<'ctx>)> {
+    let normalized_arg_types = Self::normalized_arg_types(arg_types);
+    let mut candidates: Vec<(FunctionSignature, FunctionValue<'ctx>)> = self
+        .reverse_func_index
+        .get(name)
+        .into_iter()
+        .flatten()
+        .filter(|sig| sig.param_types.len() == args.len())
+        .filter(|sig| {
+            sig.param_types.iter().zip(arg_types).zip(args.iter().zip(dynamic)).all(|((param, ty), (arg, kind))| match kind {
+                Some(kind) => self.may_match_at_runtime(param, *kind, ty),
+                None => self.static_arg_matches(sig, param, ty, arg, &normalized_arg_types),
+            })
+        })
+        .filter_map(|sig| Some((sig.clone(), self.overload_function(sig)?)))
+        .collect();
+    candidates.sort_by_key(|(sig, _)| {
+        let depth: u32 = sig
+            .param_types
+            .iter()
+            .map(|param| match param {
+                TypeDescriptor::Entity(entity) => self.alters_depth(entity),
+                _ => 0,
+            })
+            .sum();
+        std::cmp::Reverse((self.overload_rank(sig, arg_types), depth))
+    });
+    candidates
+}
+
+/// How well an overload fits a call: the most specific patterns first, then the arguments that
+/// are fewest `alters` steps or numeric conversions away
+fn overload_rank(&self, sig: &FunctionSignature, arg_types: &[TypeDescriptor]) -> (u32, std::cmp::Reverse<u32>) {
+    let specificity = sig.param_types.iter().map(TypeDescriptor::specificity).sum();
+    let distance = sig.param_types.iter().zip(arg_types).map(|(param, arg)| self.arg_distance(param, arg)).sum();
+    (specificity, std::cmp::Reverse(distance))
+}
+
+/// Overloads picked at runtime that return different types hand their result back boxed
+fn returns_differ(candidates: &[(FunctionSignature, FunctionValue<'ctx>)]) -> bool {
+    let mut returns = candidates.iter().map(|(_, func)| func.get_type().get_return_type());
+    returns.next().is_some_and(|first| returns.any(|ret| ret != first))
+}
+
+/// `any` when a call picks its overload at runtime and the overloads return different types
+fn dispatched_call_type(&self, name: &str, args: &[Expr]) -> Option<FieldType> {
+    let arg_types: Vec<TypeDescriptor> = args.iter().map(|a| self.call_arg_type(a)).collect();
+    let dynamic = self.dynamic_args(name, args, &arg_types);
+    if dynamic.iter().all(Option::is_none) {
+        return None;
+    }
+    Self::returns_differ(&self.dispatch_candidates(name, args, &arg_types, &dynamic)).then_some(FieldType::Any)
+}
+
+/// Call `name` through a stub that picks the overload when the call runs, because some
+/// arguments' types are only known then. The stub tries the overloads that could accept them
+/// from most to least specific; if none does, the call throws.
+fn compile_dispatch_call(
+    &mut self,
+    name: &str,
+    args: &[Expr],
+    arg_types: &[TypeDescriptor],
+    dynamic: &[Option<DynamicArg>],
+    span: Span,
+) -> CompileResult<BasicValueEnum<'ctx>> {
+    let candidates = self.dispatch_candidates(name, args, arg_types, dynamic);
+    if candidates.is_empty() {
+        return Err(CompileError::NoMatchingOverload {
+            name: name.to_string(),
+            arg_types: arg_types.iter().map(TypeDescriptor::to_mangle_string).collect(),
+            span,
+        });
+    }
+    let values: Vec<BasicValueEnum<'ctx>> = args.iter().map(|arg| self.compile_expr(arg)).collect::<CompileResult<_>>()?;
+    // Pointers of unknown type get their own stubs, which test what they point to
+    let stub_name = format!(
+        "{}__dispatch_{}",
+        name,
+        arg_types
+            .iter()
+            .zip(dynamic)
+            .map(|(ty, kind)| match kind {
+                Some(DynamicArg::Pointer) => "unknown".to_string(),
+                _ => ty.to_mangle_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("_")
+    );
+    let stub = match self.module.get_function(&stub_name) {
+        Some(stub) => stub,
+        None => self.build_dispatch_stub(&stub_name, name, &values, dynamic, &candidates)?,
+    };
+
+    let call_args: Vec<BasicMetadataValueEnum<'ctx>> = values.iter().map(|v| (*v).into()).collect();
+    let call_site = self.builder.build_call(stub, &call_args, &format!("call_{}", name)).unwrap();
+    self.check_exception()?;
+    Ok(call_site
+        .try_as_basic_value()
+        .left()
+        .unwrap_or_else(|| self.i32_type.const_int(0, false).into()))
+}
+
+/// Emit the body of a dispatcher stub; see `compile_dispatch_call`
+fn build_dispatch_stub(
+    &mut self,
+    stub_name: &str,
+    name: &str,
+    values: &[BasicValueEnum<'ctx>],
+    dynamic: &[Option<DynamicArg>],
+    candidates: &[(FunctionSignature, FunctionValue<'ctx>)],
+) -> CompileResult<FunctionValue<'ctx>> {
+    let boxed = Self::returns_differ(candidates);
+    let ret_ty = if boxed {
+        Some(self.context.i8_type().ptr_type(AddressSpace::default()).into())
+    } else {
+        candidates[0].1.get_type().get_return_type()
+    };
+    let param_types: Vec<BasicMetadataTypeEnum<'ctx>> = values.iter().map(|v| v.get_type().into()).collect();
+    let fn_type = match ret_ty {
+        Some(ty) => ty.fn_type(&param_types, false),
+        None => self.context.void_type().fn_type(&param_types, false),
+    };
+    let stub = self.module.add_function(stub_name, fn_type, None);
+    let caller_block = self.builder.get_insert_block();
+    self.builder.position_at_end(self.context.append_basic_block(stub, "entry"));
+
+    for (sig, func) in candidates {
+        let next = self.context.append_basic_block(stub, "try_next");
+        let target_params = func.get_type().get_param_types();
+        let mut call_args: Vec<BasicMetadataValueEnum<'ctx>> = Vec::with_capacity(values.len());
+        for (i, param) in sig.param_types.iter().enumerate() {
+            let value = stub.get_nth_param(i as u32).unwrap();
+            let target = target_params[i];
+            let value = match dynamic[i] {
+                Some(DynamicArg::Boxed) => {
+                    let expected = Self::boxed_param_type(param, target);
+                    self.unbox_if(value.into_pointer_value(), &expected, param, stub, next)
+                }
+                Some(DynamicArg::Instance) => {
+                    if let TypeDescriptor::Entity(entity) = param {
+                        let is_entity = self.instance_of(value.into_pointer_value(), &self.subtypes_of(entity));
+                        self.dispatch_guard(is_entity, stub, next);
+                    }
+                    value
+                }
+                Some(DynamicArg::Pointer) => {
+                    // An entity or interface parameter takes the instances of its entities, and
+                    // a string parameter whatever is not an instance
+                    let ptr = value.into_pointer_value();
+                    let cond = match param {
+                        TypeDescriptor::Entity(entity) => self.pointer_is_instance(ptr, &self.subtypes_of(entity), stub)?,
+                        TypeDescriptor::Interface(interface) => self.pointer_is_instance(ptr, &self.implementers(interface), stub)?,
+                        _ => {
+                            let mut entities: Vec<String> = self.entities.keys().cloned().collect();
+                            entities.sort();
+                            let is_instance = self.pointer_is_instance(ptr, &entities, stub)?;
+                            self.builder.build_not(is_instance, "not_instance").unwrap()
+                        }
+                    };
+                    self.dispatch_guard(cond, stub, next);
+                    value
+                }
+                Some(DynamicArg::Status) => {
+                    if let Some(in_range) = self.status_matches(value.into_int_value(), param) {
+                        self.dispatch_guard(in_range, stub, next);
+                    }
+                    value
+                }
+                None => value,
+            };
+            // Numbers convert to the parameter's type, pointers are passed as they are
+            let value = if value.get_type() != target && value.get_type().is_pointer_type() && target.is_pointer_type() {
+                self.builder.build_pointer_cast(value.into_pointer_value(), target.into_pointer_type(), "dispatch_arg").unwrap().into()
+            } else if value.get_type() != target && !value.get_type().is_pointer_type() && !target.is_pointer_type() {
+                let from = Self::field_type_of_llvm(value.get_type());
+                self.convert_numeric(value, &from, &Self::field_type_of_llvm(target))
+            } else if value.get_type() != target {
+                return Err(CompileError::Backend(format!(
+                    "`{}` takes {:?} as parameter {}, but its dispatcher has {:?}",
+                    func.get_name().to_string_lossy(),
+                    target,
+                    i + 1,
+                    value.get_type()
+                )));
+            } else {
+                value
+            };
+            call_args.push(value.into());
+        }
+        let result = self.builder.build_call(*func, &call_args, &format!("call_{}", name)).unwrap();
+        match result.try_as_basic_value().left() {
+            Some(value) if boxed => {
+                let boxed = self.box_any(value, &Self::field_type_of_llvm(value.get_type()))?;
+                self.builder.build_return(Some(&boxed)).unwrap()
+            }
+            Some(value) => self.builder.build_return(Some(&value)).unwrap(),
+            None if boxed => {
+                let boxed = self.box_any(self.i32_type.const_zero().into(), &FieldType::Int32)?;
+                self.builder.build_return(Some(&boxed)).unwrap()
+            }
+            None => self.builder.build_return(None).unwrap(),
+        };
+        self.builder.position_at_end(next);
+    }
+
+    // No overload accepts the arguments this call was given
+    let msg = self
+        .builder
+        .build_global_string_ptr(&format!("no overload of `{}` accepts the arguments given at runtime", name), "dispatch_err")
+        .unwrap()
+        .as_pointer_value();
+    self.builder.build_call(self.runtime_fn("wpp_throw_message")?, &[msg.into()], "").unwrap();
+    match ret_ty {
+        Some(ty) => self.builder.build_return(Some(&ty.const_zero())).unwrap(),
+        None => self.builder.build_return(None).unwrap(),
+    };
+
+    if let Some(block) = caller_block {
+        self.builder.position_at_end(block);
+    }
+    Ok(stub)
+}
+
+/// Continue in a new block when `cond` holds, else jump to `otherwise`
+fn dispatch_guard(&self, cond: IntValue<'ctx>, stub: FunctionValue<'ctx>, otherwise: inkwell::basic_block::BasicBlock<'ctx>) {
+    let matched = self.context.append_basic_block(stub, "arg_matches");
+    self.builder.build_conditional_branch(cond, matched, otherwise).unwrap();
+    self.builder.position_at_end(matched);
+}
+
+/// Take the payload out of an `any` box if its tag (and, for entities and status codes, the
+/// value itself) fits `param`; otherwise jump to `otherwise`
+fn unbox_if(
+    &mut self,
+    boxed: PointerValue<'ctx>,
+    expected: &FieldType,
+    param: &TypeDescriptor,
+    stub: FunctionValue<'ctx>,
+    otherwise: inkwell::basic_block::BasicBlock<'ctx>,
+) -> BasicValueEnum<'ctx> {
+    let tag_ptr = self
+        .builder
+        .build_pointer_cast(boxed, self.i32_type.ptr_type(AddressSpace::default()), "any_tag")
+        .unwrap();
+    let tag = self.builder.build_load(self.i32_type, tag_ptr, "tag").unwrap().into_int_value();
+    let wanted = self.i32_type.const_int(self.field_type_tag(expected), false);
+    let tag_ok = self.builder.build_int_compare(inkwell::IntPredicate::EQ, tag, wanted, "tag_matches").unwrap();
+    self.dispatch_guard(tag_ok, stub, otherwise);
+
+    let payload = unsafe {
+        self.builder
+            .build_gep(self.context.i8_type(), boxed, &[self.i32_type.const_int(8, false)], "any_payload")
+            .unwrap()
+    };
+    let payload = self
+        .builder
+        .build_pointer_cast(payload, self.slot_type(expected).ptr_type(AddressSpace::default()), "any_payload_cast")
+        .unwrap();
+    let value = self.load_slot(payload, expected);
+
+    let extra = match param {
+        TypeDescriptor::Entity(entity) => Some(self.instance_of(value.into_pointer_value(), &self.subtypes_of(entity))),
+        TypeDescriptor::Interface(interface) => Some(self.instance_of(value.into_pointer_value(), &self.implementers(interface))),
+        TypeDescriptor::HttpStatusLiteral(_) | TypeDescriptor::HttpStatusRange(..) => {
+            self.status_matches(value.into_int_value(), param)
+        }
+        _ => None,
+    };
+    if let Some(cond) = extra {
+        self.dispatch_guard(cond, stub, otherwise);
+    }
+    value
+}
+
+/// The entities that implement `interface`, sorted by name
+fn implementers(&self, interface: &str) -> Vec<String> {
+    let mut implementers: Vec<String> = self.entities.keys().filter(|e| self.implements(e, interface)).cloned().collect();
+    implementers.sort();
+    implementers
+}
+
+/// Whether a pointer of unknown W++ type is an instance of one of `entities`. Its first word is
+/// only read as a vtable when it starts a `wpp_gc_alloc` block, so a string never is one
+fn pointer_is_instance(&self, ptr: PointerValue<'ctx>, entities: &[String], stub: FunctionValue<'ctx>) -> CompileResult<IntValue<'ctx>> {
+    let is_data = self
+        .builder
+        .build_call(self.runtime_fn("wpp_gc_is_data")?, &[ptr.into()], "is_data")
+        .unwrap()
+        .try_as_basic_value()
+        .left()
+        .unwrap()
+        .into_int_value();
+    let is_data = self.builder.build_int_compare(inkwell::IntPredicate::NE, is_data, self.i32_type.const_zero(), "is_data").unwrap();
+    let from = self.builder.get_insert_block().unwrap();
+    let read_vtable = self.context.append_basic_block(stub, "read_vtable");
+    let done = self.context.append_basic_block(stub, "instance_checked");
+    self.builder.build_conditional_branch(is_data, read_vtable, done).unwrap();
+    self.builder.position_at_end(read_vtable);
+    let matches = self.instance_of(ptr, entities);
+    let read_end = self.builder.get_insert_block().unwrap();
+    self.builder.build_unconditional_branch(done).unwrap();
+    self.builder.position_at_end(done);
+    let phi = self.builder.build_phi(self.context.bool_type(), "is_instance").unwrap();
+    phi.add_incoming(&[(&self.context.bool_type().const_zero(), from), (&matches, read_end)]);
+    Ok(phi.as_basic_value().into_int_value())
+}
+
+/// Whether an instance's vtable is the vtable of one of `entities`
+fn instance_of(&self, instance: PointerValue<'ctx>, entities: &[String]) -> IntValue<'ctx> {
+    let i8_ptr = self.context.i8_type().ptr_type(AddressSpace::default());
+    let vtable_field = self.builder.build_pointer_cast(instance, i8_ptr.ptr_type(AddressSpace::default()), "vtable_field").unwrap();
+    let vtable = self.builder.build_load(i8_ptr, vtable_field, "vtable").unwrap().into_pointer_value();
+    entities.iter().fold(self.context.bool_type().const_zero(), |acc, entity| {
+        let entity_vtable = self.entities[entity].vtable.as_pointer_value().const_cast(i8_ptr);
+        let is_entity = self
+            .builder
+            .build_int_compare(inkwell::IntPredicate::EQ, vtable, entity_vtable, &format!("is_{}", entity))
+            .unwrap();
+        self.builder.build_or(acc, is_entity, "is_instance").unwrap()
+    })
+}
+
+/// Whether an integer fits an HTTP status pattern; `None` when `param` is not one
+fn status_matches(&self, value: IntValue<'ctx>, param: &TypeDescriptor) -> Option<IntValue<'ctx>> {
+    let code = |n: u16| self.i32_type.const_int(u64::from(n), false);
+    match param {
+        TypeDescriptor::HttpStatusLiteral(n) => {
+            Some(self.builder.build_int_compare(inkwell::IntPredicate::EQ, value, code(*n), "is_status").unwrap())
+        }
+        TypeDescriptor::HttpStatusRange(min, max) => {
+            let above = self.builder.build_int_compare(inkwell::IntPredicate::SGE, value, code(*min), "above_min").unwrap();
+            let below = self.builder.build_int_compare(inkwell::IntPredicate::SLE, value, code(*max), "below_max").unwrap();
+            Some(self.builder.build_and(above, below, "in_status_range").unwrap())
+        }
+        _ => None,
+    }
+}
+
+/// The implementation of `method` that instances of `entity` use: its own, or the nearest one
+/// up the `alters` chain, with the entity that declares it
+fn resolve_method(&self, entity: &str, method: &str) -> Option<(String, FunctionValue<'ctx>)> {
+    let mut current = Some(entity.to_string());
+    while let Some(name) = current {
+        let info = self.entities.get(&name)?;
+        if let Some(func) = info.methods.get(method) {
+            return Some((name, *func));
+        }
+        current = info.base.clone();
+    }
+    None
+}
+
+/// `d.age` where `d` holds an entity instance: the variable, the field's index and its W++ type
+fn entity_field(&self, name: &str) -> Option<(&VarInfo<'ctx>, usize, FieldType)> {
+    let (var_name, field) = name.split_once('.')?;
+    let var = self.vars.get(var_name).or_else(|| self.globals.get(var_name))?;
+    let entity = self.entities.get(var.entity_type.as_ref()?)?;
+    let index = entity.fields.iter().position(|(f, _)| f == field)?;
+    Some((var, index, entity.defaults[index].0.clone()))
+}
+
+/// Address of the field named by `entity_field`, loading the instance from its variable
+fn entity_field_ptr(&self, name: &str) -> Option<(PointerValue<'ctx>, FieldType)> {
+    let (var, index, ty) = self.entity_field(name)?;
+    let struct_ty = self.entities.get(var.entity_type.as_ref()?)?.struct_type;
+    let instance = self.builder.build_load(var.ty, var.ptr, "instance").unwrap().into_pointer_value();
+    let typed = self
+        .builder
+        .build_pointer_cast(instance, struct_ty.ptr_type(AddressSpace::default()), "as_struct")
+        .unwrap();
+    // Field 0 is the vtable pointer
+    let field_ptr = self.builder.build_struct_gep(struct_ty, typed, index as u32 + 1, name).unwrap();
+    Some((field_ptr, ty))
+}
+
+
+
+fn compile_switch(
+    &mut self,
+    discr_expr: &Expr,
+    cases: &Vec<(Expr, Vec<Node>)>,
+    default: &Option<Vec<Node>>,
+) -> CompileResult<BasicValueEnum<'ctx>> {
+    let discr_val = self.compile_expr(discr_expr)?.into_int_value();
+
+    let function = self.builder.get_insert_block().unwrap().get_parent().unwrap();
+    let end_bb = self.context.append_basic_block(function, "switch.end");
+    self.switch_stack.push((end_bb, self.defers.len()));
+    let default_bb = self.context.append_basic_block(function, "switch.default");
+
+    // Prepare case blocks
+    let mut case_blocks = Vec::new();
+    for (case_expr, body) in cases {
+        let bb = self.context.append_basic_block(function, "switch.case");
+        case_blocks.push((case_expr.clone(), bb, body));
+    }
+
+    // Compile expressions before switch instruction
+    let mut case_values = Vec::new();
+    for (case_expr, bb, _) in &case_blocks {
+        let val = self.compile_expr(case_expr)?;
+        case_values.push((val.into_int_value(), *bb));
+    }
+
+    // Build switch instruction
+    self.builder
+        .build_switch(discr_val, default_bb, &case_values)
+        .unwrap();
+
+    // Compile each case body
+    for (_, bb, body) in &case_blocks {
+        self.builder.position_at_end(*bb);
+        self.compile_block(body)?;
+        if self.builder.get_insert_block().unwrap().get_terminator().is_none() {
+            self.builder.build_unconditional_branch(end_bb).unwrap();
+        }
+    }
+
+    // Default body
+    self.builder.position_at_end(default_bb);
+    if let Some(body) = default {
+        self.compile_block(body)?;
+    }
+    if self.builder.get_insert_block().unwrap().get_terminator().is_none() {
+        self.builder.build_unconditional_branch(end_bb).unwrap();
+    }
+
+    // Return to end
+    self.builder.position_at_end(end_bb);
+    self.switch_stack.pop();
+    Ok(self.context.i32_type().const_int(0, false).into())
+}
+pub fn compile_funcy(
+    &mut self,
+    name: &str,
+    params: &[String],
+    body: &[Node],
+    param_override: Option<&[TypeDescriptor]>,
+    entity_name: Option<&str>, // 👈 added
+    return_type_override: Option<&TypeDescriptor>, // ✅ NEW: Explicit return type from annotation
+ // 👈 optional explicit type list
+) -> CompileResult<FunctionValue<'ctx>> {
+    // 🔒 Variables a lambda closes over (set by `compile_lambda`), passed in a trailing env pointer
+    let captures = std::mem::take(&mut self.pending_captures);
+    // === Step 1: Detect inferred types from body ===
+    let mut int_params = std::collections::HashSet::new();
+    let mut ptr_params = std::collections::HashSet::new();
+    let mut float_params = std::collections::HashSet::new();
+    let mut bool_params = std::collections::HashSet::new();
+    let mut contains_string_literal = false;
+
+    fn scan_for_types(
+    nodes: &[Node],
+    int_params: &mut std::collections::HashSet<String>,
+    ptr_params: &mut std::collections::HashSet<String>,
+    float_params: &mut std::collections::HashSet<String>,
+    bool_params: &mut std::collections::HashSet<String>,
+    contains_string_literal: &mut bool,
+) {
+    for node in nodes {
+        match &node.kind {
+            NodeKind::Expr(expr) => match &expr.kind {
+                ExprKind::BinaryOp { left, right, op } => {
+                    // 🧵 detect string concatenation
+                    if let (ExprKind::StringLiteral(_), _) | (_, ExprKind::StringLiteral(_)) =
+                        (&left.kind, &right.kind)
+                    {
+                        *contains_string_literal = true;
+                        if let ExprKind::Variable(name) = &left.kind {
+                            ptr_params.insert(name.clone());
+                        }
+                        if let ExprKind::Variable(name) = &right.kind {
+                            ptr_params.insert(name.clone());
+                        }
+                    }
+
+                    // 🧮 detect arithmetic or logical types
+                    else if ["+", "-", "*", "/", "%"].contains(&op.as_str()) {
+                        // detect if either side is float literal
+                        let mut is_float = false;
+                        if let ExprKind::TypedLiteral { ty, .. } = &left.kind {
+                            if ty == "f32" {
+                                is_float = true;
+                            }
+                        }
+                        if let ExprKind::TypedLiteral { ty, .. } = &right.kind {
+                            if ty == "f32" {
+                                is_float = true;
+                            }
+                        }
+
+                        if is_float {
+                            if let ExprKind::Variable(name) = &left.kind {
+                                float_params.insert(name.clone());
+                            }
+                            if let ExprKind::Variable(name) = &right.kind {
+                                float_params.insert(name.clone());
+                            }
+                        } else {
+                            if let ExprKind::Variable(name) = &left.kind {
+                                int_params.insert(name.clone());
+                            }
+                            if let ExprKind::Variable(name) = &right.kind {
+                                int_params.insert(name.clone());
+                            }
+                        }
+                    }
+
+                    // 🔀 detect boolean expressions (comparisons, logic ops)
+                    else if ["==", "!=", "<", ">", "<=", ">=", "and", "or"].contains(&op.as_str()) {
+                        if let ExprKind::Variable(name) = &left.kind {
+                            bool_params.insert(name.clone());
+                        }
+                        if let ExprKind::Variable(name) = &right.kind {
+                            bool_params.insert(name.clone());
+                        }
+                    }
+
+                    // recursive descent into both sides
+                    scan_for_types(
+                        &[Node::from(*left.clone())],
+                        int_params,
+                        ptr_params,
+                        float_params,
+                        bool_params,
+                        contains_string_literal,
+                    );
+                    scan_for_types(
+                        &[Node::from(*right.clone())],
+                        int_params,
                         ptr_params,
                         float_params,
                         bool_params,
@@ -5138,705 +6349,3678 @@ pub fn compile_funcy(
                     );
                 }
 
-                Expr::StringLiteral(_) => *contains_string_literal = true,
+                ExprKind::StringLiteral(_) => *contains_string_literal = true,
+
+                ExprKind::TypedLiteral { ty, .. } => {
+                    match ty.as_str() {
+                        "f32" => {
+                            float_params.insert("".to_string());
+                        }
+                        "bool" => {
+                            bool_params.insert("".to_string());
+                        }
+                        _ => {}
+                    }
+                }
+
+                ExprKind::Call { args, .. } => {
+                    for a in args {
+                        if let ExprKind::StringLiteral(_) = a.kind {
+                            *contains_string_literal = true;
+                        }
+                        scan_for_types(
+                            &[Node::from(a.clone())],
+                            int_params,
+                            ptr_params,
+                            float_params,
+                            bool_params,
+                            contains_string_literal,
+                        );
+                    }
+                }
+
+                ExprKind::Return(inner) => {
+                    if let Some(inner_expr) = inner {
+                        scan_for_types(
+                            &[Node::from(*inner_expr.clone())],
+                            int_params,
+                            ptr_params,
+                            float_params,
+                            bool_params,
+                            contains_string_literal,
+                        );
+                    }
+                }
+
+                ExprKind::If { cond, then_branch, else_branch } => {
+                    scan_for_types(
+                        &[Node::from(*cond.clone())],
+                        int_params,
+                        ptr_params,
+                        float_params,
+                        bool_params,
+                        contains_string_literal,
+                    );
+                    scan_for_types(
+                        then_branch,
+                        int_params,
+                        ptr_params,
+                        float_params,
+                        bool_params,
+                        contains_string_literal,
+                    );
+                    if let Some(e) = else_branch {
+                        scan_for_types(
+                            e,
+                            int_params,
+                            ptr_params,
+                            float_params,
+                            bool_params,
+                            contains_string_literal,
+                        );
+                    }
+                }
+
+                ExprKind::While { cond, body } => {
+                    scan_for_types(
+                        &[Node::from(*cond.clone())],
+                        int_params,
+                        ptr_params,
+                        float_params,
+                        bool_params,
+                        contains_string_literal,
+                    );
+                    scan_for_types(
+                        body,
+                        int_params,
+                        ptr_params,
+                        float_params,
+                        bool_params,
+                        contains_string_literal,
+                    );
+                }
+
+                ExprKind::ForIn { body, .. } => {
+                    scan_for_types(
+                        body,
+                        int_params,
+                        ptr_params,
+                        float_params,
+                        bool_params,
+                        contains_string_literal,
+                    );
+                }
+
+                ExprKind::Funcy { body, .. } => {
+                    scan_for_types(
+                        body,
+                        int_params,
+                        ptr_params,
+                        float_params,
+                        bool_params,
+                        contains_string_literal,
+                    );
+                }
+
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+
+    scan_for_types(
+        body,
+        &mut int_params,
+        &mut ptr_params,
+        &mut float_params,
+        &mut bool_params,
+        &mut contains_string_literal,
+    );
+
+    // === Step 2: Handle string fallback ===
+    if contains_string_literal && int_params.is_empty() && float_params.is_empty() {
+        for p in params {
+            ptr_params.insert(p.clone());
+        }
+    }
+    // === 🧩 OOPSIE: inject implicit `me` for entity methods ===
+let mut effective_params = params.to_vec();
+if let Some(ent_name) = entity_name {
+    // prepend "me:ptr" if it's not already there
+    if !effective_params.iter().any(|p| p == "me" || p.starts_with("me:")) {
+        effective_params.insert(0, "me:ptr".to_string());
+    }
+    println!("🧩 Added implicit 'me' to method of entity '{}'", ent_name);
+}
+    // === Step 3: Build LLVM parameter types (explicit-type aware) ===
+let mut param_type_names: Vec<String> = Vec::new();
+
+let param_types: Vec<BasicMetadataTypeEnum<'ctx>> = effective_params
+    .iter()
+    .map(|p| {
+        // Handle explicit annotations like "a:f32"
+        let (pname, pty) = if let Some((n, t)) = p.split_once(':') {
+            (n.to_string(), t.to_string())
+        } else {
+            (p.clone(), "i32".to_string())
+        };
+
+        param_type_names.push(pty.clone());
+
+        match self.primitive_type(&pty) {
+            Some(t) => t.into(),
+            None => {
+                // fallback based on inferred type sets; objects, entities and interfaces are pointers
+                if ptr_params.contains(&pname) || pty.starts_with("obj_") {
+                    self.context.i8_type().ptr_type(AddressSpace::default()).into()
+                } else if float_params.contains(&pname) {
+                    self.context.f32_type().into()
+                } else if bool_params.contains(&pname) {
+                    self.context.bool_type().into()
+                } else {
+                    self.i32_type.into()
+                }
+            }
+        }
+    })
+    .collect();
+    
+
+// === Step 4: Apply override if present ===
+let mut final_param_types: Vec<BasicMetadataTypeEnum<'ctx>> = if let Some(overrides) = param_override {
+    param_type_names = overrides.iter().map(|td| td.to_mangle_string()).collect();
+    // Rebuild LLVM param_types based on TypeDescriptors
+    overrides.iter().map(|td| {
+        match td {
+            TypeDescriptor::Function { .. } => {
+                // Function types are passed as function pointers (i8* in LLVM)
+                self.context.i8_type().ptr_type(AddressSpace::default()).into()
+            }
+            TypeDescriptor::Entity(_) | TypeDescriptor::Interface(_) | TypeDescriptor::ObjectType(_) => {
+                // Entities and objects are passed as pointers
+                self.context.i8_type().ptr_type(AddressSpace::default()).into()
+            }
+            TypeDescriptor::Primitive(name) => self.primitive_type(name).unwrap_or(self.i32_type.into()).into(),
+            _ => self.i32_type.into(), // Default for HttpStatus, Any, etc.
+        }
+    }).collect()
+} else {
+    param_types
+};
+// 🔒 Closures receive their captured environment as a trailing i8*
+if !captures.is_empty() {
+    final_param_types.push(self.context.i8_type().ptr_type(AddressSpace::default()).into());
+}
+
+    let llvm_name = if let Some(ent_name) = entity_name {
+    format!("{}.{}", ent_name, name)  // e.g. Dog.bark
+} else if param_type_names.is_empty() {
+    name.to_string()
+} else {
+    format!("{}__{}", name, param_type_names.join("_"))
+};
+
+    // === Step 4.5: Infer return type from function body ===
+    fn infer_return_type<'ctx>(
+        codegen: &Codegen<'ctx>,
+        expr: &Expr,
+        locals: &std::collections::HashSet<String>,
+    ) -> BasicTypeEnum<'ctx> {
+        match &expr.kind {
+            ExprKind::TypedLiteral { ty, .. } => codegen.primitive_type(ty).unwrap_or(codegen.i32_type.into()),
+            ExprKind::BinaryOp { left, right, op } => {
+                // Comparison and logical operators return bool
+                if ["==", "!=", "<", ">", "<=", ">=", "and", "or"].contains(&op.as_str()) {
+                    return codegen.context.bool_type().into();
+                }
+
+                let l = infer_return_type(codegen, left, locals);
+                let r = infer_return_type(codegen, right, locals);
+                if l.is_float_type() && r.is_float_type() && l != r {
+                    codegen.context.f64_type().into()
+                } else if l.is_float_type() || r.is_float_type() {
+                    if l.is_float_type() { l } else { r }
+                } else if l.is_int_type() && r.is_int_type() {
+                    codegen.i32_type.into()
+                } else {
+                    l
+                }
+            }
+            // 🔒 Returning a lambda returns a closure pointer
+            ExprKind::Funcy { .. } => codegen.context.i8_type().ptr_type(AddressSpace::default()).into(),
+            // Instances are heap pointers
+            ExprKind::NewInstance { .. } => codegen.context.i8_type().ptr_type(AddressSpace::default()).into(),
+            // 🏷️ Enum values are heap pointers
+            ExprKind::Call { name, .. } | ExprKind::Variable(name) if codegen.enum_constructor(name).is_some() => {
+                codegen.context.i8_type().ptr_type(AddressSpace::default()).into()
+            }
+            // A match returns what its first arm returns
+            ExprKind::Match { arms, .. } => match arms.first().and_then(|arm| arm.body.last()).map(|node| &node.kind) {
+                Some(NodeKind::Expr(value)) => infer_return_type(codegen, value, locals),
+                _ => codegen.i32_type.into(),
+            },
+            ExprKind::Variable(name) => {
+                if locals.contains(name) {
+                    codegen.i32_type.into() // assume int for local vars
+                } else {
+                    codegen.i32_type.into()
+                }
+            }
+            _ => codegen.i32_type.into(),
+        }
+    }
+
+    let mut inferred_ret_ty: Option<BasicTypeEnum<'ctx>> = None;
+    let local_params: std::collections::HashSet<String> = params.iter().cloned().collect();
+
+    for stmt in body {
+        if let NodeKind::Expr(Expr { kind: ExprKind::Return(Some(inner)), .. }) = &stmt.kind {
+            inferred_ret_ty = Some(infer_return_type(self, inner, &local_params));
+            println!("🔍 [type-infer] Function '{}' inferred return type: {:?}", name, inferred_ret_ty);
+            break;
+        }
+    }
+
+    // === Step 5: Create or fetch LLVM function ===
+    // === 🧮 Step 5: Create correct LLVM function type ===
+let fn_type = if let Some(return_type_desc) = return_type_override {
+    // ✅ NEW: Use explicit return type annotation if provided
+    println!("🎯 [return-type] Function '{}' has explicit return type annotation: {:?}", name, return_type_desc);
+    self.descriptor_value_type(return_type_desc).fn_type(&final_param_types, false)
+} else if let Some(ret_ty) = inferred_ret_ty {
+    // Use inferred return type
+    ret_ty.fn_type(&final_param_types, false)
+} else if name == "add" && param_type_names.iter().all(|t| t == "ptr") {
+    // 🧠 String overload returns a pointer
+    self.context
+        .i8_type()
+        .ptr_type(AddressSpace::default())
+        .fn_type(&final_param_types, false)
+} else if param_type_names.iter().any(|t| t == "f64" || t == "double") {
+    // 🧮 Float overload returns its widest float
+    self.context.f64_type().fn_type(&final_param_types, false)
+} else if param_type_names.iter().any(|t| t == "f32" || t == "float") {
+    self.context.f32_type().fn_type(&final_param_types, false)
+} else if param_type_names.iter().any(|t| t == "bool") {
+    // 🧩 Bool overload returns i1
+    self.context.bool_type().fn_type(&final_param_types, false)
+} else {
+    // 🧱 Default integer return
+    self.i32_type.fn_type(&final_param_types, false)
+};
+
+
+    let function = if let Some(existing) = self.module.get_function(&llvm_name) {
+        // `compile_main` compiles top-level functions before the top-level code reaches them
+        if existing.count_basic_blocks() > 0 {
+            return Ok(existing);
+        }
+        existing
+    } else {
+        self.module.add_function(&llvm_name, fn_type, None)
+    };
+
+    println!("🧱 Compiling funcy {} as {}", name, llvm_name);
+
+    // === Step 6: Create entry block ===
+    let entry = self.context.append_basic_block(function, "entry");
+    let saved_block = self.builder.get_insert_block();
+    self.builder.position_at_end(entry);
+
+    // === Step 7: Allocate locals (type-accurate) ===
+    let mut local_vars: HashMap<String, VarInfo<'ctx>> = HashMap::new();
+    for (i, param_name) in effective_params.iter().enumerate() {
+    let param = function.get_nth_param(i as u32).unwrap();
+    let param_ty = param.get_type();
+
+    // 🧠 Split "a:f32" -> ("a", "f32")
+    let pure_name = if let Some((n, _)) = param_name.split_once(':') {
+        n.to_string()
+    } else {
+        param_name.clone()
+    };
+
+    let alloca = self.builder.build_alloca(param_ty, &pure_name).unwrap();
+    self.builder.build_store(alloca, param).unwrap();
+
+    // 🎯 Check if this parameter has a function type
+    let function_signature = if let Some(overrides) = param_override {
+        if i < overrides.len() {
+            if let TypeDescriptor::Function { param_types, return_type } = &overrides[i] {
+                Some(FunctionTypeSignature {
+                    param_types: param_types.clone(),
+                    return_type: (**return_type).clone(),
+                })
+            } else {
+                None
+            }
+        } else {
+            None
+        }
+    } else {
+        None
+    };
+
+    // 🏷️ Object and enum parameters keep their type name for dispatch and printing
+    let object_type_name = match param_override.and_then(|overrides| overrides.get(i)) {
+        Some(TypeDescriptor::ObjectType(type_name)) => Some(type_name.clone()),
+        _ => None,
+    };
+    // 🧮 Numeric parameters keep their signedness (`n: u32`)
+    let numeric_type = match param_override.and_then(|overrides| overrides.get(i)) {
+        Some(TypeDescriptor::Primitive(ty)) => Some(FieldType::from_string(ty)),
+        _ => param_name.split_once(':').map(|(_, ty)| FieldType::from_string(ty)),
+    }
+    .filter(FieldType::is_numeric);
+    // `me` and entity-typed parameters reach the instance's fields and methods
+    let entity_type = match param_override.and_then(|overrides| overrides.get(i)) {
+        Some(TypeDescriptor::Entity(entity) | TypeDescriptor::Interface(entity)) => Some(entity.clone()),
+        // Without overrides the parser's "s:obj_Speaker" form still names the type
+        _ => match param_name.split_once(':').and_then(|(_, ty)| ty.strip_prefix("obj_")) {
+            Some(ty) if self.entities.contains_key(ty) || self.interfaces.contains_key(ty) => Some(ty.to_string()),
+            _ => entity_name.filter(|_| pure_name == "me").map(str::to_string),
+        },
+    };
+
+    local_vars.insert(
+        pure_name,
+        VarInfo {
+            ptr: alloca,
+            ty: param_ty,
+            is_const: false,
+            is_thread_state: false,
+            entity_type,
+            field_type: object_type_name.clone().map(FieldType::Object).or(numeric_type),
+            object_type_name,
+            function_signature,
+        },
+    );
+}
+    // 🔒 Captured variables are read and written in place inside the environment
+    if !captures.is_empty() {
+        let env_ty = self.closure_env_type(&captures);
+        let env = function.get_last_param().unwrap().into_pointer_value();
+        let env = self
+            .builder
+            .build_pointer_cast(env, env_ty.ptr_type(AddressSpace::default()), "env")
+            .unwrap();
+        for (i, (captured, info)) in captures.iter().enumerate() {
+            let slot = self.builder.build_struct_gep(env_ty, env, i as u32, captured).unwrap();
+            local_vars.insert(captured.clone(), VarInfo { ptr: slot, ..info.clone() });
+        }
+    }
+
+
+    // === Step 8: Replace current scope ===
+    let old_vars = std::mem::replace(&mut self.vars, local_vars);
+    // A function body is never lexically inside the caller's `try`
+    let old_exceptions = std::mem::replace(
+        &mut self.exceptions,
+        ExceptionScope { is_entry: name == "main" && entity_name.is_none(), ..Default::default() },
+    );
+    self.enter_frame(&entity_name.map_or_else(|| name.to_string(), |entity| format!("{}.{}", entity, name)))?;
+    let old_defers = std::mem::take(&mut self.defers);
+
+    // === Step 9: Compile body ===
+    let last_val = self.compile_block(body)?;
+
+   // === Step 10: Type-aware return handling with bool coercion ===
+if self.builder.get_insert_block().unwrap().get_terminator().is_none() {
+    let fn_ret_ty = function.get_type().get_return_type();
+
+    // Determine correct return value
+    let ret_val: BasicValueEnum<'ctx> = if let Some(val) = last_val {
+        if let Some(rt) = fn_ret_ty {
+            match rt {
+                // int → float
+                BasicTypeEnum::FloatType(f) if val.is_int_value() => {
+                    let casted = self
+                        .builder
+                        .build_signed_int_to_float(val.into_int_value(), f, "int_to_float_ret")
+                        .unwrap();
+                    casted.into()
+                }
+                // float → int
+                BasicTypeEnum::IntType(i) if val.is_float_value() => {
+                    let casted = self
+                        .builder
+                        .build_float_to_signed_int(val.into_float_value(), i, "float_to_int_ret")
+                        .unwrap();
+                    casted.into()
+                }
+                // i32 ↔ i1 (for bool returns or accidental widening)
+                BasicTypeEnum::IntType(i) if val.is_int_value() => {
+                    let src = val.into_int_value();
+                    let src_bits = src.get_type().get_bit_width();
+                    let dst_bits = i.get_bit_width();
+                    let adjusted = if src_bits > dst_bits {
+                        self.builder
+                            .build_int_truncate(src, i, "ret_trunc_i32_to_i1")
+                            .unwrap()
+                    } else if src_bits < dst_bits {
+                        self.builder
+                            .build_int_z_extend(src, i, "ret_zext_i1_to_i32")
+                            .unwrap()
+                    } else {
+                        src
+                    };
+                    adjusted.into()
+                }
+                // The unreachable block after a `return` yields a placeholder integer
+                BasicTypeEnum::PointerType(p) if !val.is_pointer_value() => p.const_null().into(),
+                // match already OK
+                _ => val,
+            }
+        } else {
+            val
+        }
+    } else {
+        // default 0/null
+        if let Some(rt) = fn_ret_ty {
+            match rt {
+                BasicTypeEnum::IntType(i) => i.const_int(0, false).into(),
+                BasicTypeEnum::FloatType(f) => f.const_float(0.0).into(),
+                BasicTypeEnum::PointerType(p) => p.const_null().into(),
+                _ => self.i32_type.const_int(0, false).into(),
+            }
+        } else {
+            self.i32_type.const_int(0, false).into()
+        }
+    };
+
+    self.leave_frame()?;
+    self.builder.build_return(Some(&ret_val)).unwrap();
+}
+
+
+
+    // === Step 11: Restore previous state ===
+    self.vars = old_vars;
+    self.exceptions = old_exceptions;
+    self.defers = old_defers;
+    if let Some(block) = saved_block {
+        self.builder.position_at_end(block);
+    }
+
+    // === Step 12: Register overload signature ===
+    // Convert param_type_names (strings) to TypeDescriptors for registration
+    let type_descriptors: Vec<TypeDescriptor> = if let Some(overrides) = param_override {
+        overrides.to_vec()
+    } else {
+        param_type_names.iter().map(|s| TypeDescriptor::Primitive(s.clone())).collect()
+    };
+
+    let sig = FunctionSignature {
+        name: name.to_string(),
+        param_types: type_descriptors,
+        return_type: self.result_type_descriptor(return_type_override, body),
+    };
+    // The compiled signature replaces a predeclared one, which could only guess the result
+    if let Some(sigs) = self.reverse_func_index.get_mut(name) {
+        sigs.retain(|s| s.param_types != sig.param_types);
+    }
+    self.functions.retain(|s, _| s.name != sig.name || s.param_types != sig.param_types);
+    self.functions.insert(sig.clone(), function);
+    self.reverse_func_index
+        .entry(name.to_string())
+        .or_default()
+        .push(sig.clone());
+
+    let type_display: Vec<String> = sig.param_types.iter()
+        .map(|td| td.to_mangle_string())
+        .collect();
+    println!(
+        "🪶 Registered funcy {}({}) as {}",
+        name,
+        type_display.join(", "),
+        llvm_name
+    );
+
+    Ok(function)
+}
+
+
+pub fn compile_async_funcy(
+    &mut self,
+    name: &str,
+    params: &[String],
+    body: &[Node],
+) -> CompileResult<FunctionValue<'ctx>> {
+    // === Remove stale definitions (for hot recompilation) ===
+    if let Some(existing) = self.module.get_function(name) {
+        unsafe { let _ = existing.delete(); }
+    }
+
+    println!("⚙️ compiling async funcy {}", name);
+
+    // === Define function signature (i32 return, i32 params) ===
+    let param_types: Vec<BasicMetadataTypeEnum<'ctx>> =
+        params.iter().map(|_| self.i32_type.into()).collect();
+    let fn_type = self.i32_type.fn_type(&param_types, false);
+
+    // === Create function + entry block ===
+    let function = self.module.add_function(name, fn_type, None);
+    let entry = self.context.append_basic_block(function, "entry");
+    self.builder.position_at_end(entry);
+
+    // === Allocate and store parameters ===
+let mut local_vars: HashMap<String, VarInfo<'ctx>> = HashMap::new();
+    for (i, pname) in params.iter().enumerate() {
+        let param = function.get_nth_param(i as u32).unwrap();
+        let alloca = self.builder.build_alloca(self.i32_type, pname).unwrap();
+        self.builder.build_store(alloca, param).unwrap();
+        local_vars.insert(
+    pname.clone(),
+    VarInfo {
+        ptr: alloca,
+        ty: self.i32_type.as_basic_type_enum(),
+        is_const: false,
+        is_thread_state: false,
+        entity_type: None,
+        object_type_name: None,
+        function_signature: None,
+        field_type: None,
+    },
+);
+
+    }
+
+    // === Scoped variable map ===
+    let old_vars: HashMap<String, VarInfo<'ctx>> = std::mem::replace(&mut self.vars, local_vars);
+    let old_exceptions = std::mem::replace(
+        &mut self.exceptions,
+        ExceptionScope { is_entry: name == "main", ..Default::default() },
+    );
+    self.enter_frame(name)?;
+    let old_defers = std::mem::take(&mut self.defers);
+
+    // === Compile body ===
+    let last_val = self.compile_block(body)?;
+
+    // === Determine return value (default to 0) ===
+    let ret_val = match last_val {
+        Some(BasicValueEnum::IntValue(iv)) => iv,
+        _ => self.i32_type.const_int(0, false),
+    };
+
+    // === Add async footer only if block isn’t already terminated ===
+    if self
+        .builder
+        .get_insert_block()
+        .and_then(|b| b.get_terminator())
+        .is_none()
+    {
+        let void_ty = self.context.void_type();
+
+        // wpp_return(i32)
+        let wpp_return = self.module.get_function("wpp_return").unwrap_or_else(|| {
+            let fn_ty = void_ty.fn_type(&[self.i32_type.into()], false);
+            self.module.add_function("wpp_return", fn_ty, None)
+        });
+
+        // wpp_yield()
+        let yield_fn = self.module.get_function("wpp_yield").unwrap_or_else(|| {
+            let fn_ty = void_ty.fn_type(&[], false);
+            self.module.add_function("wpp_yield", fn_ty, None)
+        });
+
+        // --- async return footer ---
+        // 1️⃣ Notify runtime of result (only once)
+        self.builder
+            .build_call(wpp_return, &[ret_val.into()], "final_async_return")
+            .unwrap();
+
+        // 2️⃣ Yield to scheduler to finalize
+        self.builder.build_call(yield_fn, &[], "yield_after_return").unwrap();
+
+        // 3️⃣ Actually return from LLVM func
+        self.leave_frame()?;
+        self.builder.build_return(Some(&ret_val)).unwrap();
+    } else {
+        println!("⚠️ [compile_async_funcy] Block already terminated, skipping footer");
+    }
+
+    // === Restore outer variable scope ===
+    self.vars = old_vars;
+    self.exceptions = old_exceptions;
+    self.defers = old_defers;
+    let sig = FunctionSignature {
+    name: name.to_string(),
+    param_types: params.iter().map(|_| TypeDescriptor::Primitive("i32".to_string())).collect(),
+    return_type: TypeDescriptor::Primitive("i32".to_string()), // Default return type
+};
+
+self.functions.insert(sig.clone(), function);
+self.reverse_func_index
+    .entry(name.to_string())
+    .or_default()
+    .push(sig);
+
+
+    println!("✅ async funcy {} compiled successfully", name);
+    Ok(function)
+}
+
+
+
+pub fn compile_entity(&mut self, entity: &EntityNode, span: Span) -> CompileResult<()> {
+        // The pre-pass of `compile_main` already compiled it. A second vtable would break the
+        // vtable address as the entity's identity (`instance_of`, interface dispatch)
+        if self.entities.contains_key(&entity.name) {
+            return Ok(());
+        }
+        println!("🏗️ Compiling entity: {}", entity.name);
+
+        // === 1️⃣ Inherit base fields and vtable slots (if any) ===
+        let mut all_fields: Vec<(String, BasicTypeEnum<'ctx>)> = Vec::new();
+        let mut defaults: Vec<(FieldType, Expr)> = Vec::new();
+        let mut slots: Vec<String> = Vec::new();
+
+        if let Some(base_name) = &entity.base {
+            if let Some(base_entity) = self.entities.get(base_name) {
+                println!("🔗 Inheriting fields from base entity '{}'", base_name);
+                all_fields.extend(base_entity.fields.clone());
+                defaults.extend(base_entity.defaults.clone());
+                slots.extend(base_entity.slots.clone());
+            } else {
+                eprintln!("⚠️ Base entity '{}' not found", base_name);
+            }
+        }
+
+        // === 2️⃣ Add this entity’s fields, typed by their defaults ===
+        for member in &entity.members {
+            if let EntityMember::Field { name, value, .. } = member {
+                let ty = self.static_field_type(value).unwrap_or(FieldType::Int32);
+                all_fields.push((name.clone(), self.slot_type(&ty)));
+                defaults.push((ty, value.clone()));
+            }
+        }
+
+        // Constructors run by name, so `init` takes no slot
+        for member in &entity.members {
+            if let EntityMember::Method { name, .. } = member
+                && name != "init"
+                && !slots.contains(name)
+            {
+                slots.push(name.clone());
+            }
+        }
+
+        // === 3️⃣ Define LLVM struct type for the entity; field 0 is the vtable pointer ===
+        let i8_ptr = self.context.i8_type().ptr_type(AddressSpace::default());
+        let struct_type = self.context.opaque_struct_type(&entity.name);
+        let field_types: Vec<_> = std::iter::once(i8_ptr.into()).chain(all_fields.iter().map(|(_, t)| *t)).collect();
+        struct_type.set_body(&field_types, false);
+        // A trailing null keeps the vtable from being empty: zero-sized globals may share an
+        // address, and the address is the entity's identity
+        let vtable = self
+            .module
+            .add_global(i8_ptr.array_type(slots.len() as u32 + 1), None, &format!("{}.vtable", entity.name));
+        vtable.set_constant(true);
+
+        // Registered before the methods so their bodies can reach `me.field`
+        self.entities.insert(
+            entity.name.clone(),
+            OopsieEntity {
+                name: entity.name.clone(),
+                base: entity.base.clone(),
+                struct_type,
+                fields: all_fields,
+                defaults,
+                methods: HashMap::new(),
+                slots: slots.clone(),
+                vtable,
+                interfaces: entity.interfaces.clone(),
+            },
+        );
+
+        // === 4️⃣ Register methods ===
+        for member in &entity.members {
+            if let EntityMember::Method { name, func, .. } = member {
+                if let ExprKind::Funcy {
+                    params,
+                    body,
+                    ..
+                } = &func.kind
+                {
+                    // 👇 compile_funcy with entity context ("Dog.bark")
+                    let full_name = format!("{}.{}", entity.name, name);
+
+                    // Compile with qualified name
+                    let func_val: FunctionValue<'_> = self.compile_funcy(
+                        &full_name,              // ✅ use "Dog.bark" instead of "bark"
+                        params,
+                        body,
+                        None,
+                        Some(&entity.name),
+                        None,
+                    )?;
+
+                    // Register under both function table and entity-local map
+                    self.functions.insert(
+                        FunctionSignature {
+                            name: full_name.clone(),
+                            param_types: vec![],
+                            return_type: TypeDescriptor::Primitive("i32".to_string()), // Default return type
+                        },
+                        func_val,
+                    );
+
+                    // An override is called through the base's slot, so it must have the same shape
+                    if name != "init"
+                        && let Some(base_name) = &entity.base
+                        && let Some((owner, overridden)) = self.resolve_method(base_name, name)
+                        && overridden.get_type() != func_val.get_type()
+                    {
+                        return Err(CompileError::type_mismatch(
+                            func.span,
+                            format!(
+                                "`{}.{}` must take and return the same types as `{}.{}`, which it overrides",
+                                entity.name, name, owner, name
+                            ),
+                        ));
+                    }
+
+                    if let Some(oopsie) = self.entities.get_mut(&entity.name) {
+                        oopsie.methods.insert(name.clone(), func_val);
+                    }
+                }
+            }
+        }
+
+        // === 5️⃣ Fill the vtable with the nearest implementation of each slot ===
+        let entries: Vec<PointerValue<'ctx>> = slots
+            .iter()
+            .filter_map(|slot| self.resolve_method(&entity.name, slot))
+            .map(|(_, func)| func.as_global_value().as_pointer_value().const_cast(i8_ptr))
+            .chain(std::iter::once(i8_ptr.const_null()))
+            .collect();
+        vtable.set_initializer(&i8_ptr.const_array(&entries));
+
+        // === 6️⃣ Check that the entity provides every method of the interfaces it implements ===
+        for interface in &entity.interfaces {
+            self.check_conformance(&entity.name, interface, span)?;
+        }
+
+        println!("✅ Entity '{}' compiled successfully", entity.name);
+        Ok(())
+    }
+
+
+    
+}
+impl<'ctx> Codegen<'ctx> {
+    fn ensure_builder_position(&self) {
+    if let Some(block) = self.builder.get_insert_block() {
+        if block.get_terminator().is_some() {
+            // Only reposition if the current block is part of an *active* function
+            if let Some(func) = block.get_parent() {
+                // Do NOT create multiple “fix_blocks” if already at an end block
+                if func.get_last_basic_block() != Some(block) {
+                    let fix_block = self.context.append_basic_block(func, "fix_block");
+                    self.builder.position_at_end(fix_block);
+                }
+            }
+        }
+    }
+}
+
+}
+impl<'ctx> Codegen<'ctx> {
+    /// Safely emit a branch only if the current block has no terminator.
+    fn safe_branch(&self, target: inkwell::basic_block::BasicBlock<'ctx>) {
+        if let Some(block) = self.builder.get_insert_block() {
+            if block.get_terminator().is_none() {
+                self.builder.build_unconditional_branch(target).unwrap();
+            }
+        }
+    }
+}
+impl<'ctx> Codegen<'ctx> {
+    // 📦 An array is a pointer to a runtime `WppVec` { i32 len, i32 elem_tag, i32 cap, pad,
+    // i64* data } (runtime/vector.rs); elements are 8-byte slots in `data`. Element types are
+    // tracked statically through `FieldType`; the tag only exists so `wpp_print_array` can
+    // walk nested and mixed arrays at runtime. Maps (runtime/map.rs) use the same slots for
+    // their values, keyed by strings.
+
+    /// Runtime tag for an element type (the ids match `wpp_print_value_basic`)
+    fn field_type_tag(&self, ty: &FieldType) -> u64 {
+        match ty {
+            FieldType::Any => 0,
+            FieldType::Int32 => 1,
+            FieldType::Int64 => 2,
+            FieldType::Float32 => 3,
+            FieldType::Float64 => 4,
+            FieldType::Int8 => 12,
+            FieldType::Int16 => 13,
+            FieldType::UInt8 => 14,
+            FieldType::UInt16 => 15,
+            FieldType::UInt32 => 16,
+            FieldType::UInt64 => 17,
+            FieldType::Bool => 5,
+            FieldType::String => 6,
+            FieldType::Array(_) => 7,
+            FieldType::Map(_) => 10,
+            FieldType::Object(name) if self.entities.contains_key(name) || self.interfaces.contains_key(name) => 9,
+            FieldType::Object(name) if self.enums.contains_key(name) => 11,
+            FieldType::Object(_) => 8,
+        }
+    }
+
+    /// Two object types hold the same kind of value: both object literals, both entity
+    /// instances, or the same enum
+    fn same_object_kind(&self, a: &FieldType, b: &FieldType) -> bool {
+        match (a, b) {
+            (FieldType::Object(x), FieldType::Object(y)) if self.enums.contains_key(x) || self.enums.contains_key(y) => x == y,
+            _ => self.field_type_tag(a) == self.field_type_tag(b),
+        }
+    }
+
+    /// How a value of this type is held in an 8-byte array slot
+    fn slot_type(&self, ty: &FieldType) -> BasicTypeEnum<'ctx> {
+        match ty {
+            FieldType::Int8 | FieldType::UInt8 => self.context.i8_type().into(),
+            FieldType::Int16 | FieldType::UInt16 => self.context.i16_type().into(),
+            FieldType::Int32 | FieldType::UInt32 | FieldType::Bool => self.i32_type.into(),
+            FieldType::Int64 | FieldType::UInt64 => self.context.i64_type().into(),
+            FieldType::Float32 => self.context.f32_type().into(),
+            FieldType::Float64 => self.context.f64_type().into(),
+            _ => self.context.i8_type().ptr_type(AddressSpace::default()).into(),
+        }
+    }
+
+    /// Compile both operands of a binary operator. Numbers are converted to their common type
+    /// (`FieldType::common_numeric`), with an untyped literal taking the other side's type;
+    /// the flag says whether that type is unsigned. Mixed int/float comparisons are left
+    /// unconverted and rejected by the caller.
+    fn compile_operands(
+        &mut self,
+        left: &Expr,
+        op: &str,
+        right: &Expr,
+        span: Span,
+    ) -> CompileResult<(BasicValueEnum<'ctx>, BasicValueEnum<'ctx>, bool)> {
+        // An untyped literal is compiled after the other side so it is range-checked against
+        // the type it adopts (`g * 1000000000000` with `g: i64`); a shift amount never leads
+        let shift = op == "<<" || op == ">>";
+        let (mut l, lt, mut r, rt) = if left.untyped_number().is_some() && !shift {
+            let (r, rt) = self.compile_typed(right)?;
+            let (l, lt) = self.compile_adopting(left, &rt)?;
+            (l, lt, r, rt)
+        } else {
+            let (l, lt) = self.compile_typed(left)?;
+            let (r, rt) = self.compile_adopting(right, &lt)?;
+            (l, lt, r, rt)
+        };
+        // A string joined with an `any` (such as an untyped `catch` variable) joins its text
+        if op == "+" {
+            match (&lt, &rt) {
+                (FieldType::String, FieldType::Any) => r = self.any_to_string(r.into_pointer_value())?.into(),
+                (FieldType::Any, FieldType::String) => l = self.any_to_string(l.into_pointer_value())?.into(),
+                _ => {}
+            }
+        }
+        if op == "and" || op == "or" || !lt.is_numeric() || !rt.is_numeric() {
+            return Ok((l, r, false));
+        }
+        // A shift keeps the value's type; the amount is converted to match it
+        if shift {
+            if lt.is_integer() && rt.is_integer() {
+                r = self.convert_numeric(r, &rt, &lt);
+            }
+            return Ok((l, r, lt.is_unsigned()));
+        }
+        if lt.is_float() != rt.is_float() && Self::is_comparison(op) {
+            return Ok((l, r, false));
+        }
+        let Some(common) = FieldType::common_numeric(&lt, &rt) else {
+            return Err(CompileError::type_mismatch(
+                span,
+                format!("`{}` and `{}` have no common type; convert one with `as`", lt, rt),
+            ));
+        };
+        let l = self.convert_numeric(l, &lt, &common);
+        let r = self.convert_numeric(r, &rt, &common);
+        Ok((l, r, common.is_unsigned()))
+    }
+
+    /// The text `print` shows for an `any` box
+    fn any_to_string(&mut self, boxed: PointerValue<'ctx>) -> CompileResult<PointerValue<'ctx>> {
+        // A box has the layout of one `wpp_format` argument
+        let fmt = self.builder.build_global_string_ptr("{}", "any_fmt").unwrap().as_pointer_value();
+        Ok(self.builder
+            .build_call(
+                self.runtime_fn("wpp_format")?,
+                &[fmt.into(), self.i32_type.const_int(1, false).into(), boxed.into()],
+                "any_text",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value())
+    }
+
+    /// `value as ty`: numbers and bools convert to any numeric type or to `bool`
+    fn compile_cast(&mut self, inner: &Expr, ty: &str, span: Span) -> CompileResult<BasicValueEnum<'ctx>> {
+        let to = FieldType::from_string(ty);
+        let castable = |t: &FieldType| t.is_numeric() || *t == FieldType::Bool;
+        if !castable(&to) {
+            return Err(CompileError::type_mismatch(span, format!("cannot cast to `{}`: `as` converts between numbers and bools", ty)));
+        }
+        if let Some(n) = Self::adopted_literal(inner, &to) {
+            return self.number_const(n, &to, inner.span);
+        }
+        let (val, from) = self.compile_typed(inner)?;
+        if !castable(&from) {
+            return Err(CompileError::type_mismatch(inner.span, format!("cannot cast `{}` to `{}`", from, to)));
+        }
+        Ok(self.convert_numeric(val, &from, &to))
+    }
+
+    /// Compile one operand of a binary operator, letting an untyped literal take the type
+    /// `other` of the operand beside it when it fits
+    fn compile_adopting(&mut self, expr: &Expr, other: &FieldType) -> CompileResult<(BasicValueEnum<'ctx>, FieldType)> {
+        match Self::adopted_literal(expr, other) {
+            Some(n) => Ok((self.number_const(n, other, expr.span)?, other.clone())),
+            None => self.compile_typed(expr),
+        }
+    }
+
+    /// The value of an untyped number literal that can take the numeric type `other`
+    /// (so `x + 1` with `x: u8` stays `u8`)
+    fn adopted_literal(expr: &Expr, other: &FieldType) -> Option<Number> {
+        let n = expr.untyped_number()?;
+        (other.is_numeric() && other.fits(&n)).then_some(n)
+    }
+
+    fn is_comparison(op: &str) -> bool {
+        matches!(op, "==" | "!=" | "<" | "<=" | ">" | ">=")
+    }
+
+    /// A numeric constant of type `ty`, or an error when `n` does not fit in it
+    fn number_const(&self, n: Number, ty: &FieldType, span: Span) -> CompileResult<BasicValueEnum<'ctx>> {
+        if !ty.fits(&n) {
+            let message = match n {
+                Number::Int(v) => format!("integer literal `{}` does not fit in `{}`", v, ty),
+                Number::Float(f) => format!("float literal `{}` does not fit in `{}`", f, ty),
+            };
+            return Err(CompileError::type_mismatch(span, message));
+        }
+        Ok(match (self.slot_type(ty), n) {
+            (BasicTypeEnum::FloatType(ft), Number::Int(v)) => ft.const_float(v as f64).into(),
+            (BasicTypeEnum::FloatType(ft), Number::Float(f)) => ft.const_float(f).into(),
+            // Two's complement bits of the value, truncated to the type's width by LLVM
+            (BasicTypeEnum::IntType(it), Number::Int(v)) => it.const_int(v as u64, false).into(),
+            (other, _) => unreachable!("numeric types have int or float slots, not {:?}", other),
+        })
+    }
+
+    /// LLVM type of a primitive type annotation (`u8`, `f64`, `string`, ...)
+    fn primitive_type(&self, name: &str) -> Option<BasicTypeEnum<'ctx>> {
+        match name {
+            "bool" | "boolean" => Some(self.context.bool_type().into()),
+            "ptr" | "string" | "str" => Some(self.context.i8_type().ptr_type(AddressSpace::default()).into()),
+            _ => match FieldType::from_string(name) {
+                ty if ty.is_numeric() => Some(self.slot_type(&ty)),
+                _ => None,
+            },
+        }
+    }
+
+    /// Convert a number (or bool) of type `from` to `to`: integers are sign- or zero-extended
+    /// by `from`'s signedness or truncated, and int/float conversions follow the signedness of
+    /// the integer side. Widths come from the value itself, so a stale `from` only decides the sign.
+    fn convert_numeric(&mut self, val: BasicValueEnum<'ctx>, from: &FieldType, to: &FieldType) -> BasicValueEnum<'ctx> {
+        if *to == FieldType::Bool {
+            return match val {
+                BasicValueEnum::IntValue(iv) if iv.get_type().get_bit_width() == 1 => iv.into(),
+                BasicValueEnum::IntValue(iv) => self
+                    .builder
+                    .build_int_compare(inkwell::IntPredicate::NE, iv, iv.get_type().const_zero(), "to_bool")
+                    .unwrap()
+                    .into(),
+                BasicValueEnum::FloatValue(fv) => self
+                    .builder
+                    .build_float_compare(inkwell::FloatPredicate::ONE, fv, fv.get_type().const_zero(), "to_bool")
+                    .unwrap()
+                    .into(),
+                other => other,
+            };
+        }
+        // Bools are 0 or 1, so they convert like unsigned integers
+        let unsigned = from.is_unsigned() || *from == FieldType::Bool;
+        match (val, self.slot_type(to)) {
+            (BasicValueEnum::IntValue(iv), BasicTypeEnum::IntType(it)) => {
+                let (from_bits, to_bits) = (iv.get_type().get_bit_width(), it.get_bit_width());
+                if from_bits == to_bits {
+                    iv.into()
+                } else if from_bits > to_bits {
+                    self.builder.build_int_truncate(iv, it, "num_trunc").unwrap().into()
+                } else if unsigned || from_bits == 1 {
+                    self.builder.build_int_z_extend(iv, it, "num_zext").unwrap().into()
+                } else {
+                    self.builder.build_int_s_extend(iv, it, "num_sext").unwrap().into()
+                }
+            }
+            (BasicValueEnum::IntValue(iv), BasicTypeEnum::FloatType(ft)) if unsigned || iv.get_type().get_bit_width() == 1 => {
+                self.builder.build_unsigned_int_to_float(iv, ft, "num_uitofp").unwrap().into()
+            }
+            (BasicValueEnum::IntValue(iv), BasicTypeEnum::FloatType(ft)) => {
+                self.builder.build_signed_int_to_float(iv, ft, "num_sitofp").unwrap().into()
+            }
+            (BasicValueEnum::FloatValue(fv), BasicTypeEnum::FloatType(ft)) => {
+                if fv.get_type() == ft {
+                    fv.into()
+                } else {
+                    self.builder.build_float_cast(fv, ft, "num_fcast").unwrap().into()
+                }
+            }
+            (BasicValueEnum::FloatValue(fv), BasicTypeEnum::IntType(it)) if to.is_unsigned() => {
+                self.builder.build_float_to_unsigned_int(fv, it, "num_fptoui").unwrap().into()
+            }
+            (BasicValueEnum::FloatValue(fv), BasicTypeEnum::IntType(it)) => {
+                self.builder.build_float_to_signed_int(fv, it, "num_fptosi").unwrap().into()
+            }
+            (other, _) => other,
+        }
+    }
+
+    /// LLVM type of a loaded value (bools are widened to i32 only while stored)
+    fn field_value_type(&self, ty: &FieldType) -> BasicTypeEnum<'ctx> {
+        match ty {
+            FieldType::Bool => self.context.bool_type().into(),
+            _ => self.slot_type(ty),
+        }
+    }
+
+    /// The W++ type a variable of type `ty` records, for the types its LLVM type cannot
+    /// tell apart: containers, objects, and numbers (`u8` and `i8` are both `i8`)
+    fn tracked_field_type(ty: FieldType) -> Option<FieldType> {
+        (matches!(ty, FieldType::Array(_) | FieldType::Map(_) | FieldType::Object(_) | FieldType::Any) || ty.is_numeric()).then_some(ty)
+    }
+
+    /// W++ type of an expression when it can be known without compiling it
+    fn static_field_type(&self, expr: &Expr) -> Option<FieldType> {
+        match &expr.kind {
+            ExprKind::Literal(_) => Some(FieldType::Int32),
+            ExprKind::TypedLiteral { ty, .. } => Some(match FieldType::from_string(ty) {
+                ft if ft.is_numeric() => ft,
+                _ if ty == "i1" || ty == "bool" => FieldType::Bool,
+                _ => FieldType::Int32,
+            }),
+            ExprKind::BinaryOp { left, op, right } => match op.as_str() {
+                "=" => self.static_field_type(left),
+                "!" | "and" | "or" => Some(FieldType::Bool),
+                "~" => self.static_field_type(right),
+                "<<" | ">>" => self.static_field_type(left),
+                op if Self::is_comparison(op) => Some(FieldType::Bool),
+                _ => {
+                    let (l, r) = (self.static_field_type(left)?, self.static_field_type(right)?);
+                    if matches!((&l, &r), (FieldType::String, FieldType::String | FieldType::Any) | (FieldType::Any, FieldType::String)) {
+                        return Some(FieldType::String);
+                    }
+                    if Self::adopted_literal(left, &r).is_some() {
+                        Some(r)
+                    } else if Self::adopted_literal(right, &l).is_some() {
+                        Some(l)
+                    } else {
+                        FieldType::common_numeric(&l, &r)
+                    }
+                }
+            },
+            ExprKind::Cast { ty, .. } => Some(FieldType::from_string(ty)),
+            ExprKind::BoolLiteral(_) => Some(FieldType::Bool),
+            ExprKind::StringLiteral(_) => Some(FieldType::String),
+            ExprKind::ArrayLiteral(elements) => {
+                let tys = elements
+                    .iter()
+                    .map(|el| self.static_field_type(el))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(FieldType::Array(Box::new(Self::unify_elem_types(&tys))))
+            }
+            ExprKind::ObjectLiteral { type_name, .. } => {
+                Some(FieldType::Object(type_name.clone().unwrap_or_else(|| "object".to_string())))
+            }
+            ExprKind::NewInstance { entity, .. } => Some(FieldType::Object(entity.clone())),
+            ExprKind::Variable(name) | ExprKind::Call { name, .. } if self.enum_constructor(name).is_some() => {
+                self.enum_constructor(name).map(|(enum_name, _)| FieldType::Object(enum_name))
+            }
+            ExprKind::Variable(name) if self.entity_field(name).is_some() => self.entity_field(name).map(|(_, _, ty)| ty),
+            ExprKind::Variable(name) => {
+                let var = self.vars.get(name).or_else(|| self.globals.get(name))?;
+                if let Some(ft) = &var.field_type {
+                    return Some(ft.clone());
+                }
+                if let Some(entity) = &var.entity_type {
+                    return Some(FieldType::Object(entity.clone()));
+                }
+                Some(Self::field_type_of_llvm(var.ty))
+            }
+            ExprKind::Index { target, .. } => match self.static_field_type(target)? {
+                FieldType::Array(inner) => Some(*inner),
+                _ => None,
+            },
+            ExprKind::Call { name, args } if Self::is_array_builtin(name) && !self.reverse_func_index.contains_key(name) => {
+                match (name.as_str(), self.static_field_type(args.first()?)?) {
+                    ("push" | "insert", FieldType::Array(_)) => Some(FieldType::Int32),
+                    ("pop" | "remove", FieldType::Array(inner)) => Some(*inner),
+                    ("slice" | "concat", arr @ FieldType::Array(_)) => Some(arr),
+                    _ => None,
+                }
+            }
+            ExprKind::Call { name, args } if Self::is_map_builtin(name) && !self.reverse_func_index.contains_key(name) => {
+                if name == "Map" {
+                    let val = match args.first().map(|a| &a.kind) {
+                        Some(ExprKind::ObjectLiteral { fields, .. }) => {
+                            let tys = fields
+                                .iter()
+                                .map(|(_, v)| self.static_field_type(v))
+                                .collect::<Option<Vec<_>>>()?;
+                            Self::unify_elem_types(&tys)
+                        }
+                        _ => Self::unify_elem_types(&[]),
+                    };
+                    return Some(FieldType::Map(Box::new(val)));
+                }
+                let FieldType::Map(val) = self.static_field_type(args.first()?)? else {
+                    return None;
+                };
+                Some(match name.as_str() {
+                    "get" => *val,
+                    "set" => FieldType::Int32,
+                    "keys" => FieldType::Array(Box::new(FieldType::String)),
+                    "values" => FieldType::Array(val),
+                    _ => FieldType::Bool,
+                })
+            }
+            ExprKind::Call { name, .. } if name == "len" => Some(FieldType::Int32),
+            ExprKind::Call { name, args } if self.dispatched_call_type(name, args).is_some() => self.dispatched_call_type(name, args),
+            // A user function whose overloads are all declared to return the same string, enum,
+            // entity, interface or object (`-> Shape`)
+            ExprKind::Call { name, .. } => {
+                let mut declared = self.reverse_func_index.get(name)?.iter().map(|sig| match &sig.return_type {
+                    TypeDescriptor::ObjectType(n) | TypeDescriptor::Entity(n) | TypeDescriptor::Interface(n) => {
+                        Some(FieldType::Object(n.clone()))
+                    }
+                    TypeDescriptor::Primitive(p) if FieldType::from_string(p) == FieldType::String => Some(FieldType::String),
+                    _ => None,
+                });
+                let first = declared.next()??;
+                declared.all(|ty| ty.as_ref() == Some(&first)).then_some(first)
+            }
+            _ => None,
+        }
+    }
+
+    /// Fallback when only the LLVM type is known (pointers are treated as strings, like `print`)
+    fn field_type_of_llvm(ty: BasicTypeEnum<'ctx>) -> FieldType {
+        match ty {
+            BasicTypeEnum::IntType(it) if it.get_bit_width() == 1 => FieldType::Bool,
+            BasicTypeEnum::IntType(it) => FieldType::int_of(it.get_bit_width(), false).unwrap_or(FieldType::Int32),
+            BasicTypeEnum::FloatType(ft) if ft == ft.get_context().f32_type() => FieldType::Float32,
+            BasicTypeEnum::FloatType(_) => FieldType::Float64,
+            _ => FieldType::String,
+        }
+    }
+
+    /// One element type for a whole literal: numbers promote, anything else mixed becomes `any`
+    fn unify_elem_types(tys: &[FieldType]) -> FieldType {
+        let Some(first) = tys.first() else {
+            return FieldType::Int32;
+        };
+        if tys.iter().all(|t| t == first) {
+            first.clone()
+        } else if tys.iter().all(FieldType::is_numeric) {
+            if tys.iter().any(FieldType::is_float) {
+                FieldType::Float64
+            } else {
+                // Integers widen to one type that holds them all
+                tys[1..]
+                    .iter()
+                    .try_fold(first.clone(), |acc, t| FieldType::common_numeric(&acc, t))
+                    .unwrap_or(FieldType::Any)
+            }
+        } else {
+            FieldType::Any
+        }
+    }
+
+    /// Compile an expression together with its W++ type
+    fn compile_typed(&mut self, expr: &Expr) -> CompileResult<(BasicValueEnum<'ctx>, FieldType)> {
+        match &expr.kind {
+            ExprKind::ArrayLiteral(elements) => {
+                let (arr, elem) = self.compile_array_literal(elements, None)?;
+                Ok((arr.into(), FieldType::Array(Box::new(elem))))
+            }
+            ExprKind::Index { target, index } => self.compile_index_load(target, index),
+            ExprKind::Match { subject, arms } => self.compile_match(subject, arms),
+            ExprKind::Call { name, args } if Self::is_array_builtin(name) && !self.reverse_func_index.contains_key(name) => {
+                self.compile_array_builtin(name, args, expr.span)
+            }
+            ExprKind::Call { name, args } if Self::is_map_builtin(name) && !self.reverse_func_index.contains_key(name) => {
+                self.compile_map_builtin(name, args, None, expr.span)
+            }
+            _ => {
+                let val = self.compile_expr(expr)?;
+                let ty = self
+                    .static_field_type(expr)
+                    .unwrap_or_else(|| Self::field_type_of_llvm(val.get_type()));
+                Ok((val, ty))
+            }
+        }
+    }
+
+    /// Like `compile_typed`, but lets a known type flow into literals that cannot infer
+    /// it themselves (`let xs: str[] = []`, `let h: map<str> = Map()`, `let b: u8 = 200`)
+    fn compile_expected(&mut self, expr: &Expr, expected: &FieldType) -> CompileResult<(BasicValueEnum<'ctx>, FieldType)> {
+        // An integer literal that does not fit its declared type is an error, not a truncation
+        if let Some(n) = expr.untyped_number()
+            && (expected.is_numeric() && expected.fits(&n) || expected.is_integer() && matches!(n, Number::Int(_)))
+        {
+            return Ok((self.number_const(n, expected, expr.span)?, expected.clone()));
+        }
+        match (&expr.kind, expected) {
+            (_, FieldType::Any) => {
+                let (val, ty) = self.compile_typed(expr)?;
+                Ok((self.coerce_to_slot(val, &ty, &FieldType::Any, expr.span)?, FieldType::Any))
+            }
+            (ExprKind::ArrayLiteral(elements), FieldType::Array(elem)) => {
+                let (arr, elem) = self.compile_array_literal(elements, Some(elem))?;
+                Ok((arr.into(), FieldType::Array(Box::new(elem))))
+            }
+            (ExprKind::Call { name, args }, FieldType::Map(val)) if name == "Map" && !self.reverse_func_index.contains_key(name) => {
+                self.compile_map_builtin(name, args, Some(val), expr.span)
+            }
+            _ => self.compile_typed(expr),
+        }
+    }
+
+    /// Allocate `bytes` zeroed bytes on the W++ heap (runtime/gc.rs)
+    fn heap_alloc(&mut self, bytes: IntValue<'ctx>, name: &str) -> CompileResult<PointerValue<'ctx>> {
+        let alloc_fn = self.runtime_fn("wpp_gc_alloc")?;
+        Ok(self.builder
+            .build_call(alloc_fn, &[bytes.into()], name)
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .expect("wpp_gc_alloc must return pointer")
+            .into_pointer_value())
+    }
+
+    /// Allocate an array of `len` zeroed elements
+    fn alloc_array(&mut self, len: IntValue<'ctx>, elem: &FieldType) -> CompileResult<PointerValue<'ctx>> {
+        let new_fn = self.runtime_fn("wpp_vec_new")?;
+        let tag = self.i32_type.const_int(self.field_type_tag(elem), false);
+        Ok(self.builder
+            .build_call(new_fn, &[tag.into(), len.into()], "arr_new")
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .expect("wpp_vec_new must return pointer")
+            .into_pointer_value())
+    }
+
+    /// Pointer to slot `idx` of an array, typed for `elem`
+    fn array_slot_ptr(&self, arr: PointerValue<'ctx>, idx: IntValue<'ctx>, elem: &FieldType) -> PointerValue<'ctx> {
+        let i64_type = self.context.i64_type();
+        let i64_ptr = i64_type.ptr_type(AddressSpace::default());
+        // `data` is the third 8-byte word of the header, after len/tag and cap/pad
+        let words = self
+            .builder
+            .build_bitcast(arr, i64_ptr.ptr_type(AddressSpace::default()), "arr_words")
+            .unwrap()
+            .into_pointer_value();
+        let data_field = unsafe {
+            self.builder
+                .build_gep(i64_ptr, words, &[self.i32_type.const_int(2, false)], "arr_data_field")
+                .unwrap()
+        };
+        let data = self.builder.build_load(i64_ptr, data_field, "arr_data").unwrap().into_pointer_value();
+        let slot = unsafe { self.builder.build_gep(i64_type, data, &[idx], "slot_ptr").unwrap() };
+        self.builder
+            .build_bitcast(slot, self.slot_type(elem).ptr_type(AddressSpace::default()), "elem_ptr")
+            .unwrap()
+            .into_pointer_value()
+    }
+
+    /// Raw 8-byte slot bits for the runtime vector functions
+    fn slot_to_bits(&self, val: BasicValueEnum<'ctx>, span: Span) -> CompileResult<IntValue<'ctx>> {
+        let i64_type = self.context.i64_type();
+        Ok(match val {
+            BasicValueEnum::IntValue(iv) if iv.get_type().get_bit_width() == 64 => iv,
+            BasicValueEnum::IntValue(iv) => self.builder.build_int_z_extend(iv, i64_type, "slot_bits").unwrap(),
+            BasicValueEnum::FloatValue(fv) => {
+                self.builder.build_bitcast(fv, i64_type, "slot_bits").unwrap().into_int_value()
+            }
+            BasicValueEnum::PointerValue(pv) => self.builder.build_ptr_to_int(pv, i64_type, "slot_bits").unwrap(),
+            other => {
+                return Err(CompileError::type_mismatch(
+                    span,
+                    format!("a value of LLVM type {} does not fit an 8-byte slot", other.get_type().print_to_string()),
+                ))
+            }
+        })
+    }
+
+    /// Inverse of `slot_to_bits`, producing a value of type `elem`
+    fn bits_to_value(&self, bits: IntValue<'ctx>, elem: &FieldType) -> BasicValueEnum<'ctx> {
+        match self.slot_type(elem) {
+            BasicTypeEnum::IntType(it) if it.get_bit_width() == 64 => bits.into(),
+            BasicTypeEnum::IntType(it) => {
+                let narrow = self.builder.build_int_truncate(bits, it, "slot_val").unwrap();
+                if *elem == FieldType::Bool {
+                    self.builder
+                        .build_int_compare(inkwell::IntPredicate::NE, narrow, it.const_zero(), "slot_bool")
+                        .unwrap()
+                        .into()
+                } else {
+                    narrow.into()
+                }
+            }
+            BasicTypeEnum::FloatType(ft) => self.builder.build_bitcast(bits, ft, "slot_val").unwrap(),
+            // Every other slot holds an `i8*` (see `slot_type`)
+            _ => {
+                let i8_ptr = self.context.i8_type().ptr_type(AddressSpace::default());
+                self.builder.build_int_to_ptr(bits, i8_ptr, "slot_val").unwrap().into()
+            }
+        }
+    }
+
+    /// Convert a value of W++ type `from` into the slot representation of `to`
+    fn coerce_to_slot(
+        &mut self,
+        val: BasicValueEnum<'ctx>,
+        from: &FieldType,
+        to: &FieldType,
+        span: Span,
+    ) -> CompileResult<BasicValueEnum<'ctx>> {
+        if *to == FieldType::Any {
+            // An `any` value is already a box
+            return Ok(if *from == FieldType::Any { val } else { self.box_any(val, from)?.into() });
+        }
+
+        let compatible = from == to
+            || (from.is_numeric() && to.is_numeric())
+            || matches!((from, to), (FieldType::Object(_), FieldType::Object(_)) if self.same_object_kind(from, to));
+        if !compatible {
+            return Err(CompileError::type_mismatch(
+                span,
+                format!("expected an element of type `{}`, found `{}`", to, from),
+            ));
+        }
+
+        if from.is_numeric() && to.is_numeric() {
+            return Ok(self.convert_numeric(val, from, to));
+        }
+        let slot_ty = self.slot_type(to);
+        Ok(match (val, slot_ty) {
+            (BasicValueEnum::IntValue(iv), BasicTypeEnum::IntType(it)) => {
+                let from_bits = iv.get_type().get_bit_width();
+                if from_bits == it.get_bit_width() {
+                    iv.into()
+                } else if from_bits == 1 {
+                    self.builder.build_int_z_extend(iv, it, "slot_zext").unwrap().into()
+                } else if from_bits < it.get_bit_width() {
+                    self.builder.build_int_s_extend(iv, it, "slot_sext").unwrap().into()
+                } else {
+                    self.builder.build_int_truncate(iv, it, "slot_trunc").unwrap().into()
+                }
+            }
+            (BasicValueEnum::IntValue(iv), BasicTypeEnum::FloatType(ft)) => {
+                self.builder.build_signed_int_to_float(iv, ft, "slot_int2float").unwrap().into()
+            }
+            (BasicValueEnum::FloatValue(fv), BasicTypeEnum::FloatType(ft)) => {
+                self.builder.build_float_cast(fv, ft, "slot_fcast").unwrap().into()
+            }
+            (BasicValueEnum::FloatValue(fv), BasicTypeEnum::IntType(it)) => {
+                self.builder.build_float_to_signed_int(fv, it, "slot_float2int").unwrap().into()
+            }
+            (BasicValueEnum::PointerValue(pv), BasicTypeEnum::PointerType(pt)) => {
+                self.builder.build_pointer_cast(pv, pt, "slot_ptr_cast").unwrap().into()
+            }
+            (other, _) => {
+                return Err(CompileError::type_mismatch(
+                    span,
+                    format!("cannot store a value of type {} in a `{}` array", other.get_type().print_to_string(), to),
+                ))
+            }
+        })
+    }
+
+    /// `format(template, args...)`: the arguments are laid out like `any` boxes in one
+    /// stack array, so `wpp_format` can render each by its static type's tag
+    fn compile_format(&mut self, args: &[Expr], span: Span) -> CompileResult<BasicValueEnum<'ctx>> {
+        let Some((template, rest)) = args.split_first() else {
+            return Err(CompileError::ArgumentCount {
+                callee: "format".to_string(),
+                expected: "at least 1 argument (format string)".to_string(),
+                found: 0,
+                span,
+            });
+        };
+        let fmt = match self.compile_typed(template)? {
+            (BasicValueEnum::PointerValue(pv), FieldType::String) => pv,
+            (_, ty) => {
+                return Err(CompileError::type_mismatch(
+                    template.span,
+                    format!("`format` expects a string template, found `{}`", ty),
+                ))
+            }
+        };
+
+        // Allocated in the entry block so a `format` inside a loop reuses the same slots
+        let i64_ty = self.context.i64_type();
+        let slots = self.entry_alloca(i64_ty.array_type(2 * rest.len().max(1) as u32), "format_args");
+        let slots = self
+            .builder
+            .build_pointer_cast(slots, i64_ty.ptr_type(AddressSpace::default()), "format_slots")
+            .unwrap();
+
+        let mut compiled = Vec::with_capacity(rest.len());
+        for (i, arg) in rest.iter().enumerate() {
+            let (val, ty) = self.compile_typed(arg)?;
+            let payload = self.coerce_to_slot(val, &ty, &ty, arg.span)?;
+            let tag_ptr = unsafe {
+                self.builder
+                    .build_gep(i64_ty, slots, &[i64_ty.const_int(2 * i as u64, false)], "format_tag")
+                    .unwrap()
+            };
+            let tag_ptr = self
+                .builder
+                .build_pointer_cast(tag_ptr, self.i32_type.ptr_type(AddressSpace::default()), "format_tag_cast")
+                .unwrap();
+            self.builder
+                .build_store(tag_ptr, self.i32_type.const_int(self.field_type_tag(&ty), false))
+                .unwrap();
+            let payload_ptr = unsafe {
+                self.builder
+                    .build_gep(i64_ty, slots, &[i64_ty.const_int(2 * i as u64 + 1, false)], "format_payload")
+                    .unwrap()
+            };
+            let payload_ptr = self
+                .builder
+                .build_pointer_cast(payload_ptr, self.slot_type(&ty).ptr_type(AddressSpace::default()), "format_payload_cast")
+                .unwrap();
+            self.builder.build_store(payload_ptr, payload).unwrap();
+            compiled.push((arg, val));
+        }
+
+        let format_fn = self.runtime_fn("wpp_format")?;
+        let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
+        let argv = self.builder.build_pointer_cast(slots, i8ptr, "format_argv").unwrap();
+        let result = self
+            .builder
+            .build_call(
+                format_fn,
+                &[fmt.into(), self.i32_type.const_int(rest.len() as u64, false).into(), argv.into()],
+                "call_format",
+            )
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .expect("wpp_format must return a pointer");
+
+        for (arg, val) in compiled {
+            self.release_string_temporary(arg, val)?;
+        }
+        self.release_string_temporary(template, fmt.into())?;
+        Ok(result)
+    }
+
+    /// Box a value for an `any[]` slot: 16 bytes { i32 tag, pad, 8-byte payload }
+    fn box_any(&mut self, val: BasicValueEnum<'ctx>, ty: &FieldType) -> CompileResult<PointerValue<'ctx>> {
+        let payload = self.coerce_to_slot(val, ty, ty, Span::default())?;
+        let boxed = self.heap_alloc(self.context.i64_type().const_int(16, false), "any_box")?;
+
+        let tag_ptr = self
+            .builder
+            .build_bitcast(boxed, self.i32_type.ptr_type(AddressSpace::default()), "any_tag")
+            .unwrap()
+            .into_pointer_value();
+        self.builder
+            .build_store(tag_ptr, self.i32_type.const_int(self.field_type_tag(ty), false))
+            .unwrap();
+
+        let payload_ptr = unsafe {
+            self.builder
+                .build_gep(self.context.i8_type(), boxed, &[self.i32_type.const_int(8, false)], "any_payload")
+                .unwrap()
+        };
+        let payload_ptr = self
+            .builder
+            .build_bitcast(payload_ptr, self.slot_type(ty).ptr_type(AddressSpace::default()), "any_payload_cast")
+            .unwrap()
+            .into_pointer_value();
+        self.builder.build_store(payload_ptr, payload).unwrap();
+        Ok(boxed)
+    }
+
+    /// Build `[a, b, ...]`, inferring the element type unless the caller already knows it
+    fn compile_array_literal(
+        &mut self,
+        elements: &[Expr],
+        expected: Option<&FieldType>,
+    ) -> CompileResult<(PointerValue<'ctx>, FieldType)> {
+        let mut compiled = Vec::with_capacity(elements.len());
+        for el in elements {
+            // let the annotation flow into nested literals: `let m: f64[][] = [[1, 2]]`
+            let (val, ty) = match expected {
+                Some(elem) => self.compile_expected(el, elem)?,
+                None => self.compile_typed(el)?,
+            };
+            compiled.push((val, ty, el.span));
+        }
+
+        let elem = match expected {
+            Some(ty) => ty.clone(),
+            None => Self::unify_elem_types(&compiled.iter().map(|(_, ty, _)| ty.clone()).collect::<Vec<_>>()),
+        };
+
+        let len = self.i32_type.const_int(elements.len() as u64, false);
+        let arr = self.alloc_array(len, &elem)?;
+        for (i, (val, ty, span)) in compiled.into_iter().enumerate() {
+            let stored = self.coerce_to_slot(val, &ty, &elem, span)?;
+            let slot = self.array_slot_ptr(arr, self.i32_type.const_int(i as u64, false), &elem);
+            self.builder.build_store(slot, stored).unwrap();
+        }
+
+        Ok((arr, elem))
+    }
+
+    /// Compile an expression that must be an array; returns the buffer and its element type
+    fn compile_array_operand(&mut self, target: &Expr) -> CompileResult<(PointerValue<'ctx>, FieldType)> {
+        match self.compile_typed(target)? {
+            (BasicValueEnum::PointerValue(pv), FieldType::Array(elem)) => {
+                let i8_ptr = self.context.i8_type().ptr_type(AddressSpace::default());
+                let arr = self.builder.build_pointer_cast(pv, i8_ptr, "arr_view").unwrap();
+                Ok((arr, *elem))
+            }
+            (_, ty) => Err(CompileError::type_mismatch(
+                target.span,
+                format!("only arrays can be indexed, found `{}`", ty),
+            )),
+        }
+    }
+
+    fn compile_index_value(&mut self, index: &Expr) -> CompileResult<IntValue<'ctx>> {
+        match self.compile_expr(index)? {
+            BasicValueEnum::IntValue(iv) => Ok(self.int_to_i32(iv)),
+            other => Err(CompileError::type_mismatch(
+                index.span,
+                format!("array index must be an integer, found {}", other.get_type().print_to_string()),
+            )),
+        }
+    }
+
+    /// 📏 Emit the bounds check for `arr[idx]`.
+    ///
+    /// On return the builder sits in the in-bounds block and the slot pointer is returned
+    /// together with the out-of-bounds block and the merge block; the caller finishes the
+    /// in-bounds path and branches to the merge block itself.
+    fn compile_checked_index(
+        &mut self,
+        arr: PointerValue<'ctx>,
+        idx: IntValue<'ctx>,
+        elem: &FieldType,
+    ) -> CompileResult<(PointerValue<'ctx>, inkwell::basic_block::BasicBlock<'ctx>, inkwell::basic_block::BasicBlock<'ctx>)> {
+        let len = self.array_len(arr);
+        // Unsigned compare also rejects negative indices
+        let in_bounds = self
+            .builder
+            .build_int_compare(inkwell::IntPredicate::ULT, idx, len, "idx_in_bounds")
+            .unwrap();
+        let (oob_bb, cont_bb) = self.guard(in_bounds, |cg| cg.raise_index_error(idx, len))?;
+        let slot = self.array_slot_ptr(arr, idx, elem);
+        Ok((slot, oob_bb, cont_bb))
+    }
+
+    /// Branch on `ok`; the failing path runs `raise` and jumps to the merge block.
+    ///
+    /// Leaves the builder in the passing block and returns the block the failing path ends in
+    /// and the merge block.
+    fn guard(
+        &mut self,
+        ok: IntValue<'ctx>,
+        raise: impl FnOnce(&mut Self) -> CompileResult<()>,
+    ) -> CompileResult<(inkwell::basic_block::BasicBlock<'ctx>, inkwell::basic_block::BasicBlock<'ctx>)> {
+        let func = self.builder.get_insert_block().unwrap().get_parent().unwrap();
+        let ok_bb = self.context.append_basic_block(func, "idx_ok");
+        let oob_bb = self.context.append_basic_block(func, "idx_oob");
+        let cont_bb = self.context.append_basic_block(func, "idx_cont");
+        self.builder.build_conditional_branch(ok, ok_bb, oob_bb).unwrap();
+
+        // --- Failed check: raise a W++ exception ---
+        self.builder.position_at_end(oob_bb);
+        raise(self)?;
+        let fail_end = self.builder.get_insert_block().unwrap();
+        self.builder.build_unconditional_branch(cont_bb).unwrap();
+
+        self.builder.position_at_end(ok_bb);
+        Ok((fail_end, cont_bb))
+    }
+
+    /// Check an integer divisor: zero throws "division by zero" instead of trapping. Returns
+    /// the divisor to use, 1 on the throwing path so the division stays defined.
+    fn checked_divisor(&mut self, r: IntValue<'ctx>) -> CompileResult<IntValue<'ctx>> {
+        if r.get_zero_extended_constant().is_some_and(|c| c != 0) {
+            return Ok(r);
+        }
+        let non_zero = self
+            .builder
+            .build_int_compare(inkwell::IntPredicate::NE, r, r.get_type().const_zero(), "div_non_zero")
+            .unwrap();
+        let (_, cont_bb) = self.guard(non_zero, |cg| {
+            let msg = cg
+                .builder
+                .build_global_string_ptr("division by zero", "div_zero_msg")
+                .unwrap()
+                .as_pointer_value();
+            cg.raise_string(msg)
+        })?;
+        self.builder.build_unconditional_branch(cont_bb).unwrap();
+        self.builder.position_at_end(cont_bb);
+        Ok(self
+            .builder
+            .build_select(non_zero, r, r.get_type().const_int(1, false), "divisor")
+            .unwrap()
+            .into_int_value())
+    }
+
+    /// 📏 The length lives in the first i32 of the header
+    fn array_len(&self, arr: PointerValue<'ctx>) -> IntValue<'ctx> {
+        let header = self
+            .builder
+            .build_bitcast(arr, self.i32_type.ptr_type(AddressSpace::default()), "arr_header")
+            .unwrap()
+            .into_pointer_value();
+        self.builder.build_load(self.i32_type, header, "arr_len").unwrap().into_int_value()
+    }
+
+    /// `target[index]` as an rvalue
+    fn compile_index_load(
+        &mut self,
+        target: &Expr,
+        index: &Expr,
+    ) -> CompileResult<(BasicValueEnum<'ctx>, FieldType)> {
+        let (arr, elem) = self.compile_array_operand(target)?;
+        let idx = self.compile_index_value(index)?;
+        let (slot, oob_bb, cont_bb) = self.compile_checked_index(arr, idx, &elem)?;
+
+        let val = self.load_slot(slot, &elem);
+        let ok_end = self.builder.get_insert_block().unwrap();
+        self.builder.build_unconditional_branch(cont_bb).unwrap();
+
+        // Out-of-range reads yield a zero value while the exception propagates
+        self.builder.position_at_end(cont_bb);
+        let value_ty = self.field_value_type(&elem);
+        let phi = self.builder.build_phi(value_ty, "index_result").unwrap();
+        phi.add_incoming(&[(&val, ok_end), (&value_ty.const_zero(), oob_bb)]);
+        Ok((phi.as_basic_value(), elem))
+    }
+
+    /// Load an element from a slot pointer returned by `array_slot_ptr`
+    fn load_slot(&self, slot: PointerValue<'ctx>, elem: &FieldType) -> BasicValueEnum<'ctx> {
+        let raw = self.builder.build_load(self.slot_type(elem), slot, "elem").unwrap();
+        if *elem == FieldType::Bool {
+            self.builder
+                .build_int_compare(inkwell::IntPredicate::NE, raw.into_int_value(), self.i32_type.const_zero(), "elem_bool")
+                .unwrap()
+                .into()
+        } else {
+            raw
+        }
+    }
+
+    /// `target[index] = value`
+    /// Store into `target[index]`, returning the stored value as an `elem`
+    fn compile_index_store(&mut self, target: &Expr, index: &Expr, value: &Expr) -> CompileResult<BasicValueEnum<'ctx>> {
+        let (arr, elem) = self.compile_array_operand(target)?;
+        let idx = self.compile_index_value(index)?;
+        let (val, val_ty) = self.compile_expected(value, &elem)?;
+        let stored = self.coerce_to_slot(val, &val_ty, &elem, value.span)?;
+
+        let (slot, _, cont_bb) = self.compile_checked_index(arr, idx, &elem)?;
+        self.builder.build_store(slot, stored).unwrap();
+        self.builder.build_unconditional_branch(cont_bb).unwrap();
+        self.builder.position_at_end(cont_bb);
+        Ok(if val_ty.is_numeric() && elem.is_numeric() { self.convert_numeric(val, &val_ty, &elem) } else { val })
+    }
+
+    /// Compile `value` into the slot representation of an `elem` array
+    fn compile_element(&mut self, value: &Expr, elem: &FieldType) -> CompileResult<BasicValueEnum<'ctx>> {
+        let (val, val_ty) = self.compile_expected(value, elem)?;
+        self.coerce_to_slot(val, &val_ty, elem, value.span)
+    }
+
+    fn is_array_builtin(name: &str) -> bool {
+        matches!(name, "push" | "pop" | "insert" | "remove" | "slice" | "concat")
+    }
+
+    /// 📦 `push`, `pop`, `insert`, `remove`, `slice` and `concat`, lowered to the runtime
+    /// vector functions. Index and emptiness checks are emitted here so they raise W++ exceptions.
+    fn compile_array_builtin(
+        &mut self,
+        name: &str,
+        args: &[Expr],
+        span: Span,
+    ) -> CompileResult<(BasicValueEnum<'ctx>, FieldType)> {
+        let (arity, usage) = match name {
+            "push" => (2, "exactly 2 arguments (array, value)"),
+            "pop" => (1, "exactly 1 argument (array)"),
+            "insert" => (3, "exactly 3 arguments (array, index, value)"),
+            "remove" => (2, "exactly 2 arguments (array, index)"),
+            "slice" => (3, "exactly 3 arguments (array, start, end)"),
+            _ => (2, "exactly 2 arguments (array, array)"),
+        };
+        if args.len() != arity {
+            return Err(CompileError::ArgumentCount {
+                callee: name.to_string(),
+                expected: usage.to_string(),
+                found: args.len(),
+                span,
+            });
+        }
+
+        let (arr, elem) = match self.compile_typed(&args[0])? {
+            (BasicValueEnum::PointerValue(pv), FieldType::Array(elem)) => {
+                let i8_ptr = self.context.i8_type().ptr_type(AddressSpace::default());
+                (self.builder.build_pointer_cast(pv, i8_ptr, "arr_view").unwrap(), *elem)
+            }
+            (_, ty) => {
+                return Err(CompileError::type_mismatch(
+                    args[0].span,
+                    format!("`{}` expects an array, found `{}`", name, ty),
+                ))
+            }
+        };
+        let runtime_name = format!("wpp_vec_{}", name);
+        let runtime = self.runtime_fn(&runtime_name)?;
+        let call = |cg: &Self, args: &[BasicMetadataValueEnum<'ctx>], label: &str| {
+            cg.builder.build_call(runtime, args, label).unwrap().try_as_basic_value().left()
+        };
+
+        match name {
+            "push" => {
+                let stored = self.compile_element(&args[1], &elem)?;
+                let bits = self.slot_to_bits(stored, args[1].span)?;
+                let len = call(self, &[arr.into(), bits.into()], "push_len").expect("wpp_vec_push returns i32");
+                Ok((len, FieldType::Int32))
+            }
+            "insert" => {
+                let idx = self.compile_index_value(&args[1])?;
+                let stored = self.compile_element(&args[2], &elem)?;
+                let bits = self.slot_to_bits(stored, args[2].span)?;
+
+                // Inserting at `len` appends
+                let len = self.array_len(arr);
+                let in_bounds = self
+                    .builder
+                    .build_int_compare(inkwell::IntPredicate::ULE, idx, len, "insert_in_bounds")
+                    .unwrap();
+                let (_, cont_bb) = self.guard(in_bounds, |cg| cg.raise_index_error(idx, len))?;
+                call(self, &[arr.into(), idx.into(), bits.into()], "");
+                self.builder.build_unconditional_branch(cont_bb).unwrap();
+
+                self.builder.position_at_end(cont_bb);
+                Ok((self.array_len(arr).into(), FieldType::Int32))
+            }
+            "pop" | "remove" => {
+                let len = self.array_len(arr);
+                let (fail_bb, cont_bb, bits) = if name == "pop" {
+                    let non_empty = self
+                        .builder
+                        .build_int_compare(inkwell::IntPredicate::NE, len, self.i32_type.const_zero(), "pop_non_empty")
+                        .unwrap();
+                    let (fail_bb, cont_bb) = self.guard(non_empty, |cg| {
+                        let msg = cg
+                            .builder
+                            .build_global_string_ptr("pop from an empty array", "pop_empty_msg")
+                            .unwrap()
+                            .as_pointer_value();
+                        cg.raise_string(msg)
+                    })?;
+                    (fail_bb, cont_bb, call(self, &[arr.into()], "pop_bits"))
+                } else {
+                    let idx = self.compile_index_value(&args[1])?;
+                    let in_bounds = self
+                        .builder
+                        .build_int_compare(inkwell::IntPredicate::ULT, idx, len, "idx_in_bounds")
+                        .unwrap();
+                    let (fail_bb, cont_bb) = self.guard(in_bounds, |cg| cg.raise_index_error(idx, len))?;
+                    (fail_bb, cont_bb, call(self, &[arr.into(), idx.into()], "remove_bits"))
+                };
+                let val = self.bits_to_value(bits.expect("vector removal returns i64").into_int_value(), &elem);
+                let ok_end = self.builder.get_insert_block().unwrap();
+                self.builder.build_unconditional_branch(cont_bb).unwrap();
+
+                // A failed check yields a zero value while the exception propagates
+                self.builder.position_at_end(cont_bb);
+                let value_ty = self.field_value_type(&elem);
+                let phi = self.builder.build_phi(value_ty, &format!("{}_result", name)).unwrap();
+                phi.add_incoming(&[(&val, ok_end), (&value_ty.const_zero(), fail_bb)]);
+                Ok((phi.as_basic_value(), elem))
+            }
+            "slice" => {
+                let start = self.compile_index_value(&args[1])?;
+                let end = self.compile_index_value(&args[2])?;
+                let out = call(self, &[arr.into(), start.into(), end.into()], "slice").expect("wpp_vec_slice returns a pointer");
+                Ok((out, FieldType::Array(Box::new(elem))))
+            }
+            _ => {
+                let other = match &args[1].kind {
+                    ExprKind::ArrayLiteral(elements) => self.compile_array_literal(elements, Some(&elem))?,
+                    _ => match self.compile_typed(&args[1])? {
+                        (BasicValueEnum::PointerValue(pv), FieldType::Array(other_elem)) => {
+                            let i8_ptr = self.context.i8_type().ptr_type(AddressSpace::default());
+                            (self.builder.build_pointer_cast(pv, i8_ptr, "arr_view").unwrap(), *other_elem)
+                        }
+                        (_, ty) => {
+                            return Err(CompileError::type_mismatch(
+                                args[1].span,
+                                format!("`concat` expects an array, found `{}`", ty),
+                            ))
+                        }
+                    },
+                };
+                if other.1 != elem {
+                    return Err(CompileError::type_mismatch(
+                        args[1].span,
+                        format!("cannot concat `{}[]` with `{}[]`", elem, other.1),
+                    ));
+                }
+                let out = call(self, &[arr.into(), other.0.into()], "concat").expect("wpp_vec_concat returns a pointer");
+                Ok((out, FieldType::Array(Box::new(elem))))
+            }
+        }
+    }
+
+    fn is_map_builtin(name: &str) -> bool {
+        matches!(name, "Map" | "get" | "set" | "has" | "delete" | "keys" | "values")
+    }
+
+    /// 🗺️ `Map`, `get`, `set`, `has`, `delete`, `keys` and `values`, lowered to runtime/map.rs.
+    /// `expected` is the value type from an annotation, used when `Map()` has nothing to infer from.
+    fn compile_map_builtin(
+        &mut self,
+        name: &str,
+        args: &[Expr],
+        expected: Option<&FieldType>,
+        span: Span,
+    ) -> CompileResult<(BasicValueEnum<'ctx>, FieldType)> {
+        let arity_ok = match name {
+            "Map" => args.len() <= 1,
+            "get" => args.len() == 2 || args.len() == 3,
+            "set" => args.len() == 3,
+            "has" | "delete" => args.len() == 2,
+            _ => args.len() == 1,
+        };
+        if !arity_ok {
+            let usage = match name {
+                "Map" => "at most 1 argument (an object literal of initial entries)",
+                "get" => "2 or 3 arguments (map, key, optional default)",
+                "set" => "exactly 3 arguments (map, key, value)",
+                "has" | "delete" => "exactly 2 arguments (map, key)",
+                _ => "exactly 1 argument (map)",
+            };
+            return Err(CompileError::ArgumentCount {
+                callee: name.to_string(),
+                expected: usage.to_string(),
+                found: args.len(),
+                span,
+            });
+        }
+
+        let i8_ptr = self.context.i8_type().ptr_type(AddressSpace::default());
+        let call = |cg: &Self, f: &str, args: &[BasicMetadataValueEnum<'ctx>], label: &str| {
+            Ok::<_, CompileError>(cg.builder.build_call(cg.runtime_fn(f)?, args, label).unwrap().try_as_basic_value().left())
+        };
+
+        if name == "Map" {
+            let fields: &[(String, Expr)] = match args.first().map(|a| &a.kind) {
+                None => &[],
+                Some(ExprKind::ObjectLiteral { fields, type_name: None }) => fields,
+                Some(_) => {
+                    return Err(CompileError::type_mismatch(
+                        args[0].span,
+                        "`Map` expects an object literal of initial entries, e.g. `Map({ \"a\": 1 })`",
+                    ))
+                }
+            };
+            let mut compiled = Vec::with_capacity(fields.len());
+            for (key, value) in fields {
+                let (val, ty) = match expected {
+                    Some(val_ty) => self.compile_expected(value, val_ty)?,
+                    None => self.compile_typed(value)?,
+                };
+                compiled.push((key, val, ty, value.span));
+            }
+            let val_ty = match expected {
+                Some(ty) => ty.clone(),
+                None => Self::unify_elem_types(&compiled.iter().map(|(_, _, ty, _)| ty.clone()).collect::<Vec<_>>()),
+            };
+
+            let tag = self.i32_type.const_int(self.field_type_tag(&val_ty), false);
+            let map = call(self, "wpp_map_new", &[tag.into()], "map_new")?
+                .expect("wpp_map_new returns a pointer")
+                .into_pointer_value();
+            for (key, val, ty, span) in compiled {
+                let stored = self.coerce_to_slot(val, &ty, &val_ty, span)?;
+                let bits = self.slot_to_bits(stored, span)?;
+                let key_ptr = self.builder.build_global_string_ptr(key, "map_key").unwrap().as_pointer_value();
+                call(self, "wpp_map_set", &[map.into(), key_ptr.into(), bits.into()], "map_init")?;
+            }
+            return Ok((map.into(), FieldType::Map(Box::new(val_ty))));
+        }
+
+        let (map, val_ty) = match self.compile_typed(&args[0])? {
+            (BasicValueEnum::PointerValue(pv), FieldType::Map(val_ty)) => {
+                (self.builder.build_pointer_cast(pv, i8_ptr, "map_view").unwrap(), *val_ty)
+            }
+            (_, ty) => {
+                return Err(CompileError::type_mismatch(
+                    args[0].span,
+                    format!("`{}` expects a map, found `{}`", name, ty),
+                ))
+            }
+        };
+
+        match name {
+            "keys" => {
+                let out = call(self, "wpp_map_keys", &[map.into()], "map_keys")?.expect("wpp_map_keys returns a pointer");
+                return Ok((out, FieldType::Array(Box::new(FieldType::String))));
+            }
+            "values" => {
+                let out = call(self, "wpp_map_values", &[map.into()], "map_values")?.expect("wpp_map_values returns a pointer");
+                return Ok((out, FieldType::Array(Box::new(val_ty))));
+            }
+            _ => {}
+        }
+
+        let key = match self.compile_typed(&args[1])? {
+            (BasicValueEnum::PointerValue(pv), FieldType::String) => pv,
+            (_, ty) => {
+                return Err(CompileError::type_mismatch(
+                    args[1].span,
+                    format!("map keys must be strings, found `{}`", ty),
+                ))
+            }
+        };
+
+        match name {
+            "set" => {
+                let stored = self.compile_element(&args[2], &val_ty)?;
+                let bits = self.slot_to_bits(stored, args[2].span)?;
+                let len = call(self, "wpp_map_set", &[map.into(), key.into(), bits.into()], "map_len")?
+                    .expect("wpp_map_set returns i32");
+                Ok((len, FieldType::Int32))
+            }
+            "has" | "delete" => {
+                let (f, label) = if name == "has" { ("wpp_map_find", "map_pos") } else { ("wpp_map_delete", "map_deleted") };
+                let raw = call(self, f, &[map.into(), key.into()], label)?.expect("map lookup returns i32").into_int_value();
+                let (pred, rhs) = if name == "has" {
+                    (inkwell::IntPredicate::SGE, self.i32_type.const_zero())
+                } else {
+                    (inkwell::IntPredicate::NE, self.i32_type.const_zero())
+                };
+                let found = self.builder.build_int_compare(pred, raw, rhs, "map_found").unwrap();
+                Ok((found.into(), FieldType::Bool))
+            }
+            _ => {
+                // A default is compiled up front so both paths merge into one value
+                let fallback = match args.get(2) {
+                    Some(default) => {
+                        let stored = self.compile_element(default, &val_ty)?;
+                        Some(self.slot_to_bits(stored, default.span)?)
+                    }
+                    None => None,
+                };
+
+                let pos = call(self, "wpp_map_find", &[map.into(), key.into()], "map_pos")?
+                    .expect("wpp_map_find returns i32")
+                    .into_int_value();
+                let found = self
+                    .builder
+                    .build_int_compare(inkwell::IntPredicate::SGE, pos, self.i32_type.const_zero(), "map_found")
+                    .unwrap();
+                let (miss_bb, cont_bb) = match fallback {
+                    Some(_) => {
+                        let func = self.builder.get_insert_block().unwrap().get_parent().unwrap();
+                        let hit_bb = self.context.append_basic_block(func, "map_hit");
+                        let miss_bb = self.context.append_basic_block(func, "map_miss");
+                        let cont_bb = self.context.append_basic_block(func, "map_cont");
+                        self.builder.build_conditional_branch(found, hit_bb, miss_bb).unwrap();
+                        self.builder.position_at_end(miss_bb);
+                        self.builder.build_unconditional_branch(cont_bb).unwrap();
+                        self.builder.position_at_end(hit_bb);
+                        (miss_bb, cont_bb)
+                    }
+                    None => self.guard(found, |cg| {
+                        let msg = call(cg, "wpp_key_error", &[key.into()], "key_err_msg")?
+                            .expect("wpp_key_error returns a string")
+                            .into_pointer_value();
+                        cg.raise_string(msg)
+                    })?,
+                };
+
+                let bits = call(self, "wpp_map_value_at", &[map.into(), pos.into()], "map_bits")?
+                    .expect("wpp_map_value_at returns i64")
+                    .into_int_value();
+                let hit_end = self.builder.get_insert_block().unwrap();
+                self.builder.build_unconditional_branch(cont_bb).unwrap();
+
+                // A missing key yields the default, or zero while the exception propagates
+                self.builder.position_at_end(cont_bb);
+                let i64_type = self.context.i64_type();
+                let phi = self.builder.build_phi(i64_type, "get_bits").unwrap();
+                let missing = fallback.unwrap_or_else(|| i64_type.const_zero());
+                phi.add_incoming(&[(&bits, hit_end), (&missing, miss_bb)]);
+                Ok((self.bits_to_value(phi.as_basic_value().into_int_value(), &val_ty), val_ty))
+            }
+        }
+    }
+
+    /// A stack slot in the current function's entry block, so code that runs repeatedly (a loop
+    /// body) reuses it instead of growing the stack on every pass
+    fn entry_alloca(&self, ty: impl BasicType<'ctx>, name: &str) -> PointerValue<'ctx> {
+        let function = self.builder.get_insert_block().and_then(|bb| bb.get_parent()).expect("alloca outside a function");
+        let entry = function.get_first_basic_block().expect("function without an entry block");
+        let entry_builder = self.context.create_builder();
+        match entry.get_first_instruction() {
+            Some(first) => entry_builder.position_before(&first),
+            None => entry_builder.position_at_end(entry),
+        }
+        entry_builder.build_alloca(ty, name).unwrap()
+    }
+
+    /// Have the module's constructor (an `llvm.global_ctors` entry) register `literal` with
+    /// the runtime, which only reads the header of strings it knows (see `runtime/string.rs`)
+    fn register_string_literal(&self, literal: PointerValue<'ctx>) {
+        let i32_ty = self.context.i32_type();
+        let i8_ptr = self.context.i8_type().ptr_type(AddressSpace::default());
+        let ctor = self.module.get_function("wpp.register_literals").unwrap_or_else(|| {
+            let ctor = self.module.add_function(
+                "wpp.register_literals",
+                self.context.void_type().fn_type(&[], false),
+                Some(inkwell::module::Linkage::Internal),
+            );
+            let entry_builder = self.context.create_builder();
+            entry_builder.position_at_end(self.context.append_basic_block(ctor, "entry"));
+            entry_builder.build_return(None).unwrap();
+
+            // Appending linkage, so modules linked into `main` keep their constructors
+            let ctor_ptr = ctor.as_global_value().as_pointer_value();
+            let entry_ty = self.context.struct_type(&[i32_ty.into(), ctor_ptr.get_type().into(), i8_ptr.into()], false);
+            let ctors = self.module.add_global(entry_ty.array_type(1), None, "llvm.global_ctors");
+            ctors.set_linkage(inkwell::module::Linkage::Appending);
+            ctors.set_initializer(&entry_ty.const_array(&[entry_ty.const_named_struct(&[
+                i32_ty.const_int(65535, false).into(),
+                ctor_ptr.into(),
+                i8_ptr.const_null().into(),
+            ])]));
+            ctor
+        });
+        let register = self.module.get_function("wpp_str_register_literal").unwrap_or_else(|| {
+            let ty = self.context.void_type().fn_type(&[i8_ptr.into()], false);
+            self.module.add_function("wpp_str_register_literal", ty, None)
+        });
+        let ctor_builder = self.context.create_builder();
+        let ret = ctor.get_first_basic_block().and_then(|bb| bb.get_terminator()).expect("constructor without a return");
+        ctor_builder.position_before(&ret);
+        ctor_builder.build_call(register, &[literal.into()], "").unwrap();
+    }
+
+    /// 🔁 `for item in iterable`. Arrays and maps are walked by position and ranges count
+    /// up; the length is re-read every iteration so `push`/`pop` in the body never read
+    /// past the end. `break`/`continue` go through `loop_stack` like the C-style `for`.
+    fn compile_for_in(&mut self, key: Option<&str>, item: &str, iterable: &Expr, body: &[Node]) -> CompileResult<()> {
+        enum Source<'ctx> {
+            Range(IntValue<'ctx>),
+            Array(PointerValue<'ctx>, FieldType),
+            Map(PointerValue<'ctx>, FieldType),
+        }
+
+        let i8_ptr = self.context.i8_type().ptr_type(AddressSpace::default());
+        let (start, source) = match &iterable.kind {
+            ExprKind::Range { start, end } => {
+                if key.is_some() {
+                    return Err(CompileError::unsupported(
+                        iterable.span,
+                        "a range loop binds a single variable: `for i in 0..n`",
+                    ));
+                }
+                (self.compile_range_bound(start)?, Source::Range(self.compile_range_bound(end)?))
+            }
+            _ => {
+                let zero = self.i32_type.const_zero();
+                match self.compile_typed(iterable)? {
+                    (BasicValueEnum::PointerValue(pv), FieldType::Array(elem)) => {
+                        let arr = self.builder.build_pointer_cast(pv, i8_ptr, "arr_view").unwrap();
+                        (zero, Source::Array(arr, *elem))
+                    }
+                    (BasicValueEnum::PointerValue(pv), FieldType::Map(val)) => {
+                        let map = self.builder.build_pointer_cast(pv, i8_ptr, "map_view").unwrap();
+                        (zero, Source::Map(map, *val))
+                    }
+                    (_, ty) => {
+                        return Err(CompileError::type_mismatch(
+                            iterable.span,
+                            format!("cannot iterate over `{}`: expected an array, map or range", ty),
+                        ))
+                    }
+                }
+            }
+        };
+
+        // Loop variables in binding order: [index or key,] item
+        let bindings: Vec<(&str, FieldType)> = match (&source, key) {
+            (Source::Range(_), _) => vec![(item, FieldType::Int32)],
+            (Source::Array(_, elem), None) => vec![(item, elem.clone())],
+            (Source::Array(_, elem), Some(k)) => vec![(k, FieldType::Int32), (item, elem.clone())],
+            (Source::Map(..), None) => vec![(item, FieldType::String)],
+            (Source::Map(_, val), Some(k)) => vec![(k, FieldType::String), (item, val.clone())],
+        };
+        let mut shadowed = Vec::with_capacity(bindings.len());
+        let mut slots = Vec::with_capacity(bindings.len());
+        for (name, ty) in &bindings {
+            let llvm_ty = self.field_value_type(ty);
+            let ptr = self.entry_alloca(llvm_ty, name);
+            // Entity elements dispatch their methods like any other instance variable
+            let entity_type = match ty {
+                FieldType::Object(entity) if self.entities.contains_key(entity) || self.interfaces.contains_key(entity) => Some(entity.clone()),
+                _ => None,
+            };
+            let info = VarInfo {
+                ptr,
+                ty: llvm_ty,
+                is_const: false,
+                is_thread_state: false,
+                entity_type,
+                object_type_name: None,
+                function_signature: None,
+                field_type: Self::tracked_field_type(ty.clone()),
+            };
+            shadowed.push((name.to_string(), self.vars.insert(name.to_string(), info)));
+            slots.push(ptr);
+        }
+
+        let counter = self.entry_alloca(self.i32_type, "for_in_idx");
+        self.builder.build_store(counter, start).unwrap();
+
+        let func = self.builder.get_insert_block().unwrap().get_parent().unwrap();
+        let cond_bb = self.context.append_basic_block(func, "for_in_cond");
+        let body_bb = self.context.append_basic_block(func, "for_in_body");
+        let step_bb = self.context.append_basic_block(func, "for_in_step");
+        let end_bb = self.context.append_basic_block(func, "for_in_end");
+        self.loop_stack.push((step_bb, end_bb, self.defers.len()));
+        self.builder.build_unconditional_branch(cond_bb).unwrap();
+
+        // === Condition: idx < end / len ===
+        self.builder.position_at_end(cond_bb);
+        let idx = self.builder.build_load(self.i32_type, counter, "idx").unwrap().into_int_value();
+        let bound = match &source {
+            Source::Range(end) => *end,
+            Source::Array(coll, _) | Source::Map(coll, _) => self.array_len(*coll),
+        };
+        let more = self
+            .builder
+            .build_int_compare(inkwell::IntPredicate::SLT, idx, bound, "for_in_more")
+            .unwrap();
+        self.builder.build_conditional_branch(more, body_bb, end_bb).unwrap();
+
+        // === Body: bind the loop variables, then run the statements ===
+        self.builder.position_at_end(body_bb);
+        let idx = self.builder.build_load(self.i32_type, counter, "idx").unwrap().into_int_value();
+        let mut values: Vec<BasicValueEnum<'ctx>> = Vec::with_capacity(2);
+        match &source {
+            Source::Range(_) => values.push(idx.into()),
+            Source::Array(arr, elem) => {
+                if key.is_some() {
+                    values.push(idx.into());
+                }
+                let slot = self.array_slot_ptr(*arr, idx, elem);
+                values.push(self.load_slot(slot, elem));
+            }
+            Source::Map(map, val) => {
+                let key_fn = self.runtime_fn("wpp_map_key_at")?;
+                let k = self
+                    .builder
+                    .build_call(key_fn, &[(*map).into(), idx.into()], "map_key")
+                    .unwrap()
+                    .try_as_basic_value()
+                    .left()
+                    .expect("wpp_map_key_at returns a string");
+                values.push(k);
+                if key.is_some() {
+                    let value_fn = self.runtime_fn("wpp_map_value_at")?;
+                    let bits = self
+                        .builder
+                        .build_call(value_fn, &[(*map).into(), idx.into()], "map_bits")
+                        .unwrap()
+                        .try_as_basic_value()
+                        .left()
+                        .expect("wpp_map_value_at returns i64")
+                        .into_int_value();
+                    values.push(self.bits_to_value(bits, val));
+                }
+            }
+        }
+        for (slot, val) in slots.iter().zip(values) {
+            self.builder.build_store(*slot, val).unwrap();
+        }
+        self.compile_block(body)?;
+        self.safe_branch(step_bb);
+
+        // === Step ===
+        self.builder.position_at_end(step_bb);
+        let idx = self.builder.build_load(self.i32_type, counter, "idx").unwrap().into_int_value();
+        let next = self.builder.build_int_add(idx, self.i32_type.const_int(1, false), "idx_next").unwrap();
+        self.builder.build_store(counter, next).unwrap();
+        self.builder.build_unconditional_branch(cond_bb).unwrap();
+
+        self.builder.position_at_end(end_bb);
+        self.loop_stack.pop();
+        for (name, previous) in shadowed {
+            match previous {
+                Some(info) => self.vars.insert(name, info),
+                None => self.vars.remove(&name),
+            };
+        }
+        Ok(())
+    }
+
+    /// Sign-extend or truncate an index-like integer to i32
+    fn int_to_i32(&self, iv: IntValue<'ctx>) -> IntValue<'ctx> {
+        let width = iv.get_type().get_bit_width();
+        if width == 32 {
+            iv
+        } else if width == 1 {
+            self.builder.build_int_z_extend(iv, self.i32_type, "to_i32").unwrap()
+        } else if width < 32 {
+            self.builder.build_int_s_extend(iv, self.i32_type, "to_i32").unwrap()
+        } else {
+            self.builder.build_int_truncate(iv, self.i32_type, "to_i32").unwrap()
+        }
+    }
+
+    /// One end of `start..end`, as an i32
+    fn compile_range_bound(&mut self, bound: &Expr) -> CompileResult<IntValue<'ctx>> {
+        match self.compile_expr(bound)? {
+            BasicValueEnum::IntValue(iv) if iv.get_type().get_bit_width() > 1 => Ok(self.int_to_i32(iv)),
+            other => Err(CompileError::type_mismatch(
+                bound.span,
+                format!("range bounds must be integers, found {}", other.get_type().print_to_string()),
+            )),
+        }
+    }
 
-                Expr::TypedLiteral { ty, .. } => {
-                    match ty.as_str() {
-                        "f32" => {
-                            float_params.insert("".to_string());
-                        }
-                        "bool" => {
-                            bool_params.insert("".to_string());
-                        }
-                        _ => {}
-                    }
-                }
+    /// Raise "index out of bounds" as a string exception (see `raise_string`)
+    fn raise_index_error(&mut self, idx: IntValue<'ctx>, len: IntValue<'ctx>) -> CompileResult<()> {
+        let msg_fn = self.runtime_fn("wpp_index_error")?;
+        let msg = self
+            .builder
+            .build_call(msg_fn, &[idx.into(), len.into()], "idx_err_msg")
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .expect("wpp_index_error must return a string")
+            .into_pointer_value();
+        self.raise_string(msg)?;
+        Ok(())
+    }
 
-                Expr::Call { args, .. } => {
-                    for a in args {
-                        if let Expr::StringLiteral(_) = a {
-                            *contains_string_literal = true;
-                        }
-                        scan_for_types(
-                            &[Node::Expr(a.clone())],
-                            int_params,
-                            ptr_params,
-                            float_params,
-                            bool_params,
-                            contains_string_literal,
-                        );
-                    }
-                }
+    /// Throw `msg` as a string exception from generated code. The builder is left in a block
+    /// that is never reached, so callers can still merge it into their continuation.
+    fn raise_string(&mut self, msg: PointerValue<'ctx>) -> CompileResult<()> {
+        let throw_fn = self.runtime_fn("wpp_throw_message")?;
+        self.builder.build_call(throw_fn, &[msg.into()], "").unwrap();
+        self.check_exception()?;
+        Ok(())
+    }
 
-                Expr::Return(inner) => {
-                    if let Some(inner_expr) = inner {
-                        scan_for_types(
-                            &[Node::Expr(*inner_expr.clone())],
-                            int_params,
-                            ptr_params,
-                            float_params,
-                            bool_params,
-                            contains_string_literal,
-                        );
-                    }
+    /// A runtime function declared by `init_runtime_support`
+    fn runtime_fn(&self, name: &str) -> CompileResult<FunctionValue<'ctx>> {
+        self.module
+            .get_function(name)
+            .ok_or_else(|| CompileError::Backend(format!("missing runtime function `{}`", name)))
+    }
+
+    /// Where a pending exception goes from the code being compiled: the innermost `catch` of
+    /// the current function, or else a block that returns to the caller with the exception
+    /// still pending (in the program's entry point, one that reports it and exits). Blocks
+    /// left on the way run their deferred expressions first.
+    fn unwind_target(&mut self) -> CompileResult<inkwell::basic_block::BasicBlock<'ctx>> {
+        let target = self.exception_exit()?;
+        let depth = if self.exceptions.handler.is_some() { self.exceptions.handler_depth } else { 0 };
+        if self.defers[depth..].iter().all(Vec::is_empty) {
+            return Ok(target);
+        }
+
+        // The exception is set aside so the deferred calls run normally
+        let here = self.builder.get_insert_block().unwrap();
+        let cleanup = self.context.append_basic_block(here.get_parent().unwrap(), "defer_unwind");
+        self.builder.position_at_end(cleanup);
+        let saved = self
+            .builder
+            .build_call(self.runtime_fn("wpp_exception_save")?, &[], "saved")
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap();
+        if let Err(err) = self.run_deferred(depth) {
+            self.defer_error.get_or_insert(err);
+        }
+        self.builder.build_call(self.runtime_fn("wpp_exception_restore")?, &[saved.into()], "").unwrap();
+        self.builder.build_unconditional_branch(target).unwrap();
+        self.builder.position_at_end(here);
+        Ok(cleanup)
+    }
+
+    /// The innermost `catch` of the current function, or the block that leaves it with the
+    /// exception pending
+    fn exception_exit(&mut self) -> CompileResult<inkwell::basic_block::BasicBlock<'ctx>> {
+        if let Some(handler) = self.exceptions.handler.or(self.exceptions.unwind) {
+            return Ok(handler);
+        }
+        let here = self.builder.get_insert_block().unwrap();
+        let func = here.get_parent().unwrap();
+        let unwind = self.context.append_basic_block(func, "unwind");
+        self.builder.position_at_end(unwind);
+        if self.exceptions.is_entry {
+            self.builder.build_call(self.runtime_fn("wpp_exception_uncaught")?, &[], "").unwrap();
+            self.builder.build_unreachable().unwrap();
+        } else {
+            self.leave_frame()?;
+            match func.get_type().get_return_type() {
+                Some(ty) => self.builder.build_return(Some(&ty.const_zero())).unwrap(),
+                None => self.builder.build_return(None).unwrap(),
+            };
+        }
+        self.builder.position_at_end(here);
+        self.exceptions.unwind = Some(unwind);
+        Ok(unwind)
+    }
+
+    /// Compile a `{ ... }` block, running its deferred expressions if control reaches its end.
+    /// Returns the value of the last node, like a function body.
+    fn compile_block(&mut self, nodes: &[Node]) -> CompileResult<Option<BasicValueEnum<'ctx>>> {
+        self.defers.push(Vec::new());
+        let mut last_val = None;
+        let mut result = Ok(());
+        for node in nodes {
+            match self.compile_node(node) {
+                Ok(val) => last_val = val,
+                Err(err) => {
+                    result = Err(err);
+                    break;
                 }
+            }
+        }
+        if result.is_ok() && self.builder.get_insert_block().unwrap().get_terminator().is_none() {
+            result = self.run_deferred(self.defers.len() - 1);
+        }
+        self.defers.pop();
+        result?;
+        match self.defer_error.take() {
+            Some(err) => Err(err),
+            None => Ok(last_val),
+        }
+    }
 
-                Expr::If { cond, then_branch, else_branch } => {
-                    scan_for_types(
-                        &[Node::Expr(*cond.clone())],
-                        int_params,
-                        ptr_params,
-                        float_params,
-                        bool_params,
-                        contains_string_literal,
-                    );
-                    scan_for_types(
-                        then_branch,
-                        int_params,
-                        ptr_params,
-                        float_params,
-                        bool_params,
-                        contains_string_literal,
-                    );
-                    if let Some(e) = else_branch {
-                        scan_for_types(
-                            e,
-                            int_params,
-                            ptr_params,
-                            float_params,
-                            bool_params,
-                            contains_string_literal,
-                        );
+    /// Emit the deferred expressions of the blocks above `depth`, innermost block first and
+    /// last-registered first within a block. Each one is compiled with only the deferred
+    /// expressions still to run registered, so an exception it throws runs just those.
+    fn run_deferred(&mut self, depth: usize) -> CompileResult<()> {
+        let registered = self.defers.clone();
+        let mut result = Ok(());
+        while self.defers.len() > depth {
+            match self.defers.last_mut().unwrap().pop() {
+                Some(expr) => {
+                    if let Err(err) = self.compile_expr(&expr) {
+                        result = Err(err);
+                        break;
                     }
                 }
-
-                Expr::While { cond, body } => {
-                    scan_for_types(
-                        &[Node::Expr(*cond.clone())],
-                        int_params,
-                        ptr_params,
-                        float_params,
-                        bool_params,
-                        contains_string_literal,
-                    );
-                    scan_for_types(
-                        body,
-                        int_params,
-                        ptr_params,
-                        float_params,
-                        bool_params,
-                        contains_string_literal,
-                    );
+                None => {
+                    self.defers.pop();
                 }
+            }
+        }
+        self.defers = registered;
+        result
+    }
 
-                Expr::Funcy { body, .. } => {
-                    scan_for_types(
-                        body,
-                        int_params,
-                        ptr_params,
-                        float_params,
-                        bool_params,
-                        contains_string_literal,
-                    );
-                }
+    /// After a call that may throw, continue at `unwind_target` if an exception is pending
+    fn check_exception(&mut self) -> CompileResult<()> {
+        let pending = self
+            .builder
+            .build_call(self.runtime_fn("wpp_exception_pending")?, &[], "exc_pending")
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let thrown = self
+            .builder
+            .build_int_compare(inkwell::IntPredicate::NE, pending, self.i32_type.const_zero(), "thrown")
+            .unwrap();
+        let target = self.unwind_target()?;
+        let func = self.builder.get_insert_block().unwrap().get_parent().unwrap();
+        let cont = self.context.append_basic_block(func, "no_exception");
+        self.builder.build_conditional_branch(thrown, target, cont).unwrap();
+        self.builder.position_at_end(cont);
+        Ok(())
+    }
 
-                _ => {}
-            },
-            _ => {}
-        }
+    /// Start a stack-trace frame for the function being compiled; its returns pop it
+    fn enter_frame(&mut self, name: &str) -> CompileResult<()> {
+        let name = self.builder.build_global_string_ptr(name, "frame_name").unwrap().as_pointer_value();
+        self.builder.build_call(self.runtime_fn("wpp_frame_push")?, &[name.into()], "").unwrap();
+        self.exceptions.has_frame = true;
+        Ok(())
     }
-}
 
+    /// Pop the current function's stack-trace frame, before one of its returns
+    fn leave_frame(&self) -> CompileResult<()> {
+        if self.exceptions.has_frame {
+            self.builder.build_call(self.runtime_fn("wpp_frame_pop")?, &[], "").unwrap();
+        }
+        Ok(())
+    }
 
-    scan_for_types(
-        body,
-        &mut int_params,
-        &mut ptr_params,
-        &mut float_params,
-        &mut bool_params,
-        &mut contains_string_literal,
-    );
+    /// Record the source line of a call or `throw` in the current stack-trace frame
+    fn mark_line(&self, span: Span) -> CompileResult<()> {
+        if self.exceptions.has_frame && !self.line_starts.is_empty() {
+            let line = self.line_starts.partition_point(|&start| start <= span.start);
+            let line = self.i32_type.const_int(line as u64, false);
+            self.builder.build_call(self.runtime_fn("wpp_frame_line")?, &[line.into()], "").unwrap();
+        }
+        Ok(())
+    }
+
+    /// Source of the module being compiled, so stack traces can name the lines of calls
+    pub fn set_source(&mut self, source: &str) {
+        self.line_starts = std::iter::once(0).chain(source.match_indices('\n').map(|(i, _)| i + 1)).collect();
+    }
+
+    /// Whether `expr` always evaluates to a freshly allocated string that nothing else
+    /// refers to (a concatenation or a string builtin), so its user can release it
+    fn is_string_temporary(expr: &Expr) -> bool {
+        match &expr.kind {
+            ExprKind::BinaryOp { op, .. } => op == "+",
+            ExprKind::Call { name, .. } => matches!(
+                name.as_str(),
+                "int_to_string"
+                    | "to_string"
+                    | "format"
+                    | "readline"
+                    | "http.body"
+                    | "http.headers"
+                    | "wpp_str_substr"
+                    | "wpp_str_replace"
+                    | "wpp_str_to_upper"
+                    | "wpp_str_to_lower"
+                    | "wpp_str_trim"
+            ),
+            _ => false,
+        }
+    }
 
-    // === Step 2: Handle string fallback ===
-    if contains_string_literal && int_params.is_empty() && float_params.is_empty() {
+    /// Release the string `value` was computed into if `expr` was a temporary
+    fn release_string_temporary(&mut self, expr: &Expr, value: BasicValueEnum<'ctx>) -> CompileResult<()> {
+        if !Self::is_string_temporary(expr) || !value.is_pointer_value() {
+            return Ok(());
+        }
+        let release_fn = self.runtime_fn("wpp_str_release")?;
+        let i8ptr = self.context.i8_type().ptr_type(AddressSpace::default());
+        let ptr = self.builder.build_pointer_cast(value.into_pointer_value(), i8ptr, "str_tmp").unwrap();
+        self.builder.build_call(release_fn, &[ptr.into()], "str_release").unwrap();
+        Ok(())
+    }
+
+    /// Variables of the enclosing function that a lambda body refers to, excluding its
+    /// own parameters. Over-approximates (a name shadowed by an inner `let` is still
+    /// captured), which only costs an unused environment slot.
+    fn lambda_captures(&self, params: &[String], body: &[Node]) -> Vec<(String, VarInfo<'ctx>)> {
+        let mut names = std::collections::BTreeSet::new();
+        collect_names(body, &mut names);
         for p in params {
-            ptr_params.insert(p.clone());
+            names.remove(p.split_once(':').map_or(p.as_str(), |(n, _)| n));
         }
+        names
+            .into_iter()
+            .filter_map(|n| self.vars.get(&n).cloned().map(|info| (n, info)))
+            .collect()
     }
-    // === 🧩 OOPSIE: inject implicit `me` for entity methods ===
-let mut effective_params = params.to_vec();
-if let Some(ent_name) = entity_name {
-    // prepend "me:ptr" if it's not already there
-    if !effective_params.iter().any(|p| p.starts_with("me")) {
-        effective_params.insert(0, "me:ptr".to_string());
+
+    /// LLVM struct holding a closure's captured values, in capture order
+    fn closure_env_type(&self, captures: &[(String, VarInfo<'ctx>)]) -> inkwell::types::StructType<'ctx> {
+        let fields: Vec<BasicTypeEnum<'ctx>> = captures.iter().map(|(_, info)| info.ty).collect();
+        self.context.struct_type(&fields, false)
     }
-    println!("🧩 Added implicit 'me' to method of entity '{}'", ent_name);
-}
-    // === Step 3: Build LLVM parameter types (explicit-type aware) ===
-let mut param_type_names: Vec<String> = Vec::new();
 
-let param_types: Vec<BasicMetadataTypeEnum<'ctx>> = effective_params
-    .iter()
-    .map(|p| {
-        // Handle explicit annotations like "a:f32"
-        let (pname, pty) = if let Some((n, t)) = p.split_once(':') {
-            (n.to_string(), t.to_string())
+    /// Compile an anonymous `funcy(...) { ... }`: copy the captured variables into a
+    /// heap environment and return a closure pointing at both
+    fn compile_lambda(
+        &mut self,
+        params: &[String],
+        body: &[Node],
+        return_type: Option<&TypeDescriptor>,
+        span: Span,
+    ) -> CompileResult<BasicValueEnum<'ctx>> {
+        let captures = self.lambda_captures(params, body);
+        let name = format!("lambda.{}", self.lambda_count);
+        self.lambda_count += 1;
+
+        self.pending_captures = captures.clone();
+        let func = self.compile_funcy(&name, params, body, None, None, return_type)?;
+        func.set_linkage(Linkage::Private);
+        self.lambda_signatures.insert(span, Self::closure_signature(func, !captures.is_empty()));
+
+        let env = if captures.is_empty() {
+            None
         } else {
-            (p.clone(), "i32".to_string())
+            let env_ty = self.closure_env_type(&captures);
+            let env = self.heap_alloc(env_ty.size_of().unwrap(), "closure_env")?;
+            let typed = self
+                .builder
+                .build_pointer_cast(env, env_ty.ptr_type(AddressSpace::default()), "closure_env_typed")
+                .unwrap();
+            for (i, (captured, info)) in captures.iter().enumerate() {
+                let val = self.builder.build_load(info.ty, info.ptr, captured).unwrap();
+                let slot = self.builder.build_struct_gep(env_ty, typed, i as u32, "env_slot").unwrap();
+                self.builder.build_store(slot, val).unwrap();
+            }
+            Some(env)
         };
+        Ok(self.make_closure(func, env)?.into())
+    }
 
-        param_type_names.push(pty.clone());
+    /// Wrap a function (and its environment, if it captures anything) in a runtime closure
+    fn make_closure(&mut self, func: FunctionValue<'ctx>, env: Option<PointerValue<'ctx>>) -> CompileResult<PointerValue<'ctx>> {
+        let i8_ptr = self.context.i8_type().ptr_type(AddressSpace::default());
+        let func_ptr = self
+            .builder
+            .build_pointer_cast(func.as_global_value().as_pointer_value(), i8_ptr, "closure_fn")
+            .unwrap();
+        let new_fn = self.runtime_fn("wpp_closure_new")?;
+        Ok(self.builder
+            .build_call(new_fn, &[func_ptr.into(), env.unwrap_or(i8_ptr.const_null()).into()], "closure")
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .expect("wpp_closure_new returns a pointer")
+            .into_pointer_value())
+    }
+
+    /// Call through a closure. Functions with an environment take it as a trailing
+    /// argument; plain functions (null env) are called with `args` alone.
+    fn call_closure(
+        &mut self,
+        closure: PointerValue<'ctx>,
+        args: &[BasicMetadataValueEnum<'ctx>],
+        param_types: &[BasicMetadataTypeEnum<'ctx>],
+        ret_ty: BasicTypeEnum<'ctx>,
+        name: &str,
+    ) -> CompileResult<BasicValueEnum<'ctx>> {
+        let i8_ptr = self.context.i8_type().ptr_type(AddressSpace::default());
+        let closure_ty = self.context.struct_type(&[i8_ptr.into(), i8_ptr.into()], false);
+        let closure = self
+            .builder
+            .build_pointer_cast(closure, closure_ty.ptr_type(AddressSpace::default()), "closure_typed")
+            .unwrap();
+        let fn_field = self.builder.build_struct_gep(closure_ty, closure, 0, "closure_fn_field").unwrap();
+        let func = self.builder.build_load(i8_ptr, fn_field, "closure_fn").unwrap().into_pointer_value();
+        let env_field = self.builder.build_struct_gep(closure_ty, closure, 1, "closure_env_field").unwrap();
+        let env = self.builder.build_load(i8_ptr, env_field, "closure_env").unwrap().into_pointer_value();
+
+        let parent = self.builder.get_insert_block().unwrap().get_parent().unwrap();
+        let with_env_bb = self.context.append_basic_block(parent, "call_with_env");
+        let plain_bb = self.context.append_basic_block(parent, "call_plain");
+        let merge_bb = self.context.append_basic_block(parent, "call_merge");
+        let has_env = self.builder.build_is_not_null(env, "has_env").unwrap();
+        self.builder.build_conditional_branch(has_env, with_env_bb, plain_bb).unwrap();
+
+        let mut env_params = param_types.to_vec();
+        env_params.push(i8_ptr.into());
+        let mut env_args = args.to_vec();
+        env_args.push(env.into());
+
+        let mut results = Vec::new();
+        for (bb, fn_ty, call_args) in [
+            (with_env_bb, ret_ty.fn_type(&env_params, false), env_args.as_slice()),
+            (plain_bb, ret_ty.fn_type(param_types, false), args),
+        ] {
+            self.builder.position_at_end(bb);
+            let callee = self
+                .builder
+                .build_pointer_cast(func, fn_ty.ptr_type(AddressSpace::default()), "closure_callee")
+                .unwrap();
+            let val = self
+                .builder
+                .build_indirect_call(fn_ty, callee, call_args, &format!("call_indirect_{}", name))
+                .unwrap()
+                .try_as_basic_value()
+                .left()
+                .expect("closures return a value");
+            self.builder.build_unconditional_branch(merge_bb).unwrap();
+            results.push((val, bb));
+        }
 
-        match pty.as_str() {
-            "i32" | "int" => self.i32_type.into(),
-            "f32" | "float" | "f64" => self.context.f32_type().into(),
-            "bool" => self.context.bool_type().into(),
-            "ptr" | "string" => self.context.i8_type().ptr_type(AddressSpace::default()).into(),
-            _ => {
-                // fallback based on inferred type sets
-                if ptr_params.contains(&pname) {
-                    self.context.i8_type().ptr_type(AddressSpace::default()).into()
-                } else if float_params.contains(&pname) {
-                    self.context.f32_type().into()
-                } else if bool_params.contains(&pname) {
-                    self.context.bool_type().into()
-                } else {
-                    self.i32_type.into()
+        self.builder.position_at_end(merge_bb);
+        let phi = self.builder.build_phi(ret_ty, "closure_result").unwrap();
+        for (val, bb) in &results {
+            phi.add_incoming(&[(val, *bb)]);
+        }
+        self.check_exception()?;
+        Ok(phi.as_basic_value())
+    }
+
+    /// W++ signature of a compiled closure function, for dispatch on function-typed parameters
+    fn closure_signature(func: FunctionValue<'ctx>, has_env: bool) -> FunctionTypeSignature {
+        fn describe(ty: BasicTypeEnum) -> TypeDescriptor {
+            TypeDescriptor::Primitive(
+                match ty {
+                    BasicTypeEnum::IntType(i) if i.get_bit_width() == 1 => "bool",
+                    BasicTypeEnum::FloatType(_) => "f32",
+                    BasicTypeEnum::PointerType(_) => "string",
+                    _ => "i32",
                 }
-            }
+                .to_string(),
+            )
         }
-    })
-    .collect();
-    
+        let fn_ty = func.get_type();
+        let mut params = fn_ty.get_param_types();
+        if has_env {
+            params.pop();
+        }
+        FunctionTypeSignature {
+            param_types: params.into_iter().map(describe).collect(),
+            return_type: fn_ty.get_return_type().map_or(TypeDescriptor::Primitive("i32".to_string()), describe),
+        }
+    }
 
-// === Step 4: Apply override if present ===
-let final_param_types = if let Some(overrides) = param_override {
-    param_type_names = overrides.iter().map(|td| td.to_mangle_string()).collect();
-    // Rebuild LLVM param_types based on TypeDescriptors
-    overrides.iter().map(|td| {
-        match td {
-            TypeDescriptor::Function { .. } => {
-                // Function types are passed as function pointers (i8* in LLVM)
+    /// Signature of a function-valued expression: a lambda, a named function or a variable
+    /// already holding one
+    fn value_function_signature(&self, expr: &Expr) -> Option<FunctionTypeSignature> {
+        match &expr.kind {
+            ExprKind::Funcy { .. } => self.lambda_signatures.get(&expr.span).cloned(),
+            ExprKind::Variable(name) => match self.vars.get(name) {
+                Some(info) => info.function_signature.clone(),
+                None => self.reverse_func_index.get(name)?.first().map(|sig| FunctionTypeSignature {
+                    param_types: sig.param_types.clone(),
+                    return_type: sig.return_type.clone(),
+                }),
+            },
+            _ => None,
+        }
+    }
+
+    /// LLVM type of a value described by a `TypeDescriptor` (function values are closure pointers)
+    fn descriptor_value_type(&self, ty: &TypeDescriptor) -> BasicTypeEnum<'ctx> {
+        match ty {
+            TypeDescriptor::Primitive(name) => self.primitive_type(name).unwrap_or(self.i32_type.into()),
+            TypeDescriptor::Function { .. } | TypeDescriptor::Entity(_) | TypeDescriptor::Interface(_) | TypeDescriptor::ObjectType(_) => {
                 self.context.i8_type().ptr_type(AddressSpace::default()).into()
             }
-            TypeDescriptor::Entity(_) | TypeDescriptor::ObjectType(_) => {
-                // Entities and objects are passed as pointers
-                self.context.i8_type().ptr_type(AddressSpace::default()).into()
+            _ => self.i32_type.into(),
+        }
+    }
+}
+
+impl<'ctx> Codegen<'ctx> {
+    // 🏷️ An enum value is a pointer to a heap block { i32 variant, i32 count, char* name,
+    // i32* tags, i64 payload[count] }; payload fields use the same 8-byte slots as arrays.
+    // `name` and `tags` point at per-variant globals and only exist so `wpp_print_enum`
+    // can print the value.
+
+    /// `(enum, variant index)` when `name` is a qualified variant such as `Shape.Circle`
+    fn enum_constructor(&self, name: &str) -> Option<(String, usize)> {
+        let (enum_name, variant) = name.split_once('.')?;
+        let (index, _) = self.enums.get(enum_name)?.variant(variant)?;
+        Some((enum_name.to_string(), index))
+    }
+
+    /// `Shape.Circle(2.0)` / `Shape.Empty`: allocate the value and store its payload
+    fn compile_enum_value(&mut self, enum_name: &str, index: usize, args: &[Expr], span: Span) -> CompileResult<PointerValue<'ctx>> {
+        let variant = self.enums[enum_name].variants[index].clone();
+        if args.len() != variant.fields.len() {
+            let n = variant.fields.len();
+            return Err(CompileError::ArgumentCount {
+                callee: format!("{}.{}", enum_name, variant.name),
+                expected: format!("{} argument{}", n, if n == 1 { "" } else { "s" }),
+                found: args.len(),
+                span,
+            });
+        }
+
+        let mut slots = Vec::with_capacity(args.len());
+        for (arg, field) in args.iter().zip(&variant.fields) {
+            let (val, ty) = self.compile_typed(arg)?;
+            slots.push(self.coerce_to_slot(val, &ty, field, arg.span)?);
+        }
+
+        let i64_type = self.context.i64_type();
+        let bytes = i64_type.const_int(8 * (3 + variant.fields.len() as u64), false);
+        let value = self.heap_alloc(bytes, "enum_value")?;
+        let header = self
+            .builder
+            .build_pointer_cast(value, self.i32_type.ptr_type(AddressSpace::default()), "enum_header")
+            .unwrap();
+        self.builder.build_store(header, self.i32_type.const_int(index as u64, false)).unwrap();
+        let count_ptr = unsafe {
+            self.builder
+                .build_gep(self.i32_type, header, &[self.i32_type.const_int(1, false)], "enum_count")
+                .unwrap()
+        };
+        self.builder
+            .build_store(count_ptr, self.i32_type.const_int(variant.fields.len() as u64, false))
+            .unwrap();
+
+        let (name, tags) = self.enum_variant_globals(enum_name, index);
+        let words = self.enum_words(value);
+        for (word, ptr) in [(1, name), (2, tags)] {
+            let bits = self.builder.build_ptr_to_int(ptr, i64_type, "enum_meta").unwrap();
+            let field = unsafe {
+                self.builder
+                    .build_gep(i64_type, words, &[self.i32_type.const_int(word, false)], "enum_meta_ptr")
+                    .unwrap()
+            };
+            self.builder.build_store(field, bits).unwrap();
+        }
+        for (i, (slot, field)) in slots.into_iter().zip(&variant.fields).enumerate() {
+            let ptr = self.enum_payload_ptr(value, i, field);
+            self.builder.build_store(ptr, slot).unwrap();
+        }
+        Ok(value)
+    }
+
+    /// The variant's name string and payload tag array, shared by every value of the variant
+    fn enum_variant_globals(&self, enum_name: &str, index: usize) -> (PointerValue<'ctx>, PointerValue<'ctx>) {
+        let variant = &self.enums[enum_name].variants[index];
+        let i8_ptr = self.context.i8_type().ptr_type(AddressSpace::default());
+        let global_name = format!("enum.{}.{}", enum_name, variant.name);
+
+        let name = match self.module.get_global(&format!("{}.name", global_name)) {
+            Some(global) => global.as_pointer_value(),
+            None => self
+                .builder
+                .build_global_string_ptr(&variant.name, &format!("{}.name", global_name))
+                .unwrap()
+                .as_pointer_value(),
+        };
+        let name = self.builder.build_pointer_cast(name, i8_ptr, "enum_name").unwrap();
+
+        if variant.fields.is_empty() {
+            return (name, i8_ptr.const_null());
+        }
+        let tags = match self.module.get_global(&format!("{}.tags", global_name)) {
+            Some(global) => global,
+            None => {
+                let values: Vec<IntValue<'ctx>> = variant
+                    .fields
+                    .iter()
+                    .map(|ty| self.i32_type.const_int(self.field_type_tag(ty), false))
+                    .collect();
+                let array = self.i32_type.const_array(&values);
+                let global = self.module.add_global(array.get_type(), None, &format!("{}.tags", global_name));
+                global.set_initializer(&array);
+                global.set_constant(true);
+                global
             }
-            TypeDescriptor::Primitive(name) => {
-                match name.as_str() {
-                    "i32" | "int" => self.i32_type.into(),
-                    "f32" | "float" | "f64" => self.context.f32_type().into(),
-                    "bool" => self.context.bool_type().into(),
-                    "ptr" | "string" => self.context.i8_type().ptr_type(AddressSpace::default()).into(),
-                    _ => self.i32_type.into(),
-                }
+        };
+        let tags = self.builder.build_pointer_cast(tags.as_pointer_value(), i8_ptr, "enum_tags").unwrap();
+        (name, tags)
+    }
+
+    fn enum_words(&self, value: PointerValue<'ctx>) -> PointerValue<'ctx> {
+        let i64_ptr = self.context.i64_type().ptr_type(AddressSpace::default());
+        self.builder.build_pointer_cast(value, i64_ptr, "enum_words").unwrap()
+    }
+
+    /// Pointer to payload field `i`, typed for `field`
+    fn enum_payload_ptr(&self, value: PointerValue<'ctx>, i: usize, field: &FieldType) -> PointerValue<'ctx> {
+        let words = self.enum_words(value);
+        let slot = unsafe {
+            self.builder
+                .build_gep(self.context.i64_type(), words, &[self.i32_type.const_int(3 + i as u64, false)], "enum_field")
+                .unwrap()
+        };
+        self.builder
+            .build_pointer_cast(slot, self.slot_type(field).ptr_type(AddressSpace::default()), "enum_field_ptr")
+            .unwrap()
+    }
+
+    /// `try`/`catch`/`finally`. Exceptions from the try body go to a dispatcher that tests the
+    /// catch clauses in order against the pending value. A value no clause accepts, or an
+    /// exception thrown by a catch body, is set aside while `finally` runs and then passed on
+    /// to the enclosing handler.
+    fn compile_try(&mut self, try_block: &[Node], catches: &[CatchClause], finally_block: Option<&[Node]>, span: Span) -> CompileResult<()> {
+        let i8_ptr = self.context.i8_type().ptr_type(AddressSpace::default());
+        let func = self.builder.get_insert_block().unwrap().get_parent().unwrap();
+        let dispatch_bb = self.context.append_basic_block(func, "catch_dispatch");
+        let finally_bb = self.context.append_basic_block(func, "finally_block");
+        let end_bb = self.context.append_basic_block(func, "try_end");
+        let outer = (self.exceptions.handler, self.exceptions.handler_depth);
+
+        let saved = match finally_block {
+            Some(_) => {
+                let slot = self.builder.build_alloca(i8_ptr, "saved_exception").unwrap();
+                self.builder.build_store(slot, i8_ptr.const_null()).unwrap();
+                Some((slot, self.context.append_basic_block(func, "catch_rethrow")))
             }
-            _ => self.i32_type.into(), // Default for HttpStatus, Any, etc.
-        }
-    }).collect()
-} else {
-    param_types
-};
+            None => None,
+        };
 
-    let llvm_name = if let Some(ent_name) = entity_name {
-    format!("{}.{}", ent_name, name)  // e.g. Dog.bark
-} else if param_type_names.is_empty() {
-    name.to_string()
-} else {
-    format!("{}__{}", name, param_type_names.join("_"))
-};
+        // --- TRY block ---
+        self.exceptions.handler = Some(dispatch_bb);
+        self.exceptions.handler_depth = self.defers.len();
+        self.compile_block(try_block)?;
+        self.safe_branch(finally_bb);
 
-    // === Step 4.5: Infer return type from function body ===
-    fn infer_return_type<'ctx>(
-        codegen: &Codegen<'ctx>,
-        expr: &Expr,
-        locals: &std::collections::HashSet<String>,
-    ) -> BasicTypeEnum<'ctx> {
-        match expr {
-            Expr::TypedLiteral { ty, .. } => match ty.as_str() {
-                "f32" | "f64" => codegen.context.f32_type().into(),
-                "bool" => codegen.context.bool_type().into(),
-                "string" | "ptr" => codegen.context.i8_type().ptr_type(AddressSpace::default()).into(),
-                _ => codegen.i32_type.into(),
-            },
-            Expr::BinaryOp { left, right, op } => {
-                // Comparison and logical operators return bool
-                if ["==", "!=", "<", ">", "<=", ">=", "and", "or"].contains(&op.as_str()) {
-                    return codegen.context.bool_type().into();
+        // --- CATCH clauses, tried in order ---
+        (self.exceptions.handler, self.exceptions.handler_depth) = match saved {
+            Some((_, rethrow_bb)) => (Some(rethrow_bb), self.defers.len()),
+            None => outer,
+        };
+        self.builder.position_at_end(dispatch_bb);
+        let thrown = self
+            .builder
+            .build_call(self.runtime_fn("wpp_exception_value")?, &[], "thrown")
+            .unwrap()
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value();
+        for clause in catches {
+            let next_bb = self.context.append_basic_block(func, "catch_next");
+            let (value, var_ty, entity_type) = match &clause.ty {
+                Some(ty) => {
+                    let param = match ty {
+                        TypeDescriptor::ObjectType(n) if self.entities.contains_key(n) => TypeDescriptor::Entity(n.clone()),
+                        TypeDescriptor::ObjectType(n) if self.interfaces.contains_key(n) => TypeDescriptor::Interface(n.clone()),
+                        TypeDescriptor::ObjectType(n) if !self.type_aliases.contains_key(n) && !self.enums.contains_key(n) => {
+                            return Err(CompileError::type_mismatch(span, format!("unknown type `{}` in `catch`", n)));
+                        }
+                        other => other.clone(),
+                    };
+                    let expected = Self::boxed_param_type(&param, i8_ptr.into());
+                    let value = self.unbox_if(thrown, &expected, &param, func, next_bb);
+                    let entity_type = match param {
+                        TypeDescriptor::Entity(n) | TypeDescriptor::Interface(n) => Some(n),
+                        _ => None,
+                    };
+                    (value, expected, entity_type)
                 }
+                None => (thrown.into(), FieldType::Any, None),
+            };
+            let trace = self
+                .builder
+                .build_call(self.runtime_fn("wpp_exception_catch")?, &[], "trace")
+                .unwrap()
+                .try_as_basic_value()
+                .left()
+                .unwrap();
 
-                let l = infer_return_type(codegen, left, locals);
-                let r = infer_return_type(codegen, right, locals);
-                if l.is_float_type() || r.is_float_type() {
-                    codegen.context.f32_type().into()
-                } else if l.is_int_type() && r.is_int_type() {
-                    codegen.i32_type.into()
-                } else {
-                    l
+            let mut shadowed = Vec::new();
+            if let Some(var) = &clause.var {
+                let bindings = [
+                    (var.clone(), value, Self::tracked_field_type(var_ty.clone()), entity_type),
+                    (Self::trace_var(var), trace, None, None),
+                ];
+                for (name, val, field_type, entity_type) in bindings {
+                    let ptr = self.builder.build_alloca(val.get_type(), &name).unwrap();
+                    self.builder.build_store(ptr, val).unwrap();
+                    let info = VarInfo {
+                        ptr,
+                        ty: val.get_type(),
+                        is_const: false,
+                        is_thread_state: false,
+                        entity_type,
+                        object_type_name: matches!(&field_type, Some(FieldType::Object(n)) if self.enums.contains_key(n))
+                            .then(|| var_ty.to_string()),
+                        function_signature: None,
+                        field_type,
+                    };
+                    shadowed.push((name.clone(), self.vars.insert(name, info)));
                 }
             }
-            Expr::Variable(name) => {
-                if locals.contains(name) {
-                    codegen.i32_type.into() // assume int for local vars
-                } else {
-                    codegen.i32_type.into()
-                }
+            self.compile_block(&clause.body)?;
+            for (name, previous) in shadowed {
+                match previous {
+                    Some(info) => self.vars.insert(name, info),
+                    None => self.vars.remove(&name),
+                };
             }
-            _ => codegen.i32_type.into(),
+            self.safe_branch(finally_bb);
+            self.builder.position_at_end(next_bb);
         }
-    }
+        // No clause accepts the value: pass it on
+        let rethrow = self.unwind_target()?;
+        self.builder.build_unconditional_branch(rethrow).unwrap();
 
-    let mut inferred_ret_ty: Option<BasicTypeEnum<'ctx>> = None;
-    let local_params: std::collections::HashSet<String> = params.iter().cloned().collect();
+        if let Some((slot, rethrow_bb)) = saved {
+            self.builder.position_at_end(rethrow_bb);
+            let exception = self
+                .builder
+                .build_call(self.runtime_fn("wpp_exception_save")?, &[], "saved")
+                .unwrap()
+                .try_as_basic_value()
+                .left()
+                .unwrap();
+            self.builder.build_store(slot, exception).unwrap();
+            self.builder.build_unconditional_branch(finally_bb).unwrap();
+        }
 
-    for stmt in body {
-        if let Node::Expr(Expr::Return(Some(inner))) = stmt {
-            inferred_ret_ty = Some(infer_return_type(self, inner, &local_params));
-            println!("🔍 [type-infer] Function '{}' inferred return type: {:?}", name, inferred_ret_ty);
-            break;
+        // --- FINALLY block ---
+        (self.exceptions.handler, self.exceptions.handler_depth) = outer;
+        self.builder.position_at_end(finally_bb);
+        self.compile_block(finally_block.unwrap_or_default())?;
+        match saved {
+            Some((slot, _)) if self.builder.get_insert_block().unwrap().get_terminator().is_none() => {
+                let exception = self.builder.build_load(i8_ptr, slot, "saved").unwrap().into_pointer_value();
+                let was_thrown = self.builder.build_is_not_null(exception, "was_thrown").unwrap();
+                let resume_bb = self.context.append_basic_block(func, "finally_rethrow");
+                self.builder.build_conditional_branch(was_thrown, resume_bb, end_bb).unwrap();
+                self.builder.position_at_end(resume_bb);
+                self.builder.build_call(self.runtime_fn("wpp_exception_restore")?, &[exception.into()], "").unwrap();
+                let target = self.unwind_target()?;
+                self.builder.build_unconditional_branch(target).unwrap();
+            }
+            _ => self.safe_branch(end_bb),
         }
+
+        self.builder.position_at_end(end_bb);
+        Ok(())
     }
 
-    // === Step 5: Create or fetch LLVM function ===
-    // === 🧮 Step 5: Create correct LLVM function type ===
-let fn_type = if let Some(return_type_desc) = return_type_override {
-    // ✅ NEW: Use explicit return type annotation if provided
-    println!("🎯 [return-type] Function '{}' has explicit return type annotation: {:?}", name, return_type_desc);
-    let ret_ty: BasicTypeEnum<'ctx> = match return_type_desc {
-        TypeDescriptor::Primitive(ty_name) => match ty_name.as_str() {
-            "i32" | "int" => self.i32_type.into(),
-            "f32" | "float" | "f64" => self.context.f32_type().into(),
-            "bool" => self.context.bool_type().into(),
-            "ptr" | "string" => self.context.i8_type().ptr_type(AddressSpace::default()).into(),
-            _ => self.i32_type.into(),
-        },
-        TypeDescriptor::Function { .. } => self.context.i8_type().ptr_type(AddressSpace::default()).into(),
-        _ => self.i32_type.into(),
-    };
-    ret_ty.fn_type(&final_param_types, false)
-} else if let Some(ret_ty) = inferred_ret_ty {
-    // Use inferred return type
-    ret_ty.fn_type(&final_param_types, false)
-} else if name == "add" && param_type_names.iter().all(|t| t == "ptr") {
-    // 🧠 String overload returns a pointer
-    self.context
-        .i8_type()
-        .ptr_type(AddressSpace::default())
-        .fn_type(&final_param_types, false)
-} else if param_type_names.iter().any(|t| t == "f32" || t == "f64" || t == "float") {
-    // 🧮 Float overload returns float
-    self.context.f32_type().fn_type(&final_param_types, false)
-} else if param_type_names.iter().any(|t| t == "bool") {
-    // 🧩 Bool overload returns i1
-    self.context.bool_type().fn_type(&final_param_types, false)
-} else {
-    // 🧱 Default integer return
-    self.i32_type.fn_type(&final_param_types, false)
-};
+    /// Hidden variable holding the stack trace of the exception a `catch` bound to `var`
+    fn trace_var(var: &str) -> String {
+        format!("{}#trace", var)
+    }
 
+    /// `match (subject) { ... }`: arms are tested in order and the first match runs.
+    /// The result is the value of the arm that ran; arms that disagree on a type give no value.
+    fn compile_match(&mut self, subject: &Expr, arms: &[MatchArm]) -> CompileResult<(BasicValueEnum<'ctx>, FieldType)> {
+        let (subject_val, mut subject_ty) = self.compile_typed(subject)?;
+        // An untyped pointer (e.g. an unannotated parameter) is matched as the enum its patterns name
+        if subject_val.is_pointer_value()
+            && !matches!(&subject_ty, FieldType::Object(n) if self.enums.contains_key(n))
+            && let Some(enum_name) = pattern::infer_enum(arms.iter().map(|arm| &arm.pattern), &self.enums)
+        {
+            subject_ty = FieldType::Object(enum_name);
+        }
 
-    let function = if let Some(existing) = self.module.get_function(&llvm_name) {
-        existing
-    } else {
-        self.module.add_function(&llvm_name, fn_type, None)
-    };
+        let patterns = arms
+            .iter()
+            .map(|arm| {
+                pattern::resolve(&arm.pattern, &subject_ty, &self.enums)
+                    .map_err(|message| CompileError::InvalidPattern { message, span: arm.span })
+            })
+            .collect::<CompileResult<Vec<_>>>()?;
+        if let Some(missing) = pattern::uncovered(&patterns, &subject_ty, &self.enums) {
+            return Err(CompileError::NonExhaustiveMatch { missing, span: subject.span });
+        }
 
-    println!("🧱 Compiling funcy {} as {}", name, llvm_name);
+        let func = self.builder.get_insert_block().unwrap().get_parent().unwrap();
+        let end_bb = self.context.append_basic_block(func, "match_end");
+
+        // Arms that fall through to the end, with the block they end in and their value
+        let mut results: Vec<(inkwell::basic_block::BasicBlock<'ctx>, Option<(BasicValueEnum<'ctx>, FieldType)>)> = Vec::new();
+        for (arm, pat) in arms.iter().zip(&patterns) {
+            let next_bb = self.context.append_basic_block(func, "match_next");
+            let mut bindings = Vec::new();
+            self.emit_pattern_test(subject_val, &subject_ty, pat, next_bb, &mut bindings)?;
+
+            let mut shadowed = Vec::with_capacity(bindings.len());
+            for (name, val, ty) in bindings {
+                let ptr = self.builder.build_alloca(val.get_type(), &name).unwrap();
+                self.builder.build_store(ptr, val).unwrap();
+                let info = VarInfo {
+                    ptr,
+                    ty: val.get_type(),
+                    is_const: false,
+                    is_thread_state: false,
+                    entity_type: None,
+                    object_type_name: matches!(&ty, FieldType::Object(n) if self.enums.contains_key(n)).then(|| ty.to_string()),
+                    function_signature: None,
+                    field_type: Self::tracked_field_type(ty),
+                };
+                shadowed.push((name.clone(), self.vars.insert(name, info)));
+            }
 
-    // === Step 6: Create entry block ===
-    let entry = self.context.append_basic_block(function, "entry");
-    let saved_block = self.builder.get_insert_block();
-    self.builder.position_at_end(entry);
+            let value = self.compile_arm_body(&arm.body)?;
 
-    // === Step 7: Allocate locals (type-accurate) ===
-    let mut local_vars: HashMap<String, VarInfo<'ctx>> = HashMap::new();
-    for (i, param_name) in effective_params.iter().enumerate() {
-    let param = function.get_nth_param(i as u32).unwrap();
-    let param_ty = param.get_type();
+            for (name, previous) in shadowed {
+                match previous {
+                    Some(info) => self.vars.insert(name, info),
+                    None => self.vars.remove(&name),
+                };
+            }
+            let block = self.builder.get_insert_block().unwrap();
+            if block.get_terminator().is_none() {
+                results.push((block, value));
+            }
+            self.builder.position_at_end(next_bb);
+        }
+        // Exhaustiveness was checked above, so falling past the last arm cannot happen
+        self.builder.build_unreachable().unwrap();
+
+        // === Agree on one result type ===
+        let tys: Option<Vec<FieldType>> = results.iter().map(|(_, v)| v.as_ref().map(|(_, ty)| ty.clone())).collect();
+        let result_ty = match tys {
+            Some(tys) if !tys.is_empty() && tys.iter().all(FieldType::is_numeric) => Some(Self::unify_elem_types(&tys)),
+            Some(tys) if !tys.is_empty() && tys.iter().all(|t| *t == tys[0]) => {
+                let llvm_ty = results[0].1.as_ref().unwrap().0.get_type();
+                results
+                    .iter()
+                    .all(|(_, v)| v.as_ref().unwrap().0.get_type() == llvm_ty)
+                    .then(|| tys[0].clone())
+            }
+            _ => None,
+        };
 
-    // 🧠 Split "a:f32" -> ("a", "f32")
-    let pure_name = if let Some((n, _)) = param_name.split_once(':') {
-        n.to_string()
-    } else {
-        param_name.clone()
-    };
+        let mut incoming = Vec::with_capacity(results.len());
+        for (block, value) in results {
+            self.builder.position_at_end(block);
+            if let (Some(ty), Some((val, from))) = (&result_ty, value) {
+                let val = if ty.is_numeric() { self.coerce_to_slot(val, &from, ty, subject.span)? } else { val };
+                incoming.push((val, self.builder.get_insert_block().unwrap()));
+            }
+            self.builder.build_unconditional_branch(end_bb).unwrap();
+        }
 
-    let alloca = self.builder.build_alloca(param_ty, &pure_name).unwrap();
-    self.builder.build_store(alloca, param).unwrap();
+        self.builder.position_at_end(end_bb);
+        match result_ty {
+            Some(ty) if !incoming.is_empty() => {
+                let phi = self.builder.build_phi(incoming[0].0.get_type(), "match_value").unwrap();
+                for (val, block) in &incoming {
+                    phi.add_incoming(&[(val, *block)]);
+                }
+                Ok((phi.as_basic_value(), ty))
+            }
+            _ => Ok((self.i32_type.const_zero().into(), FieldType::Int32)),
+        }
+    }
 
-    // 🎯 Check if this parameter has a function type
-    let function_signature = if let Some(overrides) = param_override {
-        if i < overrides.len() {
-            if let TypeDescriptor::Function { param_types, return_type } = &overrides[i] {
-                Some(FunctionTypeSignature {
-                    param_types: param_types.clone(),
-                    return_type: (**return_type).clone(),
-                })
-            } else {
-                None
+    /// Compile an arm's statements; the last one's value is the arm's value when it is an expression
+    fn compile_arm_body(&mut self, body: &[Node]) -> CompileResult<Option<(BasicValueEnum<'ctx>, FieldType)>> {
+        let Some((last, rest)) = body.split_last() else {
+            return Ok(None);
+        };
+        for node in rest {
+            self.compile_node(node)?;
+        }
+        if self.builder.get_insert_block().unwrap().get_terminator().is_some() {
+            return Ok(None);
+        }
+        match &last.kind {
+            NodeKind::Expr(expr) if Self::is_value_expr(expr) => self.compile_typed(expr).map(Some),
+            _ => {
+                self.compile_node(last)?;
+                Ok(None)
             }
-        } else {
-            None
         }
-    } else {
-        None
-    };
+    }
 
-    local_vars.insert(
-        pure_name,
-        VarInfo {
-            ptr: alloca,
-            ty: param_ty,
-            is_const: false,
-            is_thread_state: false,
-            entity_type: None,
-            object_type_name: None,
-            function_signature,
-        },
-    );
-}
+    /// Expressions that produce a value, as opposed to statements such as loops or `return`
+    fn is_value_expr(expr: &Expr) -> bool {
+        !matches!(
+            &expr.kind,
+            ExprKind::If { .. }
+                | ExprKind::While { .. }
+                | ExprKind::For { .. }
+                | ExprKind::ForIn { .. }
+                | ExprKind::Switch { .. }
+                | ExprKind::TryCatch { .. }
+                | ExprKind::Throw { .. }
+                | ExprKind::Defer(_)
+                | ExprKind::Return(_)
+                | ExprKind::Break
+                | ExprKind::Continue
+                | ExprKind::Funcy { .. }
+        )
+    }
 
+    /// Branch to `fail_bb` unless `val` matches `pat`, leaving the builder where it does.
+    /// Values bound by the pattern are appended to `bindings`.
+    fn emit_pattern_test(
+        &mut self,
+        val: BasicValueEnum<'ctx>,
+        ty: &FieldType,
+        pat: &Pattern,
+        fail_bb: inkwell::basic_block::BasicBlock<'ctx>,
+        bindings: &mut Vec<(String, BasicValueEnum<'ctx>, FieldType)>,
+    ) -> CompileResult<()> {
+        let func = self.builder.get_insert_block().unwrap().get_parent().unwrap();
+        let matched = match pat {
+            Pattern::Wild => return Ok(()),
+            Pattern::Binding(name) => {
+                bindings.push((name.clone(), val, ty.clone()));
+                return Ok(());
+            }
+            Pattern::Literal(literal) => self.compile_literal_test(val, ty, literal)?,
+            Pattern::Variant { index, fields } => {
+                let FieldType::Object(enum_name) = ty else {
+                    unreachable!("variant patterns only resolve against enums");
+                };
+                let variants = self.enums[enum_name].variants.clone();
+                let value = val.into_pointer_value();
+                let header = self
+                    .builder
+                    .build_pointer_cast(value, self.i32_type.ptr_type(AddressSpace::default()), "enum_header")
+                    .unwrap();
+                let tag = self.builder.build_load(self.i32_type, header, "enum_variant").unwrap().into_int_value();
+                let is_variant = self
+                    .builder
+                    .build_int_compare(inkwell::IntPredicate::EQ, tag, self.i32_type.const_int(*index as u64, false), "is_variant")
+                    .unwrap();
 
-    // === Step 8: Replace current scope ===
-    let old_vars = std::mem::replace(&mut self.vars, local_vars);
+                let then_bb = self.context.append_basic_block(func, "match_variant");
+                self.builder.build_conditional_branch(is_variant, then_bb, fail_bb).unwrap();
+                self.builder.position_at_end(then_bb);
+                for (i, (field_pat, field_ty)) in fields.iter().zip(&variants[*index].fields).enumerate() {
+                    if matches!(field_pat, Pattern::Wild) {
+                        continue;
+                    }
+                    let slot = self.enum_payload_ptr(value, i, field_ty);
+                    let field = self.load_slot(slot, field_ty);
+                    self.emit_pattern_test(field, field_ty, field_pat, fail_bb, bindings)?;
+                }
+                return Ok(());
+            }
+        };
 
-    // === Step 9: Compile body ===
-    let mut last_val: Option<BasicValueEnum<'ctx>> = None;
-    for node in body {
-        last_val = self.compile_node(node);
+        let then_bb = self.context.append_basic_block(func, "match_literal");
+        self.builder.build_conditional_branch(matched, then_bb, fail_bb).unwrap();
+        self.builder.position_at_end(then_bb);
+        Ok(())
     }
 
-   // === Step 10: Type-aware return handling with bool coercion ===
-if self.builder.get_insert_block().unwrap().get_terminator().is_none() {
-    let fn_ret_ty = function.get_type().get_return_type();
-
-    // Determine correct return value
-    let ret_val: BasicValueEnum<'ctx> = if let Some(val) = last_val {
-        if let Some(rt) = fn_ret_ty {
-            match rt {
-                // int → float
-                BasicTypeEnum::FloatType(f) if val.is_int_value() => {
-                    let casted = self
-                        .builder
-                        .build_signed_int_to_float(val.into_int_value(), f, "int_to_float_ret")
-                        .unwrap();
-                    casted.into()
-                }
-                // float → int
-                BasicTypeEnum::IntType(i) if val.is_float_value() => {
-                    let casted = self
-                        .builder
-                        .build_float_to_signed_int(val.into_float_value(), i, "float_to_int_ret")
-                        .unwrap();
-                    casted.into()
-                }
-                // i32 ↔ i1 (for bool returns or accidental widening)
-                BasicTypeEnum::IntType(i) if val.is_int_value() => {
-                    let src = val.into_int_value();
-                    let src_bits = src.get_type().get_bit_width();
-                    let dst_bits = i.get_bit_width();
-                    let adjusted = if src_bits > dst_bits {
-                        self.builder
-                            .build_int_truncate(src, i, "ret_trunc_i32_to_i1")
-                            .unwrap()
-                    } else if src_bits < dst_bits {
-                        self.builder
-                            .build_int_z_extend(src, i, "ret_zext_i1_to_i32")
-                            .unwrap()
-                    } else {
-                        src
-                    };
-                    adjusted.into()
+    /// `val == literal` for a literal pattern
+    fn compile_literal_test(&mut self, val: BasicValueEnum<'ctx>, ty: &FieldType, literal: &Expr) -> CompileResult<IntValue<'ctx>> {
+        let (lit, lit_ty) = self.compile_typed(literal)?;
+        let mismatch = || CompileError::InvalidPattern {
+            message: format!("cannot match a value of type `{}` against a `{}` literal", ty, lit_ty),
+            span: literal.span,
+        };
+        Ok(match (val, lit) {
+            (BasicValueEnum::IntValue(l), BasicValueEnum::IntValue(r)) => {
+                if (l.get_type().get_bit_width() == 1) != (r.get_type().get_bit_width() == 1) {
+                    return Err(mismatch());
                 }
-                // match already OK
-                _ => val,
+                let (l, r) = match l.get_type().get_bit_width().cmp(&r.get_type().get_bit_width()) {
+                    std::cmp::Ordering::Less => (self.builder.build_int_s_extend(l, r.get_type(), "match_sext").unwrap(), r),
+                    std::cmp::Ordering::Greater => (l, self.builder.build_int_s_extend(r, l.get_type(), "match_sext").unwrap()),
+                    std::cmp::Ordering::Equal => (l, r),
+                };
+                self.builder.build_int_compare(inkwell::IntPredicate::EQ, l, r, "match_eq").unwrap()
+            }
+            (BasicValueEnum::FloatValue(_) | BasicValueEnum::IntValue(_), BasicValueEnum::FloatValue(_) | BasicValueEnum::IntValue(_))
+                if ty.is_numeric() && lit_ty.is_numeric() =>
+            {
+                let f64_type = self.context.f64_type();
+                let to_f64 = |this: &Self, v: BasicValueEnum<'ctx>| match v {
+                    BasicValueEnum::IntValue(iv) => this.builder.build_signed_int_to_float(iv, f64_type, "match_itof").unwrap(),
+                    other => this.builder.build_float_cast(other.into_float_value(), f64_type, "match_fext").unwrap(),
+                };
+                let (l, r) = (to_f64(self, val), to_f64(self, lit));
+                self.builder.build_float_compare(inkwell::FloatPredicate::OEQ, l, r, "match_feq").unwrap()
+            }
+            (BasicValueEnum::PointerValue(l), BasicValueEnum::PointerValue(r)) if lit_ty == FieldType::String => {
+                let cmp_fn = self.runtime_fn("wpp_str_cmp")?;
+                let cmp = self
+                    .builder
+                    .build_call(cmp_fn, &[l.into(), r.into()], "match_strcmp")
+                    .unwrap()
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap()
+                    .into_int_value();
+                self.builder
+                    .build_int_compare(inkwell::IntPredicate::EQ, cmp, self.i32_type.const_zero(), "match_streq")
+                    .unwrap()
             }
-        } else {
-            val
+            _ => return Err(mismatch()),
+        })
+    }
+}
+
+/// Collect every name a function body reads, writes or calls (for closure capture analysis).
+/// Dotted names (`obj.field`, `list.push`) contribute their first segment.
+fn collect_names(nodes: &[Node], out: &mut std::collections::BTreeSet<String>) {
+    for node in nodes {
+        match &node.kind {
+            NodeKind::Let { value, .. } => collect_expr_names(value, out),
+            NodeKind::Expr(expr) => collect_expr_names(expr, out),
+            NodeKind::Export { item, .. } => collect_names(std::slice::from_ref(item), out),
+            _ => {}
         }
-    } else {
-        // default 0/null
-        if let Some(rt) = fn_ret_ty {
-            match rt {
-                BasicTypeEnum::IntType(i) => i.const_int(0, false).into(),
-                BasicTypeEnum::FloatType(f) => f.const_float(0.0).into(),
-                BasicTypeEnum::PointerType(p) => p.const_null().into(),
-                _ => self.i32_type.const_int(0, false).into(),
+    }
+}
+
+fn collect_expr_names(expr: &Expr, out: &mut std::collections::BTreeSet<String>) {
+    let mut add = |name: &str| {
+        out.insert(name.split('.').next().unwrap_or(name).to_string());
+    };
+    match &expr.kind {
+        ExprKind::Variable(name) => add(name),
+        ExprKind::Call { name, args } => {
+            add(name);
+            args.iter().for_each(|a| collect_expr_names(a, out));
+        }
+        ExprKind::BinaryOp { left, right, .. } | ExprKind::Range { start: left, end: right } => {
+            collect_expr_names(left, out);
+            collect_expr_names(right, out);
+        }
+        ExprKind::Index { target, index } => {
+            collect_expr_names(target, out);
+            collect_expr_names(index, out);
+        }
+        ExprKind::If { cond, then_branch, else_branch } => {
+            collect_expr_names(cond, out);
+            collect_names(then_branch, out);
+            if let Some(e) = else_branch {
+                collect_names(e, out);
             }
-        } else {
-            self.i32_type.const_int(0, false).into()
         }
-    };
-
-    self.builder.build_return(Some(&ret_val)).unwrap();
+        ExprKind::While { cond, body } => {
+            collect_expr_names(cond, out);
+            collect_names(body, out);
+        }
+        ExprKind::For { init, cond, post, body } => {
+            if let Some(init) = init {
+                collect_names(std::slice::from_ref(&**init), out);
+            }
+            for e in cond.iter().chain(post.iter()) {
+                collect_expr_names(e, out);
+            }
+            collect_names(body, out);
+        }
+        ExprKind::ForIn { iterable, body, .. } => {
+            collect_expr_names(iterable, out);
+            collect_names(body, out);
+        }
+        ExprKind::Switch { expr, cases, default } => {
+            collect_expr_names(expr, out);
+            for (case, body) in cases {
+                collect_expr_names(case, out);
+                collect_names(body, out);
+            }
+            if let Some(d) = default {
+                collect_names(d, out);
+            }
+        }
+        ExprKind::TryCatch { try_block, catches, finally_block } => {
+            collect_names(try_block, out);
+            catches.iter().for_each(|c| collect_names(&c.body, out));
+            if let Some(f) = finally_block {
+                collect_names(f, out);
+            }
+        }
+        ExprKind::Throw { expr: inner }
+        | ExprKind::Defer(inner)
+        | ExprKind::Await(inner)
+        | ExprKind::Return(Some(inner)) => {
+            collect_expr_names(inner, out)
+        }
+        // Nested lambdas capture through this one
+        ExprKind::Funcy { body, .. } => collect_names(body, out),
+        ExprKind::ArrayLiteral(items) | ExprKind::NewInstance { args: items, .. } => {
+            items.iter().for_each(|a| collect_expr_names(a, out));
+        }
+        ExprKind::ObjectLiteral { fields, .. } => fields.iter().for_each(|(_, v)| collect_expr_names(v, out)),
+        ExprKind::Match { subject, arms } => {
+            collect_expr_names(subject, out);
+            arms.iter().for_each(|arm| collect_names(&arm.body, out));
+        }
+        _ => {}
+    }
 }
 
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
 
-    // === Step 11: Restore previous state ===
-    self.vars = old_vars;
-    if let Some(block) = saved_block {
-        self.builder.position_at_end(block);
+    fn compile(src: &str) -> CompileResult<()> {
+        let context = Context::create();
+        let mut codegen = Codegen::new(&context, "main", "./src");
+        let ast = parse(src).expect("test source should parse");
+        codegen.compile_main(&ast).map(|_| ())
     }
 
-    // === Step 12: Register overload signature ===
-    // Convert param_type_names (strings) to TypeDescriptors for registration
-    let type_descriptors: Vec<TypeDescriptor> = if let Some(overrides) = param_override {
-        overrides.to_vec()
-    } else {
-        param_type_names.iter().map(|s| TypeDescriptor::Primitive(s.clone())).collect()
-    };
+    /// Compile `src` and return the module's IR
+    fn compile_ir(src: &str) -> String {
+        let context = Context::create();
+        let mut codegen = Codegen::new(&context, "main", "./src");
+        codegen.compile_main(&parse(src).expect("test source should parse")).unwrap();
+        codegen.module.print_to_string().to_string()
+    }
 
-    let sig = FunctionSignature {
-        name: name.to_string(),
-        param_types: type_descriptors,
-        return_type: TypeDescriptor::Primitive("i32".to_string()), // Default return type
-    };
-    self.functions.insert(sig.clone(), function);
-    self.reverse_func_index
-        .entry(name.to_string())
-        .or_default()
-        .push(sig.clone());
+    #[test]
+    fn test_unknown_variable_is_an_error() {
+        let src = "let x = 1;\nprint(x + yy);";
+        match compile(src) {
+            Err(CompileError::UnknownVariable { name, span }) => {
+                assert_eq!(name, "yy");
+                assert_eq!(&src[span.start..span.end], "yy");
+            }
+            other => panic!("expected unknown variable error, got {:?}", other),
+        }
+    }
 
-    let type_display: Vec<String> = sig.param_types.iter()
-        .map(|td| td.to_mangle_string())
-        .collect();
-    println!(
-        "🪶 Registered funcy {}({}) as {}",
-        name,
-        type_display.join(", "),
-        llvm_name
-    );
+    #[test]
+    fn test_use_thread_without_arguments() {
+        let err = compile("useThread();").unwrap_err();
+        assert!(matches!(err, CompileError::ArgumentCount { found: 0, .. }), "{:?}", err);
+    }
 
-    function
-}
+    #[test]
+    fn test_assign_to_const() {
+        let err = compile("const c = 1;\nc = 2;").unwrap_err();
+        assert!(matches!(err, CompileError::AssignToConst { ref name, .. } if name == "c"), "{:?}", err);
+    }
+
+    #[test]
+    fn test_oversized_literal_is_an_error() {
+        let src = "let x = 99999999999999999999;";
+        match compile(src) {
+            Err(CompileError::TypeMismatch { span, .. }) => {
+                assert_eq!(&src[span.start..span.end], "99999999999999999999");
+            }
+            other => panic!("expected a type error, got {:?}", other),
+        }
+    }
 
+    #[test]
+    fn test_array_element_types_are_checked() {
+        let err = compile("let xs: i32[] = [1, \"two\"];").unwrap_err();
+        assert!(matches!(err, CompileError::TypeMismatch { .. }), "{:?}", err);
 
-pub fn compile_async_funcy(
-    &mut self,
-    name: &str,
-    params: &[String],
-    body: &[Node],
-) -> FunctionValue<'ctx> {
-    // === Remove stale definitions (for hot recompilation) ===
-    if let Some(existing) = self.module.get_function(name) {
-        unsafe { let _ = existing.delete(); }
+        let err = compile("let grid = [[1], [2]];\ngrid[0] = [\"a\"];").unwrap_err();
+        assert!(matches!(err, CompileError::TypeMismatch { .. }), "{:?}", err);
+
+        compile("let fs = [1, 2.5];\nlet names: str[] = [];\nlet m: f64[][] = [[1, 2]];\nprint(fs[0] + m[0][1], names);").unwrap();
     }
 
-    println!("⚙️ compiling async funcy {}", name);
+    #[test]
+    fn test_index_requires_array() {
+        let src = "let n = 5;\nprint(n[0]);";
+        let err = compile(src).unwrap_err();
+        assert!(matches!(err, CompileError::TypeMismatch { .. }), "{:?}", err);
+        assert_eq!(&src[err.span().unwrap().start..err.span().unwrap().end], "n");
+    }
+    #[test]
+    fn test_lambdas_capture_into_an_environment() {
+        let ir = compile_ir("let n = 10;\nlet add = funcy(x) { return x + n; };\nlet id = funcy(x) { return x; };\nprint(add(1) + id(2));\nserver.register(\"/n\", add);");
 
-    // === Define function signature (i32 return, i32 params) ===
-    let param_types: Vec<BasicMetadataTypeEnum<'ctx>> =
-        params.iter().map(|_| self.i32_type.into()).collect();
-    let fn_type = self.i32_type.fn_type(&param_types, false);
+        // Only the capturing lambda takes the trailing environment pointer
+        assert!(ir.contains("@lambda.0__i32(i32 %0, i8* %1)"), "missing environment:\n{}", ir);
+        assert!(ir.contains("@lambda.1__i32(i32 %0)"), "unexpected environment:\n{}", ir);
+        for f in ["closure_env", "@wpp_closure_new", "@wpp_register_closure_endpoint"] {
+            assert!(ir.contains(f), "missing {}:\n{}", f, ir);
+        }
 
-    // === Create function + entry block ===
-    let function = self.module.add_function(name, fn_type, None);
-    let entry = self.context.append_basic_block(function, "entry");
-    self.builder.position_at_end(entry);
+        let err = compile("server.register(\"/x\", 5);").unwrap_err();
+        assert!(matches!(err, CompileError::TypeMismatch { .. }), "{:?}", err);
+        let err = compile("let x = 5;\nprint(x());").unwrap_err();
+        assert!(matches!(err, CompileError::TypeMismatch { .. }), "{:?}", err);
+    }
 
-    // === Allocate and store parameters ===
-let mut local_vars: HashMap<String, VarInfo<'ctx>> = HashMap::new();
-    for (i, pname) in params.iter().enumerate() {
-        let param = function.get_nth_param(i as u32).unwrap();
-        let alloca = self.builder.build_alloca(self.i32_type, pname).unwrap();
-        self.builder.build_store(alloca, param).unwrap();
-        local_vars.insert(
-    pname.clone(),
-    VarInfo {
-        ptr: alloca,
-        ty: self.i32_type.as_basic_type_enum(),
-        is_const: false,
-        is_thread_state: false,
-        entity_type: None,
-        object_type_name: None,
-        function_signature: None,
-    },
-);
+    #[test]
+    fn test_string_literals_carry_a_header() {
+        // Static refcount, 5 bytes, 4 code points, then the magic word
+        let ir = compile_ir("let s = \"a\\0bé\";\nprint(strlen(s));\nprint(s + \"!\" + s);");
+        let header = format!("i32 -1, i32 5, i32 5, i32 4, i32 0, i32 {}", runtime::WPP_STRING_MAGIC as i32);
+        assert!(ir.contains(&header), "missing header {}:\n{}", header, ir);
+        for f in ["@wpp_str_len", "@wpp_str_release"] {
+            assert!(ir.contains(f), "missing call to {}:\n{}", f, ir);
+        }
+    }
 
+    #[test]
+    fn test_interpolation_lowers_to_format() {
+        // Braces in the text are escaped, and each argument carries its tag (u8 = 14, array = 7)
+        let ir = compile_ir("let n: u8 = 3;\nlet xs = [1.5];\nprint(\"{n} is ${n}, xs ${xs}\");");
+        assert!(ir.contains("{{n}} is {}, xs {}"), "missing template:\n{}", ir);
+        for needle in ["@wpp_format", "store i32 14", "store i32 7"] {
+            assert!(ir.contains(needle), "missing {}:\n{}", needle, ir);
+        }
     }
 
-    // === Scoped variable map ===
-    let old_vars: HashMap<String, VarInfo<'ctx>> = std::mem::replace(&mut self.vars, local_vars);
+    #[test]
+    fn test_objects_are_allocated_on_the_wpp_heap() {
+        let ir = compile_ir("let o = { a: 1, b: 2 };");
+        assert!(ir.contains("call i8* @wpp_gc_alloc"), "object not on the W++ heap:\n{}", ir);
+        assert!(!ir.contains("@malloc("), "stray malloc:\n{}", ir);
+    }
 
-    // === Compile body ===
-    let mut last_val: Option<BasicValueEnum<'ctx>> = None;
-    for node in body {
-        last_val = self.compile_node(node);
+    #[test]
+    fn test_growable_array_builtin_errors() {
+        let err = compile("let xs = [1];\npush(xs, \"a\");").unwrap_err();
+        assert!(matches!(err, CompileError::TypeMismatch { .. }), "{:?}", err);
+
+        let err = compile("let xs = [1];\nprint(concat(xs, [\"a\"]));").unwrap_err();
+        assert!(matches!(err, CompileError::TypeMismatch { .. }), "{:?}", err);
+
+        let err = compile("let xs = [1];\npush(xs);").unwrap_err();
+        assert!(matches!(err, CompileError::ArgumentCount { found: 1, .. }), "{:?}", err);
+
+        compile("let m = [1, \"a\"];\nprint(pop(m));").unwrap();
     }
 
-    // === Determine return value (default to 0) ===
-    let ret_val = match last_val {
-        Some(BasicValueEnum::IntValue(iv)) => iv,
-        _ => self.i32_type.const_int(0, false),
-    };
+    #[test]
+    fn test_map_builtins_are_typed() {
+        compile("let h: map<str> = Map();\nset(h, \"Host\", \"a\");\nlet host = get(h, \"Host\", \"none\");\nprint(h, keys(h), has(h, host), len(h));").unwrap();
 
-    // === Add async footer only if block isn’t already terminated ===
-    if self
-        .builder
-        .get_insert_block()
-        .and_then(|b| b.get_terminator())
-        .is_none()
-    {
-        let void_ty = self.context.void_type();
+        let err = compile("let h = Map({ \"a\": 1 });\nset(h, \"b\", \"two\");").unwrap_err();
+        assert!(matches!(err, CompileError::TypeMismatch { .. }), "{:?}", err);
 
-        // wpp_return(i32)
-        let wpp_return = self.module.get_function("wpp_return").unwrap_or_else(|| {
-            let fn_ty = void_ty.fn_type(&[self.i32_type.into()], false);
-            self.module.add_function("wpp_return", fn_ty, None)
-        });
+        let err = compile("let h = Map();\nget(h, 1);").unwrap_err();
+        assert!(matches!(err, CompileError::TypeMismatch { .. }), "{:?}", err);
 
-        // wpp_yield()
-        let yield_fn = self.module.get_function("wpp_yield").unwrap_or_else(|| {
-            let fn_ty = void_ty.fn_type(&[], false);
-            self.module.add_function("wpp_yield", fn_ty, None)
-        });
+        let err = compile("let xs = [1];\nhas(xs, \"a\");").unwrap_err();
+        assert!(matches!(err, CompileError::TypeMismatch { .. }), "{:?}", err);
 
-        // --- async return footer ---
-        // 1️⃣ Notify runtime of result (only once)
-        self.builder
-            .build_call(wpp_return, &[ret_val.into()], "final_async_return")
-            .unwrap();
+        compile("let m = Map({ \"a\": 1, \"b\": \"x\" });\nprint(get(m, \"a\"));").unwrap();
+    }
 
-        // 2️⃣ Yield to scheduler to finalize
-        self.builder.build_call(yield_fn, &[], "yield_after_return").unwrap();
+    #[test]
+    fn test_for_in_requires_a_collection() {
+        let err = compile("let n = 3;\nfor x in n { print(x); }").unwrap_err();
+        assert!(matches!(err, CompileError::TypeMismatch { .. }), "{:?}", err);
 
-        // 3️⃣ Actually return from LLVM func
-        self.builder.build_return(Some(&ret_val)).unwrap();
-    } else {
-        println!("⚠️ [compile_async_funcy] Block already terminated, skipping footer");
+        let err = compile("for i, x in 0..3 { }").unwrap_err();
+        assert!(matches!(err, CompileError::Unsupported { .. }), "{:?}", err);
+
+        compile("let m = Map({ \"a\": [1] });\nfor k, xs in m { for i, x in xs { print(k, i, x + len(xs)); } }").unwrap();
     }
 
-    // === Restore outer variable scope ===
-    self.vars = old_vars;
-    let sig = FunctionSignature {
-    name: name.to_string(),
-    param_types: params.iter().map(|_| TypeDescriptor::Primitive("i32".to_string())).collect(),
-    return_type: TypeDescriptor::Primitive("i32".to_string()), // Default return type
-};
+    #[test]
+    fn test_for_in_slots_live_in_the_entry_block() {
+        let ir = compile_ir("let xs = [1, 2];\nlet i = 0;\nwhile (i < 3) { for x in xs { print(x); } i = i + 1; }");
 
-self.functions.insert(sig.clone(), function);
-self.reverse_func_index
-    .entry(name.to_string())
-    .or_default()
-    .push(sig);
+        // A loop nested in another must not grow the stack on every outer pass
+        let main = &ir[ir.find("define i32 @main_async").unwrap()..];
+        let entry = &main[..main.find("\n\n").unwrap()];
+        for slot in ["%x = alloca", "%for_in_idx = alloca"] {
+            assert!(entry.contains(slot), "{} outside the entry block:\n{}", slot, main);
+        }
+    }
 
+    #[test]
+    fn test_unsigned_arithmetic_and_casts() {
+        // `b` widens to u32 by zero extension, and the operations use unsigned forms
+        let ir = compile_ir("let a: u32 = 4000000000;\nlet b: u8 = 3;\nprint(a / b, a < b + 1, a as i8, b as f64);");
+        for op in ["zext i8", "udiv i32", "icmp ult i32", "trunc i32", "uitofp i8"] {
+            assert!(ir.contains(op), "missing {}:\n{}", op, ir);
+        }
 
-    println!("✅ async funcy {} compiled successfully", name);
-    function
-}
+        let err = compile("let x: u8 = 256;").unwrap_err();
+        assert!(matches!(err, CompileError::TypeMismatch { .. }), "{:?}", err);
+        let err = compile("let a: u64 = 1;\nlet b: i64 = 2;\nprint(a + b);").unwrap_err();
+        assert!(matches!(err, CompileError::TypeMismatch { .. }), "{:?}", err);
+        let err = compile("let s = \"a\" as i32;").unwrap_err();
+        assert!(matches!(err, CompileError::TypeMismatch { .. }), "{:?}", err);
+    }
 
+    #[test]
+    fn test_bitwise_operators_reject_floats() {
+        let err = compile("let f = 1.5;\nprint(f & 1);").unwrap_err();
+        assert!(matches!(err, CompileError::Unsupported { .. }), "{:?}", err);
+    }
 
+    #[test]
+    fn test_new_instance_runs_the_init_chain() {
+        let src = "entity Animal {\n  name = \"?\"\n  funcy init(name: string) { me.name = name }\n}\nentity Dog alters Animal {\n  age = 0\n  weight = 1.5\n  funcy init(name: string, age) { me.age = age }\n}\nlet d = new(Dog, \"Rex\", 3);\nprint(d.age, d.weight);";
 
-pub fn compile_entity(&mut self, entity: &EntityNode) {
-        println!("🏗️ Compiling entity: {}", entity.name);
+        // Fields take their defaults' types, and the base `init` runs before the entity's own
+        let ir = compile_ir(src);
+        assert!(ir.contains("%Dog = type { i8*, i8*, i32, double }"), "wrong layout:\n{}", ir);
+        let animal = ir.find("call_Animal_init").expect("missing Animal.init call");
+        let dog = ir.find("call_Dog_init").expect("missing Dog.init call");
+        assert!(animal < dog, "init chain out of order:\n{}", ir);
 
-        // === 1️⃣ Inherit base fields (if any) ===
-        let mut all_fields: Vec<(String, BasicTypeEnum<'ctx>)> = Vec::new();
+        let err = compile("entity Rock {\n  size = 1\n}\nlet r = new(Rock, 2);").unwrap_err();
+        assert!(matches!(err, CompileError::ArgumentCount { found: 1, .. }), "{:?}", err);
+    }
 
-        if let Some(base_name) = &entity.base {
-            if let Some(base_entity) = self.entities.get(base_name) {
-                println!("🔗 Inheriting fields from base entity '{}'", base_name);
-                all_fields.extend(base_entity.fields.clone());
-            } else {
-                eprintln!("⚠️ Base entity '{}' not found", base_name);
-            }
+    #[test]
+    fn test_methods_dispatch_through_vtables() {
+        let src = "entity Animal {\n  funcy speak() { print(\"...\") }\n  funcy greet() { me.speak() }\n}\nentity Dog alters Animal {\n  funcy speak() { super.speak() }\n}\nlet a: Animal = new(Dog);\na.greet();";
+
+        // Dog keeps Animal's slot order with its own `speak` in slot 0
+        let ir = compile_ir(src);
+        let vtables: Vec<_> = ir.lines().filter(|l| l.starts_with("@Dog.vtable")).collect();
+        assert_eq!(vtables.len(), 1, "the vtable's address is the entity's identity: {:?}", vtables);
+        let dog_vtable = vtables[0];
+        let (speak, greet) = (dog_vtable.find("Dog.speak").unwrap(), dog_vtable.find("Animal.greet").unwrap());
+        assert!(speak < greet, "wrong slot order: {}", dog_vtable);
+        for needle in ["vtable_entry", "call_super_Animal_speak"] {
+            assert!(ir.contains(needle), "missing {}:\n{}", needle, ir);
         }
 
-        // === 2️⃣ Add this entity’s fields ===
-        for member in &entity.members {
-            if let EntityMember::Field { name, .. } = member {
-                all_fields.push((name.clone(), self.i32_type.into()));
-            }
+        let err = compile("entity A {\n  funcy f() { return 1 }\n}\nentity B alters A {\n  funcy f(x) { return x }\n}").unwrap_err();
+        assert!(matches!(err, CompileError::TypeMismatch { .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn test_interfaces_are_checked_and_dispatched() {
+        let src = "interface Speaker { speak() }\nentity Dog implements Speaker {\n  funcy speak() { print(\"Woof\") }\n}\nentity Robot implements Speaker {\n  funcy speak() { print(\"Beep\") }\n}\nfuncy greet(s: Speaker) { s.speak() }\nlet r: Speaker = new(Robot);\ngreet(new(Dog));\nr.speak();";
+        let ir = compile_ir(src);
+        for needle in ["is_Dog", "is_Robot"] {
+            assert!(ir.contains(needle), "missing {}:\n{}", needle, ir);
         }
 
-        // === 3️⃣ Define LLVM struct type for the entity ===
-        let struct_type = self.context.opaque_struct_type(&entity.name);
-        let field_types: Vec<_> = all_fields.iter().map(|(_, t)| *t).collect();
-        struct_type.set_body(&field_types, false);
+        let err = compile("interface Speaker { speak() }\nentity Cat implements Speaker {\n  funcy purr() { print(1) }\n}").unwrap_err();
+        assert!(matches!(&err, CompileError::MissingInterfaceMethod { method, .. } if method == "speak"), "{:?}", err);
+        let err = compile("interface Speaker { speak(volume) }\nentity Cat implements Speaker {\n  funcy speak() { print(1) }\n}").unwrap_err();
+        assert!(matches!(err, CompileError::TypeMismatch { .. }), "{:?}", err);
+        let err = compile("interface Speaker { speak() }\nentity Cat implements Speaker {\n  funcy speak() { print(1) }\n  funcy purr() { print(2) }\n}\nlet s: Speaker = new(Cat);\ns.purr();").unwrap_err();
+        assert!(matches!(err, CompileError::UnknownFunction { .. }), "{:?}", err);
+    }
 
-        // === 4️⃣ Register methods ===
-        let mut methods = HashMap::new();
+    #[test]
+    fn test_runtime_dispatch_stubs() {
+        let src = "interface Speaker { speak() }\nentity Dog implements Speaker {\n  funcy speak() { print(\"Woof\") }\n}\nfuncy describe(x: i32) { print(x) }\nfuncy describe(x: str) { print(x) }\nfuncy describe(d: Dog) { print(\"dog\") }\nfuncy handle(code: 2xx) { print(code) }\nfuncy handle(code: 404) { print(code) }\nlet items: any[] = [1, \"two\", new(Dog)];\nfor item in items { describe(item); }\nlet s: Speaker = new(Dog);\ndescribe(s);\nlet codes = [200, 404];\nfor c in codes { handle(c); }";
 
-        for member in &entity.members {
-            if let EntityMember::Method { name, func } = member {
-                if let Expr::Funcy {
-                    params,
-                    body,
-                    is_async,
-                    ..
-                } = func
-                {
-                    // 👇 compile_funcy with entity context ("Dog.bark")
-                    let full_name = format!("{}.{}", entity.name, name);
+        // One stub per shape of dynamic arguments, each falling through to a runtime error
+        let ir = compile_ir(src);
+        for stub in ["@describe__dispatch_any", "@describe__dispatch_iface_Speaker", "@handle__dispatch_i32"] {
+            assert!(ir.contains(stub), "missing {}:\n{}", stub, ir);
+        }
+        assert!(ir.contains("@wpp_throw_message"), "missing fallthrough:\n{}", ir);
 
-// Compile with qualified name
-let func_val: FunctionValue<'_> = self.compile_funcy(
-    &full_name,              // ✅ use "Dog.bark" instead of "bark"
-    params,
-    body,
-    None,
-    Some(&entity.name),
-    None,
-);
+        let err = compile("funcy describe(x: i32) { print(x) }\nlet items: any[] = [\"a\"];\nlet xs: str[] = [];\ndescribe(xs);").unwrap_err();
+        assert!(matches!(err, CompileError::NoMatchingOverload { .. }), "{:?}", err);
+    }
 
-// Register under both function table and entity-local map
-self.functions.insert(
-    FunctionSignature {
-        name: full_name.clone(),
-        param_types: vec![],
-        return_type: TypeDescriptor::Primitive("i32".to_string()), // Default return type
-    },
-    func_val,
-);
+    #[test]
+    fn test_overloads_follow_alters_chains() {
+        let entities = "entity Animal {}\nentity Dog alters Animal {}\nentity Puppy alters Dog {}\n";
+        let src = format!(
+            "{}funcy describe(a: Animal) {{ print(1) }}\nfuncy describe(d: Dog) {{ print(2) }}\ndescribe(new(Puppy));\nlet pets: any[] = [new(Puppy)];\nfor p in pets {{ describe(p); }}",
+            entities
+        );
 
-methods.insert(name.clone(), func_val);
+        // The closest base wins statically, and the runtime check accepts every entity altering it
+        let ir = compile_ir(&src);
+        assert!(ir.contains("@describe__entity_Dog(i8* %alloc_instance)"), "missing static call:\n{}", ir);
+        assert!(ir.contains("is_Puppy"), "missing subtype check:\n{}", ir);
+
+        let err = compile(&format!(
+            "{}funcy pair(a: Animal, d: Dog) {{ print(1) }}\nfuncy pair(d: Dog, a: Animal) {{ print(2) }}\npair(new(Dog), new(Dog));",
+            entities
+        ))
+        .unwrap_err();
+        match err {
+            CompileError::AmbiguousCall { candidates, .. } => assert_eq!(candidates, ["pair(Animal, Dog)", "pair(Dog, Animal)"]),
+            other => panic!("expected an ambiguous call, got {:?}", other),
+        }
+    }
 
-                }
-            }
+    #[test]
+    fn test_match_on_enums() {
+        let shape = "enum Shape { Circle(f64), Rect(f64, f64), Empty }\n";
+        compile(&format!(
+            "{}funcy area(s: Shape) -> f64 {{ return match s {{ Circle(r) => r * r, Rect(w, h) => w * h, Empty => 0 }} }}\nprint(area(Shape.Circle(2.0)), Shape.Empty);",
+            shape
+        ))
+        .unwrap();
+
+        let err = compile(&format!("{}let s = Shape.Empty;\nlet x = match s {{ Circle(r) => 1, Empty => 2 }};", shape)).unwrap_err();
+        match err {
+            CompileError::NonExhaustiveMatch { missing, .. } => assert_eq!(missing, "Rect(_, _)"),
+            other => panic!("expected a non-exhaustive match, got {:?}", other),
         }
 
-        // === 5️⃣ Register the entity in Codegen context ===
-        let oopsie = OopsieEntity {
-            name: entity.name.clone(),
-            base: entity.base.clone(),
-            struct_type,
-            fields: all_fields,
-            methods,
-        };
+        let err = compile(&format!("{}let s = Shape.Rect(1.0);", shape)).unwrap_err();
+        assert!(matches!(err, CompileError::ArgumentCount { found: 1, .. }), "{:?}", err);
 
-        self.entities.insert(entity.name.clone(), oopsie);
-        println!("✅ Entity '{}' compiled successfully", entity.name);
+        let err = compile("let n = 3;\nlet x = match n { \"a\" => 1, _ => 2 };").unwrap_err();
+        assert!(matches!(err, CompileError::InvalidPattern { .. }), "{:?}", err);
     }
 
+    #[test]
+    fn test_typed_catch_and_stack_frames() {
+        let src = "entity NotFound { message = \"\" }\nfuncy find(key: str) { throw new(NotFound) }\ntry {\n  find(\"x\");\n} catch (e: NotFound) {\n  print(stack_trace(e));\n} catch (e: i32) {\n  print(e);\n} finally {\n  print(\"done\");\n}";
 
-    
-}
-impl<'ctx> Codegen<'ctx> {
-    fn ensure_builder_position(&self) {
-    if let Some(block) = self.builder.get_insert_block() {
-        if block.get_terminator().is_some() {
-            // Only reposition if the current block is part of an *active* function
-            if let Some(func) = block.get_parent() {
-                // Do NOT create multiple “fix_blocks” if already at an end block
-                if func.get_last_basic_block() != Some(block) {
-                    let fix_block = self.context.append_basic_block(func, "fix_block");
-                    self.builder.position_at_end(fix_block);
-                }
-            }
+        // Calls are followed by a pending check; functions record their frame for traces
+        let ir = compile_ir(src);
+        for needle in ["@wpp_throw(", "@wpp_exception_pending", "@wpp_exception_catch", "@wpp_exception_save", "@wpp_frame_push", "catch_dispatch"] {
+            assert!(ir.contains(needle), "missing {}:\n{}", needle, ir);
         }
+
+        let err = compile("try { print(1); } catch (e: Missing) { print(2); }").unwrap_err();
+        assert!(matches!(err, CompileError::TypeMismatch { .. }), "{:?}", err);
+        let err = compile("let x = 1;\nprint(stack_trace(x));").unwrap_err();
+        assert!(matches!(err, CompileError::Unsupported { .. }), "{:?}", err);
     }
-}
 
-}
-impl<'ctx> Codegen<'ctx> {
-    /// Safely emit a branch only if the current block has no terminator.
-    fn safe_branch(&self, target: inkwell::basic_block::BasicBlock<'ctx>) {
-        if let Some(block) = self.builder.get_insert_block() {
-            if block.get_terminator().is_none() {
-                self.builder.build_unconditional_branch(target).unwrap();
-            }
-        }
+    #[test]
+    fn test_division_by_zero_throws() {
+        // Only a divisor that is not a non-zero constant is checked
+        let ir = compile_ir("let a = 10;\nlet b = 0;\nprint(a / b, a % 3);");
+        assert_eq!(ir.matches("%divisor = select").count(), 1, "{}", ir);
+        assert!(ir.contains("division by zero"), "missing message:\n{}", ir);
+    }
+
+    #[test]
+    fn test_defer_runs_on_every_exit() {
+        let src = "funcy cleanup() { print(0) }\nfuncy risky() { throw 1 }\nfuncy work(n: i32) -> i32 {\n  defer cleanup();\n  risky();\n  if (n == 0) { return 1; }\n  return n;\n}\nprint(work(2));";
+
+        // Both returns and the exception path out of `risky()` call the deferred function
+        let ir = compile_ir(src);
+        let work = &ir[ir.find("define i32 @work").unwrap()..];
+        let work = &work[..work.find("\n}").unwrap()];
+        assert!(work.contains("defer_unwind"), "missing exception path:\n{}", work);
+        assert!(work.matches("@cleanup(").count() >= 3, "{}", work);
+
+        let err = compile("funcy f() {\n  defer print(missing);\n}").unwrap_err();
+        assert!(matches!(err, CompileError::UnknownVariable { .. }), "{:?}", err);
     }
 }
@@ -0,0 +1,214 @@
+use std::fmt;
+
+pub use crate::ast::span::Span;
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+/// A single message produced while processing W++ source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub span: Span,
+    pub message: String,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            span,
+            message: message.into(),
+            notes: Vec::new(),
+        }
+    }
+
+    /// Attach an extra hint shown below the main message
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Plain one-line rendering with the line/column resolved against `source`
+    pub fn render_plain(&self, source: &str) -> String {
+        let (line, col) = self.span.line_col(source);
+        let mut out = format!("{} at line {}, col {}: {}", self.severity, line, col, self.message);
+        for note in &self.notes {
+            out.push_str(&format!("\n  = note: {}", note));
+        }
+        out
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)?;
+        for note in &self.notes {
+            write!(f, "\n  = note: {}", note)?;
+        }
+        Ok(())
+    }
+}
+
+/// Join a batch of diagnostics into one plain-text report (one entry per line)
+pub fn format_all(diags: &[Diagnostic], source: &str) -> String {
+    diags
+        .iter()
+        .map(|d| d.render_plain(source))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Why code generation rejected a program
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompileError {
+    UnknownVariable { name: String, span: Span },
+    UnknownFunction { name: String, span: Span },
+    UnknownEntity { name: String, span: Span },
+    /// A builtin or function was called with the wrong number of arguments
+    ArgumentCount { callee: String, expected: String, found: usize, span: Span },
+    NoMatchingOverload { name: String, arg_types: Vec<String>, span: Span },
+    /// Several overloads accept the arguments and none is more specific than the others
+    AmbiguousCall { name: String, arg_types: Vec<String>, candidates: Vec<String>, span: Span },
+    AssignToConst { name: String, span: Span },
+    InvalidAssignTarget { span: Span },
+    TypeMismatch { message: String, span: Span },
+    /// Valid syntax that codegen does not know how to lower (yet)
+    Unsupported { message: String, span: Span },
+    /// `break` / `continue` with no enclosing loop or switch
+    OutsideLoop { keyword: String, span: Span },
+    /// A `match` arm pattern that cannot match the subject's type
+    InvalidPattern { message: String, span: Span },
+    /// A `match` whose arms miss some values; `missing` is one of them, written as a pattern
+    NonExhaustiveMatch { missing: String, span: Span },
+    /// An entity that names an interface in `implements` without providing one of its methods
+    MissingInterfaceMethod { entity: String, interface: String, method: String, span: Span },
+    /// LLVM verification, linking or JIT failures that have no source location
+    Backend(String),
+}
+
+pub type CompileResult<T> = Result<T, CompileError>;
+
+impl CompileError {
+    pub fn unsupported(span: Span, message: impl Into<String>) -> Self {
+        CompileError::Unsupported { message: message.into(), span }
+    }
+
+    pub fn type_mismatch(span: Span, message: impl Into<String>) -> Self {
+        CompileError::TypeMismatch { message: message.into(), span }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            CompileError::UnknownVariable { span, .. }
+            | CompileError::UnknownFunction { span, .. }
+            | CompileError::UnknownEntity { span, .. }
+            | CompileError::ArgumentCount { span, .. }
+            | CompileError::NoMatchingOverload { span, .. }
+            | CompileError::AmbiguousCall { span, .. }
+            | CompileError::AssignToConst { span, .. }
+            | CompileError::InvalidAssignTarget { span }
+            | CompileError::TypeMismatch { span, .. }
+            | CompileError::Unsupported { span, .. }
+            | CompileError::OutsideLoop { span, .. }
+            | CompileError::InvalidPattern { span, .. }
+            | CompileError::NonExhaustiveMatch { span, .. }
+            | CompileError::MissingInterfaceMethod { span, .. } => Some(*span),
+            CompileError::Backend(_) => None,
+        }
+    }
+
+    /// Short label printed under the caret
+    pub fn label(&self) -> &'static str {
+        match self {
+            CompileError::UnknownVariable { .. } => "not found in this scope",
+            CompileError::UnknownFunction { .. } => "no function with this name",
+            CompileError::UnknownEntity { .. } => "no entity with this name",
+            CompileError::ArgumentCount { .. } => "wrong number of arguments",
+            CompileError::NoMatchingOverload { .. } => "no overload accepts these arguments",
+            CompileError::AmbiguousCall { .. } => "more than one overload fits equally well",
+            CompileError::AssignToConst { .. } => "cannot assign twice to a constant",
+            CompileError::InvalidAssignTarget { .. } => "cannot assign to this expression",
+            CompileError::TypeMismatch { .. } => "mismatched types",
+            CompileError::Unsupported { .. } => "not supported here",
+            CompileError::OutsideLoop { .. } => "not inside a loop",
+            CompileError::InvalidPattern { .. } => "invalid pattern",
+            CompileError::NonExhaustiveMatch { .. } => "pattern not covered",
+            CompileError::MissingInterfaceMethod { .. } => "missing interface method",
+            CompileError::Backend(_) => "",
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::UnknownVariable { name, .. } => write!(f, "unknown variable `{}`", name),
+            CompileError::UnknownFunction { name, .. } => write!(f, "unknown function `{}`", name),
+            CompileError::UnknownEntity { name, .. } => write!(f, "unknown entity `{}`", name),
+            CompileError::ArgumentCount { callee, expected, found, .. } => {
+                let verb = if *found == 1 { "was" } else { "were" };
+                write!(f, "`{}` expects {}, but {} {} given", callee, expected, found, verb)
+            }
+            CompileError::NoMatchingOverload { name, arg_types, .. } => {
+                write!(f, "no matching overload for `{}({})`", name, arg_types.join(", "))
+            }
+            CompileError::AmbiguousCall { name, arg_types, .. } => {
+                write!(f, "call to `{}({})` is ambiguous", name, arg_types.join(", "))
+            }
+            CompileError::AssignToConst { name, .. } => write!(f, "cannot assign to constant `{}`", name),
+            CompileError::InvalidAssignTarget { .. } => {
+                write!(f, "left-hand side of assignment must be a variable or an element `a[i]`")
+            }
+            CompileError::TypeMismatch { message, .. } => write!(f, "{}", message),
+            CompileError::Unsupported { message, .. } => write!(f, "{}", message),
+            CompileError::OutsideLoop { keyword, .. } if keyword == "break" => {
+                write!(f, "`break` used outside of a loop or switch")
+            }
+            CompileError::OutsideLoop { keyword, .. } => write!(f, "`{}` used outside of a loop", keyword),
+            CompileError::InvalidPattern { message, .. } => write!(f, "{}", message),
+            CompileError::NonExhaustiveMatch { missing, .. } => {
+                write!(f, "non-exhaustive `match`: `{}` not covered", missing)
+            }
+            CompileError::MissingInterfaceMethod { entity, interface, method, .. } => {
+                write!(f, "`{}` implements `{}` but has no method `{}`", entity, interface, method)
+            }
+            CompileError::Backend(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+impl From<CompileError> for Diagnostic {
+    fn from(err: CompileError) -> Self {
+        let span = err.span().unwrap_or_default();
+        let diag = Diagnostic::error(span, err.to_string());
+        match err {
+            CompileError::AmbiguousCall { candidates, .. } => candidates
+                .iter()
+                .fold(diag, |diag, candidate| diag.with_note(format!("candidate: {}", candidate))),
+            _ => diag,
+        }
+    }
+}
@@ -6,7 +6,7 @@ use inkwell::module::{Linkage, Module};
 use inkwell::types::BasicMetadataTypeEnum;
 use inkwell::values::FunctionValue;
 
-use crate::ast::{Expr, Node};
+use crate::ast::{Expr, ExprKind, Node, NodeKind};
 use crate::module_system::ModuleSystem;
 use std::collections::HashMap;
 
@@ -36,18 +36,20 @@ impl ExportResolver {
             let mut node_types: Vec<String> = Vec::new();
             for node in &module.ast {
                 // Collect node type for debugging
-                let node_type = match node {
-                    Node::Export { .. } => "Export",
-                    Node::ImportList { .. } => "ImportList",
-                    Node::ImportAll { .. } => "ImportAll",
-                    Node::Expr(_) => "Expr",
-                    Node::Let { .. } => "Let",
-                    Node::Entity(_) => "Entity",
-                    Node::TypeAlias(_) => "TypeAlias",
+                let node_type = match &node.kind {
+                    NodeKind::Export { .. } => "Export",
+                    NodeKind::ImportList { .. } => "ImportList",
+                    NodeKind::ImportAll { .. } => "ImportAll",
+                    NodeKind::Expr(_) => "Expr",
+                    NodeKind::Let { .. } => "Let",
+                    NodeKind::Entity(_) => "Entity",
+                    NodeKind::TypeAlias(_) => "TypeAlias",
+                    NodeKind::Enum(_) => "Enum",
+                    NodeKind::Interface(_) => "Interface",
                 };
                 node_types.push(node_type.to_string());
 
-                if let Node::Export { name: export_name, item } = node {
+                if let NodeKind::Export { name: export_name, item } = &node.kind {
                     // Store the exported function/const node
                     self.global_table.insert(export_name.clone(), *item.clone());
                     println!("📦 [resolver] Registered export '{}::{}'", name, export_name);
@@ -67,25 +69,25 @@ impl ExportResolver {
         let mut injected = Vec::new();
 
         for node in ast.iter() {
-            match node {
-                Node::ImportList { module: _, members } => {
+            match &node.kind {
+                NodeKind::ImportList { module: _, members } => {
                     for (name, alias) in members {
                         let key = name.clone();
                         if let Some(exported) = self.global_table.get(&key) {
                             let injected_name = alias.clone().unwrap_or_else(|| key.clone());
-                            injected.push(Node::Let {
+                            injected.push(Node::new(NodeKind::Let {
                                 name: injected_name,
-                                value: match exported {
-                                    Node::Expr(e) => e.clone(),
-                                    Node::Export { item, .. } => match &**item {
-                                        Node::Expr(inner) => inner.clone(),
+                                value: match &exported.kind {
+                                    NodeKind::Expr(e) => e.clone(),
+                                    NodeKind::Export { item, .. } => match &item.kind {
+                                        NodeKind::Expr(inner) => inner.clone(),
                                         _ => return Err(format!("Unsupported export item: {key}")),
                                     },
                                     _ => return Err(format!("Unsupported export node: {key}")),
                                 },
                                 is_const: true,
                                 ty: None,
-                            });
+                            }, node.span));
                         } else {
                             return Err(format!("Unknown imported symbol: {}", key));
                         }
@@ -108,14 +110,14 @@ impl ExportResolver {
 
         for (_module_name, module_data) in cache.iter() {
             for node in &module_data.ast {
-                match node {
-                    Node::ImportList { module, members } => {
+                match &node.kind {
+                    NodeKind::ImportList { module, members } => {
                         println!("🔗 [resolver] Linking selective imports from '{}'", module);
 
                         for (member_name, _alias_opt) in members {
                             if let Some(exported) = self.global_table.get(member_name) {
                                 // Only process function exports
-                                if let Node::Expr(Expr::Funcy { name, params, .. }) = exported {
+                                if let NodeKind::Expr(Expr { kind: ExprKind::Funcy { name, params, .. }, .. }) = &exported.kind {
                                     let ctx = llvm_module.get_context();
 
                                     let ret_ty = ctx.void_type(); // assume void for now
@@ -161,9 +163,9 @@ impl ExportResolver {
 
         for (module_name, module_data) in cache.iter() {
             for node in &module_data.ast {
-                match node {
-                    Node::Export { name, item } => {
-                        if let Node::Expr(Expr::Funcy { name: fn_name, .. }) = &**item {
+                match &node.kind {
+                    NodeKind::Export { name, item } => {
+                        if let NodeKind::Expr(Expr { kind: ExprKind::Funcy { name: fn_name, .. }, .. }) = &item.kind {
                             let symbol_name = fn_name.clone();
 
                             // Get compiled address from JIT
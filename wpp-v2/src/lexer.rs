@@ -1,6 +1,7 @@
 use std::iter::Peekable;
 use std::str::Chars;
 use unicode_normalization::UnicodeNormalization;
+use crate::ast::span::{FileId, Span};
 
 
 /// Kinds of tokens in W++
@@ -14,16 +15,27 @@ pub enum TokenKind {
 },
 
     String(String),      // e.g. "hello"
-    Symbol(String),      // e.g. { } ( ) ; , = + - * / == != <= >= < >
+    InterpolatedString(Vec<StringPart>), // e.g. "hello ${name}"
+    DocComment(String),  // e.g. /// Adds two numbers
+    Symbol(String),      // e.g. { } ( ) ; , = + - * / % == != <= >= < > && << += ++
     EOF,                 // End of file
 }
 
-/// A token with line/column metadata
+/// A piece of a string literal that contains `${...}`
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    Text(String),
+    /// Tokens of an embedded expression, ending in `EOF`
+    Code(Vec<Token>),
+}
+
+/// A token with line/column metadata and its byte range in the source
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub kind: TokenKind,
     pub line: usize,
     pub col: usize,
+    pub span: Span,
 }
 
 /// The W++ lexer (tokenizer)
@@ -31,23 +43,45 @@ pub struct Lexer<'a> {
     input: Peekable<Chars<'a>>,
     pub line: usize,
     pub col: usize,
+    /// Byte offset of the next unread character
+    pub offset: usize,
+    pub file: FileId,
 }
 
 impl<'a> Lexer<'a> {
     /// Create a new lexer from a source string
     pub fn new(source: &'a str) -> Self {
+        Self::with_file(source, FileId::default())
+    }
+
+    /// Create a lexer whose token spans are tagged with `file`
+    pub fn with_file(source: &'a str, file: FileId) -> Self {
         Self {
             input: source.chars().peekable(),
             line: 1,
             col: 0,
+            offset: 0,
+            file,
         }
     }
 
+    /// Consume one character, keeping the byte offset in sync
+    fn bump(&mut self) -> Option<char> {
+        let c = self.input.next()?;
+        self.offset += c.len_utf8();
+        Some(c)
+    }
+
+    fn span_from(&self, start: usize) -> Span {
+        Span::new(self.file, start, self.offset)
+    }
+
     /// Tokenize the entire source into a vector of tokens
     pub fn tokenize(&mut self) -> Vec<Token> {
         let mut tokens = Vec::new();
 
         while let Some(&ch) = self.input.peek() {
+            let start = self.offset;
             match ch {
                 // --- Whitespace ---
                 c if c.is_whitespace() => {
@@ -57,7 +91,7 @@ impl<'a> Lexer<'a> {
                     } else {
                         self.col += 1;
                     }
-                    self.input.next();
+                    self.bump();
                 }
 
                 // --- Identifiers & keywords ---
@@ -66,7 +100,7 @@ impl<'a> Lexer<'a> {
                     let kind = match ident.as_str() {
     "let" | "if" | "else" | "while" | "for"
     | "break" | "continue" | "true" | "false"
-    | "switch" | "case" | "default" | "try" | "catch" | "throw" | "finally" | "funcy" | "return" | "async" | "await" | "const" | "func" | "entity" | "alters" | "me" | "new" | "import" | "export" | "from" | "type"=> {
+    | "switch" | "case" | "default" | "try" | "catch" | "throw" | "finally" | "defer" | "funcy" | "return" | "async" | "await" | "const" | "func" | "entity" | "alters" | "interface" | "implements" | "me" | "new" | "import" | "export" | "from" | "type" | "enum" | "match" | "as" => {
         TokenKind::Keyword(ident)
     }
     _ => TokenKind::Identifier(ident),
@@ -76,6 +110,7 @@ impl<'a> Lexer<'a> {
                         kind,
                         line: self.line,
                         col: self.col,
+                        span: self.span_from(start),
                     });
                 }
 
@@ -86,18 +121,27 @@ impl<'a> Lexer<'a> {
         kind: token,
         line: self.line,
         col: self.col,
+        span: self.span_from(start),
     });
 }
 
 
                 // --- Strings ---
                 '"' => {
-                    self.input.next(); // skip the opening quote
-                    let s = self.consume_string();
+                    self.bump(); // skip the opening quote
+                    let kind = if self.input.clone().take(2).eq(['"', '"']) {
+                        self.bump();
+                        self.bump();
+                        self.col += 2;
+                        TokenKind::String(self.consume_raw_string())
+                    } else {
+                        self.consume_string()
+                    };
                     tokens.push(Token {
-                        kind: TokenKind::String(s),
+                        kind,
                         line: self.line,
                         col: self.col,
+                        span: self.span_from(start),
                     });
                 }
 
@@ -108,19 +152,37 @@ _ => {
         // Peek ahead for comment start
         let mut iter = self.input.clone();
         iter.next(); // skip '/'
-        if let Some('/') = iter.next() {
-            // ✅ It's a comment — consume the whole line
-            self.input.next(); // skip first '/'
-            self.input.next(); // skip second '/'
-            self.col += 2;
-            while let Some(&c) = self.input.peek() {
-                if c == '\n' {
-                    break;
+        match iter.next() {
+            Some('/') => {
+                // ✅ It's a comment — consume the whole line
+                self.bump(); // skip first '/'
+                self.bump(); // skip second '/'
+                self.col += 2;
+                let mut text = String::new();
+                while let Some(&c) = self.input.peek() {
+                    if c == '\n' {
+                        break;
+                    }
+                    text.push(c);
+                    self.bump();
+                    self.col += 1;
                 }
-                self.input.next();
-                self.col += 1;
+                // 📝 `/// text` documents the next declaration; `////` is still a plain comment
+                if let Some(doc) = text.strip_prefix('/').filter(|rest| !rest.starts_with('/')) {
+                    tokens.push(Token {
+                        kind: TokenKind::DocComment(doc.strip_prefix(' ').unwrap_or(doc).to_string()),
+                        line: self.line,
+                        col: self.col,
+                        span: self.span_from(start),
+                    });
+                }
+                continue; // 🧠 skip comment entirely
             }
-            continue; // 🧠 skip comment entirely
+            Some('*') => {
+                self.skip_block_comment();
+                continue;
+            }
+            _ => {}
         }
     }
 
@@ -130,6 +192,7 @@ _ => {
         kind: TokenKind::Symbol(sym),
         line: self.line,
         col: self.col,
+        span: self.span_from(start),
     });
 }
 
@@ -140,6 +203,7 @@ _ => {
             kind: TokenKind::EOF,
             line: self.line,
             col: self.col,
+            span: self.span_from(self.offset),
         });
 
         tokens
@@ -156,7 +220,7 @@ _ => {
     while let Some(&c) = self.input.peek() {
         if is_identifier_continue(c) {
             ident.push(c);
-            self.input.next();
+            self.bump();
             self.col += 1;
         } else {
             break;
@@ -173,7 +237,7 @@ _ => {
         while let Some(&c) = self.input.peek() {
             if c.is_ascii_digit() {
                 number.push(c);
-                self.input.next();
+                self.bump();
                 self.col += 1;
             } else {
                 break;
@@ -182,16 +246,82 @@ _ => {
         number
     }
 
-    fn consume_string(&mut self) -> String {
+    /// Skip a `/* ... */` comment, which may contain nested block comments
+    fn skip_block_comment(&mut self) {
+        self.bump(); // skip '/'
+        self.bump(); // skip '*'
+        self.col += 2;
+        let mut depth = 1;
+        while let Some(c) = self.bump() {
+            self.col += 1;
+            match c {
+                '\n' => {
+                    self.line += 1;
+                    self.col = 0;
+                }
+                '/' if self.input.peek() == Some(&'*') => {
+                    self.bump();
+                    self.col += 1;
+                    depth += 1;
+                }
+                '*' if self.input.peek() == Some(&'/') => {
+                    self.bump();
+                    self.col += 1;
+                    depth -= 1;
+                    if depth == 0 {
+                        return;
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// The rest of a `"""` string: taken verbatim, without escapes or interpolation, up
+    /// to the closing `"""`. A line break right after the opening quotes is dropped.
+    fn consume_raw_string(&mut self) -> String {
+        let mut result = String::new();
+        if self.input.peek() == Some(&'\n') {
+            self.bump();
+            self.line += 1;
+            self.col = 0;
+        }
+
+        while let Some(c) = self.bump() {
+            self.col += 1;
+            if c == '"' && self.input.clone().take(2).eq(['"', '"']) {
+                self.bump();
+                self.bump();
+                self.col += 2;
+                break;
+            }
+            if c == '\n' {
+                self.line += 1;
+                self.col = 0;
+            }
+            result.push(c);
+        }
+        result
+    }
+
+    /// The rest of a string literal; `${expr}` splits it into text and code parts
+    fn consume_string(&mut self) -> TokenKind {
     let mut result = String::new();
+    let mut parts = Vec::new();
 
-    while let Some(c) = self.input.next() {
+    while let Some(c) = self.bump() {
         self.col += 1;
 
         match c {
             '"' => break, // closing quote
+            '$' if self.input.peek() == Some(&'{') => {
+                self.bump();
+                self.col += 1;
+                parts.push(StringPart::Text(std::mem::take(&mut result)));
+                parts.push(StringPart::Code(self.consume_interpolation()));
+            }
             '\\' => {
-                if let Some(next) = self.input.next() {
+                if let Some(next) = self.bump() {
                     self.col += 1;
                     match next {
                         'n' => result.push('\n'),
@@ -201,6 +331,7 @@ _ => {
                         '"' => result.push('"'),
                         '\'' => result.push('\''),
                         '\\' => result.push('\\'),
+                        '$' => result.push('$'),
                         // ✅ Preserve unknown escapes as literal
                         other => {
                             result.push('\\');
@@ -216,19 +347,63 @@ _ => {
         }
     }
 
-    result
+    if parts.is_empty() {
+        return TokenKind::String(result);
+    }
+    parts.push(StringPart::Text(result));
+    TokenKind::InterpolatedString(parts)
 }
 
+    /// Tokenize the expression inside `${ ... }` up to its closing brace, keeping the
+    /// spans relative to the whole file
+    fn consume_interpolation(&mut self) -> Vec<Token> {
+        let (start, line, col) = (self.offset, self.line, self.col);
+        let mut code = String::new();
+        let mut depth = 0;
+        let mut in_string = false;
+
+        while let Some(c) = self.bump() {
+            self.col += 1;
+            match c {
+                '\\' if in_string => {
+                    code.push(c);
+                    if let Some(next) = self.bump() {
+                        self.col += 1;
+                        code.push(next);
+                    }
+                    continue;
+                }
+                '"' => in_string = !in_string,
+                '{' if !in_string => depth += 1,
+                '}' if !in_string && depth == 0 => break,
+                '}' if !in_string => depth -= 1,
+                _ => {}
+            }
+            code.push(c);
+        }
+
+        let mut inner = Lexer::with_file(&code, self.file);
+        inner.offset = start;
+        inner.line = line;
+        inner.col = col;
+        inner.tokenize()
+    }
+
 
     fn consume_symbol(&mut self) -> String {
-        let ch = self.input.next().unwrap();
+        let ch = self.bump().unwrap();
         self.col += 1;
 
         // Handle two-character operators
         if let Some(&next) = self.input.peek() {
             let pair = format!("{}{}", ch, next);
-            if ["==", "!=", "<=", ">="].contains(&pair.as_str()) {
-                self.input.next();
+            if [
+                "==", "!=", "<=", ">=", "..", "=>", "&&", "||", "<<", ">>",
+                "+=", "-=", "*=", "/=", "++", "--",
+            ]
+            .contains(&pair.as_str())
+            {
+                self.bump();
                 self.col += 1;
                 return pair;
             }
@@ -245,12 +420,18 @@ _ => {
     while let Some(&c) = self.input.peek() {
         if c.is_ascii_digit() {
             num_str.push(c);
-            self.input.next();
+            self.bump();
             self.col += 1;
         } else if c == '.' && !is_float {
+            // `0..n` is a range, not the float `0.`
+            let mut ahead = self.input.clone();
+            ahead.next();
+            if ahead.peek() == Some(&'.') {
+                break;
+            }
             is_float = true;
             num_str.push(c);
-            self.input.next();
+            self.bump();
             self.col += 1;
         } else {
             break;
@@ -264,7 +445,7 @@ _ => {
             while let Some(&next) = self.input.peek() {
                 if next.is_ascii_alphanumeric() {
                     type_str.push(next);
-                    self.input.next();
+                    self.bump();
                     self.col += 1;
                 } else {
                     break;
@@ -343,4 +524,40 @@ fn test_utf8_identifiers() {
     assert_eq!(idents, vec!["🦥", "変数", "привет"]);
 }
 
+    #[test]
+    fn test_string_interpolation_parts() {
+        let src = r#""a ${n + 1} {b} \${c}""#;
+        let tokens = Lexer::new(src).tokenize();
+        let TokenKind::InterpolatedString(parts) = &tokens[0].kind else { panic!("expected an interpolated string") };
+        assert_eq!(parts.len(), 3);
+        assert!(matches!(&parts[0], StringPart::Text(t) if t == "a "));
+        assert!(matches!(&parts[2], StringPart::Text(t) if t == " {b} ${c}"));
+
+        // Embedded tokens keep their offsets in the enclosing source
+        let StringPart::Code(code) = &parts[1] else { panic!("expected code") };
+        assert!(matches!(&code[0].kind, TokenKind::Identifier(n) if n == "n"));
+        assert_eq!(&src[code[0].span.start..code[0].span.end], "n");
+        assert!(matches!(code.last().map(|t| &t.kind), Some(TokenKind::EOF)));
+    }
+
+    #[test]
+    fn test_comments_and_raw_strings() {
+        let src = "/* a /* nested */ b */ x //// rule\n/// Doc line\nlet q = \"\"\"\nSELECT \"id\" -- ${raw}\\n\n\"\"\";";
+        let mut lexer = Lexer::new(src);
+        let kinds: Vec<_> = lexer.tokenize().into_iter().map(|t| t.kind).collect();
+        assert!(matches!(&kinds[0], TokenKind::Identifier(s) if s == "x"));
+        assert!(matches!(&kinds[1], TokenKind::DocComment(s) if s == "Doc line"));
+        assert!(matches!(&kinds[2], TokenKind::Keyword(s) if s == "let"));
+        assert!(matches!(&kinds[5], TokenKind::String(s) if s == "SELECT \"id\" -- ${raw}\\n\n"));
+        assert_eq!(lexer.line, 5);
+    }
+
+    #[test]
+    fn test_range_is_not_a_float() {
+        let kinds: Vec<_> = Lexer::new("0..n 1.5").tokenize().into_iter().map(|t| t.kind).collect();
+        assert!(matches!(&kinds[0], TokenKind::Number { raw, .. } if raw == "0"));
+        assert!(matches!(&kinds[1], TokenKind::Symbol(s) if s == ".."));
+        assert!(matches!(&kinds[2], TokenKind::Identifier(s) if s == "n"));
+        assert!(matches!(&kinds[3], TokenKind::Number { raw, ty } if raw == "1.5" && ty == "f64"));
+    }
 }
@@ -1,4 +1,6 @@
 pub mod ast;
+pub mod diagnostics;
+pub mod checker;
 pub mod parser;
 mod macros; // defines wpp_debug! macro
 pub mod codegen;
@@ -12,19 +14,18 @@ use std::io::Write;
 
 use inkwell::context::Context;
 use inkwell::execution_engine::ExecutionEngine;
-use inkwell::llvm_sys::core::LLVMDeleteGlobal;
-use inkwell::llvm_sys::prelude::LLVMValueRef;
 use inkwell::llvm_sys::support::LLVMAddSymbol;
 use inkwell::module::Module;
 use inkwell::passes::PassManager;
-use inkwell::values::{AnyValue, AsValueRef, GlobalValue};
+use inkwell::values::AnyValue;
 use inkwell::OptimizationLevel;
 use libc::{malloc, printf};
 
 use crate::codegen::Codegen;
+use crate::diagnostics::CompileError;
 use crate::lexer::Lexer;
 use crate::parser::Parser;
-use crate::runtime::thread::{wpp_mutex_lock, wpp_mutex_new, wpp_mutex_unlock, wpp_thread_join, wpp_thread_join_all, wpp_thread_poll, wpp_thread_spawn_gc, wpp_thread_state_get, wpp_thread_state_new, wpp_thread_state_set};
+use crate::runtime::thread::{wpp_mutex_lock, wpp_mutex_new, wpp_mutex_unlock, wpp_thread_join, wpp_thread_join_all, wpp_thread_poll, wpp_thread_spawn_closure, wpp_thread_spawn_gc, wpp_thread_state_get, wpp_thread_state_new, wpp_thread_state_set};
 use runtime::*;
 
 // wpp_debug! macro is defined in macros.rs
@@ -35,12 +36,10 @@ unsafe extern "C" {
     pub fn wpp_print_value_basic(ptr: *const std::ffi::c_void, type_id: i32);
     pub fn wpp_print_array(ptr: *const std::ffi::c_void);
     pub fn wpp_print_object(ptr: *const std::ffi::c_void);
+    pub fn wpp_print_enum(ptr: *const std::ffi::c_void);
     pub fn wpp_readline() -> *const std::os::raw::c_char;
     pub fn wpp_int_to_string(value: i32) -> *const std::os::raw::c_char;
-}
-unsafe fn remove_global(global: &GlobalValue) {
-    let raw: LLVMValueRef = global.as_value_ref();
-    unsafe { LLVMDeleteGlobal(raw) };
+    pub fn wpp_format(fmt: *const std::os::raw::c_char, argc: i32, args: *const std::ffi::c_void) -> *const std::os::raw::c_char;
 }
 unsafe fn add_symbol(name: &str, addr: usize) {
     let cname = CString::new(name).unwrap();
@@ -59,17 +58,62 @@ fn declare_runtime_externals<'ctx>(context: &'ctx Context, module: &Module<'ctx>
         ("wpp_print_value_basic", void_type.fn_type(&[i8_ptr.into(), i32_type.into()], false)),
         ("wpp_print_array", void_type.fn_type(&[i8_ptr.into()], false)),
         ("wpp_print_object", void_type.fn_type(&[i8_ptr.into()], false)),
+        ("wpp_print_enum", void_type.fn_type(&[i8_ptr.into()], false)),
 
         // --- String subsystem ---
         ("wpp_str_concat", i8_ptr.fn_type(&[i8_ptr.into(), i8_ptr.into()], false)),
         ("wpp_readline", i8_ptr.fn_type(&[], false)),
         ("wpp_int_to_string", i8_ptr.fn_type(&[i32_type.into()], false)),
+        ("wpp_format", i8_ptr.fn_type(&[i8_ptr.into(), i32_type.into(), i8_ptr.into()], false)),
         ("wpp_str_substr", i8_ptr.fn_type(&[i8_ptr.into(), i32_type.into(), i32_type.into()], false)),
         ("wpp_str_index_of", i32_type.fn_type(&[i8_ptr.into(), i8_ptr.into()], false)),
         ("wpp_str_replace", i8_ptr.fn_type(&[i8_ptr.into(), i8_ptr.into(), i8_ptr.into()], false)),
         ("wpp_str_to_upper", i8_ptr.fn_type(&[i8_ptr.into()], false)),
         ("wpp_str_to_lower", i8_ptr.fn_type(&[i8_ptr.into()], false)),
         ("wpp_str_trim", i8_ptr.fn_type(&[i8_ptr.into()], false)),
+        ("wpp_str_new", i8_ptr.fn_type(&[i8_ptr.into(), i32_type.into()], false)),
+        ("wpp_str_len", i32_type.fn_type(&[i8_ptr.into()], false)),
+        ("wpp_str_byte_len", i32_type.fn_type(&[i8_ptr.into()], false)),
+        ("wpp_str_cmp", i32_type.fn_type(&[i8_ptr.into(), i8_ptr.into()], false)),
+        ("wpp_str_retain", void_type.fn_type(&[i8_ptr.into()], false)),
+        ("wpp_str_release", void_type.fn_type(&[i8_ptr.into()], false)),
+        ("wpp_str_register_literal", void_type.fn_type(&[i8_ptr.into()], false)),
+
+        // --- Heap ---
+        ("wpp_gc_alloc", i8_ptr.fn_type(&[i64_type.into()], false)),
+        ("wpp_gc_is_data", i32_type.fn_type(&[i8_ptr.into()], false)),
+
+        // --- Exceptions ---
+        ("wpp_index_error", i8_ptr.fn_type(&[i32_type.into(), i32_type.into()], false)),
+        ("wpp_key_error", i8_ptr.fn_type(&[i8_ptr.into()], false)),
+        ("wpp_throw", void_type.fn_type(&[i8_ptr.into()], false)),
+        ("wpp_throw_message", void_type.fn_type(&[i8_ptr.into()], false)),
+        ("wpp_exception_pending", i32_type.fn_type(&[], false)),
+        ("wpp_exception_value", i8_ptr.fn_type(&[], false)),
+        ("wpp_exception_catch", i8_ptr.fn_type(&[], false)),
+        ("wpp_exception_save", i8_ptr.fn_type(&[], false)),
+        ("wpp_exception_restore", void_type.fn_type(&[i8_ptr.into()], false)),
+        ("wpp_exception_uncaught", void_type.fn_type(&[], false)),
+        ("wpp_frame_push", void_type.fn_type(&[i8_ptr.into()], false)),
+        ("wpp_frame_pop", void_type.fn_type(&[], false)),
+        ("wpp_frame_line", void_type.fn_type(&[i32_type.into()], false)),
+
+        // --- Array subsystem ---
+        ("wpp_vec_new", i8_ptr.fn_type(&[i32_type.into(), i32_type.into()], false)),
+        ("wpp_vec_push", i32_type.fn_type(&[i8_ptr.into(), i64_type.into()], false)),
+        ("wpp_vec_pop", i64_type.fn_type(&[i8_ptr.into()], false)),
+        ("wpp_vec_insert", void_type.fn_type(&[i8_ptr.into(), i32_type.into(), i64_type.into()], false)),
+        ("wpp_vec_remove", i64_type.fn_type(&[i8_ptr.into(), i32_type.into()], false)),
+        ("wpp_vec_slice", i8_ptr.fn_type(&[i8_ptr.into(), i32_type.into(), i32_type.into()], false)),
+        ("wpp_vec_concat", i8_ptr.fn_type(&[i8_ptr.into(), i8_ptr.into()], false)),
+        ("wpp_map_new", i8_ptr.fn_type(&[i32_type.into()], false)),
+        ("wpp_map_set", i32_type.fn_type(&[i8_ptr.into(), i8_ptr.into(), i64_type.into()], false)),
+        ("wpp_map_find", i32_type.fn_type(&[i8_ptr.into(), i8_ptr.into()], false)),
+        ("wpp_map_value_at", i64_type.fn_type(&[i8_ptr.into(), i32_type.into()], false)),
+        ("wpp_map_key_at", i8_ptr.fn_type(&[i8_ptr.into(), i32_type.into()], false)),
+        ("wpp_map_delete", i32_type.fn_type(&[i8_ptr.into(), i8_ptr.into()], false)),
+        ("wpp_map_keys", i8_ptr.fn_type(&[i8_ptr.into()], false)),
+        ("wpp_map_values", i8_ptr.fn_type(&[i8_ptr.into()], false)),
 
         // --- Validation subsystem ---
         ("validation_errors_create", i8_ptr.fn_type(&[], false)),
@@ -104,10 +148,13 @@ fn declare_runtime_externals<'ctx>(context: &'ctx Context, module: &Module<'ctx>
         ("wpp_http_headers", i8_ptr.fn_type(&[i32_type.into()], false)),
         ("wpp_http_free_all", void_type.fn_type(&[], false)),
         ("wpp_register_endpoint", void_type.fn_type(&[i8_ptr.into(), i8_ptr.into()], false)),
+        ("wpp_register_closure_endpoint", void_type.fn_type(&[i8_ptr.into(), i8_ptr.into()], false)),
         ("wpp_start_server", void_type.fn_type(&[i32_type.into()], false)),
 
         // --- Threading subsystem ---
         ("wpp_thread_spawn_gc", i8_ptr.fn_type(&[i8_ptr.into()], false)),
+        ("wpp_thread_spawn_closure", i8_ptr.fn_type(&[i8_ptr.into()], false)),
+        ("wpp_closure_new", i8_ptr.fn_type(&[i8_ptr.into(), i8_ptr.into()], false)),
         ("wpp_thread_join", void_type.fn_type(&[i8_ptr.into()], false)),
         ("wpp_thread_poll", i32_type.fn_type(&[i8_ptr.into()], false)),
         ("wpp_thread_state_new", i8_ptr.fn_type(&[i32_type.into()], false)),
@@ -141,17 +188,62 @@ fn register_all_runtime_symbols() {
         add_symbol("wpp_print_value_basic", wpp_print_value_basic as usize);
         add_symbol("wpp_print_array", wpp_print_array as usize);
         add_symbol("wpp_print_object", wpp_print_object as usize);
+        add_symbol("wpp_print_enum", wpp_print_enum as usize);
 
         // --- String subsystem ---
         add_symbol("wpp_str_concat", wpp_str_concat as usize);
         add_symbol("wpp_readline", wpp_readline as usize);
         add_symbol("wpp_int_to_string", wpp_int_to_string as usize);
+        add_symbol("wpp_format", wpp_format as *const () as usize);
         add_symbol("wpp_str_substr", wpp_str_substr as usize);
         add_symbol("wpp_str_index_of", wpp_str_index_of as usize);
         add_symbol("wpp_str_replace", wpp_str_replace as usize);
         add_symbol("wpp_str_to_upper", wpp_str_to_upper as usize);
         add_symbol("wpp_str_to_lower", wpp_str_to_lower as usize);
         add_symbol("wpp_str_trim", wpp_str_trim as usize);
+        add_symbol("wpp_str_new", wpp_str_new as *const () as usize);
+        add_symbol("wpp_str_len", wpp_str_len as *const () as usize);
+        add_symbol("wpp_str_byte_len", wpp_str_byte_len as *const () as usize);
+        add_symbol("wpp_str_cmp", wpp_str_cmp as *const () as usize);
+        add_symbol("wpp_str_retain", wpp_str_retain as *const () as usize);
+        add_symbol("wpp_str_release", wpp_str_release as *const () as usize);
+        add_symbol("wpp_str_register_literal", wpp_str_register_literal as *const () as usize);
+
+        // --- Heap ---
+        add_symbol("wpp_gc_alloc", wpp_gc_alloc as *const () as usize);
+        add_symbol("wpp_gc_is_data", wpp_gc_is_data as *const () as usize);
+
+        // --- Exceptions ---
+        add_symbol("wpp_index_error", wpp_index_error as *const () as usize);
+        add_symbol("wpp_key_error", wpp_key_error as *const () as usize);
+        add_symbol("wpp_throw", wpp_throw as *const () as usize);
+        add_symbol("wpp_throw_message", wpp_throw_message as *const () as usize);
+        add_symbol("wpp_exception_pending", wpp_exception_pending as *const () as usize);
+        add_symbol("wpp_exception_value", wpp_exception_value as *const () as usize);
+        add_symbol("wpp_exception_catch", wpp_exception_catch as *const () as usize);
+        add_symbol("wpp_exception_save", wpp_exception_save as *const () as usize);
+        add_symbol("wpp_exception_restore", wpp_exception_restore as *const () as usize);
+        add_symbol("wpp_exception_uncaught", wpp_exception_uncaught as *const () as usize);
+        add_symbol("wpp_frame_push", wpp_frame_push as *const () as usize);
+        add_symbol("wpp_frame_pop", wpp_frame_pop as *const () as usize);
+        add_symbol("wpp_frame_line", wpp_frame_line as *const () as usize);
+
+        // --- Array subsystem ---
+        add_symbol("wpp_vec_new", wpp_vec_new as *const () as usize);
+        add_symbol("wpp_vec_push", wpp_vec_push as *const () as usize);
+        add_symbol("wpp_vec_pop", wpp_vec_pop as *const () as usize);
+        add_symbol("wpp_vec_insert", wpp_vec_insert as *const () as usize);
+        add_symbol("wpp_vec_remove", wpp_vec_remove as *const () as usize);
+        add_symbol("wpp_vec_slice", wpp_vec_slice as *const () as usize);
+        add_symbol("wpp_vec_concat", wpp_vec_concat as *const () as usize);
+        add_symbol("wpp_map_new", wpp_map_new as *const () as usize);
+        add_symbol("wpp_map_set", wpp_map_set as *const () as usize);
+        add_symbol("wpp_map_find", wpp_map_find as *const () as usize);
+        add_symbol("wpp_map_value_at", wpp_map_value_at as *const () as usize);
+        add_symbol("wpp_map_key_at", wpp_map_key_at as *const () as usize);
+        add_symbol("wpp_map_delete", wpp_map_delete as *const () as usize);
+        add_symbol("wpp_map_keys", wpp_map_keys as *const () as usize);
+        add_symbol("wpp_map_values", wpp_map_values as *const () as usize);
 
         // --- Validation subsystem ---
         add_symbol("validation_errors_create", validation_errors_create as usize);
@@ -186,10 +278,13 @@ fn register_all_runtime_symbols() {
         add_symbol("wpp_http_headers", wpp_http_headers as usize);
         add_symbol("wpp_http_free_all", wpp_http_free_all as usize);
         add_symbol("wpp_register_endpoint", wpp_register_endpoint as usize);
+        add_symbol("wpp_register_closure_endpoint", wpp_register_closure_endpoint as *const () as usize);
         add_symbol("wpp_start_server", wpp_start_server as usize);
 
         // --- Threading subsystem ---
         add_symbol("wpp_thread_spawn_gc", wpp_thread_spawn_gc as usize);
+        add_symbol("wpp_thread_spawn_closure", wpp_thread_spawn_closure as *const () as usize);
+        add_symbol("wpp_closure_new", wpp_closure_new as *const () as usize);
         add_symbol("wpp_thread_join", wpp_thread_join as usize);
         add_symbol("wpp_thread_poll", wpp_thread_poll as usize);
         add_symbol("wpp_thread_state_new", wpp_thread_state_new as usize);
@@ -232,17 +327,62 @@ fn register_runtime_symbols<'ctx>(engine: &ExecutionEngine<'ctx>, module: &Modul
         map_fn("wpp_print_value_basic", wpp_print_value_basic as usize);
         map_fn("wpp_print_array", wpp_print_array as usize);
         map_fn("wpp_print_object", wpp_print_object as usize);
+        map_fn("wpp_print_enum", wpp_print_enum as usize);
         map_fn("wpp_readline", wpp_readline as usize);
 
         // === String subsystem ===
         map_fn("wpp_str_concat", wpp_str_concat as usize);
         map_fn("wpp_int_to_string", wpp_int_to_string as usize);
+        map_fn("wpp_format", wpp_format as *const () as usize);
         map_fn("wpp_str_substr", wpp_str_substr as usize);
         map_fn("wpp_str_index_of", wpp_str_index_of as usize);
         map_fn("wpp_str_replace", wpp_str_replace as usize);
         map_fn("wpp_str_to_upper", wpp_str_to_upper as usize);
         map_fn("wpp_str_to_lower", wpp_str_to_lower as usize);
         map_fn("wpp_str_trim", wpp_str_trim as usize);
+        map_fn("wpp_str_new", wpp_str_new as *const () as usize);
+        map_fn("wpp_str_len", wpp_str_len as *const () as usize);
+        map_fn("wpp_str_byte_len", wpp_str_byte_len as *const () as usize);
+        map_fn("wpp_str_cmp", wpp_str_cmp as *const () as usize);
+        map_fn("wpp_str_retain", wpp_str_retain as *const () as usize);
+        map_fn("wpp_str_release", wpp_str_release as *const () as usize);
+        map_fn("wpp_str_register_literal", wpp_str_register_literal as *const () as usize);
+
+        // === Heap ===
+        map_fn("wpp_gc_alloc", wpp_gc_alloc as *const () as usize);
+        map_fn("wpp_gc_is_data", wpp_gc_is_data as *const () as usize);
+
+        // === Exceptions ===
+        map_fn("wpp_index_error", wpp_index_error as *const () as usize);
+        map_fn("wpp_key_error", wpp_key_error as *const () as usize);
+        map_fn("wpp_throw", wpp_throw as *const () as usize);
+        map_fn("wpp_throw_message", wpp_throw_message as *const () as usize);
+        map_fn("wpp_exception_pending", wpp_exception_pending as *const () as usize);
+        map_fn("wpp_exception_value", wpp_exception_value as *const () as usize);
+        map_fn("wpp_exception_catch", wpp_exception_catch as *const () as usize);
+        map_fn("wpp_exception_save", wpp_exception_save as *const () as usize);
+        map_fn("wpp_exception_restore", wpp_exception_restore as *const () as usize);
+        map_fn("wpp_exception_uncaught", wpp_exception_uncaught as *const () as usize);
+        map_fn("wpp_frame_push", wpp_frame_push as *const () as usize);
+        map_fn("wpp_frame_pop", wpp_frame_pop as *const () as usize);
+        map_fn("wpp_frame_line", wpp_frame_line as *const () as usize);
+
+        // === Array subsystem ===
+        map_fn("wpp_vec_new", wpp_vec_new as *const () as usize);
+        map_fn("wpp_vec_push", wpp_vec_push as *const () as usize);
+        map_fn("wpp_vec_pop", wpp_vec_pop as *const () as usize);
+        map_fn("wpp_vec_insert", wpp_vec_insert as *const () as usize);
+        map_fn("wpp_vec_remove", wpp_vec_remove as *const () as usize);
+        map_fn("wpp_vec_slice", wpp_vec_slice as *const () as usize);
+        map_fn("wpp_vec_concat", wpp_vec_concat as *const () as usize);
+        map_fn("wpp_map_new", wpp_map_new as *const () as usize);
+        map_fn("wpp_map_set", wpp_map_set as *const () as usize);
+        map_fn("wpp_map_find", wpp_map_find as *const () as usize);
+        map_fn("wpp_map_value_at", wpp_map_value_at as *const () as usize);
+        map_fn("wpp_map_key_at", wpp_map_key_at as *const () as usize);
+        map_fn("wpp_map_delete", wpp_map_delete as *const () as usize);
+        map_fn("wpp_map_keys", wpp_map_keys as *const () as usize);
+        map_fn("wpp_map_values", wpp_map_values as *const () as usize);
 
         // === Validation subsystem ===
         map_fn("validation_errors_create", validation_errors_create as usize);
@@ -277,10 +417,13 @@ fn register_runtime_symbols<'ctx>(engine: &ExecutionEngine<'ctx>, module: &Modul
         map_fn("wpp_http_headers", wpp_http_headers as usize);
         map_fn("wpp_http_free_all", wpp_http_free_all as usize);
         map_fn("wpp_register_endpoint", wpp_register_endpoint as usize);
+        map_fn("wpp_register_closure_endpoint", wpp_register_closure_endpoint as *const () as usize);
         map_fn("wpp_start_server", wpp_start_server as usize);
 
         // === Threading subsystem ===
         map_fn("wpp_thread_spawn_gc", wpp_thread_spawn_gc as usize);
+        map_fn("wpp_thread_spawn_closure", wpp_thread_spawn_closure as *const () as usize);
+        map_fn("wpp_closure_new", wpp_closure_new as *const () as usize);
         map_fn("wpp_thread_join", wpp_thread_join as usize);
         map_fn("wpp_thread_poll", wpp_thread_poll as usize);
         map_fn("wpp_thread_state_new", wpp_thread_state_new as usize);
@@ -309,7 +452,7 @@ fn register_runtime_symbols<'ctx>(engine: &ExecutionEngine<'ctx>, module: &Modul
 
 
 /// 🦥 Run a W++ source file using the LLVM JIT engine
-pub fn run_file(codegen: &mut Codegen, optimize: bool) -> Result<(), String> {
+pub fn run_file(codegen: &mut Codegen, optimize: bool) -> Result<(), CompileError> {
     use std::mem;
     use inkwell::passes::PassManager;
     use inkwell::OptimizationLevel;
@@ -361,18 +504,6 @@ pub fn run_file(codegen: &mut Codegen, optimize: bool) -> Result<(), String> {
                         };
 
                         if let Ok(main_mod) = codegen.context.create_module_from_ir(mem_buf) {
-                            // 🧹 Remove duplicate globals
-                            for global_name in ["_wpp_exc_flag", "_wpp_exc_i32", "_wpp_exc_str"] {
-                                if let Some(global) = main_mod.get_global(global_name) {
-                                    #[cfg(debug_assertions)]
-                                    println!(
-                                        "🔧 [wms] Neutralized duplicate global '{}' in reloaded main module",
-                                        global_name
-                                    );
-                                    unsafe { remove_global(&global); }
-                                }
-                            }
-
                             // 🧹 Also remove duplicate main_async before link
                             if let Some(existing) = codegen.module.get_function("main_async") {
                                 println!("🧹 Removing old main_async before reattaching cached main");
@@ -435,7 +566,7 @@ pub fn run_file(codegen: &mut Codegen, optimize: bool) -> Result<(), String> {
     // === Create JIT engine ===
     let engine = module
         .create_jit_execution_engine(OptimizationLevel::None)
-        .map_err(|e| format!("JIT init failed: {e:?}"))?;
+        .map_err(|e| CompileError::Backend(format!("JIT init failed: {e:?}")))?;
     // === Optional: Link native Rust modules (.so/.dylib) ===
 {
     println!("🧩 Linking Rust modules into JIT context...");
@@ -496,7 +627,7 @@ pub fn run_file(codegen: &mut Codegen, optimize: bool) -> Result<(), String> {
         "main_async"
     } else {
         eprintln!("❌ No valid entrypoint found (expected main, main_async, or bootstrap_main)");
-        return Err("❌ No entrypoint function found in final linked module".into());
+        return Err(CompileError::Backend("❌ No entrypoint function found in final linked module".into()));
     };
     wpp_debug!("🪶 [debug7] Entrypoint resolved to {entry_name}");
     #[cfg(debug_assertions)]
@@ -553,7 +684,7 @@ println!("{}", module.print_to_string().to_string());
 
         let addr = engine
             .get_function_address(entry_name)
-            .map_err(|_| format!("❌ No entrypoint function found: {entry_name}"))?;
+            .map_err(|_| CompileError::Backend(format!("❌ No entrypoint function found: {entry_name}")))?;
 
         if std::env::var("WPP_DEBUG").ok().as_deref() == Some("1") {
             eprintln!("🔍 [debug] Function address: 0x{:x}", addr);
@@ -562,13 +693,14 @@ println!("{}", module.print_to_string().to_string());
         }
 
         let func: extern "C" fn() -> i32 = std::mem::transmute(addr);
+        engine.run_static_constructors();
 
         if std::env::var("WPP_DEBUG").ok().as_deref() == Some("1") {
             eprintln!("🔍 [debug] Transmute successful, calling function NOW...");
             std::io::stderr().flush().unwrap();
         }
 
-        let result = func();
+        let result = runtime::gc::run_mutator(|| func());
         println!("✅ [jit] Returned cleanly from `{entry_name}` with result = {result}");
         println!("🏁 Finished running {entry_name}, result = {result}");
     }
@@ -584,10 +716,19 @@ pub fn build_ir(source: &str, optimize: bool) -> Result<String, String> {
     let mut lexer = Lexer::new(source);
     let tokens = lexer.tokenize();
     let mut parser = Parser::new(tokens);
-    let nodes = parser.parse_program();
+    let nodes = parser
+        .parse_program()
+        .map_err(|diags| diagnostics::format_all(&diags, source))?;
+
+    let type_errors = checker::check_program(&nodes);
+    if !type_errors.is_empty() {
+        return Err(diagnostics::format_all(&type_errors, source));
+    }
 
     let mut codegen = Codegen::new(&context, "wpp_module", "./src");
-    codegen.compile_main(&nodes);
+    codegen
+        .compile_main(&nodes)
+        .map_err(|e| diagnostics::Diagnostic::from(e).render_plain(source))?;
 
     if optimize {
         let pass_manager = PassManager::create(());
@@ -603,3 +744,123 @@ pub fn build_ir(source: &str, optimize: bool) -> Result<String, String> {
 
     Ok(codegen.module.print_to_string().to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compile and verify `src`, JIT it the way `run_file` does and return the value its top-level
+    /// code `return`s
+    fn run(src: &str) -> i32 {
+        let context = Context::create();
+        let mut codegen = Codegen::new(&context, "main", "./src");
+        codegen.compile_main(&parser::parse(src).expect("test source should parse")).unwrap();
+        declare_runtime_externals(&context, &codegen.module);
+        codegen.module.verify().unwrap_or_else(|e| panic!("{}", e.to_string()));
+        register_all_runtime_symbols();
+        let engine = codegen.module.create_jit_execution_engine(OptimizationLevel::None).unwrap();
+        register_runtime_symbols(&engine, &codegen.module);
+        engine.run_static_constructors();
+        unsafe {
+            let main = engine.get_function::<unsafe extern "C" fn() -> i32>("main").unwrap();
+            runtime::gc::run_mutator(|| main.call())
+        }
+    }
+
+    #[test]
+    fn test_entities_without_methods_are_told_apart() {
+        let src = "entity AppError {}\nentity NotFound alters AppError {}\ntry {\n  throw new(AppError)\n} catch (e: NotFound) {\n  return 1\n} catch (e: AppError) {\n  return 2\n}\nreturn 0";
+        assert_eq!(run(src), 2);
+    }
+
+    #[test]
+    fn test_typed_catch_across_functions() {
+        let src = "entity AppError { message = \"\" }\nentity NotFound alters AppError {}\nfuncy find(key: str) { throw new(NotFound) }\nfuncy lookup(key: str) { find(key) }\nfuncy fail() { throw \"boom\" }\nlet found = 0\ntry {\n  lookup(\"key\")\n} catch (e: NotFound) {\n  found = 1\n} catch (e: AppError) {\n  found = 2\n} catch (e) {\n  found = 3\n}\nlet failed = 0\ntry {\n  fail()\n} catch (e: AppError) {\n  failed = 1\n} catch (e: str) {\n  failed = 2\n}\nreturn found * 10 + failed";
+        assert_eq!(run(src), 12);
+    }
+
+    #[test]
+    fn test_returned_closures_keep_their_state() {
+        let src = "funcy makeCounter(start) {\n  let count = start\n  return funcy() {\n    count = count + 1\n    return count\n  }\n}\nlet next = makeCounter(5)\nlet first = next()\nlet n = 10\nlet addN = funcy(x) { return x + n }\nn = 100\nreturn first * 1000 + next() * 100 + addN(1)";
+        assert_eq!(run(src), 6711);
+    }
+
+    #[test]
+    fn test_runtime_dispatch_runs_the_matching_overload() {
+        let src = "entity Dog {}\nfuncy describe(x: i32) {\n  print(\"int\", x)\n  return x\n}\nfuncy describe(x: str) {\n  print(\"string\", x)\n  return 20\n}\nfuncy describe(d: Dog) {\n  print(\"a dog\")\n  return 300\n}\nfuncy handle(code: 2xx) {\n  print(\"success\")\n  return 1000\n}\nfuncy handle(code: 404) {\n  print(\"not found\")\n  return 4000\n}\nlet total = 0\nlet items: any[] = [1, \"two\", new(Dog)]\nfor item in items { total = total + describe(item) }\nlet codes = [200, 404]\nfor c in codes { total = total + handle(c) }\nreturn total";
+        assert_eq!(run(src), 5321);
+    }
+
+    #[test]
+    fn test_call_results_pick_overloads_by_what_they_return() {
+        let src = "entity Animal {\n  funcy twin() { return new(Animal) }\n}\nentity Dog alters Animal {}\nfuncy describe(x: i32) { return 1 }\nfuncy describe(a: Animal) { return 2 }\nfuncy describe(d: Dog) { return 3 }\nfuncy describe(s: str) { return 4 }\nfuncy mk() { return new(Dog) }\nfuncy pick() -> Animal { return new(Dog) }\nlet a = new(Animal)\nlet s = \"hi\"\nreturn describe(mk()) * 1000 + describe(pick()) * 100 + describe(a.twin()) * 10 + describe(s)";
+        assert_eq!(run(src), 3324);
+    }
+
+    #[test]
+    fn test_for_in_over_entities_calls_their_methods() {
+        let src = "entity Animal {\n  funcy speak() { return 1 }\n}\nentity Dog alters Animal {\n  funcy speak() { return 2 }\n}\nlet animals: Animal[] = [new(Animal), new(Dog)]\nlet total = 0\nfor a in animals { total = total * 10 + a.speak() }\nreturn total";
+        assert_eq!(run(src), 12);
+    }
+
+    #[test]
+    fn test_interface_calls_reach_each_implementer() {
+        let src = "interface Speaker { speak() }\nentity Parrot implements Speaker {\n  funcy speak() { return 1 }\n}\nentity Robot implements Speaker {\n  funcy speak() { return 2 }\n}\nfuncy greet(s: Speaker) -> i32 { return s.speak() }\nlet s: Speaker = new(Robot)\nreturn greet(new(Parrot)) * 100 + greet(s) * 10 + s.speak()";
+        assert_eq!(run(src), 122);
+    }
+
+    #[test]
+    fn test_object_literals_format_each_field_by_its_type() {
+        let src = "let o = {a: 1, b: \"x\", c: 2.5, d: true}\nlet s = format(\"{}\", o)\nlet total = 0\nif s == \"{a: 1, b: \\\"x\\\", c: 2.5, d: true}\" { total = total + 1 }\nif s == \"${o}\" { total = total + 10 }\nreturn total";
+        assert_eq!(run(src), 11);
+    }
+
+    #[test]
+    fn test_out_of_bounds_indexes_throw() {
+        let src = "let a = [1, 2, 3]\na[0] = a[2]\nlet caught = 0\ntry {\n  let x = a[3]\n} catch (e) {\n  caught = caught + 1\n}\ntry {\n  a[-1] = 9\n} catch (e) {\n  caught = caught + 10\n}\nreturn a[0] * 1000 + len(a) * 100 + caught";
+        assert_eq!(run(src), 3311);
+    }
+
+    #[test]
+    fn test_typed_and_nested_arrays_keep_their_elements() {
+        let src = "let fs: f64[] = [1.5, 2.25]\nlet names = [\"a\", \"bc\"]\nlet grid = [[1, 2], [3, 4]]\nlet mixed = [1, \"two\", true]\nlet total = 0\nif fs[0] + fs[1] == 3.75 { total = total + 1 }\nif names[1] == \"bc\" { total = total + 10 }\nif format(\"{} {}\", mixed, mixed[1]) == \"[1, \\\"two\\\", true] two\" { total = total + 100 }\nreturn total + grid[1][0] * 1000 + grid[0][1] * 10000";
+        assert_eq!(run(src), 23111);
+    }
+
+    #[test]
+    fn test_push_and_pop_return_what_they_move() {
+        let src = "let xs: i32[] = []\npush(xs, 1)\npush(xs, 2)\nlet n = push(xs, 3)\nlet last = pop(xs)\nlet ys = concat(slice(xs, 0, 1), [last, 4])\nlet gone = remove(ys, 1)\nreturn n * 100000 + last * 10000 + len(xs) * 1000 + gone * 100 + ys[0] * 10 + ys[1]";
+        assert_eq!(run(src), 332314);
+    }
+
+    #[test]
+    fn test_map_keys_keep_insertion_order() {
+        let src = "let m = Map()\nset(m, \"b\", 2)\nset(m, \"a\", 1)\nset(m, \"c\", 3)\nset(m, \"a\", 10)\ndelete(m, \"b\")\nlet order = 0\nfor k in keys(m) { order = order * 100 + get(m, k) }\nreturn order * 10 + len(m)";
+        assert_eq!(run(src), 10032);
+    }
+
+    #[test]
+    fn test_match_binds_variant_payloads() {
+        let src = "enum Shape { Circle(i32), Rect(i32, i32), Empty }\nfuncy area(s: Shape) -> i32 {\n  return match s { Circle(r) => r * r, Rect(w, h) => w * h, Empty => 0 }\n}\nreturn area(Shape.Circle(3)) * 100 + area(Shape.Rect(2, 5)) + area(Shape.Empty)";
+        assert_eq!(run(src), 910);
+    }
+
+    #[test]
+    fn test_unsigned_remainder_and_shift() {
+        let src = "let u: u32 = 4000000000\nlet a = -7\nlet total = 0\nif u % 7 == 3 { total = total + 1 }\nif u >> 28 == 14 { total = total + 10 }\nif a % 2 == -1 { total = total + 100 }\nif a >> 1 == -4 { total = total + 1000 }\nif 7.5 % 2 == 1.5 and a << 3 == -56 and (a ^ 1) == -8 and ~u == 294967295 { total = total + 10000 }\na += 2\nlet old = a++\nreturn total + (old + a) * 100000";
+        assert_eq!(run(src), -888889);
+    }
+
+    #[test]
+    fn test_unsuffixed_literals_take_the_type_of_the_other_operand() {
+        let src = "let g: i64 = 5\nlet big = g * 1000000000000\nlet total = 0\nif big == 5000000000000 { total = total + 1 }\nif 6000000000000 - big == 1000000000000 { total = total + 10 }\nreturn total";
+        assert_eq!(run(src), 11);
+    }
+
+    #[test]
+    fn test_string_literals_are_registered_before_main() {
+        // The embedded NUL is only seen through the literal's header
+        let src = "let s = \"a\\0bé\"\nreturn strlen(s) * 10 + strlen(s + \"!\")";
+        assert_eq!(run(src), 45);
+    }
+}
@@ -1,4 +1,6 @@
 mod lexer;
+mod diagnostics;
+mod checker;
 mod parser;
 mod ast;
 mod macros; // bring wpp_debug! into this binary crate
@@ -49,7 +51,26 @@ fn main() {
 
     // === Parsing ===
     let mut parser = Parser::new(tokens);
-    let ast = parser.parse_program();
+    let ast = match parser.parse_program() {
+        Ok(ast) => ast,
+        Err(diags) => {
+            for d in &diags {
+                eprintln!("❌ {}", d.render_plain(&source));
+            }
+            eprintln!("❌ Aborting due to {} syntax error(s)", diags.len());
+            return;
+        }
+    };
+
+    // === Type checking ===
+    let type_errors = checker::check_program(&ast);
+    if !type_errors.is_empty() {
+        for d in &type_errors {
+            eprintln!("❌ {}", d.render_plain(&source));
+        }
+        eprintln!("❌ Aborting due to {} type error(s)", type_errors.len());
+        return;
+    }
 
     println!("🔍 === AST Dump ===");
     for node in &ast {
@@ -61,7 +82,10 @@ fn main() {
     let context = Context::create(); // ✅ Create the LLVM context
     
 let mut codegen = Codegen::new(&context, "wpp_module", "./src");
-let main_fn = codegen.compile_main(&ast);
+if let Err(e) = codegen.compile_main(&ast) {
+    eprintln!("❌ {}", diagnostics::Diagnostic::from(e).render_plain(&source));
+    return;
+}
 if let Err(msg) = codegen.module.verify() {
     eprintln!("❌ LLVM Verification failed:\n{}", msg.to_string());
     return;
@@ -73,6 +97,8 @@ codegen.module.print_to_stderr();
 codegen.module.print_to_file("debug.ll").unwrap();
 println!("💾 IR written to debug.ll");
 
-codegen.run_jit();
+if let Err(e) = codegen.run_jit() {
+    eprintln!("❌ {}", diagnostics::Diagnostic::from(e).render_plain(&source));
+}
 
 }
@@ -1,9 +1,9 @@
 //! W++ Module System (WMS)
 //! Handles multi-threaded module resolution, parsing, and caching of imported modules.
 
-use crate::ast::Node;
-use crate::parser::parse;
-use inkwell::module::{Linkage, Module};
+use crate::ast::{FileId, Node, NodeKind};
+use crate::diagnostics::Diagnostic;
+use crate::parser::parse_file;
 use rayon::prelude::*;
 use std::collections::HashMap;
 use std::fs;
@@ -26,10 +26,18 @@ pub struct ModuleData {
     pub llvm_ir: Option<String>,
 }
 
+/// A source file the module system has read. The `FileId` its spans carry is its index + 1;
+/// `FileId(0)` is left for a file parsed on its own, such as the CLI's main file
+struct SourceFile {
+    path: PathBuf,
+    source: String,
+}
+
 /// Thread-safe, caching, multi-threaded module system for W++.
 pub struct ModuleSystem {
     base_dir: PathBuf,
     cache: Arc<Mutex<HashMap<String, ModuleData>>>,
+    files: Arc<Mutex<Vec<SourceFile>>>,
 }
 
 impl ModuleSystem {
@@ -43,6 +51,7 @@ impl ModuleSystem {
         Self {
             base_dir: base,
             cache: Arc::new(Mutex::new(HashMap::new())),
+            files: Arc::new(Mutex::new(Vec::new())),
         }
     }
 
@@ -85,7 +94,14 @@ let source = std::fs::read_to_string(&resolved_path)
     println!("📦 [wms] Loading module '{}'", name);
 
     // === 3️⃣ Parse into AST ===
-    let ast = parse(&source)?;
+    let file = self.add_file(&resolved_path, &source);
+    let ast = parse_file(&source, file).map_err(|diags| {
+        format!("Failed to parse module '{}':\n{}", name, self.render(&diags))
+    })?;
+    let type_errors = crate::checker::check_program(&ast);
+    if !type_errors.is_empty() {
+        return Err(format!("Failed to type-check module '{}':\n{}", name, self.render(&type_errors)));
+    }
 
     // Insert placeholder early to block circular recursion
     self.cache.lock().unwrap().insert(
@@ -101,8 +117,8 @@ let source = std::fs::read_to_string(&resolved_path)
     // === 4️⃣ Gather dependency module names ===
     let deps: Vec<String> = ast
         .iter()
-        .filter_map(|node| match node {
-            Node::ImportList { module, .. } | Node::ImportAll { module } => Some(module.clone()),
+        .filter_map(|node| match &node.kind {
+            NodeKind::ImportList { module, .. } | NodeKind::ImportAll { module } => Some(module.clone()),
             _ => None,
         })
         .collect();
@@ -137,25 +153,9 @@ let source = std::fs::read_to_string(&resolved_path)
         // For dependencies, compile to IR as usual
         let context = Context::create();
         let mut codegen = Codegen::new(&context, name, "./src");
-        codegen.compile_main(&ast);
-
-        // Neutralize duplicate globals for submodules
-        for gname in ["_wpp_exc_flag", "_wpp_exc_i32", "_wpp_exc_str"] {
-            if let Some(global) = codegen.module.get_global(gname) {
-                // Force external linkage and remove its initializer
-                global.set_linkage(inkwell::module::Linkage::External);
-                unsafe {
-                    use inkwell::llvm_sys::core::LLVMSetInitializer;
-                    use inkwell::values::AsValueRef;
-                    LLVMSetInitializer(global.as_value_ref(), std::ptr::null_mut());
-                }
-                #[cfg(debug_assertions)]
-                println!(
-                    "🔧 [wms] Neutralized duplicate global '{}' in module '{}'",
-                    gname, name
-                );
-            }
-        }
+        codegen.compile_main(&ast).map_err(|e| {
+            format!("Failed to compile module '{}':\n{}", name, self.render(&[Diagnostic::from(e)]))
+        })?;
 
         Some(codegen.module.print_to_string().to_string())
     };
@@ -325,7 +325,7 @@ pub fn load_modules_parallel(&self, modules: &[String]) -> Result<(), String> {
 
             if let Some(module) = cache.get(name) {
                 for node in &module.ast {
-                    if let Node::ImportAll { module } | Node::ImportList { module, .. } = node {
+                    if let NodeKind::ImportAll { module } | NodeKind::ImportList { module, .. } = &node.kind {
                         if let Some(true) = visited.get(module) {
                             if stack.contains(module) {
                                 return Err(format!(
@@ -361,28 +361,23 @@ pub fn load_modules_parallel(&self, modules: &[String]) -> Result<(), String> {
     fn compile_single_module(&self, name: &str, path: &Path) -> Result<ModuleData, String> {
     use crate::Codegen;
     use inkwell::context::Context;
-    use inkwell::module::Linkage;
 
     let source = std::fs::read_to_string(path)
         .map_err(|e| format!("Failed to read module '{}': {}", path.display(), e))?;
 
-    let ast = crate::parser::parse(&source)?;
+    let file = self.add_file(path, &source);
+    let ast = parse_file(&source, file).map_err(|diags| {
+        format!("Failed to parse module '{}':\n{}", name, self.render(&diags))
+    })?;
+    let type_errors = crate::checker::check_program(&ast);
+    if !type_errors.is_empty() {
+        return Err(format!("Failed to type-check module '{}':\n{}", name, self.render(&type_errors)));
+    }
     let context = Context::create();
     let mut codegen = Codegen::new(&context, name, "./src");
-    codegen.compile_main(&ast);
-
-    if name != "main" {
-        for g in ["_wpp_exc_flag", "_wpp_exc_i32", "_wpp_exc_str"] {
-            if let Some(global) = codegen.module.get_global(g) {
-                global.set_linkage(Linkage::External);
-                unsafe {
-                    use inkwell::llvm_sys::core::LLVMSetInitializer;
-                    use inkwell::values::AsValueRef;
-                    LLVMSetInitializer(global.as_value_ref(), std::ptr::null_mut());
-                }
-            }
-        }
-    }
+    codegen.compile_main(&ast).map_err(|e| {
+        format!("Failed to compile module '{}':\n{}", name, self.render(&[Diagnostic::from(e)]))
+    })?;
 
     Ok(ModuleData {
         name: name.to_string(),
@@ -433,4 +428,51 @@ impl ModuleSystem {
     pub fn get_cache(&self) -> std::sync::MutexGuard<'_, HashMap<String, ModuleData>> {
         self.cache.lock().unwrap()
     }
+
+    /// Remember a module's source and hand out the `FileId` its spans will carry
+    fn add_file(&self, path: &Path, source: &str) -> FileId {
+        let mut files = self.files.lock().unwrap();
+        files.push(SourceFile { path: path.to_path_buf(), source: source.to_string() });
+        FileId(files.len() as u32)
+    }
+
+    /// Path and source text of a file this module system read, by the `FileId` of its spans
+    pub fn file(&self, file: FileId) -> Option<(PathBuf, String)> {
+        let files = self.files.lock().unwrap();
+        let entry = files.get((file.0 as usize).checked_sub(1)?)?;
+        Some((entry.path.clone(), entry.source.clone()))
+    }
+
+    /// Render diagnostics against the files their spans point into, one per line
+    pub fn render(&self, diags: &[Diagnostic]) -> String {
+        diags
+            .iter()
+            .map(|d| match self.file(d.span.file) {
+                Some((path, source)) => format!("{}: {}", path.display(), d.render_plain(&source)),
+                None => d.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_module_errors_render_against_the_module_source() {
+        let dir = std::env::temp_dir().join(format!("wpp_ms_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("broken.wpp");
+        std::fs::write(&path, "let a = 1;\nlet b = 2;\nlet = 3;\n").unwrap();
+
+        let ms = ModuleSystem::new(&dir);
+        let err = ms.compile_single_module("broken", &path).err().expect("module should not parse");
+        std::fs::remove_dir_all(&dir).ok();
+
+        assert!(err.contains(&format!("{}: error at line 3,", path.display())), "{}", err);
+        assert_eq!(ms.file(FileId(1)).map(|(p, _)| p), Some(path));
+        assert!(ms.file(FileId::default()).is_none());
+    }
 }
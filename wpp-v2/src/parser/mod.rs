@@ -1,2 +1,2 @@
 pub mod parser;
-pub use parser::{Parser, parse};
+pub use parser::{Parser, parse, parse_file};
@@ -1,11 +1,19 @@
-use crate::ast::{node::{EntityMember, EntityNode}, Expr, Node};
-use crate::ast::types::{ObjectTypeDefinition, ObjectField, FieldType, ParameterPattern, TypePattern, TypeDescriptor};
+use crate::ast::{node::{EntityMember, EntityNode, InterfaceMethod, InterfaceNode}, CatchClause, Expr, ExprKind, FileId, MatchArm, Node, NodeKind, Span};
+use crate::ast::types::{ObjectTypeDefinition, ObjectField, FieldType, ParameterPattern, TypePattern, TypeDescriptor, EnumDefinition, EnumVariant};
+use crate::diagnostics::Diagnostic;
 use std::mem;
-use crate::lexer::{Token, TokenKind};
+use crate::lexer::{StringPart, Token, TokenKind};
 use std::collections::HashMap;
 use crate::lexer::Lexer;
 
+/// Result of a single parse step; the error is the first problem found in that step
+type PResult<T> = Result<T, Diagnostic>;
 
+/// Keywords that can only start a statement, used as recovery points after a syntax error
+const STMT_KEYWORDS: &[&str] = &[
+    "let", "const", "if", "while", "for", "switch", "try", "throw", "defer", "return",
+    "func", "funcy", "async", "entity", "interface", "type", "enum", "import", "export",
+];
 
 /// Simple W++ parser that turns text into AST nodes.
 /// This can later be replaced with your real parser.
@@ -13,59 +21,191 @@ pub struct Parser {
     tokens: Vec<Token>,
     pos: usize,
         pub functions: HashMap<String, Expr>, 
+    /// Errors collected so far; parsing keeps going after each one
+    diagnostics: Vec<Diagnostic>,
+    /// Set while parsing a `for ... in` iterable, where `xs {` starts the loop body
+    /// rather than a typed object literal
+    no_typed_object: bool,
+    /// `///` comments, keyed by the index of the token they precede
+    docs: HashMap<usize, String>,
 }
-pub fn parse(source: &str) -> Result<Vec<crate::ast::Node>, String> {
+
+/// Tokenize and parse a whole source file.
+/// On failure every syntax error in the file is returned, not just the first.
+pub fn parse(source: &str) -> Result<Vec<crate::ast::Node>, Vec<Diagnostic>> {
+    parse_file(source, FileId::default())
+}
+
+/// Like [`parse`], for a file whose spans should carry `file` (see `ModuleSystem`)
+pub fn parse_file(source: &str, file: FileId) -> Result<Vec<crate::ast::Node>, Vec<Diagnostic>> {
     // 1️⃣ Tokenize the input source
-    let mut lexer = Lexer::new(source);
+    let mut lexer = Lexer::with_file(source, file);
     let tokens = lexer.tokenize();
 
     // 2️⃣ Parse the token stream
     let mut parser = Parser::new(tokens);
-    let ast = parser.parse_program();
-
-    Ok(ast)
+    parser.parse_program()
 }
 impl Parser {
+    /// Build an error diagnostic pointing at the current token
     #[inline(never)]
-    fn error_here(&self, msg: &str) -> ! {
+    fn error_here(&self, msg: &str) -> Diagnostic {
         let tok = self.tokens.get(self.pos);
-        let (line, col, got) = match tok {
-            Some(t) => (t.line, t.col, format!("{:?}", t.kind)),
-            None => (0, 0, "<eof>".to_string()),
+        let (span, got) = match tok {
+            Some(t) => (t.span, describe_token(&t.kind)),
+            None => {
+                let file = self.tokens.last().map_or(FileId::default(), |t| t.span.file);
+                (Span::new(file, 0, 0), "end of file".to_string())
+            }
         };
-        panic!("Parse error at line {}, col {}: {}. Got {}", line, col, msg, got);
+        Diagnostic::error(span, msg).with_note(format!("found {}", got))
     }
     pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, pos: 0, functions: HashMap::new(),
+        // Doc comments are set aside so they never get in the way of the grammar;
+        // declarations look up the run of lines just before them
+        let mut docs: HashMap<usize, String> = HashMap::new();
+        let mut kept = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            match token.kind {
+                TokenKind::DocComment(line) => {
+                    let doc = docs.entry(kept.len()).or_default();
+                    if !doc.is_empty() {
+                        doc.push('\n');
+                    }
+                    doc.push_str(&line);
+                }
+                _ => kept.push(token),
+            }
+        }
+        Self { tokens: kept, pos: 0, functions: HashMap::new(),
+            diagnostics: Vec::new(),
+            no_typed_object: false,
+            docs,
  }
     }
 
-    /// Main entrypoint: parse an entire program into AST nodes
-    pub fn parse_program(&mut self) -> Vec<Node> {
+    /// Main entrypoint: parse an entire program into AST nodes.
+    /// Returns every syntax error found when the program is malformed.
+    pub fn parse_program(&mut self) -> Result<Vec<Node>, Vec<Diagnostic>> {
         let mut nodes = Vec::new();
 
         while !self.check(TokenKind::EOF) {
-            if let Some(node) = self.parse_stmt() {
-                nodes.push(node);
-            } else {
+            let start = self.pos;
+            match self.parse_stmt() {
+                Ok(Some(node)) => nodes.push(node),
                 // skip unrecognized token instead of infinite loop
+                Ok(None) => { self.advance(); }
+                Err(diag) => self.recover(diag, start),
+            }
+            // a stray '}' at top level can never start a statement
+            if self.check(TokenKind::Symbol("}".into())) {
+                let diag = self.error_here("Unexpected '}' with no matching '{'");
+                self.diagnostics.push(diag);
                 self.advance();
             }
         }
 
+        if self.diagnostics.iter().any(|d| d.is_error()) {
+            Err(mem::take(&mut self.diagnostics))
+        } else {
+            Ok(nodes)
+        }
+    }
+
+    /// Parse statements until `}` (or EOF), recovering from errors statement by statement
+    fn parse_stmt_list(&mut self) -> Vec<Node> {
+        let mut nodes = Vec::new();
+        while !self.check(TokenKind::Symbol("}".into())) && !self.check(TokenKind::EOF) {
+            let start = self.pos;
+            match self.parse_stmt() {
+                Ok(Some(stmt)) => nodes.push(stmt),
+                Ok(None) => { self.advance(); }
+                Err(diag) => self.recover(diag, start),
+            }
+        }
         nodes
     }
+
+    /// Record a diagnostic and skip ahead to the next statement boundary:
+    /// just past a `;`, just past a skipped `{ ... }` block, or right before
+    /// a statement keyword or the `}` closing the enclosing block.
+    fn recover(&mut self, diag: Diagnostic, start: usize) {
+        self.diagnostics.push(diag);
+
+        // always make progress, even if the statement failed on its first token
+        if self.pos == start && !self.check(TokenKind::EOF) {
+            self.advance();
+        }
+
+        let mut depth = 0usize;
+        loop {
+            match self.peek().clone() {
+                TokenKind::EOF => return,
+                TokenKind::Symbol(s) if s == "{" => depth += 1,
+                TokenKind::Symbol(s) if s == "}" => {
+                    if depth == 0 {
+                        return;
+                    }
+                    depth -= 1;
+                    if depth == 0 {
+                        self.advance();
+                        return;
+                    }
+                }
+                TokenKind::Symbol(s) if s == ";" && depth == 0 => {
+                    self.advance();
+                    return;
+                }
+                TokenKind::Keyword(k) if depth == 0 && STMT_KEYWORDS.contains(&k.as_str()) => return,
+                _ => {}
+            }
+            self.advance();
+        }
+    }
+}
+
+/// Human-readable description of a token for error messages
+fn describe_token(kind: &TokenKind) -> String {
+    match kind {
+        TokenKind::Keyword(k) => format!("keyword `{}`", k),
+        TokenKind::Identifier(id) => format!("identifier `{}`", id),
+        TokenKind::Number { raw, .. } => format!("number `{}`", raw),
+        TokenKind::String(s) => format!("string {:?}", s),
+        TokenKind::InterpolatedString(_) => "interpolated string".to_string(),
+        TokenKind::DocComment(_) => "doc comment".to_string(),
+        TokenKind::Symbol(s) => format!("`{}`", s),
+        TokenKind::EOF => "end of file".to_string(),
+    }
 }
 impl Parser {
     fn peek(&self) -> &TokenKind {
         &self.tokens[self.pos].kind
     }
 
+    /// Consume the current token and return it; never moves past EOF
     fn advance(&mut self) -> &TokenKind {
-        if self.pos < self.tokens.len() {
+        let idx = self.pos;
+        if self.pos + 1 < self.tokens.len() {
             self.pos += 1;
         }
-        &self.tokens[self.pos - 1].kind
+        &self.tokens[idx].kind
+    }
+
+    /// Byte offset where the current token starts
+    fn start_pos(&self) -> usize {
+        self.tokens[self.pos].span.start
+    }
+
+    /// Byte offset where the last consumed token starts (usually a keyword)
+    fn prev_start(&self) -> usize {
+        self.tokens[self.pos.saturating_sub(1)].span.start
+    }
+
+    /// Span from `start` to the end of the last consumed token
+    fn span_from(&self, start: usize) -> Span {
+        let file = self.tokens[self.pos].span.file;
+        let end = if self.pos > 0 { self.tokens[self.pos - 1].span.end } else { start };
+        Span::new(file, start, end.max(start))
     }
 
     fn check(&self, kind: TokenKind) -> bool {
@@ -90,11 +230,12 @@ impl Parser {
     }
     false
 }
-fn expect_symbol(&mut self, sym: &str) {
+fn expect_symbol(&mut self, sym: &str) -> PResult<()> {
     if self.check(TokenKind::Symbol(sym.to_string())) {
         self.advance();
+        Ok(())
     } else {
-        self.error_here(&format!("Expected symbol '{}'", sym));
+        Err(self.error_here(&format!("Expected symbol '{}'", sym)))
     }
 }
 
@@ -109,67 +250,90 @@ impl Parser {
     }
 }
 
-    fn parse_stmt(&mut self) -> Option<Node> {
-    match self.peek() {
+    /// Parse one statement, attaching the doc comment before it when it is a declaration
+    fn parse_stmt(&mut self) -> PResult<Option<Node>> {
+        let doc = self.docs.get(&self.pos).cloned();
+        let mut node = self.parse_bare_stmt()?;
+        if let Some(node) = &mut node {
+            let declaration = match &node.kind {
+                NodeKind::Expr(expr) => matches!(expr.kind, ExprKind::Funcy { .. }),
+                NodeKind::Let { .. } | NodeKind::Entity(_) | NodeKind::Export { .. } | NodeKind::TypeAlias(_) | NodeKind::Enum(_) | NodeKind::Interface(_) => true,
+                NodeKind::ImportAll { .. } | NodeKind::ImportList { .. } => false,
+            };
+            if declaration {
+                node.doc = doc;
+            }
+        }
+        Ok(node)
+    }
+
+    fn parse_bare_stmt(&mut self) -> PResult<Option<Node>> {
+    let start = self.start_pos();
+    Ok(match self.peek() {
         TokenKind::Keyword(k) if k == "let" || k == "const" => {
             let is_const = k == "const"; // ✅ determine constness
             self.advance();
-            self.parse_let(is_const)
+            self.parse_let(is_const)?
         }
 
         TokenKind::Keyword(k) if k == "if" => {
             self.advance();
-            Some(self.parse_if())
+            Some(self.parse_if()?)
         }
 
         TokenKind::Keyword(k) if k == "while" => {
             self.advance();
-            Some(Node::Expr(self.parse_while()))
+            Some(Node::from(self.parse_while()?))
         }
 
         TokenKind::Keyword(k) if k == "for" => {
             self.advance();
-            Some(Node::Expr(self.parse_for()))
+            Some(Node::from(self.parse_for()?))
         }
 
         TokenKind::Keyword(k) if k == "break" => {
             self.advance();
             if self.check(TokenKind::Symbol(";".into())) { self.advance(); }
-            Some(Node::Expr(Expr::Break))
+            Some(Node::from(Expr::new(ExprKind::Break, self.span_from(start))))
         }
 
         TokenKind::Keyword(k) if k == "continue" => {
             self.advance();
             if self.check(TokenKind::Symbol(";".into())) { self.advance(); }
-            Some(Node::Expr(Expr::Continue))
+            Some(Node::from(Expr::new(ExprKind::Continue, self.span_from(start))))
         }
         TokenKind::Keyword(k) if k == "switch" => {
     self.advance();
-    Some(Node::Expr(self.parse_switch()))
+    Some(Node::from(self.parse_switch()?))
 }
         TokenKind::Keyword(k) if k == "try" => {
     self.advance();
-    Some(Node::Expr(self.parse_try_catch()))
+    Some(Node::from(self.parse_try_catch()?))
 }
 TokenKind::Keyword(k) if k == "throw" => {
     self.advance();
-    Some(Node::Expr(self.parse_throw()))
+    Some(Node::from(self.parse_throw()?))
+}
+TokenKind::Keyword(k) if k == "defer" => {
+    self.advance();
+    Some(Node::from(self.parse_defer()?))
 }
 TokenKind::Keyword(k) if k == "async" => {
     self.advance(); // consume 'async'
     if self.check(TokenKind::Keyword("funcy".into())) {
         self.advance(); // consume 'funcy'
-        let expr = self.parse_funcy(true);
-        Some(Node::Expr(expr))
+        let mut expr = self.parse_funcy(true)?;
+        expr.span = self.span_from(start); // include 'async'
+        Some(Node::from(expr))
     } else {
-        panic!("Expected 'funcy' after 'async'");
+        return Err(self.error_here("Expected 'funcy' after 'async'"));
     }
 }
 
 TokenKind::Keyword(k) if k == "func" || k == "funcy" => {
     self.advance();
-    let expr = self.parse_funcy(false);
-    Some(Node::Expr(expr))
+    let expr = self.parse_funcy(false)?;
+    Some(Node::from(expr))
 }
 
 
@@ -182,7 +346,7 @@ TokenKind::Keyword(k) if k == "return" => {
     let expr = if !self.check(TokenKind::Symbol(";".into()))
         && !self.check(TokenKind::Symbol("}".into()))
     {
-        Some(Box::new(self.parse_expr()))
+        Some(Box::new(self.parse_expr()?))
     } else {
         None
     };
@@ -192,14 +356,22 @@ TokenKind::Keyword(k) if k == "return" => {
         self.advance();
     }
 
-    Some(Node::Expr(Expr::Return(expr)))
+    Some(Node::from(Expr::new(ExprKind::Return(expr), self.span_from(start))))
 }
 TokenKind::Keyword(k) if k == "entity" => {
-    self.parse_entity()
+    self.parse_entity()?
+}
+TokenKind::Keyword(k) if k == "interface" => {
+    self.advance(); // consume 'interface'
+    Some(self.parse_interface()?)
 }
 TokenKind::Keyword(k) if k == "type" => {
     self.advance(); // consume 'type'
-    self.parse_type_alias()
+    self.parse_type_alias()?
+}
+TokenKind::Keyword(k) if k == "enum" => {
+    self.advance(); // consume 'enum'
+    Some(self.parse_enum()?)
 }
 TokenKind::Keyword(k) if k == "export" => {
     self.advance(); // consume 'export'
@@ -208,46 +380,46 @@ TokenKind::Keyword(k) if k == "export" => {
     // or export const X = 5;
     if self.check(TokenKind::Keyword("async".into())) {
     self.advance(); // consume 'async'
-    self.expect(TokenKind::Keyword("funcy".into()), "Expected 'funcy' after 'async'");
-    let func_expr = self.parse_funcy(true);
-    let name = match &func_expr {
-        Expr::Funcy { name, .. } => name.clone(),
+    self.expect(TokenKind::Keyword("funcy".into()), "Expected 'funcy' after 'async'")?;
+    let func_expr = self.parse_funcy(true)?;
+    let name = match &func_expr.kind {
+        ExprKind::Funcy { name, .. } => name.clone(),
         _ => "anonymous".to_string(),
     };
-    return Some(Node::Export {
+    return Ok(Some(Node::new(NodeKind::Export {
         name,
-        item: Box::new(Node::Expr(func_expr)),
-    });
+        item: Box::new(Node::from(func_expr)),
+    }, self.span_from(start))));
 } else if self.check(TokenKind::Keyword("func".into())) || self.check(TokenKind::Keyword("funcy".into())) {
 
         self.advance();
-        let func_expr = self.parse_funcy(false);
-        let name = match &func_expr {
-            Expr::Funcy { name, .. } => name.clone(),
+        let func_expr = self.parse_funcy(false)?;
+        let name = match &func_expr.kind {
+            ExprKind::Funcy { name, .. } => name.clone(),
             _ => "anonymous".to_string(),
         };
-        Some(Node::Export {
+        Some(Node::new(NodeKind::Export {
             name,
-            item: Box::new(Node::Expr(func_expr)),
-        })
+            item: Box::new(Node::from(func_expr)),
+        }, self.span_from(start)))
     } else if self.check(TokenKind::Keyword("const".into())) || self.check(TokenKind::Keyword("let".into())) {
         // export const/let ...
         let is_const = self.check(TokenKind::Keyword("const".into()));
         self.advance();
-        if let Some(decl) = self.parse_let(is_const) {
-            let name = match &decl {
-                Node::Let { name, .. } => name.clone(),
+        if let Some(decl) = self.parse_let(is_const)? {
+            let name = match &decl.kind {
+                NodeKind::Let { name, .. } => name.clone(),
                 _ => "unknown".to_string(),
             };
-            Some(Node::Export {
+            Some(Node::new(NodeKind::Export {
                 name,
                 item: Box::new(decl),
-            })
+            }, self.span_from(start)))
         } else {
             None
         }
     } else {
-        panic!("Expected function or variable after 'export'");
+        return Err(self.error_here("Expected function or variable after 'export'"));
     }
 }
 
@@ -257,7 +429,7 @@ TokenKind::Keyword(k) if k == "import" => {
     // Case 1: import "pkg"
     if let TokenKind::String(module_name) = self.peek().clone() {
     self.advance(); // only consume if confirmed
-    return Some(Node::ImportAll { module: module_name });
+    return Ok(Some(Node::new(NodeKind::ImportAll { module: module_name }, self.span_from(start))));
 }
 
 
@@ -267,18 +439,12 @@ TokenKind::Keyword(k) if k == "import" => {
 
         let mut members = Vec::new();
         while !self.check(TokenKind::Symbol("}".into())) && !self.check(TokenKind::EOF) {
-            let name = match self.advance().clone() {
-                TokenKind::Identifier(n) => n,
-                other => panic!("Expected identifier in import list, got {:?}", other),
-            };
+            let name = self.expect_identifier("Expected identifier in import list")?;
 
             // Optional alias: "as"
             let alias = if self.check(TokenKind::Keyword("as".into())) {
                 self.advance();
-                match self.advance().clone() {
-                    TokenKind::Identifier(a) => Some(a),
-                    _ => panic!("Expected alias name after 'as'"),
-                }
+                Some(self.expect_identifier("Expected alias name after 'as'")?)
             } else {
                 None
             };
@@ -292,73 +458,80 @@ TokenKind::Keyword(k) if k == "import" => {
             }
         }
 
-        self.expect(TokenKind::Symbol("}".into()), "Expected '}' after import list");
-        self.expect(TokenKind::Keyword("from".into()), "Expected 'from' after import list");
+        self.expect(TokenKind::Symbol("}".into()), "Expected '}' after import list")?;
+        self.expect(TokenKind::Keyword("from".into()), "Expected 'from' after import list")?;
 
-        let module = match self.advance().clone() {
-            TokenKind::String(m) => m,
-            other => panic!("Expected string after 'from', got {:?}", other),
+        let module = match self.peek().clone() {
+            TokenKind::String(m) => {
+                self.advance();
+                m
+            }
+            _ => return Err(self.error_here("Expected module string after 'from'")),
         };
 
-        return Some(Node::ImportList { module, members });
+        return Ok(Some(Node::new(NodeKind::ImportList { module, members }, self.span_from(start))));
     }
 
-    panic!("Invalid import syntax – expected string or '{{...}}'");
+    return Err(self.error_here("Invalid import syntax – expected string or '{...}'"));
 }
 
 
         _ => {
-            let expr = self.parse_expr();
+            let expr = self.parse_expr()?;
             if self.check(TokenKind::Symbol(";".into())) {
                 self.advance();
             }
-            Some(Node::Expr(expr))
+            Some(Node::from(expr))
         }
-    }
+    })
 }
-pub fn parse_entity(&mut self) -> Option<Node> {
-    self.expect(TokenKind::Keyword("entity".into()), "Expected 'entity' keyword");
+pub fn parse_entity(&mut self) -> PResult<Option<Node>> {
+    let start = self.start_pos();
+    self.expect(TokenKind::Keyword("entity".into()), "Expected 'entity' keyword")?;
 
     // --- Parse entity name ---
-    let name = match self.advance().clone() {
-        TokenKind::Identifier(n) => n,
-        other => panic!("Expected entity name, got {:?}", other),
-    };
+    let name = self.expect_identifier("Expected entity name")?;
 
     // --- Optional inheritance: `entity Dog alters Animal`
     let base = if self.check(TokenKind::Keyword("alters".into())) {
         self.advance();
-        match self.advance().clone() {
-            TokenKind::Identifier(b) => Some(b),
-            other => panic!("Expected base entity name after 'alters', got {:?}", other),
-        }
+        Some(self.expect_identifier("Expected base entity name after 'alters'")?)
     } else {
         None
     };
 
-    self.expect(TokenKind::Symbol("{".into()), "Expected '{' to start entity block");
+    // --- Optional interfaces: `entity Dog implements Speaker, Walker`
+    let mut interfaces = Vec::new();
+    if self.matches(&[TokenKind::Keyword("implements".into())]) {
+        loop {
+            interfaces.push(self.expect_identifier("Expected interface name after 'implements'")?);
+            if !self.matches(&[TokenKind::Symbol(",".into())]) {
+                break;
+            }
+        }
+    }
+
+    self.expect(TokenKind::Symbol("{".into()), "Expected '{' to start entity block")?;
 
     // --- Parse members ---
     let mut members = Vec::new();
 
     while !self.check(TokenKind::Symbol("}".into())) && !self.check(TokenKind::EOF) {
+        let doc = self.docs.get(&self.pos).cloned();
         match self.peek() {
             // --- Field like: `age = 5;`
             TokenKind::Identifier(_) => {
-                let field_name = match self.advance().clone() {
-                    TokenKind::Identifier(id) => id,
-                    other => panic!("Expected field name, got {:?}", other),
-                };
+                let field_name = self.expect_identifier("Expected field name")?;
 
                 // Allow both `=` and `:` syntax
                 if self.check(TokenKind::Symbol("=".into())) || self.check(TokenKind::Symbol(":".into())) {
                     self.advance();
                 } else {
-                    panic!("Expected '=' or ':' in field declaration");
+                    return Err(self.error_here("Expected '=' or ':' in field declaration"));
                 }
 
-                let value = self.parse_expr();
-                members.push(EntityMember::Field { name: field_name, value });
+                let value = self.parse_expr()?;
+                members.push(EntityMember::Field { name: field_name, value, doc });
 
                 // Optional semicolon or newline
                 if self.check(TokenKind::Symbol(";".into())) {
@@ -369,24 +542,24 @@ pub fn parse_entity(&mut self) -> Option<Node> {
             // --- Method like: `func bark() => print("woof");`
             TokenKind::Keyword(k) if k == "func" || k == "funcy" => {
                 self.advance();
-                let func_expr = self.parse_funcy(false);
-                let func_name = match &func_expr {
-                    Expr::Funcy { name, .. } => name.clone(),
+                let func_expr = self.parse_funcy(false)?;
+                let func_name = match &func_expr.kind {
+                    ExprKind::Funcy { name, .. } => name.clone(),
                     _ => "anonymous".to_string(),
                 };
-                members.push(EntityMember::Method { name: func_name, func: func_expr });
+                members.push(EntityMember::Method { name: func_name, func: func_expr, doc });
             }
 
             // --- Async methods: `async funcy bark() { ... }`
             TokenKind::Keyword(k) if k == "async" => {
                 self.advance();
-                self.expect(TokenKind::Keyword("funcy".into()), "Expected 'funcy' after 'async'");
-                let func_expr = self.parse_funcy(true);
-                let func_name = match &func_expr {
-                    Expr::Funcy { name, .. } => name.clone(),
+                self.expect(TokenKind::Keyword("funcy".into()), "Expected 'funcy' after 'async'")?;
+                let func_expr = self.parse_funcy(true)?;
+                let func_name = match &func_expr.kind {
+                    ExprKind::Funcy { name, .. } => name.clone(),
                     _ => "anonymous".to_string(),
                 };
-                members.push(EntityMember::Method { name: func_name, func: func_expr });
+                members.push(EntityMember::Method { name: func_name, func: func_expr, doc });
             }
 
             // --- Ignore stray semicolons
@@ -402,14 +575,15 @@ pub fn parse_entity(&mut self) -> Option<Node> {
         }
     }
 
-    self.expect(TokenKind::Symbol("}".into()), "Expected '}' to end entity");
+    self.expect(TokenKind::Symbol("}".into()), "Expected '}' to end entity")?;
 
-    Some(Node::Entity(EntityNode { name, base, members }))
+    Ok(Some(Node::new(NodeKind::Entity(EntityNode { name, base, members, interfaces }), self.span_from(start))))
 }
 
 
 
-    fn parse_let(&mut self, is_const: bool) -> Option<Node> {
+    fn parse_let(&mut self, is_const: bool) -> PResult<Option<Node>> {
+    let start = self.prev_start(); // the 'let'/'const' keyword
     // check if the next token is a type annotation like i32/i64/f64/i8/u64
     let mut explicit_type: Option<String> = None;
 
@@ -422,19 +596,18 @@ pub fn parse_entity(&mut self) -> Option<Node> {
     }
 
     // expect variable name
-    let var_name = match self.advance().clone() {
-        TokenKind::Identifier(n) => n,
-        _ => panic!("Expected variable name after let"),
-    };
+    let var_name = self.expect_identifier("Expected variable name after let")?;
 
-    // expect '='
-    match self.advance().clone() {
-        TokenKind::Symbol(s) if s == "=" => {}
-        _ => panic!("Expected symbol '='"),
+    // optional `: type` annotation, e.g. `let xs: str[] = [...]`
+    if explicit_type.is_none() && self.matches(&[TokenKind::Symbol(":".into())]) {
+        explicit_type = Some(self.parse_let_type()?);
     }
 
+    // expect '='
+    self.expect_symbol("=")?;
+
     // parse expression (right-hand side)
-    let expr = self.parse_expr();
+    let expr = self.parse_expr()?;
 
     // optional ';'
     if let TokenKind::Symbol(semi) = self.peek() {
@@ -443,68 +616,98 @@ pub fn parse_entity(&mut self) -> Option<Node> {
         }
     }
 
-    Some(Node::Let {
+    Ok(Some(Node::new(NodeKind::Let {
         name: var_name,
         value: expr,
         is_const,
         ty: explicit_type,
 
-    })
+    }, self.span_from(start))))
 }
 
 
 
-    fn parse_if(&mut self) -> Node {
+    /// Type after `let name:` — a type name followed by any number of `[]` suffixes
+    fn parse_let_type(&mut self) -> PResult<String> {
+        let mut ty = self.expect_identifier("Expected a type after ':'")?;
+        // `map<V>`
+        if self.matches(&[TokenKind::Symbol("<".into())]) {
+            let inner = self.parse_let_type()?;
+            // `map<map<i32>>` lexes its closers as one `>>`; take the first half
+            if self.check(TokenKind::Symbol(">>".into())) {
+                let tok = &mut self.tokens[self.pos];
+                tok.kind = TokenKind::Symbol(">".into());
+                tok.span.start += 1;
+            } else {
+                self.expect(TokenKind::Symbol(">".into()), "Expected '>' after map value type")?;
+            }
+            ty = format!("{}<{}>", ty, inner);
+        }
+        while self.matches(&[TokenKind::Symbol("[".into())]) {
+            self.expect(TokenKind::Symbol("]".into()), "Expected ']' in array type")?;
+            ty.push_str("[]");
+        }
+        Ok(ty)
+    }
+
+    fn parse_if(&mut self) -> PResult<Node> {
+        let start = self.prev_start(); // the 'if' keyword
         // parse condition (parentheses are optional)
         let has_parens = self.matches(&[TokenKind::Symbol("(".into())]);
-        let condition = self.parse_expr();
+        let condition = self.parse_expr()?;
         if has_parens {
-            self.expect(TokenKind::Symbol(")".into()), "Expected ')' after condition");
+            self.expect(TokenKind::Symbol(")".into()), "Expected ')' after condition")?;
         }
 
         // parse body block
-        let then_block = self.parse_block();
+        let then_block = self.parse_block()?;
 
         // optional else
         let else_block = if self.matches(&[TokenKind::Keyword("else".into())]) {
             if self.check(TokenKind::Keyword("if".into())) {
-                // else if chaining (we can support this later)
-                let else_if = self.parse_if();
+                // else if chaining
+                self.advance(); // consume 'if'
+                let else_if = self.parse_if()?;
                 Some(vec![else_if])
             } else {
-                Some(self.parse_block())
+                Some(self.parse_block()?)
             }
         } else {
             None
         };
 
-        Node::Expr(Expr::If {
+        Ok(Node::from(Expr::new(ExprKind::If {
             cond: Box::new(condition),
             then_branch: then_block,
             else_branch: else_block,
-        })
+        }, self.span_from(start))))
     }
-    fn parse_while(&mut self) -> Expr {
+    fn parse_while(&mut self) -> PResult<Expr> {
+    let start = self.prev_start(); // the 'while' keyword
     // Expect '('
-    self.expect(TokenKind::Symbol("(".into()), "Expected '(' after 'while'");
-    let cond = self.parse_expr();
-    self.expect(TokenKind::Symbol(")".into()), "Expected ')' after while condition");
+    self.expect(TokenKind::Symbol("(".into()), "Expected '(' after 'while'")?;
+    let cond = self.parse_expr()?;
+    self.expect(TokenKind::Symbol(")".into()), "Expected ')' after while condition")?;
 
     // Expect block start '{'
     if !self.check(TokenKind::Symbol("{".into())) {
-        panic!("Expected '{{' to start while block");
+        return Err(self.error_here("Expected '{' to start while block"));
     }
 
     // Parse body
-    let body = self.parse_block();
+    let body = self.parse_block()?;
 
-    Expr::While {
+    Ok(Expr::new(ExprKind::While {
         cond: Box::new(cond),
         body,
-    }
+    }, self.span_from(start)))
 }
-fn parse_for(&mut self) -> Expr {
-    self.expect(TokenKind::Symbol("(".into()), "Expected '(' after 'for'");
+fn parse_for(&mut self) -> PResult<Expr> {
+    let start = self.prev_start(); // the 'for' keyword
+    if !self.check(TokenKind::Symbol("(".into())) {
+        return self.parse_for_in(start);
+    }
+    self.expect(TokenKind::Symbol("(".into()), "Expected '(' after 'for'")?;
 
     // --- Parse initializer ---
     let mut init: Option<Node> = None;
@@ -512,17 +715,17 @@ fn parse_for(&mut self) -> Expr {
     if !self.check(TokenKind::Symbol(";".into())) {
         if self.check(TokenKind::Keyword("let".into())) {
     self.advance(); // consume 'let'
-    if let Some(node) = self.parse_let(false) {
+    if let Some(node) = self.parse_let(false)? {
         init = Some(node);
     }
 }
 else {
             // parse expression initializer
-            let expr = self.parse_expr();
-            init = Some(Node::Expr(expr));
+            let expr = self.parse_expr()?;
+            init = Some(Node::from(expr));
 
             // explicitly consume ';' here
-            self.expect(TokenKind::Symbol(";".into()), "Expected ';' after for-init expression");
+            self.expect(TokenKind::Symbol(";".into()), "Expected ';' after for-init expression")?;
         }
     } else {
         // skip empty initializer
@@ -535,46 +738,87 @@ else {
     // --- Parse condition ---
     let mut cond: Option<Expr> = None;
     if !self.check(TokenKind::Symbol(";".into())) {
-        cond = Some(self.parse_expr());
+        cond = Some(self.parse_expr()?);
     }
-    self.expect(TokenKind::Symbol(";".into()), "Expected ';' after for-condition");
+    self.expect(TokenKind::Symbol(";".into()), "Expected ';' after for-condition")?;
 
     // --- Parse post expression ---
 let mut post: Option<Expr> = None;
 if !self.check(TokenKind::Symbol(")".into())) {
-    post = Some(self.parse_expr());
+    post = Some(self.parse_expr()?);
 }
 
 // ✅ Instead of self.expect(), do a conditional advance:
 if self.check(TokenKind::Symbol(")".into())) {
     self.advance(); // consume ')'
 } else {
-    panic!("Expected ')' after for-header");
+    return Err(self.error_here("Expected ')' after for-header"));
 }
 
 // --- Parse body ---
 // --- Parse body ---
 if !self.check(TokenKind::Symbol("{".into())) {
-    panic!("Expected '{{' to start for-body");
+    return Err(self.error_here("Expected '{' to start for-body"));
 }
-let body: Vec<Node> = self.parse_block();
+let body: Vec<Node> = self.parse_block()?;
 
-Expr::For {
+Ok(Expr::new(ExprKind::For {
     init: init.map(Box::new),
     cond: cond.map(Box::new),
     post: post.map(Box::new),
     body,
-}
+}, self.span_from(start)))
 
 }
 
 
-fn parse_switch(&mut self) -> Expr {
-    self.expect(TokenKind::Symbol("(".into()), "Expected '(' after 'switch'");
-    let switch_expr = self.parse_expr();
-    self.expect(TokenKind::Symbol(")".into()), "Expected ')' after switch expression");
+/// `for x in xs { }`, `for k, v in m { }` or `for i in 0..n { }` (after the `for` keyword)
+fn parse_for_in(&mut self, start: usize) -> PResult<Expr> {
+    let first = self.expect_identifier("Expected '(' or a loop variable after 'for'")?;
+    let (key, item) = if self.matches_symbol(",") {
+        let second = self.expect_identifier("Expected a second loop variable after ','")?;
+        (Some(first), second)
+    } else {
+        (None, first)
+    };
+
+    if !matches!(self.peek(), TokenKind::Identifier(w) if w == "in") {
+        return Err(self.error_here("Expected 'in' after loop variable"));
+    }
+    self.advance(); // consume 'in'
+
+    let saved = mem::replace(&mut self.no_typed_object, true);
+    let iterable = self.parse_expr().and_then(|from| {
+        if !self.matches_symbol("..") {
+            return Ok(from);
+        }
+        let to = self.parse_expr()?;
+        let span = from.span.to(to.span);
+        Ok(Expr::new(ExprKind::Range { start: Box::new(from), end: Box::new(to) }, span))
+    });
+    self.no_typed_object = saved;
+    let iterable = iterable?;
+
+    if !self.check(TokenKind::Symbol("{".into())) {
+        return Err(self.error_here("Expected '{' to start for-body"));
+    }
+    let body = self.parse_block()?;
+
+    Ok(Expr::new(ExprKind::ForIn {
+        key,
+        item,
+        iterable: Box::new(iterable),
+        body,
+    }, self.span_from(start)))
+}
+
+fn parse_switch(&mut self) -> PResult<Expr> {
+    let start = self.prev_start(); // the 'switch' keyword
+    self.expect(TokenKind::Symbol("(".into()), "Expected '(' after 'switch'")?;
+    let switch_expr = self.parse_expr()?;
+    self.expect(TokenKind::Symbol(")".into()), "Expected ')' after switch expression")?;
 
-    self.expect(TokenKind::Symbol("{".into()), "Expected '{' to start switch block");
+    self.expect(TokenKind::Symbol("{".into()), "Expected '{' to start switch block")?;
 
     let mut cases: Vec<(Expr, Vec<Node>)> = Vec::new();
     let mut default: Option<Vec<Node>> = None;
@@ -583,94 +827,113 @@ fn parse_switch(&mut self) -> Expr {
         match self.peek() {
             TokenKind::Keyword(k) if k == "case" => {
                 self.advance(); // consume 'case'
-                let case_expr = self.parse_expr();
-                self.expect(TokenKind::Symbol(":".into()), "Expected ':' after case value");
-                let body = self.parse_case_body();
+                let case_expr = self.parse_expr()?;
+                self.expect(TokenKind::Symbol(":".into()), "Expected ':' after case value")?;
+                let body = self.parse_case_body()?;
                 cases.push((case_expr, body));
             }
             TokenKind::Keyword(k) if k == "default" => {
                 self.advance();
-                self.expect(TokenKind::Symbol(":".into()), "Expected ':' after 'default'");
-                default = Some(self.parse_case_body());
+                self.expect(TokenKind::Symbol(":".into()), "Expected ':' after 'default'")?;
+                default = Some(self.parse_case_body()?);
             }
             _ => { self.advance(); }
         }
     }
 
-    self.expect(TokenKind::Symbol("}".into()), "Expected '}' to close switch");
+    self.expect(TokenKind::Symbol("}".into()), "Expected '}' to close switch")?;
 
-    Expr::Switch {
+    Ok(Expr::new(ExprKind::Switch {
         expr: Box::new(switch_expr),
         cases,
         default,
-    }
+    }, self.span_from(start)))
 }
 
 /// helper to parse everything until next `case`, `default`, or `}`
-fn parse_case_body(&mut self) -> Vec<Node> {
+fn parse_case_body(&mut self) -> PResult<Vec<Node>> {
     let mut nodes = Vec::new();
     while !self.check(TokenKind::Keyword("case".into()))
         && !self.check(TokenKind::Keyword("default".into()))
         && !self.check(TokenKind::Symbol("}".into()))
         && !self.check(TokenKind::EOF)
     {
-        if let Some(stmt) = self.parse_stmt() {
+        if let Some(stmt) = self.parse_stmt()? {
             nodes.push(stmt);
         } else {
             self.advance();
         }
     }
-    nodes
+    Ok(nodes)
 }
-fn parse_try_catch(&mut self) -> Expr {
-    let try_block = self.parse_block();
+fn parse_try_catch(&mut self) -> PResult<Expr> {
+    let start = self.prev_start(); // the 'try' keyword
+    let try_block = self.parse_block()?;
 
-    let mut catch_var = None;
-    let mut catch_block = Vec::new();
+    let mut catches = Vec::new();
     let mut finally_block = None;
 
-    // --- Parse optional catch
-    if self.matches(&[TokenKind::Keyword("catch".into())]) {
-        self.expect(TokenKind::Symbol("(".into()), "Expected '(' after catch");
-        if let TokenKind::Identifier(name) = self.advance().clone() {
-            catch_var = Some(name);
+    // --- Parse catch clauses: `catch (e)`, `catch (e: NotFoundError)` or a bare `catch`
+    while self.matches(&[TokenKind::Keyword("catch".into())]) {
+        let mut var = None;
+        let mut ty = None;
+        if self.matches(&[TokenKind::Symbol("(".into())]) {
+            match self.advance().clone() {
+                TokenKind::Identifier(name) => var = Some(name),
+                _ => return Err(self.error_here("Expected catch variable")),
+            }
+            if self.matches(&[TokenKind::Symbol(":".into())]) {
+                ty = Some(self.parse_type_annotation()?);
+            }
+            self.expect(TokenKind::Symbol(")".into()), "Expected ')' after catch variable")?;
         }
-        self.expect(TokenKind::Symbol(")".into()), "Expected ')' after catch variable");
-        catch_block = self.parse_block();
+        let body = self.parse_block()?;
+        catches.push(CatchClause { var, ty, body });
     }
 
     // --- Parse optional finally
     if self.matches(&[TokenKind::Keyword("finally".into())]) {
-        finally_block = Some(self.parse_block());
+        finally_block = Some(self.parse_block()?);
     }
 
-    Expr::TryCatch {
+    Ok(Expr::new(ExprKind::TryCatch {
         try_block,
-        catch_var,
-        catch_block,
+        catches,
         finally_block,
+    }, self.span_from(start)))
+}
+
+fn parse_throw(&mut self) -> PResult<Expr> {
+    let start = self.prev_start(); // the 'throw' keyword
+    let expr = self.parse_expr()?;
+    if self.check(TokenKind::Symbol(";".into())) {
+        self.advance();
     }
+    Ok(Expr::new(ExprKind::Throw { expr: Box::new(expr) }, self.span_from(start)))
 }
 
-fn parse_throw(&mut self) -> Expr {
-    let expr = self.parse_expr();
+fn parse_defer(&mut self) -> PResult<Expr> {
+    let start = self.prev_start(); // the 'defer' keyword
+    let expr = self.parse_expr()?;
     if self.check(TokenKind::Symbol(";".into())) {
         self.advance();
     }
-    Expr::Throw { expr: Box::new(expr) }
+    Ok(Expr::new(ExprKind::Defer(Box::new(expr)), self.span_from(start)))
 }
-fn parse_funcy(&mut self, is_async: bool) -> Expr {
+fn parse_funcy(&mut self, is_async: bool) -> PResult<Expr> {
+    let start = self.prev_start(); // the 'func'/'funcy' keyword
     // expect function name
     // ✅ allow both identifiers and 'new' keyword
-let name = match self.advance().clone() {
+let name = match self.peek().clone() {
     TokenKind::Identifier(n) => n,
     TokenKind::Keyword(k) if k == "new" => "new".to_string(),
-    other => panic!("Expected function name after 'func' or 'funcy', got {:?}", other),
+    _ => return Err(self.error_here("Expected function name after 'func' or 'funcy'")),
 };
+self.advance();
 
 
     // expect '('
-    self.expect(TokenKind::Symbol("(".into()), "Expected '(' after function name");
+    self.expect(TokenKind::Symbol("(".into()), "Expected '(' after function name")?;
 
     // === 🧠 parse parameters (with optional types and patterns)
     let mut params = Vec::new();
@@ -680,10 +943,7 @@ let name = match self.advance().clone() {
     if !self.check(TokenKind::Symbol(")".into())) {
         loop {
             // parameter name
-            let param_name = match self.advance().clone() {
-                TokenKind::Identifier(p) => p,
-                other => panic!("Expected parameter name, got {:?}", other),
-            };
+            let param_name = self.expect_identifier("Expected parameter name")?;
 
             // optional type annotation or pattern like a: f32, req: Request, status: 200, code: 2xx, fn: func(i32) -> string
             let mut param_type = "i32".to_string(); // default type
@@ -693,7 +953,7 @@ let name = match self.advance().clone() {
                 self.advance(); // consume ':'
 
                 // Use the new parse_type_annotation method to handle all type annotations including function types
-                let type_descriptor = self.parse_type_annotation();
+                let type_descriptor = self.parse_type_annotation()?;
 
                 // Set param_type string for backward compatibility
                 param_type = type_descriptor.to_mangle_string();
@@ -710,6 +970,7 @@ let name = match self.advance().clone() {
                     TypeDescriptor::Function { .. } |
                     TypeDescriptor::Entity(_) |
                     TypeDescriptor::ObjectType(_) |
+                    TypeDescriptor::Interface(_) |
                     TypeDescriptor::HttpStatusLiteral(_) |
                     TypeDescriptor::HttpStatusRange(_, _) => {
                         // All these create dispatch patterns
@@ -745,13 +1006,13 @@ let name = match self.advance().clone() {
         }
     }
 
-    self.expect(TokenKind::Symbol(")".into()), "Expected ')' after parameters");
+    self.expect(TokenKind::Symbol(")".into()), "Expected ')' after parameters")?;
 
     // === 📝 Optional return type annotation: -> RetType
     let return_type = if self.check(TokenKind::Symbol("-".into())) {
         self.advance(); // consume '-'
-        self.expect(TokenKind::Symbol(">".into()), "Expected '>' after '-' in function return type");
-        Some(self.parse_type_annotation())
+        self.expect(TokenKind::Symbol(">".into()), "Expected '>' after '-' in function return type")?;
+        Some(self.parse_type_annotation()?)
     } else {
         None
     };
@@ -763,61 +1024,51 @@ let name = match self.advance().clone() {
             self.advance(); // consume '>'
 
             // parse single-expression arrow body
-            let expr = self.parse_expr();
-            let body = vec![Node::Expr(Expr::Return(Some(Box::new(expr))))];
+            let expr = self.parse_expr()?;
+            let body_span = expr.span;
+            let body = vec![Node::from(Expr::new(ExprKind::Return(Some(Box::new(expr))), body_span))];
 
-            return Expr::Funcy {
+            return Ok(Expr::new(ExprKind::Funcy {
                 name,
                 params,
                 params_patterns: if has_patterns { Some(params_patterns) } else { None },
                 body,
                 is_async,
                 return_type,
-            };
+            }, self.span_from(start)));
         } else {
-            panic!("Expected '>' after '=' for arrow function");
+            return Err(self.error_here("Expected '>' after '=' for arrow function"));
         }
     }
 
     // === 🧱 fallback to block-style: "{ ... }"
-    self.expect(TokenKind::Symbol("{".into()), "Expected '{' to start function body");
+    self.expect(TokenKind::Symbol("{".into()), "Expected '{' to start function body")?;
 
-    let mut body = Vec::new();
-    while !self.check(TokenKind::Symbol("}".into())) && !self.check(TokenKind::EOF) {
-        if let Some(stmt) = self.parse_stmt() {
-            body.push(stmt);
-        } else {
-            self.advance();
-        }
-    }
+    let body = self.parse_stmt_list();
 
-    self.expect(TokenKind::Symbol("}".into()), "Expected '}' to close function body");
+    self.expect(TokenKind::Symbol("}".into()), "Expected '}' to close function body")?;
 
-    Expr::Funcy {
+    Ok(Expr::new(ExprKind::Funcy {
         name,
         params,
         params_patterns: if has_patterns { Some(params_patterns) } else { None },
         body,
         is_async,
         return_type,
-    }
+    }, self.span_from(start)))
 }
 
 /// Parse type alias: type Name = { "field": type, ... }
-fn parse_type_alias(&mut self) -> Option<Node> {
+fn parse_type_alias(&mut self) -> PResult<Option<Node>> {
+    let start = self.prev_start(); // the 'type' keyword
     // Expect type name
-    let name = match self.advance().clone() {
-        TokenKind::Identifier(n) => n,
-        other => {
-            panic!("Expected type name after 'type', got {:?}", other);
-        }
-    };
+    let name = self.expect_identifier("Expected type name after 'type'")?;
 
     // Expect '='
-    self.expect(TokenKind::Symbol("=".into()), "Expected '=' after type name");
+    self.expect(TokenKind::Symbol("=".into()), "Expected '=' after type name")?;
 
     // Expect '{'
-    self.expect(TokenKind::Symbol("{".into()), "Expected '{' to start object type definition");
+    self.expect(TokenKind::Symbol("{".into()), "Expected '{' to start object type definition")?;
 
     let mut fields = Vec::new();
 
@@ -825,20 +1076,18 @@ fn parse_type_alias(&mut self) -> Option<Node> {
     if !self.check(TokenKind::Symbol("}".into())) {
         loop {
             // Field name (string)
-            let field_name = match self.advance().clone() {
+            let field_name = match self.peek().clone() {
                 TokenKind::String(s) => s,
                 TokenKind::Identifier(id) => id,
-                other => panic!("Expected field name, got {:?}", other),
+                _ => return Err(self.error_here("Expected field name")),
             };
+            self.advance();
 
             // Expect ':'
-            self.expect(TokenKind::Symbol(":".into()), "Expected ':' after field name");
+            self.expect(TokenKind::Symbol(":".into()), "Expected ':' after field name")?;
 
             // Field type
-            let field_type_str = match self.advance().clone() {
-                TokenKind::Identifier(ty) => ty,
-                other => panic!("Expected field type, got {:?}", other),
-            };
+            let field_type_str = self.expect_identifier("Expected field type")?;
 
             let field_type = FieldType::from_string(&field_type_str);
             fields.push(ObjectField {
@@ -850,14 +1099,113 @@ fn parse_type_alias(&mut self) -> Option<Node> {
             if self.check(TokenKind::Symbol("}".into())) {
                 break;
             }
-            self.expect(TokenKind::Symbol(",".into()), "Expected ',' between fields");
+            self.expect(TokenKind::Symbol(",".into()), "Expected ',' between fields")?;
         }
     }
 
     // Expect '}'
-    self.expect(TokenKind::Symbol("}".into()), "Expected '}' to close object type definition");
+    self.expect(TokenKind::Symbol("}".into()), "Expected '}' to close object type definition")?;
 
-    Some(Node::TypeAlias(ObjectTypeDefinition { name, fields }))
+    Ok(Some(Node::new(NodeKind::TypeAlias(ObjectTypeDefinition { name, fields }), self.span_from(start))))
+}
+
+/// `interface Speaker { speak() greet(name) }`
+fn parse_interface(&mut self) -> PResult<Node> {
+    let start = self.prev_start(); // the 'interface' keyword
+    let name = self.expect_identifier("Expected interface name after 'interface'")?;
+    self.expect(TokenKind::Symbol("{".into()), "Expected '{' to start interface methods")?;
+
+    let mut methods: Vec<InterfaceMethod> = Vec::new();
+    while !self.check(TokenKind::Symbol("}".into())) && !self.check(TokenKind::EOF) {
+        // Methods may be written like entity methods: `funcy speak()`
+        self.matches(&[TokenKind::Keyword("func".into()), TokenKind::Keyword("funcy".into())]);
+        let method_start = self.start_pos();
+        let method = self.expect_identifier("Expected method name")?;
+        if methods.iter().any(|m| m.name == method) {
+            return Err(Diagnostic::error(
+                self.span_from(method_start),
+                format!("Method `{}` is declared twice in interface `{}`", method, name),
+            ));
+        }
+
+        self.expect(TokenKind::Symbol("(".into()), "Expected '(' after method name")?;
+        let mut params = Vec::new();
+        if !self.check(TokenKind::Symbol(")".into())) {
+            loop {
+                params.push(self.expect_identifier("Expected parameter name")?);
+                // A parameter type is documentation only: the entity's method decides it
+                if self.matches(&[TokenKind::Symbol(":".into())]) {
+                    self.parse_type_annotation()?;
+                }
+                if !self.matches(&[TokenKind::Symbol(",".into())]) {
+                    break;
+                }
+            }
+        }
+        self.expect(TokenKind::Symbol(")".into()), "Expected ')' after parameters")?;
+        // So is an optional `-> RetType`
+        if self.matches(&[TokenKind::Symbol("-".into())]) {
+            self.expect(TokenKind::Symbol(">".into()), "Expected '>' after '-' in method return type")?;
+            self.parse_type_annotation()?;
+        }
+        methods.push(InterfaceMethod { name: method, params });
+
+        // Methods may be separated by `;` or `,`
+        self.matches(&[TokenKind::Symbol(";".into()), TokenKind::Symbol(",".into())]);
+    }
+
+    self.expect(TokenKind::Symbol("}".into()), "Expected '}' to close interface")?;
+    Ok(Node::new(NodeKind::Interface(InterfaceNode { name, methods }), self.span_from(start)))
+}
+
+/// `enum Shape { Circle(f64), Rect(f64, f64), Empty }`
+fn parse_enum(&mut self) -> PResult<Node> {
+    let start = self.prev_start(); // the 'enum' keyword
+    let name = self.expect_identifier("Expected enum name after 'enum'")?;
+    self.expect(TokenKind::Symbol("{".into()), "Expected '{' to start enum variants")?;
+
+    let mut variants: Vec<EnumVariant> = Vec::new();
+    let mut duplicate = None;
+    while !self.check(TokenKind::Symbol("}".into())) && !self.check(TokenKind::EOF) {
+        let variant_start = self.start_pos();
+        let variant = self.expect_identifier("Expected variant name")?;
+        if duplicate.is_none() && variants.iter().any(|v| v.name == variant) {
+            duplicate = Some(Diagnostic::error(
+                self.span_from(variant_start),
+                format!("Variant `{}` is declared twice in enum `{}`", variant, name),
+            ));
+        }
+
+        // Optional payload: Rect(f64, f64)
+        let mut fields = Vec::new();
+        if self.matches(&[TokenKind::Symbol("(".into())]) {
+            if !self.check(TokenKind::Symbol(")".into())) {
+                loop {
+                    fields.push(FieldType::from_string(&self.parse_let_type()?));
+                    if !self.matches(&[TokenKind::Symbol(",".into())]) {
+                        break;
+                    }
+                }
+            }
+            self.expect(TokenKind::Symbol(")".into()), "Expected ')' after variant fields")?;
+        }
+        variants.push(EnumVariant { name: variant, fields });
+
+        // Variants are comma separated; a trailing comma is allowed
+        if !self.matches(&[TokenKind::Symbol(",".into())]) {
+            break;
+        }
+    }
+
+    self.expect(TokenKind::Symbol("}".into()), "Expected '}' to close enum")?;
+    if let Some(diag) = duplicate {
+        return Err(diag);
+    }
+    if variants.is_empty() {
+        return Err(Diagnostic::error(self.span_from(start), format!("Enum `{}` has no variants", name)));
+    }
+
+    Ok(Node::new(NodeKind::Enum(EnumDefinition { name, variants }), self.span_from(start)))
 }
 
 
@@ -870,13 +1218,13 @@ fn parse_type_alias(&mut self) -> Option<Node> {
 }
 impl Parser {
     /// Entry point for expression parsing
-    pub fn parse_expr(&mut self) -> Expr {
+    pub fn parse_expr(&mut self) -> PResult<Expr> {
     self.parse_assignment()
 }
 
-fn parse_assignment(&mut self) -> Expr {
+fn parse_assignment(&mut self) -> PResult<Expr> {
     // 🧠 Start from logical OR, not equality
-    let left = self.parse_logical_or();
+    let left = self.parse_logical_or()?;
 
     if self.matches(&[TokenKind::Symbol("=".into())]) {
         let op = if let TokenKind::Symbol(op) = self.tokens[self.pos - 1].kind.clone() {
@@ -884,69 +1232,105 @@ fn parse_assignment(&mut self) -> Expr {
         } else {
             unreachable!()
         };
-        let right = self.parse_assignment(); // allow chaining
-        return Expr::BinaryOp {
+        let right = self.parse_assignment()?; // allow chaining
+        let span = left.span.to(right.span);
+        return Ok(Expr::new(ExprKind::BinaryOp {
             left: Box::new(left),
             op,
             right: Box::new(right),
-        };
+        }, span));
+    }
+
+    // `x += e` is sugar for `x = x + e`
+    if self.matches(&[
+        TokenKind::Symbol("+=".into()),
+        TokenKind::Symbol("-=".into()),
+        TokenKind::Symbol("*=".into()),
+        TokenKind::Symbol("/=".into())
+    ]) {
+        let op = if let TokenKind::Symbol(op) = self.tokens[self.pos - 1].kind.clone() { op } else { unreachable!() };
+        let right = self.parse_assignment()?;
+        let span = left.span.to(right.span);
+        return Ok(Self::compound_assign(left, &op[..1], right, span));
     }
 
-    left
+    Ok(left)
+}
+
+/// The unsuffixed literal `1`, which adopts the type of whatever it is added to
+fn one(span: Span) -> Expr {
+    Expr::new(ExprKind::TypedLiteral { value: "1".into(), ty: "i32".into() }, span)
+}
+
+/// Build `target = target op value`, the desugaring of `op=` and `++`/`--`
+fn compound_assign(target: Expr, op: &str, value: Expr, span: Span) -> Expr {
+    let current = Expr::new(ExprKind::BinaryOp {
+        left: Box::new(target.clone()),
+        op: op.to_string(),
+        right: Box::new(value),
+    }, span);
+    Expr::new(ExprKind::BinaryOp {
+        left: Box::new(target),
+        op: "=".to_string(),
+        right: Box::new(current),
+    }, span)
 }
 
 
 }
 impl Parser {
-    fn parse_equality(&mut self) -> Expr {
-        let mut expr = self.parse_comparison();
+    fn parse_equality(&mut self) -> PResult<Expr> {
+        let mut expr = self.parse_comparison()?;
 
         while self.matches(&[
             TokenKind::Symbol("==".into()),
             TokenKind::Symbol("!=".into())
         ]) {
             let op = if let TokenKind::Symbol(op) = self.tokens[self.pos - 1].kind.clone() { op } else { unreachable!() };
-            let right = self.parse_comparison();
-            expr = Expr::BinaryOp { left: Box::new(expr), op, right: Box::new(right) };
+            let right = self.parse_comparison()?;
+            let span = expr.span.to(right.span);
+            expr = Expr::new(ExprKind::BinaryOp { left: Box::new(expr), op, right: Box::new(right) }, span);
         }
 
-        expr
+        Ok(expr)
     }
-    fn parse_logical_or(&mut self) -> Expr {
-    let mut expr = self.parse_logical_and();
+    fn parse_logical_or(&mut self) -> PResult<Expr> {
+    let mut expr = self.parse_logical_and()?;
 
-    while self.matches(&[TokenKind::Identifier("or".into())]) {
+    while self.matches(&[TokenKind::Identifier("or".into()), TokenKind::Symbol("||".into())]) {
         let op = "or".to_string();
-        let right = self.parse_logical_and();
-        expr = Expr::BinaryOp {
+        let right = self.parse_logical_and()?;
+        let span = expr.span.to(right.span);
+        expr = Expr::new(ExprKind::BinaryOp {
             left: Box::new(expr),
             op,
             right: Box::new(right),
-        };
+        }, span);
     }
 
-    expr
+    Ok(expr)
 }
 
-fn parse_logical_and(&mut self) -> Expr {
-    let mut expr = self.parse_equality();
+fn parse_logical_and(&mut self) -> PResult<Expr> {
+    let mut expr = self.parse_equality()?;
 
-    while self.matches(&[TokenKind::Identifier("and".into())]) {
+    while self.matches(&[TokenKind::Identifier("and".into()), TokenKind::Symbol("&&".into())]) {
         let op = "and".to_string();
-        let right = self.parse_equality();
-        expr = Expr::BinaryOp {
+        let right = self.parse_equality()?;
+        let span = expr.span.to(right.span);
+        expr = Expr::new(ExprKind::BinaryOp {
             left: Box::new(expr),
             op,
             right: Box::new(right),
-        };
+        }, span);
     }
 
-    expr
+    Ok(expr)
 }
 
 }impl Parser {
-    fn parse_comparison(&mut self) -> Expr {
-        let mut expr = self.parse_term();
+    fn parse_comparison(&mut self) -> PResult<Expr> {
+        let mut expr = self.parse_bit_or()?;
 
         while self.matches(&[
             TokenKind::Symbol("<".into()),
@@ -955,93 +1339,226 @@ fn parse_logical_and(&mut self) -> Expr {
             TokenKind::Symbol(">=".into())
         ]) {
             let op = if let TokenKind::Symbol(op) = self.tokens[self.pos - 1].kind.clone() { op } else { unreachable!() };
-            let right = self.parse_term();
-            expr = Expr::BinaryOp { left: Box::new(expr), op, right: Box::new(right) };
+            let right = self.parse_bit_or()?;
+            let span = expr.span.to(right.span);
+            expr = Expr::new(ExprKind::BinaryOp { left: Box::new(expr), op, right: Box::new(right) }, span);
+        }
+
+        Ok(expr)
+    }
+
+    /// Bitwise operators bind tighter than comparisons: `a & mask == 0` tests the masked value
+    fn parse_bit_or(&mut self) -> PResult<Expr> {
+        let mut expr = self.parse_bit_xor()?;
+
+        while self.matches(&[TokenKind::Symbol("|".into())]) {
+            let right = self.parse_bit_xor()?;
+            let span = expr.span.to(right.span);
+            expr = Expr::new(ExprKind::BinaryOp { left: Box::new(expr), op: "|".into(), right: Box::new(right) }, span);
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_bit_xor(&mut self) -> PResult<Expr> {
+        let mut expr = self.parse_bit_and()?;
+
+        while self.matches(&[TokenKind::Symbol("^".into())]) {
+            let right = self.parse_bit_and()?;
+            let span = expr.span.to(right.span);
+            expr = Expr::new(ExprKind::BinaryOp { left: Box::new(expr), op: "^".into(), right: Box::new(right) }, span);
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_bit_and(&mut self) -> PResult<Expr> {
+        let mut expr = self.parse_shift()?;
+
+        while self.matches(&[TokenKind::Symbol("&".into())]) {
+            let right = self.parse_shift()?;
+            let span = expr.span.to(right.span);
+            expr = Expr::new(ExprKind::BinaryOp { left: Box::new(expr), op: "&".into(), right: Box::new(right) }, span);
+        }
+
+        Ok(expr)
+    }
+
+    fn parse_shift(&mut self) -> PResult<Expr> {
+        let mut expr = self.parse_term()?;
+
+        while self.matches(&[
+            TokenKind::Symbol("<<".into()),
+            TokenKind::Symbol(">>".into())
+        ]) {
+            let op = if let TokenKind::Symbol(op) = self.tokens[self.pos - 1].kind.clone() { op } else { unreachable!() };
+            let right = self.parse_term()?;
+            let span = expr.span.to(right.span);
+            expr = Expr::new(ExprKind::BinaryOp { left: Box::new(expr), op, right: Box::new(right) }, span);
         }
 
-        expr
+        Ok(expr)
     }
 }impl Parser {
-    fn parse_term(&mut self) -> Expr {
-        let mut expr = self.parse_factor();
+    fn parse_term(&mut self) -> PResult<Expr> {
+        let mut expr = self.parse_factor()?;
 
         while self.matches(&[
             TokenKind::Symbol("+".into()),
             TokenKind::Symbol("-".into())
         ]) {
             let op = if let TokenKind::Symbol(op) = self.tokens[self.pos - 1].kind.clone() { op } else { unreachable!() };
-            let right = self.parse_factor();
-            expr = Expr::BinaryOp { left: Box::new(expr), op, right: Box::new(right) };
+            let right = self.parse_factor()?;
+            let span = expr.span.to(right.span);
+            expr = Expr::new(ExprKind::BinaryOp { left: Box::new(expr), op, right: Box::new(right) }, span);
         }
 
-        expr
+        Ok(expr)
     }
 }impl Parser {
-    fn parse_factor(&mut self) -> Expr {
-        let mut expr = self.parse_unary();
+    fn parse_factor(&mut self) -> PResult<Expr> {
+        let mut expr = self.parse_cast()?;
 
         while self.matches(&[
             TokenKind::Symbol("*".into()),
-            TokenKind::Symbol("/".into())
+            TokenKind::Symbol("/".into()),
+            TokenKind::Symbol("%".into())
         ]) {
             let op = if let TokenKind::Symbol(op) = self.tokens[self.pos - 1].kind.clone() { op } else { unreachable!() };
-            let right = self.parse_unary();
-            expr = Expr::BinaryOp { left: Box::new(expr), op, right: Box::new(right) };
+            let right = self.parse_cast()?;
+            let span = expr.span.to(right.span);
+            expr = Expr::new(ExprKind::BinaryOp { left: Box::new(expr), op, right: Box::new(right) }, span);
         }
 
-        expr
+        Ok(expr)
+    }
+
+    /// `value as u8`, binding tighter than `*` and looser than unary minus
+    fn parse_cast(&mut self) -> PResult<Expr> {
+        let mut expr = self.parse_unary()?;
+
+        while self.matches(&[TokenKind::Keyword("as".into())]) {
+            let ty = self.expect_identifier("Expected a type after 'as'")?;
+            let span = expr.span.to(self.tokens[self.pos - 1].span);
+            expr = Expr::new(ExprKind::Cast { expr: Box::new(expr), ty }, span);
+        }
+
+        Ok(expr)
     }
 }impl Parser {
-    fn parse_unary(&mut self) -> Expr {
+    fn parse_unary(&mut self) -> PResult<Expr> {
+        // `++x` is `x = x + 1` and yields the new value
+        if self.matches(&[
+            TokenKind::Symbol("++".into()),
+            TokenKind::Symbol("--".into())
+        ]) {
+            let op_span = self.tokens[self.pos - 1].span;
+            let op = if self.tokens[self.pos - 1].kind == TokenKind::Symbol("++".into()) { "+" } else { "-" };
+            let target = self.parse_unary()?;
+            if !matches!(target.kind, ExprKind::Variable(_) | ExprKind::Index { .. }) {
+                return Err(Diagnostic::error(target.span, "increment and decrement need a variable or element"));
+            }
+            let span = op_span.to(target.span);
+            return Ok(Self::compound_assign(target, op, Self::one(op_span), span));
+        }
+
         if self.matches(&[
             TokenKind::Symbol("-".into()),
-            TokenKind::Symbol("!".into())
+            TokenKind::Symbol("!".into()),
+            TokenKind::Symbol("~".into())
         ]) {
             let op = if let TokenKind::Symbol(op) = self.tokens[self.pos - 1].kind.clone() { op } else { unreachable!() };
-            let right = self.parse_unary();
-            return Expr::BinaryOp {
-                left: Box::new(Expr::Literal(0)),
+            let op_span = self.tokens[self.pos - 1].span;
+            let right = self.parse_unary()?;
+            let span = op_span.to(right.span);
+            // `-128i8` is one literal, so its range is checked with the sign
+            if let (ExprKind::TypedLiteral { value, ty }, "-") = (&right.kind, op.as_str())
+                && !value.starts_with('-')
+            {
+                return Ok(Expr::new(ExprKind::TypedLiteral { value: format!("-{}", value), ty: ty.clone() }, span));
+            }
+            return Ok(Expr::new(ExprKind::BinaryOp {
+                left: Box::new(Expr::new(ExprKind::Literal(0), op_span)),
                 op,
                 right: Box::new(right),
-            };
+            }, span));
         }
 
-        self.parse_primary()
+        self.parse_postfix()
+    }
+
+    /// Postfix index access: `a[i]`, `grid[i][j]`, `make()[0]`
+    fn parse_postfix(&mut self) -> PResult<Expr> {
+        let mut expr = self.parse_primary()?;
+
+        while self.matches(&[TokenKind::Symbol("[".into())]) {
+            let index = self.parse_expr()?;
+            self.expect(TokenKind::Symbol("]".into()), "Expected ']' after index")?;
+            let span = expr.span.to(self.tokens[self.pos - 1].span);
+            expr = Expr::new(ExprKind::Index {
+                target: Box::new(expr),
+                index: Box::new(index),
+            }, span);
+        }
+
+        // `x++` stores `x + 1` but yields the old value: `(x = x + 1) - 1`
+        if matches!(expr.kind, ExprKind::Variable(_) | ExprKind::Index { .. })
+            && self.matches(&[
+                TokenKind::Symbol("++".into()),
+                TokenKind::Symbol("--".into())
+            ])
+        {
+            let op_span = self.tokens[self.pos - 1].span;
+            let (op, undo) = if self.tokens[self.pos - 1].kind == TokenKind::Symbol("++".into()) { ("+", "-") } else { ("-", "+") };
+            let span = expr.span.to(op_span);
+            let updated = Self::compound_assign(expr, op, Self::one(op_span), span);
+            expr = Expr::new(ExprKind::BinaryOp {
+                left: Box::new(updated),
+                op: undo.to_string(),
+                right: Box::new(Self::one(op_span)),
+            }, span);
+        }
+
+        Ok(expr)
     }
 }impl Parser {
-    fn parse_primary(&mut self) -> Expr {
+    fn parse_primary(&mut self) -> PResult<Expr> {
+    let start = self.start_pos();
     // ✅ handle 'await' keyword
     if self.check(TokenKind::Keyword("await".into())) {
         self.advance();
-        let inner = self.parse_primary();
-        return Expr::Await(Box::new(inner));
+        let inner = self.parse_primary()?;
+        return Ok(Expr::new(ExprKind::Await(Box::new(inner)), self.span_from(start)));
     }
         // 🆕 handle 'new' keyword for entity instantiation
     if self.check(TokenKind::Keyword("new".into())) {
         self.advance(); // consume 'new'
 
-        // Expect an entity name next
-        let entity = match self.advance().clone() {
-            TokenKind::Identifier(id) => id,
-            other => panic!("Expected entity name after 'new', got {:?}", other),
-        };
-
-        // Parse optional argument list
-        self.expect(TokenKind::Symbol("(".into()), "Expected '(' after entity name");
         let mut args = Vec::new();
-        if !self.check(TokenKind::Symbol(")".into())) {
-            loop {
-                args.push(self.parse_expr());
-                if self.check(TokenKind::Symbol(",".into())) {
-                    self.advance();
-                    continue;
+        let entity = if self.matches(&[TokenKind::Symbol("(".into())]) {
+            // `new(Dog, "Rex", 3)`
+            let entity = self.expect_identifier("Expected entity name after 'new('")?;
+            while self.matches(&[TokenKind::Symbol(",".into())]) {
+                args.push(self.parse_expr()?);
+            }
+            entity
+        } else {
+            // `new Dog("Rex", 3)`
+            let entity = self.expect_identifier("Expected entity name after 'new'")?;
+            self.expect(TokenKind::Symbol("(".into()), "Expected '(' after entity name")?;
+            if !self.check(TokenKind::Symbol(")".into())) {
+                loop {
+                    args.push(self.parse_expr()?);
+                    if !self.matches(&[TokenKind::Symbol(",".into())]) {
+                        break;
+                    }
                 }
-                break;
             }
-        }
-        self.expect(TokenKind::Symbol(")".into()), "Expected ')' after arguments");
+            entity
+        };
+        self.expect(TokenKind::Symbol(")".into()), "Expected ')' after arguments")?;
 
-        return Expr::NewInstance { entity, args };
+        return Ok(Expr::new(ExprKind::NewInstance { entity, args }, self.span_from(start)));
     }
 
     // ✅ handle array literals
@@ -1069,15 +1586,12 @@ if let TokenKind::Keyword(k) = self.peek() {
             self.advance();
         }
 
-        self.expect(TokenKind::Symbol("(".into()), "Expected '(' after func");
+        self.expect(TokenKind::Symbol("(".into()), "Expected '(' after func")?;
         let mut params = Vec::new();
 
         if !self.check(TokenKind::Symbol(")".into())) {
             loop {
-                match self.advance().clone() {
-                    TokenKind::Identifier(p) => params.push(p),
-                    other => panic!("Expected parameter name, got {:?}", other),
-                }
+                params.push(self.expect_identifier("Expected parameter name")?);
                 if self.check(TokenKind::Symbol(",".into())) {
                     self.advance();
                     continue;
@@ -1087,54 +1601,62 @@ if let TokenKind::Keyword(k) = self.peek() {
             }
         }
 
-        self.expect(TokenKind::Symbol(")".into()), "Expected ')' after params");
+        self.expect(TokenKind::Symbol(")".into()), "Expected ')' after params")?;
 
         // Parse the function body
-        let body = self.parse_block();
+        let body = self.parse_block()?;
 
-        return Expr::Funcy {
+        return Ok(Expr::new(ExprKind::Funcy {
             name,
             params,
             params_patterns: None, // No patterns for entity methods
             body,
             is_async: false,
             return_type: None,
-        };
+        }, self.span_from(start)));
     }
 }
 
+    if self.check(TokenKind::Keyword("match".into())) {
+        self.advance(); // consume 'match'
+        return self.parse_match();
+    }
+
     // ✅ fallback to existing literal/identifier logic
-    match self.advance().clone() {
-        TokenKind::Number { raw, ty } => Expr::TypedLiteral { value: raw, ty },
-        TokenKind::String(s) => Expr::StringLiteral(s),
+    let token = match self.advance().clone() {
+        // `me` is the instance inside entity methods and reads like any variable
+        TokenKind::Keyword(k) if k == "me" => TokenKind::Identifier(k),
+        other => other,
+    };
+    let kind = match token {
+        TokenKind::Number { raw, ty } => ExprKind::TypedLiteral { value: raw, ty },
+        TokenKind::String(s) => ExprKind::StringLiteral(s),
+        TokenKind::InterpolatedString(parts) => self.parse_interpolation(parts)?,
         TokenKind::Identifier(mut name) => {
     // 🔗 Merge dotted identifiers like "server.register" or "http.get"
     while self.check(TokenKind::Symbol(".".into())) {
         self.advance(); // consume '.'
-        match self.advance().clone() {
-            TokenKind::Identifier(next) => {
-                name = format!("{}.{}", name, next);
-            }
-            other => panic!("Expected identifier after '.', got {:?}", other),
-        }
+        let next = self.expect_identifier("Expected identifier after '.'")?;
+        name = format!("{}.{}", name, next);
     }
 
     // 🏷️ Handle typed object literals: Request { "method": "GET" }
-    if self.check(TokenKind::Symbol("{".into())) {
+    if !self.no_typed_object && self.check(TokenKind::Symbol("{".into())) {
         self.advance(); // consume '{'
         let mut fields = Vec::new();
 
         if !self.check(TokenKind::Symbol("}".into())) {
             loop {
                 // Field name (must be string literal)
-                let field_name = if let TokenKind::String(s) = self.advance().clone() {
+                let field_name = if let TokenKind::String(s) = self.peek().clone() {
+                    self.advance();
                     s
                 } else {
-                    panic!("Expected string literal for object field name");
+                    return Err(self.error_here("Expected string literal for object field name"));
                 };
 
-                self.expect(TokenKind::Symbol(":".into()), "Expected ':' after field name");
-                let field_value = self.parse_expr();
+                self.expect(TokenKind::Symbol(":".into()), "Expected ':' after field name")?;
+                let field_value = self.parse_expr()?;
                 fields.push((field_name, field_value));
 
                 if self.check(TokenKind::Symbol(",".into())) {
@@ -1149,11 +1671,11 @@ if let TokenKind::Keyword(k) = self.peek() {
             }
         }
 
-        self.expect(TokenKind::Symbol("}".into()), "Expected '}' to close typed object literal");
-        return Expr::ObjectLiteral {
+        self.expect(TokenKind::Symbol("}".into()), "Expected '}' to close typed object literal")?;
+        return Ok(Expr::new(ExprKind::ObjectLiteral {
             fields,
             type_name: Some(name.clone()),
-        };
+        }, self.span_from(start)));
     }
 
     // 📞 Handle function calls (after full name is built)
@@ -1161,46 +1683,51 @@ if let TokenKind::Keyword(k) = self.peek() {
         let mut args = Vec::new();
         if !self.check(TokenKind::Symbol(")".into())) {
             loop {
-                args.push(self.parse_expr());
+                args.push(self.parse_expr()?);
                 if !self.matches(&[TokenKind::Symbol(",".into())]) {
                     break;
                 }
             }
         }
-        self.expect(TokenKind::Symbol(")".into()), "Expected ')' after function args");
-        Expr::Call { name, args }
+        self.expect(TokenKind::Symbol(")".into()), "Expected ')' after function args")?;
+        ExprKind::Call { name, args }
     } else {
-        Expr::Variable(name)
+        ExprKind::Variable(name)
     }
 }
 
-        TokenKind::Keyword(k) if k == "true" => Expr::BoolLiteral(true),
-        TokenKind::Keyword(k) if k == "false" => Expr::BoolLiteral(false),
+        TokenKind::Keyword(k) if k == "true" => ExprKind::BoolLiteral(true),
+        TokenKind::Keyword(k) if k == "false" => ExprKind::BoolLiteral(false),
         TokenKind::Symbol(sym) if sym == "(".to_string() => {
-            let expr = self.parse_expr();
-            self.expect(TokenKind::Symbol(")".into()), "Expected ')' after group");
-            expr
+            let expr = self.parse_expr()?;
+            self.expect(TokenKind::Symbol(")".into()), "Expected ')' after group")?;
+            return Ok(expr);
         }
-        unexpected => {
-            println!("⚠️ Unexpected token in expression: {:?}", unexpected);
-            Expr::Literal(0)
+        TokenKind::EOF => {
+            return Err(self.error_here("Unexpected end of file, expected an expression"));
         }
-    }
+        _ => {
+            // step back so the error points at the offending token and recovery can see it
+            self.pos -= 1;
+            return Err(self.error_here("Expected an expression"));
+        }
+    };
+    Ok(Expr::new(kind, self.span_from(start)))
 }
 
     /// Parse a type annotation (used for parameter types, including function types)
     /// Supports syntax like: i32, string, Dog, func(i32) -> string, func(i32, string) -> bool
-    fn parse_type_annotation(&mut self) -> TypeDescriptor {
-        match self.peek().clone() {
+    fn parse_type_annotation(&mut self) -> PResult<TypeDescriptor> {
+        Ok(match self.peek().clone() {
             // Function type: func(Type1, Type2) -> RetType
             TokenKind::Keyword(k) if k == "func" || k == "funcy" => {
                 self.advance(); // consume 'func' or 'funcy'
-                self.expect(TokenKind::Symbol("(".into()), "Expected '(' after func in type annotation");
+                self.expect(TokenKind::Symbol("(".into()), "Expected '(' after func in type annotation")?;
 
                 let mut param_types = Vec::new();
                 if !self.check(TokenKind::Symbol(")".into())) {
                     loop {
-                        param_types.push(self.parse_type_annotation());
+                        param_types.push(self.parse_type_annotation()?);
                         if self.check(TokenKind::Symbol(",".into())) {
                             self.advance();
                             continue;
@@ -1209,13 +1736,13 @@ if let TokenKind::Keyword(k) = self.peek() {
                         }
                     }
                 }
-                self.expect(TokenKind::Symbol(")".into()), "Expected ')' after function parameter types");
+                self.expect(TokenKind::Symbol(")".into()), "Expected ')' after function parameter types")?;
 
                 // Optional return type annotation: -> RetType
                 let return_type = if self.check(TokenKind::Symbol("-".into())) {
                     self.advance(); // consume '-'
-                    self.expect(TokenKind::Symbol(">".into()), "Expected '>' after '-' in return type");
-                    Box::new(self.parse_type_annotation())
+                    self.expect(TokenKind::Symbol(">".into()), "Expected '>' after '-' in return type")?;
+                    Box::new(self.parse_type_annotation()?)
                 } else {
                     // Default return type is i32
                     Box::new(TypeDescriptor::Primitive("i32".to_string()))
@@ -1233,7 +1760,7 @@ if let TokenKind::Keyword(k) = self.peek() {
                     let max = min + 99;
                     TypeDescriptor::HttpStatusRange(min, max)
                 } else {
-                    panic!("Invalid HTTP range pattern: {}", id);
+                    return Err(self.error_here(&format!("Invalid HTTP range pattern: {}", id)));
                 }
             },
 
@@ -1243,7 +1770,10 @@ if let TokenKind::Keyword(k) = self.peek() {
                 self.advance();
 
                 // Check if it's a primitive type
-                if ["i32", "i64", "i8", "i16", "u8", "u16", "u32", "u64", "f32", "f64", "bool", "ptr", "string", "str"].contains(&name.as_str()) {
+                if name == "str" {
+                    // `str` is the spelling used in `let` and field annotations
+                    TypeDescriptor::Primitive("string".to_string())
+                } else if ["i32", "i64", "i8", "i16", "u8", "u16", "u32", "u64", "f32", "f64", "bool", "ptr", "string"].contains(&name.as_str()) {
                     TypeDescriptor::Primitive(name)
                 } else {
                     // Assume it's an object type or entity (will be resolved in codegen)
@@ -1255,6 +1785,13 @@ if let TokenKind::Keyword(k) = self.peek() {
             TokenKind::Number { ref raw, .. } => {
                 let raw = raw.clone();
                 self.advance();
+                // `2xx` lexes as the number `2` followed by `xx`
+                if self.check(TokenKind::Identifier("xx".into()))
+                    && let Ok(class @ 1..=5) = raw.parse::<u16>()
+                {
+                    self.advance();
+                    return Ok(TypeDescriptor::HttpStatusRange(class * 100, class * 100 + 99));
+                }
                 if let Ok(code) = raw.parse::<u16>() {
                     if code >= 100 && code < 600 {
                         TypeDescriptor::HttpStatusLiteral(code)
@@ -1262,42 +1799,164 @@ if let TokenKind::Keyword(k) = self.peek() {
                         TypeDescriptor::Primitive("i32".to_string())
                     }
                 } else {
-                    panic!("Invalid numeric type annotation: {}", raw);
+                    return Err(self.error_here(&format!("Invalid numeric type annotation: {}", raw)));
                 }
             },
 
-            other => {
-                self.error_here(&format!("Expected type annotation, got {:?}", other))
+            _ => {
+                return Err(self.error_here("Expected type annotation"));
             }
-        }
+        })
     }
 
 
 
-    fn expect(&mut self, kind: TokenKind, msg: &str) {
+    fn expect(&mut self, kind: TokenKind, msg: &str) -> PResult<()> {
         if self.check(kind.clone()) {
             self.advance();
+            Ok(())
         } else {
-            self.error_here(msg);
+            Err(self.error_here(msg))
+        }
+    }
+
+    /// Consume an identifier and return its name, or fail with `msg`
+    fn expect_identifier(&mut self, msg: &str) -> PResult<String> {
+        if let TokenKind::Identifier(name) = self.peek().clone() {
+            self.advance();
+            Ok(name)
+        } else {
+            Err(self.error_here(msg))
         }
     }
 }
 impl Parser {
-    fn parse_block(&mut self) -> Vec<Node> {
-    self.expect(TokenKind::Symbol("{".into()), "Expected '{' to start block");
+    fn parse_block(&mut self) -> PResult<Vec<Node>> {
+    self.expect(TokenKind::Symbol("{".into()), "Expected '{' to start block")?;
 
-    let mut nodes = Vec::new();
+    let nodes = self.parse_stmt_list();
 
+    self.expect(TokenKind::Symbol("}".into()), "Expected '}' to end block")?;
+    Ok(nodes)
+}
+
+/// `match (subject) { Circle(r) => expr, Rect(w, h) => { ... }, _ => ... }`
+fn parse_match(&mut self) -> PResult<Expr> {
+    let start = self.prev_start(); // the 'match' keyword
+    // The subject is usually parenthesized, but `match s {` works too
+    let no_typed_object = mem::replace(&mut self.no_typed_object, true);
+    let subject = self.parse_expr();
+    self.no_typed_object = no_typed_object;
+    let subject = subject?;
+
+    self.expect(TokenKind::Symbol("{".into()), "Expected '{' to start match arms")?;
+    let mut arms = Vec::new();
     while !self.check(TokenKind::Symbol("}".into())) && !self.check(TokenKind::EOF) {
-        if let Some(stmt) = self.parse_stmt() {
-            nodes.push(stmt);
+        let pattern_start = self.start_pos();
+        let pattern = self.parse_match_pattern()?;
+        let span = self.span_from(pattern_start);
+        self.expect(TokenKind::Symbol("=>".into()), "Expected '=>' after match pattern")?;
+
+        let body = if self.check(TokenKind::Symbol("{".into())) && !self.lookahead_is_object_literal() {
+            self.parse_block()?
         } else {
+            vec![Node::from(self.parse_expr()?)]
+        };
+        arms.push(MatchArm { pattern, span, body });
+
+        // Arms may be separated by commas or semicolons
+        while self.matches(&[TokenKind::Symbol(",".into()), TokenKind::Symbol(";".into())]) {}
+    }
+    self.expect(TokenKind::Symbol("}".into()), "Expected '}' to close match")?;
+
+    if arms.is_empty() {
+        return Err(Diagnostic::error(self.span_from(start), "`match` needs at least one arm"));
+    }
+    Ok(Expr::new(ExprKind::Match { subject: Box::new(subject), arms }, self.span_from(start)))
+}
+
+/// A match arm pattern: `_`, a literal, or a variant such as `Empty`, `Circle(r)`,
+/// `Shape.Rect(w, _)`
+fn parse_match_pattern(&mut self) -> PResult<TypePattern> {
+    match self.peek().clone() {
+        TokenKind::Identifier(name) if name == "_" => {
+            self.advance();
+            Ok(TypePattern::Type(TypeDescriptor::Any))
+        }
+        TokenKind::Identifier(mut name) => {
             self.advance();
+            while self.matches(&[TokenKind::Symbol(".".into())]) {
+                let next = self.expect_identifier("Expected variant name after '.'")?;
+                name = format!("{}.{}", name, next);
+            }
+            let mut fields = Vec::new();
+            if self.matches(&[TokenKind::Symbol("(".into())]) {
+                if !self.check(TokenKind::Symbol(")".into())) {
+                    loop {
+                        fields.push(self.parse_payload_pattern()?);
+                        if !self.matches(&[TokenKind::Symbol(",".into())]) {
+                            break;
+                        }
+                    }
+                }
+                self.expect(TokenKind::Symbol(")".into()), "Expected ')' after variant fields")?;
+            }
+            Ok(TypePattern::Variant { name, fields })
         }
+        _ if self.peek_literal_pattern() => Ok(TypePattern::Value(self.parse_unary()?)),
+        _ => Err(self.error_here("Expected a match pattern")),
     }
+}
 
-    self.expect(TokenKind::Symbol("}".into()), "Expected '}' to end block");
-    nodes
+/// `"Hi ${name}!"` becomes `format("Hi {}!", name)`
+fn parse_interpolation(&mut self, parts: Vec<StringPart>) -> PResult<ExprKind> {
+    let span = self.tokens[self.pos - 1].span;
+    let mut template = String::new();
+    let mut args = Vec::new();
+    for part in parts {
+        match part {
+            StringPart::Text(text) => template.push_str(&text.replace('{', "{{").replace('}', "}}")),
+            StringPart::Code(tokens) => {
+                let mut inner = Parser::new(tokens);
+                let expr = inner.parse_expr()?;
+                if !inner.check(TokenKind::EOF) {
+                    return Err(inner.error_here("Expected '}' after interpolated expression"));
+                }
+                self.diagnostics.append(&mut inner.diagnostics);
+                template.push_str("{}");
+                args.push(expr);
+            }
+        }
+    }
+    args.insert(0, Expr::new(ExprKind::StringLiteral(template), span));
+    Ok(ExprKind::Call { name: "format".to_string(), args })
+}
+
+/// Literal patterns: numbers (optionally negative), strings, `true` and `false`
+fn peek_literal_pattern(&self) -> bool {
+    match self.peek() {
+        TokenKind::Number { .. } | TokenKind::String(_) => true,
+        TokenKind::Keyword(k) => k == "true" || k == "false",
+        TokenKind::Symbol(s) if s == "-" => {
+            matches!(self.tokens.get(self.pos + 1), Some(Token { kind: TokenKind::Number { .. }, .. }))
+        }
+        _ => false,
+    }
+}
+
+/// One payload position of a variant pattern: a binding, `_`, or a nested pattern.
+/// A bare name is a binding here; it is resolved against the field's enum later, so a
+/// unit variant such as `Empty` still matches as a variant.
+fn parse_payload_pattern(&mut self) -> PResult<ParameterPattern> {
+    if let TokenKind::Identifier(name) = self.peek().clone() {
+        let next = self.tokens.get(self.pos + 1).map(|t| &t.kind);
+        if !matches!(next, Some(TokenKind::Symbol(s)) if s == "(" || s == ".") {
+            self.advance();
+            return Ok(ParameterPattern { name, pattern: None });
+        }
+    }
+    let pattern = self.parse_match_pattern()?;
+    Ok(ParameterPattern { name: "_".to_string(), pattern: Some(pattern) })
 }
 
 fn lookahead_is_object_literal(&self) -> bool {
@@ -1309,52 +1968,273 @@ fn lookahead_is_object_literal(&self) -> bool {
         && matches!(next2, Some(Token { kind: TokenKind::Symbol(s), .. }) if s == ":")
 }
 
-fn parse_array_literal(&mut self) -> Expr {
-    self.expect(TokenKind::Symbol("[".into()), "Expected '[' to start array literal");
+fn parse_array_literal(&mut self) -> PResult<Expr> {
+    let start = self.start_pos();
+    self.expect(TokenKind::Symbol("[".into()), "Expected '[' to start array literal")?;
     let mut elements = Vec::new();
 
     if !self.check(TokenKind::Symbol("]".into())) {
         loop {
-            elements.push(self.parse_expr());
+            elements.push(self.parse_expr()?);
             if self.check(TokenKind::Symbol("]".into())) {
                 break;
             }
-            self.expect(TokenKind::Symbol(",".into()), "Expected ',' between array elements");
+            self.expect(TokenKind::Symbol(",".into()), "Expected ',' between array elements")?;
         }
     }
 
-    self.expect(TokenKind::Symbol("]".into()), "Expected ']' to close array literal");
-    Expr::ArrayLiteral(elements)
+    self.expect(TokenKind::Symbol("]".into()), "Expected ']' to close array literal")?;
+    Ok(Expr::new(ExprKind::ArrayLiteral(elements), self.span_from(start)))
 }
 
-fn parse_object_literal(&mut self) -> Expr {
-    self.expect(TokenKind::Symbol("{".into()), "Expected '{' to start object literal");
+fn parse_object_literal(&mut self) -> PResult<Expr> {
+    let start = self.start_pos();
+    self.expect(TokenKind::Symbol("{".into()), "Expected '{' to start object literal")?;
     let mut fields = Vec::new();
 
     if !self.check(TokenKind::Symbol("}".into())) {
         loop {
-            let key = match self.advance().clone() {
+            let key = match self.peek().clone() {
                 TokenKind::String(s) => s,
                 TokenKind::Identifier(id) => id,
-                _ => panic!("Expected string or identifier as object key"),
+                _ => return Err(self.error_here("Expected string or identifier as object key")),
             };
-            self.expect(TokenKind::Symbol(":".into()), "Expected ':' after object key");
-            let val = self.parse_expr();
+            self.advance();
+            self.expect(TokenKind::Symbol(":".into()), "Expected ':' after object key")?;
+            let val = self.parse_expr()?;
             fields.push((key, val));
 
             if self.check(TokenKind::Symbol("}".into())) {
                 break;
             }
-            self.expect(TokenKind::Symbol(",".into()), "Expected ',' between object fields");
+            self.expect(TokenKind::Symbol(",".into()), "Expected ',' between object fields")?;
         }
     }
 
-    self.expect(TokenKind::Symbol("}".into()), "Expected '}' to close object literal");
-    Expr::ObjectLiteral {
+    self.expect(TokenKind::Symbol("}".into()), "Expected '}' to close object literal")?;
+    Ok(Expr::new(ExprKind::ObjectLiteral {
         fields,
         type_name: None, // ✅ Type inference will be handled in codegen
-    }
+    }, self.span_from(start)))
 }
 
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_valid_program() {
+        let ast = parse(r#"let x = 1; funcy add(a, b) { return a + b; } print(add(x, 2));"#).unwrap();
+        assert_eq!(ast.len(), 3);
+    }
+
+    #[test]
+    fn test_reports_every_syntax_error() {
+        let src = "let = 5;\nfuncy f() { let y = ; return 1; }\nlet ok = 2;\nprint(ok +);\n";
+        let diags = parse(src).unwrap_err();
+
+        assert_eq!(diags.len(), 3, "{:#?}", diags);
+        assert_eq!(diags[0].span.line_col(src).0, 1);
+        assert_eq!(diags[1].span.line_col(src).0, 2);
+        assert_eq!(diags[2].span.line_col(src).0, 4);
+        assert!(diags.iter().all(|d| d.is_error()));
+    }
+
+    #[test]
+    fn test_nodes_carry_source_spans() {
+        let src = "let x = 1;\nprint(x + 22);";
+        let ast = parse(src).unwrap();
+
+        assert_eq!(&src[ast[0].span.start..ast[0].span.end], "let x = 1;");
+        assert_eq!(ast[1].span.line_col(src), (2, 1));
+        let NodeKind::Expr(call) = &ast[1].kind else { panic!("expected expression statement") };
+        let ExprKind::Call { args, .. } = &call.kind else { panic!("expected call") };
+        assert_eq!(&src[args[0].span.start..args[0].span.end], "x + 22");
+    }
+
+    #[test]
+    fn test_parse_file_stamps_its_file_on_spans() {
+        let ast = parse_file("let x = 1;\nprint(x);", FileId(3)).unwrap();
+        assert!(ast.iter().all(|node| node.span.file == FileId(3)));
+
+        let diags = parse_file("let = 5;", FileId(3)).unwrap_err();
+        assert_eq!(diags[0].span.file, FileId(3));
+    }
+
+    #[test]
+    fn test_index_expressions_and_element_assignment() {
+        let src = "grid[i][j + 1] = xs[0];";
+        let ast = parse(src).unwrap();
+
+        let NodeKind::Expr(assign) = &ast[0].kind else { panic!("expected expression statement") };
+        let ExprKind::BinaryOp { left, op, right } = &assign.kind else { panic!("expected assignment") };
+        assert_eq!(op, "=");
+        assert_eq!(&src[left.span.start..left.span.end], "grid[i][j + 1]");
+        let ExprKind::Index { target, .. } = &left.kind else { panic!("expected index target") };
+        assert!(matches!(target.kind, ExprKind::Index { .. }));
+        assert!(matches!(right.kind, ExprKind::Index { .. }));
+    }
+
+    #[test]
+    fn test_let_array_type_annotation() {
+        let ast = parse("let grid: str[][] = [];").unwrap();
+        let NodeKind::Let { ty, .. } = &ast[0].kind else { panic!("expected let") };
+        assert_eq!(ty.as_deref(), Some("str[][]"));
+
+        let ast = parse("let rows: map<str[]>[] = [];").unwrap();
+        let NodeKind::Let { ty, .. } = &ast[0].kind else { panic!("expected let") };
+        assert_eq!(ty.as_deref(), Some("map<str[]>[]"));
+    }
+
+    #[test]
+    fn test_for_in_loops() {
+        let ast = parse("for k, v in headers { print(k); }\nfor i in 0..n { }").unwrap();
+        let NodeKind::Expr(Expr { kind: ExprKind::ForIn { key, item, iterable, .. }, .. }) = &ast[0].kind else {
+            panic!("expected for-in, got {:?}", ast[0].kind)
+        };
+        assert_eq!((key.as_deref(), item.as_str()), (Some("k"), "v"));
+        assert!(matches!(&iterable.kind, ExprKind::Variable(name) if name == "headers"));
+
+        let NodeKind::Expr(Expr { kind: ExprKind::ForIn { iterable, .. }, .. }) = &ast[1].kind else {
+            panic!("expected for-in")
+        };
+        assert!(matches!(&iterable.kind, ExprKind::Range { .. }));
+    }
+
+    #[test]
+    fn test_enums_and_match() {
+        let src = "enum Shape { Circle(f64), Rect(f64, f64), Empty, }\nlet a = match s { Rect(w, _) => w, Some(Empty) => 0, -1 => 2, _ => { 0 } };";
+        let ast = parse(src).unwrap();
+        let NodeKind::Enum(def) = &ast[0].kind else { panic!("expected enum") };
+        let fields: Vec<usize> = def.variants.iter().map(|v| v.fields.len()).collect();
+        assert_eq!((def.name.as_str(), fields), ("Shape", vec![1, 2, 0]));
+
+        let NodeKind::Let { value: Expr { kind: ExprKind::Match { arms, .. }, .. }, .. } = &ast[1].kind else {
+            panic!("expected a match")
+        };
+        assert!(matches!(&arms[0].pattern, TypePattern::Variant { name, fields } if name == "Rect" && fields[1].name == "_"));
+        let TypePattern::Variant { fields, .. } = &arms[1].pattern else { panic!("expected a variant") };
+        assert_eq!(fields[0].name, "Empty");
+        assert!(matches!(arms[2].pattern, TypePattern::Value(_)));
+        assert!(matches!(arms[3].pattern, TypePattern::Type(TypeDescriptor::Any)));
+
+        let diags = parse("enum E { A, A }\nlet x = match y { };").unwrap_err();
+        assert_eq!(diags.len(), 2);
+    }
+
+    #[test]
+    fn test_as_binds_tighter_than_multiplication() {
+        let ast = parse("let x = -n as u8 * 2;").unwrap();
+        let NodeKind::Let { value: Expr { kind: ExprKind::BinaryOp { left, op, .. }, .. }, .. } = &ast[0].kind else {
+            panic!("expected a binary operation")
+        };
+        assert_eq!(op, "*");
+        let ExprKind::Cast { expr, ty } = &left.kind else { panic!("expected a cast") };
+        assert_eq!(ty, "u8");
+        assert!(matches!(&expr.kind, ExprKind::BinaryOp { op, .. } if op == "-"));
+    }
+
+    #[test]
+    fn test_operator_precedence_and_desugaring() {
+        // `&` binds tighter than `==`, and `<<` tighter than `&`
+        let ast = parse("let ok = a & 1 << 2 == 0 && !b;").unwrap();
+        let NodeKind::Let { value: Expr { kind: ExprKind::BinaryOp { left, op, .. }, .. }, .. } = &ast[0].kind else {
+            panic!("expected a binary operation")
+        };
+        assert_eq!(op, "and");
+        let ExprKind::BinaryOp { left, op, .. } = &left.kind else { panic!("expected `==`") };
+        assert_eq!(op, "==");
+        let ExprKind::BinaryOp { right, op, .. } = &left.kind else { panic!("expected `&`") };
+        assert_eq!(op, "&");
+        assert!(matches!(&right.kind, ExprKind::BinaryOp { op, .. } if op == "<<"));
+
+        // `x += 2` is `x = x + 2`; `x++` is `(x = x + 1) - 1`
+        let ast = parse("x += 2; x++; let m: map<map<i32>> = Map();").unwrap();
+        let NodeKind::Expr(Expr { kind: ExprKind::BinaryOp { op, right, .. }, .. }) = &ast[0].kind else {
+            panic!("expected an assignment")
+        };
+        assert_eq!(op, "=");
+        assert!(matches!(&right.kind, ExprKind::BinaryOp { op, .. } if op == "+"));
+        let NodeKind::Expr(Expr { kind: ExprKind::BinaryOp { left, op, .. }, .. }) = &ast[1].kind else {
+            panic!("expected a postfix increment")
+        };
+        assert_eq!(op, "-");
+        assert!(matches!(&left.kind, ExprKind::BinaryOp { op, .. } if op == "="));
+        assert!(matches!(&ast[2].kind, NodeKind::Let { ty: Some(ty), .. } if ty == "map<map<i32>>"));
+
+        assert!(parse("let y = ++3;").is_err());
+    }
+
+    #[test]
+    fn test_doc_comments_attach_to_declarations() {
+        let src = "/// Adds one.\n/// Returns i32.\nfuncy inc(x) { return x + 1 }\n/// Not a declaration\nprint(1)\nentity Dog {\n  /// Says hi\n  func bark() { print(\"woof\") }\n}";
+        let ast = parse(src).unwrap();
+        assert_eq!(ast[0].doc.as_deref(), Some("Adds one.\nReturns i32."));
+        assert_eq!(ast[1].doc, None);
+        let NodeKind::Entity(dog) = &ast[2].kind else { panic!("expected an entity") };
+        assert!(matches!(&dog.members[0], EntityMember::Method { doc: Some(d), .. } if d == "Says hi"));
+    }
+
+    #[test]
+    fn test_new_forms_and_me() {
+        let ast = parse("let a = new Dog(\"Rex\", 3);\nlet b = new(Dog, \"Rex\", 3);\nme.age = 1;").unwrap();
+        for node in &ast[..2] {
+            let NodeKind::Let { value, .. } = &node.kind else { panic!("expected a let") };
+            assert!(matches!(&value.kind, ExprKind::NewInstance { entity, args } if entity == "Dog" && args.len() == 2));
+        }
+        let NodeKind::Expr(Expr { kind: ExprKind::BinaryOp { left, .. }, .. }) = &ast[2].kind else {
+            panic!("expected an assignment")
+        };
+        assert!(matches!(&left.kind, ExprKind::Variable(name) if name == "me.age"));
+    }
+
+    #[test]
+    fn test_interfaces_and_implements() {
+        let ast = parse("interface Speaker {\n  speak()\n  func shout(times: i32) -> str\n}\nentity Dog alters Animal implements Speaker, Walker {}").unwrap();
+        let NodeKind::Interface(def) = &ast[0].kind else { panic!("expected an interface") };
+        let methods: Vec<_> = def.methods.iter().map(|m| (m.name.as_str(), m.params.len())).collect();
+        assert_eq!(methods, [("speak", 0), ("shout", 1)]);
+        let NodeKind::Entity(entity) = &ast[1].kind else { panic!("expected an entity") };
+        assert_eq!(entity.base.as_deref(), Some("Animal"));
+        assert_eq!(entity.interfaces, ["Speaker", "Walker"]);
+
+        let diags = parse("interface Speaker { speak() speak() }").unwrap_err();
+        assert!(diags[0].message.contains("speak"), "{}", diags[0].message);
+    }
+
+    #[test]
+    fn test_status_range_parameters() {
+        let ast = parse("funcy handle(code: 2xx) { print(code) }").unwrap();
+        let NodeKind::Expr(Expr { kind: ExprKind::Funcy { params_patterns: Some(params), .. }, .. }) = &ast[0].kind else {
+            panic!("expected a funcy")
+        };
+        assert!(matches!(params[0].pattern, Some(TypePattern::Type(TypeDescriptor::HttpStatusRange(200, 299)))), "{:?}", params[0]);
+    }
+
+    #[test]
+    fn test_typed_catch_clauses() {
+        let ast = parse("try { find() } catch (e: NotFoundError) { } catch (e: str) { } catch { } finally { }").unwrap();
+        let NodeKind::Expr(Expr { kind: ExprKind::TryCatch { catches, finally_block, .. }, .. }) = &ast[0].kind else {
+            panic!("expected a try")
+        };
+        let clauses: Vec<_> = catches.iter().map(|c| (c.var.as_deref(), c.ty.clone())).collect();
+        assert!(matches!(
+            clauses.as_slice(),
+            [
+                (Some("e"), Some(TypeDescriptor::ObjectType(ty))),
+                (Some("e"), Some(TypeDescriptor::Primitive(s))),
+                (None, None),
+            ] if ty == "NotFoundError" && s == "string"
+        ), "{:?}", clauses);
+        assert!(finally_block.is_some());
+    }
+
+    #[test]
+    fn test_unclosed_block_does_not_hang() {
+        let diags = parse("while (1) { print(1);").unwrap_err();
+        assert!(diags[0].message.contains("'}'"));
+    }
+}
@@ -0,0 +1,79 @@
+//! W++ closures: a function pointer plus the heap environment it captured.
+//!
+//! Every function value (`let f = funcy(x) { ... }`, or a named function used as a value)
+//! is a pointer to a `WppClosure`. Lambdas that capture variables take the environment as
+//! a trailing `i8*` parameter; everything else has a null `env` and is called without it.
+
+use std::ffi::c_void;
+use std::mem;
+use std::ptr;
+
+use super::gc::{self, BlockKind};
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct WppClosure {
+    pub func: *const c_void,
+    pub env: *mut c_void,
+}
+
+// The environment is owned by the closure; sharing it with a thread or the
+// HTTP server is the same trust W++ already places in plain function pointers.
+unsafe impl Send for WppClosure {}
+unsafe impl Sync for WppClosure {}
+
+impl WppClosure {
+    /// Wrap a plain `fn() -> i32` (or `fn()`) pointer
+    pub fn plain(func: *const c_void) -> Self {
+        Self { func, env: ptr::null_mut() }
+    }
+
+    /// Call a zero-argument closure, as `useThread` and `server.register` handlers are.
+    ///
+    /// # Safety
+    /// `func` must point to a function taking no W++ parameters and returning `i32` (or
+    /// nothing, in which case the result is garbage and must be ignored).
+    pub unsafe fn call0(&self) -> i32 {
+        unsafe {
+            if self.env.is_null() {
+                let f: extern "C" fn() -> i32 = mem::transmute(self.func);
+                f()
+            } else {
+                let f: extern "C" fn(*mut c_void) -> i32 = mem::transmute(self.func);
+                f(self.env)
+            }
+        }
+    }
+}
+
+/// Allocate a closure; `env` is null for functions that capture nothing
+#[unsafe(no_mangle)]
+pub extern "C" fn wpp_closure_new(func: *const c_void, env: *mut c_void) -> *mut WppClosure {
+    let closure = gc::alloc(size_of::<WppClosure>(), BlockKind::Data) as *mut WppClosure;
+    unsafe { ptr::write(closure, WppClosure { func, env }) };
+    closure
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn seven() -> i32 {
+        7
+    }
+
+    extern "C" fn read_env(env: *mut c_void) -> i32 {
+        unsafe { *(env as *const i32) }
+    }
+
+    #[test]
+    fn test_call0_passes_env_only_when_present() {
+        let plain = unsafe { &*wpp_closure_new(seven as *const c_void, ptr::null_mut()) };
+        assert_eq!(unsafe { plain.call0() }, 7);
+
+        let mut captured = 42i32;
+        let env = &mut captured as *mut i32 as *mut c_void;
+        let with_env = unsafe { &*wpp_closure_new(read_env as *const c_void, env) };
+        assert_eq!(unsafe { with_env.call0() }, 42);
+    }
+}
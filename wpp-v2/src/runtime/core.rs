@@ -5,6 +5,8 @@ use std::{
 };
 use tokio::runtime::Runtime;
 
+use super::gc;
+
 
 /// === SAFETY WRAPPERS ===
 #[derive(Clone, Copy)]
@@ -195,7 +197,7 @@ fn schedule_next() {
     unsafe {
         let func: extern "C" fn() = mem::transmute(task.func);
         drop(queue);
-        func();
+        gc::run_mutator(|| func());
     }
 
     dynamic_sleep();
@@ -247,122 +249,31 @@ where
 #[unsafe(no_mangle)]
 pub extern "C" fn wpp_runtime_wait() {
     if debug_enabled() { println!("🕓 [runtime] Waiting for async background tasks (press Ctrl+C to stop)..."); }
-    loop {
+    // Parked so server handlers can collect garbage while `main` waits here
+    gc::parked(|| loop {
         thread::sleep(Duration::from_secs(1));
-    }
-}
-
-#[unsafe(no_mangle)]
-pub extern "C" fn wpp_str_concat(a: *const i8, b: *const i8) -> *mut i8 {
-    unsafe {
-        if a.is_null() || b.is_null() {
-            return std::ptr::null_mut();
-        }
-
-        let sa = std::ffi::CStr::from_ptr(a).to_str().unwrap_or("");
-        let sb = std::ffi::CStr::from_ptr(b).to_str().unwrap_or("");
-
-        let concat = format!("{}{}", sa, sb);
-        let cstring = std::ffi::CString::new(concat).unwrap();
-        cstring.into_raw()
-    }
-}
-
-#[unsafe(no_mangle)]
-pub extern "C" fn wpp_str_substr(s: *const i8, start: i32, length: i32) -> *mut i8 {
-    unsafe {
-        if s.is_null() {
-            return std::ptr::null_mut();
-        }
-
-        let string = std::ffi::CStr::from_ptr(s).to_str().unwrap_or("");
-        let start = start.max(0) as usize;
-        let length = length.max(0) as usize;
-
-        let result = if start < string.len() {
-            let end = (start + length).min(string.len());
-            &string[start..end]
-        } else {
-            ""
-        };
-
-        let cstring = std::ffi::CString::new(result).unwrap();
-        cstring.into_raw()
-    }
-}
-
-#[unsafe(no_mangle)]
-pub extern "C" fn wpp_str_index_of(haystack: *const i8, needle: *const i8) -> i32 {
-    unsafe {
-        if haystack.is_null() || needle.is_null() {
-            return -1;
-        }
-
-        let hay = std::ffi::CStr::from_ptr(haystack).to_str().unwrap_or("");
-        let need = std::ffi::CStr::from_ptr(needle).to_str().unwrap_or("");
-
-        match hay.find(need) {
-            Some(pos) => pos as i32,
-            None => -1,
-        }
-    }
-}
-
-#[unsafe(no_mangle)]
-pub extern "C" fn wpp_str_replace(s: *const i8, find: *const i8, replace: *const i8) -> *mut i8 {
-    unsafe {
-        if s.is_null() || find.is_null() || replace.is_null() {
-            return std::ptr::null_mut();
-        }
-
-        let string = std::ffi::CStr::from_ptr(s).to_str().unwrap_or("");
-        let find_str = std::ffi::CStr::from_ptr(find).to_str().unwrap_or("");
-        let replace_str = std::ffi::CStr::from_ptr(replace).to_str().unwrap_or("");
-
-        let result = string.replace(find_str, replace_str);
-        let cstring = std::ffi::CString::new(result).unwrap();
-        cstring.into_raw()
-    }
-}
-
-#[unsafe(no_mangle)]
-pub extern "C" fn wpp_str_to_upper(s: *const i8) -> *mut i8 {
-    unsafe {
-        if s.is_null() {
-            return std::ptr::null_mut();
-        }
-
-        let string = std::ffi::CStr::from_ptr(s).to_str().unwrap_or("");
-        let result = string.to_uppercase();
-        let cstring = std::ffi::CString::new(result).unwrap();
-        cstring.into_raw()
-    }
+    })
 }
 
+/// === EXCEPTIONS ===
+/// Message for an out-of-range array access (caller owns the returned string)
 #[unsafe(no_mangle)]
-pub extern "C" fn wpp_str_to_lower(s: *const i8) -> *mut i8 {
-    unsafe {
-        if s.is_null() {
-            return std::ptr::null_mut();
-        }
-
-        let string = std::ffi::CStr::from_ptr(s).to_str().unwrap_or("");
-        let result = string.to_lowercase();
-        let cstring = std::ffi::CString::new(result).unwrap();
-        cstring.into_raw()
-    }
+pub extern "C" fn wpp_index_error(index: i32, len: i32) -> *mut i8 {
+    let msg = format!("index {} out of bounds for array of length {}", index, len);
+    std::ffi::CString::new(msg).unwrap().into_raw()
 }
 
+/// Message for `get` on a missing map key (caller owns the returned string)
+///
+/// # Safety
+/// `key` must be null or point to a NUL-terminated string.
 #[unsafe(no_mangle)]
-pub extern "C" fn wpp_str_trim(s: *const i8) -> *mut i8 {
-    unsafe {
-        if s.is_null() {
-            return std::ptr::null_mut();
-        }
-
-        let string = std::ffi::CStr::from_ptr(s).to_str().unwrap_or("");
-        let result = string.trim();
-        let cstring = std::ffi::CString::new(result).unwrap();
-        cstring.into_raw()
-    }
+pub unsafe extern "C" fn wpp_key_error(key: *const i8) -> *mut i8 {
+    let key = if key.is_null() {
+        String::new()
+    } else {
+        unsafe { std::ffi::CStr::from_ptr(key).to_string_lossy().into_owned() }
+    };
+    let msg = format!("key \"{}\" not found in map", key);
+    std::ffi::CString::new(msg).unwrap().into_raw()
 }
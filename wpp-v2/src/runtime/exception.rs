@@ -0,0 +1,228 @@
+//! W++ exceptions: the value being thrown and the W++ call stack it was thrown from.
+//!
+//! Generated code does not unwind. `throw` hands an `any` box to [`wpp_throw`], which
+//! records it as this thread's pending exception, and every call that may throw is followed
+//! by a [`wpp_exception_pending`] check that jumps to the enclosing `catch` or returns from
+//! the function. The runtime raises exceptions the same way ([`raise`]), so a fault inside a
+//! runtime or FFI call reaches W++ code without unwinding through foreign frames.
+//!
+//! ## Stack traces
+//!
+//! Each W++ function pushes a frame with its name on entry and pops it on every return;
+//! statements update the line of the innermost frame. `wpp_throw` snapshots the frames into
+//! the trace, innermost first, which `catch` hands to the program and an uncaught exception
+//! prints.
+
+use std::cell::RefCell;
+use std::ffi::CStr;
+use std::io::Write;
+use std::os::raw::c_char;
+
+use super::gc::{self, BlockKind};
+use super::string::new_string;
+
+unsafe extern "C" {
+    // runtime/wpp_runtime.c
+    fn wpp_format(fmt: *const c_char, argc: i32, args: *const std::ffi::c_void) -> *const c_char;
+}
+
+/// `any` box tag of a string (codegen.rs `field_type_tag`)
+const STRING_TAG: i32 = 6;
+
+struct Frame {
+    /// Function name, a string constant in the generated module
+    function: *const c_char,
+    /// Source line of the statement running in this frame; 0 when unknown
+    line: i32,
+}
+
+struct Pending {
+    /// The thrown value, as an `any` box
+    value: *mut u8,
+    trace: String,
+}
+
+thread_local! {
+    static FRAMES: RefCell<Vec<Frame>> = const { RefCell::new(Vec::new()) };
+    static PENDING: RefCell<Option<Pending>> = const { RefCell::new(None) };
+}
+
+/// Enter a W++ function
+///
+/// # Safety
+/// `function` must point to a NUL-terminated string that outlives the frame.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_frame_push(function: *const c_char) {
+    FRAMES.with(|frames| frames.borrow_mut().push(Frame { function, line: 0 }));
+}
+
+/// Leave the innermost W++ function
+#[unsafe(no_mangle)]
+pub extern "C" fn wpp_frame_pop() {
+    FRAMES.with(|frames| frames.borrow_mut().pop());
+}
+
+/// Record the line the innermost W++ function is running
+#[unsafe(no_mangle)]
+pub extern "C" fn wpp_frame_line(line: i32) {
+    FRAMES.with(|frames| {
+        if let Some(frame) = frames.borrow_mut().last_mut() {
+            frame.line = line;
+        }
+    });
+}
+
+/// The current W++ call stack, one `  at name (line N)` entry per frame, innermost first
+fn capture_trace() -> String {
+    FRAMES.with(|frames| {
+        frames
+            .borrow()
+            .iter()
+            .rev()
+            .map(|frame| {
+                let name = unsafe { CStr::from_ptr(frame.function) }.to_string_lossy();
+                match frame.line {
+                    0 => format!("  at {}", name),
+                    line => format!("  at {} (line {})", name, line),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    })
+}
+
+/// Throw `value`, an `any` box; generated code checks [`wpp_exception_pending`] next
+///
+/// # Safety
+/// `value` must be an `any` box allocated on the W++ heap.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_throw(value: *mut u8) {
+    // The box is only referenced from here until a `catch` takes it
+    gc::pin(value);
+    let trace = capture_trace();
+    PENDING.with(|pending| {
+        if let Some(old) = pending.borrow_mut().replace(Pending { value, trace }) {
+            gc::unpin(old.value);
+        }
+    });
+}
+
+/// Throw a string exception with `msg` as its value
+///
+/// # Safety
+/// `msg` must be null or point to a NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_throw_message(msg: *const c_char) {
+    let text = if msg.is_null() {
+        String::new()
+    } else {
+        unsafe { CStr::from_ptr(msg).to_string_lossy().into_owned() }
+    };
+    raise(&text);
+}
+
+/// Throw a string exception from the runtime. The caller returns normally (with any value);
+/// the generated code that called it sees the exception when the call returns.
+pub fn raise(msg: &str) {
+    let boxed = gc::alloc(16, BlockKind::Data);
+    unsafe {
+        (boxed as *mut i32).write(STRING_TAG);
+        (boxed.add(8) as *mut *mut c_char).write(new_string(msg.as_bytes()));
+        wpp_throw(boxed);
+    }
+}
+
+/// 1 if an exception is waiting to be caught, else 0
+#[unsafe(no_mangle)]
+pub extern "C" fn wpp_exception_pending() -> i32 {
+    PENDING.with(|pending| pending.borrow().is_some() as i32)
+}
+
+/// The pending exception's value, left pending so an unmatched `catch` can pass it on
+#[unsafe(no_mangle)]
+pub extern "C" fn wpp_exception_value() -> *mut u8 {
+    PENDING.with(|pending| pending.borrow().as_ref().map_or(std::ptr::null_mut(), |p| p.value))
+}
+
+/// Catch the pending exception, returning its stack trace as a W++ string
+#[unsafe(no_mangle)]
+pub extern "C" fn wpp_exception_catch() -> *mut c_char {
+    let caught = PENDING.with(|pending| pending.borrow_mut().take());
+    match caught {
+        Some(Pending { value, trace }) => {
+            gc::unpin(value);
+            new_string(trace.as_bytes())
+        }
+        None => new_string(b""),
+    }
+}
+
+/// Set the pending exception aside while a `finally` block runs; null when none is pending
+#[unsafe(no_mangle)]
+pub extern "C" fn wpp_exception_save() -> *mut u8 {
+    let saved = PENDING.with(|pending| pending.borrow_mut().take());
+    saved.map_or(std::ptr::null_mut(), |saved| Box::into_raw(Box::new(saved)) as *mut u8)
+}
+
+/// Make an exception set aside by [`wpp_exception_save`] pending again
+///
+/// # Safety
+/// `saved` must be null or come from `wpp_exception_save`, and be restored only once.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_exception_restore(saved: *mut u8) {
+    if saved.is_null() {
+        return;
+    }
+    let saved = unsafe { Box::from_raw(saved as *mut Pending) };
+    PENDING.with(|pending| {
+        if let Some(old) = pending.borrow_mut().replace(*saved) {
+            gc::unpin(old.value);
+        }
+    });
+}
+
+/// Report an exception that reached the entry point uncaught, and exit
+#[unsafe(no_mangle)]
+pub extern "C" fn wpp_exception_uncaught() {
+    let caught = PENDING.with(|pending| pending.borrow_mut().take());
+    let Some(Pending { value, trace }) = caught else { return };
+    let text = unsafe {
+        let formatted = wpp_format(c"{}".as_ptr(), 1, value as *const std::ffi::c_void);
+        CStr::from_ptr(formatted).to_string_lossy().into_owned()
+    };
+    // Output the program printed so far comes first
+    let _ = std::io::stdout().flush();
+    unsafe { libc::fflush(std::ptr::null_mut()) };
+    eprintln!("❌ Uncaught exception: {}", text);
+    if !trace.is_empty() {
+        eprintln!("{}", trace);
+    }
+    std::process::exit(1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throw_captures_the_stack_until_caught() {
+        let (main, helper) = (c"main", c"helper");
+        unsafe {
+            wpp_frame_push(main.as_ptr());
+            wpp_frame_line(7);
+            wpp_frame_push(helper.as_ptr());
+            wpp_frame_line(2);
+        }
+        raise("boom");
+        wpp_frame_pop();
+        wpp_frame_pop();
+
+        assert_eq!(wpp_exception_pending(), 1);
+        let value = wpp_exception_value();
+        assert_eq!(unsafe { (value as *const i32).read() }, STRING_TAG);
+        let trace = wpp_exception_catch();
+        let trace = unsafe { CStr::from_ptr(trace) }.to_str().unwrap().to_string();
+        assert_eq!(trace, "  at helper (line 2)\n  at main (line 7)");
+        assert_eq!(wpp_exception_pending(), 0);
+    }
+}
@@ -0,0 +1,479 @@
+//! W++ heap: a conservative mark-and-sweep collector.
+//!
+//! Every heap value W++ code can reach (arrays and their element buffers, maps, object
+//! literals, entity instances, `any` boxes, closures and their environments, strings) is
+//! allocated through [`alloc`] and recorded in a block table. Values are raw pointers in
+//! generated code and untyped 8-byte slots in containers, so marking is conservative: any
+//! word that points into a block, including its interior, keeps the block alive.
+//!
+//! ## Roots
+//!
+//! - Stacks of threads running W++ code. Each such thread is a *mutator* for the
+//!   duration of [`run_mutator`]; a mutator that blocks in the runtime (waiting on the
+//!   server, joining a thread) is *parked* via [`parked`], which publishes the part of its
+//!   stack that belongs to W++ frames.
+//! - Values the runtime keeps outside the W++ heap (server handlers, closures of running
+//!   threads) are pinned with [`pin`].
+//!
+//! ## When it runs
+//!
+//! Collection needs every mutator to be parked, since a running thread's registers and
+//! stack cannot be read. It is attempted when allocation passes a threshold (the
+//! allocating mutator parks itself) and after every server request. If some other
+//! mutator is busy the attempt is skipped and retried later; nothing is ever freed
+//! while W++ code might still be using it.
+
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::thread::{self, ThreadId};
+
+use once_cell::sync::Lazy;
+
+/// Allocation volume that triggers the first collection
+const INITIAL_THRESHOLD: usize = 1 << 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockKind {
+    /// May contain pointers to other blocks anywhere in it
+    Data,
+    /// Character data; never scanned
+    String,
+    /// A `WppMap`, which owns a Rust `HashMap` that must be dropped
+    Map,
+}
+
+struct Block {
+    size: usize,
+    kind: BlockKind,
+    marked: bool,
+}
+
+/// Block table and allocation accounting
+pub struct Heap {
+    blocks: BTreeMap<usize, Block>,
+    pinned: HashMap<usize, usize>,
+    live_bytes: usize,
+    allocated_since: usize,
+    threshold: usize,
+}
+
+impl Heap {
+    pub fn new() -> Self {
+        Self {
+            blocks: BTreeMap::new(),
+            pinned: HashMap::new(),
+            live_bytes: 0,
+            allocated_since: 0,
+            threshold: INITIAL_THRESHOLD,
+        }
+    }
+
+    /// Allocate `size` zeroed bytes and record them as a block
+    pub fn alloc(&mut self, size: usize, kind: BlockKind) -> *mut u8 {
+        let size = size.max(1);
+        let ptr = unsafe { libc::calloc(1, size) } as *mut u8;
+        assert!(!ptr.is_null(), "out of memory allocating {} bytes", size);
+        self.blocks.insert(ptr as usize, Block { size, kind, marked: false });
+        self.live_bytes += size;
+        self.allocated_since += size;
+        ptr
+    }
+
+    /// Resize a block (or allocate one if `ptr` is null); bytes past the old size are zeroed
+    pub fn realloc(&mut self, ptr: *mut u8, size: usize, kind: BlockKind) -> *mut u8 {
+        let Some(old) = (!ptr.is_null()).then(|| self.blocks.remove(&(ptr as usize))).flatten() else {
+            return self.alloc(size, kind);
+        };
+        let size = size.max(1);
+        let new = unsafe { libc::realloc(ptr as *mut libc::c_void, size) } as *mut u8;
+        assert!(!new.is_null(), "out of memory growing a block to {} bytes", size);
+        if size > old.size {
+            unsafe { std::ptr::write_bytes(new.add(old.size), 0, size - old.size) };
+        }
+        self.blocks.insert(new as usize, Block { size, kind, marked: false });
+        self.live_bytes = self.live_bytes + size - old.size;
+        self.allocated_since += size.saturating_sub(old.size);
+        new
+    }
+
+    /// Free a block now. Pointers the heap does not own are ignored.
+    pub fn free(&mut self, ptr: *mut u8) {
+        if let Some(block) = self.blocks.remove(&(ptr as usize)) {
+            self.live_bytes -= block.size;
+            unsafe { release_block(ptr as usize, &block) };
+        }
+    }
+
+    /// Whether `ptr` is the start of a live block of the given kind
+    pub fn owns(&self, ptr: *const u8, kind: BlockKind) -> bool {
+        self.blocks.get(&(ptr as usize)).is_some_and(|block| block.kind == kind)
+    }
+
+    /// Whether `ptr` starts a data block with room for at least a pointer
+    pub fn starts_data(&self, ptr: *const u8) -> bool {
+        self.blocks
+            .get(&(ptr as usize))
+            .is_some_and(|block| block.kind == BlockKind::Data && block.size >= size_of::<usize>())
+    }
+
+    pub fn pin(&mut self, ptr: *const u8) {
+        *self.pinned.entry(ptr as usize).or_insert(0) += 1;
+    }
+
+    pub fn unpin(&mut self, ptr: *const u8) {
+        if let Some(count) = self.pinned.get_mut(&(ptr as usize)) {
+            *count -= 1;
+            if *count == 0 {
+                self.pinned.remove(&(ptr as usize));
+            }
+        }
+    }
+
+    pub fn live_bytes(&self) -> usize {
+        self.live_bytes
+    }
+
+    fn due(&self) -> bool {
+        self.allocated_since >= self.threshold
+    }
+
+    fn postpone(&mut self) {
+        self.allocated_since = self.threshold / 2;
+    }
+
+    /// Start of the block containing `addr`, if any
+    fn block_containing(&self, addr: usize) -> Option<usize> {
+        let (&start, block) = self.blocks.range(..=addr).next_back()?;
+        (addr < start + block.size).then_some(start)
+    }
+
+    /// Mark everything reachable from the pinned values and the given memory ranges, then free the rest. Returns the number of bytes freed.
+    ///
+    /// # Safety
+    /// Every range must be readable memory, and no pointer into the heap may be held
+    /// anywhere else (in particular, by a running mutator).
+    pub unsafe fn collect(&mut self, ranges: &[(usize, usize)]) -> usize {
+        let mut pending = Vec::new();
+        let pinned: Vec<usize> = self.pinned.keys().copied().collect();
+        for p in pinned {
+            self.mark(p, &mut pending);
+        }
+        for &(lo, hi) in ranges {
+            unsafe { self.scan(lo, hi, &mut pending) };
+        }
+        while let Some(start) = pending.pop() {
+            let block = &self.blocks[&start];
+            if block.kind != BlockKind::String {
+                let end = start + block.size;
+                unsafe { self.scan(start, end, &mut pending) };
+            }
+        }
+
+        let dead: Vec<usize> = self.blocks.iter().filter(|(_, b)| !b.marked).map(|(&a, _)| a).collect();
+        let before = self.live_bytes;
+        for addr in dead {
+            let block = self.blocks.remove(&addr).expect("dead block is in the table");
+            self.live_bytes -= block.size;
+            unsafe { release_block(addr, &block) };
+        }
+        for block in self.blocks.values_mut() {
+            block.marked = false;
+        }
+        self.allocated_since = 0;
+        self.threshold = INITIAL_THRESHOLD.max(self.live_bytes);
+        before - self.live_bytes
+    }
+
+    /// Mark blocks pointed to by the aligned words in `lo..hi`, queueing newly marked ones
+    unsafe fn scan(&mut self, lo: usize, hi: usize, pending: &mut Vec<usize>) {
+        let word = size_of::<usize>();
+        let mut at = lo.next_multiple_of(word);
+        while at + word <= hi {
+            self.mark(unsafe { std::ptr::read_volatile(at as *const usize) }, pending);
+            at += word;
+        }
+    }
+
+    /// Mark the block containing `addr`, queueing it if it was not marked yet
+    fn mark(&mut self, addr: usize, pending: &mut Vec<usize>) {
+        if let Some(start) = self.block_containing(addr) {
+            let block = self.blocks.get_mut(&start).expect("block was just found");
+            if !block.marked {
+                block.marked = true;
+                pending.push(start);
+            }
+        }
+    }
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe fn release_block(addr: usize, block: &Block) {
+    if block.kind == BlockKind::Map {
+        unsafe { std::ptr::drop_in_place(addr as *mut super::map::WppMap) };
+    }
+    unsafe { libc::free(addr as *mut libc::c_void) };
+}
+
+static HEAP: Lazy<Mutex<Heap>> = Lazy::new(|| Mutex::new(Heap::new()));
+
+/// Threads currently executing W++ code, and the stacks of those that are parked
+struct Mutators {
+    running: usize,
+    parked: HashMap<ThreadId, (usize, usize)>,
+}
+
+static MUTATORS: Lazy<Mutex<Mutators>> = Lazy::new(|| {
+    Mutex::new(Mutators { running: 0, parked: HashMap::new() })
+});
+
+thread_local! {
+    /// Nesting depth of `run_mutator` on this thread
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+    /// Highest stack address that W++ frames on this thread can occupy
+    static STACK_BASE: Cell<usize> = const { Cell::new(0) };
+}
+
+fn heap() -> std::sync::MutexGuard<'static, Heap> {
+    HEAP.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn mutators() -> std::sync::MutexGuard<'static, Mutators> {
+    MUTATORS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Allocate a zeroed block, collecting first if enough has been allocated since the last
+/// collection and the calling thread is running W++ code
+pub fn alloc(size: usize, kind: BlockKind) -> *mut u8 {
+    if DEPTH.get() > 0 {
+        collect_if_due();
+    }
+    heap().alloc(size, kind)
+}
+
+/// Resize a block allocated by [`alloc`]; see [`Heap::realloc`]
+pub fn realloc(ptr: *mut u8, size: usize, kind: BlockKind) -> *mut u8 {
+    if DEPTH.get() > 0 {
+        collect_if_due();
+    }
+    heap().realloc(ptr, size, kind)
+}
+
+/// Free a block right away (used when a string's refcount drops to zero)
+pub fn free(ptr: *mut u8) {
+    heap().free(ptr)
+}
+
+/// Whether `ptr` is the start of a live block of the given kind; see [`Heap::owns`]
+pub fn owns(ptr: *const u8, kind: BlockKind) -> bool {
+    heap().owns(ptr, kind)
+}
+
+/// Keep the block containing `ptr` alive until a matching [`unpin`]
+pub fn pin(ptr: *const u8) {
+    if !ptr.is_null() {
+        heap().pin(ptr)
+    }
+}
+
+pub fn unpin(ptr: *const u8) {
+    if !ptr.is_null() {
+        heap().unpin(ptr)
+    }
+}
+
+/// Bytes currently allocated on the W++ heap
+pub fn live_bytes() -> usize {
+    heap().live_bytes()
+}
+
+fn stack_address() -> usize {
+    let marker = 0u8;
+    std::hint::black_box(&marker) as *const u8 as usize
+}
+
+/// Run W++ code on this thread. Its stack is scanned whenever it parks.
+pub fn run_mutator<R>(f: impl FnOnce() -> R) -> R {
+    struct Leave;
+    impl Drop for Leave {
+        fn drop(&mut self) {
+            let depth = DEPTH.get() - 1;
+            DEPTH.set(depth);
+            if depth == 0 {
+                mutators().running -= 1;
+            }
+        }
+    }
+
+    if DEPTH.get() == 0 {
+        let mut m = mutators();
+        m.running += 1;
+        STACK_BASE.set(stack_address());
+    }
+    DEPTH.set(DEPTH.get() + 1);
+    let _leave = Leave;
+    f()
+}
+
+/// Run blocking runtime code called from W++ code, letting other threads collect
+/// meanwhile. Outside of [`run_mutator`] this just runs `f`.
+pub fn parked<R>(f: impl FnOnce() -> R) -> R {
+    struct Unpark;
+    impl Drop for Unpark {
+        fn drop(&mut self) {
+            let mut m = mutators();
+            m.parked.remove(&thread::current().id());
+            m.running += 1;
+        }
+    }
+
+    if DEPTH.get() == 0 {
+        return f();
+    }
+    let registers = callee_saved_registers();
+    let top = std::hint::black_box(&registers) as *const _ as usize;
+    {
+        let mut m = mutators();
+        m.running -= 1;
+        m.parked.insert(thread::current().id(), (top, STACK_BASE.get()));
+    }
+    let unpark = Unpark;
+    let result = f();
+    drop(unpark);
+    std::hint::black_box(&registers);
+    result
+}
+
+/// Collect now if no thread is running W++ code. Returns the number of bytes freed.
+pub fn collect() -> Option<usize> {
+    let m = mutators();
+    if m.running > 0 {
+        return None;
+    }
+    let ranges: Vec<(usize, usize)> = m.parked.values().copied().collect();
+    let freed = unsafe { heap().collect(&ranges) };
+    if std::env::var("WPP_DEBUG").is_ok_and(|v| v == "1") {
+        println!("🧹 [gc] freed {} bytes, {} bytes live", freed, live_bytes());
+    }
+    Some(freed)
+}
+
+/// Collect if enough has been allocated since the last collection. When another thread
+/// is busy, wait for another half threshold before trying again.
+pub fn collect_if_due() {
+    if heap().due() && parked(collect).is_none() {
+        heap().postpone();
+    }
+}
+
+/// Copy the callee-saved registers onto the stack so pointers held in them by W++
+/// frames are seen when this thread's stack is scanned. Registers a frame already
+/// saved in its prologue are on the stack anyway.
+#[inline(never)]
+fn callee_saved_registers() -> [usize; 12] {
+    let mut saved = [0usize; 12];
+    #[cfg(target_arch = "x86_64")]
+    unsafe {
+        std::arch::asm!(
+            "mov [{0}], rbx",
+            "mov [{0} + 8], rbp",
+            "mov [{0} + 16], r12",
+            "mov [{0} + 24], r13",
+            "mov [{0} + 32], r14",
+            "mov [{0} + 40], r15",
+            in(reg) saved.as_mut_ptr(),
+            options(nostack, preserves_flags),
+        );
+    }
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        std::arch::asm!(
+            "stp x19, x20, [{0}]",
+            "stp x21, x22, [{0}, #16]",
+            "stp x23, x24, [{0}, #32]",
+            "stp x25, x26, [{0}, #48]",
+            "stp x27, x28, [{0}, #64]",
+            "str x29, [{0}, #80]",
+            in(reg) saved.as_mut_ptr(),
+            options(nostack, preserves_flags),
+        );
+    }
+    saved
+}
+
+/// Allocate `size` zeroed bytes on the W++ heap (object literals, entity instances,
+/// `any` boxes and closure environments)
+#[unsafe(no_mangle)]
+pub extern "C" fn wpp_gc_alloc(size: i64) -> *mut u8 {
+    alloc(size.max(0) as usize, BlockKind::Data)
+}
+
+/// 1 when `ptr` starts a block from [`wpp_gc_alloc`] big enough to begin with a vtable, so a
+/// dispatcher can read it to tell an entity instance from a string
+#[unsafe(no_mangle)]
+pub extern "C" fn wpp_gc_is_data(ptr: *const u8) -> i32 {
+    heap().starts_data(ptr) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(at: *mut u8, ptr: *mut u8) {
+        unsafe { *(at as *mut usize) = ptr as usize };
+    }
+
+    #[test]
+    fn test_collect_keeps_what_roots_reach() {
+        let mut heap = Heap::new();
+        let root = heap.alloc(16, BlockKind::Data);
+        let child = heap.alloc(32, BlockKind::Data);
+        let text = heap.alloc(8, BlockKind::String);
+        let garbage = heap.alloc(64, BlockKind::Data);
+        // Interior pointers count, and strings are not scanned for pointers
+        store(root, unsafe { child.add(8) });
+        store(unsafe { root.add(8) }, text);
+        store(text, garbage);
+
+        heap.pin(root);
+        assert_eq!(unsafe { heap.collect(&[]) }, 64);
+        assert_eq!(heap.live_bytes(), 16 + 32 + 8);
+
+        heap.unpin(root);
+        assert_eq!(unsafe { heap.collect(&[]) }, 16 + 32 + 8);
+    }
+
+    #[test]
+    fn test_stack_ranges_are_roots() {
+        let mut heap = Heap::new();
+        let kept = heap.alloc(8, BlockKind::Data);
+        heap.alloc(8, BlockKind::Data);
+        let frame = [kept as usize, 0, 12345];
+        let lo = frame.as_ptr() as usize;
+        let freed = unsafe { heap.collect(&[(lo, lo + size_of_val(&frame))]) };
+        assert_eq!(freed, 8);
+
+        // Growing keeps the block registered under its new address
+        let grown = heap.realloc(kept, 4096, BlockKind::Data);
+        assert_eq!(heap.live_bytes(), 4096);
+        heap.free(grown);
+        assert_eq!(heap.live_bytes(), 0);
+    }
+
+    #[test]
+    fn test_only_data_blocks_can_hold_a_vtable() {
+        let mut heap = Heap::new();
+        let instance = heap.alloc(8, BlockKind::Data);
+        let empty = heap.alloc(0, BlockKind::Data);
+        let text = heap.alloc(16, BlockKind::String);
+        assert!(heap.starts_data(instance));
+        assert!(!heap.starts_data(unsafe { instance.add(1) }));
+        assert!(!heap.starts_data(empty));
+        assert!(!heap.starts_data(text));
+    }
+}
@@ -2,10 +2,16 @@ use once_cell::sync::Lazy;
 use reqwest::Client;
 use std::{
     collections::HashMap,
-    ffi::{CStr, CString, c_char},
-    sync::Mutex,
+    ffi::{CStr, c_char},
+    sync::{
+        Mutex,
+        atomic::{AtomicI32, Ordering},
+    },
 };
 
+use super::exception::raise;
+use super::string::new_string;
+
 /// === HTTP Response Struct ===
 #[derive(Debug, Clone)]
 pub struct HttpResponse {
@@ -14,34 +20,60 @@ pub struct HttpResponse {
     pub headers: HashMap<String, String>,
 }
 
-/// === Global Stores ===
-/// Holds HTTP responses for handle-based access.
-static RESP_STORE: Lazy<Mutex<Vec<HttpResponse>>> = Lazy::new(|| Mutex::new(Vec::new()));
+/// A stored response; the body and headers are dropped once copied out
+struct StoredResponse {
+    status: i32,
+    body: Option<String>,
+    headers: Option<HashMap<String, String>>,
+}
 
-/// Holds all CString allocations that have been passed to C.
-/// Prevents Rust from freeing memory prematurely.
-static STRING_ARENA: Lazy<Mutex<Vec<CString>>> = Lazy::new(|| Mutex::new(Vec::new()));
+/// === Global Stores ===
+/// Holds HTTP responses for handle-based access until both the body and the
+/// headers have been read.
+static RESP_STORE: Lazy<Mutex<HashMap<i32, StoredResponse>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+static NEXT_HANDLE: AtomicI32 = AtomicI32::new(0);
 
 /// === Store / Retrieve Helpers ===
 fn store_response(resp: HttpResponse) -> i32 {
+    let handle = NEXT_HANDLE.fetch_add(1, Ordering::Relaxed);
+    let stored = StoredResponse { status: resp.status, body: Some(resp.body), headers: Some(resp.headers) };
+    RESP_STORE.lock().unwrap().insert(handle, stored);
+    handle
+}
+
+/// Run `f` on a stored response, evicting it once nothing is left to read.
+/// An unknown handle throws a W++ exception.
+fn with_response<T>(handle: i32, f: impl FnOnce(&mut StoredResponse) -> Option<T>) -> Option<T> {
     let mut store = RESP_STORE.lock().unwrap();
-    store.push(resp);
-    (store.len() - 1) as i32
+    let Some(resp) = store.get_mut(&handle) else {
+        drop(store);
+        raise(&format!("invalid HTTP response handle {}", handle));
+        return None;
+    };
+    let out = f(resp);
+    if resp.body.is_none() && resp.headers.is_none() {
+        store.remove(&handle);
+    }
+    out
 }
 
-fn get_response(handle: i32) -> Option<HttpResponse> {
-    RESP_STORE.lock().unwrap().get(handle as usize).cloned()
+/// Take one part of a response, throwing if it was already read
+fn take_part<T>(handle: i32, part: &str, take: impl FnOnce(&mut StoredResponse) -> Option<T>) -> Option<T> {
+    let taken = with_response(handle, |r| Some(take(r)))?;
+    if taken.is_none() {
+        raise(&format!("the {} of HTTP response {} was already read", part, handle));
+    }
+    taken
 }
 
-/// Leak-safe CString creation.
-/// The CString is kept alive in STRING_ARENA, and the raw pointer is safe for C use.
-fn leak_cstring_owned(s: String) -> *mut c_char {
-    let c = CString::new(s).unwrap_or_default();
-    c.into_raw() // true heap leak; caller never frees it
+/// Copy into a W++ string (see `string.rs`) owned by the caller,
+/// who releases it with `wpp_str_release`.
+fn owned_wpp_string(s: String) -> *mut c_char {
+    new_string(s.as_bytes())
 }
 
 /// === Async Request Core ===
-async fn do_request(method: &str, url: &str, body: Option<&str>) -> HttpResponse {
+async fn do_request(method: &str, url: &str, body: Option<&str>) -> Result<HttpResponse, reqwest::Error> {
     let client = Client::new();
 
     let mut req = match method {
@@ -57,7 +89,7 @@ async fn do_request(method: &str, url: &str, body: Option<&str>) -> HttpResponse
         req = req.body(b.to_string());
     }
 
-    let resp = req.send().await.expect("HTTP request failed");
+    let resp = req.send().await?;
     let status = resp.status().as_u16() as i32;
     let headers = resp
         .headers()
@@ -66,14 +98,19 @@ async fn do_request(method: &str, url: &str, body: Option<&str>) -> HttpResponse
         .collect::<HashMap<_, _>>();
     let body = resp.text().await.unwrap_or_default();
 
-    HttpResponse { status, body, headers }
+    Ok(HttpResponse { status, body, headers })
 }
 
 /// === Blocking FFI Wrapper ===
 /// Executes an HTTP request synchronously for W++ → C interop.
+/// A request that fails throws a W++ exception and returns -1.
 fn call_blocking_http(url_ptr: *const c_char, body_ptr: Option<*const c_char>, method: &str) -> i32 {
     if url_ptr.is_null() {
-        eprintln!("⚠️ [http] Null URL pointer");
+        raise(&format!("null URL passed to HTTP {}", method));
+        return -1;
+    }
+    if body_ptr.is_some_and(|b| b.is_null()) {
+        raise(&format!("null body passed to HTTP {}", method));
         return -1;
     }
 
@@ -83,7 +120,13 @@ fn call_blocking_http(url_ptr: *const c_char, body_ptr: Option<*const c_char>, m
     println!("🌐 [{}] {}", method, url);
 
     let rt = tokio::runtime::Runtime::new().unwrap();
-    let res = rt.block_on(async { do_request(method, &url, body.as_deref()).await });
+    let res = match rt.block_on(async { do_request(method, &url, body.as_deref()).await }) {
+        Ok(res) => res,
+        Err(e) => {
+            raise(&format!("HTTP {} {} failed: {}", method, url, e));
+            return -1;
+        }
+    };
 
     println!(
         "✅ [{}] {} => {} ({} bytes)",
@@ -125,37 +168,35 @@ pub extern "C" fn wpp_http_delete(url_ptr: *const c_char) -> i32 {
 }
 
 /// === Response Accessors ===
+/// An unknown handle throws a W++ exception. The body and the headers can be
+/// read once each; the response is freed after both have been read.
 #[unsafe(no_mangle)]
 pub extern "C" fn wpp_http_status(handle: i32) -> i32 {
-    get_response(handle).map(|r| r.status).unwrap_or(-1)
+    with_response(handle, |r| Some(r.status)).unwrap_or(-1)
 }
 
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn wpp_http_body(handle: i32) -> *mut std::ffi::c_void {
-    match get_response(handle) {
-        Some(r) => {
-            let ptr = leak_cstring_owned(r.body) as *mut std::ffi::c_void;
+    match take_part(handle, "body", |r| r.body.take()) {
+        Some(body) => {
+            let ptr = owned_wpp_string(body) as *mut std::ffi::c_void;
             println!("🔹 [wpp_http_body] handle={} -> {:?}", handle, ptr);
             ptr
         }
-        None => {
-            println!("❌ [wpp_http_body] invalid handle {}", handle);
-            std::ptr::null_mut()
-        }
+        None => std::ptr::null_mut(),
     }
 }
 
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn wpp_http_headers(handle: i32) -> *mut std::ffi::c_void {
-    match get_response(handle) {
-        Some(r) => {
-            let joined = r
-                .headers
+    match take_part(handle, "headers", |r| r.headers.take()) {
+        Some(headers) => {
+            let joined = headers
                 .iter()
                 .map(|(k, v)| format!("{}: {}", k, v))
                 .collect::<Vec<_>>()
                 .join("\n");
-            let ptr = leak_cstring_owned(joined) as *mut std::ffi::c_void;
+            let ptr = owned_wpp_string(joined) as *mut std::ffi::c_void;
             println!("🔹 [wpp_http_headers] handle={} -> {:?}", handle, ptr);
             ptr
         }
@@ -163,13 +204,36 @@ pub unsafe extern "C" fn wpp_http_headers(handle: i32) -> *mut std::ffi::c_void
     }
 }
 
-
-/// === Cleanup (Optional) ===
-/// Frees all stored CStrings in STRING_ARENA.
+/// === Cleanup ===
+/// Drops every stored response, read or not.
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn wpp_http_free_all() {
-    let mut arena = STRING_ARENA.lock().unwrap();
-    let count = arena.len();
-    arena.clear();
-    println!("🧹 [http] Freed {} stored HTTP strings", count);
+    RESP_STORE.lock().unwrap().clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runtime::string::wpp_str_release;
+
+    fn stored(handle: i32) -> bool {
+        RESP_STORE.lock().unwrap().contains_key(&handle)
+    }
+
+    #[test]
+    fn test_responses_are_evicted_once_read() {
+        let response = || HttpResponse { status: 200, body: "ok".to_string(), headers: HashMap::new() };
+        let read = store_response(response());
+        assert_eq!(wpp_http_status(read), 200);
+        unsafe {
+            wpp_str_release(wpp_http_body(read) as *mut c_char);
+            assert!(stored(read), "evicted before the headers were read");
+            wpp_str_release(wpp_http_headers(read) as *mut c_char);
+        }
+        assert!(!stored(read));
+
+        let unread = store_response(response());
+        unsafe { wpp_http_free_all() };
+        assert!(!stored(unread));
+    }
 }
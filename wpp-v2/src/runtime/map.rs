@@ -0,0 +1,225 @@
+//! W++ maps: string keys, 8-byte value slots.
+//!
+//! Keys and values are kept in insertion order so `keys`, `values` and `print` are
+//! deterministic; a Rust `HashMap` indexes the key positions. Keys are copied into W++
+//! strings (see `string.rs`), so they may contain NULs. Values use the same slot
+//! encoding as arrays (see `vector.rs`) and `tag` records their type for the printer.
+//!
+//! The header starts like an object literal (`{ i32 len, i32 kind, i8** keys, vals }`) so
+//! `wpp_print_object` can print both; `kind` tells them apart.
+
+use std::collections::HashMap;
+use std::os::raw::c_char;
+use std::ptr;
+
+use super::gc::{self, BlockKind};
+use super::string::{new_string, str_bytes};
+use super::vector::{wpp_vec_new, WppVec};
+
+/// `kind` value of a map header (object literals leave it 0)
+pub const WPP_MAP_KIND: i32 = 1;
+
+/// Runtime tag for string elements (see `wpp_runtime.c`)
+const STRING_TAG: i32 = 6;
+
+#[repr(C)]
+pub struct WppMap {
+    pub len: i32,
+    pub kind: i32,
+    pub keys: *mut *mut c_char,
+    pub vals: *mut i64,
+    pub tag: i32,
+    cap: i32,
+    index: HashMap<Vec<u8>, i32>,
+}
+
+impl WppMap {
+    unsafe fn key_at(&self, i: i32) -> &[u8] {
+        unsafe { str_bytes(*self.keys.add(i as usize)) }
+    }
+
+    fn grow(&mut self) {
+        if self.len < self.cap {
+            return;
+        }
+        let new_cap = (self.cap * 2).max(4);
+        self.keys = gc::realloc(self.keys as *mut u8, new_cap as usize * size_of::<*mut c_char>(), BlockKind::Data)
+            as *mut *mut c_char;
+        self.vals = gc::realloc(self.vals as *mut u8, new_cap as usize * size_of::<i64>(), BlockKind::Data) as *mut i64;
+        self.cap = new_cap;
+    }
+}
+
+/// Allocate an empty map whose values have runtime tag `tag`
+#[unsafe(no_mangle)]
+pub extern "C" fn wpp_map_new(tag: i32) -> *mut WppMap {
+    let m = gc::alloc(size_of::<WppMap>(), BlockKind::Map) as *mut WppMap;
+    unsafe {
+        ptr::write(
+            m,
+            WppMap {
+                len: 0,
+                kind: WPP_MAP_KIND,
+                keys: ptr::null_mut(),
+                vals: ptr::null_mut(),
+                tag,
+                cap: 0,
+                index: HashMap::new(),
+            },
+        )
+    };
+    m
+}
+
+/// Insert or overwrite `key`; the key string is copied. Returns the new length.
+///
+/// # Safety
+/// `m` must be a map created by `wpp_map_new` and `key` a string or null.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_map_set(m: *mut WppMap, key: *const c_char, bits: i64) -> i32 {
+    let m = unsafe { &mut *m };
+    let bytes = unsafe { str_bytes(key) };
+    if let Some(&i) = m.index.get(bytes) {
+        unsafe { *m.vals.add(i as usize) = bits };
+        return m.len;
+    }
+
+    m.grow();
+    let owned = new_string(bytes);
+    unsafe {
+        *m.keys.add(m.len as usize) = owned;
+        *m.vals.add(m.len as usize) = bits;
+    }
+    m.index.insert(bytes.to_vec(), m.len);
+    m.len += 1;
+    m.len
+}
+
+/// Position of `key`, or -1 when it is missing
+///
+/// # Safety
+/// `m` must be a map created by `wpp_map_new` and `key` a string or null.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_map_find(m: *const WppMap, key: *const c_char) -> i32 {
+    let m = unsafe { &*m };
+    m.index.get(unsafe { str_bytes(key) }).copied().unwrap_or(-1)
+}
+
+/// Value bits at a position returned by `wpp_map_find`
+///
+/// # Safety
+/// `m` must be a map created by `wpp_map_new` and `0 <= pos < len`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_map_value_at(m: *const WppMap, pos: i32) -> i64 {
+    unsafe { *(*m).vals.add(pos as usize) }
+}
+
+/// Key at a position in `0..len`. The string belongs to the map.
+///
+/// # Safety
+/// `m` must be a map created by `wpp_map_new` and `0 <= pos < len`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_map_key_at(m: *const WppMap, pos: i32) -> *const c_char {
+    unsafe { *(*m).keys.add(pos as usize) }
+}
+
+/// Remove `key`, keeping the order of the remaining entries. Returns 1 if it was present.
+/// The key string is left to the collector: a `for k, v in m` loop may still have it bound.
+///
+/// # Safety
+/// `m` must be a map created by `wpp_map_new` and `key` a string or null.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_map_delete(m: *mut WppMap, key: *const c_char) -> i32 {
+    let m = unsafe { &mut *m };
+    let Some(pos) = m.index.remove(unsafe { str_bytes(key) }) else {
+        return 0;
+    };
+    let tail = (m.len - pos - 1) as usize;
+    unsafe {
+        ptr::copy(m.keys.add(pos as usize + 1), m.keys.add(pos as usize), tail);
+        ptr::copy(m.vals.add(pos as usize + 1), m.vals.add(pos as usize), tail);
+    }
+    m.len -= 1;
+    unsafe {
+        *m.keys.add(m.len as usize) = ptr::null_mut();
+        *m.vals.add(m.len as usize) = 0;
+    }
+    for i in m.index.values_mut() {
+        if *i > pos {
+            *i -= 1;
+        }
+    }
+    1
+}
+
+/// New `str[]` holding copies of the keys, in insertion order
+///
+/// # Safety
+/// `m` must be a map created by `wpp_map_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_map_keys(m: *const WppMap) -> *mut WppVec {
+    let m = unsafe { &*m };
+    let out = wpp_vec_new(STRING_TAG, m.len);
+    for i in 0..m.len {
+        let copy = new_string(unsafe { m.key_at(i) });
+        unsafe { *(*out).data.add(i as usize) = copy as i64 };
+    }
+    out
+}
+
+/// New array holding the values, in insertion order
+///
+/// # Safety
+/// `m` must be a map created by `wpp_map_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_map_values(m: *const WppMap) -> *mut WppVec {
+    let m = unsafe { &*m };
+    let out = wpp_vec_new(m.tag, m.len);
+    if m.len > 0 {
+        unsafe { ptr::copy_nonoverlapping(m.vals, (*out).data, m.len as usize) };
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    fn keys(m: *const WppMap) -> Vec<String> {
+        let v = unsafe { &*wpp_map_keys(m) };
+        (0..v.len as usize)
+            .map(|i| unsafe { CStr::from_ptr(*v.data.add(i) as *const c_char) }.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_set_overwrites_and_keeps_order() {
+        let m = wpp_map_new(1);
+        unsafe {
+            assert_eq!(wpp_map_set(m, c"b".as_ptr(), 1), 1);
+            assert_eq!(wpp_map_set(m, c"a".as_ptr(), 2), 2);
+            assert_eq!(wpp_map_set(m, c"b".as_ptr(), 3), 2);
+            assert_eq!(wpp_map_value_at(m, wpp_map_find(m, c"b".as_ptr())), 3);
+            assert_eq!(wpp_map_find(m, c"zz".as_ptr()), -1);
+        }
+        assert_eq!(keys(m), ["b", "a"]);
+    }
+
+    #[test]
+    fn test_delete_reindexes_later_entries() {
+        let m = wpp_map_new(1);
+        unsafe {
+            for (i, k) in [c"x", c"y", c"z"].iter().enumerate() {
+                wpp_map_set(m, k.as_ptr(), i as i64);
+            }
+            assert_eq!(wpp_map_delete(m, c"x".as_ptr()), 1);
+            assert_eq!(wpp_map_delete(m, c"x".as_ptr()), 0);
+            assert_eq!(wpp_map_value_at(m, wpp_map_find(m, c"z".as_ptr())), 2);
+            assert_eq!(CStr::from_ptr(wpp_map_key_at(m, 1)), c"z");
+            let vals = &*wpp_map_values(m);
+            assert_eq!(*vals.data.add(0), 1);
+        }
+        assert_eq!(keys(m), ["y", "z"]);
+    }
+}
@@ -3,10 +3,22 @@ pub mod server;
 pub mod core;
 pub mod link_rust;
 pub mod validation;
+pub mod vector;
+pub mod map;
+pub mod closure;
+pub mod gc;
+pub mod string;
+pub mod exception;
 pub use core::*;  // re-export async logic
 pub use http::*;
 pub use server::*;
 pub use validation::*;  // re-export validation functions
+pub use vector::*;
+pub use map::*;
+pub use closure::*;
+pub use gc::{wpp_gc_alloc, wpp_gc_is_data};
+pub use string::*;
+pub use exception::*;
 pub mod thread;
 pub use thread::{ThreadHandle, ThreadState};
 pub use link_rust::link_rust_modules;
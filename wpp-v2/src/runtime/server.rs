@@ -13,6 +13,8 @@ use httparse;
 use dashmap::DashMap;
 
 use crate::runtime::core::register_task; // ✅ use shared async runtime
+use crate::runtime::closure::WppClosure;
+use crate::runtime::gc;
 
 // ✅ Pre-compiled HTTP response headers (Phase 1 v2)
 const HTTP_200_KEEPALIVE: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 3\r\nConnection: keep-alive\r\n\r\n";
@@ -20,10 +22,10 @@ const HTTP_200_CLOSE: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nC
 const HTTP_404_KEEPALIVE: &[u8] = b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: keep-alive\r\n\r\n";
 const HTTP_404_CLOSE: &[u8] = b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
 
-/// Wrapper for raw function pointer used in W++ runtime.
+/// Wrapper for a W++ handler (a closure; plain functions have a null env).
 /// This allows it to implement Send/Sync safely.
 #[derive(Clone, Copy)]
-pub struct WppFunctionRef(pub *const ());
+pub struct WppFunctionRef(pub WppClosure);
 unsafe impl Send for WppFunctionRef {}
 unsafe impl Sync for WppFunctionRef {}
 
@@ -171,13 +173,11 @@ fn parse_http_request(request: &[u8]) -> (&[u8], bool) {
 
 /// Invoke a W++ handler function dynamically
 fn invoke_handler(handler: WppFunctionRef) -> i32 {
-    // Cast the raw function pointer to the correct signature: fn() -> i32
-    type HandlerFn = unsafe extern "C" fn() -> i32;
-
-    unsafe {
-        let handler_fn: HandlerFn = std::mem::transmute(handler.0);
-        handler_fn()
-    }
+    // Handlers take no W++ parameters and return i32
+    let status = gc::run_mutator(|| unsafe { handler.0.call0() });
+    // Whatever the handler allocated is garbage now unless it was stored somewhere
+    gc::collect_if_due();
+    status
 }
 
 /// === C ABI Bindings ===
@@ -190,7 +190,25 @@ pub extern "C" fn wpp_register_endpoint(path_ptr: *const c_char, handler_ptr: *c
     }
 
     let path = unsafe { CStr::from_ptr(path_ptr) }.to_string_lossy().to_string();
-    register_endpoint(path, WppFunctionRef(handler_ptr));
+    register_endpoint(path, WppFunctionRef(WppClosure::plain(handler_ptr as *const std::ffi::c_void)));
+}
+
+/// `server.register` entry point: the handler is a W++ closure, so it may carry state
+///
+/// # Safety
+/// `path_ptr` must be a NUL-terminated string and `closure` come from `wpp_closure_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_register_closure_endpoint(path_ptr: *const c_char, closure: *const WppClosure) {
+    if path_ptr.is_null() || closure.is_null() {
+        eprintln!("❌ Null path or handler pointer");
+        return;
+    }
+
+    let path = unsafe { CStr::from_ptr(path_ptr) }.to_string_lossy().to_string();
+    let closure = unsafe { *closure };
+    // Endpoints live for the rest of the program, and so does their captured state
+    gc::pin(closure.env as *const u8);
+    register_endpoint(path, WppFunctionRef(closure));
 }
 
 /// ✅ New version integrated with async scheduler
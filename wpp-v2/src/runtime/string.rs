@@ -0,0 +1,391 @@
+//! W++ strings: NUL-terminated bytes preceded by a `WppStringHeader`.
+//!
+//! A string value is still a `char*` pointing at the first byte, so it can be handed to C
+//! (`printf`, FFI libraries) unchanged. The header sits just before the bytes and records
+//! the byte length (so embedded NULs survive), the number of code points (so `strlen` is
+//! O(1)), the capacity and a reference count. Literals are emitted by codegen with the same
+//! layout and `STATIC_REFCOUNT`, so they are never freed.
+//!
+//! Strings that did not come from W++ (C buffers, FFI libraries) have no header, and the
+//! bytes before them may not even be readable. A header is only read for a string the
+//! runtime knows: a block of the W++ heap, or a literal registered by its module's
+//! constructor (see `wpp_str_register_literal`). The C printer in `wpp_runtime.c` asks for
+//! lengths through `wpp_str_byte_len`.
+//!
+//! Strings live on the W++ heap (see `gc.rs`): releasing the last reference frees one at
+//! once, and the collector frees those that become unreachable without being released.
+
+use std::collections::HashSet;
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::ptr;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use once_cell::sync::Lazy;
+
+use super::exception::raise;
+
+use super::gc::{self, BlockKind};
+
+/// Last header word of every W++ string ("WPSS")
+pub const WPP_STRING_MAGIC: u32 = 0x5750_5353;
+
+/// Reference count of strings that live forever (literals)
+pub const STATIC_REFCOUNT: u32 = u32::MAX;
+
+#[repr(C)]
+pub struct WppStringHeader {
+    pub refcount: AtomicU32,
+    /// Length in bytes, not counting the trailing NUL
+    pub len: u32,
+    /// Bytes available for data, not counting the trailing NUL
+    pub cap: u32,
+    /// Length in code points
+    pub chars: u32,
+    _reserved: u32,
+    pub magic: u32,
+}
+
+const HEADER_SIZE: usize = size_of::<WppStringHeader>();
+
+/// First bytes of the string literals of every loaded module
+static LITERALS: Lazy<RwLock<HashSet<usize>>> = Lazy::new(|| RwLock::new(HashSet::new()));
+
+/// Record `s`, the first byte of a string literal emitted by codegen, as carrying a header.
+/// Called from the constructor of each compiled module.
+#[unsafe(no_mangle)]
+pub extern "C" fn wpp_str_register_literal(s: *const c_char) {
+    if !s.is_null() {
+        LITERALS.write().unwrap_or_else(|e| e.into_inner()).insert(s as usize);
+    }
+}
+
+/// Header of a W++ string, or `None` for a plain C string
+///
+/// Only strings allocated by [`new_string`] or registered literals are looked at; for any
+/// other pointer the bytes before it are never read.
+///
+/// # Safety
+/// `s` must be null or point to a W++ string or a NUL-terminated C string.
+pub unsafe fn header<'a>(s: *const c_char) -> Option<&'a WppStringHeader> {
+    if s.is_null() {
+        return None;
+    }
+    let block = s.wrapping_sub(HEADER_SIZE) as *const u8;
+    let known = gc::owns(block, BlockKind::String)
+        || LITERALS.read().unwrap_or_else(|e| e.into_inner()).contains(&(s as usize));
+    if !known {
+        return None;
+    }
+    let header = unsafe { &*(block as *const WppStringHeader) };
+    (header.magic == WPP_STRING_MAGIC).then_some(header)
+}
+
+/// Contents of a W++ or C string (empty for null)
+///
+/// # Safety
+/// Same as [`header`]; the bytes must outlive the returned slice.
+pub unsafe fn str_bytes<'a>(s: *const c_char) -> &'a [u8] {
+    if s.is_null() {
+        return b"";
+    }
+    match unsafe { header(s) } {
+        Some(h) => unsafe { std::slice::from_raw_parts(s as *const u8, h.len as usize) },
+        None => unsafe { CStr::from_ptr(s).to_bytes() },
+    }
+}
+
+/// Contents as text; invalid UTF-8 is replaced rather than rejected
+unsafe fn text<'a>(s: *const c_char) -> std::borrow::Cow<'a, str> {
+    String::from_utf8_lossy(unsafe { str_bytes(s) })
+}
+
+fn count_chars(bytes: &[u8]) -> usize {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => s.chars().count(),
+        Err(_) => String::from_utf8_lossy(bytes).chars().count(),
+    }
+}
+
+/// Allocate a new W++ string (refcount 1) holding a copy of `bytes`
+pub fn new_string(bytes: &[u8]) -> *mut c_char {
+    let len = bytes.len();
+    unsafe {
+        let block = gc::alloc(HEADER_SIZE + len + 1, BlockKind::String);
+        ptr::write(
+            block as *mut WppStringHeader,
+            WppStringHeader {
+                refcount: AtomicU32::new(1),
+                len: len as u32,
+                cap: len as u32,
+                chars: count_chars(bytes) as u32,
+                _reserved: 0,
+                magic: WPP_STRING_MAGIC,
+            },
+        );
+        let data = block.add(HEADER_SIZE);
+        ptr::copy_nonoverlapping(bytes.as_ptr(), data, len);
+        *data.add(len) = 0;
+        data as *mut c_char
+    }
+}
+
+/// New W++ string from `len` bytes at `bytes` (which may contain NULs)
+///
+/// # Safety
+/// `bytes` must be valid for reads of `len` bytes (or null with `len == 0`).
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_str_new(bytes: *const c_char, len: i32) -> *mut c_char {
+    if bytes.is_null() || len <= 0 {
+        return new_string(b"");
+    }
+    new_string(unsafe { std::slice::from_raw_parts(bytes as *const u8, len as usize) })
+}
+
+/// Length in code points: O(1) for W++ strings, a scan for C strings
+///
+/// # Safety
+/// `s` must be null or point to a W++ string or a NUL-terminated C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_str_len(s: *const c_char) -> i32 {
+    match unsafe { header(s) } {
+        Some(h) => h.chars as i32,
+        None => count_chars(unsafe { str_bytes(s) }) as i32,
+    }
+}
+
+/// Length in bytes, not counting the trailing NUL
+///
+/// # Safety
+/// `s` must be null or point to a W++ string or a NUL-terminated C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_str_byte_len(s: *const c_char) -> i32 {
+    unsafe { str_bytes(s) }.len() as i32
+}
+
+/// Take another reference to `s`. Literals and C strings are left alone.
+///
+/// # Safety
+/// `s` must be null or point to a live W++ string or a NUL-terminated C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_str_retain(s: *const c_char) {
+    if let Some(h) = unsafe { header(s) }
+        && h.refcount.load(Ordering::Relaxed) != STATIC_REFCOUNT
+    {
+        h.refcount.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Drop a reference to `s`, freeing it when it was the last one. Literals and C strings
+/// are left alone.
+///
+/// # Safety
+/// `s` must be null or point to a live W++ string or a NUL-terminated C string, and the
+/// caller must own the reference it gives up.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_str_release(s: *const c_char) {
+    let Some(h) = (unsafe { header(s) }) else {
+        return;
+    };
+    if h.refcount.load(Ordering::Relaxed) == STATIC_REFCOUNT {
+        return;
+    }
+    if h.refcount.fetch_sub(1, Ordering::AcqRel) == 1 {
+        gc::free(unsafe { s.sub(HEADER_SIZE) } as *mut u8);
+    }
+}
+
+/// Byte-wise comparison (negative, zero or positive, like `strcmp`), embedded NULs included
+///
+/// # Safety
+/// `a` and `b` must be null or point to W++ strings or NUL-terminated C strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_str_cmp(a: *const c_char, b: *const c_char) -> i32 {
+    match unsafe { str_bytes(a).cmp(str_bytes(b)) } {
+        std::cmp::Ordering::Less => -1,
+        std::cmp::Ordering::Equal => 0,
+        std::cmp::Ordering::Greater => 1,
+    }
+}
+
+/// Throw if any of `args` is null; the caller then returns its empty result
+fn null_argument(operation: &str, args: &[*const c_char]) -> bool {
+    let null = args.iter().any(|s| s.is_null());
+    if null {
+        raise(&format!("null string passed to {}", operation));
+    }
+    null
+}
+
+/// `a + b` as a new string
+///
+/// # Safety
+/// `a` and `b` must be null or point to W++ strings or NUL-terminated C strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_str_concat(a: *const c_char, b: *const c_char) -> *mut c_char {
+    if null_argument("string concatenation", &[a, b]) {
+        return ptr::null_mut();
+    }
+    let (a, b) = unsafe { (str_bytes(a), str_bytes(b)) };
+    let mut joined = Vec::with_capacity(a.len() + b.len());
+    joined.extend_from_slice(a);
+    joined.extend_from_slice(b);
+    new_string(&joined)
+}
+
+/// `length` code points starting at code point `start` (clamped to the string)
+///
+/// # Safety
+/// `s` must be null or point to a W++ string or a NUL-terminated C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_str_substr(s: *const c_char, start: i32, length: i32) -> *mut c_char {
+    if null_argument("wpp_str_substr", &[s]) {
+        return ptr::null_mut();
+    }
+    let result: String = unsafe { text(s) }
+        .chars()
+        .skip(start.max(0) as usize)
+        .take(length.max(0) as usize)
+        .collect();
+    new_string(result.as_bytes())
+}
+
+/// Code point index of the first `needle` in `haystack`, or -1
+///
+/// # Safety
+/// Both arguments must be null or point to W++ strings or NUL-terminated C strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_str_index_of(haystack: *const c_char, needle: *const c_char) -> i32 {
+    if null_argument("wpp_str_index_of", &[haystack, needle]) {
+        return -1;
+    }
+    let hay = unsafe { text(haystack) };
+    match hay.find(&*unsafe { text(needle) }) {
+        Some(pos) => hay[..pos].chars().count() as i32,
+        None => -1,
+    }
+}
+
+/// Copy of `s` with every `find` replaced by `replace`
+///
+/// # Safety
+/// All arguments must be null or point to W++ strings or NUL-terminated C strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_str_replace(s: *const c_char, find: *const c_char, replace: *const c_char) -> *mut c_char {
+    if null_argument("wpp_str_replace", &[s, find, replace]) {
+        return ptr::null_mut();
+    }
+    let result = unsafe { text(s).replace(&*text(find), &text(replace)) };
+    new_string(result.as_bytes())
+}
+
+/// Upper-cased copy of `s`
+///
+/// # Safety
+/// `s` must be null or point to a W++ string or a NUL-terminated C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_str_to_upper(s: *const c_char) -> *mut c_char {
+    if null_argument("wpp_str_to_upper", &[s]) {
+        return ptr::null_mut();
+    }
+    new_string(unsafe { text(s) }.to_uppercase().as_bytes())
+}
+
+/// Lower-cased copy of `s`
+///
+/// # Safety
+/// `s` must be null or point to a W++ string or a NUL-terminated C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_str_to_lower(s: *const c_char) -> *mut c_char {
+    if null_argument("wpp_str_to_lower", &[s]) {
+        return ptr::null_mut();
+    }
+    new_string(unsafe { text(s) }.to_lowercase().as_bytes())
+}
+
+/// Copy of `s` without leading and trailing whitespace
+///
+/// # Safety
+/// `s` must be null or point to a W++ string or a NUL-terminated C string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_str_trim(s: *const c_char) -> *mut c_char {
+    if null_argument("wpp_str_trim", &[s]) {
+        return ptr::null_mut();
+    }
+    new_string(unsafe { text(s) }.trim().as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes_of(s: *const c_char) -> Vec<u8> {
+        unsafe { str_bytes(s) }.to_vec()
+    }
+
+    #[test]
+    fn test_header_tracks_bytes_and_code_points() {
+        let s = new_string("héllo\0wörld".as_bytes());
+        unsafe {
+            assert_eq!(wpp_str_byte_len(s), 13);
+            assert_eq!(wpp_str_len(s), 11);
+            // C strings have no header and are measured up to the NUL
+            assert_eq!(wpp_str_len(c"héllo".as_ptr()), 5);
+            assert_eq!(wpp_str_cmp(s, c"héllo".as_ptr()), 1);
+            wpp_str_release(s);
+        }
+    }
+
+    #[test]
+    fn test_foreign_strings_are_not_mistaken_for_wpp_strings() {
+        // A C buffer whose preceding bytes happen to look like a header
+        let mut buffer = [0u8; HEADER_SIZE + 3];
+        buffer[HEADER_SIZE - 4..HEADER_SIZE].copy_from_slice(&WPP_STRING_MAGIC.to_ne_bytes());
+        buffer[HEADER_SIZE..].copy_from_slice(b"hi\0");
+        let s = unsafe { buffer.as_ptr().add(HEADER_SIZE) } as *const c_char;
+        unsafe {
+            assert!(header(s).is_none());
+            assert_eq!(wpp_str_byte_len(s), 2);
+        }
+    }
+
+    #[test]
+    fn test_helpers_index_code_points() {
+        let s = new_string("añb€c".as_bytes());
+        unsafe {
+            assert_eq!(bytes_of(wpp_str_substr(s, 1, 3)), "ñb€".as_bytes());
+            assert_eq!(wpp_str_index_of(s, c"€".as_ptr()), 3);
+            let joined = wpp_str_concat(s, wpp_str_new(b"\0!".as_ptr() as *const c_char, 2));
+            assert_eq!(bytes_of(joined), "añb€c\0!".as_bytes());
+            assert_eq!(wpp_str_len(joined), 7);
+        }
+    }
+
+    #[test]
+    fn test_refcount_frees_on_last_release() {
+        let s = new_string(b"shared");
+        unsafe {
+            wpp_str_retain(s);
+            wpp_str_release(s);
+            assert_eq!(header(s).unwrap().refcount.load(Ordering::Relaxed), 1);
+            wpp_str_release(s);
+            // Releasing C strings is a no-op
+            wpp_str_release(c"static".as_ptr());
+        }
+    }
+
+    #[test]
+    fn test_null_arguments_throw() {
+        use crate::runtime::exception::{wpp_exception_catch, wpp_exception_pending};
+
+        unsafe {
+            assert!(wpp_str_concat(c"a".as_ptr(), ptr::null()).is_null());
+            assert_eq!(wpp_exception_pending(), 1);
+            wpp_exception_catch();
+            assert_eq!(wpp_str_index_of(ptr::null(), c"a".as_ptr()), -1);
+            assert_eq!(wpp_exception_pending(), 1);
+            wpp_exception_catch();
+        }
+    }
+}
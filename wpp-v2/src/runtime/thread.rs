@@ -103,7 +103,6 @@
 use std::{
     any::Any,
     collections::HashMap,
-    mem,
     os::raw::{c_int, c_void},
     sync::{
         atomic::{AtomicBool, AtomicU64, Ordering},
@@ -116,6 +115,10 @@ use std::{
 use once_cell::sync::Lazy;
 use rand::Rng;
 
+use super::closure::WppClosure;
+use super::exception::raise;
+use super::gc;
+
 // ===========================================================
 // 🔧 Configuration Constants
 // ===========================================================
@@ -330,16 +333,24 @@ pub struct ThreadHandle {
 
 impl ThreadHandle {
     pub fn spawn(func_ptr: *const c_void) -> Arc<ThreadHandle> {
-        if func_ptr.is_null() {
+        Self::spawn_closure(WppClosure::plain(func_ptr))
+    }
+
+    /// Spawn a thread running a W++ closure (a plain function has a null `env`)
+    pub fn spawn_closure(task: WppClosure) -> Arc<ThreadHandle> {
+        if task.func.is_null() {
             eprintln!("❌ [thread] null func pointer");
             ThreadGC::collect_now();
-            return Arc::new(ThreadHandle {
+            let handle = Arc::new(ThreadHandle {
                 id: 0,
                 finished: Arc::new(AtomicBool::new(true)),
                 result: Arc::new(Mutex::new(None)),
                 join_handle: Mutex::new(None), // ✅ now matches type
                 ref_count: Arc::new(AtomicU64::new(0)),
             });
+            // Registered so joining it is valid
+            ThreadGC::register(handle.clone());
+            return handle;
         }
 
         static NEXT_ID: AtomicU64 = AtomicU64::new(1);
@@ -360,13 +371,15 @@ impl ThreadHandle {
         });
         if recursion_violation {
             eprintln!("💥 [thread] recursion prevented: thread #{id} tried to spawn itself!");
-            return Arc::new(ThreadHandle {
+            let handle = Arc::new(ThreadHandle {
                 id,
                 finished: Arc::new(AtomicBool::new(true)),
                 result: Arc::new(Mutex::new(None)),
                 join_handle: Mutex::new(None),
                 ref_count: Arc::new(AtomicU64::new(0)),
             });
+            ThreadGC::register(handle.clone());
+            return handle;
         }
 
         // FIX 10: Use RAII guard for automatic ancestry cleanup
@@ -376,7 +389,6 @@ impl ThreadHandle {
         let result = Arc::new(Mutex::new(None));
         let ref_count = Arc::new(AtomicU64::new(1));
 
-        let func: extern "C" fn() = unsafe { mem::transmute(func_ptr) };
         let fin_clone = finished.clone();
 
         // Track that at least one thread has been spawned
@@ -384,16 +396,20 @@ impl ThreadHandle {
 
         println!("🚀 [thread] spawning GC-managed thread #{id}");
 
+        // The closure environment is only referenced from the new thread until it runs
+        gc::pin(task.env as *const u8);
+
         // ✅ Store inside Mutex<Option<JoinHandle>>
         let join_handle = Mutex::new(Some(thread::spawn(move || {
             // FIX 10: Create RAII guard - ensures cleanup even on panic
             let _ancestry_guard = AncestryGuard(id);
 
-            let result = std::panic::catch_unwind(|| func());
+            let result = std::panic::catch_unwind(|| gc::run_mutator(|| unsafe { task.call0() }));
             match result {
                 Ok(_) => println!("✅ [thread] thread #{id} finished normally"),
                 Err(_) => eprintln!("💥 [thread] thread #{id} panicked"),
             }
+            gc::unpin(task.env as *const u8);
             fin_clone.store(true, Ordering::SeqCst);
 
             // _ancestry_guard drops here, automatically cleaning up ancestry
@@ -417,7 +433,7 @@ impl ThreadHandle {
             println!("🧵 [thread] joining thread #{}", self.id);
 
             // FIX 15: Capture and log join results instead of discarding them
-            match handle.join() {
+            match gc::parked(|| handle.join()) {
                 Ok(_) => {
                     println!("✅ [thread] Successfully joined thread #{}", self.id);
                 }
@@ -539,6 +555,16 @@ impl ThreadGC {
         GC_LOCK.store(false, Ordering::Release);
     }
 
+/// Whether `ptr` is a registered handle that has not been dropped
+pub fn is_live(ptr: *const ThreadHandle) -> bool {
+    ThreadGC::global()
+        .threads
+        .lock()
+        .unwrap()
+        .values()
+        .any(|handle| std::ptr::eq(handle.as_ptr(), ptr) && handle.strong_count() > 0)
+}
+
 pub fn register_mutex<T: Send + 'static>(ptr: Arc<GcMutex<T>>) {
     // FIX 9: Add same GC_LOCK protection as thread registration
     let mut backoff = 1;
@@ -648,10 +674,21 @@ pub extern "C" fn wpp_thread_spawn_gc(fn_ptr: *const c_void) -> *mut ThreadHandl
     Arc::into_raw(handle_arc) as *mut ThreadHandle // ✅ convert safely to raw ptr
 }
 
+/// `useThread` entry point: spawn a thread running a W++ closure
+///
+/// # Safety
+/// `closure` must come from `wpp_closure_new` and wrap a function with no W++ parameters.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_thread_spawn_closure(closure: *const WppClosure) -> *mut ThreadHandle {
+    let task = if closure.is_null() { WppClosure::plain(std::ptr::null()) } else { unsafe { *closure } };
+    Arc::into_raw(ThreadHandle::spawn_closure(task)) as *mut ThreadHandle
+}
+
+/// Wait for a thread; a null, unknown or already joined handle throws a W++ exception
 #[unsafe(no_mangle)]
 pub extern "C" fn wpp_thread_join(ptr: *mut ThreadHandle) {
-    if ptr.is_null() {
-        eprintln!("⚠️ wpp_thread_join: null pointer");
+    if ptr.is_null() || !ThreadGC::is_live(ptr) {
+        raise("invalid thread handle passed to join");
         return;
     }
 
@@ -818,7 +855,7 @@ pub extern "C" fn wpp_thread_join_all() {
             // Join if not already joined
             if let Some(join_handle) = join_guard.take() {
                 println!("🧵 [thread] joining thread #{id}");
-                match join_handle.join() {
+                match gc::parked(|| join_handle.join()) {
                     Ok(_) => {
                         println!("✅ [thread] joined thread #{id}");
                         joined += 1;
@@ -0,0 +1,184 @@
+//! W++ growable arrays.
+//!
+//! Every array value is a pointer to a `WppVec` header. Elements live in a separate
+//! buffer of 8-byte slots so the header address stays stable when the buffer grows.
+//! Codegen passes elements as raw slot bits (`i64`): ints are zero-extended, floats are
+//! bit-cast and pointers are converted with `ptrtoint`. `tag` describes the element type
+//! for the printer (see `wpp_runtime.c`); this module never interprets it.
+//!
+//! Headers and buffers live on the W++ heap (see `gc.rs`).
+//!
+//! Index and emptiness checks are emitted by codegen so failures become W++ exceptions;
+//! these functions assume their arguments were already validated.
+
+use std::{mem, ptr};
+
+use super::gc::{self, BlockKind};
+
+#[repr(C)]
+pub struct WppVec {
+    pub len: i32,
+    pub tag: i32,
+    pub cap: i32,
+    _pad: i32,
+    pub data: *mut i64,
+}
+
+const MIN_CAPACITY: i32 = 4;
+
+unsafe fn grow_to(v: &mut WppVec, needed: i32) {
+    if needed <= v.cap {
+        return;
+    }
+    let new_cap = needed.max(v.cap * 2).max(MIN_CAPACITY);
+    let bytes = new_cap as usize * std::mem::size_of::<i64>();
+    v.data = gc::realloc(v.data as *mut u8, bytes, BlockKind::Data) as *mut i64;
+    v.cap = new_cap;
+}
+
+/// Allocate an array of `len` zeroed slots
+#[unsafe(no_mangle)]
+pub extern "C" fn wpp_vec_new(tag: i32, len: i32) -> *mut WppVec {
+    let len = len.max(0);
+    let v = gc::alloc(size_of::<WppVec>(), BlockKind::Data) as *mut WppVec;
+    unsafe {
+        ptr::write(v, WppVec { len, tag, cap: 0, _pad: 0, data: ptr::null_mut() });
+        grow_to(&mut *v, len);
+    }
+    v
+}
+
+/// Append one element and return the new length
+///
+/// # Safety
+/// `v` must be an array created by `wpp_vec_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_vec_push(v: *mut WppVec, bits: i64) -> i32 {
+    let v = unsafe { &mut *v };
+    unsafe {
+        grow_to(v, v.len + 1);
+        *v.data.add(v.len as usize) = bits;
+    }
+    v.len += 1;
+    v.len
+}
+
+/// Remove and return the last element
+///
+/// # Safety
+/// `v` must be a non-empty array created by `wpp_vec_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_vec_pop(v: *mut WppVec) -> i64 {
+    let v = unsafe { &mut *v };
+    v.len -= 1;
+    // Clear the vacated slot so it does not keep its value alive
+    unsafe { mem::take(&mut *v.data.add(v.len as usize)) }
+}
+
+/// Insert an element before `index` (`index == len` appends)
+///
+/// # Safety
+/// `v` must be an array created by `wpp_vec_new` and `0 <= index <= len`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_vec_insert(v: *mut WppVec, index: i32, bits: i64) {
+    let v = unsafe { &mut *v };
+    unsafe {
+        grow_to(v, v.len + 1);
+        let at = v.data.add(index as usize);
+        ptr::copy(at, at.add(1), (v.len - index) as usize);
+        *at = bits;
+    }
+    v.len += 1;
+}
+
+/// Remove and return the element at `index`, shifting the rest down
+///
+/// # Safety
+/// `v` must be an array created by `wpp_vec_new` and `0 <= index < len`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_vec_remove(v: *mut WppVec, index: i32) -> i64 {
+    let v = unsafe { &mut *v };
+    unsafe {
+        let at = v.data.add(index as usize);
+        let removed = *at;
+        ptr::copy(at.add(1), at, (v.len - index - 1) as usize);
+        v.len -= 1;
+        *v.data.add(v.len as usize) = 0;
+        removed
+    }
+}
+
+/// Copy elements `start..end` into a new array. Bounds are clamped to the array,
+/// so `slice(xs, 2, 100)` is everything from index 2 on.
+///
+/// # Safety
+/// `v` must be an array created by `wpp_vec_new`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_vec_slice(v: *const WppVec, start: i32, end: i32) -> *mut WppVec {
+    let v = unsafe { &*v };
+    let start = start.clamp(0, v.len);
+    let end = end.clamp(start, v.len);
+    let out = wpp_vec_new(v.tag, end - start);
+    unsafe {
+        if end > start {
+            ptr::copy_nonoverlapping(v.data.add(start as usize), (*out).data, (end - start) as usize);
+        }
+    }
+    out
+}
+
+/// New array holding the elements of `a` followed by those of `b`
+///
+/// # Safety
+/// `a` and `b` must be arrays created by `wpp_vec_new` with the same element type.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn wpp_vec_concat(a: *const WppVec, b: *const WppVec) -> *mut WppVec {
+    let (a, b) = unsafe { (&*a, &*b) };
+    let out = wpp_vec_new(a.tag, a.len + b.len);
+    unsafe {
+        if a.len > 0 {
+            ptr::copy_nonoverlapping(a.data, (*out).data, a.len as usize);
+        }
+        if b.len > 0 {
+            ptr::copy_nonoverlapping(b.data, (*out).data.add(a.len as usize), b.len as usize);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(v: *const WppVec) -> Vec<i64> {
+        let v = unsafe { &*v };
+        (0..v.len as usize).map(|i| unsafe { *v.data.add(i) }).collect()
+    }
+
+    #[test]
+    fn test_push_grows_past_capacity() {
+        let v = wpp_vec_new(1, 0);
+        for i in 0..10 {
+            assert_eq!(unsafe { wpp_vec_push(v, i) }, i as i32 + 1);
+        }
+        assert_eq!(items(v), (0..10).collect::<Vec<_>>());
+        assert_eq!(unsafe { wpp_vec_pop(v) }, 9);
+    }
+
+    #[test]
+    fn test_insert_remove_slice_concat() {
+        let v = wpp_vec_new(1, 0);
+        unsafe {
+            wpp_vec_push(v, 1);
+            wpp_vec_push(v, 3);
+            wpp_vec_insert(v, 1, 2);
+            wpp_vec_insert(v, 0, 0);
+            assert_eq!(items(v), vec![0, 1, 2, 3]);
+            assert_eq!(wpp_vec_remove(v, 0), 0);
+
+            assert_eq!(items(wpp_vec_slice(v, 1, 100)), vec![2, 3]);
+            assert_eq!(items(wpp_vec_slice(v, 2, 1)), Vec::<i64>::new());
+            assert_eq!(items(wpp_vec_concat(v, v)), vec![1, 2, 3, 1, 2, 3]);
+        }
+    }
+}
@@ -2,8 +2,12 @@
 extern "C" {
 #endif
 
+// open_memstream
+#define _POSIX_C_SOURCE 200809L
+
 #include <stdio.h>
 #include <stdint.h>
+#include <stdlib.h>
 #include <string.h>
 #include <ctype.h>
 
@@ -28,10 +32,26 @@ static int is_probably_valid_string(const char *s) {
     return 1;
 }
 
+// =====================================================
+// === W++ STRINGS (runtime/string.rs)
+// =====================================================
+//  String values point at their first byte; W++ strings carry
+//  a header just before it, plain C strings do not. Only the
+//  Rust side can tell them apart, so it measures both
+// =====================================================
+extern int32_t wpp_str_byte_len(const char *s);
+
+static size_t wpp_string_byte_len(const char *s) {
+    return (size_t)wpp_str_byte_len(s);
+}
+
+// Allocates a W++ string (runtime/string.rs)
+extern char *wpp_str_new(const char *bytes, int32_t len);
+
 // =====================================================
 // === SAFE PRINT
 // =====================================================
-static int is_probably_valid_utf8(const unsigned char *s) {
+static int is_probably_valid_utf8(const unsigned char *s, size_t len) {
     if (!s) return 0;
     uintptr_t addr = (uintptr_t)s;
 
@@ -39,24 +59,23 @@ static int is_probably_valid_utf8(const unsigned char *s) {
     if (addr > 0x7fffffffffff) return 0;
 
     size_t i = 0;
-    while (i < 1024) {
+    while (i < len && i < 1024) {
         unsigned char c = s[i];
-        if (c == 0) return 1; // null terminator found
 
-        // Single-byte (ASCII)
+        // Single-byte (ASCII, including embedded NULs)
         if (c < 0x80) { i++; continue; }
 
         // Multi-byte UTF-8 checks
         if ((c & 0xE0) == 0xC0) { // 2-byte sequence
-            if ((s[i+1] & 0xC0) != 0x80) return 0;
+            if (i + 1 >= len || (s[i+1] & 0xC0) != 0x80) return 0;
             i += 2; continue;
         }
         if ((c & 0xF0) == 0xE0) { // 3-byte sequence
-            if ((s[i+1] & 0xC0) != 0x80 || (s[i+2] & 0xC0) != 0x80) return 0;
+            if (i + 2 >= len || (s[i+1] & 0xC0) != 0x80 || (s[i+2] & 0xC0) != 0x80) return 0;
             i += 3; continue;
         }
         if ((c & 0xF8) == 0xF0) { // 4-byte sequence
-            if ((s[i+1] & 0xC0) != 0x80 || (s[i+2] & 0xC0) != 0x80 || (s[i+3] & 0xC0) != 0x80) return 0;
+            if (i + 3 >= len || (s[i+1] & 0xC0) != 0x80 || (s[i+2] & 0xC0) != 0x80 || (s[i+3] & 0xC0) != 0x80) return 0;
             i += 4; continue;
         }
 
@@ -67,66 +86,182 @@ static int is_probably_valid_utf8(const unsigned char *s) {
     return 1;
 }
 
-static void safe_print_string_checked(const char *s) {
+static void safe_print_string_checked(FILE *out, const char *s) {
     if (!s) {
-        printf("(null)");
+        fprintf(out, "(null)");
         return;
     }
 
-    if (!is_probably_valid_utf8((const unsigned char *)s)) {
-        printf("(invalid UTF-8 or ptr=%p)", s);
+    uintptr_t addr = (uintptr_t)s;
+    if (addr < 0x1000 || addr > 0x7fffffffffff) {
+        fprintf(out, "(invalid UTF-8 or ptr=%p)", s);
+        return;
+    }
+
+    size_t len = wpp_string_byte_len(s);
+    if (!is_probably_valid_utf8((const unsigned char *)s, len)) {
+        fprintf(out, "(invalid UTF-8 or ptr=%p)", s);
         return;
     }
 
-    size_t len = strlen(s);
     if (len > 300) {
-        fwrite(s, 1, 300, stdout);
-        printf("... [truncated %zu bytes]", len - 300);
+        fwrite(s, 1, 300, out);
+        fprintf(out, "... [truncated %zu bytes]", len - 300);
     } else {
-        fwrite(s, 1, len, stdout);
+        fwrite(s, 1, len, out);
     }
 }
 
 // =====================================================
 // === ARRAY / OBJECT PRINTERS (EXPORTED)
 // =====================================================
-__attribute__((visibility("default")))
-void wpp_print_array(int32_t *arr) {
-    if (!arr) { printf("(null array)\n"); return; }
-    int32_t len = arr[0];
-    printf("[");
-    for (int i = 0; i < len; i++) {
-        printf("%d", arr[i + 1]);
-        if (i < len - 1) printf(", ");
+//  Arrays are WppVec headers (runtime/vector.rs) pointing at 8-byte slots
+//  elem_tag uses the basic type ids below, plus:
+//  0 = any (slot holds a box { int32 tag, pad, 8-byte payload })
+//  7 = array, 8 = object literal, 9 = entity instance, 10 = map
+//  Object literals and maps (runtime/map.rs) share a header whose
+//  second word tells them apart
+// =====================================================
+typedef struct {
+    int32_t len;
+    int32_t tag;
+    int32_t cap;
+    int32_t _pad;
+    int64_t *data;
+} WppVec;
+
+typedef struct {
+    int32_t len;
+    int32_t kind;          // 0 = object literal, 1 = map
+    const char **keys;
+    const int64_t *vals;   // 8-byte slots
+    union {
+        int32_t tag;           // map: the tag of every value
+        const int32_t *tags;   // object literal: the tag of each field
+    };
+} WppObjectHeader;
+
+// Enum values (codegen.rs `compile_enum_value`): the variant index, its name and
+// the tags of its payload fields, followed by the payload in 8-byte slots
+typedef struct {
+    int32_t variant;
+    int32_t count;
+    const char *name;
+    const int32_t *tags;
+    int64_t payload[];
+} WppEnumValue;
+
+static void print_array_inner(FILE *out, const WppVec *arr);
+static void print_object_inner(FILE *out, const void *obj_ptr);
+static void print_enum_inner(FILE *out, const WppEnumValue *val);
+
+static void print_slot(FILE *out, int32_t tag, const void *slot) {
+    switch (tag) {
+        case 0: {
+            const char *box = *(const char *const *)slot;
+            if (!box) { fprintf(out, "null"); break; }
+            print_slot(out, *(const int32_t *)box, box + 8);
+            break;
+        }
+        case 1: fprintf(out, "%d", *(const int32_t *)slot); break;
+        case 2: fprintf(out, "%lld", (long long)*(const int64_t *)slot); break;
+        case 3: fprintf(out, "%.6g", *(const float *)slot); break;
+        case 4: fprintf(out, "%.6g", *(const double *)slot); break;
+        case 5: fprintf(out, "%s", *(const int32_t *)slot ? "true" : "false"); break;
+        case 6:
+            fprintf(out, "\"");
+            safe_print_string_checked(out, *(const char *const *)slot);
+            fprintf(out, "\"");
+            break;
+        case 7: print_array_inner(out, *(const WppVec *const *)slot); break;
+        case 8:
+        case 10: print_object_inner(out, *(const void *const *)slot); break;
+        case 9: fprintf(out, "<instance>"); break;
+        case 11: print_enum_inner(out, *(const WppEnumValue *const *)slot); break;
+        case 12: fprintf(out, "%d", *(const int8_t *)slot); break;
+        case 13: fprintf(out, "%d", *(const int16_t *)slot); break;
+        case 14: fprintf(out, "%u", *(const uint8_t *)slot); break;
+        case 15: fprintf(out, "%u", *(const uint16_t *)slot); break;
+        case 16: fprintf(out, "%u", *(const uint32_t *)slot); break;
+        case 17: fprintf(out, "%llu", (unsigned long long)*(const uint64_t *)slot); break;
+        default: fprintf(out, "(unknown tag=%d)", tag); break;
+    }
+}
+
+static void print_array_inner(FILE *out, const WppVec *arr) {
+    if (!arr) { fprintf(out, "(null array)"); return; }
+    fprintf(out, "[");
+    for (int i = 0; i < arr->len; i++) {
+        print_slot(out, arr->tag, &arr->data[i]);
+        if (i < arr->len - 1) fprintf(out, ", ");
     }
-    printf("]\n");
+    fprintf(out, "]");
+}
+
+static void print_object_inner(FILE *out, const void *obj_ptr) {
+    if (!obj_ptr) { fprintf(out, "(null object)"); return; }
+    const WppObjectHeader *obj = (const WppObjectHeader *)obj_ptr;
+    fprintf(out, "{");
+    for (int i = 0; i < obj->len; i++) {
+        if (obj->kind == 1) {
+            fprintf(out, "\"");
+            safe_print_string_checked(out, obj->keys[i]);
+            fprintf(out, "\": ");
+            print_slot(out, obj->tag, &obj->vals[i]);
+        } else {
+            fprintf(out, "%s: ", obj->keys[i]);
+            print_slot(out, obj->tags[i], &obj->vals[i]);
+        }
+        if (i < obj->len - 1) fprintf(out, ", ");
+    }
+    fprintf(out, "}");
+}
+
+static void print_enum_inner(FILE *out, const WppEnumValue *val) {
+    if (!val) { fprintf(out, "(null enum)"); return; }
+    fprintf(out, "%s", val->name);
+    if (val->count == 0) return;
+    fprintf(out, "(");
+    for (int i = 0; i < val->count; i++) {
+        print_slot(out, val->tags[i], &val->payload[i]);
+        if (i < val->count - 1) fprintf(out, ", ");
+    }
+    fprintf(out, ")");
+}
+
+__attribute__((visibility("default")))
+void wpp_print_array(const WppVec *arr) {
+    print_array_inner(stdout, arr);
+    printf(" ");
+    fflush(stdout);
 }
 
 __attribute__((visibility("default")))
 void wpp_print_object(void *obj_ptr) {
-    if (!obj_ptr) { printf("(null object)\n"); return; }
-    int32_t len = *(int32_t *)obj_ptr;
-    void **fields = (void **)((char *)obj_ptr + 8);
-    const char **keys = (const char **)fields[0];
-    int32_t *vals = (int32_t *)fields[1];
-    printf("{");
-    for (int i = 0; i < len; i++) {
-        printf("%s: %d", keys[i], vals[i]);
-        if (i < len - 1) printf(", ");
-    }
-    printf("}\n");
+    print_object_inner(stdout, obj_ptr);
+    printf(" ");
+    fflush(stdout);
+}
+
+__attribute__((visibility("default")))
+void wpp_print_enum(const WppEnumValue *val) {
+    print_enum_inner(stdout, val);
+    printf(" ");
+    fflush(stdout);
 }
 
 // =====================================================
 // === UNIFIED BASIC TYPE PRINTER (EXPORT)
 // =====================================================
 //  type_id mapping:
+//  0 = an `any` box { i32 tag, pad, payload }; ptr is the box
 //  1 = i32
 //  2 = i64
 //  3 = f32
 //  4 = f64
 //  5 = bool
 //  6 = string
+//  12..17 = i8, i16, u8, u16, u32, u64 (see print_slot)
 // =====================================================
 
 __attribute__((visibility("default")))
@@ -138,6 +273,18 @@ void wpp_print_value_basic(const void *ptr, int32_t type_id) {
     }
 
     switch (type_id) {
+        case 0: { // any
+            const char *box = (const char *)ptr;
+            int32_t tag = *(const int32_t *)box;
+            if (tag == 6) {
+                // Strings print bare at the top level, as they do unboxed
+                safe_print_string_checked(stdout, *(const char *const *)(box + 8));
+            } else {
+                print_slot(stdout, tag, box + 8);
+            }
+            printf(" ");
+            break;
+        }
         case 1: { // i32
             int32_t v = *(int32_t *)ptr;
             printf("%d ", v);
@@ -165,10 +312,14 @@ void wpp_print_value_basic(const void *ptr, int32_t type_id) {
         }
         case 6: { // string
             const char *s = (const char *)ptr;
-            safe_print_string_checked(s);
+            safe_print_string_checked(stdout, s);
             printf(" ");
             break;
         }
+        case 12: case 13: case 14: case 15: case 16: case 17:
+            print_slot(stdout, type_id, ptr);
+            printf(" ");
+            break;
 
         default:
             printf("(unknown type_id=%d, ptr=%p) ", type_id, ptr);
@@ -182,12 +333,12 @@ void wpp_print_value_basic(const void *ptr, int32_t type_id) {
 // =====================================================
 __attribute__((visibility("default")))
 char* wpp_readline() {
-    static char buffer[1024]; // static so pointer stays valid after return
+    char buffer[1024];
     if (fgets(buffer, sizeof(buffer), stdin) != NULL) {
         size_t len = strlen(buffer);
         if (len > 0 && buffer[len - 1] == '\n')
-            buffer[len - 1] = '\0';
-        return buffer;
+            len--;
+        return wpp_str_new(buffer, (int32_t)len);
     }
     return "";
 }
@@ -197,10 +348,85 @@ char* wpp_readline() {
 // =====================================================
 __attribute__((visibility("default")))
 char* wpp_int_to_string(int32_t value) {
-    // Thread-local buffer to avoid conflicts
-    static _Thread_local char buffer[32];
-    snprintf(buffer, sizeof(buffer), "%d", value);
-    return buffer;
+    char buffer[32];
+    int len = snprintf(buffer, sizeof(buffer), "%d", value);
+    return wpp_str_new(buffer, len);
+}
+
+// =====================================================
+// === FORMAT (EXPORTED)
+// =====================================================
+//  `format("{} items at {:.2}", n, price)`: each `{}` takes the
+//  next argument, `{:.N}` prints a float with N decimals and
+//  `{{` / `}}` are literal braces. Arguments use the layout of
+//  an `any` box (codegen.rs `compile_format`)
+// =====================================================
+typedef struct {
+    int32_t tag;
+    int32_t _pad;
+    int64_t payload;
+} WppFormatArg;
+
+static void format_arg(FILE *out, const WppFormatArg *arg, int precision) {
+    const void *slot = &arg->payload;
+    // An `any` argument is formatted as the value in its box, which has this same layout
+    const WppFormatArg *boxed = *(const WppFormatArg *const *)slot;
+    if (arg->tag == 0 && boxed) {
+        format_arg(out, boxed, precision);
+        return;
+    }
+    if (precision >= 0 && (arg->tag == 3 || arg->tag == 4)) {
+        double v = arg->tag == 3 ? *(const float *)slot : *(const double *)slot;
+        fprintf(out, "%.*f", precision, v);
+    } else if (arg->tag == 6) {
+        // Strings are inserted as they are, without the quotes arrays print
+        const char *s = *(const char *const *)slot;
+        if (s) fwrite(s, 1, wpp_string_byte_len(s), out);
+        else fprintf(out, "(null)");
+    } else {
+        print_slot(out, arg->tag, slot);
+    }
+}
+
+__attribute__((visibility("default")))
+char* wpp_format(const char *fmt, int32_t argc, const WppFormatArg *args) {
+    char *buf = NULL;
+    size_t size = 0;
+    FILE *out = open_memstream(&buf, &size);
+    if (!out) return wpp_str_new("", 0);
+
+    size_t len = fmt ? wpp_string_byte_len(fmt) : 0;
+    int32_t next = 0;
+    for (size_t i = 0; i < len; i++) {
+        char c = fmt[i];
+        if ((c == '{' || c == '}') && i + 1 < len && fmt[i + 1] == c) {
+            fputc(c, out);
+            i++;
+            continue;
+        }
+        if (c == '{') {
+            size_t j = i + 1;
+            int precision = -1;
+            if (j + 1 < len && fmt[j] == ':' && fmt[j + 1] == '.') {
+                j += 2;
+                precision = 0;
+                while (j < len && isdigit((unsigned char)fmt[j]))
+                    precision = precision * 10 + (fmt[j++] - '0');
+            }
+            // Placeholders without an argument are kept as written
+            if (j < len && fmt[j] == '}' && next < argc) {
+                format_arg(out, &args[next++], precision);
+                i = j;
+                continue;
+            }
+        }
+        fputc(c, out);
+    }
+
+    fclose(out);
+    char *result = wpp_str_new(buf, (int32_t)size);
+    free(buf);
+    return result;
 }
 
 #ifdef __cplusplus